@@ -34,6 +34,10 @@ pub enum KaosError {
     #[error("process terminated by signal")]
     TerminatedBySignal,
 
+    /// Watching files for changes failed.
+    #[error("watch error: {0}")]
+    Watch(String),
+
     /// A generic error with a message.
     #[error("{0}")]
     Other(String),
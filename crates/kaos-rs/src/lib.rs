@@ -11,6 +11,7 @@
 //! - **Path Abstraction**: [`KaosPath`] provides async methods for file operations
 //! - **Process Execution**: [`Command`] and [`Process`] for running external commands
 //! - **Stream Abstractions**: [`LineReader`], [`CountingWriter`], and stream extensions
+//! - **File Watching**: [`FileWatcher`] for debounced change notifications
 //! - **Error Handling**: Comprehensive error types via [`KaosError`]
 //!
 //! ## Quick Start
@@ -42,6 +43,7 @@
 //! - [`path`]: Path abstraction and file operations
 //! - [`exec`]: Process execution and command running
 //! - [`stream`]: Async stream utilities and extensions
+//! - [`watch`]: Watching directory trees for changes
 //! - [`error`]: Error types and results
 
 #![warn(missing_docs)]
@@ -51,12 +53,14 @@ pub mod error;
 pub mod exec;
 pub mod path;
 pub mod stream;
+pub mod watch;
 
 // Re-export main types for convenience
 pub use error::{KaosError, Result};
 pub use exec::{Command, CommandOutput, Output, Process};
 pub use path::KaosPath;
 pub use stream::{AsyncReadable, AsyncWritable, CountingWriter, LineReader, StreamExt};
+pub use watch::{watch_path, FileWatcher};
 
 // Re-export stream extension traits
 pub use stream::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
@@ -132,4 +136,22 @@ mod tests {
         assert!(output.success());
         assert!(output.stdout_str().unwrap().contains("test_value"));
     }
+
+    #[tokio::test]
+    async fn test_file_watcher_batches_changes() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut watcher = FileWatcher::new(temp_dir.path())
+            .unwrap()
+            .with_debounce(std::time::Duration::from_millis(100));
+
+        std::fs::write(temp_dir.path().join("a.rs"), "fn a() {}").unwrap();
+        std::fs::write(temp_dir.path().join("b.rs"), "fn b() {}").unwrap();
+        let changes = tokio::time::timeout(std::time::Duration::from_secs(5), watcher.changes())
+            .await
+            .unwrap()
+            .unwrap();
+        let names: Vec<_> = changes.iter().filter_map(|path| path.file_name()).collect();
+        assert!(names.contains(&std::ffi::OsStr::new("a.rs")));
+        assert!(names.contains(&std::ffi::OsStr::new("b.rs")));
+    }
 }
@@ -0,0 +1,150 @@
+//! Watching directory trees for changes.
+//!
+//! [`FileWatcher`] reports changed files in debounced batches: a batch ends
+//! once no further change has arrived for the debounce period, so an editor
+//! saving several files, or writing one in several steps, results in a
+//! single batch. [`watch_path`] watches a single file, or a directory tree
+//! as [`FileWatcher::new`] does.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use kaos_rs::FileWatcher;
+//!
+//! # async fn example() -> kaos_rs::Result<()> {
+//! let mut watcher = FileWatcher::new("src")?;
+//! while let Some(paths) = watcher.changes().await {
+//!     println!("changed: {:?}", paths);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+use crate::error::{KaosError, Result};
+
+/// Default quiet period that ends a batch of changes.
+const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Watches a directory tree and reports changed files in batches.
+///
+/// Watching stops when the watcher is dropped.
+pub struct FileWatcher {
+    // Kept alive for as long as events are wanted
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    debounce: Duration,
+    /// The one file changes are reported for, when watching a file
+    file: Option<PathBuf>,
+}
+
+impl std::fmt::Debug for FileWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileWatcher").field("debounce", &self.debounce).finish_non_exhaustive()
+    }
+}
+
+impl FileWatcher {
+    /// Watch `root` and everything below it.
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        Self::watch(root.as_ref(), RecursiveMode::Recursive, None)
+    }
+
+    fn watch(dir: &Path, mode: RecursiveMode, file: Option<PathBuf>) -> Result<Self> {
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is gone only once the watcher is being dropped
+            let _ = tx.send(event);
+        })
+        .map_err(|e| KaosError::Watch(e.to_string()))?;
+        watcher.watch(dir, mode).map_err(|e| KaosError::Watch(e.to_string()))?;
+        Ok(Self { _watcher: watcher, events, debounce: DEFAULT_DEBOUNCE, file })
+    }
+
+    /// Set the quiet period that ends a batch (300 ms by default).
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Wait for files to be created, changed or removed, and return their
+    /// paths once no further change has arrived for the debounce period.
+    ///
+    /// Paths are sorted and listed once each. Returns `None` if the watcher
+    /// stopped delivering events.
+    pub async fn changes(&mut self) -> Option<Vec<PathBuf>> {
+        let mut paths = BTreeSet::new();
+        while paths.is_empty() {
+            let event = self.events.recv().await?;
+            collect(event, &mut paths, self.file.as_deref());
+        }
+        loop {
+            match tokio::time::timeout(self.debounce, self.events.recv()).await {
+                Ok(Some(event)) => collect(event, &mut paths, self.file.as_deref()),
+                Ok(None) | Err(_) => return Some(paths.into_iter().collect()),
+            }
+        }
+    }
+
+    /// Discard the changes reported so far without waiting for more.
+    pub fn clear(&mut self) {
+        while self.events.try_recv().is_ok() {}
+    }
+}
+
+/// Watch `path`: a directory and everything below it, or a single file.
+///
+/// A file is watched through its directory, so it may not exist yet, e.g. a
+/// log a build is about to write, and is still watched after it is
+/// replaced. Only changes to the file itself are reported.
+pub fn watch_path(path: impl AsRef<Path>) -> Result<FileWatcher> {
+    let path = std::path::absolute(path.as_ref())?;
+    if path.is_dir() {
+        return FileWatcher::new(&path);
+    }
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Err(KaosError::Watch(format!("Cannot watch {}", path.display())));
+    };
+    // Events carry the real path of the directory
+    let dir = dir.canonicalize()?;
+    let file = dir.join(name);
+    FileWatcher::watch(&dir, RecursiveMode::NonRecursive, Some(file))
+}
+
+/// Add the paths an event changed, only `file` if given; reads and
+/// metadata-only accesses do not count as changes.
+fn collect(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>, file: Option<&Path>) {
+    match event {
+        Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+            paths.extend(event.paths.into_iter().filter(|path| file.is_none_or(|file| path == file)))
+        }
+        Ok(_) => {}
+        Err(e) => warn!("File watch error: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_path() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().canonicalize().unwrap();
+        let log = dir.join("build.log");
+        let mut watcher = watch_path(&log).unwrap().with_debounce(Duration::from_millis(100));
+
+        // Other files in the directory are not reported
+        std::fs::write(dir.join("other.txt"), "ignored").unwrap();
+        std::fs::write(&log, "compiling").unwrap();
+        let changes = tokio::time::timeout(Duration::from_secs(5), watcher.changes()).await.unwrap();
+        assert_eq!(changes, Some(vec![log.clone()]));
+    }
+}
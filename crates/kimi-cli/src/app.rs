@@ -3,28 +3,35 @@
 //! This module provides the main application structure that coordinates
 //! between the CLI, core systems, and UI layers.
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use anyhow::Result;
-use tracing::{debug, info};
+use secrecy::ExposeSecret;
+use tracing::{debug, info, warn};
 
 use kimi_core::{
-    Approval, Config, Context, Session,
-    config::ConfigError,
+    Approval, Config, Context, EventLog, ProjectMemory, Redactor, Retriever, Session, SessionManager, SnapshotStore, Transcript,
+    UsageStore, WebConfig, Workspace, WorkspaceError, WorkspaceIndex,
+    auth::{manager::BackgroundRefreshHandle, OAuthManager, SecretsManager},
+    config::{find_project_config, ConfigError},
+    llm,
     context::ContextError,
     session::SessionError,
-    soul::{KimiSoul, SoulError, Agent, SimpleCompaction},
+    prompts::PromptTemplates,
+    skill::{Skill, SkillDiscovery},
+    soul::{custom_commands, AgentConfig, AgentFactory, KimiSoul, McpServerInfo, SoulError, Agent, SimpleCompaction, SpawnAgentTool, ToolProfiles, UndoEditTool, WatchPathTool},
     types::LoopControl,
 };
 use kimi_tools::{
     ReadFileTool, WriteFileTool, StrReplaceFileTool,
-    ShellTool, GlobTool, GrepTool, SetTodoListTool,
-    TaskTool, FetchURLTool, SearchWebTool,
+    ShellTool, GlobTool, GrepTool, EditNotebookCellTool, SetTodoListTool,
+    FetchURLTool, SearchWebTool, SearchCodebaseTool, HttpRequestTool, AuthProfile, WebClient, WebPolicy,
 };
 
 use crate::cli::Cli;
-use crate::ui::{ShellUI, PrintUI, UIError};
+use crate::commands::mcp;
+use crate::ui::{ShellUI, PrintUI, ServerUI, UIError};
 
 /// Default agent file path
 #[allow(dead_code)]
@@ -56,6 +63,12 @@ pub enum AppError {
     
     #[error("Agent not found: {0}")]
     AgentNotFound(PathBuf),
+
+    #[error("Setup error: {0}")]
+    Setup(String),
+
+    #[error("Workspace error: {0}")]
+    Workspace(#[from] WorkspaceError),
 }
 
 /// Main application structure
@@ -66,6 +79,14 @@ pub struct App {
     context: Context,
     approval: Arc<Approval>,
     agent: Option<Agent>,
+    memory: ProjectMemory,
+    prompts: PromptTemplates,
+    skills: Vec<Skill>,
+    retriever: Option<Retriever>,
+    /// Directories the file tools are limited to
+    workspace: Workspace,
+    /// The OAuth token refresh task, stopped when the app is dropped
+    oauth_refresh: Option<BackgroundRefreshHandle>,
     cli: Cli,
 }
 
@@ -74,8 +95,15 @@ impl App {
     pub async fn create(cli: &Cli) -> Result<Self, AppError> {
         info!("Creating application instance");
 
+        // Walk new users through setup before loading the config it writes
+        if is_first_run(cli) {
+            crate::commands::setup::execute()
+                .await
+                .map_err(|e| AppError::Setup(e.to_string()))?;
+        }
+
         // Load configuration
-        let config = load_config(cli.config_file.as_ref()).await?;
+        let config = load_config(cli.config_file.as_ref(), &cli.effective_work_dir()).await?;
         debug!("Configuration loaded successfully");
 
         // Create or load session
@@ -86,12 +114,24 @@ impl App {
         let context = Context::load(session.context_file.clone())?;
         debug!("Context loaded with {} messages", context.message_count());
 
-        // Create approval manager
-        let approval = if cli.yolo {
-            Approval::yolo()
-        } else {
-            Approval::new()
-        };
+        // Create approval manager: configured rules, then the allowed tools,
+        // except disallowed ones, which are never approved
+        let allowed_tools = cli.allowed_tools.iter().filter(|tool| !cli.disallowed_tools.contains(tool)).cloned();
+        let approval = Approval::with_mode(cli.permission_mode(&config))
+            .with_rules(config.approval.tools.iter().map(|(tool, rule)| (tool.clone(), *rule)))
+            .with_commands(config.approval.commands.iter().cloned())
+            .with_allowed_tools(allowed_tools);
+
+        // File tools stay in the working directory and those added with --add-dir
+        let workspace = Workspace::with_roots(std::iter::once(cli.effective_work_dir()).chain(cli.add_dirs.iter().cloned()))?;
+
+        // Keep OAuth tokens fresh through long sessions; providers read
+        // them from storage on every request
+        let oauth_refresh = config
+            .providers
+            .values()
+            .any(|provider| provider.oauth.is_some())
+            .then(|| OAuthManager::new(config.clone()).start_background_refresh());
 
         Ok(Self {
             config,
@@ -99,6 +139,12 @@ impl App {
             context,
             approval: Arc::new(approval),
             agent: None,
+            memory: ProjectMemory::default(),
+            prompts: PromptTemplates::default(),
+            skills: Vec::new(),
+            retriever: None,
+            workspace,
+            oauth_refresh,
             cli: cli.clone(),
         })
     }
@@ -107,50 +153,166 @@ impl App {
     pub async fn initialize(&mut self) -> Result<(), AppError> {
         info!("Initializing agent");
 
-        // Create agent
+        // Load AGENTS.md / KIMI.md project memory; the soul appends it to the system prompt
+        let memory = ProjectMemory::discover(&self.cli.effective_work_dir());
+        if !memory.is_empty() {
+            info!("Loaded project memory from {} file(s)", memory.files.len());
+        }
+
+        // Create agent, with the system prompt template from the config or project
+        let prompts = PromptTemplates::load(&self.config.prompts, &self.cli.effective_work_dir());
+        let model = self.cli.model.clone().unwrap_or_else(|| self.config.default_model.clone());
+        if let Some(profile) = &self.cli.tool_profile {
+            ToolProfiles::from_config(&self.config).lookup(profile)?;
+        }
         let agent = Agent::new(
             "kimi",
             "A helpful AI assistant",
-        );
+        )
+        .with_system_prompt(prompts.system.clone())
+        .with_config(AgentConfig {
+            model,
+            tool_profile: self.cli.tool_profile.clone(),
+            ..AgentConfig::default()
+        });
         
         self.agent = Some(agent);
+        self.memory = memory;
+        self.prompts = prompts;
+
+        // Discover skills from the project and user skill directories
+        let work_dir = self.cli.effective_work_dir();
+        for root in SkillDiscovery::resolve_roots(&work_dir).await {
+            self.skills.extend(SkillDiscovery::discover(&root).await);
+        }
+        if !self.skills.is_empty() {
+            info!("Discovered {} skill(s)", self.skills.len());
+        }
+
+        // Index the workspace for retrieval when `[rag]` names a provider
+        self.retriever = match llm::create_embedding_provider(&self.config).await {
+            Ok(Some(provider)) => {
+                let index_path = WorkspaceIndex::default_path(&work_dir);
+                match WorkspaceIndex::open(&work_dir, index_path, provider.model_name()) {
+                    Ok(index) => {
+                        let mut retriever = Retriever::new(index, provider);
+                        if let Some(top_k) = self.config.rag.top_k {
+                            retriever.top_k = top_k;
+                        }
+                        if let Some(min_score) = self.config.rag.min_score {
+                            retriever.min_score = min_score;
+                        }
+                        Some(retriever)
+                    }
+                    Err(e) => {
+                        warn!("Failed to open the workspace index: {}", e);
+                        None
+                    }
+                }
+            }
+            Ok(None) => None,
+            Err(e) => {
+                warn!("Workspace retrieval is disabled: {}", e);
+                None
+            }
+        };
         debug!("Agent initialized");
 
         Ok(())
     }
 
-    /// Create the default set of tools
-    fn create_default_tools() -> Vec<std::sync::Arc<dyn kimi_core::soul::Tool>> {
-        vec![
-            std::sync::Arc::new(ReadFileTool::new()),
-            std::sync::Arc::new(WriteFileTool::new()),
-            std::sync::Arc::new(StrReplaceFileTool::new()),
+    /// Create the default set of tools, with SearchCodebase when the
+    /// workspace is indexed, the file tools limited to `workspace` and the
+    /// web tools to what the `web` policy allows
+    pub(crate) fn create_default_tools(
+        retriever: Option<&Retriever>,
+        workspace: Option<&Workspace>,
+        web: &WebConfig,
+    ) -> Vec<std::sync::Arc<dyn kimi_core::soul::Tool>> {
+        let mut read = ReadFileTool::new();
+        let mut write = WriteFileTool::new();
+        let mut replace = StrReplaceFileTool::new();
+        let mut glob = GlobTool::new();
+        let mut grep = GrepTool::new();
+        let mut notebook = EditNotebookCellTool::new();
+        if let Some(workspace) = workspace {
+            read = read.with_workspace(workspace.clone());
+            write = write.with_workspace(workspace.clone());
+            replace = replace.with_workspace(workspace.clone());
+            glob = glob.with_workspace(workspace.clone());
+            grep = grep.with_workspace(workspace.clone());
+            notebook = notebook.with_workspace(workspace.clone());
+        }
+        let mut http = HttpRequestTool::new();
+        let secrets = SecretsManager::new();
+        for (name, profile) in &web.profiles {
+            let token = match profile.load_token(&secrets) {
+                Ok(token) => token,
+                Err(e) => {
+                    warn!("Web profile {} is unavailable: {}", name, e);
+                    continue;
+                }
+            };
+            match AuthProfile::from_config(profile, token) {
+                Ok(profile) => http = http.with_profile(name, profile),
+                Err(e) => warn!("Web profile {} is invalid: {}", name, e),
+            }
+        }
+        let web = std::sync::Arc::new(WebClient::new(WebPolicy::from_config(web)));
+        let mut tools: Vec<std::sync::Arc<dyn kimi_core::soul::Tool>> = vec![
+            std::sync::Arc::new(read),
+            std::sync::Arc::new(write),
+            std::sync::Arc::new(replace),
+            std::sync::Arc::new(notebook),
             std::sync::Arc::new(ShellTool::new()),
-            std::sync::Arc::new(GlobTool::new()),
-            std::sync::Arc::new(GrepTool::new()),
+            std::sync::Arc::new(glob),
+            std::sync::Arc::new(grep),
             std::sync::Arc::new(SetTodoListTool::new()),
-            std::sync::Arc::new(TaskTool::new()),
-            std::sync::Arc::new(FetchURLTool::new()),
-            std::sync::Arc::new(SearchWebTool::new()),
-        ]
+            std::sync::Arc::new(SpawnAgentTool::new()),
+            std::sync::Arc::new(UndoEditTool::new()),
+            std::sync::Arc::new(WatchPathTool::new()),
+            std::sync::Arc::new(FetchURLTool::new().with_client(web.clone())),
+            std::sync::Arc::new(SearchWebTool::new().with_client(web.clone())),
+            std::sync::Arc::new(http.with_client(web)),
+        ];
+        if let Some(retriever) = retriever {
+            tools.push(std::sync::Arc::new(SearchCodebaseTool::new(retriever.clone())));
+        }
+        tools
     }
 
-    /// Run the interactive shell mode
-    pub async fn run_shell(mut self) -> Result<(), AppError> {
-        info!("Starting shell mode");
-
-        // Ensure agent is initialized
-        if self.agent.is_none() {
-            self.initialize().await?;
+    /// Drop the tools `--disallowed-tools` names, warning about names that
+    /// match none
+    fn without_disallowed(
+        mut tools: Vec<std::sync::Arc<dyn kimi_core::soul::Tool>>,
+        disallowed: &[String],
+    ) -> Vec<std::sync::Arc<dyn kimi_core::soul::Tool>> {
+        for name in disallowed {
+            if !tools.iter().any(|tool| tool.name() == name) {
+                warn!("--disallowed-tools: unknown tool {}", name);
+            }
         }
+        tools.retain(|tool| !disallowed.iter().any(|name| name == tool.name()));
+        tools
+    }
 
-        // Create KimiSoul with tools
-        let agent = self.agent.take().unwrap();
+    /// Create the KimiSoul from the initialized agent, with tools, MCP
+    /// servers, project memory, skills and custom slash commands attached
+    async fn into_soul(mut self) -> (KimiSoul, Cli) {
+        let mcp_servers = self.mcp_servers().await;
+        let agent = self.agent.take().expect("agent must be initialized");
+        let transcript = self.open_transcript();
         let denwa_renji = Arc::new(kimi_core::soul::DenwaRenji::new());
-        let loop_control = self.config.loop_control.clone();
-        let compaction = SimpleCompaction::new(4000);
-        let tools = Self::create_default_tools();
-        
+        let mut loop_control = self.config.loop_control.clone();
+        if let Some(max_turns) = self.cli.max_turns {
+            loop_control.max_steps_per_turn = max_turns as usize;
+        }
+        let model = self.config.models.get(self.cli.model.as_ref().unwrap_or(&self.config.default_model)).cloned();
+        let context_length = model.as_ref().and_then(|m| m.max_tokens);
+        let compaction = SimpleCompaction::new(context_length.unwrap_or(SimpleCompaction::default().max_tokens));
+        let tools = Self::create_default_tools(self.retriever.as_ref(), Some(&self.workspace), &self.config.web);
+        let tools = Self::without_disallowed(tools, &self.cli.disallowed_tools);
+
         let mut soul = KimiSoul::with_tools(
             agent,
             self.context,
@@ -160,9 +322,131 @@ impl App {
             compaction,
             tools,
         );
+        if !mcp_servers.is_empty() {
+            soul.toolset_mut().connect_mcp_servers(mcp_servers).await;
+        }
+        soul.tool_profiles = ToolProfiles::from_config(&self.config);
+        if let Some(profile) = soul.agent.config().tool_profile.clone() {
+            match soul.tool_profiles.apply(&profile, &soul.toolset) {
+                Ok(toolset) => soul.toolset = toolset,
+                Err(e) => warn!("{}", e),
+            }
+        }
+        if let Some(model) = &model {
+            soul.use_model(model);
+        }
+        soul.context_window.strategy = self.config.compaction.strategy();
+        soul.transcript = transcript;
+        soul.memory = self.memory;
+        soul.prompts = self.prompts;
+        soul.prompt_vars.set("cwd", self.cli.effective_work_dir().display().to_string());
+        soul.skills = self.skills;
+        soul.retriever = self.retriever;
+        soul.agents = AgentFactory::from_config(&self.config);
+        soul.snapshots = Some(SnapshotStore::for_session(&self.session));
+        soul.workspace = Some(self.workspace.clone());
+        soul.usage = Some(UsageStore::from_config(&self.config));
+        soul.session = Some(self.session.clone());
+        soul.load_edits();
+        soul.event_log = match EventLog::open(&self.session.wire_file) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!("Failed to open the session event log: {}", e);
+                None
+            }
+        };
+
+        let commands = custom_commands::discover_commands(&self.cli.effective_work_dir());
+        let registered = soul.slash_commands.register_custom(commands);
+        if registered > 0 {
+            info!("Loaded {} custom slash command(s)", registered);
+        }
+
+        (soul, self.cli)
+    }
+
+    /// The MCP servers to start: those under `[mcp]` in the config, the
+    /// enabled ones in `mcp.json` and those in `--mcp-config-file` files,
+    /// each overriding servers of the same name from the ones before
+    async fn mcp_servers(&self) -> Vec<McpServerInfo> {
+        let mut servers: Vec<McpServerInfo> = self
+            .config
+            .mcp
+            .servers
+            .iter()
+            .map(|server| {
+                McpServerInfo::new(&server.name, &server.command)
+                    .with_args(server.args.clone())
+                    .with_env(server.env.clone().unwrap_or_default())
+            })
+            .collect();
+        let mut files = vec![mcp::load_config().await.unwrap_or_else(|e| {
+            warn!("Failed to load mcp.json: {}", e);
+            mcp::McpConfig::default()
+        })];
+        for path in &self.cli.mcp_config_file {
+            match mcp::load_config_from(path).await {
+                Ok(config) => files.push(config),
+                Err(e) => warn!("Failed to load {}: {:#}", path.display(), e),
+            }
+        }
+        for file in files {
+            for (name, server) in file.servers {
+                servers.retain(|existing| existing.name != name);
+                if server.enabled {
+                    servers.push(server.to_server_info(&name));
+                }
+            }
+        }
+        if let Some(enabled) = &self.config.mcp.enabled_tools {
+            servers = servers.into_iter().map(|server| server.with_enabled_tools(enabled.clone())).collect();
+        }
+        servers
+    }
+
+    /// Open the transcript asked for with `--log-file` or `transcript_file`
+    ///
+    /// The configured API keys, web profile tokens and credential-like
+    /// environment variables are redacted from it along with the usual
+    /// credential patterns.
+    fn open_transcript(&self) -> Option<Transcript> {
+        let path = self.cli.log_file.as_ref().or(self.config.transcript_file.as_ref())?;
+        let path = kimi_core::transcript::resolve_path(path, &self.cli.effective_work_dir());
+        let secrets = SecretsManager::new();
+        let api_keys = self
+            .config
+            .providers
+            .values()
+            .filter_map(|provider| provider.resolve_api_key(&secrets).ok())
+            .chain(self.config.web.profiles.values().filter_map(|profile| profile.load_token(&secrets).ok().flatten()))
+            .map(|key| key.expose_secret().to_string());
+        let redactor = Redactor::new(api_keys).with_env_secrets();
+        match Transcript::open(&path, &self.session.id_string(), redactor) {
+            Ok(transcript) => {
+                info!("Writing the session transcript to {}", path.display());
+                Some(transcript)
+            }
+            Err(e) => {
+                warn!("Failed to open the transcript {}: {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Run the interactive shell mode
+    pub async fn run_shell(mut self) -> Result<(), AppError> {
+        info!("Starting shell mode");
+
+        // Ensure agent is initialized
+        if self.agent.is_none() {
+            self.initialize().await?;
+        }
+
+        let _oauth_refresh = self.oauth_refresh.take();
+        let (mut soul, cli) = self.into_soul().await;
 
         // Create and run shell UI
-        let mut shell = ShellUI::new(self.cli).await?;
+        let mut shell = ShellUI::new(cli).await?;
         shell.run_with_soul(&mut soul).await?;
 
         Ok(())
@@ -177,30 +461,34 @@ impl App {
             self.initialize().await?;
         }
 
-        // Create KimiSoul with tools
-        let agent = self.agent.take().unwrap();
-        let denwa_renji = Arc::new(kimi_core::soul::DenwaRenji::new());
-        let loop_control = self.config.loop_control.clone();
-        let compaction = SimpleCompaction::new(4000);
-        let tools = Self::create_default_tools();
-        
-        let mut soul = KimiSoul::with_tools(
-            agent,
-            self.context,
-            self.approval.clone(),
-            denwa_renji,
-            loop_control,
-            compaction,
-            tools,
-        );
+        let _oauth_refresh = self.oauth_refresh.take();
+        let (mut soul, cli) = self.into_soul().await;
 
         // Create and run print UI
-        let mut print_ui = PrintUI::new(self.cli)?;
+        let mut print_ui = PrintUI::new(cli)?;
         print_ui.run_with_soul(&mut soul, prompt).await?;
 
         Ok(())
     }
 
+    /// Run the headless JSON-lines server on stdin/stdout
+    pub async fn run_server(mut self) -> Result<(), AppError> {
+        info!("Starting server mode");
+
+        // Ensure agent is initialized
+        if self.agent.is_none() {
+            self.initialize().await?;
+        }
+
+        let _oauth_refresh = self.oauth_refresh.take();
+        let (mut soul, cli) = self.into_soul().await;
+
+        let mut server = ServerUI::new(cli)?;
+        server.run_with_soul(&mut soul).await?;
+
+        Ok(())
+    }
+
     /// Continue an existing session
     pub async fn run_continue(mut self) -> Result<(), AppError> {
         info!("Continuing existing session");
@@ -210,28 +498,13 @@ impl App {
             self.initialize().await?;
         }
 
-        // Create KimiSoul with tools
-        let agent = self.agent.take().unwrap();
-        let denwa_renji = Arc::new(kimi_core::soul::DenwaRenji::new());
-        let loop_control = self.config.loop_control.clone();
-        let compaction = SimpleCompaction::new(4000);
-        let tools = Self::create_default_tools();
-        
-        let mut soul = KimiSoul::with_tools(
-            agent,
-            self.context,
-            self.approval.clone(),
-            denwa_renji,
-            loop_control,
-            compaction,
-            tools,
-        );
+        let _oauth_refresh = self.oauth_refresh.take();
+        let (mut soul, cli) = self.into_soul().await;
 
         // If there's a prompt, run print mode; otherwise, run shell mode
-        let cli = self.cli.clone();
-        if let Some(ref prompt) = self.cli.prompt {
+        if let Some(prompt) = cli.prompt.clone() {
             let mut print_ui = PrintUI::new(cli)?;
-            print_ui.run_with_soul(&mut soul, prompt).await?;
+            print_ui.run_with_soul(&mut soul, &prompt).await?;
         } else {
             let mut shell = ShellUI::new(cli).await?;
             shell.run_with_soul(&mut soul).await?;
@@ -241,8 +514,9 @@ impl App {
     }
 }
 
-/// Load configuration from file or create default
-async fn load_config(config_path: Option<&PathBuf>) -> Result<Config, ConfigError> {
+/// Load configuration from file or create default, with the project config
+/// nearest to `work_dir` merged over it unless a file is given
+async fn load_config(config_path: Option<&PathBuf>, work_dir: &Path) -> Result<Config, ConfigError> {
     if let Some(path) = config_path {
         info!("Loading configuration from: {:?}", path);
         if path.extension().map(|e| e == "yaml" || e == "yml").unwrap_or(false) {
@@ -252,27 +526,47 @@ async fn load_config(config_path: Option<&PathBuf>) -> Result<Config, ConfigErro
         }
     } else {
         // Try to load from default locations
-        let default_paths = [
-            PathBuf::from("kimi.toml"),
-            PathBuf::from(".kimi/config.toml"),
-            dirs::config_dir()
-                .map(|d| d.join("kimi/config.toml"))
-                .unwrap_or_else(|| PathBuf::from("/etc/kimi/config.toml")),
-        ];
-
-        for path in &default_paths {
-            if path.exists() {
+        let config = match default_config_paths().iter().find(|path| path.exists()) {
+            Some(path) => {
                 info!("Loading configuration from: {:?}", path);
-                return Config::from_file(path);
+                Config::from_file(path)?
             }
-        }
-
-        // Create default configuration
-        info!("No configuration file found, using defaults");
-        create_default_config()
+            None => {
+                info!("No configuration file found, using defaults");
+                create_default_config()?
+            }
+        };
+        config.with_project_config(work_dir)
     }
 }
 
+/// Where the config is looked for without `--config-file`, in order; a
+/// project config is merged over it
+pub(crate) fn default_config_paths() -> [PathBuf; 2] {
+    [
+        PathBuf::from("kimi.toml"),
+        dirs::config_dir()
+            .map(|d| d.join("kimi/config.toml"))
+            .unwrap_or_else(|| PathBuf::from("/etc/kimi/config.toml")),
+    ]
+}
+
+/// Whether the interactive shell is starting for the first time: there is
+/// no config file, no `KIMI_API_KEY` to fall back on, and a terminal to ask
+/// the user at
+fn is_first_run(cli: &Cli) -> bool {
+    use std::io::IsTerminal;
+
+    let interactive = !cli.print && !cli.server && cli.prompt.is_none();
+    interactive
+        && cli.config_file.is_none()
+        && std::env::var_os("KIMI_API_KEY").is_none()
+        && default_config_paths().iter().all(|path| !path.exists())
+        && find_project_config(&cli.effective_work_dir()).is_none()
+        && std::io::stdin().is_terminal()
+        && std::io::stdout().is_terminal()
+}
+
 /// Create a default configuration
 fn create_default_config() -> Result<Config, ConfigError> {
     use std::collections::HashMap;
@@ -286,6 +580,7 @@ fn create_default_config() -> Result<Config, ConfigError> {
             provider: "kimi".to_string(),
             max_tokens: Some(8192),
             temperature: Some(0.7),
+            pricing: None,
         },
     );
 
@@ -303,11 +598,15 @@ fn create_default_config() -> Result<Config, ConfigError> {
         default_model: "kimi-k2".to_string(),
         default_thinking: false,
         default_yolo: false,
+        default_permission_mode: None,
+        fallback_model: Vec::new(),
+        transcript_file: None,
         models,
         providers,
         loop_control: LoopControl {
             max_iterations: 50,
             timeout_seconds: 300,
+            ..LoopControl::default()
         },
         services: Services {
             enabled: vec![],
@@ -317,6 +616,19 @@ fn create_default_config() -> Result<Config, ConfigError> {
             servers: vec![],
             enabled_tools: None,
         },
+        agents: HashMap::new(),
+        tool_profiles: HashMap::new(),
+        telemetry: Default::default(),
+        prompts: Default::default(),
+        platforms: HashMap::new(),
+        rag: Default::default(),
+        theme: Default::default(),
+        notifications: Default::default(),
+        updates: Default::default(),
+        cache: Default::default(),
+        compaction: Default::default(),
+        approval: Default::default(),
+        web: Default::default(),
         is_from_default_location: true,
     })
 }
@@ -337,14 +649,14 @@ async fn create_session(cli: &Cli) -> Result<Session, SessionError> {
     }
 
     if let Some(session_name) = cli.session_name() {
-        // Try to load the named session
-        if let Ok(session_id) = uuid::Uuid::parse_str(&session_name) {
-            let session = Session::load(work_dir, session_id)?;
-            info!("Loaded session: {}", session.id_string());
-            return Ok(session);
-        }
-        // If not a UUID, create a new session with that as identifier
-        // (This is a simplified approach - in practice you might want to map names to IDs)
+        // A session ID, or a name, which creates the session the first time
+        let session = if uuid::Uuid::parse_str(&session_name).is_ok() {
+            Session::find(&work_dir, &session_name)?
+        } else {
+            SessionManager::new(work_dir).get_or_create(&session_name)?
+        };
+        info!("Loaded session: {}", session.display_name());
+        return Ok(session);
     }
 
     // Create a new session
@@ -420,4 +732,13 @@ enabled_tools = []
         // Should succeed with valid config file
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_disallowed_tools_are_removed() {
+        let tools = App::create_default_tools(None, None, &Default::default());
+        let count = tools.len();
+        let tools = App::without_disallowed(tools, &["Shell".to_string(), "Teleport".to_string()]);
+        assert_eq!(tools.len(), count - 1);
+        assert!(tools.iter().all(|tool| tool.name() != "Shell"));
+    }
 }
@@ -1,6 +1,10 @@
 use std::path::PathBuf;
 
 use clap::{Parser, Subcommand};
+use kimi_core::approval::PermissionMode;
+use kimi_core::attachment::{self, AttachmentError};
+use kimi_core::types::Attachment;
+use kimi_core::config::Config;
 
 /// Kimi CLI - Your next CLI agent
 #[derive(Parser, Debug, Clone)]
@@ -12,6 +16,11 @@ pub struct Cli {
     #[arg(short, long, value_name = "DIR")]
     pub work_dir: Option<PathBuf>,
 
+    /// Another directory file tools may read and write, besides the
+    /// working directory; may be repeated
+    #[arg(long = "add-dir", value_name = "DIR")]
+    pub add_dirs: Vec<PathBuf>,
+
     /// Session name or ID to use
     #[arg(short, long, value_name = "NAME")]
     pub session: Option<String>,
@@ -32,14 +41,56 @@ pub struct Cli {
     #[arg(long)]
     pub yolo: bool,
 
+    /// Permission mode: default, plan, accept-edits or full-auto
+    #[arg(long, visible_alias = "approval-mode", value_name = "MODE", conflicts_with = "yolo")]
+    pub mode: Option<PermissionMode>,
+
+    /// Tools that run without asking in any mode, e.g. `ReadFile,Shell`
+    #[arg(long, value_name = "TOOLS", value_delimiter = ',')]
+    pub allowed_tools: Vec<String>,
+
+    /// Tools removed from the toolset, so the model cannot call them
+    #[arg(long, value_name = "TOOLS", value_delimiter = ',')]
+    pub disallowed_tools: Vec<String>,
+
+    /// Tool profile to limit the toolset to: full, read-only, web-only or
+    /// one from `[tool_profiles]`
+    #[arg(long = "tools", value_name = "PROFILE")]
+    pub tool_profile: Option<String>,
+
+    /// Most LLM calls (steps) the turn may make: sets
+    /// `loop_control.max_steps_per_turn` for this run
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_turns: Option<u64>,
+
     /// Single prompt to execute (non-interactive)
     #[arg(short, long, value_name = "TEXT")]
     pub prompt: Option<String>,
 
+    /// File to attach to the first message; repeat for more
+    #[arg(long = "file", value_name = "PATH")]
+    pub files: Vec<PathBuf>,
+
+    /// Image to attach to the first message; repeat for more
+    #[arg(long = "image", value_name = "PATH")]
+    pub images: Vec<PathBuf>,
+
     /// Print mode - output to stdout without interactive UI
     #[arg(long)]
     pub print: bool,
 
+    /// Server mode - read user inputs as JSON lines on stdin and write wire messages as JSON lines to stdout
+    #[arg(long, conflicts_with = "print")]
+    pub server: bool,
+
+    /// Serve the server mode protocol over WebSocket on this address instead of stdin/stdout
+    #[arg(long, value_name = "ADDR", requires = "server")]
+    pub listen: Option<std::net::SocketAddr>,
+
+    /// Token WebSocket clients must present (generated if not set)
+    #[arg(long, value_name = "TOKEN", requires = "listen")]
+    pub token: Option<String>,
+
     /// Path to configuration file
     #[arg(long, value_name = "FILE")]
     pub config_file: Option<PathBuf>,
@@ -52,6 +103,18 @@ pub struct Cli {
     #[arg(short, long)]
     pub verbose: bool,
 
+    /// Print only the response, without logs or notes, for use in scripts
+    #[arg(short, long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Disable colors and markdown formatting
+    #[arg(long)]
+    pub no_color: bool,
+
+    /// Append a transcript of the session, with secrets redacted, to this file
+    #[arg(long, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
     /// Subcommand to execute
     #[command(subcommand)]
     pub command: Option<Commands>,
@@ -61,7 +124,14 @@ pub struct Cli {
 #[derive(Subcommand, Debug, Clone)]
 pub enum Commands {
     /// Authenticate with Kimi API (OAuth flow)
-    Login,
+    Login {
+        /// OAuth platform to sign in to (default: kimi-code)
+        #[arg(long)]
+        platform: Option<String>,
+        /// Account to sign in to, kept alongside the platform's other accounts
+        #[arg(long)]
+        account: Option<String>,
+    },
     /// Interactive setup wizard for configuring providers
     Setup,
     /// Manage MCP (Model Context Protocol) servers
@@ -69,6 +139,125 @@ pub enum Commands {
         #[command(subcommand)]
         subcommand: McpCommands,
     },
+    /// Work with skills
+    Skill {
+        #[command(subcommand)]
+        subcommand: SkillCommands,
+    },
+    /// Read and change the config file
+    Config {
+        #[command(subcommand)]
+        subcommand: ConfigCommands,
+    },
+    /// Manage stored API keys and other secrets
+    Secret {
+        #[command(subcommand)]
+        subcommand: SecretCommands,
+    },
+    /// Resume a session of the working directory, picking it from a list
+    /// unless an ID is given
+    Resume {
+        /// Session ID, or a prefix of it such as the short ID
+        session: Option<String>,
+    },
+    /// Show usage statistics: turns, tokens, cost and tool calls
+    Stats {
+        /// Only the last N days, today included (default: all recorded days)
+        #[arg(long, value_name = "N")]
+        days: Option<u32>,
+    },
+    /// List the tools the agent can call and describe them
+    Tools {
+        #[command(subcommand)]
+        subcommand: ToolsCommands,
+    },
+    /// Re-run an agent task whenever matching files change
+    Watch {
+        /// Instruction for the agent; the changed files are appended
+        #[arg(short, long, value_name = "TEXT")]
+        prompt: String,
+        /// Only changes to files matching this pattern, e.g. `src/**/*.rs`
+        /// or `*.md`; repeat for more
+        #[arg(long = "glob", value_name = "PATTERN")]
+        globs: Vec<String>,
+        /// Quiet period in milliseconds that ends a batch of changes
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+    },
+    /// Download the latest release and replace this binary with it
+    Update {
+        /// Only report whether a newer release is available
+        #[arg(long)]
+        check: bool,
+        /// Replace the binary without asking first
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// Tools subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum ToolsCommands {
+    /// List tools with their safety and a one-line description
+    List,
+    /// Show a tool's description, parameters and JSON schema
+    Describe {
+        /// Name of the tool, e.g. `ReadFile`
+        name: String,
+    },
+}
+
+/// Config subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommands {
+    /// Print a value, or its default when the file leaves it unset
+    Get {
+        /// Dotted key of the value, e.g. `loop_control.max_iterations`
+        key: String,
+    },
+    /// Set a value, keeping the rest of the file as it is
+    Set {
+        /// Dotted key of the value, e.g. `loop_control.max_iterations`
+        key: String,
+        /// The value; strings need no quotes, arrays are written as TOML
+        value: String,
+    },
+    /// Open the config file in $VISUAL or $EDITOR, saving it once valid
+    Edit,
+    /// Print the path of the config file
+    Path,
+}
+
+/// Secret subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum SecretCommands {
+    /// Store a secret, reading its value from stdin
+    Set {
+        /// Name of the secret
+        name: String,
+        /// Provider under [providers] whose API key this is
+        #[arg(short, long)]
+        provider: Option<String>,
+    },
+    /// Delete a secret
+    Delete {
+        /// Name of the secret
+        name: String,
+    },
+    /// List stored secrets
+    List,
+    /// Move API keys written in the config file into the secrets store
+    Migrate,
+}
+
+/// Skill subcommands
+#[derive(Subcommand, Debug, Clone)]
+pub enum SkillCommands {
+    /// Validate skills and report problems
+    Check {
+        /// A skill directory, or a directory of skills (defaults to the discovered skill roots)
+        path: Option<PathBuf>,
+    },
 }
 
 /// MCP management subcommands
@@ -118,6 +307,30 @@ impl Cli {
         self.yolo
     }
 
+    /// Permission mode to start in: `--mode`, then `--yolo`, then the config
+    pub fn permission_mode(&self, config: &Config) -> PermissionMode {
+        match self.mode {
+            Some(mode) => mode,
+            None if self.yolo => PermissionMode::FullAuto,
+            None => config.permission_mode(),
+        }
+    }
+
+    /// Attachments for the first message, from `--file` and `--image`
+    pub fn attachments(&self) -> Result<Vec<Attachment>, AttachmentError> {
+        let files = self.files.iter().map(|path| attachment::file(path));
+        let images = self.images.iter().map(|path| attachment::image(path));
+        files.chain(images).collect()
+    }
+
+    /// Whether output may be styled: not with `--no-color`, `NO_COLOR`
+    /// set or a dumb terminal
+    pub fn color_enabled(&self) -> bool {
+        !self.no_color
+            && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            && std::env::var("TERM").map_or(true, |term| term != "dumb")
+    }
+
     /// Check if thinking mode is enabled
     pub fn is_thinking_mode(&self) -> bool {
         self.thinking
@@ -136,10 +349,67 @@ mod tests {
         assert_eq!(cli.prompt, Some("Hello".to_string()));
     }
 
+    #[test]
+    fn test_mode_parsing() {
+        let cli = Cli::parse_from(["kimi", "--mode", "plan"]);
+        assert_eq!(cli.mode, Some(PermissionMode::Plan));
+        let cli = Cli::parse_from(["kimi", "--mode", "auto-edit"]);
+        assert_eq!(cli.mode, Some(PermissionMode::AcceptEdits));
+        assert!(Cli::try_parse_from(["kimi", "--mode", "sometimes"]).is_err());
+        assert!(Cli::try_parse_from(["kimi", "--mode", "plan", "--yolo"]).is_err());
+
+        let cli = Cli::parse_from([
+            "kimi", "--approval-mode", "plan", "--allowed-tools", "Shell,WriteFile", "--disallowed-tools", "FetchURL",
+        ]);
+        assert_eq!(cli.mode, Some(PermissionMode::Plan));
+        assert_eq!(cli.allowed_tools, ["Shell", "WriteFile"]);
+        assert_eq!(cli.disallowed_tools, ["FetchURL"]);
+        assert_eq!(Cli::parse_from(["kimi", "--tools", "read-only"]).tool_profile.as_deref(), Some("read-only"));
+
+        let cli = Cli::parse_from(["kimi", "--print", "--max-turns", "10", "-p", "Fix the failing test"]);
+        assert_eq!(cli.max_turns, Some(10));
+        assert!(Cli::try_parse_from(["kimi", "--max-turns", "0"]).is_err());
+    }
+
     #[test]
     fn test_session_parsing() {
         let cli = Cli::parse_from(["kimi", "-s", "my-session", "--continue"]);
         assert_eq!(cli.session, Some("my-session".to_string()));
         assert!(cli.continue_);
     }
+
+    #[test]
+    fn test_attachment_flags() {
+        let temp = tempfile::tempdir().unwrap();
+        let notes = temp.path().join("notes.md");
+        std::fs::write(&notes, "# Notes\n").unwrap();
+        let notes = notes.to_str().unwrap();
+
+        let cli = Cli::parse_from(["kimi", "--file", notes, "--file", notes, "-p", "Summarize"]);
+        assert_eq!(cli.attachments().unwrap().len(), 2);
+        let cli = Cli::parse_from(["kimi", "--image", notes]);
+        assert!(cli.attachments().is_err());
+    }
+
+    #[test]
+    fn test_server_parsing() {
+        let cli = Cli::parse_from(["kimi", "--server", "--yolo"]);
+        assert!(cli.server);
+        assert!(Cli::try_parse_from(["kimi", "--server", "--print"]).is_err());
+
+        let cli = Cli::parse_from(["kimi", "--server", "--listen", "127.0.0.1:8765", "--token", "secret"]);
+        assert_eq!(cli.listen, Some("127.0.0.1:8765".parse().unwrap()));
+        assert_eq!(cli.token.as_deref(), Some("secret"));
+        assert!(Cli::try_parse_from(["kimi", "--listen", "127.0.0.1:8765"]).is_err());
+        assert!(Cli::try_parse_from(["kimi", "--server", "--token", "secret"]).is_err());
+    }
+
+    #[test]
+    fn test_skill_check_parsing() {
+        let cli = Cli::parse_from(["kimi", "skill", "check", "skills/pdf"]);
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Skill { subcommand: SkillCommands::Check { path: Some(ref p) } }) if p == &PathBuf::from("skills/pdf")
+        ));
+    }
 }
@@ -0,0 +1,78 @@
+//! `kimi config`: read and change the config file from the command line
+
+use anyhow::{bail, Result};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use kimi_core::config::{default_config_path, ConfigDocument};
+
+use crate::app::default_config_paths;
+use crate::cli::ConfigCommands;
+use crate::ui::editor;
+
+/// Execute config subcommand on the file given with `--config-file`, or
+/// the one kimi would load
+pub async fn execute(subcommand: ConfigCommands, config_file: Option<&Path>) -> Result<()> {
+    let path = config_path(config_file);
+    if path.extension().is_some_and(|e| e == "yaml" || e == "yml") {
+        bail!("kimi config only works on TOML config files, not {}", path.display());
+    }
+    match subcommand {
+        ConfigCommands::Get { key } => {
+            let document = ConfigDocument::load(&path)?;
+            match document.get(&key)? {
+                Some(value) => println!("{}", value),
+                None => bail!("{} is not set", key),
+            }
+        }
+        ConfigCommands::Set { key, value } => {
+            let mut document = ConfigDocument::load(&path)?;
+            document.set(&key, &value)?;
+            document.save()?;
+            println!("Set {} in {}", key, path.display());
+        }
+        ConfigCommands::Edit => edit(&path)?,
+        ConfigCommands::Path => println!("{}", path.display()),
+    }
+    Ok(())
+}
+
+/// `config_file`, else the first default location with a config, else the
+/// user's config file
+fn config_path(config_file: Option<&Path>) -> PathBuf {
+    config_file
+        .map(Path::to_path_buf)
+        .or_else(|| default_config_paths().into_iter().find(|path| path.exists()))
+        .unwrap_or_else(default_config_path)
+}
+
+/// Edit a copy of the config in the user's editor, saving it once it is a
+/// valid config
+fn edit(path: &Path) -> Result<()> {
+    let mut document = ConfigDocument::load(path)?;
+    let original = document.to_string();
+    let mut text = original.clone();
+    loop {
+        text = editor::edit_text(&text, Some("toml"))?;
+        if text == original {
+            println!("No changes made to {}", path.display());
+            return Ok(());
+        }
+        match document.replace(&text) {
+            Ok(()) => break,
+            Err(e) => {
+                eprintln!("{}", e);
+                print!("Edit again? [Y/n] ");
+                io::stdout().flush()?;
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                if input.trim().eq_ignore_ascii_case("n") {
+                    bail!("{} was not changed", path.display());
+                }
+            }
+        }
+    }
+    document.save()?;
+    println!("Saved {}", path.display());
+    Ok(())
+}
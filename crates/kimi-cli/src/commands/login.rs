@@ -1,17 +1,41 @@
 use anyhow::Result;
 use kimi_core::auth::{
-    login_kimi_code, logout_kimi_code, OAuthEvent, OAuthError,
+    login_platform, logout_platform, validate_account_name, OAuthEvent, OAuthError, Platform,
+    PlatformRegistry, DEFAULT_ACCOUNT, KIMI_CODE_PLATFORM_ID,
 };
+use kimi_core::config::Config;
 use kimi_core::config::{load_config, save_config};
 use std::io::{self, Write};
 use tracing::{error, info, warn};
 
+/// Look up an OAuth platform by ID, defaulting to Kimi Code
+fn oauth_platform(config: &Config, platform: Option<&str>) -> Result<Platform, OAuthError> {
+    let registry = PlatformRegistry::from_config(config);
+    let id = platform.unwrap_or(KIMI_CODE_PLATFORM_ID);
+    match registry.get(id) {
+        Some(platform) if platform.oauth.is_some() => Ok(platform.clone()),
+        Some(platform) => Err(OAuthError::General(format!(
+            "{} does not support OAuth login",
+            platform.name
+        ))),
+        None => {
+            let known: Vec<_> = registry.oauth_platforms().map(|p| p.id.as_str()).collect();
+            Err(OAuthError::General(format!(
+                "Unknown platform {}; OAuth platforms: {}",
+                id,
+                known.join(", ")
+            )))
+        }
+    }
+}
+
 /// Execute the login command
 ///
-/// This handles OAuth authentication with the Kimi Code platform.
-/// It will open a browser for the user to authenticate and
-/// store the resulting credentials securely.
-pub async fn execute(open_browser: bool) -> Result<()> {
+/// This handles OAuth authentication with the given platform, or Kimi
+/// Code if none is given, into the given or the default account. It will
+/// open a browser for the user to authenticate and store the resulting
+/// credentials securely.
+pub async fn execute(open_browser: bool, platform: Option<&str>, account: Option<&str>) -> Result<()> {
     info!("Starting OAuth login flow");
 
     // Load existing config
@@ -27,10 +51,17 @@ pub async fn execute(open_browser: bool) -> Result<()> {
         .into());
     }
 
-    println!("Initiating login to Kimi Code...\n");
+    let platform = oauth_platform(&config, platform)?;
+    let account = account.unwrap_or(DEFAULT_ACCOUNT);
+    validate_account_name(account)?;
+    if account == DEFAULT_ACCOUNT {
+        println!("Initiating login to {}...\n", platform.name);
+    } else {
+        println!("Initiating login to {} as account {}...\n", platform.name, account);
+    }
 
     // Run the OAuth flow
-    let events = login_kimi_code(&mut config, open_browser).await?;
+    let events = login_platform(&mut config, &platform, account, open_browser).await?;
 
     // Display events
     let mut success = false;
@@ -99,9 +130,11 @@ pub async fn execute(open_browser: bool) -> Result<()> {
 
 /// Execute the logout command
 ///
-/// This clears stored OAuth credentials and removes
-/// the managed provider configuration.
-pub async fn logout() -> Result<()> {
+/// This clears the stored OAuth credentials of the given account, or the
+/// active one, of the given platform, or Kimi Code. Logging out of the
+/// active account also removes the platform's managed provider
+/// configuration.
+pub async fn logout(platform: Option<&str>, account: Option<&str>) -> Result<()> {
     info!("Starting logout");
 
     // Load existing config
@@ -117,10 +150,17 @@ pub async fn logout() -> Result<()> {
         .into());
     }
 
-    println!("Logging out from Kimi Code...\n");
+    let platform = oauth_platform(&config, platform)?;
+    match account {
+        Some(account) => {
+            validate_account_name(account)?;
+            println!("Logging out of account {} on {}...\n", account, platform.name);
+        }
+        None => println!("Logging out from {}...\n", platform.name),
+    }
 
     // Run the logout flow
-    let events = logout_kimi_code(&mut config).await?;
+    let events = logout_platform(&mut config, &platform.id, account).await?;
 
     // Display events
     let mut success = false;
@@ -4,6 +4,9 @@ use std::collections::HashMap;
 use std::path::PathBuf;
 use tracing::info;
 
+use kimi_core::mcp::McpClient;
+use kimi_core::soul::McpServerInfo;
+
 use crate::cli::McpCommands;
 
 /// MCP server configuration
@@ -16,6 +19,15 @@ pub struct McpServerConfig {
     pub enabled: bool,
 }
 
+impl McpServerConfig {
+    /// The server as the toolset starts it, under `name`
+    pub fn to_server_info(&self, name: &str) -> McpServerInfo {
+        McpServerInfo::new(name, &self.command)
+            .with_args(self.args.clone())
+            .with_env(self.env.clone().unwrap_or_default())
+    }
+}
+
 /// MCP configuration file structure
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct McpConfig {
@@ -128,14 +140,19 @@ async fn test_server(name: String) -> Result<()> {
     println!("Testing connection to '{}'...", name);
     println!("  Command: {} {}", server.command, server.args.join(" "));
 
-    // TODO: Implement actual MCP connection test
-    // This would involve:
-    // 1. Starting the MCP server process
-    // 2. Sending initialize request
-    // 3. Waiting for response
-    // 4. Checking capabilities
+    let client = McpClient::connect(&server.to_server_info(&name))
+        .await
+        .with_context(|| format!("Failed to connect to '{}'", name))?;
+    let tools = client.list_tools().await.context("Failed to list the server's tools")?;
 
     println!("✓ Connection successful!");
+    if let Some(info) = client.server_info() {
+        println!("  Server: {}", info);
+    }
+    println!("  Tools ({}):", tools.len());
+    for tool in &tools {
+        println!("    {} - {}", tool.name, tool.description.lines().next().unwrap_or(""));
+    }
 
     Ok(())
 }
@@ -150,7 +167,7 @@ fn get_config_path() -> Result<PathBuf> {
 }
 
 /// Load MCP configuration from file
-async fn load_config() -> Result<McpConfig> {
+pub(crate) async fn load_config() -> Result<McpConfig> {
     let path = get_config_path()?;
 
     if !path.exists() {
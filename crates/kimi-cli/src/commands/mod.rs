@@ -3,6 +3,14 @@
 //! This module contains implementations for various subcommands
 //! like login, MCP management, etc.
 
+pub mod config;
 pub mod login;
 pub mod mcp;
+pub mod resume;
+pub mod secret;
 pub mod setup;
+pub mod skill;
+pub mod stats;
+pub mod tools;
+pub mod update;
+pub mod watch;
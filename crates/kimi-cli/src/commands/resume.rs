@@ -0,0 +1,145 @@
+//! `kimi resume`: pick a session of the working directory to continue
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+use kimi_core::{Session, SessionManager, SessionSummary};
+
+use crate::ui::fuzzy_score;
+use crate::ui::theme::theme;
+
+/// Longest title shown in a session list
+const MAX_TITLE_CHARS: usize = 60;
+
+/// The ID of the session to resume: the one `id` names, a session name, a
+/// full ID or a prefix like the short ID, or else the one picked from a list
+///
+/// `None` when the user leaves the list without picking.
+pub fn select(work_dir: &Path, id: Option<&str>) -> Result<Option<String>> {
+    if let Some(id) = id {
+        return Ok(Some(SessionManager::new(work_dir).get(id)?.id_string()));
+    }
+
+    let summaries = Session::list_summaries(work_dir)?;
+    if summaries.is_empty() {
+        bail!("No sessions in {}", work_dir.display());
+    }
+    let mut shown: Vec<&SessionSummary> = summaries.iter().collect();
+    loop {
+        println!("\n{}", theme().heading.paint("Sessions"));
+        print_sessions(&shown);
+        print!(
+            "\n  {} ",
+            theme().emphasis.paint("Number, text to filter, or Enter to cancel:")
+        );
+        std::io::stdout().flush()?;
+        let mut input = String::new();
+        std::io::stdin().read_line(&mut input)?;
+
+        let input = input.trim();
+        if input.is_empty() {
+            return Ok(None);
+        }
+        if let Some(summary) = input.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| shown.get(i)) {
+            return Ok(Some(summary.session.id_string()));
+        }
+        let matches = filter(&summaries, input);
+        match matches.as_slice() {
+            [] => println!("{}", theme().warning.paint(format!("No sessions match '{}'", input))),
+            [only] => return Ok(Some(only.session.id_string())),
+            _ => shown = matches,
+        }
+    }
+}
+
+/// Print a numbered line per session: name or short ID, title, age,
+/// message count and working directory
+pub fn print_sessions(summaries: &[&SessionSummary]) {
+    let now = Utc::now();
+    for (index, summary) in summaries.iter().enumerate() {
+        println!(
+            "  {:>2}. {} {}",
+            index + 1,
+            theme().command.paint(summary.session.display_name()),
+            theme().emphasis.paint(title(summary))
+        );
+        println!(
+            "      {}",
+            theme().muted.paint(format!(
+                "{} · {} message(s) · {}",
+                age(summary.updated_at, now),
+                summary.message_count,
+                summary.session.work_dir.display()
+            ))
+        );
+    }
+}
+
+/// Sessions whose title, ID or working directory match `query`, best first
+fn filter<'a>(summaries: &'a [SessionSummary], query: &str) -> Vec<&'a SessionSummary> {
+    let mut matches: Vec<(i64, &SessionSummary)> = summaries
+        .iter()
+        .filter_map(|summary| {
+            let work_dir = summary.session.work_dir.display().to_string();
+            [title(summary), summary.session.display_name(), summary.session.id_string(), work_dir]
+                .iter()
+                .filter_map(|text| fuzzy_score(query, text))
+                .max()
+                .map(|score| (score, summary))
+        })
+        .collect();
+    matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+    matches.into_iter().map(|(_, summary)| summary).collect()
+}
+
+fn title(summary: &SessionSummary) -> String {
+    match &summary.title {
+        Some(title) if title.chars().count() > MAX_TITLE_CHARS => {
+            format!("{}…", title.chars().take(MAX_TITLE_CHARS - 1).collect::<String>())
+        }
+        Some(title) => title.clone(),
+        None => "(no messages)".to_string(),
+    }
+}
+
+/// How long ago `time` was, e.g. `5m ago`
+fn age(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let seconds = (now - time).num_seconds().max(0);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn test_session_list_formatting() {
+        let now = Utc::now();
+        assert_eq!(age(now - Duration::seconds(5), now), "just now");
+        assert_eq!(age(now - Duration::minutes(90), now), "1h ago");
+        assert_eq!(age(now - Duration::days(3), now), "3d ago");
+
+        let session = Session::new("/work/app".into());
+        let summary = |title: Option<&str>| SessionSummary {
+            session: session.clone(),
+            title: title.map(str::to_string),
+            message_count: 4,
+            updated_at: now,
+        };
+        let summaries = [summary(Some("Fix the flaky login test")), summary(None)];
+        assert_eq!(title(&summaries[1]), "(no messages)");
+        assert_eq!(title(&summary(Some(&"x".repeat(80)))).chars().count(), MAX_TITLE_CHARS);
+        let matches = filter(&summaries, "login");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].title.as_deref(), Some("Fix the flaky login test"));
+    }
+}
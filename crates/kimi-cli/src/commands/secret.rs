@@ -0,0 +1,88 @@
+//! Secret subcommands
+
+use anyhow::{bail, Result};
+use std::io::{self, BufRead, IsTerminal, Write};
+
+use crate::cli::SecretCommands;
+use kimi_core::auth::SecretsManager;
+use kimi_core::config::{load_config, save_config};
+
+/// Execute secret subcommand
+pub async fn execute(subcommand: SecretCommands) -> Result<()> {
+    let secrets = SecretsManager::new();
+    match subcommand {
+        SecretCommands::Set { name, provider } => set_secret(&secrets, &name, provider.as_deref()),
+        SecretCommands::Delete { name } => {
+            if !secrets.delete(&name)? {
+                bail!("No secret named {}", name);
+            }
+            println!("Deleted secret {}", name);
+            Ok(())
+        }
+        SecretCommands::List => {
+            let list = secrets.list()?;
+            if list.is_empty() {
+                println!("No secrets stored.");
+            }
+            for (name, backend) in list {
+                println!("{}  ({})", name, backend);
+            }
+            Ok(())
+        }
+        SecretCommands::Migrate => migrate(&secrets),
+    }
+}
+
+/// Store a secret read from stdin, and point `provider` at it
+fn set_secret(secrets: &SecretsManager, name: &str, provider: Option<&str>) -> Result<()> {
+    let mut config = match provider {
+        Some(provider) => {
+            let config = load_config(None)?;
+            if !config.providers.contains_key(provider) {
+                bail!("No provider named {} in the config", provider);
+            }
+            Some(config)
+        }
+        None => None,
+    };
+
+    if io::stdin().is_terminal() {
+        print!("Value for {}: ", name);
+        io::stdout().flush()?;
+    }
+    let mut value = String::new();
+    io::stdin().lock().read_line(&mut value)?;
+    let value = value.trim();
+    if value.is_empty() {
+        bail!("The secret value is empty");
+    }
+
+    let backend = secrets.set(name, value)?;
+    println!("Stored secret {} in the {}", name, backend);
+
+    if let (Some(config), Some(provider)) = (config.as_mut(), provider) {
+        if let Some(entry) = config.providers.get_mut(provider) {
+            entry.api_key_ref = Some(name.to_string());
+        }
+        // Saving drops plain API keys, so move the other providers' keys too
+        config.move_api_keys_to_secrets(secrets)?;
+        save_config(config, None)?;
+        println!("Provider {} now reads its API key from secret {}", provider, name);
+    }
+    Ok(())
+}
+
+/// Move plain API keys from the config file into the secrets store
+fn migrate(secrets: &SecretsManager) -> Result<()> {
+    let mut config = load_config(None)?;
+    let moved = config.move_api_keys_to_secrets(secrets)?;
+    if moved.is_empty() {
+        println!("No API keys to move; the config has none in plain text.");
+        return Ok(());
+    }
+    save_config(&config, None)?;
+    for provider in moved {
+        println!("Moved the API key of provider {} to secret {}", provider, provider);
+    }
+    Ok(())
+}
@@ -1,17 +1,23 @@
-//! Setup command for manual provider configuration
+//! Setup wizard for configuring a provider
 //!
-//! This module provides an interactive setup wizard for configuring
-//! LLM providers with API keys, similar to the original kimi-cli setup.
+//! `kimi setup`, `/setup` and the first run of the shell without a config
+//! file all go through the same guided flow: sign in with OAuth, enter an
+//! API key or point at Ollama or another local model server, test the
+//! connection by listing the models, pick the default model and pick the
+//! permission mode.
 
 use anyhow::Result;
-use kimi_core::auth::platforms::{list_models, Platform};
-use kimi_core::auth::KIMI_CODE_PLATFORM_ID;
+use kimi_core::approval::PermissionMode;
+use kimi_core::auth::platforms::{list_models, managed_provider_key, ModelInfo, Platform};
+use kimi_core::auth::{SecretsManager, KIMI_CODE_PLATFORM_ID};
 use kimi_core::config::{load_config, save_config, LlmProvider, ProviderType};
 use kimi_core::types::LlmModel;
-use secrecy::SecretString;
+use kosong_rs::{ModelCapability, OllamaProvider};
 use std::io::{self, Write};
 use tracing::info;
 
+use crate::ui::theme::theme;
+
 /// Provider choice for setup
 #[derive(Debug, Clone)]
 pub enum ProviderChoice {
@@ -21,6 +27,10 @@ pub enum ProviderChoice {
     MoonshotCN,
     /// Moonshot AI Overseas (api.moonshot.ai)
     MoonshotAI,
+    /// A local Ollama server, through its native API
+    Ollama,
+    /// Another local OpenAI-compatible server such as LM Studio
+    Local,
 }
 
 impl ProviderChoice {
@@ -30,6 +40,8 @@ impl ProviderChoice {
             ProviderChoice::KimiCode => "Kimi Code (OAuth)",
             ProviderChoice::MoonshotCN => "Moonshot AI (China) - api.moonshot.cn",
             ProviderChoice::MoonshotAI => "Moonshot AI (Overseas) - api.moonshot.ai",
+            ProviderChoice::Ollama => "Ollama (local models)",
+            ProviderChoice::Local => "Another local OpenAI-compatible server (LM Studio, llama.cpp, ...)",
         }
     }
 
@@ -39,6 +51,8 @@ impl ProviderChoice {
             ProviderChoice::KimiCode => KIMI_CODE_PLATFORM_ID,
             ProviderChoice::MoonshotCN => "moonshot-cn",
             ProviderChoice::MoonshotAI => "moonshot-ai",
+            ProviderChoice::Ollama => "ollama",
+            ProviderChoice::Local => "local",
         }
     }
 
@@ -47,6 +61,11 @@ impl ProviderChoice {
         matches!(self, ProviderChoice::KimiCode)
     }
 
+    /// Whether this provider needs an API key
+    pub fn requires_api_key(&self) -> bool {
+        matches!(self, ProviderChoice::MoonshotCN | ProviderChoice::MoonshotAI)
+    }
+
     /// Get base URL
     pub fn base_url(&self) -> String {
         match self {
@@ -56,10 +75,25 @@ impl ProviderChoice {
             }
             ProviderChoice::MoonshotCN => "https://api.moonshot.cn/v1".to_string(),
             ProviderChoice::MoonshotAI => "https://api.moonshot.ai/v1".to_string(),
+            ProviderChoice::Ollama => ProviderType::Ollama.default_base_url().to_string(),
+            ProviderChoice::Local => "http://localhost:1234/v1".to_string(),
+        }
+    }
+
+    /// The provider type models are configured with
+    fn provider_type(&self) -> ProviderType {
+        match self {
+            ProviderChoice::Ollama => ProviderType::Ollama,
+            ProviderChoice::Local => ProviderType::OpenAiLegacy,
+            _ => ProviderType::Kimi,
         }
     }
 }
 
+/// Largest context the wizard configures for an Ollama model, in tokens;
+/// Ollama allocates memory for the whole context when it loads a model
+const OLLAMA_MAX_CONTEXT: usize = 32768;
+
 /// Execute the interactive setup wizard
 pub async fn execute() -> Result<()> {
     info!("Starting setup wizard");
@@ -69,22 +103,37 @@ pub async fn execute() -> Result<()> {
     println!("{}", "═".repeat(60));
     println!();
 
-    // Load existing config
-    let mut config = load_config(None)?;
-
-    // Step 1: Choose provider
-    let provider_choice = select_provider()?;
+    // Step 1 and 2: choose a provider and connect to it, until one works
+    loop {
+        let Some(provider_choice) = select_provider()? else {
+            println!("Setup skipped. Run `kimi setup` or /setup any time to configure a provider.");
+            return Ok(());
+        };
+        println!();
+
+        let result = if provider_choice.requires_oauth() {
+            // OAuth flow - delegate to login command, then let the user
+            // pick among the platform's models
+            println!("Starting OAuth authentication for {}...", provider_choice.name());
+            match crate::commands::login::execute(true, None, None).await {
+                Ok(()) => select_managed_model(&provider_choice),
+                Err(e) => Err(e),
+            }
+        } else {
+            setup_api_key_provider(&provider_choice).await
+        };
+        match result {
+            Ok(()) => break,
+            Err(e) => {
+                println!("\n{} {}", theme().error.paint("Setup failed:"), e);
+                println!("Choose a provider to try again, or skip.\n");
+            }
+        }
+    }
     println!();
 
-    // Step 2: Configure based on provider type
-    if provider_choice.requires_oauth() {
-        // OAuth flow - delegate to login command
-        println!("Starting OAuth authentication for {}...", provider_choice.name());
-        crate::commands::login::execute(true).await?;
-    } else {
-        // API key flow
-        setup_api_key_provider(&mut config, &provider_choice).await?;
-    }
+    // Step 3: how much the agent may do without asking
+    select_permission_mode()?;
 
     println!();
     println!("{}", "═".repeat(60));
@@ -97,12 +146,14 @@ pub async fn execute() -> Result<()> {
     Ok(())
 }
 
-/// Select provider interactively
-fn select_provider() -> Result<ProviderChoice> {
-    let choices = vec![
+/// Select provider interactively, or `None` to skip setup
+fn select_provider() -> Result<Option<ProviderChoice>> {
+    let choices = [
         ProviderChoice::KimiCode,
         ProviderChoice::MoonshotCN,
         ProviderChoice::MoonshotAI,
+        ProviderChoice::Ollama,
+        ProviderChoice::Local,
     ];
 
     println!("Select a provider:");
@@ -111,42 +162,31 @@ fn select_provider() -> Result<ProviderChoice> {
     for (i, choice) in choices.iter().enumerate() {
         println!("  {}. {}", i + 1, choice.name());
     }
+    println!("  {}. Skip for now", choices.len() + 1);
     println!();
 
-    loop {
-        print!("Enter your choice (1-{}): ", choices.len());
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-
-        match input.trim().parse::<usize>() {
-            Ok(n) if n >= 1 && n <= choices.len() => {
-                return Ok(choices[n - 1].clone());
-            }
-            _ => {
-                println!("Invalid choice. Please enter a number between 1 and {}.", choices.len());
-            }
-        }
-    }
+    let n = read_choice(&format!("Enter your choice (1-{}): ", choices.len() + 1), choices.len() + 1, None)?;
+    Ok(choices.get(n).cloned())
 }
 
-/// Setup provider with API key
-async fn setup_api_key_provider(
-    config: &mut kimi_core::config::Config,
-    choice: &ProviderChoice,
-) -> Result<()> {
+/// Setup a provider with an API key, or a local server without one
+async fn setup_api_key_provider(choice: &ProviderChoice) -> Result<()> {
+    let mut config = load_config(None)?;
     let platform_id = choice.platform_id();
-    let base_url = choice.base_url();
 
     println!("Setting up {}...", choice.name());
     println!();
 
-    // Step 1: Get API key
-    let api_key = prompt_api_key()?;
-    if api_key.is_empty() {
-        return Err(anyhow::anyhow!("API key is required"));
-    }
+    // Step 1: Get API key, or the local server's address
+    let (api_key, base_url) = if choice.requires_api_key() {
+        let api_key = prompt_api_key()?;
+        if api_key.is_empty() {
+            return Err(anyhow::anyhow!("API key is required"));
+        }
+        (api_key, choice.base_url())
+    } else {
+        (String::new(), prompt_base_url(&choice.base_url())?)
+    };
     println!();
 
     // Step 2: Test connection and fetch models
@@ -157,21 +197,41 @@ async fn setup_api_key_provider(
         base_url: base_url.clone(),
         search_url: None,
         fetch_url: None,
-        allowed_prefixes: Some(vec!["kimi-k".to_string()]),
+        allowed_prefixes: choice.requires_api_key().then(|| vec!["kimi-k".to_string()]),
+        oauth: None,
     };
 
-    let models = match list_models(&platform, &api_key).await {
+    let listed = match choice {
+        ProviderChoice::Ollama => list_ollama_models(&base_url).await,
+        _ => list_models(&platform, &api_key).await.map_err(Into::into),
+    };
+    let models = match listed {
         Ok(models) => models,
-        Err(e) => {
+        Err(e) if choice.requires_api_key() => {
             return Err(anyhow::anyhow!(
                 "Failed to connect to API or fetch models: {}. Please check your API key and try again.",
                 e
             ));
         }
+        Err(e) => {
+            return Err(anyhow::anyhow!(
+                "Failed to reach {}: {}. Is the server running{}?",
+                base_url,
+                e,
+                match choice {
+                    ProviderChoice::Ollama => " (start it with `ollama serve`)",
+                    _ => "",
+                }
+            ));
+        }
     };
 
     if models.is_empty() {
-        return Err(anyhow::anyhow!("No models available for this API key"));
+        return Err(match choice {
+            ProviderChoice::Ollama => anyhow::anyhow!("The server has no models; pull one first, e.g. `ollama pull qwen2.5-coder`"),
+            ProviderChoice::Local => anyhow::anyhow!("The server has no models; load one first"),
+            _ => anyhow::anyhow!("No models available for this API key"),
+        });
     }
 
     println!("  Found {} models", models.len());
@@ -187,18 +247,17 @@ async fn setup_api_key_provider(
     // Remove existing models for this provider
     config.models.retain(|_, model| model.provider != provider_key);
 
-    // Add provider
-    config.providers.insert(
-        provider_key.clone(),
-        LlmProvider {
-            provider_type: ProviderType::Kimi,
-            base_url,
-            api_key: SecretString::new(api_key),
-            env: None,
-            custom_headers: None,
-            oauth: None,
-        },
-    );
+    // Keep the API key in the secrets store; the config only names it.
+    // Local servers need none.
+    let provider = LlmProvider::new(choice.provider_type(), base_url, String::new());
+    let backend = if api_key.is_empty() {
+        config.providers.insert(provider_key.clone(), provider);
+        None
+    } else {
+        let backend = SecretsManager::new().set(&provider_key, &api_key)?;
+        config.providers.insert(provider_key.clone(), provider.with_api_key_ref(provider_key.clone()));
+        Some(backend)
+    };
 
     // Add models
     for model_info in &models {
@@ -206,8 +265,10 @@ async fn setup_api_key_provider(
         let model = LlmModel {
             name: model_info.id.clone(),
             provider: provider_key.clone(),
-            max_tokens: Some(model_info.context_length),
+            // OpenAI-compatible local servers do not report the context length
+            max_tokens: (model_info.context_length > 0).then_some(model_info.context_length),
             temperature: None,
+            pricing: None,
         };
         config.models.insert(model_key, model);
     }
@@ -217,17 +278,140 @@ async fn setup_api_key_provider(
     config.default_model = default_model_key;
 
     // Save config
-    save_config(config, None)?;
+    save_config(&config, None)?;
 
     println!("Configuration saved successfully!");
     println!();
     println!("  Provider: {}", choice.name());
     println!("  Default model: {}", selected_model.id);
     println!("  Available models: {}", models.len());
+    if let Some(backend) = backend {
+        println!("  API key stored in: {}", backend);
+    }
+    if matches!(choice, ProviderChoice::Ollama) && selected_model.context_length == OLLAMA_MAX_CONTEXT {
+        println!("  Context: {} tokens; raise the model's max_tokens in the config for more", format_number(OLLAMA_MAX_CONTEXT));
+    }
 
     Ok(())
 }
 
+/// The models on the Ollama server at `base_url`, with the context each is
+/// loaded with and its capabilities as the server reports them
+async fn list_ollama_models(base_url: &str) -> Result<Vec<ModelInfo>> {
+    let server = OllamaProvider::with_base_url("", base_url)?;
+    let mut models = Vec::new();
+    for model in server.list_models().await? {
+        let info = server.show_model(&model.name).await?;
+        // Embedding-only models cannot chat; older servers report nothing
+        if !info.capabilities.is_empty() && !info.capabilities.iter().any(|c| c == "completion") {
+            continue;
+        }
+        let capabilities = OllamaProvider::infer_capabilities(&model.name);
+        let has = |name: &str, capability: ModelCapability| match info.capabilities.is_empty() {
+            true => capabilities.contains(&capability),
+            false => info.capabilities.iter().any(|c| c == name),
+        };
+        let context_length = info.context_length.map_or(0, |tokens| tokens as usize);
+        models.push(ModelInfo {
+            supports_reasoning: has("thinking", ModelCapability::Thinking),
+            supports_image_in: has("vision", ModelCapability::Vision),
+            supports_video_in: false,
+            context_length: context_length.min(OLLAMA_MAX_CONTEXT),
+            id: model.name,
+        });
+    }
+    Ok(models)
+}
+
+/// After signing in, pick the default among the platform's models; login
+/// picked the first one
+fn select_managed_model(choice: &ProviderChoice) -> Result<()> {
+    let mut config = load_config(None)?;
+    let provider_key = managed_provider_key(choice.platform_id());
+    let mut models: Vec<(&String, &LlmModel)> =
+        config.models.iter().filter(|(_, model)| model.provider == provider_key).collect();
+    if models.len() < 2 {
+        return Ok(());
+    }
+    models.sort_by_key(|(key, model)| (std::cmp::Reverse(model.max_tokens), key.to_string()));
+
+    println!("\nAvailable models:");
+    println!();
+    for (i, (key, model)) in models.iter().enumerate() {
+        let current = if **key == config.default_model { " (current)" } else { "" };
+        match model.max_tokens {
+            Some(tokens) => println!("  {}. {} ({} tokens){}", i + 1, model.name, format_number(tokens), current),
+            None => println!("  {}. {}{}", i + 1, model.name, current),
+        }
+    }
+    println!();
+
+    let current = models.iter().position(|(key, _)| **key == config.default_model);
+    let prompt = match current {
+        Some(i) => format!("Select default model (1-{}, Enter for {}): ", models.len(), i + 1),
+        None => format!("Select default model (1-{}): ", models.len()),
+    };
+    let n = read_choice(&prompt, models.len(), current)?;
+    config.default_model = models[n].0.clone();
+    save_config(&config, None)?;
+    Ok(())
+}
+
+/// Pick the permission mode sessions start in and save it
+fn select_permission_mode() -> Result<()> {
+    let mut config = load_config(None)?;
+    let modes = PermissionMode::ALL;
+    let current = modes.iter().position(|mode| *mode == config.permission_mode());
+
+    println!("How much may the agent do without asking?");
+    println!();
+    for (i, mode) in modes.iter().enumerate() {
+        println!("  {}. {} - {}", i + 1, mode.as_str(), mode.description());
+    }
+    println!();
+
+    let prompt = match current {
+        Some(i) => format!("Select permission mode (1-{}, Enter for {}): ", modes.len(), i + 1),
+        None => format!("Select permission mode (1-{}): ", modes.len()),
+    };
+    let mode = modes[read_choice(&prompt, modes.len(), current)?];
+    config.default_permission_mode = Some(mode);
+    save_config(&config, None)?;
+    println!("Sessions will start in {} mode; change it with --mode or /permissions.", mode.as_str());
+    Ok(())
+}
+
+/// Ask for a number between 1 and `count` until one is given, returning it
+/// zero-based; an empty answer picks `default` if there is one
+fn read_choice(prompt: &str, count: usize, default: Option<usize>) -> Result<usize> {
+    loop {
+        print!("{}", prompt);
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        if io::stdin().read_line(&mut input)? == 0 {
+            return Err(anyhow::anyhow!("Setup cancelled"));
+        }
+
+        match (input.trim().parse::<usize>(), default) {
+            (Ok(n), _) if n >= 1 && n <= count => return Ok(n - 1),
+            (_, Some(default)) if input.trim().is_empty() => return Ok(default),
+            _ => println!("Invalid choice. Please enter a number between 1 and {}.", count),
+        }
+    }
+}
+
+/// Prompt for the address of a local server, defaulting to `default`
+fn prompt_base_url(default: &str) -> Result<String> {
+    print!("Server URL (Enter for {}): ", default);
+    io::stdout().flush()?;
+
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let url = input.trim().trim_end_matches('/');
+    Ok(if url.is_empty() { default.to_string() } else { url.to_string() })
+}
+
 /// Prompt for API key
 fn prompt_api_key() -> Result<String> {
     println!("Please enter your API key.");
@@ -271,21 +455,25 @@ fn prompt_api_key() -> Result<String> {
 }
 
 /// Select model interactively
-fn select_model(models: &[kimi_core::auth::platforms::ModelInfo]) -> Result<&kimi_core::auth::platforms::ModelInfo> {
+fn select_model(models: &[ModelInfo]) -> Result<&ModelInfo> {
     println!("Available models:");
     println!();
 
     // Sort models by context length (descending)
     let mut sorted_models: Vec<_> = models.iter().collect();
-    sorted_models.sort_by(|a, b| b.context_length.cmp(&a.context_length));
+    sorted_models.sort_by_key(|m| std::cmp::Reverse(m.context_length));
 
     for (i, model) in sorted_models.iter().enumerate() {
         let capabilities = format_capabilities(model);
+        let context = match model.context_length {
+            0 => String::new(),
+            tokens => format!(" ({} tokens)", format_number(tokens)),
+        };
         println!(
-            "  {}. {} ({} tokens){}",
+            "  {}. {}{}{}",
             i + 1,
             model.id,
-            format_number(model.context_length),
+            context,
             if capabilities.is_empty() {
                 String::new()
             } else {
@@ -295,29 +483,12 @@ fn select_model(models: &[kimi_core::auth::platforms::ModelInfo]) -> Result<&kim
     }
     println!();
 
-    loop {
-        print!("Select default model (1-{}): ", sorted_models.len());
-        io::stdout().flush()?;
-
-        let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
-
-        match input.trim().parse::<usize>() {
-            Ok(n) if n >= 1 && n <= sorted_models.len() => {
-                return Ok(sorted_models[n - 1]);
-            }
-            _ => {
-                println!(
-                    "Invalid choice. Please enter a number between 1 and {}.",
-                    sorted_models.len()
-                );
-            }
-        }
-    }
+    let n = read_choice(&format!("Select default model (1-{}): ", sorted_models.len()), sorted_models.len(), None)?;
+    Ok(sorted_models[n])
 }
 
 /// Format model capabilities for display
-fn format_capabilities(model: &kimi_core::auth::platforms::ModelInfo) -> String {
+fn format_capabilities(model: &ModelInfo) -> String {
     let mut caps = Vec::new();
 
     if model.supports_reasoning {
@@ -363,6 +534,17 @@ mod tests {
         let ai = ProviderChoice::MoonshotAI;
         assert!(!ai.requires_oauth());
         assert_eq!(ai.base_url(), "https://api.moonshot.ai/v1");
+
+        let ollama = ProviderChoice::Ollama;
+        assert!(!ollama.requires_api_key());
+        assert_eq!(ollama.base_url(), "http://localhost:11434");
+        assert!(matches!(ollama.provider_type(), ProviderType::Ollama));
+
+        let local = ProviderChoice::Local;
+        assert!(!local.requires_oauth());
+        assert!(!local.requires_api_key());
+        assert_eq!(local.base_url(), "http://localhost:1234/v1");
+        assert!(matches!(local.provider_type(), ProviderType::OpenAiLegacy));
     }
 
     #[test]
@@ -0,0 +1,108 @@
+//! Skill subcommands
+
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use crate::cli::SkillCommands;
+use crate::ui::theme::theme;
+use kimi_core::skill::{Severity, SkillCheck, SkillDiscovery};
+
+/// Execute skill subcommand
+pub async fn execute(subcommand: SkillCommands, work_dir: &Path) -> Result<()> {
+    match subcommand {
+        SkillCommands::Check { path } => check_skills(path, work_dir).await,
+    }
+}
+
+/// Validate skills and print a report, failing if any skill has errors
+async fn check_skills(path: Option<PathBuf>, work_dir: &Path) -> Result<()> {
+    let dirs = match path {
+        Some(path) if path.join("SKILL.md").exists() => vec![path],
+        Some(path) if path.is_dir() => skill_dirs(&path),
+        Some(path) => bail!("{} is not a skill directory", path.display()),
+        None => {
+            let mut dirs = Vec::new();
+            for root in SkillDiscovery::resolve_roots(work_dir).await {
+                dirs.extend(skill_dirs(&root));
+            }
+            dirs
+        }
+    };
+
+    if dirs.is_empty() {
+        println!("No skills found.");
+        return Ok(());
+    }
+
+    let checks: Vec<SkillCheck> = dirs.iter().map(|dir| SkillDiscovery::check(dir)).collect();
+    for check in &checks {
+        print_check(check);
+    }
+
+    let failed = checks.iter().filter(|c| c.has_errors()).count();
+    println!();
+    if failed > 0 {
+        bail!("{} of {} skill(s) failed validation", failed, checks.len());
+    }
+    println!("{} skill(s) checked, no errors", checks.len());
+    Ok(())
+}
+
+/// Subdirectories of `root` that contain a SKILL.md, sorted by path
+fn skill_dirs(root: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(root)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.join("SKILL.md").is_file())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+fn print_check(check: &SkillCheck) {
+    let name = check
+        .skill
+        .as_ref()
+        .map(|s| s.name.clone())
+        .unwrap_or_else(|| check.dir.display().to_string());
+    let status = if check.has_errors() {
+        theme().error.paint("✗")
+    } else if check.issues.is_empty() {
+        theme().success.paint("✓")
+    } else {
+        theme().warning.paint("!")
+    };
+    println!("{} {} {}",
+        status,
+        theme().emphasis.paint(name),
+        theme().muted.paint(check.dir.display().to_string())
+    );
+
+    for issue in &check.issues {
+        let style = match issue.severity {
+            Severity::Error => theme().error,
+            Severity::Warning => theme().warning,
+        };
+        println!("    {}: {}", style.paint(issue.severity.to_string()), issue.message);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skill_dirs() {
+        let temp = tempfile::tempdir().unwrap();
+        for name in ["b", "a"] {
+            std::fs::create_dir(temp.path().join(name)).unwrap();
+            std::fs::write(temp.path().join(name).join("SKILL.md"), "").unwrap();
+        }
+        std::fs::create_dir(temp.path().join("notes")).unwrap();
+
+        let dirs = skill_dirs(temp.path());
+        assert_eq!(dirs, vec![temp.path().join("a"), temp.path().join("b")]);
+    }
+}
@@ -0,0 +1,75 @@
+//! Usage statistics report, for `kimi stats` and `/stats`
+
+use anyhow::Result;
+use chrono::{Duration, Local, NaiveDate};
+
+use kimi_core::stats::{ModelUsage, UsageStats, UsageStore};
+
+use crate::ui::theme::theme;
+
+/// Print the usage of the last `days` days, or of all recorded days
+pub async fn execute(days: Option<u32>) -> Result<()> {
+    let stats = UsageStore::new(UsageStore::default_path()).load()?;
+    print_report(&stats, days);
+    Ok(())
+}
+
+/// Print usage per day, per model and in total
+pub fn print_report(stats: &UsageStats, days: Option<u32>) {
+    let since = days.map(|days| Local::now().date_naive() - Duration::days(i64::from(days.max(1)) - 1));
+    let by_day = stats.by_day(since);
+    if by_day.is_empty() {
+        println!("No usage recorded{}.", period(since));
+        return;
+    }
+
+    println!("\n{}", theme().heading.paint(format!("Usage{}", period(since))));
+    print_header("Day");
+    for (day, usage) in &by_day {
+        print_row(day, usage);
+    }
+
+    println!();
+    print_header("Model");
+    for (model, usage) in stats.by_model(since) {
+        print_row(&model, &usage);
+    }
+
+    println!();
+    print_row("Total", &stats.total(since));
+    println!(
+        "\n{}",
+        theme()
+            .muted
+            .paint("Token counts are estimated where the provider reports none; cost is shown for models with configured pricing.")
+    );
+}
+
+fn period(since: Option<NaiveDate>) -> String {
+    match since {
+        Some(day) => format!(" since {}", day.format("%Y-%m-%d")),
+        None => String::new(),
+    }
+}
+
+fn print_header(first: &str) {
+    println!(
+        "{}",
+        theme().emphasis.paint(format!(
+            "  {:<24} {:>7} {:>13} {:>13} {:>10} {:>10}",
+            first, "Turns", "Input tokens", "Output tokens", "Tool calls", "Cost"
+        ))
+    );
+}
+
+fn print_row(label: &str, usage: &ModelUsage) {
+    println!(
+        "  {:<24} {:>7} {:>13} {:>13} {:>10} {:>10}",
+        label,
+        usage.turns,
+        usage.input_tokens,
+        usage.output_tokens,
+        usage.tool_calls,
+        format!("${:.2}", usage.cost)
+    );
+}
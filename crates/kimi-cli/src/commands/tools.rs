@@ -0,0 +1,176 @@
+//! `kimi tools`: list and describe the tools the agent can call, for
+//! `kimi tools list`, `kimi tools describe` and `/tools`
+
+use anyhow::{bail, Result};
+use nu_ansi_term::Style;
+use serde_json::Value;
+
+use kimi_core::approval::ToolSafety;
+use kimi_core::soul::KimiToolset;
+
+use crate::app::App;
+use crate::cli::ToolsCommands;
+use crate::commands::mcp;
+use crate::ui::theme::theme;
+
+/// Execute tools subcommand
+pub async fn execute(subcommand: ToolsCommands) -> Result<()> {
+    let mut toolset = KimiToolset::new();
+    toolset.register_many(App::create_default_tools(None, None, &Default::default()));
+    let mcp_servers = mcp::load_config().await.unwrap_or_default();
+
+    match subcommand {
+        ToolsCommands::List => {
+            print_list(&toolset);
+            print_mcp_servers(&toolset, &mcp_servers);
+            println!(
+                "\n{}",
+                theme()
+                    .muted
+                    .paint("SearchCodebase is added when [rag] is configured. See `kimi tools describe <name>`.")
+            );
+            Ok(())
+        }
+        ToolsCommands::Describe { name } => describe(&toolset, &mcp_servers, &name),
+    }
+}
+
+/// Print one line per tool: name, safety and the first line of its
+/// description
+pub fn print_list(toolset: &KimiToolset) {
+    let mut names: Vec<&String> = toolset.tool_names().collect();
+    names.sort();
+    println!("\n{}", theme().heading.paint("Tools"));
+    for name in names {
+        let Some(tool) = toolset.get(name) else { continue };
+        let safety = ToolSafety::of(name);
+        println!(
+            "  {:<16} {} {}",
+            theme().emphasis.paint(name),
+            safety_style(safety).paint(format!("{:<13}", safety.as_str())),
+            tool.description().lines().next().unwrap_or("")
+        );
+    }
+}
+
+/// Print the MCP servers' tools, and the configured servers that are not
+/// connected and whose tools are therefore not known
+fn print_mcp_servers(toolset: &KimiToolset, config: &mcp::McpConfig) {
+    let mut names: Vec<&String> = config.servers.iter().filter(|(_, s)| s.enabled).map(|(name, _)| name).collect();
+    names.extend(toolset.mcp_servers().keys().filter(|name| !config.servers.contains_key(*name)));
+    if names.is_empty() {
+        return;
+    }
+    names.sort();
+    println!("\n{}", theme().heading.paint("MCP tools"));
+    for name in names {
+        match toolset.get_mcp_server(name).filter(|server| server.connected) {
+            Some(server) => {
+                for tool in &server.tools {
+                    println!(
+                        "  {:<16} {} from {}",
+                        theme().emphasis.paint(tool),
+                        safety_style(ToolSafety::SideEffects).paint(format!("{:<13}", ToolSafety::SideEffects.as_str())),
+                        name
+                    );
+                }
+            }
+            None => println!(
+                "  {}",
+                theme()
+                    .muted
+                    .paint(format!("{}: not connected; its tools are listed once it is", name))
+            ),
+        }
+    }
+}
+
+/// Print a tool's description, safety, parameters and schema
+fn describe(toolset: &KimiToolset, config: &mcp::McpConfig, name: &str) -> Result<()> {
+    let Some(tool) = toolset.get(name) else {
+        let server = toolset.mcp_servers().values().find(|server| server.tools.iter().any(|tool| tool == name));
+        if let Some(server) = server {
+            println!("{} is provided by the MCP server {}.", name, server.name);
+            println!("Safety: {}", ToolSafety::SideEffects.description());
+            return Ok(());
+        }
+        if !config.servers.is_empty() {
+            bail!("Unknown tool: {}. Tools of MCP servers are known once the servers are connected.", name);
+        }
+        bail!("Unknown tool: {}. See `kimi tools list`.", name);
+    };
+
+    let safety = ToolSafety::of(name);
+    let schema = tool.parameters_schema();
+    println!(
+        "\n{}  {}",
+        theme().heading.paint(name),
+        safety_style(safety).paint(safety.as_str())
+    );
+    println!("\n{}", tool.description().trim());
+    println!("\n{} {}", theme().emphasis.paint("Safety:"), safety.description());
+
+    println!("\n{}", theme().emphasis.paint("Parameters:"));
+    for line in parameter_lines(&schema) {
+        println!("  {}", line);
+    }
+    println!("\n{}", theme().emphasis.paint("Schema:"));
+    println!("{}", serde_json::to_string_pretty(&schema)?);
+    Ok(())
+}
+
+/// One line per parameter of a JSON schema: name, type, whether it is
+/// required and its description
+fn parameter_lines(schema: &Value) -> Vec<String> {
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(Value::as_array)
+        .map(|names| names.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+        return vec!["(none)".to_string()];
+    };
+    properties
+        .iter()
+        .map(|(name, property)| {
+            let kind = property.get("type").and_then(Value::as_str).unwrap_or("any");
+            let mut line = match required.contains(&name.as_str()) {
+                true => format!("{} ({}, required)", name, kind),
+                false => format!("{} ({})", name, kind),
+            };
+            if let Some(description) = property.get("description").and_then(Value::as_str) {
+                line.push_str(&format!(" - {}", description));
+            }
+            line
+        })
+        .collect()
+}
+
+fn safety_style(safety: ToolSafety) -> Style {
+    match safety {
+        ToolSafety::ReadOnly => theme().success,
+        ToolSafety::EditsFiles => theme().warning,
+        ToolSafety::SideEffects => theme().error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parameter_lines() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string", "description": "File to read"},
+                "n_lines": {"type": "integer"}
+            },
+            "required": ["path"]
+        });
+        let mut lines = parameter_lines(&schema);
+        lines.sort();
+        assert_eq!(lines, ["n_lines (integer)", "path (string, required) - File to read"]);
+        assert_eq!(parameter_lines(&serde_json::json!({"type": "object"})), ["(none)"]);
+    }
+}
@@ -0,0 +1,87 @@
+//! `kimi update` and the shell's startup notice of new releases
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use tracing::debug;
+
+use kimi_core::update::{self, UpdateChecker};
+use kimi_core::UpdatesConfig;
+
+use crate::ui::theme::theme;
+
+/// Version of this binary
+const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Check for a newer release and, unless `check_only`, install it in place
+/// of the running binary, asking first unless `yes`
+pub async fn execute(check_only: bool, yes: bool) -> Result<()> {
+    let checker = UpdateChecker::new(UpdateChecker::default_path(), CURRENT_VERSION);
+    let latest = checker.refresh().await?.latest_version;
+    if !update::is_newer(&latest, CURRENT_VERSION) {
+        println!("kimi-cli {} is up to date.", CURRENT_VERSION);
+        return Ok(());
+    }
+    if check_only {
+        println!(
+            "Version {} is available (you have {}). Run {} to install it.",
+            theme().command.paint(&latest),
+            CURRENT_VERSION,
+            theme().command.paint("kimi update")
+        );
+        return Ok(());
+    }
+
+    // self_update downloads with a blocking client and may prompt on stdin
+    let status = tokio::task::spawn_blocking(move || {
+        self_update::backends::github::Update::configure()
+            .repo_owner(update::REPO_OWNER)
+            .repo_name(update::REPO_NAME)
+            .bin_name("kimi-cli")
+            .target(&asset_target())
+            .current_version(CURRENT_VERSION)
+            .show_download_progress(true)
+            .no_confirm(yes)
+            .build()?
+            .update()
+    })
+    .await?
+    .context("Failed to update kimi-cli")?;
+
+    if status.updated() {
+        println!("{}", theme().success.paint(format!("Updated kimi-cli to {}.", status.version())));
+    } else {
+        println!("kimi-cli {} is up to date.", status.version());
+    }
+    Ok(())
+}
+
+/// The part of release asset names naming the platform, such as
+/// `linux-x86_64` in `kimi-cli-linux-x86_64.tar.gz`
+fn asset_target() -> String {
+    format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// A line for the shell's welcome screen when the last check found a newer
+/// release
+///
+/// When the last check is over a day old, a new one is started in the
+/// background; what it finds shows the next time the shell starts.
+pub fn startup_notice(config: &UpdatesConfig) -> Option<String> {
+    if !config.check_enabled() {
+        return None;
+    }
+    let checker = UpdateChecker::new(UpdateChecker::default_path(), CURRENT_VERSION);
+    if checker.is_stale(Utc::now()) {
+        let refresh = checker.clone();
+        tokio::spawn(async move {
+            if let Err(e) = refresh.refresh().await {
+                debug!("Update check failed: {}", e);
+            }
+        });
+    }
+    let latest = checker.available()?;
+    Some(format!(
+        "Version {} is available (you have {}); run `kimi update` to install it.",
+        latest, CURRENT_VERSION
+    ))
+}
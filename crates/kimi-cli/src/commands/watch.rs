@@ -0,0 +1,127 @@
+//! `kimi watch`: re-run an agent task whenever matching files change
+//!
+//! Each run is a print mode turn in a new session, given the instruction
+//! and the changed files. Runs never overlap: changes made while one is in
+//! progress, the agent's own edits included, are discarded rather than
+//! starting another run, so a task that edits the files it watches does not
+//! trigger itself.
+
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use glob::Pattern;
+use kaos_rs::FileWatcher;
+
+use crate::app::{App, AppError};
+use crate::cli::Cli;
+use crate::ui::theme::theme;
+use crate::ui::UIError;
+
+/// Directories whose changes never start a run: version control, kimi's own
+/// sessions and common build output
+const IGNORED_DIRS: &[&str] = &[".git", ".kimi", "target", "node_modules"];
+
+/// Watch the working directory and run `instruction` on each batch of
+/// changed files matching `globs`, or any file if none are given
+pub async fn execute(cli: &Cli, instruction: &str, globs: &[String], debounce: Duration) -> Result<()> {
+    // Events name paths under the canonical directory
+    let work_dir = cli.effective_work_dir().canonicalize()?;
+    let patterns = globs
+        .iter()
+        .map(|glob| Pattern::new(glob).with_context(|| format!("Invalid glob '{}'", glob)))
+        .collect::<Result<Vec<_>>>()?;
+    let mut watcher = FileWatcher::new(&work_dir)?.with_debounce(debounce);
+
+    // Runs are print mode turns, with the approvals that allows
+    let mut cli = cli.clone();
+    cli.print = true;
+
+    let scope = match globs.is_empty() {
+        true => "any file".to_string(),
+        false => globs.join(", "),
+    };
+    eprintln!(
+        "{}",
+        theme().accent.paint(format!("Watching {} in {}; Ctrl+C to stop.", scope, work_dir.display()))
+    );
+
+    loop {
+        let changes = tokio::select! {
+            changes = watcher.changes() => changes,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+        let Some(changes) = changes else {
+            return Ok(());
+        };
+        let changed: Vec<PathBuf> = changes
+            .iter()
+            .filter_map(|path| path.strip_prefix(&work_dir).ok())
+            .filter(|path| is_watched(path, &patterns))
+            .map(Path::to_path_buf)
+            .collect();
+        if changed.is_empty() {
+            continue;
+        }
+
+        eprintln!(
+            "\n{}",
+            theme().heading.paint(format!("{} changed file(s); running the task", changed.len()))
+        );
+        match run(&cli, &task(instruction, &changed)).await {
+            Ok(()) => eprintln!("{}", theme().success.paint("Done; watching for changes.")),
+            Err(AppError::Ui(UIError::Interrupted)) => return Ok(()),
+            Err(e) => eprintln!("{} {}", theme().error.paint("Run failed:"), e),
+        }
+        watcher.clear();
+    }
+}
+
+/// Run the task as one print mode turn
+async fn run(cli: &Cli, task: &str) -> Result<(), AppError> {
+    App::create(cli).await?.run_print(task).await
+}
+
+/// Whether a change to `path`, relative to the working directory, should
+/// start a run
+fn is_watched(path: &Path, patterns: &[Pattern]) -> bool {
+    let ignored = path.components().any(|component| match component {
+        Component::Normal(name) => IGNORED_DIRS.iter().any(|dir| name == *dir),
+        _ => false,
+    });
+    if ignored {
+        return false;
+    }
+    let file_name = path.file_name().map(Path::new).unwrap_or(path);
+    patterns.is_empty()
+        || patterns
+            .iter()
+            .any(|pattern| pattern.matches_path(path) || pattern.matches_path(file_name))
+}
+
+/// The message of a run: the instruction and the files that changed
+fn task(instruction: &str, changed: &[PathBuf]) -> String {
+    let files: Vec<String> = changed.iter().map(|path| format!("- {}", path.display())).collect();
+    format!("{}\n\nFiles changed since the last run:\n{}", instruction, files.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watched_paths() {
+        let patterns = [Pattern::new("*.rs").unwrap(), Pattern::new("docs/**/*.md").unwrap()];
+        assert!(is_watched(Path::new("src/main.rs"), &patterns));
+        assert!(is_watched(Path::new("docs/guide/intro.md"), &patterns));
+        assert!(!is_watched(Path::new("README.md"), &patterns));
+        assert!(!is_watched(Path::new("target/debug/build.rs"), &patterns));
+        assert!(!is_watched(Path::new(".kimi/sessions/abc/context.jsonl"), &[]));
+        assert!(is_watched(Path::new("Cargo.toml"), &[]));
+
+        assert_eq!(
+            task("Fix lints", &[PathBuf::from("src/lib.rs")]),
+            "Fix lints\n\nFiles changed since the last run:\n- src/lib.rs"
+        );
+    }
+}
@@ -0,0 +1,87 @@
+//! Process exit codes
+//!
+//! `kimi` exits with a code that tells scripts why a run failed, so a
+//! Makefile can, say, retry on a provider error but not on a denied tool.
+
+use kimi_core::llm::LlmError;
+use kimi_core::soul::SoulError;
+
+use crate::app::AppError;
+use crate::ui::UIError;
+
+/// Why `kimi` exited
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitCode {
+    Success = 0,
+    /// Any failure without a code of its own, e.g. an invalid config
+    Error = 1,
+    /// Invalid command line, as reported by the argument parser
+    Usage = 2,
+    /// The model provider failed or could not be set up
+    Provider = 3,
+    /// A tool call needed approval no one could give
+    ToolDenied = 4,
+    /// The turn ran into a limit of `loop_control`
+    BudgetExceeded = 5,
+    /// Stopped by Ctrl+C
+    Interrupted = 130,
+}
+
+impl ExitCode {
+    /// The code for a run that failed with `error`
+    pub fn for_error(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<SoulError>() {
+                return Self::for_turn(e);
+            }
+            if let Some(e) = cause.downcast_ref::<UIError>() {
+                match e {
+                    UIError::Turn(e) => return Self::for_turn(e),
+                    UIError::Provider(_) => return Self::Provider,
+                    UIError::ToolDenied(_) => return Self::ToolDenied,
+                    UIError::Interrupted => return Self::Interrupted,
+                    _ => {}
+                }
+            }
+            if matches!(cause.downcast_ref::<AppError>(), Some(AppError::Provider(_)))
+                || cause.downcast_ref::<LlmError>().is_some()
+            {
+                return Self::Provider;
+            }
+        }
+        Self::Error
+    }
+
+    fn for_turn(error: &SoulError) -> Self {
+        match error {
+            SoulError::Provider(_) | SoulError::Llm(_) => Self::Provider,
+            SoulError::LimitReached(_) | SoulError::MaxIterations | SoulError::Timeout => Self::BudgetExceeded,
+            SoulError::Cancelled => Self::Interrupted,
+            _ => Self::Error,
+        }
+    }
+
+    /// The code as passed to [`std::process::exit`]
+    pub fn code(self) -> i32 {
+        self as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exit_codes() {
+        let error = anyhow::Error::from(AppError::Ui(UIError::Turn(SoulError::MaxIterations)));
+        assert_eq!(ExitCode::for_error(&error), ExitCode::BudgetExceeded);
+        let error = anyhow::Error::from(AppError::Soul(SoulError::Llm("overloaded".into())));
+        assert_eq!(ExitCode::for_error(&error), ExitCode::Provider);
+        let error = anyhow::Error::from(AppError::Ui(UIError::ToolDenied(2)));
+        assert_eq!(ExitCode::for_error(&error).code(), 4);
+        let error = anyhow::Error::from(UIError::Interrupted).context("print mode");
+        assert_eq!(ExitCode::for_error(&error).code(), 130);
+        let error = anyhow::anyhow!("No input provided");
+        assert_eq!(ExitCode::for_error(&error), ExitCode::Error);
+    }
+}
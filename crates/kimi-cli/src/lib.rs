@@ -6,6 +6,9 @@
 pub mod app;
 pub mod cli;
 pub mod commands;
+pub mod exit_code;
+pub mod telemetry;
 pub mod ui;
 
-pub use cli::{Cli, Commands, McpCommands};
+pub use cli::{Cli, Commands, McpCommands, SkillCommands};
+pub use exit_code::ExitCode;
@@ -1,34 +1,48 @@
 use std::process;
 
 use anyhow::Result;
-use tracing::{error, info};
+use tracing::info;
 
 use clap::Parser;
-use kimi_cli::{Cli, Commands};
+use kimi_cli::{Cli, Commands, ExitCode};
 use kimi_cli::app::App;
+use kimi_cli::telemetry::{otel_layer, TelemetryGuard};
+use kimi_cli::ui::theme::{self, Theme};
+use kimi_core::TelemetryConfig;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
-        error!("Error: {:#}", e);
-        process::exit(1);
+        eprintln!("Error: {:#}", e);
+        process::exit(ExitCode::for_error(&e).code());
     }
 }
 
 async fn run() -> Result<()> {
     // Parse CLI arguments
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
-    // Initialize logging
-    init_logging(cli.verbose);
+    // Initialize logging; in server mode stdout carries the protocol, and
+    // quiet runs keep it for the response alone. Spans are also exported if
+    // the config sets up telemetry.
+    let config = kimi_core::config::load_session_config(cli.config_file.as_deref(), &cli.effective_work_dir()).ok();
+    let telemetry = config.as_ref().map(|config| config.telemetry.clone()).unwrap_or_default();
+    let _telemetry = init_logging(cli.verbose, cli.quiet, cli.server || cli.quiet, &telemetry);
 
     info!("Starting kimi-cli v{}", env!("CARGO_PKG_VERSION"));
 
+    let theme = config.map(|config| config.theme).unwrap_or_default();
+    theme::init(Theme::from_config(&theme, cli.color_enabled()));
+
     // Handle subcommands first
-    if let Some(command) = cli.command {
+    let work_dir = cli.effective_work_dir();
+    if let Some(command) = cli.command.take() {
         match command {
-            Commands::Login => {
-                kimi_cli::commands::login::execute(true).await?;
+            Commands::Login { platform, account } => {
+                kimi_cli::commands::login::execute(true, platform.as_deref(), account.as_deref()).await?;
                 return Ok(());
             }
             Commands::Setup => {
@@ -39,6 +53,42 @@ async fn run() -> Result<()> {
                 kimi_cli::commands::mcp::execute(subcommand).await?;
                 return Ok(());
             }
+            Commands::Skill { subcommand } => {
+                kimi_cli::commands::skill::execute(subcommand, &work_dir).await?;
+                return Ok(());
+            }
+            Commands::Config { subcommand } => {
+                kimi_cli::commands::config::execute(subcommand, cli.config_file.as_deref()).await?;
+                return Ok(());
+            }
+            Commands::Secret { subcommand } => {
+                kimi_cli::commands::secret::execute(subcommand).await?;
+                return Ok(());
+            }
+            Commands::Resume { session } => {
+                // Open the shell on the picked session
+                match kimi_cli::commands::resume::select(&work_dir, session.as_deref())? {
+                    Some(id) => cli.session = Some(id),
+                    None => return Ok(()),
+                }
+            }
+            Commands::Stats { days } => {
+                kimi_cli::commands::stats::execute(days).await?;
+                return Ok(());
+            }
+            Commands::Tools { subcommand } => {
+                kimi_cli::commands::tools::execute(subcommand).await?;
+                return Ok(());
+            }
+            Commands::Watch { prompt, globs, debounce } => {
+                let debounce = std::time::Duration::from_millis(debounce);
+                kimi_cli::commands::watch::execute(&cli, &prompt, &globs, debounce).await?;
+                return Ok(());
+            }
+            Commands::Update { check, yes } => {
+                kimi_cli::commands::update::execute(check, yes).await?;
+                return Ok(());
+            }
         }
     }
 
@@ -46,7 +96,10 @@ async fn run() -> Result<()> {
     let app = App::create(&cli).await?;
 
     // Run based on mode
-    if cli.print {
+    if cli.server {
+        // Server mode - JSON lines on stdin/stdout for other frontends
+        app.run_server().await?;
+    } else if cli.print {
         // Print mode - non-interactive output
         if let Some(ref prompt) = cli.prompt {
             app.run_print(prompt).await?;
@@ -76,21 +129,43 @@ async fn run() -> Result<()> {
     Ok(())
 }
 
-fn init_logging(verbose: bool) {
+fn init_logging(verbose: bool, quiet: bool, to_stderr: bool, telemetry: &TelemetryConfig) -> Option<TelemetryGuard> {
     let filter = if verbose {
         "debug"
+    } else if quiet {
+        "error"
     } else {
         "info"
     };
 
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(filter)),
-        )
+    let writer = if to_stderr {
+        BoxMakeWriter::new(std::io::stderr)
+    } else {
+        BoxMakeWriter::new(std::io::stdout)
+    };
+    let fmt_layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
         .with_target(false)
         .with_thread_ids(false)
         .with_file(false)
-        .with_line_number(false)
+        .with_line_number(false);
+
+    let (otel_layer, guard) = match otel_layer(telemetry) {
+        Ok(Some((layer, guard))) => (Some(layer), Some(guard)),
+        Ok(None) => (None, None),
+        Err(e) => {
+            eprintln!("Failed to set up trace export: {}", e);
+            (None, None)
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(filter)),
+        )
+        .with(fmt_layer)
+        .with(otel_layer)
         .init();
+    guard
 }
@@ -0,0 +1,79 @@
+//! OpenTelemetry export
+//!
+//! When `[telemetry] otlp_endpoint` is set in the config, the turn, step,
+//! LLM request and tool spans recorded by kimi-core (see
+//! [`kimi_core::telemetry`]) are exported in batches over OTLP/HTTP, next to
+//! the usual log output.
+
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use tracing::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use kimi_core::TelemetryConfig;
+
+/// Service name reported when the config does not set one
+pub const DEFAULT_SERVICE_NAME: &str = "kimi-cli";
+
+/// Keeps span export running; dropping it flushes the spans still queued
+pub struct TelemetryGuard {
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush traces: {}", e);
+        }
+    }
+}
+
+/// Build a layer exporting spans as `config` describes
+///
+/// Returns `Ok(None)` when no OTLP endpoint is configured.
+pub fn otel_layer<S>(
+    config: &TelemetryConfig,
+) -> Result<Option<(impl Layer<S>, TelemetryGuard)>, opentelemetry_otlp::ExporterBuildError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let Some(endpoint) = &config.otlp_endpoint else {
+        return Ok(None);
+    };
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(endpoint.clone())
+        .build()?;
+    let service_name = config
+        .service_name
+        .clone()
+        .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(Resource::builder().with_service_name(service_name).build())
+        .build();
+
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME));
+    Ok(Some((layer, TelemetryGuard { provider })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::Registry;
+
+    #[test]
+    fn test_otel_layer_only_with_endpoint() {
+        let config = TelemetryConfig::default();
+        assert!(otel_layer::<Registry>(&config).unwrap().is_none());
+
+        let config = TelemetryConfig {
+            otlp_endpoint: Some("http://127.0.0.1:4318/v1/traces".to_string()),
+            service_name: Some("kimi-test".to_string()),
+        };
+        assert!(otel_layer::<Registry>(&config).unwrap().is_some());
+    }
+}
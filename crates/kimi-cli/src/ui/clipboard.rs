@@ -0,0 +1,124 @@
+//! Copying text to the system clipboard, for `/copy`
+//!
+//! The platform's clipboard command is used when there is one: `pbcopy`,
+//! `wl-copy`, `xclip`, `xsel` or `clip`. Over SSH, or when none of them
+//! works, the text is written to the terminal as an OSC 52 escape sequence,
+//! which terminals that support it put on the clipboard of the machine the
+//! user sits at.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use base64::Engine;
+
+/// How text was put on the clipboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// With the named clipboard command
+    Command(&'static str),
+    /// With an OSC 52 escape sequence, which the terminal may ignore
+    Osc52,
+}
+
+/// Put `text` on the clipboard
+pub fn copy(text: &str) -> std::io::Result<Method> {
+    let remote = std::env::var_os("SSH_TTY").is_some() || std::env::var_os("SSH_CONNECTION").is_some();
+    if !remote {
+        for (program, args) in commands() {
+            if run(program, args, text) {
+                return Ok(Method::Command(program));
+            }
+        }
+    }
+    let mut stdout = std::io::stdout();
+    stdout.write_all(osc52(text, std::env::var_os("TMUX").is_some()).as_bytes())?;
+    stdout.flush()?;
+    Ok(Method::Osc52)
+}
+
+/// Clipboard commands to try on this platform, most specific first
+fn commands() -> Vec<(&'static str, &'static [&'static str])> {
+    if cfg!(target_os = "macos") {
+        return vec![("pbcopy", &[])];
+    }
+    if cfg!(windows) {
+        return vec![("clip", &[])];
+    }
+    let mut commands: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        commands.push(("wl-copy", &[]));
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        commands.push(("xclip", &["-selection", "clipboard"]));
+        commands.push(("xsel", &["--clipboard", "--input"]));
+    }
+    commands
+}
+
+/// Pipe `text` into `program`, returning whether it succeeded
+fn run(program: &str, args: &[&str], text: &str) -> bool {
+    let child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let Ok(mut child) = child else {
+        return false;
+    };
+    let written = child.stdin.take().is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+    // Closing stdin above lets the command finish
+    child.wait().is_ok_and(|status| status.success()) && written
+}
+
+/// The OSC 52 sequence setting the clipboard to `text`, wrapped for tmux
+/// to pass it on to the terminal
+fn osc52(text: &str, tmux: bool) -> String {
+    let sequence = format!("\x1b]52;c;{}\x07", base64::engine::general_purpose::STANDARD.encode(text));
+    match tmux {
+        true => format!("\x1bPtmux;\x1b{}\x1b\\", sequence),
+        false => sequence,
+    }
+}
+
+/// The contents of the fenced code blocks in a markdown text
+pub fn code_blocks(markdown: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut fence: Option<(&str, Vec<&str>)> = None;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        match &mut fence {
+            Some((marker, lines)) => {
+                if trimmed.starts_with(*marker) && trimmed.trim_end().chars().all(|c| marker.contains(c)) {
+                    blocks.push(lines.join("\n"));
+                    fence = None;
+                } else {
+                    lines.push(line);
+                }
+            }
+            None if trimmed.starts_with("```") => fence = Some(("```", Vec::new())),
+            None if trimmed.starts_with("~~~") => fence = Some(("~~~", Vec::new())),
+            None => {}
+        }
+    }
+    // An unclosed block runs to the end of the text
+    if let Some((_, lines)) = fence {
+        blocks.push(lines.join("\n"));
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_code_blocks_and_osc52() {
+        let reply = "Run this:\n\n```bash\ncargo test\ncargo clippy\n```\n\nThen:\n~~~\nmake\n~~~\n\n```rust\nfn main() {}";
+        assert_eq!(code_blocks(reply), ["cargo test\ncargo clippy", "make", "fn main() {}"]);
+        assert!(code_blocks("No code here").is_empty());
+
+        assert_eq!(osc52("hi", false), "\x1b]52;c;aGk=\x07");
+        assert_eq!(osc52("hi", true), "\x1bPtmux;\x1b\x1b]52;c;aGk=\x07\x1b\\");
+    }
+}
@@ -0,0 +1,135 @@
+//! Colored rendering of unified diffs
+
+use std::ops::Range;
+
+use nu_ansi_term::Style;
+
+use crate::ui::theme::theme;
+
+/// Color a unified diff for the terminal with the theme's diff colors,
+/// file headers bold
+///
+/// Where removed lines are replaced by as many added ones, each pair also
+/// has the part that changed highlighted, leaving their common start and
+/// end plain.
+pub fn render_diff(diff: &str, color: bool) -> String {
+    if !color {
+        return diff.to_string();
+    }
+    let lines: Vec<&str> = diff.lines().collect();
+    let mut out = String::with_capacity(diff.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let removed = lines[i..].iter().take_while(|line| is_removed(line)).count();
+        let added = lines[i + removed..].iter().take_while(|line| is_added(line)).count();
+        if removed > 0 && removed == added {
+            let (old, new) = lines[i..i + 2 * removed].split_at(removed);
+            let spans: Vec<_> = old.iter().zip(new).map(|(old, new)| changed_spans(old, new)).collect();
+            for (line, (span, _)) in old.iter().zip(&spans) {
+                push_highlighted(&mut out, line, span, theme().diff_removed);
+            }
+            for (line, (_, span)) in new.iter().zip(&spans) {
+                push_highlighted(&mut out, line, span, theme().diff_added);
+            }
+            i += 2 * removed;
+            continue;
+        }
+
+        let line = lines[i];
+        let style = if line.starts_with("+++") || line.starts_with("---") {
+            Style::new().bold()
+        } else if line.starts_with('+') {
+            theme().diff_added
+        } else if line.starts_with('-') {
+            theme().diff_removed
+        } else if line.starts_with("@@") {
+            theme().diff_hunk
+        } else {
+            Style::new()
+        };
+        out.push_str(&style.paint(line).to_string());
+        out.push('\n');
+        i += 1;
+    }
+    out
+}
+
+fn is_removed(line: &str) -> bool {
+    line.starts_with('-') && !line.starts_with("---")
+}
+
+fn is_added(line: &str) -> bool {
+    line.starts_with('+') && !line.starts_with("+++")
+}
+
+/// Byte ranges of what differs between two diff lines, after their `-` and
+/// `+` markers and without their common start and end; `None` when they
+/// have nothing in common, so highlighting would only add noise, or for
+/// a side where nothing changed
+fn changed_spans(old: &str, new: &str) -> (Option<Range<usize>>, Option<Range<usize>>) {
+    let (old_text, new_text) = (&old[1..], &new[1..]);
+    let prefix: usize = old_text
+        .chars()
+        .zip(new_text.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    let suffix: usize = old_text[prefix..]
+        .chars()
+        .rev()
+        .zip(new_text[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum();
+    if prefix + suffix == 0 {
+        return (None, None);
+    }
+    let span = |text: &str| Some(1 + prefix..1 + text.len() - suffix).filter(|span| !span.is_empty());
+    (span(old_text), span(new_text))
+}
+
+/// Push `line` in `style`, with `span` of it highlighted
+fn push_highlighted(out: &mut String, line: &str, span: &Option<Range<usize>>, style: Style) {
+    match span {
+        Some(span) => {
+            out.push_str(&style.paint(&line[..span.start]).to_string());
+            out.push_str(&style.reverse().paint(&line[span.clone()]).to_string());
+            out.push_str(&style.paint(&line[span.end..]).to_string());
+        }
+        None => out.push_str(&style.paint(line).to_string()),
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nu_ansi_term::Color;
+
+    #[test]
+    fn test_render_diff() {
+        let diff = "--- a/x\n+++ b/x\n@@ -1,1 +1,1 @@\n-old\n+new\n";
+        assert_eq!(render_diff(diff, false), diff);
+        let colored = render_diff(diff, true);
+        assert!(colored.contains(&Color::Red.paint("-old").to_string()));
+        assert!(colored.contains(&Color::Green.paint("+new").to_string()));
+    }
+
+    #[test]
+    fn test_changed_part_is_highlighted() {
+        let diff = "@@ -1,2 +1,2 @@\n-let x = 1;\n-fn main() {}\n+let x = 2;\n+fn main() {}\n";
+        let colored = render_diff(diff, true);
+        let removed = Color::Red.normal();
+        assert!(colored.contains(&format!(
+            "{}{}{}",
+            removed.paint("-let x = "),
+            removed.reverse().paint("1"),
+            removed.paint(";")
+        )));
+        assert!(colored.contains(&Color::Green.normal().reverse().paint("2").to_string()));
+        // Unchanged pairs and unrelated lines are not highlighted
+        assert!(colored.contains(&removed.paint("-fn main() {}").to_string()));
+        assert_eq!(changed_spans("-abc", "+xyz"), (None, None));
+        assert_eq!(changed_spans("-héllo", "+hello"), (Some(2..4), Some(2..3)));
+    }
+}
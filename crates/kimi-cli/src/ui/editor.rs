@@ -0,0 +1,56 @@
+//! Editing text in the user's external editor
+
+use std::io;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Editor used when neither `VISUAL` nor `EDITOR` is set
+const DEFAULT_EDITOR: &str = "vi";
+
+/// The user's editor: `$VISUAL`, `$EDITOR` or `vi`
+///
+/// The command may include arguments, e.g. `code --wait`.
+pub fn editor() -> String {
+    std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// A command running `editor`, with its arguments split on whitespace
+pub fn command(editor: &str) -> Command {
+    let mut parts = editor.split_whitespace();
+    let mut command = Command::new(parts.next().unwrap_or(DEFAULT_EDITOR));
+    command.args(parts);
+    command
+}
+
+/// File the prompt is edited in with Ctrl+G, one per shell process
+pub fn prompt_file() -> PathBuf {
+    std::env::temp_dir().join(format!("kimi-prompt-{}.md", std::process::id()))
+}
+
+/// Open `text` in the user's [`editor`] and return what the user saved
+///
+/// The text is put in a temporary file named with `extension`, so editors
+/// pick the right syntax highlighting.
+pub fn edit_text(text: &str, extension: Option<&str>) -> io::Result<String> {
+    let editor = editor();
+    let mut name = format!("kimi-edit-{}", uuid::Uuid::new_v4());
+    if let Some(extension) = extension {
+        name.push('.');
+        name.push_str(extension);
+    }
+    let path = std::env::temp_dir().join(name);
+    std::fs::write(&path, text)?;
+
+    let status = command(&editor).arg(&path).status();
+    let result = match status {
+        Ok(status) if status.success() => std::fs::read_to_string(&path),
+        Ok(status) => Err(io::Error::other(format!("{} exited with {}", editor, status))),
+        Err(e) => Err(io::Error::new(e.kind(), format!("Failed to run {}: {}", editor, e))),
+    };
+    let _ = std::fs::remove_file(&path);
+    result
+}
@@ -0,0 +1,300 @@
+//! Terminal rendering of streamed markdown
+//!
+//! Assistant text arrives in arbitrary chunks, so [`MarkdownRenderer`]
+//! renders it a line at a time: headings, list items, block quotes, rules,
+//! fenced code and inline `code`, **bold**, *italic* and links. Table rows
+//! are held back until the table ends so the columns can be aligned. With
+//! formatting disabled the text passes through unchanged as it streams.
+
+use nu_ansi_term::Style;
+
+use crate::ui::theme::theme;
+
+/// Renders markdown chunks into styled terminal text
+#[derive(Debug, Default)]
+pub struct MarkdownRenderer {
+    enabled: bool,
+    /// Text after the last complete line
+    pending: String,
+    /// Inside a fenced code block
+    in_code: bool,
+    /// Rows of the table being read
+    table: Vec<String>,
+}
+
+impl MarkdownRenderer {
+    /// A renderer; a disabled one passes text through unchanged
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    /// Add a chunk, returning the output for the lines it completes
+    pub fn push(&mut self, chunk: &str) -> String {
+        if !self.enabled {
+            return chunk.to_string();
+        }
+        self.pending.push_str(chunk);
+        let mut out = String::new();
+        while let Some(end) = self.pending.find('\n') {
+            let line: String = self.pending.drain(..=end).collect();
+            self.render_line(line.trim_end_matches(['\n', '\r']), &mut out);
+        }
+        out
+    }
+
+    /// Render whatever is still held back, e.g. at the end of a reply
+    pub fn finish(&mut self) -> String {
+        let mut out = String::new();
+        if !self.pending.is_empty() {
+            let line = std::mem::take(&mut self.pending);
+            self.render_line(&line, &mut out);
+            // The line had no newline of its own
+            out.pop();
+        }
+        self.flush_table(&mut out);
+        self.in_code = false;
+        out
+    }
+
+    fn render_line(&mut self, line: &str, out: &mut String) {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            self.flush_table(out);
+            self.in_code = !self.in_code;
+            let language = trimmed.trim_start_matches(['`', '~']).trim();
+            if self.in_code && !language.is_empty() {
+                out.push_str(&format!("{}\n", theme().muted.paint(language)));
+            }
+            return;
+        }
+        if self.in_code {
+            out.push_str(&format!("    {}\n", theme().code.paint(line)));
+            return;
+        }
+
+        if trimmed.starts_with('|') {
+            self.table.push(trimmed.to_string());
+            return;
+        }
+        self.flush_table(out);
+
+        if let Some((level, text)) = heading(trimmed) {
+            let style = match level {
+                1 => theme().heading.underline(),
+                2 => theme().heading,
+                _ => Style::new().bold(),
+            };
+            out.push_str(&format!("{}\n", style.paint(strip_inline(text))));
+        } else if is_rule(trimmed) {
+            out.push_str(&format!("{}\n", theme().muted.paint("─".repeat(40))));
+        } else if let Some(quote) = trimmed.strip_prefix('>') {
+            out.push_str(&format!(
+                "{} {}\n",
+                theme().muted.paint("│"),
+                Style::new().italic().paint(strip_inline(quote.trim_start()))
+            ));
+        } else if let Some(item) = ["- ", "* ", "+ "].iter().find_map(|bullet| trimmed.strip_prefix(bullet)) {
+            let indent = &line[..line.len() - trimmed.len()];
+            out.push_str(&format!("{}  • {}\n", indent, render_inline(item)));
+        } else {
+            out.push_str(&format!("{}\n", render_inline(line)));
+        }
+    }
+
+    /// Render the held back table rows with aligned columns
+    fn flush_table(&mut self, out: &mut String) {
+        if self.table.is_empty() {
+            return;
+        }
+        let rows: Vec<Vec<&str>> = self
+            .table
+            .iter()
+            .filter(|row| !is_separator_row(row))
+            .map(|row| split_row(row))
+            .collect();
+        let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let mut widths = vec![0; columns];
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(strip_inline(cell).chars().count());
+            }
+        }
+
+        for (index, row) in rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, width)| {
+                    let cell = row.get(column).copied().unwrap_or("");
+                    let padding = " ".repeat(width - strip_inline(cell).chars().count());
+                    let text = if index == 0 {
+                        Style::new().bold().paint(strip_inline(cell)).to_string()
+                    } else {
+                        render_inline(cell)
+                    };
+                    format!("{}{}", text, padding)
+                })
+                .collect();
+            out.push_str(&format!("  {}\n", cells.join("  │  ")));
+            if index == 0 && rows.len() > 1 {
+                let rule: Vec<String> = widths.iter().map(|width| "─".repeat(*width)).collect();
+                out.push_str(&format!("  {}\n", theme().muted.paint(rule.join("──┼──"))));
+            }
+        }
+        self.table.clear();
+    }
+}
+
+/// Level and text of an ATX heading
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let text = line[level..].strip_prefix(' ')?;
+    (1..=6).contains(&level).then(|| (level, text.trim_end_matches('#').trim()))
+}
+
+fn is_rule(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3
+        && ['-', '*', '_']
+            .iter()
+            .any(|&c| line.chars().all(|l| l == c || l == ' ') && line.starts_with(c))
+}
+
+fn is_separator_row(row: &str) -> bool {
+    row.chars().all(|c| matches!(c, '|' | '-' | ':' | ' ')) && row.contains('-')
+}
+
+fn split_row(row: &str) -> Vec<&str> {
+    let row = row.trim();
+    let row = row.strip_prefix('|').unwrap_or(row);
+    let row = row.strip_suffix('|').unwrap_or(row);
+    row.split('|').map(str::trim).collect()
+}
+
+/// Inline markup of a span of text
+enum Span<'a> {
+    Text(&'a str),
+    Code(&'a str),
+    Bold(&'a str),
+    Italic(&'a str),
+    Link(&'a str, &'a str),
+}
+
+/// Split `text` into plain text and inline markup
+fn spans(text: &str) -> Vec<Span<'_>> {
+    let mut spans = Vec::new();
+    let mut rest = text;
+    let mut plain = 0;
+    while plain < rest.len() {
+        let tail = &rest[plain..];
+        let found = if let Some(inner) = tail.strip_prefix('`') {
+            inner.find('`').map(|end| (Span::Code(&inner[..end]), end + 2))
+        } else if let Some(inner) = tail.strip_prefix("**") {
+            inner.find("**").filter(|&end| end > 0).map(|end| (Span::Bold(&inner[..end]), end + 4))
+        } else if let Some(inner) = tail.strip_prefix('*').filter(|inner| !inner.starts_with([' ', '*'])) {
+            inner.find('*').filter(|&end| end > 0).map(|end| (Span::Italic(&inner[..end]), end + 2))
+        } else if let Some(inner) = tail.strip_prefix('[') {
+            inner.find("](").and_then(|mid| {
+                let url = &inner[mid + 2..];
+                url.find(')').map(|end| (Span::Link(&inner[..mid], &url[..end]), mid + end + 4))
+            })
+        } else {
+            None
+        };
+        match found {
+            Some((span, len)) => {
+                if plain > 0 {
+                    spans.push(Span::Text(&rest[..plain]));
+                }
+                spans.push(span);
+                rest = &tail[len..];
+                plain = 0;
+            }
+            None => plain += tail.chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    if !rest.is_empty() {
+        spans.push(Span::Text(rest));
+    }
+    spans
+}
+
+/// Style the inline markup of `text`
+fn render_inline(text: &str) -> String {
+    spans(text)
+        .into_iter()
+        .map(|span| match span {
+            Span::Text(text) => text.to_string(),
+            Span::Code(code) => theme().code.paint(code).to_string(),
+            Span::Bold(text) => Style::new().bold().paint(text).to_string(),
+            Span::Italic(text) => Style::new().italic().paint(text).to_string(),
+            Span::Link(text, url) if text == url => theme().link.paint(url).to_string(),
+            Span::Link(text, url) => format!(
+                "{} {}",
+                Style::new().underline().paint(text),
+                theme().muted.paint(format!("({})", url))
+            ),
+        })
+        .collect()
+}
+
+/// `text` without its inline markup, as [`render_inline`] shows it
+fn strip_inline(text: &str) -> String {
+    spans(text)
+        .into_iter()
+        .map(|span| match span {
+            Span::Text(text) | Span::Code(text) | Span::Bold(text) | Span::Italic(text) => text.to_string(),
+            Span::Link(text, url) if text == url => url.to_string(),
+            Span::Link(text, url) => format!("{} ({})", text, url),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(text: &str) -> String {
+        let mut out = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_render_streamed_markdown() {
+        let mut renderer = MarkdownRenderer::new(true);
+        let mut out = String::new();
+        for chunk in ["# Plan\n\nUse `cargo", " test` and **check**:\n- one\n", "```rust\nfn main", "() {}\n```\n"] {
+            out.push_str(&renderer.push(chunk));
+        }
+        out.push_str(&renderer.push("| Name | Size |\n|---|---|\n| `a.rs` | 10 |\n"));
+        assert!(!plain(&out).contains("Name"), "tables wait for their end");
+        out.push_str(&renderer.push("See [docs](https://example.com)"));
+        out.push_str(&renderer.finish());
+
+        assert_eq!(
+            plain(&out),
+            "Plan\n\nUse cargo test and check:\n  • one\nrust\n    fn main() {}\n  \
+             Name  │  Size\n  ──────┼──────\n  a.rs  │  10  \nSee docs (https://example.com)"
+        );
+        assert!(out.contains("\x1b["));
+    }
+
+    #[test]
+    fn test_disabled_passes_text_through() {
+        let mut renderer = MarkdownRenderer::new(false);
+        assert_eq!(renderer.push("# Title\n**partial"), "# Title\n**partial");
+        assert_eq!(renderer.finish(), "");
+    }
+}
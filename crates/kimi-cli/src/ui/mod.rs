@@ -3,12 +3,29 @@
 //! Provides different user interface modes:
 //! - ShellUI: Interactive shell with readline support
 //! - PrintUI: Non-interactive mode for scripts and automation
+//! - ServerUI: Headless JSON-lines protocol on stdin/stdout for other frontends,
+//!   or over a token-authenticated WebSocket with `--listen`
 
+mod clipboard;
+mod diff;
+pub(crate) mod editor;
+mod markdown;
+mod model_picker;
+mod notify;
 mod print;
+mod server;
 mod shell;
+mod status;
+pub mod theme;
+#[cfg(test)]
+mod testing;
+mod websocket;
 
 pub use print::PrintUI;
+pub use server::{serve, ServerUI};
 pub use shell::ShellUI;
+pub(crate) use model_picker::fuzzy_score;
+pub use websocket::serve_websocket;
 
 use thiserror::Error;
 
@@ -24,6 +41,15 @@ pub enum UIError {
     #[error("Core error: {0}")]
     Core(String),
 
+    #[error("Provider error: {0}")]
+    Provider(String),
+
+    #[error(transparent)]
+    Turn(#[from] kimi_core::soul::SoulError),
+
+    #[error("{0} tool call(s) denied; allow them with --allowed-tools, --mode or --yolo")]
+    ToolDenied(usize),
+
     #[error("User interrupted")]
     Interrupted,
 
@@ -0,0 +1,173 @@
+//! Fuzzy model selection for `/model`
+//!
+//! Models are matched on their config key, model name and provider, with
+//! the query's characters appearing in order but not necessarily together,
+//! so `/model k2` or `/model turbo` finds a model without its exact key.
+
+use kimi_core::config::Config;
+use kimi_core::llm::model_capabilities;
+use kosong_rs::ModelCapability;
+
+use crate::ui::theme::theme;
+
+/// A configured model as the picker lists it
+#[derive(Debug, Clone)]
+pub struct ModelEntry {
+    /// Key of the model in the config
+    pub key: String,
+    /// Model name sent to the provider
+    pub name: String,
+    pub provider: String,
+    /// Context size, if configured
+    pub max_tokens: Option<usize>,
+    pub capabilities: Vec<ModelCapability>,
+}
+
+impl ModelEntry {
+    /// All configured models, in key order
+    pub fn from_config(config: &Config) -> Vec<Self> {
+        let mut entries: Vec<Self> = config
+            .models
+            .iter()
+            .map(|(key, model)| Self {
+                key: key.clone(),
+                name: model.name.clone(),
+                provider: model.provider.clone(),
+                max_tokens: model.max_tokens,
+                capabilities: model_capabilities(config, key),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.key.cmp(&b.key));
+        entries
+    }
+
+    /// How well `query` matches the key, name or provider
+    fn score(&self, query: &str) -> Option<i64> {
+        [&self.key, &self.name, &self.provider]
+            .into_iter()
+            .filter_map(|text| fuzzy_score(query, text))
+            .max()
+    }
+
+    /// One line of the picker, numbered from 1
+    pub fn format(&self, number: usize, current: bool) -> String {
+        let marker = if current {
+            theme().success.paint(" (current)").to_string()
+        } else {
+            String::new()
+        };
+        let context = match self.max_tokens {
+            Some(tokens) if tokens >= 1000 => format!("{}k context", tokens / 1000),
+            Some(tokens) => format!("{} context", tokens),
+            None => "context not set".to_string(),
+        };
+        // Every model streams and calls tools; only the rest tells them apart
+        let capabilities: Vec<&str> = self
+            .capabilities
+            .iter()
+            .filter(|c| !matches!(c, ModelCapability::Streaming | ModelCapability::ToolCalling))
+            .map(ModelCapability::as_str)
+            .collect();
+        let mut details = vec![self.provider.clone(), context];
+        if self.name != self.key {
+            details.insert(0, self.name.clone());
+        }
+        if !capabilities.is_empty() {
+            details.push(capabilities.join(", "));
+        }
+        format!(
+            "  {:>2}. {}{}\n      {}",
+            number,
+            theme().emphasis.paint(&self.key),
+            marker,
+            theme().muted.paint(details.join(" · "))
+        )
+    }
+}
+
+/// Entries matching `query`, best match first; all of them for an empty query
+pub fn filter<'a>(entries: &'a [ModelEntry], query: &str) -> Vec<&'a ModelEntry> {
+    let mut matches: Vec<(i64, &ModelEntry)> = entries
+        .iter()
+        .filter_map(|entry| entry.score(query).map(|score| (score, entry)))
+        .collect();
+    matches.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| x.key.cmp(&y.key)));
+    matches.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Score of `query` as a case-insensitive subsequence of `text`
+///
+/// `None` when it is not one. Runs of adjacent characters, matches at the
+/// start of a word and an exact match score higher.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i64> {
+    let query = query.to_lowercase();
+    let text = text.to_lowercase();
+    if query.is_empty() {
+        return Some(0);
+    }
+    if query == text {
+        return Some(1000);
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    // A query found whole is matched where it is, not spread out before it
+    let substring = text.find(&query).map(|pos| text[..pos].chars().count());
+    let mut score = 0;
+    let mut next = substring.unwrap_or(0);
+    let mut previous: Option<usize> = None;
+    for q in query.chars() {
+        let found = (next..chars.len()).find(|&i| chars[i] == q)?;
+        score += 1;
+        if previous.is_some_and(|p| p + 1 == found) {
+            score += 5;
+        }
+        if found == 0 || matches!(chars[found - 1], '-' | '_' | '.' | '/' | ' ') {
+            score += 3;
+        }
+        previous = Some(found);
+        next = found + 1;
+    }
+    if substring.is_some() {
+        score += 10;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, name: &str, provider: &str) -> ModelEntry {
+        ModelEntry {
+            key: key.to_string(),
+            name: name.to_string(),
+            provider: provider.to_string(),
+            max_tokens: Some(128_000),
+            capabilities: vec![ModelCapability::Streaming, ModelCapability::Vision],
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_model_matching() {
+        assert_eq!(fuzzy_score("k2", "kimi-k2"), Some(20));
+        assert!(fuzzy_score("kk2", "kimi-k2").is_some());
+        assert_eq!(fuzzy_score("2k", "kimi-k2"), None);
+        assert!(fuzzy_score("K2", "kimi-k2") > fuzzy_score("k2", "kimi-k1-2"));
+
+        let entries = vec![
+            entry("kimi-k2", "kimi-k2-0905-preview", "moonshot"),
+            entry("kimi-k2-turbo", "kimi-k2-turbo-preview", "moonshot"),
+            entry("gpt-4o", "gpt-4o", "openai"),
+        ];
+        let keys = |query| filter(&entries, query).iter().map(|e| e.key.as_str()).collect::<Vec<_>>();
+        assert_eq!(keys("turbo"), ["kimi-k2-turbo"]);
+        assert_eq!(keys("k2"), ["kimi-k2", "kimi-k2-turbo"]);
+        assert_eq!(keys("openai"), ["gpt-4o"]);
+        assert_eq!(keys("").len(), 3);
+        assert!(keys("claude").is_empty());
+
+        let line = entries[0].format(1, true);
+        assert!(line.contains("kimi-k2-0905-preview · moonshot · 128k context · vision"));
+        assert!(!line.contains("streaming"));
+    }
+}
@@ -0,0 +1,149 @@
+//! Alerts for turns that ran long, so the user can look away meanwhile
+//!
+//! When a turn has been running for `[notifications] after_seconds`, the
+//! shell alerts once it ends or stops for approval. The alert is the
+//! terminal bell by default, which terminals flag on background tabs; an
+//! OSC 9 notification, which terminals such as iTerm2, kitty, WezTerm and
+//! Windows Terminal show on the desktop; or a desktop notification through
+//! `notify-send` or `osascript`. On X11 a desktop notification is skipped
+//! while the terminal window has focus.
+
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use kimi_core::NotificationsConfig;
+
+/// How the user is alerted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Bell,
+    /// An OSC 9 escape sequence, which the terminal may ignore
+    Terminal,
+    Desktop,
+    Off,
+}
+
+/// Alerts the user about turns that took at least `after`
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    method: Method,
+    after: Duration,
+}
+
+impl Notifier {
+    pub fn from_config(config: &NotificationsConfig) -> Self {
+        let method = match config.method.as_deref() {
+            Some("terminal") => Method::Terminal,
+            Some("desktop") => Method::Desktop,
+            Some("off") => Method::Off,
+            _ => Method::Bell,
+        };
+        let after = config.after_seconds.unwrap_or(NotificationsConfig::DEFAULT_AFTER_SECONDS);
+        Self { method, after: Duration::from_secs(after) }
+    }
+
+    /// Whether a turn that has run for `elapsed` is long enough to alert
+    /// about
+    pub fn due(&self, elapsed: Duration) -> bool {
+        self.method != Method::Off && elapsed >= self.after
+    }
+
+    /// Alert the user with a `title` and `body`
+    ///
+    /// Failures are not reported: a missed alert is no reason to interrupt
+    /// the turn.
+    pub fn notify(&self, title: &str, body: &str) {
+        let terminal = std::io::stdout().is_terminal();
+        let sequence = match self.method {
+            Method::Off => return,
+            Method::Desktop if terminal_focused() == Some(true) => return,
+            Method::Desktop if desktop(title, body) => return,
+            Method::Bell | Method::Desktop => "\x07".to_string(),
+            Method::Terminal => osc9(&format!("{}: {}", title, body), std::env::var_os("TMUX").is_some()),
+        };
+        if terminal {
+            let mut stdout = std::io::stdout();
+            let _ = stdout.write_all(sequence.as_bytes()).and_then(|_| stdout.flush());
+        }
+    }
+}
+
+/// Show a desktop notification, returning whether it was shown
+fn desktop(title: &str, body: &str) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else if cfg!(unix) {
+        let mut command = Command::new("notify-send");
+        command.args(["--app-name=kimi", title, body]);
+        command
+    } else {
+        return false;
+    };
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether the terminal window has focus, when that can be told: on X11
+/// with `xdotool` and a terminal that sets `WINDOWID`
+fn terminal_focused() -> Option<bool> {
+    let window: u64 = std::env::var("WINDOWID").ok()?.parse().ok()?;
+    let output = Command::new("xdotool").arg("getactivewindow").stderr(Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let active: u64 = String::from_utf8_lossy(&output.stdout).trim().parse().ok()?;
+    Some(active == window)
+}
+
+/// The OSC 9 sequence showing `message`, wrapped for tmux to pass it on to
+/// the terminal
+fn osc9(message: &str, tmux: bool) -> String {
+    // Control characters would end the sequence early
+    let message: String = message.chars().filter(|c| !c.is_control()).collect();
+    let sequence = format!("\x1b]9;{}\x07", message);
+    match tmux {
+        true => format!("\x1bPtmux;\x1b{}\x1b\\", sequence),
+        false => sequence,
+    }
+}
+
+/// `text` as an AppleScript string literal
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notifier_settings_and_sequences() {
+        let notifier = Notifier::from_config(&NotificationsConfig::default());
+        assert_eq!(notifier.method, Method::Bell);
+        assert!(!notifier.due(Duration::from_secs(29)));
+        assert!(notifier.due(Duration::from_secs(30)));
+
+        let config = NotificationsConfig { method: Some("off".to_string()), after_seconds: Some(0) };
+        assert!(!Notifier::from_config(&config).due(Duration::from_secs(60)));
+        let config = NotificationsConfig { method: Some("terminal".to_string()), after_seconds: Some(5) };
+        let notifier = Notifier::from_config(&config);
+        assert_eq!(notifier.method, Method::Terminal);
+        assert!(notifier.due(Duration::from_secs(5)));
+
+        assert_eq!(osc9("Kimi finished\n", false), "\x1b]9;Kimi finished\x07");
+        assert_eq!(osc9("done", true), "\x1bPtmux;\x1b\x1b]9;done\x07\x1b\\");
+        assert_eq!(applescript_string(r#"say "hi" \o/"#), r#""say \"hi\" \\o/""#);
+    }
+}
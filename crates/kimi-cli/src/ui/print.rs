@@ -1,29 +1,36 @@
 use std::io::{self, Read};
 
-use nu_ansi_term::{Color, Style};
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use kimi_core::{
+    attachment,
     llm,
+    EventLog,
+    Transcript,
     soul::{KimiSoul, WireSoulSide},
     types::UserInput,
     wire::WireMessage,
 };
 
 use crate::cli::Cli;
+use crate::ui::theme::theme;
 use crate::ui::{UIError, UIResult, UI};
 
 /// Non-interactive print UI for scripts and automation
 pub struct PrintUI {
     cli: Cli,
+    /// Session event log every wire message is recorded to
+    event_log: Option<EventLog>,
+    /// Transcript from `--log-file`, if any
+    transcript: Option<Transcript>,
 }
 
 impl PrintUI {
     /// Create a new print UI instance
     pub fn new(cli: Cli) -> UIResult<Self> {
         info!("Initializing print UI");
-        Ok(Self { cli })
+        Ok(Self { cli, event_log: None, transcript: None })
     }
 
     /// Run the print UI with a KimiSoul for processing
@@ -33,108 +40,139 @@ impl PrintUI {
         prompt: &str,
     ) -> UIResult<()> {
         info!("Running print UI with prompt: {}", prompt);
+        self.event_log = soul.event_log.clone();
+        self.transcript = soul.transcript.clone();
 
-        // Create LLM provider
-        let config = kimi_core::config::load_config(None)
+        // Create LLM provider, for --model if given
+        let config = kimi_core::config::load_session_config(self.cli.config_file.as_deref(), &self.cli.effective_work_dir())
             .map_err(|e| UIError::Shell(format!("Failed to load config: {}", e)))?;
-        let provider = llm::create_provider(&config).await
-            .map_err(|e| UIError::Core(format!("Failed to create provider: {}", e)))?;
+        let provider = match &self.cli.model {
+            Some(model) => llm::create_provider_for_model(&config, model).await,
+            None => llm::create_provider(&config).await,
+        };
+        let provider = provider
+            .map_err(|e| UIError::Provider(format!("Failed to create provider: {}", e)))?;
+        soul.fallback_provider = llm::create_fallback_provider(&config, soul.failover_handler()).await.unwrap_or_else(|e| {
+            warn!("Fallback model unavailable: {}", e);
+            None
+        });
+
+        // No one is there to answer approval requests
+        soul.approval.set_unattended(true);
 
         // Create channels for wire communication
         let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
         let wire = WireSoulSide::with_sender(ui_tx.clone());
 
-        // Create user input
+        // Create user input with the files given as --file and --image, and
+        // those mentioned as @path
+        let mut attachments = self.cli.attachments().map_err(|e| UIError::InvalidInput(e.to_string()))?;
+        for mention in attachment::mentioned_files(prompt, &self.cli.effective_work_dir(), soul.workspace.as_ref()) {
+            match mention {
+                Ok(attachment) if !attachments.contains(&attachment) => attachments.push(attachment),
+                Ok(_) => {}
+                Err(e) => self.note(&format!("Not attached: {}", e)),
+            }
+        }
         let user_input = UserInput {
             text: prompt.to_string(),
-            attachments: vec![],
+            attachments,
         };
 
-        // Run LLM processing and UI loop concurrently
+        // Run LLM processing and UI loop concurrently; the UI loop ends at
+        // the turn's end, so everything sent before it is printed
         let llm_future = async {
-            match soul.process_with_llm(provider.as_ref(), user_input, &wire).await {
-                Ok(_) => {
-                    let _ = ui_tx.send(WireMessage::TurnEnd).await;
-                    Ok(())
-                }
-                Err(e) => {
-                    let _ = ui_tx.send(WireMessage::TextPart { 
-                        text: format!("Error: {}", e) 
-                    }).await;
-                    let _ = ui_tx.send(WireMessage::TurnEnd).await;
-                    Err(UIError::Core(e.to_string()))
-                }
-            }
+            let result = soul.process_with_llm(provider.as_ref(), user_input, &wire).await;
+            let _ = ui_tx.send(WireMessage::TurnEnd).await;
+            result
+        };
+        let (result, denied) = tokio::select! {
+            results = async { tokio::join!(llm_future, self.run_ui_loop(&mut ui_rx)) } => results,
+            _ = tokio::signal::ctrl_c() => return Err(UIError::Interrupted),
         };
 
-        // Run both futures concurrently
-        tokio::select! {
-            result = llm_future => {
-                result?;
-            }
-            result = self.run_ui_loop(&mut ui_rx) => {
-                result?;
-            }
+        result?;
+        match denied? {
+            0 => Ok(()),
+            denied => Err(UIError::ToolDenied(denied)),
         }
-
-        Ok(())
     }
 
+    /// Print the turn's messages until it ends, returning the number of
+    /// tool calls that were denied
     async fn run_ui_loop(
         &self,
         ui_rx: &mut mpsc::Receiver<WireMessage>,
-    ) -> UIResult<()> {
-        loop {
-            match ui_rx.recv().await {
-                Some(msg) => {
-                    match msg {
-                        WireMessage::TextPart { text } => {
-                            print!("{}", text);
-                            std::io::Write::flush(&mut std::io::stdout()).map_err(UIError::Io)?;
-                        }
-                        WireMessage::ThinkPart { text } => {
-                            // In print mode, we might want to suppress thinking
-                            if self.cli.verbose {
-                                eprintln!("[Thinking: {}]", text);
-                            }
-                        }
-                        WireMessage::ToolCall { name, arguments, .. } => {
-                            if self.cli.verbose {
-                                eprintln!("[Tool: {}] {}", name, arguments);
-                            }
-                        }
-                        WireMessage::ToolResult { output, is_error, .. } => {
-                            if self.cli.verbose {
-                                if is_error {
-                                    eprintln!("[Tool Error: {}]", output);
-                                } else {
-                                    eprintln!("[Tool Result: {}]", output);
-                                }
-                            }
-                        }
-                        WireMessage::TurnEnd => {
-                            println!(); // New line after response
-                            break;
-                        }
-                        WireMessage::StatusUpdate { context_usage, token_usage, .. } => {
-                            if self.cli.verbose {
-                                if let Some(usage) = context_usage {
-                                    eprintln!("[Context: {:.1}%]", usage * 100.0);
-                                }
-                                if let Some(tokens) = token_usage {
-                                    eprintln!("[Tokens: {} in / {} out]", 
-                                        tokens.input_tokens, tokens.output_tokens);
-                                }
-                            }
-                        }
-                        _ => {}
+    ) -> UIResult<usize> {
+        let mut denied = 0;
+        while let Some(msg) = ui_rx.recv().await {
+            if let Some(log) = &self.event_log {
+                log.record(&msg);
+            }
+            if let Some(transcript) = &self.transcript {
+                transcript.record(&msg);
+            }
+            match msg {
+                WireMessage::TextPart { text } => {
+                    print!("{}", text);
+                    std::io::Write::flush(&mut std::io::stdout()).map_err(UIError::Io)?;
+                }
+                // In print mode, we might want to suppress thinking
+                WireMessage::ThinkPart { text } if self.cli.verbose => {
+                    eprintln!("[Thinking: {}]", text);
+                }
+                WireMessage::ToolBegin { .. } | WireMessage::ToolEnd { .. } => {
+                    if let Some(note) = self.tool_note(&msg) {
+                        eprintln!("{}", note);
+                    }
+                }
+                WireMessage::ApprovalRequest { action, description, .. } => {
+                    denied += 1;
+                    self.note(&format!("[Denied: {} ({}); it needs approval]", action, description));
+                }
+                WireMessage::ProviderFailover { from, to, reason } => {
+                    self.note(&format!("[{} failed ({}); retrying on {}]", from, reason, to));
+                }
+                WireMessage::TurnEnd => {
+                    println!(); // New line after response
+                    break;
+                }
+                WireMessage::StatusUpdate { context_usage, token_usage, .. } if self.cli.verbose => {
+                    if let Some(usage) = context_usage {
+                        eprintln!("[Context: {:.1}%]", usage * 100.0);
+                    }
+                    if let Some(tokens) = token_usage {
+                        eprintln!("[Tokens: {} in / {} out]", 
+                            tokens.input_tokens, tokens.output_tokens);
                     }
                 }
-                None => break,
+                _ => {}
             }
         }
 
-        Ok(())
+        Ok(denied)
+    }
+
+    /// The line printed on stderr for a tool starting or ending, if any
+    ///
+    /// Without --verbose, only the tool's name is noted as it starts, so the
+    /// user still sees what the agent is doing.
+    fn tool_note(&self, msg: &WireMessage) -> Option<String> {
+        match msg {
+            WireMessage::ToolBegin { name, arguments } if self.cli.verbose => {
+                Some(format!("[Tool: {}] {}", name, arguments))
+            }
+            WireMessage::ToolBegin { name, .. } if !self.cli.quiet => Some(format!("[Tool: {}]", name)),
+            WireMessage::ToolEnd { result, .. } if self.cli.verbose => Some(format!("[Tool Result: {}]", result)),
+            _ => None,
+        }
+    }
+
+    /// Print a note on stderr, unless running quietly
+    fn note(&self, note: &str) {
+        if !self.cli.quiet {
+            eprintln!("{}", note);
+        }
     }
 
     async fn execute_prompt(&self, prompt: &str) -> UIResult<()> {
@@ -147,21 +185,21 @@ impl PrintUI {
         if self.cli.verbose {
             output.push_str(&format!(
                 "{} Processing prompt...\n",
-                Style::new().fg(Color::Cyan).paint("[INFO]")
+                theme().accent.paint("[INFO]")
             ));
         }
 
         // Simulated response
         output.push_str(&format!(
             "Response to: {}\n",
-            Style::new().bold().paint(prompt)
+            theme().emphasis.paint(prompt)
         ));
 
         // Add footer if verbose
         if self.cli.verbose {
             output.push_str(&format!(
                 "{} Done.\n",
-                Style::new().fg(Color::Cyan).paint("[INFO]")
+                theme().accent.paint("[INFO]")
             ));
         }
 
@@ -225,7 +263,7 @@ impl UI for PrintUI {
     }
 
     fn error(&self, err: &str) {
-        eprintln!("{}", Style::new().fg(Color::Red).paint(err));
+        eprintln!("{}", theme().error.paint(err));
     }
 }
 
@@ -240,4 +278,25 @@ mod tests {
         let ui = PrintUI::new(cli);
         assert!(ui.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_tool_begin_is_noted() {
+        let begin = WireMessage::ToolBegin { name: "Shell".to_string(), arguments: r#"{"command":"ls"}"#.to_string() };
+        let end = WireMessage::ToolEnd { name: "Shell".to_string(), result: "a.txt".to_string() };
+
+        let ui = PrintUI::new(Cli::parse_from(["kimi", "--print", "-p", "test"])).unwrap();
+        assert_eq!(ui.tool_note(&begin).as_deref(), Some("[Tool: Shell]"));
+        assert_eq!(ui.tool_note(&end), None);
+        let (tx, mut rx) = mpsc::channel(4);
+        for msg in [begin.clone(), end.clone(), WireMessage::TurnEnd] {
+            tx.send(msg).await.unwrap();
+        }
+        assert_eq!(ui.run_ui_loop(&mut rx).await.unwrap(), 0);
+
+        let verbose = PrintUI::new(Cli::parse_from(["kimi", "--print", "--verbose", "-p", "test"])).unwrap();
+        assert_eq!(verbose.tool_note(&begin).as_deref(), Some(r#"[Tool: Shell] {"command":"ls"}"#));
+        assert_eq!(verbose.tool_note(&end).as_deref(), Some("[Tool Result: a.txt]"));
+        let quiet = PrintUI::new(Cli::parse_from(["kimi", "--print", "--quiet", "-p", "test"])).unwrap();
+        assert_eq!(quiet.tool_note(&begin), None);
+    }
 }
@@ -0,0 +1,332 @@
+//! Headless JSON-lines server
+//!
+//! Reads one JSON object per line from stdin and writes every wire message
+//! to stdout as one JSON object per line, so editors and other frontends can
+//! embed the agent without scraping terminal output. An input line is either
+//! a `UserInput` (`{"text": "..."}`), which starts a turn, or a wire message
+//! such as `ApprovalResponse`, which answers the pending approval request,
+//! or `Interrupt`, which stops the running turn. Turns run one at a time;
+//! inputs sent during a turn are queued. With `--listen`, the same protocol
+//! is served over WebSocket instead.
+
+use std::collections::VecDeque;
+
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+use tracing::{info, warn};
+
+use kimi_core::{
+    approval::Approval,
+    event_log::EventLog,
+    transcript::Transcript,
+    llm,
+    soul::{Interrupt, KimiSoul, SoulError, WireSoulSide},
+    types::UserInput,
+    wire::WireMessage,
+};
+use kosong_rs::ChatProvider;
+
+use crate::cli::Cli;
+use crate::ui::websocket::serve_websocket;
+use crate::ui::{UIError, UIResult};
+
+/// A line sent by the client
+#[derive(Debug)]
+pub(super) enum ClientLine {
+    Wire(WireMessage),
+    Input(UserInput),
+}
+
+impl ClientLine {
+    /// Objects with a `type` are wire messages; anything else is user input
+    pub(super) fn parse(line: &str) -> Result<Self, serde_json::Error> {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if value.get("type").is_some() {
+            WireMessage::from_json(line).map(Self::Wire)
+        } else {
+            serde_json::from_value(value).map(Self::Input)
+        }
+    }
+}
+
+/// JSON-lines server on stdin/stdout
+pub struct ServerUI {
+    cli: Cli,
+}
+
+impl ServerUI {
+    /// Create a new server UI instance
+    pub fn new(cli: Cli) -> UIResult<Self> {
+        info!("Initializing server UI");
+        Ok(Self { cli })
+    }
+
+    /// Serve stdin/stdout until stdin is closed
+    pub async fn run_with_soul(&mut self, soul: &mut KimiSoul) -> UIResult<()> {
+        let config = kimi_core::config::load_session_config(self.cli.config_file.as_deref(), &self.cli.effective_work_dir())
+            .map_err(|e| UIError::Shell(format!("Failed to load config: {}", e)))?;
+        let provider = match &self.cli.model {
+            Some(model) => llm::create_provider_for_model(&config, model).await,
+            None => llm::create_provider(&config).await,
+        }
+        .map_err(|e| UIError::Core(format!("Failed to create provider: {}", e)))?;
+        soul.fallback_provider = llm::create_fallback_provider(&config, soul.failover_handler()).await.unwrap_or_else(|e| {
+            warn!("Fallback model unavailable: {}", e);
+            None
+        });
+
+        match self.cli.listen {
+            Some(addr) => {
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                let token = self
+                    .cli
+                    .token
+                    .clone()
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().simple().to_string());
+                eprintln!("Listening on ws://{}/wire?token={}", listener.local_addr()?, token);
+                serve_websocket(soul, provider.as_ref(), listener, token).await
+            }
+            None => serve(soul, provider.as_ref(), BufReader::new(tokio::io::stdin()), tokio::io::stdout()).await,
+        }
+    }
+}
+
+/// Where a session's wire messages go
+pub(super) trait WireOutput {
+    async fn send(&mut self, message: &WireMessage) -> UIResult<()>;
+}
+
+/// Writes each message as one JSON line
+struct JsonLines<W>(W);
+
+impl<W: AsyncWrite + Unpin> WireOutput for JsonLines<W> {
+    async fn send(&mut self, message: &WireMessage) -> UIResult<()> {
+        let json = message
+            .to_json()
+            .map_err(|e| UIError::Core(format!("Failed to serialize wire message: {}", e)))?;
+        self.0.write_all(json.as_bytes()).await?;
+        self.0.write_all(b"\n").await?;
+        self.0.flush().await?;
+        Ok(())
+    }
+}
+
+/// Records each message to the session's event log and transcript before
+/// passing it on
+struct Recorded<'a, O> {
+    log: Option<EventLog>,
+    transcript: Option<Transcript>,
+    output: &'a mut O,
+}
+
+impl<O: WireOutput> WireOutput for Recorded<'_, O> {
+    async fn send(&mut self, message: &WireMessage) -> UIResult<()> {
+        if let Some(log) = &self.log {
+            log.record(message);
+        }
+        if let Some(transcript) = &self.transcript {
+            transcript.record(message);
+        }
+        self.output.send(message).await
+    }
+}
+
+/// Run turns for the inputs read from `reader`, writing wire messages to `writer`
+///
+/// Returns once the reader is closed and the remaining turns have run.
+/// Approval requests made after the reader closes are rejected, since no
+/// one is left to answer them.
+pub async fn serve<R, W>(
+    soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
+    reader: R,
+    writer: W,
+) -> UIResult<()>
+where
+    R: AsyncBufRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin,
+{
+    let (line_tx, line_rx) = mpsc::channel::<ClientLine>(16);
+    tokio::spawn(read_lines(reader, line_tx));
+    run_session(soul, provider, line_rx, &mut JsonLines(writer)).await
+}
+
+/// Run a turn for each user input received on `line_rx`, one at a time
+///
+/// Every wire message, including the `TurnBegin` and `TurnEnd` around each
+/// turn, goes to `output` and the soul's event log. Returns once `line_rx`
+/// is closed and the queued turns have run.
+pub(super) async fn run_session<O: WireOutput>(
+    soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
+    mut line_rx: mpsc::Receiver<ClientLine>,
+    output: &mut O,
+) -> UIResult<()> {
+    let approval = soul.approval.clone();
+    let interrupt = soul.interrupt_handle();
+    let mut output = Recorded {
+        log: soul.event_log.clone(),
+        transcript: soul.transcript.clone(),
+        output,
+    };
+    let mut queued: VecDeque<UserInput> = VecDeque::new();
+    let mut closed = false;
+
+    loop {
+        let user_input = match queued.pop_front() {
+            Some(user_input) => user_input,
+            None if closed => break,
+            None => match line_rx.recv().await {
+                Some(ClientLine::Input(user_input)) => user_input,
+                Some(ClientLine::Wire(message)) => {
+                    handle_client_message(&approval, &interrupt, message).await;
+                    continue;
+                }
+                None => break,
+            },
+        };
+
+        output.send(&WireMessage::TurnBegin { user_input: user_input.clone() }).await?;
+        let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
+        let wire = WireSoulSide::with_sender(ui_tx);
+        let turn = soul.process_with_llm(provider, user_input, &wire);
+        tokio::pin!(turn);
+
+        let result = loop {
+            tokio::select! {
+                result = &mut turn => break result,
+                Some(message) = ui_rx.recv() => {
+                    output.send(&message).await?;
+                    if closed && matches!(message, WireMessage::ApprovalRequest { .. }) {
+                        let _ = approval.cancel().await;
+                    }
+                }
+                line = line_rx.recv(), if !closed => match line {
+                    Some(ClientLine::Input(user_input)) => queued.push_back(user_input),
+                    Some(ClientLine::Wire(message)) => handle_client_message(&approval, &interrupt, message).await,
+                    None => {
+                        info!("Input closed, finishing the remaining turns");
+                        closed = true;
+                        let _ = approval.cancel().await;
+                    }
+                },
+            }
+        };
+
+        while let Ok(message) = ui_rx.try_recv() {
+            output.send(&message).await?;
+        }
+        // An interrupted turn has already reported StepInterrupted
+        if let Err(e) = result {
+            if !matches!(e, SoulError::Cancelled) {
+                output.send(&WireMessage::TextPart { text: format!("Error: {}", e) }).await?;
+            }
+        }
+        output.send(&WireMessage::TurnEnd).await?;
+    }
+
+    Ok(())
+}
+
+/// Parse client lines and pass them on until the reader closes
+async fn read_lines<R: AsyncBufRead + Unpin>(reader: R, line_tx: mpsc::Sender<ClientLine>) {
+    let mut lines = reader.lines();
+    loop {
+        let line = match lines.next_line().await {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            Err(e) => {
+                warn!("Failed to read input: {}", e);
+                break;
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        match ClientLine::parse(&line) {
+            Ok(parsed) => {
+                if line_tx.send(parsed).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => warn!("Ignoring invalid input line: {}", e),
+        }
+    }
+}
+
+/// Act on a wire message sent by the client
+async fn handle_client_message(approval: &Approval, interrupt: &Interrupt, message: WireMessage) {
+    match message {
+        WireMessage::Interrupt => interrupt.interrupt(),
+        WireMessage::ApprovalResponse { response, .. } => {
+            if let Err(e) = approval.respond(response).await {
+                warn!("Ignoring approval response: {}", e);
+            }
+        }
+        other => warn!("Ignoring unsupported client message: {:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::testing::{test_soul, EchoProvider};
+
+    #[test]
+    fn test_parse_client_lines() {
+        assert!(matches!(ClientLine::parse(r#"{"text": "hi"}"#), Ok(ClientLine::Input(ref i)) if i.text == "hi"));
+        assert!(matches!(
+            ClientLine::parse(r#"{"type": "ApprovalResponse", "payload": {"request_id": "r1", "response": "approve"}}"#),
+            Ok(ClientLine::Wire(WireMessage::ApprovalResponse { .. }))
+        ));
+        assert!(ClientLine::parse(r#"{"version": 99, "type": "TurnEnd"}"#).is_err());
+        assert!(ClientLine::parse("not json").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_serve_json_lines() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let log_path = temp.path().join("wire.jsonl");
+        soul.event_log = Some(kimi_core::EventLog::open(&log_path).unwrap());
+        let provider = EchoProvider::new();
+        let input = "{\"text\": \"hello\"}\nnot json\n\n{\"text\": \"fail\", \"attachments\": []}\n";
+        let mut output = Vec::new();
+
+        serve(&mut soul, &provider, input.as_bytes(), &mut output).await.unwrap();
+
+        let messages: Vec<WireMessage> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| WireMessage::from_json(line).unwrap())
+            .collect();
+        // Leave out the progress of the steps
+        let summary: Vec<String> = messages
+            .iter()
+            .filter(|m| !matches!(m, WireMessage::StepBegin { .. } | WireMessage::StatusUpdate { .. }))
+            .map(|m| match m {
+                WireMessage::TurnBegin { user_input } => format!("begin {}", user_input.text),
+                WireMessage::TextPart { text } => text.clone(),
+                WireMessage::TurnEnd => "end".to_string(),
+                other => format!("{:?}", other),
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                "begin hello",
+                "echo: hello",
+                "end",
+                "begin fail",
+                "Error: LLM error: Stream ended unexpectedly",
+                "end",
+            ]
+        );
+        assert_eq!(*provider.calls.lock().unwrap(), 2);
+
+        let logged = kimi_core::event_log::read_events(&log_path).unwrap();
+        assert_eq!(logged.len(), messages.len());
+        assert!(matches!(logged[1].message, WireMessage::StepBegin { n: 1 }));
+        assert!(matches!(logged[3].message, WireMessage::TextPart { ref text } if text == "echo: hello"));
+    }
+}
@@ -1,26 +1,47 @@
 use std::borrow::Cow;
+use std::io::IsTerminal;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 
-use nu_ansi_term::{Color, Style};
+use nu_ansi_term::Style;
 use reedline::{
     default_emacs_keybindings, ColumnarMenu, Completer, DefaultCompleter, DefaultHinter,
     Emacs, FileBackedHistory, Highlighter, KeyCode, KeyModifiers, MenuBuilder, Prompt,
     PromptHistorySearch, PromptHistorySearchStatus, Reedline, ReedlineEvent, ReedlineMenu, Signal,
-    ValidationResult, Validator, StyledText,
+    Span, Suggestion, ValidationResult, Validator, StyledText,
 };
+use kosong_rs::ChatProvider;
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
 use kimi_core::{
+    auth::{active_account, list_accounts, switch_account, SecretsManager},
+    approval::{Approval, PermissionMode, ToolRule},
+    attachment,
     ApprovalKind,
-    soul::{KimiSoul, Compaction},
-    types::UserInput,
-    wire::WireMessage,
-    Session,
-    config::{load_config, save_config, Config},
+    event_log::{self, Exchange},
+    EventLog,
+    Transcript,
+    soul::{checkpoint_label, slash::parse_slash_command, KimiSoul, CustomCommand, DMail, FlowRunner, Interrupt, SoulError},
+    types::{Attachment, Message, Role, UserInput},
+    wire::{OutputStream, WireMessage},
+    Session, SessionManager,
+    config::{load_config, load_session_config, save_config, Config, ConfigError},
+    diff::unified_diff,
+    git::{self, CommitOptions},
     llm::{self, LlmError},
 };
 
 use crate::cli::Cli;
+use crate::exit_code::ExitCode;
+use crate::ui::clipboard::{self, Method};
+use crate::ui::diff::render_diff;
+use crate::ui::editor::{self, edit_text};
+use crate::ui::markdown::MarkdownRenderer;
+use crate::ui::model_picker::{self, ModelEntry};
+use crate::ui::notify::Notifier;
+use crate::ui::status::{StatusLine, TICK as STATUS_TICK};
+use crate::ui::theme::theme;
 use crate::ui::{UIError, UIResult, UI};
 
 /// Interactive shell UI using reedline
@@ -32,6 +53,21 @@ pub struct ShellUI {
     config: Config,
     mode: ShellMode,
     current_model: String,
+    /// Model of the active agent persona, overriding the default model
+    agent_model: Option<String>,
+    completions: Arc<Mutex<DefaultCompleter>>,
+    /// Session event log every wire message is recorded to
+    event_log: Option<EventLog>,
+    /// Transcript from `--log-file`, if any
+    transcript: Option<Transcript>,
+    /// The soul's approval, for showing and switching the permission mode
+    permissions: Option<Approval>,
+    /// Files from `--file` and `--image`, attached to the first message
+    initial_attachments: Vec<Attachment>,
+    /// Stops the soul's running turn when Ctrl+C is pressed
+    interrupt: Option<Interrupt>,
+    /// Alerts the user when a long turn ends or needs approval
+    notifier: Notifier,
 }
 
 /// Custom highlighter for the shell
@@ -40,7 +76,7 @@ struct ShellHighlighter;
 impl Highlighter for ShellHighlighter {
     fn highlight(&self, line: &str, _cursor: usize) -> StyledText {
         let mut styled_text = StyledText::new();
-        styled_text.push((Style::new().fg(Color::White), line.to_string()));
+        styled_text.push((theme().input, line.to_string()));
         styled_text
     }
 }
@@ -63,32 +99,56 @@ impl std::fmt::Display for ShellMode {
     }
 }
 
+/// Style of the prompt in a mode
+fn mode_style(mode: ShellMode) -> Style {
+    match mode {
+        ShellMode::Agent => theme().prompt,
+        ShellMode::Shell => theme().shell_prompt,
+    }
+}
+
+/// Longest diff shown in an approval prompt, in lines
+const MAX_DIFF_LINES: usize = 200;
+
+/// Lines of context around each change in `/diff head`
+const DIFF_CONTEXT: usize = 3;
+
+/// What the user chose in an approval prompt
+enum ApprovalChoice {
+    Answer(ApprovalKind),
+    /// Rewrite the proposed file change in the editor
+    Edit,
+}
+
 /// Custom prompt for Kimi
 struct KimiPrompt {
     mode: ShellMode,
     model: String,
+    /// The soul's approval, read on every render to show the permission mode
+    permissions: Option<Approval>,
 }
 
 impl KimiPrompt {
-    fn new(mode: ShellMode, model: String) -> Self {
-        Self { mode, model }
+    fn new(mode: ShellMode, model: String, permissions: Option<Approval>) -> Self {
+        Self { mode, model, permissions }
     }
 }
 
 impl Prompt for KimiPrompt {
     fn render_prompt_left(&self) -> Cow<'_, str> {
-        let mode_color = match self.mode {
-            ShellMode::Agent => Color::Green,
-            ShellMode::Shell => Color::Blue,
-        };
         let mode_indicator = match self.mode {
             ShellMode::Agent => "🤖",
             ShellMode::Shell => "🐚",
         };
+        let permission = match self.permissions.as_ref().map(Approval::mode) {
+            Some(PermissionMode::Default) | None => String::new(),
+            Some(mode) => format!(" {}", theme().warning.paint(mode.as_str())),
+        };
         Cow::Owned(format!(
-            "{} {}",
-            Style::new().bold().fg(mode_color).paint(format!("kimi {}", mode_indicator)),
-            Style::new().fg(Color::DarkGray).paint(format!("[{}]", self.model))
+            "{} {}{}",
+            mode_style(self.mode).paint(format!("kimi {}", mode_indicator)),
+            theme().muted.paint(format!("[{}]", self.model)),
+            permission
         ))
     }
 
@@ -97,19 +157,11 @@ impl Prompt for KimiPrompt {
     }
 
     fn render_prompt_indicator(&self, _prompt_mode: reedline::PromptEditMode) -> Cow<'_, str> {
-        let color = match self.mode {
-            ShellMode::Agent => Color::Green,
-            ShellMode::Shell => Color::Blue,
-        };
-        Cow::Owned(Style::new().bold().fg(color).paint(" ❯ ").to_string())
+        Cow::Owned(mode_style(self.mode).paint(" ❯ ").to_string())
     }
 
     fn render_prompt_multiline_indicator(&self) -> Cow<'_, str> {
-        let color = match self.mode {
-            ShellMode::Agent => Color::Green,
-            ShellMode::Shell => Color::Blue,
-        };
-        Cow::Owned(Style::new().fg(color).paint("... ").to_string())
+        Cow::Owned(mode_style(self.mode).paint("... ").to_string())
     }
 
     fn render_prompt_history_search_indicator(
@@ -140,13 +192,18 @@ impl Validator for KimiValidator {
     }
 }
 
-/// Command completer
+/// Command and `@path` completer
+///
+/// The word list is shared so commands discovered later (custom slash
+/// commands) can be added after the editor is built.
 struct KimiCompleter {
-    inner: DefaultCompleter,
+    inner: Arc<Mutex<DefaultCompleter>>,
+    /// Directory `@path` mentions are relative to
+    work_dir: PathBuf,
 }
 
 impl KimiCompleter {
-    fn new() -> Self {
+    fn new(work_dir: PathBuf) -> Self {
         let commands = vec![
             "/help".to_string(),
             "/h".to_string(),
@@ -158,8 +215,23 @@ impl KimiCompleter {
             "/reset".to_string(),
             "/model".to_string(),
             "/models".to_string(),
+            "/agent".to_string(),
+            "/rewind".to_string(),
+            "/checkpoints".to_string(),
+            "/rollback".to_string(),
+            "/undo".to_string(),
+            "/diff".to_string(),
+            "/commit".to_string(),
+            "/copy".to_string(),
+            "/editor".to_string(),
+            "/history".to_string(),
+            "/pin".to_string(),
+            "/unpin".to_string(),
             "/session".to_string(),
             "/yolo".to_string(),
+            "/permissions".to_string(),
+            "/approve".to_string(),
+            "/stats".to_string(),
             "/compact".to_string(),
             "/tools".to_string(),
             "/version".to_string(),
@@ -173,17 +245,57 @@ impl KimiCompleter {
             "/login".to_string(),
             "/setup".to_string(),
             "/logout".to_string(),
+            "/account".to_string(),
             "/init".to_string(),
+            "/memory".to_string(),
             "/mode".to_string(),
         ];
         let inner = DefaultCompleter::new_with_wordlen(commands, 1);
-        Self { inner }
+        Self { inner: Arc::new(Mutex::new(inner)), work_dir }
+    }
+
+    /// Paths completing the `@path` mention that ends at `pos`, if there is one
+    fn complete_mention(&self, line: &str, pos: usize) -> Option<Vec<Suggestion>> {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let partial = line[start..pos].strip_prefix('@')?;
+        let (dir, prefix) = match partial.rfind('/') {
+            Some(slash) => partial.split_at(slash + 1),
+            None => ("", partial),
+        };
+
+        let mut suggestions: Vec<Suggestion> = std::fs::read_dir(self.work_dir.join(dir))
+            .ok()?
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // Hidden entries only when asked for
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                    return None;
+                }
+                let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+                Some(Suggestion {
+                    value: format!("@{}{}{}", dir, name, if is_dir { "/" } else { "" }),
+                    span: Span::new(start, pos),
+                    append_whitespace: !is_dir,
+                    ..Suggestion::default()
+                })
+            })
+            .collect();
+        suggestions.sort_by(|a, b| a.value.cmp(&b.value));
+        Some(suggestions)
     }
 }
 
 impl Completer for KimiCompleter {
     fn complete(&mut self, line: &str, pos: usize) -> Vec<reedline::Suggestion> {
-        self.inner.complete(line, pos)
+        if let Some(suggestions) = self.complete_mention(line, pos) {
+            return suggestions;
+        }
+        self.inner.lock().map(|mut inner| inner.complete(line, pos)).unwrap_or_default()
     }
 }
 
@@ -191,11 +303,14 @@ impl ShellUI {
     /// Create a new shell UI instance
     pub async fn new(cli: Cli) -> UIResult<Self> {
         info!("Initializing interactive shell UI");
+        let initial_attachments = cli.attachments().map_err(|e| UIError::InvalidInput(e.to_string()))?;
 
-        let editor = Self::create_editor()?;
+        let completer = KimiCompleter::new(cli.effective_work_dir());
+        let completions = completer.inner.clone();
+        let editor = Self::create_editor(completer)?;
         
         // Load config
-        let config = load_config(None).map_err(|e| {
+        let config = load_session_config(None, &cli.effective_work_dir()).map_err(|e| {
             UIError::Shell(format!("Failed to load config: {}", e))
         })?;
         
@@ -208,10 +323,11 @@ impl ShellUI {
             "none".to_string()
         };
 
-        let prompt = Box::new(KimiPrompt::new(ShellMode::Agent, current_model.clone()));
+        let prompt = Box::new(KimiPrompt::new(ShellMode::Agent, current_model.clone(), None));
         
         // Print boot screen
         Self::print_boot_screen(&current_model, &config);
+        let notifier = Notifier::from_config(&config.notifications);
 
         Ok(Self {
             editor,
@@ -220,6 +336,14 @@ impl ShellUI {
             config,
             mode: ShellMode::Agent,
             current_model,
+            agent_model: None,
+            completions,
+            event_log: None,
+            transcript: None,
+            permissions: None,
+            initial_attachments,
+            interrupt: None,
+            notifier,
         })
     }
 
@@ -238,41 +362,41 @@ impl ShellUI {
         "#;
         
         for line in art.lines() {
-            println!("{}", Style::new().bold().fg(Color::Cyan).paint(line));
+            println!("{}", theme().heading.paint(line));
         }
         
         println!();
         println!("  {} {}", 
-            Style::new().bold().paint("Version:"),
-            Style::new().fg(Color::Green).paint(env!("CARGO_PKG_VERSION"))
+            theme().emphasis.paint("Version:"),
+            theme().success.paint(env!("CARGO_PKG_VERSION"))
         );
         println!("  {} {}", 
-            Style::new().bold().paint("Model:"),
-            Style::new().fg(Color::Yellow).paint(model)
+            theme().emphasis.paint("Model:"),
+            theme().warning.paint(model)
         );
         
         // Show provider info
         let provider_count = config.providers.len();
         if provider_count > 0 {
             println!("  {} {}", 
-                Style::new().bold().paint("Providers:"),
-                Style::new().fg(Color::Green).paint(format!("{} configured", provider_count))
+                theme().emphasis.paint("Providers:"),
+                theme().success.paint(format!("{} configured", provider_count))
             );
         } else {
             println!("  {} {}", 
-                Style::new().bold().paint("Status:"),
-                Style::new().fg(Color::Yellow).paint("Not authenticated - use /login or /setup")
+                theme().emphasis.paint("Status:"),
+                theme().warning.paint("Not authenticated - use /login or /setup")
             );
         }
         
         println!("  {} {}", 
-            Style::new().bold().paint("Mode:"),
-            Style::new().fg(Color::Green).paint("Agent")
+            theme().emphasis.paint("Mode:"),
+            theme().success.paint("Agent")
         );
         
         println!();
-        println!("  {}", Style::new().fg(Color::DarkGray).paint("Type /help for available commands"));
-        println!("  {}", Style::new().fg(Color::DarkGray).paint("Type /mode to switch between agent and shell mode"));
+        println!("  {}", theme().muted.paint("Type /help for available commands"));
+        println!("  {}", theme().muted.paint("Type /mode to switch between agent and shell mode"));
         println!();
     }
 
@@ -284,33 +408,28 @@ impl ShellUI {
         };
         
         // Update prompt
-        self.prompt = Box::new(KimiPrompt::new(self.mode, self.current_model.clone()));
+        self.prompt = Box::new(KimiPrompt::new(self.mode, self.current_model.clone(), self.permissions.clone()));
         
         let mode_str = format!("{:?}", self.mode);
-        let color = match self.mode {
-            ShellMode::Agent => Color::Green,
-            ShellMode::Shell => Color::Blue,
-        };
-        
         println!("\n{} Switched to {} mode\n", 
-            Style::new().bold().fg(color).paint("✓"),
-            Style::new().bold().fg(color).paint(&mode_str)
+            mode_style(self.mode).paint("✓"),
+            mode_style(self.mode).paint(&mode_str)
         );
         
         match self.mode {
             ShellMode::Agent => {
-                println!("  {}", Style::new().fg(Color::DarkGray).paint("AI assistant with tool calling enabled"));
-                println!("  {}", Style::new().fg(Color::DarkGray).paint("The AI can use tools to help you"));
+                println!("  {}", theme().muted.paint("AI assistant with tool calling enabled"));
+                println!("  {}", theme().muted.paint("The AI can use tools to help you"));
             }
             ShellMode::Shell => {
-                println!("  {}", Style::new().fg(Color::DarkGray).paint("Traditional shell command execution"));
-                println!("  {}", Style::new().fg(Color::DarkGray).paint("Commands execute directly without AI"));
+                println!("  {}", theme().muted.paint("Traditional shell command execution"));
+                println!("  {}", theme().muted.paint("Commands execute directly without AI"));
             }
         }
         println!();
     }
 
-    fn create_editor() -> UIResult<Reedline> {
+    fn create_editor(completer: KimiCompleter) -> UIResult<Reedline> {
         // Set up history
         let history_path = dirs::cache_dir()
             .ok_or_else(|| UIError::Shell("Failed to determine cache directory".to_string()))?
@@ -329,7 +448,7 @@ impl ShellUI {
         );
 
         // Set up completer
-        let completer = Box::new(KimiCompleter::new());
+        let completer = Box::new(completer);
 
         // Set up highlighter
         let highlighter = Box::new(ShellHighlighter);
@@ -337,7 +456,7 @@ impl ShellUI {
         // Set up hinter
         let hinter = Box::new(
             DefaultHinter::default()
-                .with_style(Style::new().fg(Color::DarkGray))
+                .with_style(theme().muted)
         );
 
         // Set up validator
@@ -357,7 +476,8 @@ impl ShellUI {
             .with_highlighter(highlighter)
             .with_hinter(hinter)
             .with_validator(validator)
-            .with_menu(ReedlineMenu::EngineCompleter(completion_menu));
+            .with_menu(ReedlineMenu::EngineCompleter(completion_menu))
+            .with_buffer_editor(editor::command(&editor::editor()), editor::prompt_file());
 
         // Set up keybindings
         let mut keybindings = default_emacs_keybindings();
@@ -366,6 +486,12 @@ impl ShellUI {
             KeyCode::Char('d'),
             ReedlineEvent::Edit(vec![reedline::EditCommand::Delete]),
         );
+        // Compose the prompt in $EDITOR, sending it once saved
+        keybindings.add_binding(
+            KeyModifiers::CONTROL,
+            KeyCode::Char('g'),
+            ReedlineEvent::Multiple(vec![ReedlineEvent::OpenEditor, ReedlineEvent::Submit]),
+        );
         editor = editor.with_edit_mode(Box::new(Emacs::new(keybindings)));
 
         Ok(editor)
@@ -374,18 +500,35 @@ impl ShellUI {
     /// Run the shell with a KimiSoul for processing
     pub async fn run_with_soul(&mut self, soul: &mut KimiSoul) -> UIResult<()> {
         info!("Starting interactive shell with soul");
+        self.event_log = soul.event_log.clone();
+        self.transcript = soul.transcript.clone();
+        self.permissions = Some((*soul.approval).clone());
+        self.interrupt = Some(soul.interrupt_handle());
+        self.prompt = Box::new(KimiPrompt::new(self.mode, self.current_model.clone(), self.permissions.clone()));
+
+        // Offer custom slash commands and flow skills in tab completion
+        let custom: Vec<String> = soul.slash_commands.custom_commands()
+            .iter()
+            .map(|command| format!("/{}", command.name))
+            .chain(soul.skills.iter().filter(|skill| skill.flow.is_some()).map(|skill| format!("/flow:{}", skill.name)))
+            .collect();
+        if let Ok(mut completions) = self.completions.lock() {
+            completions.insert(custom);
+        }
 
         println!(
             "\n{}",
-            Style::new()
-                .bold()
-                .fg(Color::Cyan)
+            theme()
+                .heading
                 .paint("Welcome to Kimi CLI!")
         );
+        if let Some(notice) = crate::commands::update::startup_notice(&self.config.updates) {
+            println!("{}", theme().accent.paint(notice));
+        }
         println!(
             "{}",
-            Style::new()
-                .fg(Color::DarkGray)
+            theme()
+                .muted
                 .paint("Type /help for commands, /exit to quit.\n")
         );
 
@@ -397,7 +540,7 @@ impl ShellUI {
                         Ok(false) => break,
                         Err(e) => {
                             eprintln!("{} {}", 
-                                Style::new().fg(Color::Red).paint("Error:"),
+                                theme().error.paint("Error:"),
                                 e
                             );
                             continue;
@@ -418,6 +561,8 @@ impl ShellUI {
             }
         }
 
+        // Left behind by Ctrl+G
+        let _ = std::fs::remove_file(editor::prompt_file());
         Ok(())
     }
 
@@ -437,6 +582,24 @@ impl ShellUI {
             return self.handle_command(input, soul).await;
         }
 
+        // `!command` runs a shell command without leaving agent mode;
+        // `!!command` also adds its output to the conversation
+        if self.mode == ShellMode::Agent {
+            if let Some(command) = input.strip_prefix('!') {
+                let (command, observe) = match command.strip_prefix('!') {
+                    Some(command) => (command.trim(), true),
+                    None => (command.trim(), false),
+                };
+                let output = self.execute_shell_command(command).await?;
+                if let Some(output) = output.filter(|_| observe) {
+                    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+                    soul.add_shell_output(command, output.status.code(), &text);
+                    println!("{}", theme().muted.paint("[Output added to the conversation]"));
+                }
+                return Ok(true);
+            }
+        }
+
         // Handle based on current mode
         match self.mode {
             ShellMode::Agent => {
@@ -454,8 +617,9 @@ impl ShellUI {
         Ok(true)
     }
 
-    /// Execute a shell command in restricted shell mode
-    async fn execute_shell_command(&self, command: &str) -> UIResult<()> {
+    /// Execute a shell command in restricted shell mode, returning its
+    /// output unless it was not allowed to run
+    async fn execute_shell_command(&self, command: &str) -> UIResult<Option<std::process::Output>> {
         use std::process::Stdio;
         use tokio::process::Command;
 
@@ -472,17 +636,17 @@ impl ShellUI {
         for blocked in &blocked_commands {
             if cmd_lower.contains(blocked) {
                 println!("\n{} Command '{}' is not allowed in shell mode", 
-                    Style::new().fg(Color::Red).paint("✗"),
+                    theme().error.paint("✗"),
                     blocked
                 );
-                return Ok(());
+                return Ok(None);
             }
         }
 
         // Parse command to get the executable
         let cmd_parts: Vec<&str> = command.split_whitespace().collect();
         if cmd_parts.is_empty() {
-            return Ok(());
+            return Ok(None);
         }
 
         let executable = cmd_parts[0];
@@ -505,13 +669,15 @@ impl ShellUI {
 
         if !allowed_commands.contains(&executable) {
             println!("\n{} Command '{}' is not allowed in shell mode", 
-                Style::new().fg(Color::Red).paint("✗"),
+                theme().error.paint("✗"),
                 executable
             );
-            println!("  {} Type /mode to switch to agent mode for AI assistance", 
-                Style::new().fg(Color::DarkGray).paint("→")
-            );
-            return Ok(());
+            if self.mode == ShellMode::Shell {
+                println!("  {} Type /mode to switch to agent mode for AI assistance", 
+                    theme().muted.paint("→")
+                );
+            }
+            return Ok(None);
         }
 
         // Execute the command
@@ -521,7 +687,7 @@ impl ShellUI {
         let output = Command::new("bash")
             .arg("-c")
             .arg(command)
-            .current_dir(&self.cli.effective_work_dir())
+            .current_dir(self.cli.effective_work_dir())
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
             .output()
@@ -539,23 +705,23 @@ impl ShellUI {
         // Print stderr
         if !output.stderr.is_empty() {
             let stderr = String::from_utf8_lossy(&output.stderr);
-            eprint!("{}", Style::new().fg(Color::Yellow).paint(stderr.to_string()));
+            eprint!("{}", theme().warning.paint(stderr.to_string()));
         }
 
         // Print status
         if output.status.success() {
             println!("\n{} Command completed in {:.2}s", 
-                Style::new().fg(Color::Green).paint("✓"),
+                theme().success.paint("✓"),
                 elapsed.as_secs_f64()
             );
         } else {
             println!("\n{} Command failed with exit code: {}", 
-                Style::new().fg(Color::Red).paint("✗"),
+                theme().error.paint("✗"),
                 output.status.code().unwrap_or(-1)
             );
         }
 
-        Ok(())
+        Ok(Some(output))
     }
 
     async fn handle_command(&mut self, input: &str, soul: &mut KimiSoul) -> UIResult<bool> {
@@ -565,7 +731,7 @@ impl ShellUI {
         }
 
         let cmd = parts[0];
-        let _args = parts.get(1..).map(|v| v.join(" ")).unwrap_or_default();
+        let args = parts.get(1..).map(|v| v.join(" ")).unwrap_or_default();
 
         match cmd {
             // General commands
@@ -575,6 +741,7 @@ impl ShellUI {
             }
             "/help" | "/h" | "/?" => {
                 self.print_help();
+                self.print_custom_commands(soul);
                 Ok(true)
             }
             "/version" => {
@@ -596,33 +763,29 @@ impl ShellUI {
                 Ok(true)
             }
             "/session" => {
-                if parts.len() > 1 {
-                    println!("Session: {}", parts[1]);
-                } else {
-                    println!("Current session: {}", soul.context.message_count());
-                }
+                self.handle_session(&parts[1..], soul);
                 Ok(true)
             }
 
             // Authentication commands
             "/login" => {
-                if let Err(e) = crate::commands::login::execute(true).await {
+                if let Err(e) = crate::commands::login::execute(true, parts.get(1).copied(), parts.get(2).copied()).await {
                     eprintln!("{} {}", 
-                        Style::new().fg(Color::Red).paint("Login failed:"),
+                        theme().error.paint("Login failed:"),
                         e
                     );
                 } else {
                     // Reload config after successful login
-                    match load_config(None) {
+                    match load_session_config(None, &self.cli.effective_work_dir()) {
                         Ok(new_config) => {
                             self.config = new_config;
                             println!("{}", 
-                                Style::new().fg(Color::Green).paint("Config reloaded successfully!")
+                                theme().success.paint("Config reloaded successfully!")
                             );
                         }
                         Err(e) => {
                             eprintln!("{} {}", 
-                                Style::new().fg(Color::Yellow).paint("Warning: Failed to reload config:"),
+                                theme().warning.paint("Warning: Failed to reload config:"),
                                 e
                             );
                         }
@@ -633,21 +796,21 @@ impl ShellUI {
             "/setup" => {
                 if let Err(e) = crate::commands::setup::execute().await {
                     eprintln!("{} {}", 
-                        Style::new().fg(Color::Red).paint("Setup failed:"),
+                        theme().error.paint("Setup failed:"),
                         e
                     );
                 } else {
                     // Reload config after successful setup
-                    match load_config(None) {
+                    match load_session_config(None, &self.cli.effective_work_dir()) {
                         Ok(new_config) => {
                             self.config = new_config;
                             println!("{}", 
-                                Style::new().fg(Color::Green).paint("Setup complete! Config reloaded successfully!")
+                                theme().success.paint("Setup complete! Config reloaded successfully!")
                             );
                         }
                         Err(e) => {
                             eprintln!("{} {}", 
-                                Style::new().fg(Color::Yellow).paint("Warning: Failed to reload config:"),
+                                theme().warning.paint("Warning: Failed to reload config:"),
                                 e
                             );
                         }
@@ -655,24 +818,28 @@ impl ShellUI {
                 }
                 Ok(true)
             }
+            "/account" => {
+                self.handle_account(&parts[1..]);
+                Ok(true)
+            }
             "/logout" => {
-                if let Err(e) = crate::commands::login::logout().await {
+                if let Err(e) = crate::commands::login::logout(parts.get(1).copied(), parts.get(2).copied()).await {
                     eprintln!("{} {}", 
-                        Style::new().fg(Color::Red).paint("Logout failed:"),
+                        theme().error.paint("Logout failed:"),
                         e
                     );
                 } else {
                     // Reload config after logout
-                    match load_config(None) {
+                    match load_session_config(None, &self.cli.effective_work_dir()) {
                         Ok(new_config) => {
                             self.config = new_config;
                             println!("{}", 
-                                Style::new().fg(Color::Green).paint("Logged out and config reloaded.")
+                                theme().success.paint("Logged out and config reloaded.")
                             );
                         }
                         Err(e) => {
                             eprintln!("{} {}", 
-                                Style::new().fg(Color::Yellow).paint("Warning: Failed to reload config:"),
+                                theme().warning.paint("Warning: Failed to reload config:"),
                                 e
                             );
                         }
@@ -685,182 +852,438 @@ impl ShellUI {
             "/clear" | "/reset" => {
                 soul.context.clear_messages();
                 print!("\x1B[2J\x1B[1;1H");
-                println!("{}", Style::new().fg(Color::Green).paint("Context cleared."));
+                println!("{}", theme().success.paint("Context cleared."));
                 Ok(true)
             }
             "/compact" => {
-                println!("Compacting context...");
-                match soul.compaction.compact(&mut soul.context) {
-                    Ok(removed) => {
-                        println!("{} Removed {} messages.", 
-                            Style::new().fg(Color::Green).paint("Context compacted."),
-                            removed
-                        );
-                    }
-                    Err(e) => {
-                        eprintln!("{} {}", 
-                            Style::new().fg(Color::Red).paint("Compaction failed:"),
-                            e
-                        );
-                    }
+                if let Err(e) = self.run_compact(soul, &args).await {
+                    eprintln!("{} {}", 
+                        theme().error.paint("Compaction failed:"),
+                        e
+                    );
                 }
                 Ok(true)
             }
             "/yolo" => {
-                // Toggle YOLO mode in the soul's approval
-                let current = soul.approval.is_yolo();
-                let _new_state = !current;
-                
-                // We need to get a mutable reference to the approval
-                // Since it's wrapped in Arc, we need to use interior mutability
-                // For now, print a message indicating the limitation
-                // Note: Since approval is wrapped in Arc, we can't easily modify it at runtime
-                // without interior mutability. For now, inform the user about the limitation.
-                println!("{}", Style::new().fg(Color::Yellow).paint("Note: YOLO mode state cannot be changed at runtime in this version."));
-                println!("Current state: {}", if current { "enabled" } else { "disabled" });
-                println!("Restart with --yolo flag to enable YOLO mode.");
+                // Toggle between full-auto and asking for every tool call
+                let mode = if soul.approval.is_yolo() {
+                    PermissionMode::Default
+                } else {
+                    PermissionMode::FullAuto
+                };
+                soul.approval.set_mode(mode);
+                println!("YOLO mode {}.", if soul.approval.is_yolo() { "enabled" } else { "disabled" });
+                Ok(true)
+            }
+            "/permissions" => {
+                self.handle_permissions(parts.get(1).copied(), soul);
+                Ok(true)
+            }
+            "/approve" => {
+                self.handle_approve(&parts[1..], soul);
+                Ok(true)
+            }
+            "/stats" => {
+                let days = match parts.get(1).map(|days| days.parse::<u32>()) {
+                    None => None,
+                    Some(Ok(days)) => Some(days),
+                    Some(Err(_)) => {
+                        eprintln!("Usage: /stats [days]");
+                        return Ok(true);
+                    }
+                };
+                match soul.usage.as_ref().map(|store| store.load()) {
+                    Some(Ok(stats)) => crate::commands::stats::print_report(&stats, days),
+                    Some(Err(e)) => eprintln!("Failed to read usage statistics: {}", e),
+                    None => println!("Usage statistics are not kept for this session."),
+                }
                 Ok(true)
             }
 
             // Other commands
             "/model" => {
-                if parts.len() > 1 {
-                    let model_name = parts[1];
-                    // Validate model exists
-                    if self.config.models.contains_key(model_name) {
-                        self.config.default_model = model_name.to_string();
-                        // Save config
-                        if let Err(e) = save_config(&self.config, None) {
-                            eprintln!("Failed to save config: {}", e);
-                        } else {
-                            println!("Model set to: {}", model_name);
+                self.pick_model(&args)?;
+                Ok(true)
+            }
+            "/agent" => {
+                let name = parts.get(1).copied().unwrap_or("");
+                if name.is_empty() {
+                    self.print_agents(soul);
+                    return Ok(true);
+                }
+                if name == "default" {
+                    soul.reset_persona();
+                    println!("Switched back to the default agent");
+                } else {
+                    match soul.use_persona(name) {
+                        Ok(agent) => println!("Switched to agent: {}", agent.name),
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(true);
                         }
-                    } else {
-                        eprintln!("Unknown model: {}", model_name);
-                        eprintln!("Available models: {}", 
-                            self.config.models.keys().cloned().collect::<Vec<_>>().join(", "));
                     }
-                } else if self.config.default_model.is_empty() {
-                    println!("No default model set. Use /login to authenticate or /model <name> to set one.");
+                }
+                self.agent_model = soul.persona_model().map(str::to_string);
+                self.current_model = self.agent_model.clone().unwrap_or_else(|| self.config.default_model.clone());
+                self.prompt = Box::new(KimiPrompt::new(self.mode, self.current_model.clone(), self.permissions.clone()));
+                Ok(true)
+            }
+            "/history" => {
+                self.print_history(&args)?;
+                Ok(true)
+            }
+            "/checkpoints" => {
+                self.print_rewind_points(soul);
+                Ok(true)
+            }
+            "/rollback" | "/rewind" => {
+                let Some(arg) = parts.get(1) else {
+                    self.print_rewind_points(soul);
+                    return Ok(true);
+                };
+                let Some(index) = arg.parse::<usize>().ok().and_then(|n| n.checked_sub(1)) else {
+                    eprintln!("Usage: {} [n] [revised instruction]", parts[0]);
+                    return Ok(true);
+                };
+                let Some(discarded) = soul.context.messages_since_checkpoint(index) else {
+                    eprintln!("No checkpoint {} (the conversation has {})", index + 1, soul.rewind_points().len());
+                    return Ok(true);
+                };
+                self.print_discarded(discarded);
+                let dropped = soul.context.diff_checkpoints(index, soul.rewind_points().len());
+
+                // Rewind through the D-Mail system, as the agent would
+                soul.denwa_renji.send_dmail(DMail::new(index, parts[2..].join(" "))).await;
+                let dmail = match soul.deliver_dmail().await {
+                    Ok(Some((rewind, dmail))) => {
+                        println!(
+                            "Rewound to before: {}",
+                            rewind.checkpoint.summary.as_deref().unwrap_or("(no label)")
+                        );
+                        if let Some(diff) = dropped {
+                            println!("{}", theme().muted.paint(format!("Dropped {}", diff.render())));
+                        }
+                        for path in &rewind.restored {
+                            println!("{}", theme().muted.paint(format!("Restored {}", path.display())));
+                        }
+                        for (path, error) in &rewind.failed {
+                            eprintln!("Could not restore {}: {}", path.display(), error);
+                        }
+                        dmail
+                    }
+                    Ok(None) => return Ok(true),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return Ok(true);
+                    }
+                };
+                if !dmail.message.is_empty() {
+                    self.process_message_with_soul(&dmail.message, soul).await?;
+                }
+                Ok(true)
+            }
+            "/undo" => {
+                let count = match parts.get(1) {
+                    Some(arg) => match arg.parse::<usize>() {
+                        Ok(count) if count > 0 => count,
+                        _ => {
+                            eprintln!("Usage: /undo [n]");
+                            return Ok(true);
+                        }
+                    },
+                    None => 1,
+                };
+                if soul.file_edits().is_empty() {
+                    println!("No file changes to undo.");
+                    return Ok(true);
+                }
+                let undo = soul.undo(count);
+                println!("Undid {} file change(s)", undo.undone);
+                for path in &undo.restored {
+                    println!("{}", theme().muted.paint(format!("Restored {}", path.display())));
+                }
+                for (path, error) in &undo.failed {
+                    eprintln!("Could not restore {}: {}", path.display(), error);
+                }
+                let changed = soul.changed_files();
+                if !changed.is_empty() {
+                    println!("\nStill changed this session:");
+                    for path in changed {
+                        println!("  {}", path.display());
+                    }
+                }
+                Ok(true)
+            }
+            "/diff" => {
+                let work_dir = self.cli.effective_work_dir();
+                let diff = match parts.get(1).copied() {
+                    None => soul.edits_diff(&work_dir),
+                    Some("head") => match self.head_diff(&soul.changed_files()) {
+                        Ok(diff) => diff,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            return Ok(true);
+                        }
+                    },
+                    Some(_) => {
+                        eprintln!("Usage: /diff [head]");
+                        return Ok(true);
+                    }
+                };
+                if diff.is_empty() {
+                    println!("No file changes this session.");
                 } else {
-                    println!("Current model: {}", self.config.default_model);
-                    if let Some(model) = self.config.models.get(&self.config.default_model) {
-                        println!("  Provider: {}", model.provider);
-                        if let Some(max_tokens) = model.max_tokens {
-                            println!("  Max tokens: {}", max_tokens);
+                    print!("{}", render_diff(&diff, self.cli.color_enabled()));
+                }
+                Ok(true)
+            }
+            "/commit" => {
+                let mut options = CommitOptions::default();
+                for flag in &parts[1..] {
+                    match *flag {
+                        "--amend" => options.amend = true,
+                        "--signoff" | "-s" => options.signoff = true,
+                        _ => {
+                            eprintln!("Usage: /commit [--amend] [--signoff]");
+                            return Ok(true);
+                        }
+                    }
+                }
+                if let Err(e) = self.run_commit(soul, options).await {
+                    eprintln!("{} {}", theme().error.paint("Commit failed:"), e);
+                }
+                Ok(true)
+            }
+            "/copy" => {
+                let last = soul.context.messages().iter().rev()
+                    .find(|m| matches!(m.role, Role::Assistant) && !m.content.trim().is_empty());
+                let Some(last) = last else {
+                    println!("No response to copy yet.");
+                    return Ok(true);
+                };
+                let text = match parts.get(1).copied() {
+                    None => last.content.clone(),
+                    Some("code") => {
+                        let blocks = clipboard::code_blocks(&last.content);
+                        if blocks.is_empty() {
+                            println!("The last response has no code blocks.");
+                            return Ok(true);
+                        }
+                        blocks.join("\n\n")
+                    }
+                    Some(_) => {
+                        eprintln!("Usage: /copy [code]");
+                        return Ok(true);
+                    }
+                };
+                match clipboard::copy(&text) {
+                    Ok(Method::Command(_)) => println!("Copied {} characters to the clipboard.", text.chars().count()),
+                    Ok(Method::Osc52) => println!(
+                        "Sent {} characters to the terminal's clipboard (needs a terminal with OSC 52 support).",
+                        text.chars().count()
+                    ),
+                    Err(e) => eprintln!("Could not copy: {}", e),
+                }
+                Ok(true)
+            }
+            "/editor" => {
+                let content = match edit_text(&args, Some("md")) {
+                    Ok(content) => content.trim().to_string(),
+                    Err(e) => {
+                        eprintln!("{} {}", theme().error.paint("Editor failed:"), e);
+                        return Ok(true);
+                    }
+                };
+                if content.is_empty() {
+                    println!("Nothing to send.");
+                    return Ok(true);
+                }
+                match self.mode {
+                    ShellMode::Agent => self.process_message_with_soul(&content, soul).await?,
+                    ShellMode::Shell => {
+                        self.execute_shell_command(&content).await?;
+                    }
+                }
+                Ok(true)
+            }
+            "/pin" => {
+                match parts.get(1).copied() {
+                    Some("list") => self.print_pinned(soul),
+                    arg => {
+                        let id = match arg {
+                            Some(id) => Some(id.to_string()),
+                            None => soul.context.last_message().and_then(|m| m.id()).map(str::to_string),
+                        };
+                        let Some(id) = id else {
+                            println!("Nothing to pin yet.");
+                            return Ok(true);
+                        };
+                        match soul.context.pin_message(&id) {
+                            Ok(()) => self.print_pinned(soul),
+                            Err(e) => eprintln!("{}", e),
                         }
                     }
                 }
                 Ok(true)
             }
+            "/unpin" => {
+                let Some(id) = parts.get(1) else {
+                    eprintln!("Usage: /unpin <id>");
+                    return Ok(true);
+                };
+                if soul.context.unpin_message(id) {
+                    println!("Unpinned message {}.", id);
+                } else {
+                    println!("Message {} is not pinned.", id);
+                }
+                Ok(true)
+            }
             "/models" => {
-                if self.config.models.is_empty() {
+                let entries = ModelEntry::from_config(&self.config);
+                if entries.is_empty() {
                     println!("No models configured. Use /login to authenticate.");
                 } else {
-                    println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Available Models:"));
-                    for (name, model) in &self.config.models {
-                        let marker = if name == &self.config.default_model {
-                            Style::new().fg(Color::Green).paint(" (default)")
-                        } else {
-                            Style::new().fg(Color::DarkGray).paint("")
-                        };
-                        println!("  {}{}", name, marker);
-                        println!("    Provider: {}", model.provider);
-                        if let Some(max_tokens) = model.max_tokens {
-                            println!("    Max tokens: {}", max_tokens);
-                        }
+                    println!("\n{}", theme().heading.paint("Available Models:"));
+                    for (index, entry) in entries.iter().enumerate() {
+                        println!("{}", entry.format(index + 1, entry.key == self.config.default_model));
                     }
                 }
                 Ok(true)
             }
             "/tools" => {
-                println!("Available tools:");
-                for name in soul.toolset.tool_names() {
-                    println!("  - {}", name);
-                }
+                crate::commands::tools::print_list(&soul.toolset);
                 Ok(true)
             }
             "/mcp" => {
-                if let Err(e) = self.show_mcp_servers().await {
+                if let Err(e) = self.show_mcp_servers(soul).await {
                     eprintln!("{} {}", 
-                        Style::new().fg(Color::Red).paint("Error:"),
+                        theme().error.paint("Error:"),
                         e
                     );
                 }
                 Ok(true)
             }
             "/web" => {
-                println!("{}", Style::new().fg(Color::Cyan).paint("Web UI is not available in this version."));
+                println!("{}", theme().accent.paint("Web UI is not available in this version."));
                 println!("Visit https://kimi.moonshot.cn for the web interface.");
                 Ok(true)
             }
             "/init" => {
-                println!("{}", Style::new().fg(Color::Cyan).paint("Initializing codebase analysis..."));
-                println!("This would analyze the codebase and generate AGENTS.md");
-                println!("(Full implementation pending)");
+                if let Err(e) = self.run_init(soul).await {
+                    eprintln!("{} {}", 
+                        theme().error.paint("Error:"),
+                        e
+                    );
+                }
+                Ok(true)
+            }
+            "/memory" => {
+                self.show_memory(soul);
                 Ok(true)
             }
             "/mode" => {
                 self.switch_mode();
                 Ok(true)
             }
+            _ if cmd.starts_with("/flow:") => {
+                let args = parse_slash_command(input).map(|(_, args)| args).unwrap_or("");
+                self.run_flow(&cmd["/flow:".len()..], args, soul).await?;
+                Ok(true)
+            }
             _ => {
-                println!("Unknown command: {}. Type /help for available commands.", cmd);
+                let custom = parse_slash_command(input)
+                    .and_then(|(name, args)| Some((soul.slash_commands.get(name)?.prompt.clone()?, args)));
+                match custom {
+                    Some((command, args)) => self.run_custom_command(&command, args, soul).await?,
+                    None => println!("Unknown command: {}. Type /help for available commands.", cmd),
+                }
                 Ok(true)
             }
         }
     }
 
-    async fn process_message_with_soul(
-        &mut self,
-        message: &str,
-        soul: &mut KimiSoul,
-    ) -> UIResult<()> {
+    /// Create the LLM provider for the current config, printing guidance
+    /// and returning `None` when the user still has to log in
+    async fn create_provider(&self) -> UIResult<Option<Box<dyn ChatProvider>>> {
         // Check if a model is configured
         if self.config.default_model.is_empty() || self.config.models.is_empty() {
             println!("\n{}", 
-                Style::new().fg(Color::Yellow).paint("No model configured.")
+                theme().warning.paint("No model configured.")
             );
             println!("{}", 
-                Style::new().fg(Color::DarkGray).paint("Use /login to authenticate and set up a model.")
+                theme().muted.paint("Use /login to authenticate and set up a model.")
             );
-            return Ok(());
+            return Ok(None);
         }
 
-        // Create LLM provider from config
-        let provider = match llm::create_provider(&self.config).await {
-            Ok(provider) => provider,
+        // Create LLM provider from config, honoring the agent persona's model
+        let provider = match &self.agent_model {
+            Some(model) => llm::create_provider_for_model(&self.config, model).await,
+            None => llm::create_provider(&self.config).await,
+        };
+        match provider {
+            Ok(provider) => Ok(Some(provider)),
             Err(LlmError::NoProvider) => {
                 println!("\n{}", 
-                    Style::new().fg(Color::Yellow).paint("No provider configured.")
+                    theme().warning.paint("No provider configured.")
                 );
                 println!("{}", 
-                    Style::new().fg(Color::DarkGray).paint("Use /login to authenticate and set up a model.")
+                    theme().muted.paint("Use /login to authenticate and set up a model.")
                 );
-                return Ok(());
+                Ok(None)
             }
             Err(LlmError::MissingToken) => {
                 println!("\n{}", 
-                    Style::new().fg(Color::Yellow).paint("Authentication token not found.")
+                    theme().warning.paint("Authentication token not found.")
                 );
                 println!("{}", 
-                    Style::new().fg(Color::DarkGray).paint("Use /login to authenticate.")
+                    theme().muted.paint("Use /login to authenticate.")
                 );
-                return Ok(());
+                Ok(None)
             }
             Err(e) => {
-                return Err(UIError::Core(format!("Failed to create LLM provider: {}", e)));
+                Err(UIError::Core(format!("Failed to create LLM provider: {}", e)))
             }
+        }
+    }
+
+    async fn process_message_with_soul(
+        &mut self,
+        message: &str,
+        soul: &mut KimiSoul,
+    ) -> UIResult<()> {
+        let Some(provider) = self.create_provider().await? else {
+            return Ok(());
         };
+        if let Some(model) = self.config.models.get(&self.current_model) {
+            soul.use_model(model);
+        }
+        soul.fallback_provider = llm::create_fallback_provider(&self.config, soul.failover_handler()).await.unwrap_or_else(|e| {
+            warn!("Fallback model unavailable: {}", e);
+            None
+        });
 
         // Create channels for wire communication
         let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
         let (_approval_tx, mut approval_rx) = mpsc::channel::<(WireMessage, mpsc::Sender<ApprovalKind>)>(10);
 
-        // Create user input
+        // Create user input, attaching the files given on the command line,
+        // mentioned as @path or dropped into the terminal
+        let mut attachments = std::mem::take(&mut self.initial_attachments);
+        let mentions = attachment::mentioned_files(message, &self.cli.effective_work_dir(), soul.workspace.as_ref());
+        for mention in mentions.into_iter().chain(attachment::dropped_files(message)) {
+            match mention {
+                Ok(attachment) if !attachments.contains(&attachment) => attachments.push(attachment),
+                Ok(_) => {}
+                Err(e) => eprintln!("{}", theme().warning.paint(format!("Not attached: {}", e))),
+            }
+        }
+        if !attachments.is_empty() {
+            println!("{}", theme().muted.paint(format!("[Attached {} file(s)]", attachments.len())));
+        }
         let user_input = UserInput {
             text: message.to_string(),
-            attachments: vec![],
+            attachments,
         };
 
         // Create wire soul side that sends to our mpsc channel
@@ -875,7 +1298,8 @@ impl ShellUI {
         // The LLM processing future sends messages through the wire
         let llm_future = async {
             match soul.process_with_llm(provider.as_ref(), user_input, &wire_soul).await {
-                Ok(_) => {
+                // Interrupted with Ctrl+C; what was streamed stays in the context
+                Ok(_) | Err(SoulError::Cancelled) => {
                     let _ = ui_tx.send(WireMessage::TurnEnd).await;
                     Ok(())
                 }
@@ -889,16 +1313,195 @@ impl ShellUI {
             }
         };
 
-        // Run both futures concurrently
-        tokio::select! {
-            result = llm_future => {
-                result?;
-            }
-            result = self.run_ui_loop(&mut ui_rx, &mut approval_rx) => {
-                result?;
+        // Run both futures concurrently, the UI loop until it has shown the
+        // turn's end
+        let (result, ui_result) = tokio::join!(llm_future, self.run_ui_loop(&mut ui_rx, &mut approval_rx));
+        ui_result?;
+        result
+    }
+
+    /// Replace the older turns with an LLM summary, following `instructions` if given
+    async fn run_compact(&mut self, soul: &mut KimiSoul, instructions: &str) -> UIResult<()> {
+        let Some(provider) = self.create_provider().await? else {
+            return Ok(());
+        };
+
+        println!("{}", theme().accent.paint("Summarizing the conversation..."));
+        let instructions = Some(instructions).filter(|i| !i.is_empty());
+        let report = soul
+            .compact_with_llm(provider.as_ref(), instructions)
+            .await
+            .map_err(|e| UIError::Core(e.to_string()))?;
+
+        if report.removed == 0 {
+            println!("{}", theme().muted.paint("Nothing to compact yet."));
+            return Ok(());
+        }
+        println!("{} Summarized {} messages:\n",
+            theme().success.paint("Context compacted."),
+            report.removed
+        );
+        println!("{}", theme().muted.paint(&report.summary));
+        Ok(())
+    }
+
+    /// Have the model write a commit message for the changes in the working
+    /// directory and commit with it once the user approves or edits it
+    async fn run_commit(&mut self, soul: &KimiSoul, mut options: CommitOptions) -> UIResult<()> {
+        let work_dir = self.cli.effective_work_dir();
+        let changes = git::changes(&work_dir, options.amend).map_err(|e| UIError::Core(e.to_string()))?;
+        if !changes.staged {
+            println!("{}", theme().muted.paint("Nothing is staged; committing all changes to tracked files."));
+            options.all = true;
+        }
+        println!("{}\n", theme().muted.paint(&changes.stat));
+
+        let Some(provider) = self.create_provider().await? else {
+            return Ok(());
+        };
+        println!("{}", theme().accent.paint("Writing a commit message..."));
+        let mut message = soul
+            .write_commit_message(provider.as_ref(), &changes)
+            .await
+            .map_err(|e| UIError::Core(e.to_string()))?;
+
+        loop {
+            println!("\n{}", theme().heading.paint("Commit message:"));
+            println!("{}", message.trim_end());
+            print!(
+                "\n  {} ",
+                theme().emphasis.paint("Commit with this message? [y]es, [e]dit, [n]o:")
+            );
+            use std::io::Write;
+            std::io::stdout().flush().map_err(UIError::Io)?;
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input).map_err(UIError::Io)?;
+
+            match input.trim().to_lowercase().as_str() {
+                "y" | "yes" => break,
+                "e" | "edit" => match edit_text(&message, Some("txt")) {
+                    Ok(edited) if edited.trim().is_empty() => {
+                        println!("{}", theme().warning.paint("Empty message; nothing committed."));
+                        return Ok(());
+                    }
+                    Ok(edited) => message = edited,
+                    Err(e) => eprintln!("{} {}", theme().error.paint("Editor failed:"), e),
+                },
+                _ => {
+                    println!("{}", theme().muted.paint("Nothing committed."));
+                    return Ok(());
+                }
             }
         }
 
+        let summary = git::commit(&work_dir, &message, &options).map_err(|e| UIError::Core(e.to_string()))?;
+        println!("{}", theme().success.paint("Committed."));
+        if !summary.is_empty() {
+            println!("{}", theme().muted.paint(summary));
+        }
+        Ok(())
+    }
+
+    /// Analyze the project with read-only tools and write AGENTS.md, then show the diff
+    async fn run_init(&mut self, soul: &mut KimiSoul) -> UIResult<()> {
+        let Some(provider) = self.create_provider().await? else {
+            return Ok(());
+        };
+
+        println!("{}", theme().accent.paint("Analyzing the codebase to generate AGENTS.md..."));
+
+        let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
+        let (_approval_tx, mut approval_rx) = mpsc::channel::<(WireMessage, mpsc::Sender<ApprovalKind>)>(10);
+        let wire_soul = kimi_core::soul::WireSoulSide::with_sender(ui_tx.clone());
+        let work_dir = self.cli.effective_work_dir();
+
+        let init_future = async {
+            let result = soul.run_init(provider.as_ref(), &work_dir, &wire_soul).await;
+            let _ = ui_tx.send(WireMessage::TurnEnd).await;
+            result
+        };
+        let (result, ui_result) = tokio::join!(init_future, self.run_ui_loop(&mut ui_rx, &mut approval_rx));
+        ui_result?;
+        let report = result.map_err(|e| UIError::Core(e.to_string()))?;
+
+        let verb = if report.created { "Created" } else { "Updated" };
+        println!("\n{} {} ({}, {})",
+            theme().success.bold().paint(verb),
+            report.path.display(),
+            theme().diff_added.paint(format!("+{}", report.stat.insertions)),
+            theme().diff_removed.paint(format!("-{}", report.stat.deletions))
+        );
+        if report.diff.is_empty() {
+            println!("{}", theme().muted.paint("No changes."));
+        }
+        print!("{}", render_diff(&report.diff, self.cli.color_enabled()));
+
+        Ok(())
+    }
+
+    /// Send a custom command's prompt to the agent, using the command's model if it sets one
+    async fn run_custom_command(
+        &mut self,
+        command: &CustomCommand,
+        args: &str,
+        soul: &mut KimiSoul,
+    ) -> UIResult<()> {
+        let provider = match &command.model {
+            Some(model) => llm::create_provider_for_model(&self.config, model)
+                .await
+                .map_err(|e| UIError::Core(format!("Failed to create provider for model '{}': {}", model, e)))?,
+            None => match self.create_provider().await? {
+                Some(provider) => provider,
+                None => return Ok(()),
+            },
+        };
+
+        let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
+        let (_approval_tx, mut approval_rx) = mpsc::channel::<(WireMessage, mpsc::Sender<ApprovalKind>)>(10);
+        let wire_soul = kimi_core::soul::WireSoulSide::with_sender(ui_tx.clone());
+        let work_dir = self.cli.effective_work_dir();
+
+        let command_future = async {
+            let result = soul.run_custom_command(provider.as_ref(), command, args, &work_dir, &wire_soul).await;
+            if let Err(e) = &result {
+                let _ = ui_tx.send(WireMessage::TextPart { text: format!("Error: {}", e) }).await;
+            }
+            let _ = ui_tx.send(WireMessage::TurnEnd).await;
+            result
+        };
+        let (result, ui_result) = tokio::join!(command_future, self.run_ui_loop(&mut ui_rx, &mut approval_rx));
+        ui_result?;
+        result.map_err(|e| UIError::Core(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Run a flow-type skill from Begin to End
+    async fn run_flow(&mut self, name: &str, args: &str, soul: &mut KimiSoul) -> UIResult<()> {
+        let Some(runner) = soul.skills.iter().find(|skill| skill.name == name).and_then(FlowRunner::from_skill) else {
+            println!("No flow skill named '{}'. Type /help to list flows.", name);
+            return Ok(());
+        };
+        let Some(provider) = self.create_provider().await? else {
+            return Ok(());
+        };
+
+        let (ui_tx, mut ui_rx) = mpsc::channel::<WireMessage>(100);
+        let (_approval_tx, mut approval_rx) = mpsc::channel::<(WireMessage, mpsc::Sender<ApprovalKind>)>(10);
+        let wire_soul = kimi_core::soul::WireSoulSide::with_sender(ui_tx.clone());
+
+        let flow_future = async {
+            let result = runner.run(soul, provider.as_ref(), args, &wire_soul).await;
+            if let Err(e) = &result {
+                let _ = ui_tx.send(WireMessage::TextPart { text: format!("Error: {}", e) }).await;
+            }
+            let _ = ui_tx.send(WireMessage::TurnEnd).await;
+            result
+        };
+        let (result, ui_result) = tokio::join!(flow_future, self.run_ui_loop(&mut ui_rx, &mut approval_rx));
+        ui_result?;
+        result.map_err(|e| UIError::Core(e.to_string()))?;
+
         Ok(())
     }
 
@@ -908,29 +1511,47 @@ impl ShellUI {
         approval_rx: &mut mpsc::Receiver<(WireMessage, mpsc::Sender<ApprovalKind>)>,
     ) -> UIResult<()> {
         // Print assistant prefix
-        print!("\n{} ", Style::new().bold().fg(Color::Blue).paint("Kimi:"));
+        print!("\n{} ", theme().agent.paint("Kimi:"));
+        let mut markdown = MarkdownRenderer::new(self.cli.color_enabled());
+        let mut status = StatusLine::new(self.cli.color_enabled() && std::io::stdout().is_terminal());
+        let mut ticks = tokio::time::interval(STATUS_TICK);
+        let mut interrupted = false;
+        let started = std::time::Instant::now();
         
         loop {
             tokio::select! {
-                Some(msg) = ui_rx.recv() => {
+                msg = ui_rx.recv() => {
+                    let Some(msg) = msg else { break };
+                    status.clear().map_err(UIError::Io)?;
+                    status.observe(&msg);
+                    if let Some(log) = &self.event_log {
+                        log.record(&msg);
+                    }
+                    if let Some(transcript) = &self.transcript {
+                        transcript.record(&msg);
+                    }
+                    // Anything else ends the text streamed so far
+                    if !matches!(msg, WireMessage::TextPart { .. }) {
+                        print!("{}", markdown.finish());
+                    }
                     match msg {
                         WireMessage::TextPart { text } => {
-                            print!("{}", text);
+                            print!("{}", markdown.push(&text));
                             std::io::Write::flush(&mut std::io::stdout()).map_err(UIError::Io)?;
                         }
                         WireMessage::ThinkPart { text } => {
                             // Display thinking in dimmed color
-                            print!("{}", Style::new().fg(Color::DarkGray).paint(text));
+                            print!("{}", theme().muted.paint(text));
                             std::io::Write::flush(&mut std::io::stdout()).map_err(UIError::Io)?;
                         }
                         WireMessage::ToolCall { name, arguments, .. } => {
                             println!("\n{} {}", 
-                                Style::new().fg(Color::Yellow).paint("[Tool Call:]"),
-                                Style::new().bold().paint(&name)
+                                theme().tool.paint("[Tool Call:]"),
+                                theme().emphasis.paint(&name)
                             );
                             if !arguments.is_empty() {
                                 println!("  {}: {}",
-                                    Style::new().fg(Color::DarkGray).paint("Arguments"),
+                                    theme().muted.paint("Arguments"),
                                     arguments
                                 );
                             }
@@ -938,26 +1559,86 @@ impl ShellUI {
                         WireMessage::ToolResult { output, is_error, .. } => {
                             if is_error {
                                 println!("{} {}",
-                                    Style::new().fg(Color::Red).paint("[Tool Error:]"),
+                                    theme().error.paint("[Tool Error:]"),
                                     output
                                 );
                             } else {
                                 println!("{} {}",
-                                    Style::new().fg(Color::Green).paint("[Tool Result:]"),
+                                    theme().success.paint("[Tool Result:]"),
                                     output
                                 );
                             }
                         }
+                        WireMessage::ToolOutputPart { stream, text, .. } => {
+                            // Show a running tool's output as it comes, dimmed
+                            let style = match stream {
+                                OutputStream::Stdout => theme().muted,
+                                OutputStream::Stderr => theme().warning,
+                            };
+                            for line in text.lines() {
+                                println!("  {} {}", theme().muted.paint("│"), style.paint(line));
+                            }
+                        }
                         WireMessage::TurnEnd => {
                             println!(); // New line after response
+                            let elapsed = started.elapsed();
+                            if !interrupted && self.notifier.due(elapsed) {
+                                self.notifier.notify("Kimi finished", &format!("The turn took {}s", elapsed.as_secs()));
+                            }
                             break;
                         }
                         WireMessage::StepBegin { n } => {
                             debug!("Step {} began", n);
                         }
+                        WireMessage::SkillActivated { name } => {
+                            println!("{}",
+                                theme().skill.paint(format!("[Skill: {}]", name))
+                            );
+                        }
+                        WireMessage::FileChanged { path, changed } => {
+                            println!("{}",
+                                theme().muted.paint(format!("[Watched {} changed: {}]", path, changed.join(", ")))
+                            );
+                        }
+                        WireMessage::ProviderFailover { from, to, reason } => {
+                            println!("\n{}",
+                                theme().warning.paint(format!("[{} failed ({}); retrying on {}]", from, reason, to))
+                            );
+                        }
+                        WireMessage::SubagentEvent { event, .. } => {
+                            if let WireMessage::ToolBegin { name, .. } = *event {
+                                println!("{}",
+                                    theme().muted.paint(format!("  [Sub-agent tool: {}]", name))
+                                );
+                            }
+                        }
+                        WireMessage::SubagentResult { name, summary, is_error, .. } => {
+                            let style = if is_error { theme().error } else { theme().skill };
+                            println!("\n{}\n{}",
+                                style.paint(format!("[Sub-agent: {}]", name)),
+                                summary
+                            );
+                        }
+                        WireMessage::FlowStep { node_id, label, .. } => {
+                            println!("\n{} {}",
+                                theme().skill.paint(format!("[Flow: {}]", node_id)),
+                                theme().emphasis.paint(&label)
+                            );
+                        }
+                        WireMessage::FlowDecision { choice, .. } => {
+                            println!("\n{} {}",
+                                theme().skill.paint("[Flow choice:]"),
+                                choice
+                            );
+                        }
+                        WireMessage::FlowEnd { flow } => {
+                            println!("\n{}",
+                                theme().skill.paint(format!("[Flow {} finished]", flow))
+                            );
+                        }
                         WireMessage::StepInterrupted => {
                             println!("\n{}", 
-                                Style::new().fg(Color::Yellow).paint("[Step interrupted]")
+                                theme().warning.paint("[Step interrupted]")
                             );
                         }
                         WireMessage::StatusUpdate { context_usage, token_usage, .. } => {
@@ -968,56 +1649,156 @@ impl ShellUI {
                                 debug!("Tokens: {} in / {} out", tokens.input_tokens, tokens.output_tokens);
                             }
                         }
+                        WireMessage::ApprovalRequest { action, description, diff, .. } => {
+                            if self.notifier.due(started.elapsed()) {
+                                self.notifier.notify("Kimi needs approval", &action);
+                            }
+                            self.answer_approval(&action, &description, diff.as_deref()).await?;
+                        }
                         _ => {}
                     }
                 }
                 Some((msg, response_tx)) = approval_rx.recv() => {
-                    if let WireMessage::ApprovalRequest { action, description, .. } = msg {
-                        let approved = self.handle_approval_request(&action, &description).await?;
+                    status.clear().map_err(UIError::Io)?;
+                    if let WireMessage::ApprovalRequest { action, description, diff, .. } = msg {
+                        if self.notifier.due(started.elapsed()) {
+                            self.notifier.notify("Kimi needs approval", &action);
+                        }
+                        let approved = match self.handle_approval_request(&action, &description, diff.as_deref(), false, None).await? {
+                            ApprovalChoice::Answer(kind) => kind,
+                            ApprovalChoice::Edit => ApprovalKind::Reject,
+                        };
                         let _ = response_tx.send(approved).await;
                     }
                 }
-                else => break,
+                _ = tokio::signal::ctrl_c() => {
+                    status.clear().map_err(UIError::Io)?;
+                    match &self.interrupt {
+                        Some(interrupt) if !interrupted => {
+                            interrupted = true;
+                            interrupt.interrupt();
+                            println!("\n{}",
+                                theme().warning.paint("[Interrupting; press Ctrl+C again to quit]")
+                            );
+                        }
+                        _ => {
+                            println!();
+                            std::process::exit(ExitCode::Interrupted.code());
+                        }
+                    }
+                }
+                _ = ticks.tick() => status.tick().map_err(UIError::Io)?,
             }
         }
+        status.clear().map_err(UIError::Io)?;
 
         Ok(())
     }
 
+    /// Ask the user about the soul's pending approval request and answer
+    /// it, letting them rewrite a proposed file change in their editor
+    async fn answer_approval(&self, action: &str, description: &str, diff: Option<&str>) -> UIResult<()> {
+        let Some(approval) = &self.permissions else {
+            warn!("No approval to answer the request for {} with", action);
+            return Ok(());
+        };
+        let pending = approval.get_pending().await;
+        let always = pending.as_ref().and_then(Approval::always_scope);
+        let edit = pending.and_then(|request| request.edit);
+
+        let result = loop {
+            match self.handle_approval_request(action, description, diff, edit.is_some(), always.as_deref()).await? {
+                ApprovalChoice::Answer(kind) => break approval.respond(kind).await,
+                ApprovalChoice::Edit => {
+                    let Some(edit) = &edit else { continue };
+                    let extension = std::path::Path::new(&edit.path).extension().and_then(|e| e.to_str());
+                    match edit_text(&edit.new, extension) {
+                        Ok(content) if content == edit.new => {
+                            println!("  {}\n", theme().warning.paint("✓ Unchanged, approved once"));
+                            break approval.respond(ApprovalKind::ApproveOnce).await;
+                        }
+                        Ok(content) => {
+                            println!("  {}\n", theme().success.paint("✓ Approved with your edits"));
+                            break approval.respond_with_arguments(edit.arguments_with(&content)).await;
+                        }
+                        // Ask again, so the change can still be answered
+                        Err(e) => eprintln!("  {}", theme().error.paint(e.to_string())),
+                    }
+                }
+            }
+        };
+        if let Err(e) = result {
+            warn!("Failed to answer the approval request: {}", e);
+        }
+        Ok(())
+    }
+
     async fn handle_approval_request(
         &self,
         action: &str,
         description: &str,
-    ) -> UIResult<ApprovalKind> {
+        diff: Option<&str>,
+        can_edit: bool,
+        always: Option<&str>,
+    ) -> UIResult<ApprovalChoice> {
         println!();
-        println!("{}", Style::new().bold().fg(Color::Yellow).paint("╔══════════════════════════════════════════════════════════════╗"));
-        println!("{}", Style::new().bold().fg(Color::Yellow).paint("║                    🔒 APPROVAL REQUIRED                      ║"));
-        println!("{}", Style::new().bold().fg(Color::Yellow).paint("╚══════════════════════════════════════════════════════════════╝"));
+        println!("{}", theme().warning.bold().paint("╔══════════════════════════════════════════════════════════════╗"));
+        println!("{}", theme().warning.bold().paint("║                    🔒 APPROVAL REQUIRED                      ║"));
+        println!("{}", theme().warning.bold().paint("╚══════════════════════════════════════════════════════════════╝"));
         println!();
         println!("  {}: {}", 
-            Style::new().bold().paint("Tool"), 
-            Style::new().fg(Color::Cyan).paint(action)
+            theme().emphasis.paint("Tool"), 
+            theme().accent.paint(action)
         );
         println!("  {}: {}", 
-            Style::new().bold().paint("Action"), 
+            theme().emphasis.paint("Action"), 
             description
         );
+        if let Some(diff) = diff.filter(|d| !d.is_empty()) {
+            let stat = kimi_core::diff::DiffStat::from_unified(diff);
+            println!("  {}: {} {}",
+                theme().emphasis.paint("Changes"),
+                theme().diff_added.paint(format!("+{}", stat.insertions)),
+                theme().diff_removed.paint(format!("-{}", stat.deletions))
+            );
+            println!();
+            let rendered = render_diff(diff, self.cli.color_enabled());
+            let lines: Vec<&str> = rendered.lines().collect();
+            for line in lines.iter().take(MAX_DIFF_LINES) {
+                println!("    {}", line);
+            }
+            if lines.len() > MAX_DIFF_LINES {
+                println!("    {}", theme().muted.paint(format!("... {} more lines", lines.len() - MAX_DIFF_LINES)));
+            }
+        }
         println!();
-        println!("  {}", Style::new().fg(Color::DarkGray).paint("Options:"));
+        println!("  {}", theme().muted.paint("Options:"));
         println!("    {} - {}", 
-            Style::new().bold().fg(Color::Green).paint("y"),
+            theme().success.bold().paint("y"),
             Style::new().paint("Yes, approve this action")
         );
         println!("    {} - {}", 
-            Style::new().bold().fg(Color::Red).paint("n"),
+            theme().error.bold().paint("n"),
             Style::new().paint("No, reject this action")
         );
         println!("    {} - {}", 
-            Style::new().bold().fg(Color::Yellow).paint("o"),
+            theme().warning.bold().paint("o"),
             Style::new().paint("Once, approve this time only")
         );
+        if let Some(always) = always {
+            println!("    {} - {}",
+                theme().success.bold().paint("a"),
+                Style::new().paint(format!("Always, approve {} for this session", always))
+            );
+        }
+        if can_edit {
+            println!("    {} - {}", 
+                theme().accent.bold().paint("e"),
+                Style::new().paint("Edit the change in $EDITOR, then apply it")
+            );
+        }
         println!();
-        print!("  {} ", Style::new().bold().paint("Your choice:"));
+        print!("  {} ", theme().emphasis.paint("Your choice:"));
         
         // Flush stdout to ensure prompt appears
         use std::io::Write;
@@ -1031,86 +1812,612 @@ impl ShellUI {
         
         match choice.as_str() {
             "y" | "yes" => {
-                println!("  {}\n", Style::new().fg(Color::Green).paint("✓ Approved"));
-                Ok(ApprovalKind::Approve)
+                println!("  {}\n", theme().success.paint("✓ Approved"));
+                Ok(ApprovalChoice::Answer(ApprovalKind::Approve))
             }
             "o" | "once" => {
-                println!("  {}\n", Style::new().fg(Color::Yellow).paint("✓ Approved once"));
-                Ok(ApprovalKind::ApproveOnce)
+                println!("  {}\n", theme().warning.paint("✓ Approved once"));
+                Ok(ApprovalChoice::Answer(ApprovalKind::ApproveOnce))
+            }
+            "a" | "always" if always.is_some() => {
+                println!("  {}\n", theme().success.paint("✓ Approved for this session"));
+                Ok(ApprovalChoice::Answer(ApprovalKind::ApproveAlways))
             }
+            "e" | "edit" if can_edit => Ok(ApprovalChoice::Edit),
             _ => {
-                println!("  {}\n", Style::new().fg(Color::Red).paint("✗ Rejected"));
-                Ok(ApprovalKind::Reject)
+                println!("  {}\n", theme().error.paint("✗ Rejected"));
+                Ok(ApprovalChoice::Answer(ApprovalKind::Reject))
             }
         }
     }
 
+    /// Unified diff of `paths` from their content in git's `HEAD` to now
+    fn head_diff(&self, paths: &[&std::path::Path]) -> UIResult<String> {
+        let work_dir = self.cli.effective_work_dir();
+        let git = |args: &[&str]| std::process::Command::new("git").args(args).current_dir(&work_dir).output();
+        let head = git(&["rev-parse", "--verify", "--quiet", "HEAD"]).map_err(UIError::Io)?;
+        if !head.status.success() {
+            return Err(UIError::InvalidInput(format!("{} has no git HEAD to compare with", work_dir.display())));
+        }
+
+        let mut out = String::new();
+        for path in paths {
+            let name = path.strip_prefix(&work_dir).unwrap_or(path).display().to_string();
+            // Paths starting with ./ are relative to the working directory
+            let old = git(&["show", &format!("HEAD:./{}", name)]).map_err(UIError::Io)?;
+            let old = old.status.success().then(|| String::from_utf8_lossy(&old.stdout).into_owned());
+            let new = std::fs::read(path).ok().map(|content| String::from_utf8_lossy(&content).into_owned());
+            let label = |side: &str, content: &Option<String>| match content {
+                Some(_) => format!("{}/{}", side, name),
+                None => "/dev/null".to_string(),
+            };
+            out.push_str(&unified_diff(
+                old.as_deref().unwrap_or_default(),
+                new.as_deref().unwrap_or_default(),
+                &label("a", &old),
+                &label("b", &new),
+                DIFF_CONTEXT,
+            ));
+        }
+        Ok(out)
+    }
+
+    /// List the session's exchanges, those matching a query, or show one
+    /// in full
+    ///
+    /// They are read from the event log, so turns since dropped from the
+    /// context by compaction or `/rewind` are included.
+    fn print_history(&self, args: &str) -> UIResult<()> {
+        let Some(log) = &self.event_log else {
+            println!("This session has no event log to read the history from.");
+            return Ok(());
+        };
+        let events = event_log::read_events(&log.path()).map_err(|e| UIError::Core(e.to_string()))?;
+        let exchanges = event_log::exchanges(&events);
+        if exchanges.is_empty() {
+            println!("No exchanges yet.");
+            return Ok(());
+        }
+
+        if let Ok(n) = args.parse::<usize>() {
+            let Some(exchange) = n.checked_sub(1).and_then(|i| exchanges.get(i)) else {
+                eprintln!("No exchange {} (the session has {})", n, exchanges.len());
+                return Ok(());
+            };
+            println!("\n{} {}",
+                theme().heading.paint(format!("Exchange {}", n)),
+                theme().muted.paint(exchange.timestamp.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            );
+            println!("\n{}\n{}", theme().emphasis.paint("You:"), exchange.prompt.trim());
+            if !exchange.tools.is_empty() {
+                println!("\n{}", theme().muted.paint(format!("[Tools: {}]", exchange.tools.join(", "))));
+            }
+            let mut markdown = MarkdownRenderer::new(self.cli.color_enabled());
+            println!("\n{}", theme().agent.paint("Kimi:"));
+            println!("{}{}\n", markdown.push(exchange.reply.trim()), markdown.finish());
+            return Ok(());
+        }
+
+        let matches: Vec<(usize, &Exchange)> = exchanges
+            .iter()
+            .enumerate()
+            .filter(|(_, exchange)| args.is_empty() || exchange.matches(args))
+            .collect();
+        if matches.is_empty() {
+            println!("{}", theme().warning.paint(format!("No exchanges match '{}'", args)));
+            return Ok(());
+        }
+        println!("\n{}", theme().heading.paint("History:"));
+        for (i, exchange) in matches {
+            println!("  {} {} {}",
+                theme().command.paint(format!("{:>3}", i + 1)),
+                theme().muted.paint(exchange.timestamp.with_timezone(&chrono::Local).format("%H:%M").to_string()),
+                checkpoint_label(&exchange.prompt)
+            );
+            if !exchange.reply.trim().is_empty() {
+                println!("            {}", theme().muted.paint(checkpoint_label(&exchange.reply)));
+            }
+        }
+        println!("\n{}\n", theme().muted.paint("Use /history <n> to show an exchange in full, or /history <text> to search."));
+        Ok(())
+    }
+
+    /// List the checkpoints `/rollback` can go back to, with when they
+    /// were taken and what followed each
+    fn print_rewind_points(&self, soul: &KimiSoul) {
+        let checkpoints = soul.rewind_points();
+        if checkpoints.is_empty() {
+            println!("Nothing to rewind yet.");
+            return;
+        }
+
+        println!("\n{}", theme().heading.paint("Checkpoints:"));
+        for (n, checkpoint) in checkpoints.iter().enumerate() {
+            let time = checkpoint
+                .created_at
+                .map(|time| time.with_timezone(&chrono::Local).format("%H:%M").to_string())
+                .unwrap_or_else(|| "--:--".to_string());
+            println!("  {} {} {}",
+                theme().command.paint(format!("{:>3}", n + 1)),
+                theme().muted.paint(time),
+                checkpoint.summary.as_deref().unwrap_or("(no label)")
+            );
+            if let Some(diff) = soul.context.checkpoint_changes(n) {
+                println!("            {}", theme().muted.paint(diff.render()));
+            }
+        }
+        println!(
+            "\n{}\n",
+            theme()
+                .muted
+                .paint("Use /rollback <n> [revised instruction] to go back to before message n.")
+        );
+    }
+
+    /// Show the messages a rollback discards, as removed lines of a diff
+    fn print_discarded(&self, messages: &[Message]) {
+        println!("\n{}", theme().heading.paint(format!(
+            "Discarding {} message{}:",
+            messages.len(),
+            if messages.len() == 1 { "" } else { "s" }
+        )));
+        for message in messages {
+            let (who, text) = match message.role {
+                Role::User => ("You", checkpoint_label(&message.content)),
+                Role::Assistant if message.content.trim().is_empty() => ("Kimi", "(tool calls)".to_string()),
+                Role::Assistant => ("Kimi", checkpoint_label(&message.content)),
+                Role::Tool => ("Tool", checkpoint_label(&message.content)),
+                Role::System => ("System", checkpoint_label(&message.content)),
+            };
+            println!("{}", theme().diff_removed.paint(format!("- {}: {}", who, text)));
+        }
+        println!();
+    }
+
+    /// Pick the default model from a fuzzy-filtered list
+    ///
+    /// An exact key or a query matching one model switches straight away;
+    /// otherwise the matches are listed to pick from by number or to narrow
+    /// down with more text.
+    fn pick_model(&mut self, query: &str) -> UIResult<()> {
+        let entries = ModelEntry::from_config(&self.config);
+        if entries.is_empty() {
+            println!("No models configured. Use /login to authenticate.");
+            return Ok(());
+        }
+
+        let mut query = query.trim().to_string();
+        loop {
+            if let Some(entry) = entries.iter().find(|entry| entry.key == query) {
+                return self.set_model(&entry.key);
+            }
+            let mut matches = model_picker::filter(&entries, &query);
+            if matches.len() == 1 && !query.is_empty() {
+                return self.set_model(&matches[0].key);
+            }
+            if matches.is_empty() {
+                println!("{}", theme().warning.paint(format!("No models match '{}'", query)));
+                query.clear();
+                matches = model_picker::filter(&entries, "");
+            }
+
+            println!("\n{}", theme().heading.paint("Models:"));
+            for (index, entry) in matches.iter().enumerate() {
+                println!("{}", entry.format(index + 1, entry.key == self.config.default_model));
+            }
+            print!(
+                "\n  {} ",
+                theme().emphasis.paint("Number, text to filter, or Enter to keep the current model:")
+            );
+            use std::io::Write;
+            std::io::stdout().flush().map_err(UIError::Io)?;
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input).map_err(UIError::Io)?;
+
+            let input = input.trim();
+            if input.is_empty() {
+                return Ok(());
+            }
+            if let Some(entry) = input.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| matches.get(i)) {
+                return self.set_model(&entry.key);
+            }
+            query = input.to_string();
+        }
+    }
+
+    /// Apply `change` to the session's config and save it in the global
+    /// config file
+    ///
+    /// The change is made to the file's own config, so that settings merged
+    /// in from a project config are not saved into it.
+    fn save_global_config(&mut self, change: impl Fn(&mut Config)) -> Result<(), ConfigError> {
+        change(&mut self.config);
+        let mut config = load_config(None)?;
+        change(&mut config);
+        config.validate()?;
+        save_config(&config, None)
+    }
+
+    /// Make `key` the default model and save the config
+    fn set_model(&mut self, key: &str) -> UIResult<()> {
+        match self.save_global_config(|config| config.default_model = key.to_string()) {
+            Ok(()) => println!("Model set to: {}", key),
+            // e.g. a model only the project config defines
+            Err(e) => eprintln!("Model set to {} for this session; failed to save config: {}", key, e),
+        }
+        // An agent persona with a model of its own keeps using it
+        if self.agent_model.is_none() {
+            self.current_model = key.to_string();
+            self.prompt = Box::new(KimiPrompt::new(self.mode, self.current_model.clone(), self.permissions.clone()));
+        }
+        Ok(())
+    }
+
+    /// List the permission modes, or switch to one
+    fn handle_permissions(&mut self, mode: Option<&str>, soul: &KimiSoul) {
+        let Some(mode) = mode else {
+            let active = soul.approval.mode();
+            println!("\n{}", theme().heading.paint("Permission modes:"));
+            for mode in PermissionMode::ALL {
+                let marker = if mode == active {
+                    theme().success.paint(" (active)")
+                } else {
+                    theme().muted.paint("")
+                };
+                println!("  {:<13} {}{}", mode.as_str(), mode.description(), marker);
+            }
+            println!();
+            println!("{}", theme().muted.paint("Use /permissions <mode> to switch."));
+            return;
+        };
+        match mode.parse::<PermissionMode>() {
+            Ok(mode) => {
+                soul.approval.set_mode(mode);
+                println!("Permission mode set to {}: {}", mode, mode.description());
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// List the per-tool approval rules and approved Shell commands, set a
+    /// rule with `/approve tool <name> always|ask|never|default`, forget a
+    /// command or save them all to the config
+    fn handle_approve(&mut self, args: &[&str], soul: &KimiSoul) {
+        match args {
+            [] => {
+                let rules = soul.approval.rules();
+                println!("\n{}", theme().heading.paint("Approval rules:"));
+                if rules.is_empty() {
+                    println!("  {}", theme().muted.paint("None; the permission mode decides for every tool."));
+                }
+                for (tool, rule) in rules {
+                    println!("  {:<20} {}", tool, rule);
+                }
+                let commands = soul.approval.commands();
+                if !commands.is_empty() {
+                    println!("\n{}", theme().heading.paint("Shell commands approved:"));
+                    for pattern in commands {
+                        println!("  {} ...", pattern);
+                    }
+                }
+                println!();
+                println!(
+                    "{}",
+                    theme().muted.paint(
+                        "Use /approve tool <name> always|ask|never|default to change a rule, /approve forget <command> \
+                         to ask about a command again, or /approve save to keep them in the config."
+                    )
+                );
+            }
+            ["forget", command @ ..] if !command.is_empty() => {
+                let pattern = command.join(" ");
+                if soul.approval.forget_command(&pattern) {
+                    println!("Shell commands starting with `{}` will be asked about again.", pattern);
+                } else {
+                    eprintln!("No approved Shell commands starting with `{}`", pattern);
+                }
+            }
+            ["save"] => {
+                let saved = self.save_global_config(|config| {
+                    config.approval.tools = soul.approval.rules().into_iter().collect();
+                    config.approval.commands = soul.approval.commands();
+                });
+                match saved {
+                    Ok(()) => println!("Approval rules and commands saved to the config."),
+                    Err(e) => eprintln!("Failed to save config: {}", e),
+                }
+            }
+            ["tool", tool, "default"] => {
+                soul.approval.set_rule(tool, None);
+                println!("Approval rule for {} removed; the {} mode decides.", tool, soul.approval.mode());
+            }
+            ["tool", tool, rule] => match rule.parse::<ToolRule>() {
+                Ok(rule) => {
+                    soul.approval.set_rule(tool, Some(rule));
+                    println!("Approval rule for {} set to {}.", tool, rule);
+                }
+                Err(e) => eprintln!("{}", e),
+            },
+            _ => eprintln!("Usage: /approve [tool <name> always|ask|never|default | forget <command> | save]"),
+        }
+    }
+
+    /// List the accounts of the current model's provider, or switch to one
+    fn handle_account(&mut self, args: &[&str]) {
+        let Some(provider_key) = self.config.models.get(&self.current_model).map(|m| m.provider.clone()) else {
+            println!("No model selected. Use /login or /setup first.");
+            return;
+        };
+        let secrets = SecretsManager::new();
+
+        match args {
+            [] => {
+                let Some(provider) = self.config.providers.get(&provider_key) else {
+                    eprintln!("No provider named {} in the config", provider_key);
+                    return;
+                };
+                let accounts = match list_accounts(&provider_key, provider, &secrets) {
+                    Ok(accounts) => accounts,
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return;
+                    }
+                };
+                if accounts.is_empty() {
+                    println!("Provider {} has no stored accounts.", provider_key);
+                    return;
+                }
+                let active = active_account(&provider_key, provider);
+                println!("\n{}", theme().heading.paint(format!("Accounts of {}:", provider_key)));
+                for account in accounts {
+                    let marker = if active.as_deref() == Some(account.as_str()) {
+                        theme().success.paint(" (active)")
+                    } else {
+                        theme().muted.paint("")
+                    };
+                    println!("  {}{}", account, marker);
+                }
+            }
+            ["switch", account] => {
+                if let Err(e) = switch_account(&mut self.config, &provider_key, account, &secrets) {
+                    eprintln!("{}", e);
+                    return;
+                }
+                let saved = self.save_global_config(|config| {
+                    // A provider from a project config is only switched for the session
+                    let _ = switch_account(config, &provider_key, account, &secrets);
+                });
+                match saved {
+                    Ok(()) => println!("Provider {} now uses account {}", provider_key, account),
+                    Err(e) => eprintln!("Failed to save config: {}", e),
+                }
+            }
+            _ => eprintln!("Usage: /account [switch <name>]"),
+        }
+    }
+
+    /// `/session`: show the current session, or create, switch to or
+    /// delete a named one
+    fn handle_session(&mut self, args: &[&str], soul: &mut KimiSoul) {
+        let manager = SessionManager::new(self.cli.effective_work_dir());
+        let current = soul.session.as_ref().map(|session| session.id);
+        match args {
+            [] => match &soul.session {
+                Some(session) => println!(
+                    "Current session: {} {}",
+                    theme().command.paint(session.display_name()),
+                    theme().muted.paint(format!("({} message(s))", soul.context.message_count()))
+                ),
+                None => println!("Not in a session ({} message(s))", soul.context.message_count()),
+            },
+            ["new", name] => match manager.create(name) {
+                Ok(session) => self.switch_session(session, soul),
+                Err(e) => eprintln!("{}", e),
+            },
+            ["switch", name] => match manager.get(name) {
+                Ok(session) if Some(session.id) == current => {
+                    println!("Already in session {}", session.display_name());
+                }
+                Ok(session) => self.switch_session(session, soul),
+                Err(e) => eprintln!("{}", e),
+            },
+            ["delete", name] => match manager.get(name) {
+                Ok(session) if Some(session.id) == current => {
+                    eprintln!("Cannot delete the current session; switch to another one first");
+                }
+                Ok(session) => match session.delete() {
+                    Ok(()) => println!("Deleted session {}", session.display_name()),
+                    Err(e) => eprintln!("{}", e),
+                },
+                Err(e) => eprintln!("{}", e),
+            },
+            _ => eprintln!("Usage: /session [new|switch|delete <name>]"),
+        }
+    }
+
+    /// Continue the conversation of `session`, saving the current one
+    fn switch_session(&mut self, session: Session, soul: &mut KimiSoul) {
+        let name = session.display_name();
+        if let Err(e) = soul.switch_session(session) {
+            eprintln!("{} {}", theme().error.paint("Failed to switch sessions:"), e);
+            return;
+        }
+        self.event_log = soul.event_log.clone();
+        println!(
+            "Switched to session {} {}",
+            theme().command.paint(name),
+            theme().muted.paint(format!("({} message(s))", soul.context.message_count()))
+        );
+    }
+
+    /// List the pinned messages with their IDs
+    fn print_pinned(&self, soul: &KimiSoul) {
+        let pinned = soul.context.pinned_messages();
+        if pinned.is_empty() {
+            println!("No pinned messages.");
+            return;
+        }
+        println!("\n{}", theme().heading.paint("Pinned messages:"));
+        for message in pinned {
+            println!("  {} {}",
+                theme().command.paint(format!("{:>4}", message.id().unwrap_or("?"))),
+                checkpoint_label(&message.content)
+            );
+        }
+        println!(
+            "\n{}\n",
+            theme()
+                .muted
+                .paint("Pinned messages are kept when the conversation is trimmed. Use /unpin <id> to release one.")
+        );
+    }
+
+    /// List configured agent personas, marking the active one
+    fn print_agents(&self, soul: &KimiSoul) {
+        let names = soul.agents.names();
+        if names.is_empty() {
+            println!("No agents configured. Add them under [agents.<name>] in the config file.");
+            return;
+        }
+
+        println!("\n{}", theme().heading.paint("Agents:"));
+        let active = soul.persona();
+        let marker = |current: bool| if current {
+            theme().success.paint(" (active)")
+        } else {
+            theme().muted.paint("")
+        };
+        println!("  default{}", marker(active.is_none()));
+        for name in names {
+            let persona = soul.agents.get(name).expect("listed persona exists");
+            println!("  {}{}", name, marker(active == Some(name)));
+            if let Some(description) = &persona.description {
+                println!("    {}", description);
+            }
+            if let Some(model) = &persona.model {
+                println!("    Model: {}", model);
+            }
+            if let Some(tools) = &persona.tools {
+                println!("    Tools: {}", tools.join(", "));
+            }
+        }
+        println!();
+    }
+
+    /// List user-defined slash commands and flow skills, if any were loaded
+    fn print_custom_commands(&self, soul: &KimiSoul) {
+        let commands = soul.slash_commands.custom_commands();
+        if !commands.is_empty() {
+            println!("{}", theme().section.paint("Custom Commands:"));
+            for command in commands {
+                println!("  {} - {} {}",
+                    theme().command.paint(format!("/{}", command.name)),
+                    command.description,
+                    theme().muted.paint(format!("({})", command.scope))
+                );
+            }
+            println!();
+        }
+
+        let flows: Vec<_> = soul.skills.iter().filter(|skill| skill.flow.is_some()).collect();
+        if !flows.is_empty() {
+            println!("{}", theme().section.paint("Flows:"));
+            for skill in flows {
+                println!("  {} - {}",
+                    theme().command.paint(format!("/flow:{}", skill.name)),
+                    skill.description
+                );
+            }
+            println!();
+        }
+    }
+
     fn print_help(&self) {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Available Commands:"));
+        println!("\n{}", theme().heading.paint("Available Commands:"));
         
-        println!("\n{}", Style::new().bold().fg(Color::Yellow).paint("General:"));
-        println!("  {} - Show this help message", Style::new().fg(Color::Green).paint("/help, /h, /?"));
-        println!("  {} - Exit the shell", Style::new().fg(Color::Green).paint("/exit, /quit, /q"));
-        println!("  {} - Show version information", Style::new().fg(Color::Green).paint("/version"));
-        println!("  {} - Show release notes", Style::new().fg(Color::Green).paint("/changelog, /release-notes"));
-        println!("  {} - Submit feedback (open GitHub issues)", Style::new().fg(Color::Green).paint("/feedback"));
+        println!("\n{}", theme().section.paint("General:"));
+        println!("  {} - Show this help message", theme().command.paint("/help, /h, /?"));
+        println!("  {} - Exit the shell", theme().command.paint("/exit, /quit, /q"));
+        println!("  {} - Show version information", theme().command.paint("/version"));
+        println!("  {} - Show release notes", theme().command.paint("/changelog, /release-notes"));
+        println!("  {} - Submit feedback (open GitHub issues)", theme().command.paint("/feedback"));
         
-        println!("\n{}", Style::new().bold().fg(Color::Yellow).paint("Session:"));
-        println!("  {} - List and resume sessions", Style::new().fg(Color::Green).paint("/sessions, /resume"));
-        println!("  {} - Set or show current session", Style::new().fg(Color::Green).paint("/session [name]"));
+        println!("\n{}", theme().section.paint("Session:"));
+        println!("  {} - List sessions to resume with kimi resume", theme().command.paint("/sessions, /resume"));
+        println!("  {} - Show the current session", theme().command.paint("/session"));
+        println!("  {} - Start a named session and switch to it", theme().command.paint("/session new <name>"));
+        println!("  {} - Switch to a session by name or ID", theme().command.paint("/session switch <name>"));
+        println!("  {} - Delete a session other than the current one", theme().command.paint("/session delete <name>"));
         
-        println!("\n{}", Style::new().bold().fg(Color::Yellow).paint("Authentication:"));
-        println!("  {} - Login to Kimi (OAuth device flow)", Style::new().fg(Color::Green).paint("/login"));
-        println!("  {} - Setup wizard (choose provider, enter API key)", Style::new().fg(Color::Green).paint("/setup"));
-        println!("  {} - Logout from Kimi", Style::new().fg(Color::Green).paint("/logout"));
+        println!("\n{}", theme().section.paint("Authentication:"));
+        println!("  {} - Login to Kimi (OAuth device flow)", theme().command.paint("/login [platform] [account]"));
+        println!("  {} - Setup wizard (choose provider, enter API key)", theme().command.paint("/setup"));
+        println!("  {} - Logout from Kimi", theme().command.paint("/logout [platform] [account]"));
+        println!("  {} - List or switch the model provider's accounts", theme().command.paint("/account [switch <name>]"));
         
-        println!("\n{}", Style::new().bold().fg(Color::Yellow).paint("Context:"));
-        println!("  {} - Clear the screen and context", Style::new().fg(Color::Green).paint("/clear, /reset"));
-        println!("  {} - Compact conversation context", Style::new().fg(Color::Green).paint("/compact"));
-        println!("  {} - Toggle YOLO mode (auto-execute)", Style::new().fg(Color::Green).paint("/yolo"));
+        println!("\n{}", theme().section.paint("Context:"));
+        println!("  {} - Clear the screen and context", theme().command.paint("/clear, /reset"));
+        println!("  {} - Summarize older turns to free up context", theme().command.paint("/compact [instructions]"));
+        println!("  {} - Toggle YOLO mode (auto-execute)", theme().command.paint("/yolo"));
+        println!("  {} - Show or switch the permission mode", theme().command.paint("/permissions [mode]"));
+        println!("  {} - Show or set per-tool approval rules", theme().command.paint("/approve [tool <name> <rule> | forget <command> | save]"));
+        println!("  {} - Show usage statistics, optionally of the last N days", theme().command.paint("/stats [days]"));
+        println!("  {} - Browse, search or show the session's exchanges", theme().command.paint("/history [n|text]"));
+        println!("  {} - List checkpoints with their time and messages", theme().command.paint("/checkpoints"));
+        println!("  {} - Rewind to before message n, showing what is discarded (or /rewind)", theme().command.paint("/rollback <n> [text]"));
+        println!("  {} - Revert the last n file changes made by tools", theme().command.paint("/undo [n]"));
+        println!("  {} - Show the session's file changes, or the files against git HEAD", theme().command.paint("/diff [head]"));
+        println!("  {} - Commit with a message written by the model", theme().command.paint("/commit [--amend] [--signoff]"));
+        println!("  {} - Copy the last response, or its code blocks, to the clipboard", theme().command.paint("/copy [code]"));
+        println!("  {} - Compose the next message in $EDITOR (or press Ctrl+G)", theme().command.paint("/editor [text]"));
+        println!("  {} - Pin the last or a given message so compaction keeps it, or list pins", theme().command.paint("/pin [id|list]"));
+        println!("  {} - Unpin a message", theme().command.paint("/unpin <id>"));
         
-        println!("\n{}", Style::new().bold().fg(Color::Yellow).paint("Other:"));
-        println!("  {} - Set or show current model", Style::new().fg(Color::Green).paint("/model [name]"));
-        println!("  {} - List all available models", Style::new().fg(Color::Green).paint("/models"));
-        println!("  {} - List available tools", Style::new().fg(Color::Green).paint("/tools"));
-        println!("  {} - Show MCP servers and tools", Style::new().fg(Color::Green).paint("/mcp"));
-        println!("  {} - Open Web UI (info only)", Style::new().fg(Color::Green).paint("/web"));
-        println!("  {} - Analyze codebase and generate AGENTS.md", Style::new().fg(Color::Green).paint("/init"));
-        println!("  {} - Switch between agent and shell mode", Style::new().fg(Color::Green).paint("/mode"));
+        println!("\n{}", theme().section.paint("Other:"));
+        println!("  {} - Set or show current model", theme().command.paint("/model [query]"));
+        println!("  {} - List all available models", theme().command.paint("/models"));
+        println!("  {} - List agents or switch to one", theme().command.paint("/agent [name]"));
+        println!("  {} - List available tools", theme().command.paint("/tools"));
+        println!("  {} - Show MCP servers and tools", theme().command.paint("/mcp"));
+        println!("  {} - Open Web UI (info only)", theme().command.paint("/web"));
+        println!("  {} - Analyze codebase and generate AGENTS.md", theme().command.paint("/init"));
+        println!("  {} - Show loaded AGENTS.md / KIMI.md memory files", theme().command.paint("/memory"));
+        println!("  {} - Switch between agent and shell mode", theme().command.paint("/mode"));
+        println!("  {} - Run a shell command from agent mode; !! also adds its output to the conversation", theme().command.paint("!<command>, !!<command>"));
         
         println!();
         println!(
             "{}",
-            Style::new()
-                .fg(Color::DarkGray)
+            theme()
+                .muted
                 .paint("Type your message and press Enter to chat with Kimi.")
         );
         println!();
     }
 
     fn print_version(&self) {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Kimi CLI"));
+        println!("\n{}", theme().heading.paint("Kimi CLI"));
         println!("  Version: {}", env!("CARGO_PKG_VERSION"));
         println!("  Repository: https://github.com/moonshot-ai/kimi-cli");
         println!();
     }
 
     fn print_changelog(&self) {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Release Notes"));
+        println!("\n{}", theme().heading.paint("Release Notes"));
         println!("  See the latest releases at:");
         println!("  https://github.com/moonshot-ai/kimi-cli/releases");
         println!();
     }
 
     async fn open_feedback(&self) {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Submit Feedback"));
+        println!("\n{}", theme().heading.paint("Submit Feedback"));
         println!("  Opening GitHub issues page...");
         
         let url = "https://github.com/moonshot-ai/kimi-cli/issues";
         match open::that(url) {
-            Ok(_) => println!("  {}", Style::new().fg(Color::Green).paint("Browser opened successfully.")),
+            Ok(_) => println!("  {}", theme().success.paint("Browser opened successfully.")),
             Err(e) => {
-                println!("  {}: {}", Style::new().fg(Color::Yellow).paint("Could not open browser"), e);
+                println!("  {}: {}", theme().warning.paint("Could not open browser"), e);
                 println!("  Please visit: {}", url);
             }
         }
@@ -1118,34 +2425,28 @@ impl ShellUI {
     }
 
     async fn list_sessions(&self) {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("Sessions"));
+        println!("\n{}", theme().heading.paint("Sessions"));
         
         let work_dir = self.cli.effective_work_dir();
-        match Session::list_all(&work_dir) {
-            Ok(sessions) => {
-                if sessions.is_empty() {
+        match Session::list_summaries(&work_dir) {
+            Ok(summaries) => {
+                if summaries.is_empty() {
                     println!("  No sessions found.");
                     println!("  Start chatting to create a new session.");
                 } else {
-                    println!("  Available sessions (newest first):");
+                    println!("  Most recently active first:");
                     println!();
-                    for session in sessions {
-                        let short_id = session.short_id();
-                        let created = session.created_at.format("%Y-%m-%d %H:%M:%S");
-                        println!("  {} - Created: {}", 
-                            Style::new().fg(Color::Green).paint(&short_id),
-                            created
-                        );
-                    }
+                    crate::commands::resume::print_sessions(&summaries.iter().collect::<Vec<_>>());
                     println!();
-                    println!("  Use {} to resume a session.", 
-                        Style::new().fg(Color::Yellow).paint("kimi --continue")
+                    println!("  Use {} to continue one here, or {} to resume one later.",
+                        theme().code.paint("/session switch <name>"),
+                        theme().code.paint("kimi resume <name>")
                     );
                 }
             }
             Err(e) => {
                 eprintln!("  {}: {}", 
-                    Style::new().fg(Color::Red).paint("Error listing sessions"),
+                    theme().error.paint("Error listing sessions"),
                     e
                 );
             }
@@ -1153,38 +2454,89 @@ impl ShellUI {
         println!();
     }
 
-    async fn show_mcp_servers(&self) -> anyhow::Result<()> {
-        println!("\n{}", Style::new().bold().fg(Color::Cyan).paint("MCP Servers"));
-        
-        // Load MCP config
-        let config = crate::commands::mcp::load_config_from(
-            &dirs::config_dir()
-                .ok_or_else(|| anyhow::anyhow!("Failed to determine config directory"))?
-                .join("kimi")
-                .join("mcp.json")
-        ).await?;
-        
-        if config.servers.is_empty() {
+    fn show_memory(&self, soul: &KimiSoul) {
+        println!("\n{}", theme().heading.paint("Memory Files"));
+
+        let memory = &soul.memory;
+        if memory.is_empty() {
+            println!("  No memory files loaded.");
+            println!("  Create AGENTS.md or KIMI.md in the project, or ~/.kimi/KIMI.md for global memory;");
+            println!("  CLAUDE.md and GEMINI.md are read where neither exists.");
+            println!("  Use {} to generate one.", theme().code.paint("/init"));
+            println!();
+            return;
+        }
+
+        println!("  Loaded in merge order (later files take precedence):");
+        println!();
+        for (i, file) in memory.files.iter().enumerate() {
+            let truncated = if file.truncated {
+                theme().warning.paint(" (truncated)")
+            } else {
+                theme().muted.paint("")
+            };
+            println!("  {}. {} {} - {} bytes{}",
+                i + 1,
+                theme().success.paint(format!("[{}]", file.scope)),
+                theme().emphasis.paint(file.path.display().to_string()),
+                file.content.len(),
+                truncated
+            );
+            for import in &file.imports {
+                println!("       {} {}",
+                    theme().muted.paint("@import"),
+                    import.display()
+                );
+            }
+        }
+        println!();
+        println!("  Total: {} bytes", memory.total_bytes());
+        println!();
+    }
+
+    /// Show the MCP servers with their connection status and tools, listing
+    /// the tools of connected servers again first
+    async fn show_mcp_servers(&self, soul: &mut KimiSoul) -> anyhow::Result<()> {
+        println!("\n{}", theme().heading.paint("MCP Servers"));
+
+        soul.toolset_mut().refresh_mcp_servers().await;
+        let config = crate::commands::mcp::load_config().await?;
+        let disabled: Vec<&String> = config
+            .servers
+            .iter()
+            .filter(|(name, server)| !server.enabled && !soul.toolset.has_mcp_server(name))
+            .map(|(name, _)| name)
+            .collect();
+
+        let mut servers: Vec<_> = soul.toolset.mcp_servers().values().collect();
+        if servers.is_empty() && disabled.is_empty() {
             println!("  No MCP servers configured.");
-            println!("  Use {} to add a server.", 
-                Style::new().fg(Color::Yellow).paint("kimi mcp add <name>")
+            println!("  Use {} to add a server.",
+                theme().code.paint("kimi mcp add <name>")
             );
-        } else {
-            println!("  Configured servers:");
             println!();
-            for (name, server) in &config.servers {
-                let status = if server.enabled {
-                    Style::new().fg(Color::Green).paint("● enabled")
-                } else {
-                    Style::new().fg(Color::DarkGray).paint("○ disabled")
-                };
-                println!("  {} - {}", 
-                    Style::new().bold().paint(name),
-                    status
-                );
-                println!("    Command: {} {}", server.command, server.args.join(" "));
+            return Ok(());
+        }
+        servers.sort_by(|a, b| a.name.cmp(&b.name));
+        for server in servers {
+            let status = match (&server.error, server.connected) {
+                (_, true) => theme().success.paint("● connected"),
+                (Some(error), false) => theme().error.paint(format!("✗ {}", error)),
+                (None, false) => theme().muted.paint("○ not connected"),
+            };
+            println!("  {} - {}",
+                theme().emphasis.paint(&server.name),
+                status
+            );
+            println!("    Command: {} {}", server.command, server.args.join(" "));
+            if server.connected {
+                let tools = if server.tools.is_empty() { "none".to_string() } else { server.tools.join(", ") };
+                println!("    Tools: {}", tools);
             }
         }
+        for name in disabled {
+            println!("  {} - {}", theme().emphasis.paint(name), theme().muted.paint("○ disabled"));
+        }
         println!();
         Ok(())
     }
@@ -1204,15 +2556,14 @@ impl UI for ShellUI {
 
         println!(
             "\n{}",
-            Style::new()
-                .bold()
-                .fg(Color::Cyan)
+            theme()
+                .heading
                 .paint("Welcome to Kimi CLI!")
         );
         println!(
             "{}",
-            Style::new()
-                .fg(Color::DarkGray)
+            theme()
+                .muted
                 .paint("Type /help for commands, /exit to quit.\n")
         );
 
@@ -1242,11 +2593,11 @@ impl UI for ShellUI {
                     }
 
                     // Placeholder response
-                    println!("{}", Style::new().fg(Color::Blue).paint("Kimi: "));
+                    println!("{}", theme().agent.paint("Kimi: "));
                     println!(
                         "  {}",
-                        Style::new()
-                            .fg(Color::White)
+                        theme()
+                            .input
                             .paint(format!("Received: {}", input))
                     );
                 }
@@ -1272,6 +2623,6 @@ impl UI for ShellUI {
     }
 
     fn error(&self, err: &str) {
-        eprintln!("{}", Style::new().fg(Color::Red).paint(err));
+        eprintln!("{}", theme().error.paint(err));
     }
 }
@@ -0,0 +1,154 @@
+//! Status line shown below the output while a turn runs
+//!
+//! The line shows a spinner, the time since the turn began, the tokens
+//! streamed so far, the context window usage and the running tool. It is
+//! drawn on the line under the cursor, so streamed text that has not ended
+//! its line is left alone, and it is cleared before anything else is
+//! printed and when the turn ends.
+
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use kimi_core::prompts::builder::estimate_tokens;
+use kimi_core::wire::WireMessage;
+
+use crate::ui::theme::theme;
+
+/// How often the spinner moves
+pub const TICK: Duration = Duration::from_millis(100);
+
+const FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// Longest tool name shown, so the line stays on one terminal row
+const MAX_TOOL_CHARS: usize = 24;
+
+/// Move down a line, scrolling if needed, then back up: the line under the
+/// cursor exists, and the cursor keeps its column
+const MAKE_ROOM: &str = "\x1bD\x1bM";
+/// Save the cursor, then clear the line under it
+const ENTER_LINE: &str = "\x1b7\x1bD\r\x1b[2K";
+/// Restore the cursor
+const LEAVE_LINE: &str = "\x1b8";
+
+/// Progress of the running turn, drawn as a transient line
+pub struct StatusLine {
+    enabled: bool,
+    started: Instant,
+    frame: usize,
+    /// Tokens of the steps the model has answered
+    turn_tokens: usize,
+    /// Text streamed in the current step, until its usage arrives
+    step_text: String,
+    context_usage: Option<f64>,
+    tool: Option<String>,
+    shown: bool,
+}
+
+impl StatusLine {
+    /// A status line for a turn beginning now; a disabled one draws
+    /// nothing
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            frame: 0,
+            turn_tokens: 0,
+            step_text: String::new(),
+            context_usage: None,
+            tool: None,
+            shown: false,
+        }
+    }
+
+    /// Update the status from a message of the turn
+    pub fn observe(&mut self, message: &WireMessage) {
+        match message {
+            WireMessage::StepBegin { .. } => self.step_text.clear(),
+            WireMessage::TextPart { text } | WireMessage::ThinkPart { text } => self.step_text.push_str(text),
+            WireMessage::ToolBegin { name, .. } => self.tool = Some(name.clone()),
+            WireMessage::ToolEnd { .. } => self.tool = None,
+            WireMessage::StatusUpdate { context_usage, token_usage, .. } => {
+                if context_usage.is_some() {
+                    self.context_usage = *context_usage;
+                }
+                if let Some(tokens) = token_usage {
+                    self.turn_tokens += tokens.output_tokens;
+                    self.step_text.clear();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Tokens streamed in the turn, estimated for the current step
+    fn tokens(&self) -> usize {
+        self.turn_tokens + estimate_tokens(&self.step_text)
+    }
+
+    /// The status text, e.g. `⠋ 12s · 340 tokens · 23% context · Shell`
+    fn render(&self, elapsed: Duration) -> String {
+        let mut parts = vec![
+            format!("{} {}s", FRAMES[self.frame % FRAMES.len()], elapsed.as_secs()),
+            format!("{} tokens", self.tokens()),
+        ];
+        if let Some(usage) = self.context_usage {
+            parts.push(format!("{:.0}% context", usage * 100.0));
+        }
+        if let Some(tool) = &self.tool {
+            match tool.chars().count() > MAX_TOOL_CHARS {
+                true => parts.push(format!("{}…", tool.chars().take(MAX_TOOL_CHARS - 1).collect::<String>())),
+                false => parts.push(tool.clone()),
+            }
+        }
+        parts.join(" · ")
+    }
+
+    /// Advance the spinner and draw the line
+    pub fn tick(&mut self) -> std::io::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.frame += 1;
+        let text = theme().muted.paint(self.render(self.started.elapsed()));
+        let mut stdout = std::io::stdout();
+        write!(stdout, "{}{}{}{}", MAKE_ROOM, ENTER_LINE, text, LEAVE_LINE)?;
+        self.shown = true;
+        stdout.flush()
+    }
+
+    /// Remove the line, if drawn
+    pub fn clear(&mut self) -> std::io::Result<()> {
+        if !self.shown {
+            return Ok(());
+        }
+        self.shown = false;
+        let mut stdout = std::io::stdout();
+        write!(stdout, "{}{}", ENTER_LINE, LEAVE_LINE)?;
+        stdout.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kimi_core::types::TokenUsage;
+
+    #[test]
+    fn test_status_follows_the_turn() {
+        let mut status = StatusLine::new(false);
+        assert_eq!(status.render(Duration::from_secs(3)), "⠋ 3s · 0 tokens");
+
+        status.observe(&WireMessage::StatusUpdate { context_usage: Some(0.234), token_usage: None, message_id: None });
+        status.observe(&WireMessage::StepBegin { n: 1 });
+        status.observe(&WireMessage::TextPart { text: "a".repeat(40) });
+        status.observe(&WireMessage::ToolBegin { name: "Shell".to_string(), arguments: "{}".to_string() });
+        assert_eq!(status.render(Duration::from_secs(12)), "⠋ 12s · 10 tokens · 23% context · Shell");
+
+        // The step's usage replaces the estimate of its text
+        let tokens = TokenUsage { input_tokens: 900, output_tokens: 25, total_tokens: 925 };
+        status.observe(&WireMessage::StatusUpdate { context_usage: None, token_usage: Some(tokens), message_id: None });
+        status.observe(&WireMessage::ToolEnd { name: "Shell".to_string(), result: String::new() });
+        status.observe(&WireMessage::TextPart { text: "a".repeat(8) });
+        assert_eq!(status.render(Duration::from_secs(20)), "⠋ 20s · 27 tokens · 23% context");
+    }
+}
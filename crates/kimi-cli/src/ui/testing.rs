@@ -0,0 +1,65 @@
+//! Test helpers for the server frontends
+
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use kimi_core::{
+    approval::Approval,
+    context::Context,
+    soul::{Agent, DenwaRenji, KimiSoul, SimpleCompaction},
+    types::LoopControl,
+};
+use kosong_rs::chat_provider::ToolDefinition;
+use kosong_rs::{ChatError, ChatProvider, GenerateStream, Message, ModelCapability, StreamChunk, ThinkingEffort};
+
+/// Replies "echo: <last message>", or fails when the last message is "fail"
+pub(crate) struct EchoProvider {
+    pub calls: Mutex<usize>,
+}
+
+impl EchoProvider {
+    pub fn new() -> Self {
+        Self { calls: Mutex::new(0) }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChatProvider for EchoProvider {
+    async fn generate_with_tools(
+        &self,
+        _system_prompt: Option<&str>,
+        messages: &[Message],
+        _tools: Option<&[ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        *self.calls.lock().unwrap() += 1;
+        let last = messages.last().and_then(|m| m.text()).unwrap_or_default();
+        if last == "fail" {
+            return Err(ChatError::StreamEnded);
+        }
+        Ok(Box::pin(futures::stream::iter([Ok(StreamChunk::Text(format!("echo: {}", last)))])))
+    }
+
+    fn model_name(&self) -> &str {
+        "echo"
+    }
+
+    fn with_thinking(&self, _effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        unimplemented!()
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        &[]
+    }
+}
+
+/// A yolo-mode soul whose context lives in `dir`
+pub(crate) fn test_soul(dir: &Path) -> KimiSoul {
+    KimiSoul::new(
+        Agent::new("Kimi", "Default agent"),
+        Context::new(dir.join("context.json")),
+        Arc::new(Approval::yolo()),
+        Arc::new(DenwaRenji::new()),
+        LoopControl::default(),
+        SimpleCompaction::new(8000),
+    )
+}
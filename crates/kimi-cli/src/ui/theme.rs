@@ -0,0 +1,279 @@
+//! Colors of the interactive shell
+//!
+//! Output is styled by role (prompt, headings, warnings, diff lines, ...)
+//! rather than by color, and [`theme`] maps the roles to styles. The mapping
+//! starts from the `dark` or `light` preset, takes colors from the config's
+//! `[theme.colors]` table, and is plain text throughout when colors are off
+//! with `--no-color`, `NO_COLOR` or a dumb terminal.
+
+use std::sync::OnceLock;
+
+use kimi_core::ThemeConfig;
+use nu_ansi_term::{Color, Style};
+use tracing::warn;
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Styles of the roles output is shown in
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    /// The prompt in agent mode
+    pub prompt: Style,
+    /// The prompt in shell mode
+    pub shell_prompt: Style,
+    /// Text being typed at the prompt
+    pub input: Style,
+    /// The label in front of replies
+    pub agent: Style,
+    /// Titles of lists and reports
+    pub heading: Style,
+    /// Group titles within a list, e.g. in `/help`
+    pub section: Style,
+    /// Commands, identifiers and counts
+    pub command: Style,
+    /// Progress and informational notes
+    pub accent: Style,
+    /// Secondary text such as hints and details
+    pub muted: Style,
+    /// Labels and names that stand out from the text around them
+    pub emphasis: Style,
+    pub success: Style,
+    pub warning: Style,
+    pub error: Style,
+    /// Tool calls
+    pub tool: Style,
+    /// Skills and flows
+    pub skill: Style,
+    /// Inline and fenced code in replies
+    pub code: Style,
+    /// Links in replies
+    pub link: Style,
+    pub diff_added: Style,
+    pub diff_removed: Style,
+    pub diff_hunk: Style,
+}
+
+impl Theme {
+    /// Light text on a dark background
+    pub fn dark() -> Self {
+        Self {
+            prompt: Style::new().bold().fg(Color::Green),
+            shell_prompt: Style::new().bold().fg(Color::Blue),
+            input: Style::new().fg(Color::White),
+            agent: Style::new().bold().fg(Color::Blue),
+            heading: Style::new().bold().fg(Color::Cyan),
+            section: Style::new().bold().fg(Color::Yellow),
+            command: Style::new().fg(Color::Green),
+            accent: Style::new().fg(Color::Cyan),
+            muted: Style::new().fg(Color::DarkGray),
+            emphasis: Style::new().bold(),
+            success: Style::new().fg(Color::Green),
+            warning: Style::new().fg(Color::Yellow),
+            error: Style::new().fg(Color::Red),
+            tool: Style::new().fg(Color::Yellow),
+            skill: Style::new().fg(Color::Purple),
+            code: Style::new().fg(Color::Yellow),
+            link: Style::new().underline().fg(Color::Blue),
+            diff_added: Style::new().fg(Color::Green),
+            diff_removed: Style::new().fg(Color::Red),
+            diff_hunk: Style::new().fg(Color::Cyan),
+        }
+    }
+
+    /// Dark text on a light background, avoiding the pale yellows and
+    /// whites of the dark preset
+    pub fn light() -> Self {
+        Self {
+            prompt: Style::new().bold().fg(Color::Fixed(28)),
+            shell_prompt: Style::new().bold().fg(Color::Blue),
+            input: Style::new(),
+            agent: Style::new().bold().fg(Color::Blue),
+            heading: Style::new().bold().fg(Color::Fixed(25)),
+            section: Style::new().bold().fg(Color::Fixed(130)),
+            command: Style::new().fg(Color::Fixed(28)),
+            accent: Style::new().fg(Color::Fixed(25)),
+            muted: Style::new().fg(Color::Fixed(244)),
+            emphasis: Style::new().bold(),
+            success: Style::new().fg(Color::Fixed(28)),
+            warning: Style::new().fg(Color::Fixed(130)),
+            error: Style::new().fg(Color::Fixed(160)),
+            tool: Style::new().fg(Color::Fixed(130)),
+            skill: Style::new().fg(Color::Purple),
+            code: Style::new().fg(Color::Fixed(130)),
+            link: Style::new().underline().fg(Color::Blue),
+            diff_added: Style::new().fg(Color::Fixed(28)),
+            diff_removed: Style::new().fg(Color::Fixed(160)),
+            diff_hunk: Style::new().fg(Color::Fixed(25)),
+        }
+    }
+
+    /// No styling at all
+    pub fn plain() -> Self {
+        let plain = Style::new();
+        Self {
+            prompt: plain,
+            shell_prompt: plain,
+            input: plain,
+            agent: plain,
+            heading: plain,
+            section: plain,
+            command: plain,
+            accent: plain,
+            muted: plain,
+            emphasis: plain,
+            success: plain,
+            warning: plain,
+            error: plain,
+            tool: plain,
+            skill: plain,
+            code: plain,
+            link: plain,
+            diff_added: plain,
+            diff_removed: plain,
+            diff_hunk: plain,
+        }
+    }
+
+    /// The theme a config asks for, or [`Theme::plain`] when colors are
+    /// off
+    ///
+    /// Colors that do not parse are skipped with a warning, keeping the
+    /// preset's.
+    pub fn from_config(config: &ThemeConfig, color: bool) -> Self {
+        if !color {
+            return Self::plain();
+        }
+        let mut theme = match config.preset.as_deref() {
+            Some("light") => Self::light(),
+            _ => Self::dark(),
+        };
+        let mut colors: Vec<_> = config.colors.iter().collect();
+        colors.sort();
+        for (role, value) in colors {
+            let Some(style) = theme.role_mut(role) else {
+                warn!("Unknown theme color role '{}'", role);
+                continue;
+            };
+            match parse_color(value) {
+                Some(color) => style.foreground = Some(color),
+                None => warn!("Invalid color '{}' for theme role '{}'", value, role),
+            }
+        }
+        theme
+    }
+
+    fn role_mut(&mut self, role: &str) -> Option<&mut Style> {
+        Some(match role {
+            "prompt" => &mut self.prompt,
+            "shell_prompt" => &mut self.shell_prompt,
+            "input" => &mut self.input,
+            "agent" => &mut self.agent,
+            "heading" => &mut self.heading,
+            "section" => &mut self.section,
+            "command" => &mut self.command,
+            "accent" => &mut self.accent,
+            "muted" => &mut self.muted,
+            "emphasis" => &mut self.emphasis,
+            "success" => &mut self.success,
+            "warning" => &mut self.warning,
+            "error" => &mut self.error,
+            "tool" => &mut self.tool,
+            "skill" => &mut self.skill,
+            "code" => &mut self.code,
+            "link" => &mut self.link,
+            "diff_added" => &mut self.diff_added,
+            "diff_removed" => &mut self.diff_removed,
+            "diff_hunk" => &mut self.diff_hunk,
+            _ => return None,
+        })
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
+
+/// Set the theme for the rest of the process; only the first call has an
+/// effect
+pub fn init(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme set with [`init`], or the dark preset before that
+pub fn theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
+
+/// Parse a color name such as `blue` or `light_red`, `#rrggbb`, or a
+/// 256-color index
+fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim().to_ascii_lowercase().replace('-', "_");
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return Some(Color::Rgb(channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = value.parse::<u8>() {
+        return Some(Color::Fixed(index));
+    }
+    Some(match value.as_str() {
+        "default" => Color::Default,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "purple" | "magenta" => Color::Purple,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        "gray" | "grey" | "dark_gray" | "dark_grey" => Color::DarkGray,
+        "light_red" => Color::LightRed,
+        "light_green" => Color::LightGreen,
+        "light_yellow" => Color::LightYellow,
+        "light_blue" => Color::LightBlue,
+        "light_purple" | "light_magenta" => Color::LightPurple,
+        "light_cyan" => Color::LightCyan,
+        "light_gray" | "light_grey" => Color::LightGray,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_from_config() {
+        let config: ThemeConfig = toml::from_str(
+            r##"
+preset = "light"
+
+[colors]
+prompt = "magenta"
+heading = "#ff8800"
+muted = "245"
+error = "not-a-color"
+"##,
+        )
+        .unwrap();
+        let theme = Theme::from_config(&config, true);
+        assert_eq!(theme.prompt, Style::new().bold().fg(Color::Purple));
+        assert_eq!(theme.heading, Style::new().bold().fg(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(theme.muted, Style::new().fg(Color::Fixed(245)));
+        assert_eq!(theme.error, Theme::light().error);
+        assert_eq!(theme.success, Theme::light().success);
+
+        assert_eq!(Theme::from_config(&ThemeConfig::default(), true), Theme::dark());
+        let plain = Theme::from_config(&config, false);
+        assert_eq!(plain, Theme::plain());
+        assert_eq!(plain.error.paint("failed").to_string(), "failed");
+
+        assert_eq!(parse_color("Light-Blue"), Some(Color::LightBlue));
+        assert_eq!(parse_color("#12345"), None);
+        assert_eq!(parse_color("256"), None);
+    }
+}
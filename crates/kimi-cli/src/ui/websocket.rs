@@ -0,0 +1,192 @@
+//! WebSocket wire endpoint
+//!
+//! Serves the server mode protocol at `/wire`, so a local web UI or a remote
+//! monitor can follow a long-running session. Each text frame from a client
+//! is one input line, and every wire message of the session is sent to all
+//! connected clients as one text frame. Clients must present the server's
+//! token, either as a `token` query parameter or as an
+//! `Authorization: Bearer` header.
+
+use std::collections::HashMap;
+use std::future::IntoFuture;
+use std::sync::Arc;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use subtle::ConstantTimeEq;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{info, warn};
+
+use kimi_core::{soul::KimiSoul, wire::WireMessage};
+use kosong_rs::ChatProvider;
+
+use super::server::{run_session, ClientLine, WireOutput};
+use super::UIResult;
+
+/// Wire messages buffered per client before it starts missing them
+const CLIENT_BUFFER: usize = 256;
+
+#[derive(Clone)]
+struct WsState {
+    token: Arc<str>,
+    lines: mpsc::Sender<ClientLine>,
+    events: broadcast::Sender<WireMessage>,
+}
+
+/// Sends each message to every connected client
+struct Broadcast(broadcast::Sender<WireMessage>);
+
+impl WireOutput for Broadcast {
+    async fn send(&mut self, message: &WireMessage) -> UIResult<()> {
+        // Nobody listening is fine; the session keeps running
+        let _ = self.0.send(message.clone());
+        Ok(())
+    }
+}
+
+/// Run a session for inputs from WebSocket clients on `listener`
+///
+/// Runs until the listener fails. Inputs from all clients share the one
+/// session and are queued while a turn runs.
+pub async fn serve_websocket(
+    soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
+    listener: TcpListener,
+    token: String,
+) -> UIResult<()> {
+    let (line_tx, line_rx) = mpsc::channel::<ClientLine>(16);
+    let (events, _) = broadcast::channel(CLIENT_BUFFER);
+    let state = WsState {
+        token: token.into(),
+        lines: line_tx,
+        events: events.clone(),
+    };
+    let app = Router::new().route("/wire", get(connect)).with_state(state);
+    let mut output = Broadcast(events);
+    info!("Serving the wire protocol on {:?}", listener.local_addr());
+
+    tokio::select! {
+        result = axum::serve(listener, app).into_future() => result?,
+        result = run_session(soul, provider, line_rx, &mut output) => result?,
+    }
+    Ok(())
+}
+
+async fn connect(
+    ws: WebSocketUpgrade,
+    State(state): State<WsState>,
+    Query(query): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Response {
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let token = query.get("token").map(String::as_str).or(bearer);
+    // Compared in constant time so the token cannot be guessed byte by byte
+    let valid = token.is_some_and(|token| bool::from(token.as_bytes().ct_eq(state.token.as_bytes())));
+    if !valid {
+        warn!("Rejected WebSocket client with a missing or wrong token");
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    ws.on_upgrade(move |socket| handle_client(socket, state))
+}
+
+/// Forward session messages to the client and its frames to the session
+async fn handle_client(socket: WebSocket, state: WsState) {
+    info!("WebSocket client connected");
+    let (mut sink, mut stream) = socket.split();
+    let mut events = state.events.subscribe();
+
+    let forward = tokio::spawn(async move {
+        loop {
+            let message = match events.recv().await {
+                Ok(message) => message,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    warn!("WebSocket client fell behind, skipped {} messages", skipped);
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+            let json = match message.to_json() {
+                Ok(json) => json,
+                Err(e) => {
+                    warn!("Failed to serialize wire message: {}", e);
+                    continue;
+                }
+            };
+            if sink.send(Message::Text(json.into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(frame)) = stream.next().await {
+        match frame {
+            Message::Text(text) => match ClientLine::parse(text.as_str()) {
+                Ok(line) => {
+                    if state.lines.send(line).await.is_err() {
+                        break;
+                    }
+                }
+                Err(e) => warn!("Ignoring invalid WebSocket message: {}", e),
+            },
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    forward.abort();
+    info!("WebSocket client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ui::testing::{test_soul, EchoProvider};
+    use tokio_tungstenite::tungstenite;
+
+    #[tokio::test]
+    async fn test_websocket_requires_token_and_runs_turns() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = EchoProvider::new();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/wire", listener.local_addr().unwrap());
+
+        let client = async {
+            let err = tokio_tungstenite::connect_async(format!("{}?token=wrong", url)).await.unwrap_err();
+            assert!(matches!(err, tungstenite::Error::Http(ref r) if r.status() == StatusCode::UNAUTHORIZED));
+
+            let (mut ws, _) = tokio_tungstenite::connect_async(format!("{}?token=secret", url)).await.unwrap();
+            ws.send(tungstenite::Message::text(r#"{"text": "hello"}"#)).await.unwrap();
+
+            let mut summary = Vec::new();
+            while let Some(frame) = ws.next().await {
+                let tungstenite::Message::Text(text) = frame.unwrap() else {
+                    continue;
+                };
+                match WireMessage::from_json(text.as_str()).unwrap() {
+                    WireMessage::TurnBegin { user_input } => summary.push(format!("begin {}", user_input.text)),
+                    WireMessage::TextPart { text } => summary.push(text),
+                    WireMessage::TurnEnd => break,
+                    _ => {}
+                }
+            }
+            summary
+        };
+
+        tokio::select! {
+            result = serve_websocket(&mut soul, &provider, listener, "secret".to_string()) => {
+                panic!("server stopped: {:?}", result.err())
+            }
+            summary = client => assert_eq!(summary, vec!["begin hello", "echo: hello"]),
+        }
+    }
+}
@@ -1,15 +1,274 @@
 //! Approval system for tool execution
-
+//!
+//! The [`PermissionMode`] decides which tool calls run without asking: in
+//! plan mode only [`READ_ONLY_TOOLS`] run, in accept-edits mode file edits
+//! are approved as well, and in full-auto mode everything is. A per-tool
+//! [`ToolRule`], from `[approval.tools]`, `--allowed-tools` or
+//! `/approve tool`, overrides the mode: `always` runs the tool in any mode,
+//! `ask` asks even when the mode would approve, and `never` takes the tool
+//! away. Other calls wait for the user's answer, which may come with tool
+//! arguments the user edited, e.g. a rewritten file change. An unattended
+//! manager, e.g. in print mode, has no one to ask and rejects them instead.
+//!
+//! Answering [`ApprovalKind::ApproveAlways`] remembers the decision for the
+//! session: the tool gets an `always` rule, except Shell, where only
+//! commands with the same [`command_pattern`] run without asking from then
+//! on. Remembered patterns can be kept under `[approval]` as `commands`.
+
+use crate::snapshot::FILE_WRITE_TOOLS;
 use crate::types::{ApprovalKind, Request};
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
 use tokio::sync::{Mutex, oneshot};
 use tracing::{debug, info, warn};
 
+/// Tools that only read, allowed in plan mode without asking
+pub const READ_ONLY_TOOLS: &[&str] = &[
+    "ReadFile",
+    "Glob",
+    "Grep",
+    "SearchCodebase",
+    "SearchWeb",
+    "FetchURL",
+    "SetTodoList",
+    "WatchPath",
+];
+
+/// The tool whose calls are remembered by command pattern instead of
+/// altogether
+pub const SHELL_TOOL: &str = "Shell";
+
+/// Characters that chain, redirect or substitute commands, which would let
+/// a remembered pattern approve more than it shows
+const SHELL_OPERATORS: &[char] = &[';', '&', '|', '<', '>', '$', '`', '(', ')', '\n'];
+
+/// Programs that run the commands in their arguments, such as `find -exec`
+/// or `xargs`, so no pattern can say what they do
+const COMMAND_RUNNERS: &[&str] = &[
+    "find", "xargs", "env", "sudo", "doas", "sh", "bash", "zsh", "fish", "eval", "exec", "command", "nohup", "nice",
+    "time", "timeout", "watch", "ssh",
+];
+
+/// The pattern a Shell command is remembered by: the program and its
+/// subcommand, so `git status --short` and `git status` share `git status`,
+/// or the program alone when it is run without arguments
+///
+/// Commands whose next word is not a subcommand, such as `find . -name x`,
+/// have no pattern, as remembering the program alone would approve it with
+/// any arguments. Neither do commands that chain, redirect or substitute
+/// others, set environment variables or run the commands in their
+/// arguments; they are always asked about.
+pub fn command_pattern(command: &str) -> Option<String> {
+    if command.contains(SHELL_OPERATORS) {
+        return None;
+    }
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .filter(|program| !program.contains('=') && !COMMAND_RUNNERS.contains(program))?;
+    match words.next() {
+        None => Some(program.to_string()),
+        Some(subcommand)
+            if !subcommand.starts_with('-')
+                && subcommand.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') =>
+        {
+            Some(format!("{} {}", program, subcommand))
+        }
+        Some(_) => None,
+    }
+}
+
+/// What a tool may do, as far as approval is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolSafety {
+    /// Only reads; runs in plan mode
+    ReadOnly,
+    /// Changes files in the workspace
+    EditsFiles,
+    /// Anything else, e.g. runs commands or tools of MCP servers
+    SideEffects,
+}
+
+impl ToolSafety {
+    /// The safety of the tool named `tool`
+    pub fn of(tool: &str) -> Self {
+        if READ_ONLY_TOOLS.contains(&tool) {
+            ToolSafety::ReadOnly
+        } else if FILE_WRITE_TOOLS.contains(&tool) {
+            ToolSafety::EditsFiles
+        } else {
+            ToolSafety::SideEffects
+        }
+    }
+
+    /// Short label for tool listings
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolSafety::ReadOnly => "read-only",
+            ToolSafety::EditsFiles => "edits files",
+            ToolSafety::SideEffects => "side effects",
+        }
+    }
+
+    /// When calls run without asking
+    pub fn description(&self) -> &'static str {
+        match self {
+            ToolSafety::ReadOnly => "only reads; runs without asking in plan mode",
+            ToolSafety::EditsFiles => "changes files; approved without asking in accept-edits mode",
+            ToolSafety::SideEffects => "may change anything; asks unless in full-auto mode",
+        }
+    }
+}
+
+/// How much the agent may do without asking
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PermissionMode {
+    /// Ask before every tool call
+    #[default]
+    Default,
+    /// Read-only tools only; anything else is refused
+    Plan,
+    /// File edits are approved, other tools ask
+    #[serde(alias = "auto-edit")]
+    AcceptEdits,
+    /// Every tool call is approved (YOLO)
+    #[serde(alias = "yolo")]
+    FullAuto,
+}
+
+impl PermissionMode {
+    /// All modes, in order of increasing autonomy
+    pub const ALL: [PermissionMode; 4] = [
+        PermissionMode::Plan,
+        PermissionMode::Default,
+        PermissionMode::AcceptEdits,
+        PermissionMode::FullAuto,
+    ];
+
+    /// Name used in the config, on the command line and in the prompt
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionMode::Default => "default",
+            PermissionMode::Plan => "plan",
+            PermissionMode::AcceptEdits => "accept-edits",
+            PermissionMode::FullAuto => "full-auto",
+        }
+    }
+
+    /// One-line description for help output
+    pub fn description(&self) -> &'static str {
+        match self {
+            PermissionMode::Default => "ask before every tool call",
+            PermissionMode::Plan => "read-only tools only",
+            PermissionMode::AcceptEdits => "approve file edits, ask for the rest",
+            PermissionMode::FullAuto => "approve everything",
+        }
+    }
+
+    /// Whether `tool` may be offered to the model at all
+    pub fn allows(&self, tool: &str) -> bool {
+        *self != PermissionMode::Plan || READ_ONLY_TOOLS.contains(&tool)
+    }
+
+    /// The answer for a call to `tool` that needs no user input, or `None`
+    /// if the user has to be asked
+    pub fn decide(&self, tool: &str) -> Option<ApprovalKind> {
+        match self {
+            PermissionMode::FullAuto => Some(ApprovalKind::Approve),
+            PermissionMode::Plan if READ_ONLY_TOOLS.contains(&tool) => Some(ApprovalKind::Approve),
+            PermissionMode::Plan => Some(ApprovalKind::Reject),
+            PermissionMode::AcceptEdits if FILE_WRITE_TOOLS.contains(&tool) => Some(ApprovalKind::Approve),
+            PermissionMode::AcceptEdits | PermissionMode::Default => None,
+        }
+    }
+}
+
+impl fmt::Display for PermissionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for PermissionMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(PermissionMode::Default),
+            "plan" => Ok(PermissionMode::Plan),
+            "accept-edits" | "auto-edit" => Ok(PermissionMode::AcceptEdits),
+            "full-auto" | "yolo" => Ok(PermissionMode::FullAuto),
+            _ => Err(format!(
+                "unknown permission mode {:?}; expected one of: {}",
+                s,
+                PermissionMode::ALL.map(|m| m.as_str()).join(", ")
+            )),
+        }
+    }
+}
+
+/// How calls to one tool are approved, whatever the permission mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToolRule {
+    /// Run without asking
+    Always,
+    /// Ask the user, even when the mode would approve; what the mode
+    /// refuses stays refused
+    Ask,
+    /// Never offer or run the tool
+    Never,
+}
+
+impl ToolRule {
+    /// All rules, as accepted by `/approve tool`
+    pub const ALL: [ToolRule; 3] = [ToolRule::Always, ToolRule::Ask, ToolRule::Never];
+
+    /// Name used in the config and by `/approve tool`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolRule::Always => "always",
+            ToolRule::Ask => "ask",
+            ToolRule::Never => "never",
+        }
+    }
+}
+
+impl fmt::Display for ToolRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ToolRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ToolRule::ALL.into_iter().find(|rule| rule.as_str() == s).ok_or_else(|| {
+            format!(
+                "unknown approval rule {:?}; expected one of: {}",
+                s,
+                ToolRule::ALL.map(|r| r.as_str()).join(", ")
+            )
+        })
+    }
+}
+
 /// Manages approval requests for tool execution
+///
+/// Clones share the pending request, the permission mode, the tool rules,
+/// the remembered commands and whether anyone is there to answer.
 #[derive(Debug, Clone)]
 pub struct Approval {
-    yolo: bool,
+    mode: Arc<RwLock<PermissionMode>>,
+    rules: Arc<RwLock<HashMap<String, ToolRule>>>,
+    commands: Arc<RwLock<BTreeSet<String>>>,
+    unattended: Arc<AtomicBool>,
     pending: Arc<Mutex<Option<PendingRequest>>>,
 }
 
@@ -17,43 +276,216 @@ pub struct Approval {
 #[derive(Debug)]
 struct PendingRequest {
     request: Request,
-    response_tx: oneshot::Sender<ApprovalKind>,
+    response_tx: oneshot::Sender<(ApprovalKind, Option<serde_json::Value>)>,
 }
 
 impl Approval {
     /// Create a new approval manager
     pub fn new() -> Self {
-        Self {
-            yolo: false,
-            pending: Arc::new(Mutex::new(None)),
-        }
+        Self::with_mode(PermissionMode::Default)
     }
 
     /// Create a new approval manager in yolo mode (auto-approve)
     pub fn yolo() -> Self {
+        Self::with_mode(PermissionMode::FullAuto)
+    }
+
+    /// Create a new approval manager in `mode`
+    pub fn with_mode(mode: PermissionMode) -> Self {
         Self {
-            yolo: true,
+            mode: Arc::new(RwLock::new(mode)),
+            rules: Arc::default(),
+            commands: Arc::default(),
+            unattended: Arc::new(AtomicBool::new(false)),
             pending: Arc::new(Mutex::new(None)),
         }
     }
 
+    /// The current permission mode
+    pub fn mode(&self) -> PermissionMode {
+        *self.mode.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Switch the permission mode, for this manager and its clones
+    pub fn set_mode(&self, mode: PermissionMode) {
+        *self.mode.write().unwrap_or_else(|e| e.into_inner()) = mode;
+        info!("Permission mode set to {}", mode);
+    }
+
+    /// Approve calls to `tools` in every mode
+    pub fn with_allowed_tools(self, tools: impl IntoIterator<Item = String>) -> Self {
+        self.with_rules(tools.into_iter().map(|tool| (tool, ToolRule::Always)))
+    }
+
+    /// Apply `rules` on top of the rules already set
+    pub fn with_rules(self, rules: impl IntoIterator<Item = (String, ToolRule)>) -> Self {
+        self.rules.write().unwrap_or_else(|e| e.into_inner()).extend(rules);
+        self
+    }
+
+    /// The rule for `tool`, if it has one
+    pub fn rule(&self, tool: &str) -> Option<ToolRule> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).get(tool).copied()
+    }
+
+    /// Tool rules, sorted by tool name
+    pub fn rules(&self) -> Vec<(String, ToolRule)> {
+        let mut rules: Vec<(String, ToolRule)> = self
+            .rules
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(tool, rule)| (tool.clone(), *rule))
+            .collect();
+        rules.sort_by(|a, b| a.0.cmp(&b.0));
+        rules
+    }
+
+    /// Set the rule for `tool`, or remove it so the permission mode
+    /// decides, for this manager and its clones
+    pub fn set_rule(&self, tool: &str, rule: Option<ToolRule>) {
+        let mut rules = self.rules.write().unwrap_or_else(|e| e.into_inner());
+        match rule {
+            Some(rule) => {
+                rules.insert(tool.to_string(), rule);
+                info!("Approval rule for {} set to {}", tool, rule);
+            }
+            None => {
+                rules.remove(tool);
+                info!("Approval rule for {} removed", tool);
+            }
+        }
+    }
+
+    /// Run Shell commands with these patterns without asking
+    pub fn with_commands(self, patterns: impl IntoIterator<Item = String>) -> Self {
+        self.commands.write().unwrap_or_else(|e| e.into_inner()).extend(patterns);
+        self
+    }
+
+    /// Patterns of Shell commands that run without asking, sorted
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.read().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// Ask about Shell commands with `pattern` again; whether it was
+    /// remembered
+    pub fn forget_command(&self, pattern: &str) -> bool {
+        self.commands.write().unwrap_or_else(|e| e.into_inner()).remove(pattern)
+    }
+
+    /// Stop asking about calls like `request` for the rest of the session:
+    /// Shell commands with the same pattern, or the tool altogether
+    ///
+    /// Returns what is approved from now on, or `None` for a Shell command
+    /// without a pattern, which is still asked about.
+    pub fn remember(&self, request: &Request) -> Option<String> {
+        let scope = Self::always_scope(request)?;
+        match &request.command_pattern {
+            Some(pattern) if request.action == SHELL_TOOL => {
+                info!("Remembering approval of Shell commands like {}", pattern);
+                self.commands.write().unwrap_or_else(|e| e.into_inner()).insert(pattern.clone());
+            }
+            _ => self.set_rule(&request.action, Some(ToolRule::Always)),
+        }
+        Some(scope)
+    }
+
+    /// What answering `request` with [`ApprovalKind::ApproveAlways`] would
+    /// approve from then on, or `None` if it cannot be remembered
+    pub fn always_scope(request: &Request) -> Option<String> {
+        if request.action != SHELL_TOOL {
+            return Some(format!("every {} call", request.action));
+        }
+        let pattern = request.command_pattern.as_ref()?;
+        Some(format!("{} commands starting with `{}`", SHELL_TOOL, pattern))
+    }
+
+    /// Whether `tool` may be offered to the model: allowed by its rule or
+    /// by the permission mode
+    pub fn offers(&self, tool: &str) -> bool {
+        match self.rule(tool) {
+            Some(ToolRule::Always) => true,
+            Some(ToolRule::Never) => false,
+            Some(ToolRule::Ask) | None => self.mode().allows(tool),
+        }
+    }
+
+    /// Whether requests the permission mode leaves open are rejected
+    /// instead of waiting for an answer
+    pub fn is_unattended(&self) -> bool {
+        self.unattended.load(Ordering::Relaxed)
+    }
+
+    /// Reject requests the permission mode leaves open, for running
+    /// without anyone to answer them
+    pub fn set_unattended(&self, unattended: bool) {
+        self.unattended.store(unattended, Ordering::Relaxed);
+    }
+
     /// Check if in yolo mode
     pub fn is_yolo(&self) -> bool {
-        self.yolo
+        self.mode() == PermissionMode::FullAuto
     }
 
     /// Set yolo mode
     pub fn set_yolo(&mut self, yolo: bool) {
-        self.yolo = yolo;
+        self.set_mode(if yolo { PermissionMode::FullAuto } else { PermissionMode::Default });
+    }
+
+    /// The answer the tool rules or the permission mode give for
+    /// `request` without asking
+    pub fn decide(&self, request: &Request) -> Option<ApprovalKind> {
+        let decided = self.decide_tool(&request.action);
+        // A remembered command runs unless the tool is refused
+        if request.action == SHELL_TOOL && !matches!(decided, Some(ApprovalKind::Reject)) {
+            if let Some(pattern) = &request.command_pattern {
+                if self.commands.read().unwrap_or_else(|e| e.into_inner()).contains(pattern) {
+                    return Some(ApprovalKind::Approve);
+                }
+            }
+        }
+        decided
+    }
+
+    /// The answer the rule for `tool` or the permission mode gives for a
+    /// call to it without asking
+    pub fn decide_tool(&self, tool: &str) -> Option<ApprovalKind> {
+        let decided = self.mode().decide(tool);
+        match self.rule(tool) {
+            Some(ToolRule::Always) => Some(ApprovalKind::Approve),
+            Some(ToolRule::Never) => Some(ApprovalKind::Reject),
+            Some(ToolRule::Ask) => decided.filter(|kind| matches!(kind, ApprovalKind::Reject)),
+            None => decided,
+        }
+    }
+
+    /// Why a call to `tool` was refused without asking, for the tool result
+    pub fn refusal(&self, tool: &str) -> String {
+        if self.rule(tool) == Some(ToolRule::Never) {
+            return format!("Tool '{}' is denied by an approval rule", tool);
+        }
+        let mode = self.mode();
+        format!("Tool '{}' is not allowed in {} mode ({})", tool, mode, mode.description())
     }
 
     /// Request approval for a tool execution
-    /// Returns ApprovalKind::Approve immediately if in yolo mode,
+    /// Returns at once if the permission mode decides the request,
     /// otherwise waits for a response
     pub async fn request(&self, request: Request) -> ApprovalKind {
-        if self.yolo {
-            info!("Yolo mode active, auto-approving request {}", request.id);
-            return ApprovalKind::Approve;
+        self.request_with_edits(request).await.0
+    }
+
+    /// Like [`request`](Self::request), also returning the tool arguments
+    /// the user edited before approving, if they did
+    pub async fn request_with_edits(&self, request: Request) -> (ApprovalKind, Option<serde_json::Value>) {
+        if let Some(kind) = self.decide(&request) {
+            info!("{} mode or a tool rule answered request {} with {:?}", self.mode(), request.id, kind);
+            return (kind, None);
+        }
+        if self.is_unattended() {
+            info!("No one to approve request {}, rejecting it", request.id);
+            return (ApprovalKind::Reject, None);
         }
 
         let (tx, rx) = oneshot::channel();
@@ -63,7 +495,7 @@ impl Approval {
             if pending.is_some() {
                 warn!("Another approval request is already pending");
                 // Return reject if there's already a pending request
-                return ApprovalKind::Reject;
+                return (ApprovalKind::Reject, None);
             }
             *pending = Some(PendingRequest {
                 request,
@@ -82,19 +514,32 @@ impl Approval {
                 warn!("Approval response channel closed");
                 let mut pending = self.pending.lock().await;
                 *pending = None;
-                ApprovalKind::Reject
+                (ApprovalKind::Reject, None)
             }
         }
     }
 
     /// Respond to a pending approval request
     pub async fn respond(&self, response: ApprovalKind) -> Result<(), ApprovalError> {
+        self.send_response(response, None).await
+    }
+
+    /// Approve the pending request with edited tool arguments, which the
+    /// tool runs with instead of the ones the model gave
+    pub async fn respond_with_arguments(&self, arguments: serde_json::Value) -> Result<(), ApprovalError> {
+        self.send_response(ApprovalKind::ApproveOnce, Some(arguments)).await
+    }
+
+    async fn send_response(&self, response: ApprovalKind, arguments: Option<serde_json::Value>) -> Result<(), ApprovalError> {
         let mut pending = self.pending.lock().await;
         
         if let Some(pending_request) = pending.take() {
+            if matches!(response, ApprovalKind::ApproveAlways) {
+                self.remember(&pending_request.request);
+            }
             pending_request
                 .response_tx
-                .send(response)
+                .send((response, arguments))
                 .map_err(|_| ApprovalError::SendFailed)?;
             Ok(())
         } else {
@@ -119,7 +564,7 @@ impl Approval {
         let mut pending = self.pending.lock().await;
         
         if let Some(pending_request) = pending.take() {
-            let _ = pending_request.response_tx.send(ApprovalKind::Reject);
+            let _ = pending_request.response_tx.send((ApprovalKind::Reject, None));
             info!("Cancelled pending approval request");
         }
         
@@ -182,6 +627,8 @@ mod tests {
             sender: "test-agent".to_string(),
             action: "write_file".to_string(),
             description: "Write to /tmp/test.txt".to_string(),
+            edit: None,
+            command_pattern: None,
         }
     }
 
@@ -249,6 +696,132 @@ mod tests {
         assert!(matches!(result, ApprovalKind::Reject));
     }
 
+    #[tokio::test]
+    async fn test_permission_modes() {
+        let request = |action: &str| Request { action: action.to_string(), ..create_test_request() };
+        let approval = Approval::with_mode(PermissionMode::Plan);
+        assert!(matches!(approval.request(request("ReadFile")).await, ApprovalKind::Approve));
+        assert!(matches!(approval.request(request("Shell")).await, ApprovalKind::Reject));
+        assert!(!PermissionMode::Plan.allows("WriteFile"));
+
+        // Clones follow a mode switch
+        let clone = approval.clone();
+        approval.set_mode(PermissionMode::AcceptEdits);
+        assert!(matches!(clone.decide(&request("WriteFile")), Some(ApprovalKind::Approve)));
+        assert!(clone.decide(&request("Shell")).is_none());
+
+        // Allowed tools run in any mode
+        let allowed = Approval::with_mode(PermissionMode::Plan).with_allowed_tools(["Shell".to_string()]);
+        assert!(matches!(allowed.request(request("Shell")).await, ApprovalKind::Approve));
+        assert!(matches!(allowed.decide(&request("WriteFile")), Some(ApprovalKind::Reject)));
+        assert!(allowed.offers("Shell") && !allowed.offers("WriteFile"));
+
+        // Unattended, what the mode leaves open is rejected without waiting
+        clone.set_unattended(true);
+        assert!(matches!(approval.request(request("WriteFile")).await, ApprovalKind::Approve));
+        assert!(matches!(approval.request(request("Shell")).await, ApprovalKind::Reject));
+        assert!(!approval.has_pending().await);
+
+        assert_eq!(ToolSafety::of("Grep"), ToolSafety::ReadOnly);
+        assert_eq!(ToolSafety::of("StrReplaceFile"), ToolSafety::EditsFiles);
+        assert_eq!(ToolSafety::of("Shell").as_str(), "side effects");
+
+        assert_eq!("auto-edit".parse(), Ok(PermissionMode::AcceptEdits));
+        assert_eq!("yolo".parse(), Ok(PermissionMode::FullAuto));
+        assert!("reckless".parse::<PermissionMode>().is_err());
+        assert_eq!(PermissionMode::FullAuto.to_string(), "full-auto");
+    }
+
+    #[tokio::test]
+    async fn test_tool_rules() {
+        let request = |action: &str| Request { action: action.to_string(), ..create_test_request() };
+        let approval = Approval::with_mode(PermissionMode::FullAuto).with_rules([
+            ("Shell".to_string(), ToolRule::Ask),
+            ("FetchURL".to_string(), ToolRule::Never),
+        ]);
+        assert!(matches!(approval.decide(&request("ReadFile")), Some(ApprovalKind::Approve)));
+        // Ask overrides the mode's approval, never takes the tool away
+        assert!(approval.decide(&request("Shell")).is_none());
+        assert!(matches!(approval.decide(&request("FetchURL")), Some(ApprovalKind::Reject)));
+        assert!(!approval.offers("FetchURL"));
+        assert_eq!(approval.refusal("FetchURL"), "Tool 'FetchURL' is denied by an approval rule");
+
+        // Rules are shared with clones and editable at runtime
+        let clone = approval.clone();
+        approval.set_rule("Shell", Some(ToolRule::Always));
+        approval.set_mode(PermissionMode::Plan);
+        assert!(matches!(clone.decide(&request("Shell")), Some(ApprovalKind::Approve)));
+        assert!(clone.offers("Shell"));
+        // What the mode refuses, asking does not allow
+        approval.set_rule("WriteFile", Some(ToolRule::Ask));
+        assert!(matches!(clone.decide(&request("WriteFile")), Some(ApprovalKind::Reject)));
+        assert!(clone.refusal("WriteFile").contains("not allowed in plan mode"));
+        approval.set_rule("FetchURL", None);
+        assert!(clone.offers("FetchURL"));
+
+        assert_eq!(
+            clone.rules(),
+            vec![("Shell".to_string(), ToolRule::Always), ("WriteFile".to_string(), ToolRule::Ask)]
+        );
+        assert_eq!("never".parse(), Ok(ToolRule::Never));
+        assert!("sometimes".parse::<ToolRule>().is_err());
+    }
+
+    #[test]
+    fn test_command_pattern() {
+        assert_eq!(command_pattern("git status --short").as_deref(), Some("git status"));
+        assert_eq!(command_pattern("  cargo build --release").as_deref(), Some("cargo build"));
+        assert_eq!(command_pattern("ls").as_deref(), Some("ls"));
+        // Without a subcommand, the program alone would approve any arguments
+        assert_eq!(command_pattern("ls -la src"), None);
+        assert_eq!(command_pattern("cat src/main.rs"), None);
+        assert_eq!(command_pattern("find . -name x"), None);
+        assert_eq!(command_pattern("find src -name x"), None);
+        assert_eq!(command_pattern("xargs rm"), None);
+        assert_eq!(command_pattern("sudo apt install"), None);
+        assert_eq!(command_pattern("git status && rm -rf target"), None);
+        assert_eq!(command_pattern("echo $(whoami)"), None);
+        assert_eq!(command_pattern("cargo test > out.txt"), None);
+        assert_eq!(command_pattern("RUST_LOG=debug cargo run"), None);
+        assert_eq!(command_pattern("   "), None);
+    }
+
+    #[tokio::test]
+    async fn test_approve_always() {
+        let shell = |command: &str| Request {
+            action: SHELL_TOOL.to_string(),
+            command_pattern: command_pattern(command),
+            ..create_test_request()
+        };
+        let approval = Approval::new();
+        let clone = approval.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(tokio::time::Duration::from_millis(10)).await;
+            clone.respond(ApprovalKind::ApproveAlways).await.unwrap();
+        });
+        assert!(matches!(approval.request(shell("git status")).await, ApprovalKind::ApproveAlways));
+
+        // The same pattern runs without asking; others, and the tool, still ask
+        assert!(matches!(approval.decide(&shell("git status --short")), Some(ApprovalKind::Approve)));
+        assert!(approval.decide(&shell("git push")).is_none());
+        assert!(approval.decide(&shell("git status; git push")).is_none());
+        assert_eq!(approval.rule(SHELL_TOOL), None);
+        assert_eq!(approval.commands(), ["git status"]);
+        assert_eq!(Approval::always_scope(&shell("git status | head")), None);
+
+        // Other tools are remembered altogether
+        let write = create_test_request();
+        assert_eq!(approval.remember(&write).as_deref(), Some("every write_file call"));
+        assert!(matches!(approval.decide(&write), Some(ApprovalKind::Approve)));
+
+        // A refused tool stays refused
+        approval.set_mode(PermissionMode::Plan);
+        assert!(matches!(approval.decide(&shell("git status")), Some(ApprovalKind::Reject)));
+        approval.set_mode(PermissionMode::Default);
+        assert!(approval.forget_command("git status"));
+        assert!(approval.decide(&shell("git status")).is_none());
+    }
+
     #[tokio::test]
     async fn test_no_pending_request() {
         let approval = Approval::new();
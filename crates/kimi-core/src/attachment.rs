@@ -0,0 +1,389 @@
+//! Loading attachments for the model
+//!
+//! Attachments of a [`UserInput`](crate::types::UserInput) are stored with
+//! its message in the context and turned into content parts each time the
+//! message is sent:
+//!
+//! - text files are added as text, wrapped in a `<file>` element
+//! - images are sent as base64 data URLs to models with vision support, and
+//!   mentioned in a short note otherwise
+//! - URLs are sent as images when they point at one, and otherwise listed so
+//!   the agent can fetch them with its tools
+//!
+//! Frontends turn `@path` mentions in the message text into file
+//! attachments with [`mentioned_files`] (custom commands parse them with
+//! the same [`mentions`]), paths dropped into the terminal
+//! with [`dropped_files`], and paths given on the command line with
+//! [`file`] and [`image`].
+
+use crate::types::Attachment;
+use crate::workspace::Workspace;
+use base64::Engine;
+use kosong_rs::ContentPart;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Largest text file added to a message
+pub const MAX_FILE_BYTES: u64 = 256 * 1024;
+
+/// Largest image sent to the model
+pub const MAX_IMAGE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// MIME type of an image path or URL, judged by its extension
+pub fn image_mime_type(path: &str) -> Option<&'static str> {
+    let path = path.split(['?', '#']).next().unwrap_or(path);
+    let extension = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+/// Characters ending a sentence after an `@path` mention, not part of it
+const MENTION_TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '"', '\''];
+
+/// An `@path` mention in a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mention<'a> {
+    /// Byte offset of the `@` in the text
+    pub offset: usize,
+    /// The path after the `@`
+    pub path: &'a str,
+}
+
+/// The `@path` mentions in `text`
+///
+/// A mention is a word starting with `@`, so `me@example.com` is not one.
+/// Punctuation ending a sentence after it is not part of the path.
+pub fn mentions(text: &str) -> Vec<Mention<'_>> {
+    let mut mentions = Vec::new();
+    let mut at_word_start = true;
+    for (offset, c) in text.char_indices() {
+        if c == '@' && at_word_start {
+            let after = &text[offset + 1..];
+            let word = &after[..after.find(char::is_whitespace).unwrap_or(after.len())];
+            let path = word.trim_end_matches(MENTION_TRAILING);
+            if !path.is_empty() {
+                mentions.push(Mention { offset, path });
+            }
+        }
+        at_word_start = c.is_whitespace();
+    }
+    mentions
+}
+
+/// The file a mentioned `path` names, if it is one
+///
+/// `~/` paths are taken from the home directory and other relative paths
+/// from `work_dir`. With a `workspace`, files outside it are not resolved.
+pub fn resolve_mention(path: &str, work_dir: &Path, workspace: Option<&Workspace>) -> Option<PathBuf> {
+    let path = match path.strip_prefix("~/") {
+        Some(rest) => dirs::home_dir()?.join(rest),
+        None => work_dir.join(path),
+    };
+    if !path.is_file() {
+        return None;
+    }
+    match workspace {
+        Some(workspace) => workspace.resolve(&path).ok(),
+        None => Some(path),
+    }
+}
+
+/// Files mentioned as `@path` in `text`, as attachments
+///
+/// Paths are resolved with [`resolve_mention`]. Mentions that are not
+/// files, like `@someone`, or are outside the `workspace` are ignored;
+/// files over the size limits come back as errors so they can be reported
+/// and left out.
+pub fn mentioned_files(
+    text: &str,
+    work_dir: &Path,
+    workspace: Option<&Workspace>,
+) -> Vec<Result<Attachment, AttachmentError>> {
+    let mut seen = Vec::new();
+    let mut attachments = Vec::new();
+    for mention in mentions(text) {
+        let Some(path) = resolve_mention(mention.path, work_dir, workspace) else {
+            continue;
+        };
+        if seen.contains(&path) {
+            continue;
+        }
+        seen.push(path.clone());
+        attachments.push(file(&path));
+    }
+    attachments
+}
+
+/// Files whose paths were dropped into the terminal, as attachments
+///
+/// Dropping a file pastes its absolute path, quoted or with escaped
+/// spaces depending on the terminal, or a `file://` URL. Such words that
+/// name existing files are attached; files over the size limits come back
+/// as errors.
+pub fn dropped_files(text: &str) -> Vec<Result<Attachment, AttachmentError>> {
+    let mut seen = Vec::new();
+    let mut dropped = Vec::new();
+    for word in shell_words(text) {
+        let word = word.strip_prefix("file://").unwrap_or(&word);
+        let path = match word.strip_prefix("~/") {
+            Some(rest) => match dirs::home_dir() {
+                Some(home) => home.join(rest),
+                None => continue,
+            },
+            None if word.starts_with('/') => PathBuf::from(word),
+            None => continue,
+        };
+        if !path.is_file() || seen.contains(&path) {
+            continue;
+        }
+        seen.push(path.clone());
+        dropped.push(file(&path));
+    }
+    dropped
+}
+
+/// A file attachment for `path`, sent as an image if it is one
+///
+/// Errors if the file cannot be read or is over the size limit.
+pub fn file(path: &Path) -> Result<Attachment, AttachmentError> {
+    let attachment = Attachment::from_path(path);
+    let limit = match attachment {
+        Attachment::Image { .. } => MAX_IMAGE_BYTES,
+        _ => MAX_FILE_BYTES,
+    };
+    check_size(path, limit)?;
+    Ok(attachment)
+}
+
+/// An image attachment for `path`
+///
+/// Errors unless it is a readable PNG, JPEG, GIF or WebP image within the
+/// size limit.
+pub fn image(path: &Path) -> Result<Attachment, AttachmentError> {
+    if image_mime_type(&path.to_string_lossy()).is_none() {
+        return Err(AttachmentError::UnsupportedImage(path.to_path_buf()));
+    }
+    check_size(path, MAX_IMAGE_BYTES)?;
+    Ok(Attachment::Image { path: path.to_path_buf() })
+}
+
+/// Words of `text` split as a shell would: quotes group words and a
+/// backslash escapes the next character
+fn shell_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '\\') => {
+                word.extend(chars.next());
+                in_word = true;
+            }
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// Load `attachments` as content parts, in order
+///
+/// `vision` tells whether the model can view images.
+pub fn load_parts(attachments: &[Attachment], vision: bool) -> Result<Vec<ContentPart>, AttachmentError> {
+    attachments.iter().map(|attachment| load_part(attachment, vision)).collect()
+}
+
+/// Load one attachment as a content part
+pub fn load_part(attachment: &Attachment, vision: bool) -> Result<ContentPart, AttachmentError> {
+    match attachment {
+        Attachment::File { path } => {
+            let bytes = read_limited(path, MAX_FILE_BYTES)?;
+            let text = String::from_utf8(bytes).map_err(|_| AttachmentError::NotText(path.clone()))?;
+            Ok(ContentPart::text(format!(
+                "<file path=\"{}\">\n{}\n</file>",
+                path.display(),
+                text.trim_end()
+            )))
+        }
+        Attachment::Image { path } => {
+            let mime_type = image_mime_type(&path.to_string_lossy())
+                .ok_or_else(|| AttachmentError::UnsupportedImage(path.clone()))?;
+            let bytes = read_limited(path, MAX_IMAGE_BYTES)?;
+            if !vision {
+                return Ok(ContentPart::text(format!(
+                    "[Image {} is attached, but this model cannot view images]",
+                    path.display()
+                )));
+            }
+            let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+            Ok(ContentPart::image_base64(mime_type, &data))
+        }
+        Attachment::Url { url } if vision && image_mime_type(url).is_some() => Ok(ContentPart::image_url(url.clone())),
+        Attachment::Url { url } => Ok(ContentPart::text(format!("[Attached URL: {}]", url))),
+    }
+}
+
+fn read_limited(path: &Path, limit: u64) -> Result<Vec<u8>, AttachmentError> {
+    check_size(path, limit)?;
+    std::fs::read(path).map_err(|source| AttachmentError::Io {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+fn check_size(path: &Path, limit: u64) -> Result<(), AttachmentError> {
+    let size = std::fs::metadata(path)
+        .map_err(|source| AttachmentError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?
+        .len();
+    if size > limit {
+        return Err(AttachmentError::TooLarge {
+            path: path.to_path_buf(),
+            size,
+            limit,
+        });
+    }
+    Ok(())
+}
+
+/// Attachment errors
+#[derive(Debug, Error)]
+pub enum AttachmentError {
+    #[error("Cannot read {path:?}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("{path:?} is {size} bytes, over the {limit} byte limit")]
+    TooLarge { path: PathBuf, size: u64, limit: u64 },
+    #[error("{0:?} is not a text file")]
+    NotText(PathBuf),
+    #[error("{0:?} is not a PNG, JPEG, GIF or WebP image")]
+    UnsupportedImage(PathBuf),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_parts() {
+        let temp = tempfile::tempdir().unwrap();
+        let notes = temp.path().join("notes.md");
+        std::fs::write(&notes, "# Notes\n").unwrap();
+        let image = temp.path().join("shot.PNG");
+        std::fs::write(&image, [0x89, b'P', b'N', b'G']).unwrap();
+
+        let attachments = vec![
+            Attachment::from_path(&notes),
+            Attachment::from_path(&image),
+            Attachment::Url { url: "https://example.com/cat.jpg?size=large".to_string() },
+            Attachment::Url { url: "https://example.com/docs".to_string() },
+        ];
+        assert_eq!(attachments[1], Attachment::Image { path: image.clone() });
+
+        let parts = load_parts(&attachments, true).unwrap();
+        assert_eq!(parts[0], ContentPart::text(format!("<file path=\"{}\">\n# Notes\n</file>", notes.display())));
+        assert_eq!(parts[1], ContentPart::image_url("data:image/png;base64,iVBORw=="));
+        assert_eq!(parts[2], ContentPart::image_url("https://example.com/cat.jpg?size=large"));
+        assert_eq!(parts[3], ContentPart::text("[Attached URL: https://example.com/docs]"));
+
+        let parts = load_parts(&attachments, false).unwrap();
+        assert!(parts[1].as_text().unwrap().contains("cannot view images"));
+        assert!(parts[2].as_text().unwrap().starts_with("[Attached URL"));
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let temp = tempfile::tempdir().unwrap();
+        let missing = Attachment::File { path: temp.path().join("missing.txt") };
+        assert!(matches!(load_part(&missing, true), Err(AttachmentError::Io { .. })));
+
+        let binary = temp.path().join("data.bin");
+        std::fs::write(&binary, [0xff, 0xfe, 0x00]).unwrap();
+        let binary = Attachment::File { path: binary };
+        assert!(matches!(load_part(&binary, true), Err(AttachmentError::NotText(_))));
+
+        let bmp = temp.path().join("image.bmp");
+        std::fs::write(&bmp, [0u8; 4]).unwrap();
+        let bmp = Attachment::Image { path: bmp };
+        assert!(matches!(load_part(&bmp, true), Err(AttachmentError::UnsupportedImage(_))));
+    }
+
+    #[test]
+    fn test_mentioned_files() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp.path().join("src")).unwrap();
+        std::fs::write(temp.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(temp.path().join("big.log"), vec![b'x'; MAX_FILE_BYTES as usize + 1]).unwrap();
+
+        let text = "Compare @src/main.rs, @big.log and @src with @src/main.rs; cc @someone or me@src/main.rs";
+        let mentions = mentioned_files(text, temp.path(), None);
+        assert_eq!(mentions.len(), 2);
+        assert_eq!(mentions[0].as_ref().unwrap(), &Attachment::File { path: temp.path().join("src/main.rs") });
+        assert!(matches!(mentions[1], Err(AttachmentError::TooLarge { .. })));
+
+        // Files outside the workspace are not attached
+        let workspace = Workspace::new(temp.path().join("src")).unwrap();
+        let mentions = mentioned_files("@main.rs and @../big.log", &temp.path().join("src"), Some(&workspace));
+        assert_eq!(mentions.len(), 1);
+        assert_eq!(mentions[0].as_ref().unwrap(), &Attachment::File { path: workspace.roots()[0].join("main.rs") });
+    }
+
+    #[test]
+    fn test_mentions() {
+        let text = "@a.rs, [see @src/lib.rs] me@example.com @ @b.rs.";
+        let found: Vec<_> = mentions(text).into_iter().map(|m| (m.offset, m.path)).collect();
+        assert_eq!(found, [(0, "a.rs"), (12, "src/lib.rs"), (42, "b.rs")]);
+    }
+
+    #[test]
+    fn test_dropped_and_given_files() {
+        let temp = tempfile::tempdir().unwrap();
+        let spaced = temp.path().join("my notes.md");
+        std::fs::write(&spaced, "# Notes\n").unwrap();
+        let shot = temp.path().join("shot.png");
+        std::fs::write(&shot, [0x89, b'P', b'N', b'G']).unwrap();
+
+        let escaped = spaced.display().to_string().replace(' ', "\\ ");
+        let text = format!(
+            "Compare {} with '{}' and file://{} but not {} or relative.md",
+            escaped,
+            spaced.display(),
+            shot.display(),
+            temp.path().join("missing.md").display()
+        );
+        let dropped: Vec<_> = dropped_files(&text).into_iter().map(Result::unwrap).collect();
+        assert_eq!(dropped, [Attachment::File { path: spaced.clone() }, Attachment::Image { path: shot.clone() }]);
+
+        assert_eq!(file(&shot).unwrap(), Attachment::Image { path: shot.clone() });
+        assert!(matches!(image(&spaced), Err(AttachmentError::UnsupportedImage(_))));
+        assert!(matches!(file(&temp.path().join("missing.md")), Err(AttachmentError::Io { .. })));
+    }
+}
@@ -0,0 +1,192 @@
+//! Multiple accounts per provider
+//!
+//! An OAuth provider's accounts are the tokens stored for its platform:
+//! `oauth/<platform>` for the default account and `oauth/<platform>/<name>`
+//! for the others. An API-key provider's accounts are the secrets named
+//! `<provider>` and `<provider>/<name>`. The provider's `oauth` or
+//! `api_key_ref` in the config points at the active one, so switching
+//! accounts only rewrites that reference.
+
+use crate::auth::secrets::{SecretError, SecretsManager};
+use crate::auth::storage::{list_token_accounts, load_token, OAuthRef};
+use crate::config::{Config, LlmProvider};
+
+/// Name of the account used when none is given
+pub const DEFAULT_ACCOUNT: &str = "default";
+
+/// Check that `name` can be used as an account name
+pub fn validate_account_name(name: &str) -> Result<(), AccountError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if valid {
+        Ok(())
+    } else {
+        Err(AccountError::InvalidName(name.to_string()))
+    }
+}
+
+/// Name of the secret holding an API-key provider's account
+pub fn account_secret_name(provider_key: &str, account: &str) -> String {
+    if account == DEFAULT_ACCOUNT {
+        provider_key.to_string()
+    } else {
+        format!("{}/{}", provider_key, account)
+    }
+}
+
+/// The account a provider's credentials currently come from
+///
+/// `None` if the provider has a plain API key or references a secret that
+/// is not one of its accounts.
+pub fn active_account(provider_key: &str, provider: &LlmProvider) -> Option<String> {
+    if let Some(oauth) = &provider.oauth {
+        return Some(oauth.account().to_string());
+    }
+    let secret = provider.api_key_ref.as_deref()?;
+    if secret == provider_key {
+        return Some(DEFAULT_ACCOUNT.to_string());
+    }
+    secret
+        .strip_prefix(provider_key)
+        .and_then(|rest| rest.strip_prefix('/'))
+        .map(str::to_string)
+}
+
+/// The accounts stored for a provider, sorted
+pub fn list_accounts(
+    provider_key: &str,
+    provider: &LlmProvider,
+    secrets: &SecretsManager,
+) -> Result<Vec<String>, AccountError> {
+    if let Some(oauth) = &provider.oauth {
+        return Ok(oauth.platform_id().map(list_token_accounts).unwrap_or_default());
+    }
+    let prefix = format!("{}/", provider_key);
+    let mut accounts: Vec<String> = secrets
+        .list()?
+        .into_iter()
+        .filter_map(|(name, _)| {
+            if name == provider_key {
+                Some(DEFAULT_ACCOUNT.to_string())
+            } else {
+                name.strip_prefix(&prefix).map(str::to_string)
+            }
+        })
+        .collect();
+    accounts.sort();
+    Ok(accounts)
+}
+
+/// Point a provider at another of its stored accounts
+///
+/// The caller saves the config for the switch to last.
+pub fn switch_account(
+    config: &mut Config,
+    provider_key: &str,
+    account: &str,
+    secrets: &SecretsManager,
+) -> Result<(), AccountError> {
+    validate_account_name(account)?;
+    let provider = config
+        .providers
+        .get_mut(provider_key)
+        .ok_or_else(|| AccountError::UnknownProvider(provider_key.to_string()))?;
+    let unknown = || AccountError::UnknownAccount {
+        provider: provider_key.to_string(),
+        account: account.to_string(),
+    };
+
+    if let Some(oauth) = &provider.oauth {
+        let platform_id = oauth.platform_id().ok_or_else(unknown)?;
+        let oauth_ref = OAuthRef::for_account(platform_id, account);
+        if load_token(&oauth_ref).is_none() {
+            return Err(unknown());
+        }
+        provider.oauth = Some(oauth_ref);
+    } else {
+        let secret = account_secret_name(provider_key, account);
+        if secrets.get(&secret)?.is_none() {
+            return Err(unknown());
+        }
+        provider.api_key_ref = Some(secret);
+    }
+    Ok(())
+}
+
+/// Account errors
+#[derive(Debug, thiserror::Error)]
+pub enum AccountError {
+    #[error("Invalid account name {0:?}: use letters, digits and - _ .")]
+    InvalidName(String),
+    #[error("No provider named {0} in the config")]
+    UnknownProvider(String),
+    #[error("Provider {provider} has no account named {account}")]
+    UnknownAccount { provider: String, account: String },
+    #[error(transparent)]
+    Secret(#[from] SecretError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::ProviderType;
+
+    #[test]
+    fn test_api_key_accounts() {
+        let dir = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::file_only(dir.path());
+        secrets.set("openai", "sk-personal").unwrap();
+        secrets.set("openai/work", "sk-work").unwrap();
+        secrets.set("openai-other", "sk-other").unwrap();
+
+        let provider = LlmProvider::new(ProviderType::OpenAiLegacy, "https://api.openai.com/v1", "")
+            .with_api_key_ref("openai");
+        let mut config: Config = toml::from_str(
+            r#"
+default_model = ""
+default_thinking = false
+default_yolo = false
+models = {}
+providers = {}
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#,
+        )
+        .unwrap();
+        config.providers.insert("openai".to_string(), provider);
+
+        let provider = &config.providers["openai"];
+        assert_eq!(list_accounts("openai", provider, &secrets).unwrap(), [DEFAULT_ACCOUNT, "work"]);
+        assert_eq!(active_account("openai", provider).as_deref(), Some(DEFAULT_ACCOUNT));
+
+        switch_account(&mut config, "openai", "work", &secrets).unwrap();
+        let provider = &config.providers["openai"];
+        assert_eq!(provider.api_key_ref.as_deref(), Some("openai/work"));
+        assert_eq!(active_account("openai", provider).as_deref(), Some("work"));
+
+        assert!(matches!(
+            switch_account(&mut config, "openai", "missing", &secrets),
+            Err(AccountError::UnknownAccount { .. })
+        ));
+        assert!(matches!(
+            switch_account(&mut config, "openai", "../x", &secrets),
+            Err(AccountError::InvalidName(_))
+        ));
+        assert!(matches!(
+            switch_account(&mut config, "nope", "work", &secrets),
+            Err(AccountError::UnknownProvider(_))
+        ));
+    }
+}
@@ -2,7 +2,9 @@
 
 use crate::auth::oauth::{refresh_token, OAuthError, OAuthToken};
 use crate::auth::storage::{delete_token, load_token, save_token, OAuthRef};
-use crate::auth::{KIMI_CODE_PLATFORM_ID, REFRESH_INTERVAL_SECONDS};
+use crate::auth::platforms::PlatformRegistry;
+use crate::auth::token_source::OAuthTokenSource;
+use crate::auth::REFRESH_INTERVAL_SECONDS;
 use crate::config::Config;
 use secrecy::ExposeSecret;
 use secrecy::SecretString;
@@ -174,51 +176,35 @@ impl OAuthManager {
     /// Resolve API key (from OAuth or config)
     pub fn resolve_api_key(&self, api_key: &SecretString, oauth: Option<&OAuthRef>) -> String {
         if let Some(ref_) = oauth {
-            // Try cache first
-            if let Some(token) = self.access_tokens.get(&ref_.key) {
-                return token.clone();
-            }
-
-            // Try loading from storage
+            // Storage first: the background refresh saves new tokens there
             if let Some(token) = load_token(ref_) {
                 return token.access_token;
             }
+
+            if let Some(token) = self.access_tokens.get(&ref_.key) {
+                return token.clone();
+            }
         }
 
         api_key.expose_secret().clone()
     }
 
-    /// Get the Kimi Code OAuth reference
-    fn kimi_code_ref(&self) -> Option<OAuthRef> {
-        use crate::auth::platforms::managed_provider_key;
-
-        let provider_key = managed_provider_key(KIMI_CODE_PLATFORM_ID);
-        self.config
-            .providers
-            .get(&provider_key)
-            .and_then(|p| p.oauth.clone())
-    }
-
-    /// Ensure fresh token (refresh if needed)
+    /// Ensure fresh tokens for every OAuth provider (refresh if needed)
     pub async fn ensure_fresh(&mut self) -> Result<(), OAuthError> {
-        let ref_ = match self.kimi_code_ref() {
-            Some(r) => r,
-            None => return Ok(()),
-        };
-
-        let token = match load_token(&ref_) {
-            Some(t) => t,
-            None => return Ok(()),
-        };
+        for ref_ in self.iter_oauth_refs() {
+            let token = match load_token(&ref_) {
+                Some(t) => t,
+                None => continue,
+            };
 
-        self.do_cache_access_token(&ref_.key, &token.access_token);
+            self.do_cache_access_token(&ref_.key, &token.access_token);
 
-        // Check if refresh is needed
-        if !token.needs_refresh() {
-            return Ok(());
+            // Check if refresh is needed
+            if token.needs_refresh() {
+                self.refresh_tokens(&ref_, &token).await?;
+            }
         }
-
-        self.refresh_tokens(&ref_, &token).await
+        Ok(())
     }
 
     /// Refresh tokens
@@ -261,7 +247,11 @@ impl OAuthManager {
             return Ok(());
         }
 
-        match refresh_token(&refresh_token_value).await {
+        let Some(oauth) = PlatformRegistry::from_config(&self.config).oauth_for(ref_).cloned() else {
+            return Err(OAuthError::General(format!("No OAuth platform for {}", ref_.key)));
+        };
+
+        match refresh_token(&oauth, &refresh_token_value).await {
             Ok(refreshed) => {
                 save_token(ref_, &refreshed);
                 self.do_cache_access_token(&ref_key, &refreshed.access_token);
@@ -301,25 +291,49 @@ impl OAuthManager {
     }
 
     /// Start background refresh task
-    /// 
-    /// This spawns a task that periodically checks and refreshes tokens.
+    ///
+    /// This spawns a task that every [`REFRESH_INTERVAL_SECONDS`] refreshes
+    /// the stored token of each OAuth provider that is about to expire, so
+    /// providers reading it from storage never send an expired one.
     /// The task runs until the returned handle is dropped.
     pub fn start_background_refresh(&self) -> BackgroundRefreshHandle {
-        let refresh_lock = Arc::clone(&self.refresh_lock);
+        let sources = self.token_sources();
 
         let handle = tokio::spawn(async move {
             loop {
                 sleep(Duration::from_secs(REFRESH_INTERVAL_SECONDS)).await;
-
-                // Try to refresh tokens
-                let _guard = refresh_lock.lock().await;
-                // Actual refresh logic would go here
-                // This is a simplified version
+                refresh_sources(&sources).await;
             }
         });
 
         BackgroundRefreshHandle { handle }
     }
+
+    /// A token source for each distinct OAuth reference with a platform
+    fn token_sources(&self) -> Vec<OAuthTokenSource> {
+        let registry = PlatformRegistry::from_config(&self.config);
+        let mut refs = self.iter_oauth_refs();
+        refs.sort_by(|a, b| a.key.cmp(&b.key));
+        refs.dedup_by(|a, b| a.key == b.key);
+        refs.into_iter()
+            .filter_map(|ref_| {
+                let Some(oauth) = registry.oauth_for(&ref_).cloned() else {
+                    warn!("No OAuth platform for {}, not refreshing it", ref_.key);
+                    return None;
+                };
+                Some(OAuthTokenSource::new(ref_, oauth))
+            })
+            .collect()
+    }
+}
+
+/// Refresh the stored token of each source that is about to expire
+async fn refresh_sources(sources: &[OAuthTokenSource]) {
+    for source in sources {
+        if let Err(e) = source.access_token().await {
+            warn!("Background OAuth token refresh failed: {}", e);
+        }
+    }
 }
 
 /// Handle for background refresh task
@@ -373,6 +387,159 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_background_refresh_sources() {
+        let config = Config::from_toml_str(
+            r#"
+default_model = "kimi"
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-for-coding"
+provider = "kimi-code"
+
+[providers.kimi-code]
+provider_type = "kimi"
+base_url = "https://api.kimi.com/coding/v1"
+oauth = { storage = "file", key = "oauth/kimi-code" }
+
+[providers.kimi-code-fast]
+provider_type = "kimi"
+base_url = "https://api.kimi.com/coding/v1"
+oauth = { storage = "file", key = "oauth/kimi-code" }
+
+[providers.unknown]
+provider_type = "kimi"
+base_url = "https://example.com/v1"
+oauth = { storage = "file", key = "oauth/unknown" }
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#,
+        )
+        .unwrap();
+        let manager = OAuthManager {
+            config,
+            access_tokens: HashMap::new(),
+            refresh_lock: Arc::new(Mutex::new(())),
+        };
+        // One source per token, and none without a platform to refresh with
+        let sources = manager.token_sources();
+        assert_eq!(sources.len(), 1);
+    }
+
+    /// Answers one token request with a fresh token, returning the body
+    fn serve_token() -> (String, std::thread::JoinHandle<String>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let line = line.trim_end().to_ascii_lowercase();
+                if line.is_empty() {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("content-length: ") {
+                    length = value.parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let token = r#"{"access_token":"fresh","refresh_token":"refresh-2","expires_in":3600,"scope":"chat","token_type":"Bearer"}"#;
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                token.len(),
+                token
+            )
+            .unwrap();
+            String::from_utf8(body).unwrap()
+        });
+        (url, handle)
+    }
+
+    #[tokio::test]
+    async fn test_background_refresh_reaches_the_provider() {
+        use kosong_rs::TokenSource;
+
+        let (url, server) = serve_token();
+        let key = format!("oauth/refresh-test/{}", uuid::Uuid::new_v4());
+        let config = Config::from_toml_str(&format!(
+            r#"
+default_model = "acme"
+default_thinking = false
+default_yolo = false
+
+[models.acme]
+name = "acme-chat"
+provider = "acme"
+
+[providers.acme]
+provider_type = "kimi"
+base_url = "{url}/v1"
+oauth = {{ storage = "file", key = "{key}" }}
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {{}}
+
+[mcp]
+servers = []
+
+[platforms.refresh-test]
+name = "Refresh test"
+base_url = "{url}/v1"
+
+[platforms.refresh-test.oauth]
+host = "{url}"
+client_id = "kimi-test"
+token_path = "/token"
+"#
+        ))
+        .unwrap();
+        let oauth_ref = config.providers["acme"].oauth.clone().unwrap();
+        let mut expiring = create_test_token(10.0);
+        expiring.access_token = "stale".to_string();
+        save_token(&oauth_ref, &expiring);
+
+        // The source the provider factory gives the active provider
+        let platform = PlatformRegistry::from_config(&config).oauth_for(&oauth_ref).cloned().unwrap();
+        let provider_source = OAuthTokenSource::new(oauth_ref.clone(), platform);
+
+        let manager = OAuthManager {
+            config,
+            access_tokens: HashMap::new(),
+            refresh_lock: Arc::new(Mutex::new(())),
+        };
+        refresh_sources(&manager.token_sources()).await;
+        let request = server.join().unwrap();
+        assert!(request.contains("refresh_token=test_refresh"), "{}", request);
+
+        // The provider picks up the refreshed token without refreshing again
+        assert_eq!(provider_source.token().await.unwrap(), "fresh");
+        delete_token(&oauth_ref);
+        std::fs::remove_dir(crate::auth::storage::credentials_dir().join("refresh-test")).ok();
+    }
+
     #[test]
     fn test_token_needs_refresh() {
         // Token expiring in 10 seconds (needs refresh)
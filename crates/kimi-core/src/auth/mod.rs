@@ -1,25 +1,36 @@
 //! OAuth authentication module for Kimi CLI
 //!
 //! This module provides OAuth2 device authorization flow support for
-//! authenticating with the Kimi Code platform.
+//! authenticating with the Kimi Code platform and any other OAuth platform
+//! declared in the [`PlatformRegistry`].
 
+pub mod accounts;
 pub mod manager;
 pub mod oauth;
 pub mod platforms;
+pub mod secrets;
 pub mod storage;
+pub mod token_source;
 
+pub use accounts::{
+    account_secret_name, active_account, list_accounts, switch_account, validate_account_name,
+    AccountError, DEFAULT_ACCOUNT,
+};
 pub use manager::OAuthManager;
 pub use oauth::{
-    login_kimi_code, logout_kimi_code, poll_device_token, refresh_token,
-    request_device_authorization, DeviceAuthorization, OAuthError, OAuthEvent, OAuthToken,
+    login_kimi_code, login_platform, logout_kimi_code, logout_platform, poll_device_token,
+    refresh_token, request_device_authorization, DeviceAuthorization, OAuthError, OAuthEvent, OAuthToken,
 };
 pub use platforms::{
     get_platform_by_id, is_managed_provider_key, list_platforms, list_models,
-    managed_model_key, managed_provider_key, parse_managed_provider_key, ModelInfo, Platform,
+    managed_model_key, managed_provider_key, parse_managed_provider_key, ModelInfo, OAuthPlatform,
+    Platform, PlatformConfig, PlatformRegistry,
 };
+pub use secrets::{SecretBackend, SecretError, SecretsManager};
 pub use storage::{
-    delete_token, get_device_id, load_token, save_token, OAuthRef,
+    delete_token, get_device_id, list_token_accounts, load_token, save_token, OAuthRef,
 };
+pub use token_source::OAuthTokenSource;
 
 /// Platform ID for Kimi Code
 pub const KIMI_CODE_PLATFORM_ID: &str = "kimi-code";
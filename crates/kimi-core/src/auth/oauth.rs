@@ -1,17 +1,20 @@
 //! OAuth2 device authorization flow implementation
+//!
+//! The flow works against any platform with [`OAuthPlatform`] settings;
+//! the `*_kimi_code` functions run it for the built-in Kimi Code platform.
 
 use crate::auth::{
+    accounts::DEFAULT_ACCOUNT,
+    platforms::{OAuthPlatform, Platform, PlatformRegistry},
     storage::{delete_token, get_device_id, save_token, OAuthRef},
-    ModelCapability, KIMI_CODE_CLIENT_ID, KIMI_CODE_OAUTH_KEY, KIMI_CODE_PLATFORM_ID,
-    REFRESH_THRESHOLD_SECONDS,
+    ModelCapability, KIMI_CODE_PLATFORM_ID, REFRESH_THRESHOLD_SECONDS,
 };
 use crate::config::Config;
 use crate::types::{LlmModel, Services};
-use reqwest::Client;
+use reqwest::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::collections::HashMap;
-use std::env;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::time::{sleep, Duration};
 use tracing::{debug, warn};
@@ -218,13 +221,6 @@ pub enum OAuthError {
     Io(#[from] std::io::Error),
 }
 
-/// Get the OAuth host URL from environment or default
-fn oauth_host() -> String {
-    env::var("KIMI_CODE_OAUTH_HOST")
-        .or_else(|_| env::var("KIMI_OAUTH_HOST"))
-        .unwrap_or_else(|_| crate::auth::DEFAULT_OAUTH_HOST.to_string())
-}
-
 /// Build common headers for OAuth requests
 fn common_headers() -> HashMap<String, String> {
     use std::ffi::OsStr;
@@ -248,6 +244,16 @@ fn common_headers() -> HashMap<String, String> {
     headers
 }
 
+/// Add the device headers to a request if the platform expects them
+fn with_device_headers(request: RequestBuilder, oauth: &OAuthPlatform) -> RequestBuilder {
+    if !oauth.device_headers {
+        return request;
+    }
+    common_headers()
+        .into_iter()
+        .fold(request, |request, (name, value)| request.header(name, value))
+}
+
 /// Sanitize a header value to ASCII
 fn ascii_header_value(value: &str) -> String {
     let sanitized: String = value
@@ -285,22 +291,16 @@ fn device_model() -> String {
     }
 }
 
-/// Request device authorization from OAuth server
-pub async fn request_device_authorization() -> Result<DeviceAuthorization, OAuthError> {
+/// Request device authorization from the platform's OAuth server
+pub async fn request_device_authorization(oauth: &OAuthPlatform) -> Result<DeviceAuthorization, OAuthError> {
     let client = Client::new();
-    let url = format!("{}/api/oauth/device_authorization", oauth_host().trim_end_matches('/'));
-
-    let headers = common_headers();
-
-    let response = client
-        .post(&url)
-        .form(&[("client_id", KIMI_CODE_CLIENT_ID)])
-        .header("X-Msh-Platform", headers.get("X-Msh-Platform").unwrap())
-        .header("X-Msh-Version", headers.get("X-Msh-Version").unwrap())
-        .header("X-Msh-Device-Name", headers.get("X-Msh-Device-Name").unwrap())
-        .header("X-Msh-Device-Model", headers.get("X-Msh-Device-Model").unwrap())
-        .header("X-Msh-Os-Version", headers.get("X-Msh-Os-Version").unwrap())
-        .header("X-Msh-Device-Id", headers.get("X-Msh-Device-Id").unwrap())
+    let scope = oauth.scopes.join(" ");
+    let mut form = vec![("client_id", oauth.client_id.as_str())];
+    if !scope.is_empty() {
+        form.push(("scope", scope.as_str()));
+    }
+
+    let response = with_device_headers(client.post(oauth.device_authorization_url()).form(&form), oauth)
         .send()
         .await?;
 
@@ -346,29 +346,20 @@ pub async fn request_device_authorization() -> Result<DeviceAuthorization, OAuth
 
 /// Internal function to request device token
 async fn request_device_token(
+    oauth: &OAuthPlatform,
     auth: &DeviceAuthorization,
 ) -> Result<(u16, serde_json::Value), OAuthError> {
     let client = Client::new();
-    let url = format!("{}/api/oauth/token", oauth_host().trim_end_matches('/'));
-
-    let headers = common_headers();
-
-    let response = client
-        .post(&url)
-        .form(&[
-            ("client_id", KIMI_CODE_CLIENT_ID),
-            ("device_code", &auth.device_code),
-            (
-                "grant_type",
-                "urn:ietf:params:oauth:grant-type:device_code",
-            ),
-        ])
-        .header("X-Msh-Platform", headers.get("X-Msh-Platform").unwrap())
-        .header("X-Msh-Version", headers.get("X-Msh-Version").unwrap())
-        .header("X-Msh-Device-Name", headers.get("X-Msh-Device-Name").unwrap())
-        .header("X-Msh-Device-Model", headers.get("X-Msh-Device-Model").unwrap())
-        .header("X-Msh-Os-Version", headers.get("X-Msh-Os-Version").unwrap())
-        .header("X-Msh-Device-Id", headers.get("X-Msh-Device-Id").unwrap())
+    let form = [
+        ("client_id", oauth.client_id.as_str()),
+        ("device_code", &auth.device_code),
+        (
+            "grant_type",
+            "urn:ietf:params:oauth:grant-type:device_code",
+        ),
+    ];
+
+    let response = with_device_headers(client.post(oauth.token_url()).form(&form), oauth)
         .send()
         .await?;
 
@@ -386,12 +377,12 @@ async fn request_device_token(
 }
 
 /// Poll for device token
-pub async fn poll_device_token(auth: &DeviceAuthorization) -> Result<OAuthToken, OAuthError> {
+pub async fn poll_device_token(oauth: &OAuthPlatform, auth: &DeviceAuthorization) -> Result<OAuthToken, OAuthError> {
     let interval = Duration::from_secs(auth.interval);
     let mut printed_wait = false;
 
     loop {
-        let (status, data) = request_device_token(auth).await?;
+        let (status, data) = request_device_token(oauth, auth).await?;
 
         if status == 200 && data.get("access_token").is_some() {
             return OAuthToken::from_response(data);
@@ -424,26 +415,16 @@ pub async fn poll_device_token(auth: &DeviceAuthorization) -> Result<OAuthToken,
     }
 }
 
-/// Refresh OAuth token
-pub async fn refresh_token(refresh_token_str: &str) -> Result<OAuthToken, OAuthError> {
+/// Refresh OAuth token with the platform's token endpoint
+pub async fn refresh_token(oauth: &OAuthPlatform, refresh_token_str: &str) -> Result<OAuthToken, OAuthError> {
     let client = Client::new();
-    let url = format!("{}/api/oauth/token", oauth_host().trim_end_matches('/'));
-
-    let headers = common_headers();
-
-    let response = client
-        .post(&url)
-        .form(&[
-            ("client_id", KIMI_CODE_CLIENT_ID),
-            ("grant_type", "refresh_token"),
-            ("refresh_token", refresh_token_str),
-        ])
-        .header("X-Msh-Platform", headers.get("X-Msh-Platform").unwrap())
-        .header("X-Msh-Version", headers.get("X-Msh-Version").unwrap())
-        .header("X-Msh-Device-Name", headers.get("X-Msh-Device-Name").unwrap())
-        .header("X-Msh-Device-Model", headers.get("X-Msh-Device-Model").unwrap())
-        .header("X-Msh-Os-Version", headers.get("X-Msh-Os-Version").unwrap())
-        .header("X-Msh-Device-Id", headers.get("X-Msh-Device-Id").unwrap())
+    let form = [
+        ("client_id", oauth.client_id.as_str()),
+        ("grant_type", "refresh_token"),
+        ("refresh_token", refresh_token_str),
+    ];
+
+    let response = with_device_headers(client.post(oauth.token_url()).form(&form), oauth)
         .send()
         .await?;
 
@@ -477,20 +458,18 @@ fn select_default_model_and_thinking(
     Some((selected_model, thinking))
 }
 
-/// Apply Kimi Code configuration after login
-fn apply_kimi_code_config(
+/// Add the platform's managed provider and models to the config after login
+fn apply_platform_config(
     config: &mut Config,
+    platform: &Platform,
     models: Vec<crate::auth::ModelInfo>,
     selected_model: &crate::auth::ModelInfo,
     _thinking: bool,
     oauth_ref: OAuthRef,
 ) -> Result<(), OAuthError> {
-    use crate::auth::platforms::{get_platform_by_id, managed_model_key, managed_provider_key};
+    use crate::auth::platforms::{managed_model_key, managed_provider_key};
     use secrecy::SecretString;
 
-    let platform = get_platform_by_id(KIMI_CODE_PLATFORM_ID)
-        .ok_or_else(|| OAuthError::General("Kimi Code platform not found".to_string()))?;
-
     let provider_key = managed_provider_key(&platform.id);
 
     // Add or update provider
@@ -500,7 +479,12 @@ fn apply_kimi_code_config(
         api_key: SecretString::new(String::new()),
         env: None,
         custom_headers: None,
+        proxy: None,
+        api_version: None,
         oauth: Some(oauth_ref.clone()),
+        api_key_ref: None,
+        safety_settings: None,
+        keep_alive: None,
     };
     config.providers.insert(provider_key.clone(), provider);
 
@@ -520,6 +504,7 @@ fn apply_kimi_code_config(
             provider: provider_key.clone(),
             max_tokens: Some(model_info.context_length),
             temperature: None,
+            pricing: None,
         };
         config
             .models
@@ -545,28 +530,40 @@ pub async fn login_kimi_code(
     config: &mut Config,
     open_browser: bool,
 ) -> Result<Vec<OAuthEvent>, OAuthError> {
-    use crate::auth::platforms::{get_platform_by_id, list_models};
+    let platform = PlatformRegistry::from_config(config).get(KIMI_CODE_PLATFORM_ID).cloned();
+    match platform {
+        Some(platform) => login_platform(config, &platform, DEFAULT_ACCOUNT, open_browser).await,
+        None => Ok(vec![OAuthEvent::error("Kimi Code platform is unavailable")]),
+    }
+}
+
+/// Login to an account on an OAuth platform with the device flow
+///
+/// The token is stored under `oauth/<platform id>` for the default account
+/// and `oauth/<platform id>/<account>` otherwise. The platform's models are
+/// added to the config under its managed provider, which is switched to the
+/// account.
+pub async fn login_platform(
+    config: &mut Config,
+    platform: &Platform,
+    account: &str,
+    open_browser: bool,
+) -> Result<Vec<OAuthEvent>, OAuthError> {
+    use crate::auth::platforms::list_models;
     use std::process::Command;
 
     let mut events = Vec::new();
 
-    // Check if config is from default location
-    // Note: This would need to be tracked in the Config struct
-    // For now, we assume it's valid
-
-    let platform = match get_platform_by_id(KIMI_CODE_PLATFORM_ID) {
-        Some(p) => p,
-        None => {
-            events.push(OAuthEvent::error("Kimi Code platform is unavailable"));
-            return Ok(events);
-        }
+    let Some(oauth) = &platform.oauth else {
+        events.push(OAuthEvent::error(format!("{} does not support OAuth login", platform.name)));
+        return Ok(events);
     };
 
     let mut token: Option<OAuthToken> = None;
 
     // Loop to handle device code expiration and restart
     loop {
-        let auth = match request_device_authorization().await {
+        let auth = match request_device_authorization(oauth).await {
             Ok(a) => a,
             Err(e) => {
                 events.push(OAuthEvent::error(format!("Login failed: {}", e)));
@@ -603,7 +600,7 @@ pub async fn login_kimi_code(
         let mut printed_wait = false;
 
         loop {
-            match request_device_token(&auth).await {
+            match request_device_token(oauth, &auth).await {
                 Ok((status, data)) => {
                     if status == 200 && data.get("access_token").is_some() {
                         match OAuthToken::from_response(data) {
@@ -660,14 +657,10 @@ pub async fn login_kimi_code(
     let token = token.unwrap();
 
     // Save token
-    let oauth_ref = OAuthRef {
-        storage: "file".to_string(),
-        key: KIMI_CODE_OAUTH_KEY.to_string(),
-    };
-    let oauth_ref = save_token(&oauth_ref, &token);
+    let oauth_ref = save_token(&OAuthRef::for_account(&platform.id, account), &token);
 
     // Fetch models
-    let models = match list_models(&platform, &token.access_token).await {
+    let models = match list_models(platform, &token.access_token).await {
         Ok(m) => m,
         Err(e) => {
             events.push(OAuthEvent::error(format!("Failed to get models: {}", e)));
@@ -689,8 +682,9 @@ pub async fn login_kimi_code(
     };
 
     // Apply configuration
-    if let Err(e) = apply_kimi_code_config(
+    if let Err(e) = apply_platform_config(
         config,
+        platform,
         models,
         &selected_model,
         thinking,
@@ -706,24 +700,46 @@ pub async fn login_kimi_code(
 
 /// Logout from Kimi Code platform
 pub async fn logout_kimi_code(config: &mut Config) -> Result<Vec<OAuthEvent>, OAuthError> {
+    logout_platform(config, KIMI_CODE_PLATFORM_ID, None).await
+}
+
+/// Logout from an account on an OAuth platform, the active one by default
+///
+/// Logging out of the active account also removes the platform's provider
+/// and models; other accounts only lose their token.
+pub async fn logout_platform(
+    config: &mut Config,
+    platform_id: &str,
+    account: Option<&str>,
+) -> Result<Vec<OAuthEvent>, OAuthError> {
     use crate::auth::platforms::managed_provider_key;
 
     let mut events = Vec::new();
 
+    let provider_key = managed_provider_key(platform_id);
+    let active = config
+        .providers
+        .get(&provider_key)
+        .and_then(|p| p.oauth.as_ref())
+        .map_or(DEFAULT_ACCOUNT, |oauth_ref| oauth_ref.account())
+        .to_string();
+    let account = account.unwrap_or(&active);
+
     // Delete tokens from both keyring and file
+    let file_ref = OAuthRef::for_account(platform_id, account);
     let keyring_ref = OAuthRef {
         storage: "keyring".to_string(),
-        key: KIMI_CODE_OAUTH_KEY.to_string(),
-    };
-    let file_ref = OAuthRef {
-        storage: "file".to_string(),
-        key: KIMI_CODE_OAUTH_KEY.to_string(),
+        key: file_ref.key.clone(),
     };
     delete_token(&keyring_ref);
     delete_token(&file_ref);
 
+    if account != active {
+        events.push(OAuthEvent::success(format!("Logged out of account {}.", account)));
+        return Ok(events);
+    }
+
     // Remove provider
-    let provider_key = managed_provider_key(KIMI_CODE_PLATFORM_ID);
     config.providers.remove(&provider_key);
 
     // Remove models and track if default was removed
@@ -746,11 +762,16 @@ pub async fn logout_kimi_code(config: &mut Config) -> Result<Vec<OAuthEvent>, OA
         config.default_model = String::new();
     }
 
-    // Clear services
-    config.services = Services {
-        enabled: Vec::new(),
-        config: HashMap::new(),
-    };
+    // Clear the services the platform configured at login
+    let has_services = PlatformRegistry::from_config(config)
+        .get(platform_id)
+        .is_some_and(|platform| platform.search_url.is_some());
+    if has_services {
+        config.services = Services {
+            enabled: Vec::new(),
+            config: HashMap::new(),
+        };
+    }
 
     events.push(OAuthEvent::success("Logged out successfully."));
     Ok(events)
@@ -1,13 +1,19 @@
 //! Platform configuration and model management
+//!
+//! A [`Platform`] is an API host that kimi can set up as a managed provider.
+//! Platforms that sign in with the OAuth device flow carry [`OAuthPlatform`]
+//! settings. The [`PlatformRegistry`] holds the built-in platforms plus any
+//! declared under `[platforms.<id>]` in the config, which may also override a
+//! built-in one.
 
 use crate::auth::oauth::OAuthError;
 use secrecy::ExposeSecret;
-use crate::auth::KIMI_CODE_PLATFORM_ID;
+use crate::auth::{OAuthRef, SecretsManager, DEFAULT_OAUTH_HOST, KIMI_CODE_CLIENT_ID, KIMI_CODE_PLATFORM_ID};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 /// Platform definition
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Platform {
     pub id: String,
     pub name: String,
@@ -15,6 +21,137 @@ pub struct Platform {
     pub search_url: Option<String>,
     pub fetch_url: Option<String>,
     pub allowed_prefixes: Option<Vec<String>>,
+    /// Device-flow settings for platforms that sign in with OAuth
+    pub oauth: Option<OAuthPlatform>,
+}
+
+/// OAuth device-flow settings of a platform
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OAuthPlatform {
+    /// Authorization server, e.g. `https://auth.kimi.com`
+    pub host: String,
+    pub client_id: String,
+    /// Scopes requested with the device authorization
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scopes: Vec<String>,
+    /// Path of the device authorization endpoint under `host`
+    #[serde(default = "default_device_authorization_path")]
+    pub device_authorization_path: String,
+    /// Path of the token endpoint under `host`
+    #[serde(default = "default_token_path")]
+    pub token_path: String,
+    /// Send the `X-Msh-*` device headers that Kimi's server expects
+    #[serde(default)]
+    pub device_headers: bool,
+}
+
+fn default_device_authorization_path() -> String {
+    "/api/oauth/device_authorization".to_string()
+}
+
+fn default_token_path() -> String {
+    "/api/oauth/token".to_string()
+}
+
+impl OAuthPlatform {
+    /// URL of the device authorization endpoint
+    pub fn device_authorization_url(&self) -> String {
+        format!("{}{}", self.host.trim_end_matches('/'), self.device_authorization_path)
+    }
+
+    /// URL of the token endpoint
+    pub fn token_url(&self) -> String {
+        format!("{}{}", self.host.trim_end_matches('/'), self.token_path)
+    }
+}
+
+/// A platform declared under `[platforms.<id>]` in the config
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlatformConfig {
+    pub name: String,
+    pub base_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub search_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_url: Option<String>,
+    /// Only models whose ID starts with one of these are added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_prefixes: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub oauth: Option<OAuthPlatform>,
+}
+
+impl PlatformConfig {
+    /// The platform with the given ID
+    pub fn to_platform(&self, id: &str) -> Platform {
+        Platform {
+            id: id.to_string(),
+            name: self.name.clone(),
+            base_url: self.base_url.clone(),
+            search_url: self.search_url.clone(),
+            fetch_url: self.fetch_url.clone(),
+            allowed_prefixes: self.allowed_prefixes.clone(),
+            oauth: self.oauth.clone(),
+        }
+    }
+}
+
+/// The platforms kimi knows about
+#[derive(Debug, Clone)]
+pub struct PlatformRegistry {
+    platforms: Vec<Platform>,
+}
+
+impl Default for PlatformRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl PlatformRegistry {
+    /// The built-in platforms
+    pub fn builtin() -> Self {
+        Self { platforms: platforms() }
+    }
+
+    /// The built-in platforms plus those declared in `config`, sorted by ID
+    pub fn from_config(config: &Config) -> Self {
+        let mut registry = Self::builtin();
+        let mut declared: Vec<_> = config.platforms.iter().collect();
+        declared.sort_by(|a, b| a.0.cmp(b.0));
+        for (id, platform) in declared {
+            registry.register(platform.to_platform(id));
+        }
+        registry
+    }
+
+    /// Add a platform, replacing any with the same ID
+    pub fn register(&mut self, platform: Platform) {
+        match self.platforms.iter_mut().find(|p| p.id == platform.id) {
+            Some(existing) => *existing = platform,
+            None => self.platforms.push(platform),
+        }
+    }
+
+    /// The platform with the given ID
+    pub fn get(&self, id: &str) -> Option<&Platform> {
+        self.platforms.iter().find(|p| p.id == id)
+    }
+
+    /// All platforms, built-in ones first
+    pub fn list(&self) -> &[Platform] {
+        &self.platforms
+    }
+
+    /// The platforms that sign in with OAuth
+    pub fn oauth_platforms(&self) -> impl Iterator<Item = &Platform> {
+        self.platforms.iter().filter(|p| p.oauth.is_some())
+    }
+
+    /// The OAuth settings of the platform a stored token belongs to
+    pub fn oauth_for(&self, oauth_ref: &OAuthRef) -> Option<&OAuthPlatform> {
+        self.get(oauth_ref.platform_id()?)?.oauth.as_ref()
+    }
 }
 
 /// Model info from API
@@ -72,6 +209,13 @@ fn kimi_code_base_url() -> String {
         .unwrap_or_else(|_| "https://api.kimi.com/coding/v1".to_string())
 }
 
+/// Get the Kimi Code OAuth host from environment or default
+fn kimi_code_oauth_host() -> String {
+    std::env::var("KIMI_CODE_OAUTH_HOST")
+        .or_else(|_| std::env::var("KIMI_OAUTH_HOST"))
+        .unwrap_or_else(|_| DEFAULT_OAUTH_HOST.to_string())
+}
+
 /// List of supported platforms
 fn platforms() -> Vec<Platform> {
     vec![
@@ -82,6 +226,14 @@ fn platforms() -> Vec<Platform> {
             search_url: Some(format!("{}/search", kimi_code_base_url())),
             fetch_url: Some(format!("{}/fetch", kimi_code_base_url())),
             allowed_prefixes: None,
+            oauth: Some(OAuthPlatform {
+                host: kimi_code_oauth_host(),
+                client_id: KIMI_CODE_CLIENT_ID.to_string(),
+                scopes: Vec::new(),
+                device_authorization_path: default_device_authorization_path(),
+                token_path: default_token_path(),
+                device_headers: true,
+            }),
         },
         Platform {
             id: "moonshot-cn".to_string(),
@@ -90,6 +242,7 @@ fn platforms() -> Vec<Platform> {
             search_url: None,
             fetch_url: None,
             allowed_prefixes: Some(vec!["kimi-k".to_string()]),
+            oauth: None,
         },
         Platform {
             id: "moonshot-ai".to_string(),
@@ -98,16 +251,17 @@ fn platforms() -> Vec<Platform> {
             search_url: None,
             fetch_url: None,
             allowed_prefixes: Some(vec!["kimi-k".to_string()]),
+            oauth: None,
         },
     ]
 }
 
-/// Get platform by ID
+/// Get a built-in platform by ID
 pub fn get_platform_by_id(id: &str) -> Option<Platform> {
-    platforms().into_iter().find(|p| p.id == id)
+    PlatformRegistry::builtin().get(id).cloned()
 }
 
-/// List all platforms
+/// List the built-in platforms
 pub fn list_platforms() -> Vec<Platform> {
     platforms()
 }
@@ -188,9 +342,9 @@ pub fn parse_managed_provider_key(key: &str) -> Option<String> {
 }
 
 /// Get platform name for provider key
-pub fn get_platform_name_for_provider(provider_key: &str) -> Option<String> {
+pub fn get_platform_name_for_provider(config: &Config, provider_key: &str) -> Option<String> {
     let platform_id = parse_managed_provider_key(provider_key)?;
-    get_platform_by_id(&platform_id).map(|p| p.name)
+    PlatformRegistry::from_config(config).get(&platform_id).map(|p| p.name.clone())
 }
 
 /// Refresh managed models in config
@@ -209,6 +363,7 @@ pub async fn refresh_managed_models(config: &mut Config) -> Result<bool, OAuthEr
         return Ok(false);
     }
 
+    let registry = PlatformRegistry::from_config(config);
     let mut changed = false;
 
     for (provider_key, provider) in managed_providers {
@@ -217,7 +372,7 @@ pub async fn refresh_managed_models(config: &mut Config) -> Result<bool, OAuthEr
             None => continue,
         };
 
-        let platform = match get_platform_by_id(&platform_id) {
+        let platform = match registry.get(&platform_id) {
             Some(p) => p,
             None => {
                 tracing::warn!("Managed platform not found: {}", platform_id);
@@ -232,7 +387,13 @@ pub async fn refresh_managed_models(config: &mut Config) -> Result<bool, OAuthEr
                 None => continue,
             }
         } else {
-            provider.api_key.expose_secret().to_string()
+            match provider.resolve_api_key(&SecretsManager::new()) {
+                Ok(api_key) => api_key.expose_secret().to_string(),
+                Err(e) => {
+                    tracing::warn!("Failed to load the API key for {}: {}", provider_key, e);
+                    continue;
+                }
+            }
         };
 
         if api_key.is_empty() {
@@ -240,7 +401,7 @@ pub async fn refresh_managed_models(config: &mut Config) -> Result<bool, OAuthEr
             continue;
         }
 
-        let models = match list_models(&platform, &api_key).await {
+        let models = match list_models(platform, &api_key).await {
             Ok(m) => m,
             Err(e) => {
                 tracing::error!(
@@ -290,6 +451,7 @@ fn apply_models(
                     provider: provider_key.to_string(),
                     max_tokens: Some(model.context_length),
                     temperature: None,
+                    pricing: None,
                 };
                 config.models.insert(model_key, new_model);
                 changed = true;
@@ -343,3 +505,63 @@ fn apply_models(
 
 // Need to import Config for refresh_managed_models
 use crate::config::Config;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_from_config() {
+        let config: Config = toml::from_str(
+            r#"
+default_model = ""
+default_thinking = false
+default_yolo = false
+models = {}
+providers = {}
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+
+[platforms.acme]
+name = "Acme"
+base_url = "https://api.acme.dev/v1"
+
+[platforms.acme.oauth]
+host = "https://login.acme.dev/"
+client_id = "acme-cli"
+scopes = ["models", "chat"]
+token_path = "/oauth2/token"
+
+[platforms.moonshot-cn]
+name = "Moonshot (mirror)"
+base_url = "https://mirror.example/v1"
+"#,
+        )
+        .unwrap();
+
+        let registry = PlatformRegistry::from_config(&config);
+        let acme = registry.get("acme").unwrap();
+        let oauth = acme.oauth.as_ref().unwrap();
+        assert_eq!(oauth.device_authorization_url(), "https://login.acme.dev/api/oauth/device_authorization");
+        assert_eq!(oauth.token_url(), "https://login.acme.dev/oauth2/token");
+        assert!(!oauth.device_headers);
+
+        // A declared platform replaces the built-in one with the same ID
+        assert_eq!(registry.get("moonshot-cn").unwrap().base_url, "https://mirror.example/v1");
+        assert_eq!(registry.list().len(), PlatformRegistry::builtin().list().len() + 1);
+
+        let ids: Vec<_> = registry.oauth_platforms().map(|p| p.id.as_str()).collect();
+        assert_eq!(ids, [KIMI_CODE_PLATFORM_ID, "acme"]);
+        assert_eq!(registry.oauth_for(&OAuthRef::for_platform("acme")), Some(oauth));
+        assert_eq!(registry.oauth_for(&OAuthRef::for_platform("moonshot-cn")), None);
+    }
+}
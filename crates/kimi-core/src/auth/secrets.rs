@@ -0,0 +1,397 @@
+//! Secrets storage for provider API keys
+//!
+//! Providers can name a secret with `api_key_ref` instead of putting the key
+//! itself in the config file. [`SecretsManager`] keeps such secrets in the
+//! system keyring when it is reachable and the default `keyring` feature is
+//! enabled, and otherwise in `secrets.json` under the data directory,
+//! encrypted with ChaCha20-Poly1305. The encryption key lives next to it in
+//! `secrets.key` with owner-only permissions, so the file is safe to back up
+//! or sync on its own but not once both files leak.
+//!
+//! `secrets.json` also records which backend holds each secret, so secrets
+//! can be listed whatever the backend.
+
+use crate::auth::storage::get_share_dir;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Length of a ChaCha20-Poly1305 nonce in bytes
+const NONCE_LEN: usize = 12;
+
+/// Where a secret is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SecretBackend {
+    /// The system keyring
+    Keyring,
+    /// The encrypted secrets file
+    File,
+}
+
+impl std::fmt::Display for SecretBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecretBackend::Keyring => write!(f, "keyring"),
+            SecretBackend::File => write!(f, "encrypted file"),
+        }
+    }
+}
+
+/// An entry of `secrets.json`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "backend", rename_all = "lowercase")]
+enum StoredSecret {
+    Keyring,
+    /// Base64 of the nonce followed by the ciphertext
+    File { value: String },
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SecretsFile {
+    secrets: BTreeMap<String, StoredSecret>,
+}
+
+/// Stores API keys and other secrets by name
+#[derive(Debug, Clone)]
+pub struct SecretsManager {
+    dir: PathBuf,
+    use_keyring: bool,
+}
+
+impl Default for SecretsManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecretsManager {
+    /// The secrets manager of the current user, preferring the keyring when
+    /// the `keyring` feature is enabled
+    pub fn new() -> Self {
+        Self {
+            dir: get_share_dir(),
+            use_keyring: cfg!(feature = "keyring"),
+        }
+    }
+
+    /// A secrets manager that only uses the encrypted file in `dir`
+    pub fn file_only(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            use_keyring: false,
+        }
+    }
+
+    /// Store `value` as the secret `name`, replacing any previous value
+    ///
+    /// Returns where it was stored: the keyring if it is enabled and
+    /// working, the encrypted file otherwise.
+    pub fn set(&self, name: &str, value: &str) -> Result<SecretBackend, SecretError> {
+        validate_name(name)?;
+        let mut file = self.read_file()?;
+        let previous = file.secrets.get(name).cloned();
+
+        let stored = match self.use_keyring.then(|| keyring_store::set(name, value)) {
+            Some(Ok(())) => StoredSecret::Keyring,
+            Some(Err(e)) => {
+                tracing::warn!("Keyring unavailable, storing secret {} in the encrypted file: {}", name, e);
+                StoredSecret::File { value: self.encrypt(name, value)? }
+            }
+            None => StoredSecret::File { value: self.encrypt(name, value)? },
+        };
+        if matches!(previous, Some(StoredSecret::Keyring)) && matches!(stored, StoredSecret::File { .. }) {
+            keyring_store::delete(name).ok();
+        }
+
+        let backend = match stored {
+            StoredSecret::Keyring => SecretBackend::Keyring,
+            StoredSecret::File { .. } => SecretBackend::File,
+        };
+        file.secrets.insert(name.to_string(), stored);
+        self.write_file(&file)?;
+        Ok(backend)
+    }
+
+    /// The secret `name`, or `None` if it is not stored
+    pub fn get(&self, name: &str) -> Result<Option<SecretString>, SecretError> {
+        let file = self.read_file()?;
+        match file.secrets.get(name) {
+            None => Ok(None),
+            Some(StoredSecret::Keyring) => keyring_store::get(name).map(|value| Some(SecretString::new(value))),
+            Some(StoredSecret::File { value }) => self.decrypt(name, value).map(|value| Some(SecretString::new(value))),
+        }
+    }
+
+    /// Remove the secret `name`, returning whether it existed
+    pub fn delete(&self, name: &str) -> Result<bool, SecretError> {
+        let mut file = self.read_file()?;
+        let Some(stored) = file.secrets.remove(name) else {
+            return Ok(false);
+        };
+        if let StoredSecret::Keyring = stored {
+            if let Err(e) = keyring_store::delete(name) {
+                tracing::warn!("Failed to delete secret {} from the keyring: {}", name, e);
+            }
+        }
+        self.write_file(&file)?;
+        Ok(true)
+    }
+
+    /// Names of the stored secrets with their backends, sorted by name
+    pub fn list(&self) -> Result<Vec<(String, SecretBackend)>, SecretError> {
+        Ok(self
+            .read_file()?
+            .secrets
+            .into_iter()
+            .map(|(name, stored)| {
+                let backend = match stored {
+                    StoredSecret::Keyring => SecretBackend::Keyring,
+                    StoredSecret::File { .. } => SecretBackend::File,
+                };
+                (name, backend)
+            })
+            .collect())
+    }
+
+    fn secrets_path(&self) -> PathBuf {
+        self.dir.join("secrets.json")
+    }
+
+    fn key_path(&self) -> PathBuf {
+        self.dir.join("secrets.key")
+    }
+
+    fn read_file(&self) -> Result<SecretsFile, SecretError> {
+        match fs::read_to_string(self.secrets_path()) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(SecretsFile::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_file(&self, file: &SecretsFile) -> Result<(), SecretError> {
+        write_private(&self.secrets_path(), serde_json::to_string_pretty(file)?.as_bytes())
+    }
+
+    /// The file encryption key, created on first use
+    fn cipher(&self) -> Result<ChaCha20Poly1305, SecretError> {
+        let path = self.key_path();
+        let key = match fs::read(&path) {
+            Ok(bytes) => {
+                if bytes.len() != 32 {
+                    return Err(SecretError::Decrypt(format!("{} is not a valid key", path.display())));
+                }
+                *Key::from_slice(&bytes)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+                write_private(&path, &key)?;
+                key
+            }
+            Err(e) => return Err(e.into()),
+        };
+        Ok(ChaCha20Poly1305::new(&key))
+    }
+
+    fn encrypt(&self, name: &str, value: &str) -> Result<String, SecretError> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        // The name is authenticated so a value cannot be swapped to another secret
+        let payload = Payload {
+            msg: value.as_bytes(),
+            aad: name.as_bytes(),
+        };
+        let ciphertext = self
+            .cipher()?
+            .encrypt(&nonce, payload)
+            .map_err(|_| SecretError::Decrypt(format!("Failed to encrypt secret {}", name)))?;
+        let mut bytes = nonce.to_vec();
+        bytes.extend(ciphertext);
+        Ok(base64::engine::general_purpose::STANDARD.encode(bytes))
+    }
+
+    fn decrypt(&self, name: &str, value: &str) -> Result<String, SecretError> {
+        let invalid = || SecretError::Decrypt(format!("Secret {} cannot be decrypted with {}", name, self.key_path().display()));
+        let bytes = base64::engine::general_purpose::STANDARD
+            .decode(value)
+            .map_err(|_| invalid())?;
+        if bytes.len() < NONCE_LEN {
+            return Err(invalid());
+        }
+        let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: name.as_bytes(),
+        };
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| invalid())?;
+        String::from_utf8(plaintext).map_err(|_| invalid())
+    }
+}
+
+/// Secret names are used as keyring entries, so keep them simple
+fn validate_name(name: &str) -> Result<(), SecretError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '/'));
+    if valid {
+        Ok(())
+    } else {
+        Err(SecretError::InvalidName(name.to_string()))
+    }
+}
+
+/// Write `bytes` to `path`, readable by the owner only
+fn write_private(path: &Path, bytes: &[u8]) -> Result<(), SecretError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)?.write_all(bytes)?;
+    Ok(())
+}
+
+#[cfg(feature = "keyring")]
+mod keyring_store {
+    use super::SecretError;
+    use crate::auth::KEYRING_SERVICE;
+
+    fn entry(name: &str) -> Result<keyring::Entry, SecretError> {
+        keyring::Entry::new(KEYRING_SERVICE, &format!("secret/{}", name))
+            .map_err(|e| SecretError::Keyring(e.to_string()))
+    }
+
+    pub fn set(name: &str, value: &str) -> Result<(), SecretError> {
+        entry(name)?
+            .set_password(value)
+            .map_err(|e| SecretError::Keyring(e.to_string()))
+    }
+
+    pub fn get(name: &str) -> Result<String, SecretError> {
+        entry(name)?
+            .get_password()
+            .map_err(|e| SecretError::Keyring(e.to_string()))
+    }
+
+    pub fn delete(name: &str) -> Result<(), SecretError> {
+        entry(name)?
+            .delete_credential()
+            .map_err(|e| SecretError::Keyring(e.to_string()))
+    }
+}
+
+#[cfg(not(feature = "keyring"))]
+mod keyring_store {
+    use super::SecretError;
+
+    fn disabled() -> SecretError {
+        SecretError::Keyring("built without the keyring feature".to_string())
+    }
+
+    pub fn set(_name: &str, _value: &str) -> Result<(), SecretError> {
+        Err(disabled())
+    }
+
+    pub fn get(_name: &str) -> Result<String, SecretError> {
+        Err(disabled())
+    }
+
+    pub fn delete(_name: &str) -> Result<(), SecretError> {
+        Err(disabled())
+    }
+}
+
+/// Secrets errors
+#[derive(Debug, Error)]
+pub enum SecretError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid secret name {0:?}: use letters, digits and - _ . : /")]
+    InvalidName(String),
+    #[error("Keyring error: {0}")]
+    Keyring(String),
+    #[error("{0}")]
+    Decrypt(String),
+    #[error("Secret {0} is not stored; add it with `kimi secret set {0}`")]
+    Missing(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn test_file_secrets() {
+        let temp = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::file_only(temp.path());
+        assert!(secrets.get("openai").unwrap().is_none());
+
+        assert_eq!(secrets.set("openai", "sk-test").unwrap(), SecretBackend::File);
+        assert_eq!(secrets.set("user:moonshot-cn", "sk-moon").unwrap(), SecretBackend::File);
+        assert_eq!(secrets.get("openai").unwrap().unwrap().expose_secret(), "sk-test");
+        assert_eq!(
+            secrets.list().unwrap(),
+            vec![
+                ("openai".to_string(), SecretBackend::File),
+                ("user:moonshot-cn".to_string(), SecretBackend::File),
+            ]
+        );
+
+        // Values are not stored in plain text
+        let file = fs::read_to_string(temp.path().join("secrets.json")).unwrap();
+        assert!(!file.contains("sk-test"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(temp.path().join("secrets.key")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // Reopening uses the same key
+        let secrets = SecretsManager::file_only(temp.path());
+        secrets.set("openai", "sk-new").unwrap();
+        assert_eq!(secrets.get("openai").unwrap().unwrap().expose_secret(), "sk-new");
+
+        assert!(secrets.delete("openai").unwrap());
+        assert!(!secrets.delete("openai").unwrap());
+        assert!(secrets.get("openai").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_tampered_secrets() {
+        let temp = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::file_only(temp.path());
+        secrets.set("a", "first").unwrap();
+        secrets.set("b", "second").unwrap();
+
+        // A value moved to another name no longer decrypts
+        let path = temp.path().join("secrets.json");
+        let mut file: SecretsFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let a = file.secrets["a"].clone();
+        file.secrets.insert("b".to_string(), a);
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+        assert!(matches!(secrets.get("b"), Err(SecretError::Decrypt(_))));
+
+        assert!(matches!(secrets.set("", "x"), Err(SecretError::InvalidName(_))));
+        assert!(matches!(secrets.set("my key", "x"), Err(SecretError::InvalidName(_))));
+    }
+}
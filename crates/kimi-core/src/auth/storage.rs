@@ -1,5 +1,6 @@
 //! Token storage implementation
 
+use crate::auth::accounts::DEFAULT_ACCOUNT;
 use crate::auth::oauth::{OAuthError, OAuthToken};
 // Note: KEYRING_SERVICE is defined in crate::auth but not used when keyring feature is disabled
 // use crate::auth::KEYRING_SERVICE;
@@ -15,8 +16,45 @@ pub struct OAuthRef {
     pub key: String,
 }
 
+impl OAuthRef {
+    /// Reference to the file-stored token of a platform, `oauth/<platform_id>`
+    pub fn for_platform(platform_id: &str) -> Self {
+        Self {
+            storage: "file".to_string(),
+            key: format!("oauth/{}", platform_id),
+        }
+    }
+
+    /// Reference to the token of one of a platform's accounts,
+    /// `oauth/<platform_id>/<account>`
+    ///
+    /// The default account keeps the platform's own key.
+    pub fn for_account(platform_id: &str, account: &str) -> Self {
+        if account == DEFAULT_ACCOUNT {
+            return Self::for_platform(platform_id);
+        }
+        Self {
+            storage: "file".to_string(),
+            key: format!("oauth/{}/{}", platform_id, account),
+        }
+    }
+
+    /// ID of the platform the token belongs to
+    pub fn platform_id(&self) -> Option<&str> {
+        self.key.strip_prefix("oauth/")?.split('/').next().filter(|id| !id.is_empty())
+    }
+
+    /// Name of the account the token belongs to
+    pub fn account(&self) -> &str {
+        self.key
+            .strip_prefix("oauth/")
+            .and_then(|rest| rest.split_once('/'))
+            .map_or(DEFAULT_ACCOUNT, |(_, account)| account)
+    }
+}
+
 /// Get the share directory for the application
-fn get_share_dir() -> PathBuf {
+pub(crate) fn get_share_dir() -> PathBuf {
     dirs::data_dir()
         .unwrap_or_else(|| PathBuf::from("."))
         .join("kimi")
@@ -31,11 +69,36 @@ pub fn credentials_dir() -> PathBuf {
 
 /// Get credentials file path for a key
 fn credentials_path(key: &str) -> PathBuf {
-    let name = key
-        .strip_prefix("oauth/")
-        .and_then(|s| s.split('/').next_back())
-        .unwrap_or(key);
-    credentials_dir().join(format!("{}.json", name))
+    let name = key.strip_prefix("oauth/").unwrap_or(key);
+    match name.split_once('/') {
+        // Named accounts live in a directory per platform
+        Some((platform, account)) => {
+            let dir = credentials_dir().join(platform);
+            fs::create_dir_all(&dir).ok();
+            dir.join(format!("{}.json", account))
+        }
+        None => credentials_dir().join(format!("{}.json", name)),
+    }
+}
+
+/// Names of the accounts with a stored token for a platform, sorted
+pub fn list_token_accounts(platform_id: &str) -> Vec<String> {
+    let mut accounts = Vec::new();
+    if credentials_dir().join(format!("{}.json", platform_id)).exists() {
+        accounts.push(DEFAULT_ACCOUNT.to_string());
+    }
+    if let Ok(entries) = fs::read_dir(credentials_dir().join(platform_id)) {
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                    accounts.push(name.to_string());
+                }
+            }
+        }
+    }
+    accounts.sort();
+    accounts
 }
 
 /// Ensure file has private permissions (0o600)
@@ -218,6 +281,22 @@ mod tests {
         assert_eq!(ref_.key, deserialized.key);
     }
 
+    #[test]
+    fn test_oauth_ref_platform_id() {
+        let ref_ = OAuthRef::for_platform("kimi-code");
+        assert_eq!(ref_.key, "oauth/kimi-code");
+        assert_eq!(ref_.platform_id(), Some("kimi-code"));
+        assert_eq!(ref_.account(), DEFAULT_ACCOUNT);
+        let legacy = OAuthRef { storage: "file".to_string(), key: "kimi-code".to_string() };
+        assert_eq!(legacy.platform_id(), None);
+
+        let work = OAuthRef::for_account("kimi-code", "work");
+        assert_eq!(work.key, "oauth/kimi-code/work");
+        assert_eq!(work.platform_id(), Some("kimi-code"));
+        assert_eq!(work.account(), "work");
+        assert_eq!(OAuthRef::for_account("kimi-code", DEFAULT_ACCOUNT).key, "oauth/kimi-code");
+    }
+
     #[test]
     fn test_credentials_path() {
         let path = credentials_path("oauth/kimi-code");
@@ -225,6 +304,9 @@ mod tests {
 
         let path = credentials_path("kimi-code");
         assert!(path.to_string_lossy().ends_with("kimi-code.json"));
+
+        let path = credentials_path("oauth/kimi-code/work");
+        assert!(path.ends_with("kimi-code/work.json"));
     }
 
     #[test]
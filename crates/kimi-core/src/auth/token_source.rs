@@ -0,0 +1,100 @@
+//! OAuth tokens as a provider [`TokenSource`]
+//!
+//! Providers built for an OAuth provider ask the [`OAuthTokenSource`] for
+//! the access token on every request. The stored token is refreshed when it
+//! is about to expire, in the background by the
+//! [`OAuthManager`](crate::auth::OAuthManager), and when the server rejects
+//! it.
+
+use crate::auth::oauth::{refresh_token, OAuthError, OAuthToken};
+use crate::auth::platforms::OAuthPlatform;
+use crate::auth::storage::{load_token, save_token, OAuthRef};
+use async_trait::async_trait;
+use kosong_rs::{ChatError, TokenSource};
+use tokio::sync::Mutex;
+use tracing::debug;
+
+/// Access tokens read from OAuth storage and refreshed with the platform's
+/// token endpoint
+#[derive(Debug)]
+pub struct OAuthTokenSource {
+    oauth_ref: OAuthRef,
+    platform: OAuthPlatform,
+    refresh_lock: Mutex<()>,
+}
+
+impl OAuthTokenSource {
+    /// Create a source for the token stored at `oauth_ref`
+    pub fn new(oauth_ref: OAuthRef, platform: OAuthPlatform) -> Self {
+        Self {
+            oauth_ref,
+            platform,
+            refresh_lock: Mutex::new(()),
+        }
+    }
+
+    /// The stored access token, refreshed first if it expires soon
+    pub async fn access_token(&self) -> Result<String, OAuthError> {
+        let token = self.load()?;
+        if !token.needs_refresh() {
+            return Ok(token.access_token);
+        }
+
+        let _guard = self.refresh_lock.lock().await;
+        // Another request or session may have refreshed while we waited
+        let token = self.load()?;
+        if !token.needs_refresh() {
+            return Ok(token.access_token);
+        }
+        self.refresh_stored(&token).await
+    }
+
+    /// A new access token after the server rejected `rejected`
+    ///
+    /// If the stored token has changed since, it is returned as is.
+    pub async fn refresh_rejected(&self, rejected: &str) -> Result<String, OAuthError> {
+        let _guard = self.refresh_lock.lock().await;
+        let token = self.load()?;
+        if token.access_token != rejected {
+            return Ok(token.access_token);
+        }
+        self.refresh_stored(&token).await
+    }
+
+    fn load(&self) -> Result<OAuthToken, OAuthError> {
+        load_token(&self.oauth_ref)
+            .ok_or_else(|| OAuthError::General(format!("No OAuth token stored for {}", self.oauth_ref.key)))
+    }
+
+    async fn refresh_stored(&self, token: &OAuthToken) -> Result<String, OAuthError> {
+        if token.refresh_token.is_empty() {
+            return Err(OAuthError::Unauthorized);
+        }
+        let refreshed = refresh_token(&self.platform, &token.refresh_token).await?;
+        save_token(&self.oauth_ref, &refreshed);
+        debug!("Refreshed OAuth token for {}", self.oauth_ref.key);
+        Ok(refreshed.access_token)
+    }
+}
+
+/// Report rejected credentials as a 401 so callers treat them like one
+fn chat_error(error: OAuthError) -> ChatError {
+    match error {
+        OAuthError::Unauthorized => ChatError::Api {
+            status: 401,
+            message: "OAuth credentials rejected; run `kimi login` again".to_string(),
+        },
+        e => ChatError::Other(format!("Failed to refresh OAuth token: {}", e)),
+    }
+}
+
+#[async_trait]
+impl TokenSource for OAuthTokenSource {
+    async fn token(&self) -> Result<String, ChatError> {
+        self.access_token().await.map_err(chat_error)
+    }
+
+    async fn refresh(&self, rejected: &str) -> Result<Option<String>, ChatError> {
+        self.refresh_rejected(rejected).await.map(Some).map_err(chat_error)
+    }
+}
@@ -0,0 +1,249 @@
+//! Reading and changing single config values in place
+//!
+//! [`ConfigDocument`] addresses values by dotted keys such as
+//! `loop_control.max_iterations`, with keys containing dots quoted:
+//! `providers."api.example".base_url`. Changes are made to the TOML document
+//! itself, so comments, key order and the rest of the file are written back
+//! as they were, and the changed document is validated like a loaded config
+//! before it is kept.
+
+use super::schema::{self, FieldType};
+use super::{load_config, Config, ConfigError};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml_edit::{DocumentMut, Item, Table, TableLike, Value};
+
+/// A config file, kept as the TOML document it was read from
+#[derive(Debug, Clone)]
+pub struct ConfigDocument {
+    path: PathBuf,
+    doc: DocumentMut,
+}
+
+impl ConfigDocument {
+    /// Read the config file at `path`
+    ///
+    /// A file that does not exist yet starts out as the default config.
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self, ConfigError> {
+        let path = path.into();
+        let content = if path.exists() {
+            std::fs::read_to_string(&path)?
+        } else {
+            toml::to_string_pretty(&load_config(Some(&path))?)?
+        };
+        Ok(Self { doc: content.parse()?, path })
+    }
+
+    /// The file the document is saved to
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The value of `key`, or its default when the file leaves it unset
+    ///
+    /// Strings are returned without quotes and tables as TOML.
+    pub fn get(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        let keys = parse_key(key)?;
+        let content = self.doc.to_string();
+        let file: toml::Table = content.parse()?;
+        if let Some(value) = lookup(&file, &keys) {
+            return Ok(Some(display_value(value)));
+        }
+        let config = Config::from_toml_str(&content).map_err(|e| e.with_path(&self.path))?;
+        let effective = toml::Value::try_from(&config)?;
+        Ok(effective.as_table().and_then(|table| lookup(table, &keys)).map(display_value))
+    }
+
+    /// Set `key` to `value`, read as the type the schema gives the key
+    ///
+    /// Tables missing on the way to the key are created. If the result is
+    /// not a valid config, the document is left as it was.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let keys = parse_key(key)?;
+        let names: Vec<&str> = keys.iter().map(String::as_str).collect();
+        let value = parse_value(schema::field_type(&names), value);
+        let invalid = |reason: String| ConfigError::InvalidKey { key: key.to_string(), reason };
+
+        let mut doc = self.doc.clone();
+        let (last, parents) = keys.split_last().ok_or_else(|| invalid("the key is empty".to_string()))?;
+        let mut table: &mut dyn TableLike = doc.as_table_mut();
+        for (i, name) in parents.iter().enumerate() {
+            let item = table.entry(name).or_insert_with(|| {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            });
+            table = item
+                .as_table_like_mut()
+                .ok_or_else(|| invalid(format!("{} is not a table", keys[..=i].join("."))))?;
+        }
+        match table.get_mut(last) {
+            Some(Item::Value(existing)) => {
+                // Keep the spacing and any comment after the old value
+                let decor = existing.decor().clone();
+                *existing = value;
+                *existing.decor_mut() = decor;
+            }
+            Some(item) if item.is_table_like() || item.is_array_of_tables() => {
+                return Err(invalid("it is a table; set the keys in it instead".to_string()));
+            }
+            _ => {
+                table.insert(last, Item::Value(value));
+            }
+        }
+
+        Config::from_toml_str(&doc.to_string()).map_err(|e| e.with_path(&self.path))?;
+        self.doc = doc;
+        Ok(())
+    }
+
+    /// Replace the whole document with `content`, if it is a valid config
+    pub fn replace(&mut self, content: &str) -> Result<(), ConfigError> {
+        Config::from_toml_str(content).map_err(|e| e.with_path(&self.path))?;
+        self.doc = content.parse()?;
+        Ok(())
+    }
+
+    /// Write the document to its file
+    pub fn save(&self) -> Result<(), ConfigError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, self.doc.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConfigDocument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.doc)
+    }
+}
+
+/// The keys of the dotted `key`
+fn parse_key(key: &str) -> Result<Vec<String>, ConfigError> {
+    toml_edit::Key::parse(key)
+        .map(|keys| keys.iter().map(|k| k.get().to_string()).collect())
+        .map_err(|e| ConfigError::InvalidKey { key: key.to_string(), reason: e.message().trim().to_string() })
+}
+
+/// `raw` as a value of type `ty`
+///
+/// Strings need no quotes. Other values are read as TOML, e.g. `80`, `true`
+/// or `["a", "b"]`, and taken as a string when they are not valid TOML.
+fn parse_value(ty: Option<&FieldType>, raw: &str) -> Value {
+    if let Some(FieldType::String | FieldType::OneOf(_)) = ty {
+        return Value::from(raw);
+    }
+    match raw.parse::<Value>() {
+        // A date like 2024-10-21 is meant as a string
+        Ok(mut value) if !value.is_datetime() => {
+            value.decor_mut().clear();
+            value
+        }
+        _ => Value::from(raw),
+    }
+}
+
+/// The value at `keys` in `table`
+fn lookup<'a>(table: &'a toml::Table, keys: &[String]) -> Option<&'a toml::Value> {
+    let (last, parents) = keys.split_last()?;
+    let mut table = table;
+    for key in parents {
+        table = table.get(key)?.as_table()?;
+    }
+    table.get(last)
+}
+
+fn display_value(value: &toml::Value) -> String {
+    match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Table(table) => table.to_string().trim_end().to_string(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# My config
+default_model = "kimi"   # the everyday model
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-k2"
+provider = "moonshot"
+
+[providers.moonshot]
+provider_type = "kimi"
+base_url = "https://api.moonshot.cn/v1"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#;
+
+    fn document() -> (tempfile::TempDir, ConfigDocument) {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("config.toml");
+        std::fs::write(&path, CONFIG).unwrap();
+        let document = ConfigDocument::load(&path).unwrap();
+        (temp, document)
+    }
+
+    #[test]
+    fn test_get() {
+        let (_temp, document) = document();
+        assert_eq!(document.get("default_model").unwrap().as_deref(), Some("kimi"));
+        assert_eq!(document.get("loop_control.max_iterations").unwrap().as_deref(), Some("100"));
+        assert_eq!(document.get("services.enabled").unwrap().as_deref(), Some("[]"));
+        assert_eq!(
+            document.get("models.kimi").unwrap().as_deref(),
+            Some("name = \"kimi-k2\"\nprovider = \"moonshot\"")
+        );
+        // Unset keys read as their defaults
+        assert_eq!(document.get("loop_control.max_steps_per_turn").unwrap().as_deref(), Some("25"));
+        assert_eq!(document.get("web.allowed_domains").unwrap(), None);
+        let err = document.get("loop_control.").unwrap_err();
+        assert!(err.to_string().starts_with("Invalid config key 'loop_control.'"), "{}", err);
+    }
+
+    #[test]
+    fn test_set() {
+        let (_temp, mut document) = document();
+        document.set("loop_control.max_iterations", "80").unwrap();
+        document.set("default_model", "kimi").unwrap();
+        document.set("providers.moonshot.api_version", "2024-10-21").unwrap();
+        document.set("web.allowed_domains", r#"["docs.rs"]"#).unwrap();
+        document.save().unwrap();
+
+        // Everything else is written back as it was
+        let content = std::fs::read_to_string(document.path()).unwrap();
+        assert!(content.starts_with("# My config\ndefault_model = \"kimi\"   # the everyday model\n"), "{}", content);
+        assert!(content.contains("[loop_control]\nmax_iterations = 80\ntimeout_seconds = 300\n"), "{}", content);
+        assert!(content.contains("api_version = \"2024-10-21\"\n"), "{}", content);
+        assert!(content.ends_with("[web]\nallowed_domains = [\"docs.rs\"]\n"), "{}", content);
+        let config = Config::from_toml_str(&content).unwrap();
+        assert_eq!(config.loop_control.max_iterations, 80);
+
+        // Invalid values are reported and not kept
+        let err = document.set("loop_control.max_iterations", "many").unwrap_err();
+        assert!(err.to_string().contains("loop_control.max_iterations"), "{}", err);
+        let err = document.set("default_model", "missing").unwrap_err();
+        assert!(err.to_string().contains("missing"), "{}", err);
+        let err = document.set("default_model.name", "kimi").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid config key 'default_model.name': default_model is not a table");
+        let err = document.set("loop_control", "1").unwrap_err();
+        assert!(err.to_string().contains("it is a table"), "{}", err);
+        assert_eq!(document.to_string(), content);
+    }
+}
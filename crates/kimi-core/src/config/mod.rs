@@ -0,0 +1,1152 @@
+//! Configuration types for the agent system
+
+use crate::approval::{PermissionMode, ToolRule};
+use crate::auth::{OAuthRef, PlatformConfig, SecretError, SecretsManager};
+use crate::soul::compaction::CompactionStrategy;
+use crate::types::{LoopControl, McpConfig, Services};
+use crate::LlmModel;
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub mod document;
+pub mod project;
+pub mod schema;
+
+pub use document::ConfigDocument;
+pub use project::{find_project_config, PROJECT_CONFIG_FILE};
+pub use schema::{ConfigIssue, IssueKind};
+
+/// Main configuration structure
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    pub default_model: String,
+    pub default_thinking: bool,
+    pub default_yolo: bool,
+    /// Permission mode sessions start in; `default_yolo = true` means
+    /// `full-auto` when this is not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_permission_mode: Option<PermissionMode>,
+    /// Models a step is retried on, in order, when the turn's model fails
+    /// with a retryable error; a single name or a list
+    #[serde(default, with = "one_or_many", skip_serializing_if = "Vec::is_empty")]
+    pub fallback_model: Vec<String>,
+    /// File a redacted transcript of every session is appended to;
+    /// relative paths are under the working directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transcript_file: Option<PathBuf>,
+    pub models: HashMap<String, LlmModel>,
+    pub providers: HashMap<String, LlmProvider>,
+    pub loop_control: LoopControl,
+    pub services: Services,
+    pub mcp: McpConfig,
+    /// Named agent personas, selectable with `/agent <name>`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub agents: HashMap<String, AgentPersona>,
+    /// Named tool subsets from the `[tool_profiles]` table, selectable with
+    /// `--tools` or an agent's `tool_profile`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tool_profiles: HashMap<String, Vec<String>>,
+    /// OpenTelemetry export settings from the `[telemetry]` table
+    #[serde(default, skip_serializing_if = "TelemetryConfig::is_unset")]
+    pub telemetry: TelemetryConfig,
+    /// Prompt template overrides from the `[prompts]` table
+    #[serde(default, skip_serializing_if = "PromptsConfig::is_unset")]
+    pub prompts: PromptsConfig,
+    /// Additional or overridden platforms from `[platforms.<id>]` tables
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub platforms: HashMap<String, PlatformConfig>,
+    /// Workspace retrieval settings from the `[rag]` table
+    #[serde(default, skip_serializing_if = "RagConfig::is_unset")]
+    pub rag: RagConfig,
+    /// Terminal colors from the `[theme]` table
+    #[serde(default, skip_serializing_if = "ThemeConfig::is_unset")]
+    pub theme: ThemeConfig,
+    /// Alerts when a long turn ends, from the `[notifications]` table
+    #[serde(default, skip_serializing_if = "NotificationsConfig::is_unset")]
+    pub notifications: NotificationsConfig,
+    /// Startup check for new releases, from the `[updates]` table
+    #[serde(default, skip_serializing_if = "UpdatesConfig::is_unset")]
+    pub updates: UpdatesConfig,
+    /// Caching of model responses, from the `[cache]` table
+    #[serde(default, skip_serializing_if = "CacheConfig::is_unset")]
+    pub cache: CacheConfig,
+    /// How the context is shrunk when it outgrows the context window, from
+    /// the `[compaction]` table
+    #[serde(default, skip_serializing_if = "CompactionConfig::is_unset")]
+    pub compaction: CompactionConfig,
+    /// Per-tool approval rules from the `[approval]` table
+    #[serde(default, skip_serializing_if = "ApprovalConfig::is_unset")]
+    pub approval: ApprovalConfig,
+    /// What FetchURL and SearchWeb may access, from the `[web]` table
+    #[serde(default, skip_serializing_if = "WebConfig::is_unset")]
+    pub web: WebConfig,
+    /// Whether the config was loaded from the default location
+    #[serde(skip)]
+    pub is_from_default_location: bool,
+}
+
+impl Config {
+    /// Load configuration from a TOML file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        Self::from_toml_str(&content).map_err(|e| e.with_path(path.as_ref()))
+    }
+
+    /// Parse and validate configuration from TOML text
+    ///
+    /// All schema problems and dangling model/provider references are
+    /// collected into a single [`ConfigError::Invalid`].
+    pub fn from_toml_str(content: &str) -> Result<Self, ConfigError> {
+        let doc: toml::Table = content.parse()?;
+        let issues = schema::validate_document(content, &doc);
+        if !issues.is_empty() {
+            return Err(ConfigError::Invalid { path: None, issues });
+        }
+
+        let config: Config = toml::from_str(content)?;
+        let issues = schema::validate_references(&config, Some(content));
+        if !issues.is_empty() {
+            return Err(ConfigError::Invalid { path: None, issues });
+        }
+        Ok(config)
+    }
+
+    /// Load configuration from a YAML file
+    pub fn from_yaml<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path.as_ref())?;
+        let config: Config = serde_yaml::from_str(&content)?;
+        config.validate().map_err(|e| e.with_path(path.as_ref()))?;
+        Ok(config)
+    }
+
+    /// Check that the default model and every model's provider are defined
+    /// Permission mode new sessions start in
+    pub fn permission_mode(&self) -> PermissionMode {
+        match self.default_permission_mode {
+            Some(mode) => mode,
+            None if self.default_yolo => PermissionMode::FullAuto,
+            None => PermissionMode::Default,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let issues = schema::validate_references(self, None);
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid { path: None, issues })
+        }
+    }
+
+    /// Save configuration to a TOML file
+    pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let content = toml::to_string_pretty(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Save configuration to a YAML file
+    pub fn to_yaml<P: AsRef<Path>>(&self, path: P) -> Result<(), ConfigError> {
+        let content = serde_yaml::to_string(self)?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// Get a provider by name
+    pub fn get_provider(&self, name: &str) -> Option<&LlmProvider> {
+        self.providers.get(name)
+    }
+
+    /// Get a model by name
+    pub fn get_model(&self, name: &str) -> Option<&LlmModel> {
+        self.models.get(name)
+    }
+
+    /// Move API keys written in the config into `secrets`
+    ///
+    /// Each provider with a plain `api_key` and no `api_key_ref` gets its key
+    /// stored under the provider's name and referenced from there. Returns
+    /// the names of the providers that were moved.
+    pub fn move_api_keys_to_secrets(&mut self, secrets: &SecretsManager) -> Result<Vec<String>, SecretError> {
+        use secrecy::ExposeSecret;
+
+        let mut moved = Vec::new();
+        for (name, provider) in &mut self.providers {
+            let api_key = provider.api_key.expose_secret();
+            if api_key.is_empty() || provider.api_key_ref.is_some() {
+                continue;
+            }
+            secrets.set(name, api_key)?;
+            provider.api_key_ref = Some(name.clone());
+            moved.push(name.clone());
+        }
+        moved.sort();
+        Ok(moved)
+    }
+}
+
+/// A named agent defined in the `[agents]` table
+///
+/// Unset fields fall back to the default agent's settings.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentPersona {
+    /// Short description shown when listing agents
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// System prompt that replaces the default one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_prompt: Option<String>,
+    /// Model key from `[models]`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Names of the tools this agent may use
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<String>>,
+    /// Tool profile this agent may use the tools of; `tools` takes
+    /// precedence when both are set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_profile: Option<String>,
+    /// Maximum LLM calls per turn
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_iterations: Option<usize>,
+}
+
+/// Prompt templates that replace the built-in ones
+///
+/// Templates may use the `{{os}}`, `{{cwd}}`, `{{date}}`, `{{model}}` and
+/// `{{tools}}` variables; see [`crate::prompts`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PromptsConfig {
+    /// System prompt of the default agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system: Option<String>,
+    /// Analysis prompt for `/init`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub init: Option<String>,
+    /// Summarization prompt for `/compact`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compact: Option<String>,
+    /// Commit message prompt for `/commit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl PromptsConfig {
+    /// Whether no prompts are overridden
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// OpenTelemetry export settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP traces endpoint, e.g. `http://localhost:4318/v1/traces`;
+    /// spans are only exported when this is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    /// Service name reported with the spans, `kimi-cli` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service_name: Option<String>,
+}
+
+impl TelemetryConfig {
+    /// Whether no telemetry settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Workspace retrieval settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RagConfig {
+    /// Provider under `[providers]` whose OpenAI-compatible `/embeddings`
+    /// endpoint is used; retrieval is only enabled when this is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    /// Embedding model, `text-embedding-3-small` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Chunks injected per message, 5 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<usize>,
+    /// Lowest similarity of an injected chunk, 0.3 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_score: Option<f32>,
+}
+
+impl RagConfig {
+    /// Default embedding model
+    pub const DEFAULT_MODEL: &'static str = "text-embedding-3-small";
+
+    /// Whether no retrieval settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Terminal colors of the interactive shell
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ThemeConfig {
+    /// Built-in palette, `dark` (the default) or `light`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<String>,
+    /// Colors replacing the preset's, by role: a name such as `blue` or
+    /// `light_red`, `#rrggbb`, or a 256-color index
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub colors: HashMap<String, String>,
+}
+
+impl ThemeConfig {
+    /// Whether no theme settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How the shell tells the user that a long turn finished or is waiting
+/// for approval
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationsConfig {
+    /// `bell` (the default), `terminal` for an OSC 9 notification,
+    /// `desktop`, or `off`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// Seconds a turn must run before it notifies, 30 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_seconds: Option<u64>,
+}
+
+impl NotificationsConfig {
+    /// Default for `after_seconds`
+    pub const DEFAULT_AFTER_SECONDS: u64 = 30;
+
+    /// Whether no notification settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Whether the shell looks for new releases when it starts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UpdatesConfig {
+    /// Check GitHub releases for a newer version, on by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub check: Option<bool>,
+}
+
+impl UpdatesConfig {
+    /// Whether the startup check is on
+    pub fn check_enabled(&self) -> bool {
+        self.check.unwrap_or(true)
+    }
+
+    /// Whether no update settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Caching of model responses on disk, for cheap re-runs of the same
+/// requests
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CacheConfig {
+    /// Answer requests seen before from the cache, off by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    /// Seconds a response is served from the cache, a day by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_seconds: Option<u64>,
+    /// Megabytes the cache is kept under, 100 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size_mb: Option<u64>,
+}
+
+impl CacheConfig {
+    /// Whether responses are cached
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(false)
+    }
+
+    /// Whether no cache settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How the context is shrunk when it outgrows the model's context window
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CompactionConfig {
+    /// `trim` to drop the oldest turns, listing what the user asked in
+    /// them, or `smart` to have the model summarize them; `trim` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<CompactionStrategy>,
+}
+
+impl CompactionConfig {
+    /// The configured strategy
+    pub fn strategy(&self) -> CompactionStrategy {
+        self.strategy.unwrap_or_default()
+    }
+
+    /// Whether no compaction settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// How tool calls are approved, on top of the permission mode
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    /// Rules by tool name from `[approval.tools]`: `always` to run the tool
+    /// without asking, `ask` to always ask, `never` to take it away
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tools: HashMap<String, ToolRule>,
+    /// Patterns of Shell commands that run without asking, e.g.
+    /// `git status`, as remembered with `/approve save`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+}
+
+impl ApprovalConfig {
+    /// Whether no approval rules are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The web policy: which domains the web tools may access and how often
+///
+/// A domain matches itself and its subdomains.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebConfig {
+    /// Domains the web tools may access; any domain when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_domains: Vec<String>,
+    /// Domains the web tools may never access, even when allowed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_domains: Vec<String>,
+    /// Requests a minute to any one domain, 30 by default; 0 for no limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Requests a minute by domain from `[web.rate_limits]`, overriding
+    /// `requests_per_minute` for those domains
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rate_limits: HashMap<String, u32>,
+    /// Kilobytes of a response that are read, 2048 by default; the rest is
+    /// cut off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_response_kb: Option<u64>,
+    /// Whether sites' robots.txt rules are followed, on by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub respect_robots_txt: Option<bool>,
+    /// Auth profiles for HttpRequest by name, from `[web.profiles.<name>]`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, WebAuthProfile>,
+}
+
+/// Saved credentials for an API, used by HttpRequest calls naming the
+/// profile so that the model never sees the token
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WebAuthProfile {
+    /// The API's URL; the profile is only sent to URLs under it, and
+    /// relative URLs are resolved against it
+    pub base_url: String,
+    /// Headers sent with every request, e.g. an `Accept` the API wants
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub headers: HashMap<String, String>,
+    /// Name of the secret holding the token, as stored with
+    /// `kimi secret set`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_ref: Option<String>,
+    /// Header the token is sent in: `Authorization` with `Bearer ` in
+    /// front by default, or e.g. `X-API-Key` for the token alone
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_header: Option<String>,
+}
+
+impl WebAuthProfile {
+    /// The token, from the secret `token_ref` names; `None` without one
+    pub fn load_token(&self, secrets: &SecretsManager) -> Result<Option<SecretString>, SecretError> {
+        match &self.token_ref {
+            Some(name) => secrets.get(name)?.ok_or_else(|| SecretError::Missing(name.clone())).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl WebConfig {
+    /// Requests a minute to any one domain
+    pub fn requests_per_minute(&self) -> u32 {
+        self.requests_per_minute.unwrap_or(30)
+    }
+
+    /// Bytes of a response that are read
+    pub fn max_response_bytes(&self) -> usize {
+        self.max_response_kb.unwrap_or(2048) as usize * 1024
+    }
+
+    /// Whether robots.txt rules are followed
+    pub fn respects_robots_txt(&self) -> bool {
+        self.respect_robots_txt.unwrap_or(true)
+    }
+
+    /// Whether no web settings are configured
+    pub fn is_unset(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// LLM Provider configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmProvider {
+    pub provider_type: ProviderType,
+    pub base_url: String,
+    #[serde(skip_serializing, default = "default_secret")]
+    pub api_key: SecretString,
+    pub env: Option<HashMap<String, String>>,
+    /// Headers sent with every request, over the provider's own
+    pub custom_headers: Option<HashMap<String, String>>,
+    /// HTTP(S) proxy requests go through, e.g. `http://proxy.internal:8080`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Azure OpenAI API version, 2024-10-21 by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    /// OAuth credential reference (do not store tokens here)
+    pub oauth: Option<OAuthRef>,
+    /// Name of the secret holding the API key, used instead of `api_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_ref: Option<String>,
+    /// Gemini safety filter thresholds by harm category, e.g.
+    /// `dangerous_content = "block_only_high"`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub safety_settings: Option<HashMap<String, String>>,
+    /// How long Ollama keeps a model loaded after a request, e.g. `30m`,
+    /// or `-1` to keep it loaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keep_alive: Option<String>,
+}
+
+/// (De)serializes a list that may also be written as a single value
+mod one_or_many {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(values: &[String], serializer: S) -> Result<S::Ok, S::Error> {
+        match values {
+            [value] => serializer.serialize_str(value),
+            values => serializer.collect_seq(values),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+        Ok(match OneOrMany::deserialize(deserializer)? {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        })
+    }
+}
+
+fn default_secret() -> SecretString {
+    SecretString::new(String::new())
+}
+
+impl LlmProvider {
+    /// Create a new provider configuration
+    pub fn new(
+        provider_type: ProviderType,
+        base_url: impl Into<String>,
+        api_key: impl Into<String>,
+    ) -> Self {
+        Self {
+            provider_type,
+            base_url: base_url.into(),
+            api_key: SecretString::new(api_key.into()),
+            env: None,
+            custom_headers: None,
+            proxy: None,
+            api_version: None,
+            oauth: None,
+            api_key_ref: None,
+            safety_settings: None,
+            keep_alive: None,
+        }
+    }
+
+    /// Set environment variables for the provider
+    pub fn with_env(mut self, env: HashMap<String, String>) -> Self {
+        self.env = Some(env);
+        self
+    }
+
+    /// Set custom headers for the provider
+    pub fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.custom_headers = Some(headers);
+        self
+    }
+
+    /// Send the provider's requests through the proxy at `url`
+    pub fn with_proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Set OAuth reference for the provider
+    pub fn with_oauth(mut self, oauth: OAuthRef) -> Self {
+        self.oauth = Some(oauth);
+        self
+    }
+
+    /// Set the Gemini safety filter thresholds for the provider
+    pub fn with_safety_settings(mut self, settings: HashMap<String, String>) -> Self {
+        self.safety_settings = Some(settings);
+        self
+    }
+
+    /// Set how long Ollama keeps a model loaded after a request
+    pub fn with_keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// Read the API key from the named secret instead of the config
+    pub fn with_api_key_ref(mut self, name: impl Into<String>) -> Self {
+        self.api_key_ref = Some(name.into());
+        self
+    }
+
+    /// The API key: the secret named by `api_key_ref` if set, `api_key` otherwise
+    pub fn load_api_key(&self, secrets: &SecretsManager) -> Result<SecretString, SecretError> {
+        match &self.api_key_ref {
+            Some(name) => secrets.get(name)?.ok_or_else(|| SecretError::Missing(name.clone())),
+            None => Ok(self.api_key.clone()),
+        }
+    }
+
+    /// Like [`load_api_key`](Self::load_api_key), falling back to the
+    /// provider type's variable, e.g. `OPENAI_API_KEY`, from the `env`
+    /// table or the environment when no key is configured
+    pub fn resolve_api_key(&self, secrets: &SecretsManager) -> Result<SecretString, SecretError> {
+        use secrecy::ExposeSecret;
+
+        let api_key = self.load_api_key(secrets)?;
+        if !api_key.expose_secret().is_empty() {
+            return Ok(api_key);
+        }
+        let from_env = self.provider_type.api_key_env().and_then(|name| {
+            self.env
+                .as_ref()
+                .and_then(|env| env.get(name).cloned())
+                .or_else(|| std::env::var(name).ok())
+        });
+        Ok(from_env.map(SecretString::new).unwrap_or(api_key))
+    }
+}
+
+/// Provider type enum
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProviderType {
+    Kimi,
+    OpenAiLegacy,
+    OpenAiResponses,
+    /// An Azure OpenAI resource, whose deployments are named by the
+    /// models' `name`
+    AzureOpenAi,
+    Anthropic,
+    Gemini,
+    VertexAi,
+    Ollama,
+}
+
+impl ProviderType {
+    /// Get the default base URL for this provider type
+    pub fn default_base_url(&self) -> &str {
+        match self {
+            ProviderType::Kimi => "https://api.moonshot.cn/v1",
+            ProviderType::OpenAiLegacy => "https://api.openai.com/v1",
+            ProviderType::OpenAiResponses => "https://api.openai.com/v1/responses",
+            // Each resource has its own endpoint
+            ProviderType::AzureOpenAi => "https://YOUR-RESOURCE.openai.azure.com",
+            ProviderType::Anthropic => "https://api.anthropic.com/v1",
+            ProviderType::Gemini => "https://generativelanguage.googleapis.com/v1beta",
+            ProviderType::VertexAi => "https://aiplatform.googleapis.com/v1",
+            ProviderType::Ollama => "http://localhost:11434",
+        }
+    }
+
+    /// Environment variable the API key is read from when the provider
+    /// has none configured
+    pub fn api_key_env(&self) -> Option<&'static str> {
+        match self {
+            ProviderType::Kimi => Some("KIMI_API_KEY"),
+            ProviderType::OpenAiLegacy | ProviderType::OpenAiResponses => Some("OPENAI_API_KEY"),
+            ProviderType::AzureOpenAi => Some("AZURE_OPENAI_API_KEY"),
+            ProviderType::Anthropic => Some("ANTHROPIC_API_KEY"),
+            ProviderType::Gemini => Some("GEMINI_API_KEY"),
+            ProviderType::VertexAi | ProviderType::Ollama => None,
+        }
+    }
+}
+
+/// Configuration errors
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TOML parse error: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("TOML serialize error: {0}")]
+    TomlSerialize(#[from] toml::ser::Error),
+    #[error("YAML error: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+    #[error("TOML parse error: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+    #[error("Invalid config key '{key}': {reason}")]
+    InvalidKey { key: String, reason: String },
+    #[error("Invalid configuration{}:{}", display_origin(.path), display_issues(.issues))]
+    Invalid {
+        path: Option<PathBuf>,
+        issues: Vec<ConfigIssue>,
+    },
+}
+
+impl ConfigError {
+    /// Attach the source file to a validation error
+    fn with_path(self, path: &Path) -> Self {
+        match self {
+            ConfigError::Invalid { issues, .. } => ConfigError::Invalid {
+                path: Some(path.to_path_buf()),
+                issues,
+            },
+            other => other,
+        }
+    }
+}
+
+fn display_origin(path: &Option<PathBuf>) -> String {
+    path.as_ref()
+        .map(|p| format!(" in {}", p.display()))
+        .unwrap_or_default()
+}
+
+fn display_issues(issues: &[ConfigIssue]) -> String {
+    issues.iter().map(|i| format!("\n  - {}", i)).collect()
+}
+
+/// The user's config file, `kimi/config.toml` in the config directory
+pub fn default_config_path() -> PathBuf {
+    dirs::config_dir()
+        .map(|d| d.join("kimi").join("config.toml"))
+        .unwrap_or_else(|| PathBuf::from(".kimi/config.toml"))
+}
+
+/// Load configuration from the default location or specified path
+pub fn load_config(path: Option<&Path>) -> Result<Config, ConfigError> {
+    let (path, is_default) = match path {
+        Some(p) => (p.to_path_buf(), false),
+        None => (default_config_path(), true),
+    };
+    
+    let mut config = if path.exists() {
+        Config::from_file(&path)?
+    } else {
+        // Return default config
+        Config {
+            default_model: String::new(),
+            default_thinking: false,
+            default_yolo: false,
+            default_permission_mode: None,
+            fallback_model: Vec::new(),
+            transcript_file: None,
+            models: HashMap::new(),
+            providers: HashMap::new(),
+            loop_control: LoopControl::default(),
+            services: Services::default(),
+            mcp: McpConfig::default(),
+            agents: HashMap::new(),
+            tool_profiles: HashMap::new(),
+            telemetry: TelemetryConfig::default(),
+            prompts: PromptsConfig::default(),
+            platforms: HashMap::new(),
+            rag: RagConfig::default(),
+            theme: ThemeConfig::default(),
+            notifications: NotificationsConfig::default(),
+            updates: UpdatesConfig::default(),
+            cache: CacheConfig::default(),
+            compaction: CompactionConfig::default(),
+            approval: ApprovalConfig::default(),
+            web: WebConfig::default(),
+            is_from_default_location: is_default,
+        }
+    };
+    
+    config.is_from_default_location = is_default;
+    Ok(config)
+}
+
+/// Load the configuration of a session in `work_dir`
+///
+/// This is [`load_config`], with the nearest project config merged over it
+/// unless a config file is given.
+pub fn load_session_config(path: Option<&Path>, work_dir: &Path) -> Result<Config, ConfigError> {
+    let config = load_config(path)?;
+    match path {
+        Some(_) => Ok(config),
+        None => config.with_project_config(work_dir),
+    }
+}
+
+/// Save configuration to the default location or specified path
+pub fn save_config(config: &Config, path: Option<&Path>) -> Result<(), ConfigError> {
+    use std::fs;
+    
+    let path = path.map(PathBuf::from).unwrap_or_else(default_config_path);
+    
+    // Ensure parent directory exists
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    
+    config.to_file(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use secrecy::ExposeSecret;
+
+    #[test]
+    fn test_provider_type_default_urls() {
+        assert_eq!(
+            ProviderType::Kimi.default_base_url(),
+            "https://api.moonshot.cn/v1"
+        );
+        assert_eq!(
+            ProviderType::OpenAiLegacy.default_base_url(),
+            "https://api.openai.com/v1"
+        );
+        assert_eq!(
+            ProviderType::Anthropic.default_base_url(),
+            "https://api.anthropic.com/v1"
+        );
+    }
+
+    #[test]
+    fn test_provider_builder() {
+        let provider = LlmProvider::new(
+            ProviderType::Kimi,
+            "https://custom.api.com",
+            "test-api-key",
+        )
+        .with_env(HashMap::from([("KEY".to_string(), "VALUE".to_string())]));
+
+        assert!(matches!(provider.provider_type, ProviderType::Kimi));
+        assert_eq!(provider.base_url, "https://custom.api.com");
+        assert!(provider.env.is_some());
+    }
+
+    #[test]
+    fn test_config_deserialization_without_api_key() {
+        // This tests that OAuth-based providers can be loaded without an api_key field
+        let config_str = r#"
+default_model = "kimi-code/kimi-for-coding"
+default_thinking = false
+default_yolo = false
+
+[models."kimi-code/kimi-for-coding"]
+name = "kimi-code/kimi-for-coding"
+provider = "managed:kimi-code"
+max_tokens = 128000
+
+[providers."managed:kimi-code"]
+provider_type = "kimi"
+base_url = "https://api.kimi.com/coding/v1"
+
+[providers."managed:kimi-code".oauth]
+storage = "file"
+key = "oauth/kimi-code"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#;
+        
+        let result: Result<Config, _> = toml::from_str(config_str);
+        assert!(result.is_ok(), "Failed to parse config: {:?}", result.err());
+        
+        let config = result.unwrap();
+        assert_eq!(config.default_model, "kimi-code/kimi-for-coding");
+        assert!(config.providers.contains_key("managed:kimi-code"));
+        
+        let provider = config.providers.get("managed:kimi-code").unwrap();
+        assert!(provider.oauth.is_some());
+        // api_key should default to empty string
+        assert_eq!(provider.api_key.expose_secret(), "");
+        assert!(config.agents.is_empty());
+    }
+
+    #[test]
+    fn test_config_agents() {
+        let config_str = r#"
+default_model = "kimi"
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-k2"
+provider = "moonshot"
+
+[providers.moonshot]
+provider_type = "kimi"
+base_url = "https://api.moonshot.cn/v1"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+
+[agents.reviewer]
+description = "Reviews diffs"
+system_prompt = "You review code."
+tools = ["ReadFile", "Grep"]
+max_iterations = 10
+
+[agents.fast]
+model = "missing"
+
+[agents.researcher]
+tool_profile = "research"
+
+[agents.browser]
+tool_profile = "web-only"
+
+[tool_profiles]
+research = ["ReadFile", "Grep", "SearchWeb"]
+"#;
+
+        let err = Config::from_toml_str(config_str).unwrap_err();
+        assert!(err.to_string().contains("`agents.fast.model` refers to model `missing`"), "{}", err);
+
+        let err = Config::from_toml_str(&config_str.replace("\"research\"", "\"missing\"")).unwrap_err();
+        assert!(err.to_string().contains("`agents.researcher.tool_profile` refers to tool profile `missing`"), "{}", err);
+
+        let config = Config::from_toml_str(&config_str.replace("\"missing\"", "\"kimi\"")).unwrap();
+        assert_eq!(config.tool_profiles["research"], ["ReadFile", "Grep", "SearchWeb"]);
+        assert_eq!(config.agents["researcher"].tool_profile.as_deref(), Some("research"));
+        let reviewer = &config.agents["reviewer"];
+        assert_eq!(reviewer.system_prompt.as_deref(), Some("You review code."));
+        assert_eq!(reviewer.tools, Some(vec!["ReadFile".to_string(), "Grep".to_string()]));
+        assert_eq!(reviewer.max_iterations, Some(10));
+        assert_eq!(reviewer.model, None);
+    }
+
+    #[test]
+    fn test_config_telemetry() {
+        let base = r#"
+default_model = "kimi"
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-k2"
+provider = "moonshot"
+
+[providers.moonshot]
+provider_type = "kimi"
+base_url = "https://api.moonshot.cn/v1"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#;
+        assert!(Config::from_toml_str(base).unwrap().telemetry.is_unset());
+
+        let config = Config::from_toml_str(&format!(
+            "{}\n[telemetry]\notlp_endpoint = \"http://localhost:4318/v1/traces\"\n",
+            base
+        ))
+        .unwrap();
+        assert_eq!(config.telemetry.otlp_endpoint.as_deref(), Some("http://localhost:4318/v1/traces"));
+        assert_eq!(config.telemetry.service_name, None);
+
+        let err = Config::from_toml_str(&format!("{}\n[telemetry]\nendpoint = \"x\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains("telemetry.endpoint"), "{}", err);
+    }
+
+    #[test]
+    fn test_config_rag() {
+        let base = r#"
+default_model = "kimi"
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-k2"
+provider = "moonshot"
+
+[providers.moonshot]
+provider_type = "kimi"
+base_url = "https://api.moonshot.cn/v1"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#;
+        assert!(Config::from_toml_str(base).unwrap().rag.is_unset());
+
+        let config = Config::from_toml_str(&format!("{}\n[rag]\nprovider = \"moonshot\"\ntop_k = 3\n", base)).unwrap();
+        assert_eq!(config.rag.provider.as_deref(), Some("moonshot"));
+        assert_eq!(config.rag.top_k, Some(3));
+        assert_eq!(config.rag.model, None);
+
+        let err = Config::from_toml_str(&format!("{}\n[rag]\nprovider = \"openai\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains("`rag.provider` refers to provider `openai`"), "{}", err);
+
+        assert!(!Config::from_toml_str(base).unwrap().cache.is_enabled());
+        let config = Config::from_toml_str(&format!("{}\n[cache]\nenabled = true\nttl_seconds = 60\n", base)).unwrap();
+        assert!(config.cache.is_enabled());
+        assert_eq!(config.cache.ttl_seconds, Some(60));
+        let err = Config::from_toml_str(&format!("{}\n[cache]\nenabled = \"yes\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains("cache.enabled"), "{}", err);
+
+        assert_eq!(Config::from_toml_str(base).unwrap().compaction.strategy(), CompactionStrategy::Trim);
+        let config = Config::from_toml_str(&format!("{}\n[compaction]\nstrategy = \"smart\"\n", base)).unwrap();
+        assert_eq!(config.compaction.strategy(), CompactionStrategy::Smart);
+        let err = Config::from_toml_str(&format!("{}\n[compaction]\nstrategy = \"fancy\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains(r#"must be one of "trim", "smart""#), "{}", err);
+
+        assert!(Config::from_toml_str(base).unwrap().approval.is_unset());
+        let config = Config::from_toml_str(&format!(
+            "{}\n[approval.tools]\nReadFile = \"always\"\nShell = \"ask\"\n",
+            base
+        ))
+        .unwrap();
+        assert_eq!(config.approval.tools["ReadFile"], ToolRule::Always);
+        assert_eq!(config.approval.tools["Shell"], ToolRule::Ask);
+        let config = Config::from_toml_str(&format!("{}\n[approval]\ncommands = [\"git status\"]\n", base)).unwrap();
+        assert_eq!(config.approval.commands, ["git status"]);
+        let err = Config::from_toml_str(&format!("{}\n[approval.tools]\nShell = \"sometimes\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains("approval.tools.Shell"), "{}", err);
+
+        let web = Config::from_toml_str(base).unwrap().web;
+        assert!(web.is_unset());
+        assert_eq!(web.requests_per_minute(), 30);
+        assert_eq!(web.max_response_bytes(), 2048 * 1024);
+        assert!(web.respects_robots_txt());
+        let config = Config::from_toml_str(&format!(
+            "{}\n[web]\nallowed_domains = [\"docs.rs\"]\nrespect_robots_txt = false\n[web.rate_limits]\n\"docs.rs\" = 10\n",
+            base
+        ))
+        .unwrap();
+        assert_eq!(config.web.allowed_domains, ["docs.rs"]);
+        assert_eq!(config.web.rate_limits["docs.rs"], 10);
+        assert!(!config.web.respects_robots_txt());
+        let err = Config::from_toml_str(&format!("{}\n[web]\nallowed_domain = [\"docs.rs\"]\n", base)).unwrap_err();
+        assert!(err.to_string().contains("allowed_domains"), "{}", err);
+        let config = Config::from_toml_str(&format!(
+            "{}\n[web.profiles.github]\nbase_url = \"https://api.github.com\"\ntoken_ref = \"github\"\nheaders = {{ Accept = \"application/vnd.github+json\" }}\n",
+            base
+        ))
+        .unwrap();
+        let github = &config.web.profiles["github"];
+        assert_eq!(github.token_ref.as_deref(), Some("github"));
+        assert_eq!(github.headers["Accept"], "application/vnd.github+json");
+        let err = Config::from_toml_str(&format!("{}\n[web.profiles.github]\ntoken_ref = \"github\"\n", base)).unwrap_err();
+        assert!(err.to_string().contains("web.profiles.github.base_url"), "{}", err);
+    }
+
+    #[test]
+    fn test_provider_api_key_ref() {
+        let temp = tempfile::tempdir().unwrap();
+        let secrets = SecretsManager::file_only(temp.path());
+        let plain = LlmProvider::new(ProviderType::Kimi, "https://api.moonshot.cn/v1", "sk-plain");
+        assert_eq!(plain.load_api_key(&secrets).unwrap().expose_secret(), "sk-plain");
+
+        let provider = LlmProvider::new(ProviderType::Kimi, "https://api.moonshot.cn/v1", "").with_api_key_ref("moonshot");
+        assert!(matches!(provider.load_api_key(&secrets), Err(SecretError::Missing(_))));
+        secrets.set("moonshot", "sk-secret").unwrap();
+        assert_eq!(provider.load_api_key(&secrets).unwrap().expose_secret(), "sk-secret");
+
+        // Without a key, the provider type's variable is used
+        let azure = LlmProvider::new(ProviderType::AzureOpenAi, "https://my-resource.openai.azure.com", "")
+            .with_env(HashMap::from([("AZURE_OPENAI_API_KEY".to_string(), "azure-key".to_string())]));
+        assert_eq!(azure.resolve_api_key(&secrets).unwrap().expose_secret(), "azure-key");
+        assert_eq!(plain.resolve_api_key(&secrets).unwrap().expose_secret(), "sk-plain");
+        assert_eq!(ProviderType::Ollama.api_key_env(), None);
+
+        let toml = toml::to_string(&provider).unwrap();
+        assert!(toml.contains("api_key_ref = \"moonshot\""));
+        assert!(!toml.contains("sk-secret"));
+
+        let mut config = Config::from_toml_str(
+            r#"
+default_model = "kimi"
+default_thinking = false
+default_yolo = false
+
+[models.kimi]
+name = "kimi-k2"
+provider = "openai"
+
+[providers.openai]
+provider_type = "open_ai_legacy"
+base_url = "https://api.openai.com/v1"
+api_key = "sk-openai"
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = []
+"#,
+        )
+        .unwrap();
+        assert_eq!(config.move_api_keys_to_secrets(&secrets).unwrap(), vec!["openai"]);
+        assert_eq!(config.providers["openai"].api_key_ref.as_deref(), Some("openai"));
+        assert_eq!(secrets.get("openai").unwrap().unwrap().expose_secret(), "sk-openai");
+        assert!(config.move_api_keys_to_secrets(&secrets).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,874 @@
+//! Schema validation for configuration files
+//!
+//! The TOML document is checked against the shape of [`Config`] before it is
+//! deserialized, so every unknown key, type mismatch, and dangling model or
+//! provider reference is reported at once with the line it came from, rather
+//! than surfacing later as a bare `NoProvider` error.
+
+use super::Config;
+use crate::soul::tool_profiles::ToolProfiles;
+use std::fmt;
+
+/// Expected type of a configuration value
+#[derive(Debug)]
+pub enum FieldType {
+    String,
+    Bool,
+    Integer,
+    /// Floats also accept integer literals (`temperature = 1`)
+    Float,
+    /// Any value is accepted
+    Any,
+    /// A string restricted to a fixed set of values
+    OneOf(&'static [&'static str]),
+    /// A table with a fixed set of keys
+    Table(&'static [Field]),
+    /// A table with arbitrary keys whose values share one type
+    Map(&'static FieldType),
+    /// An array whose items share one type
+    Array(&'static FieldType),
+    /// A string, or an array of strings
+    Strings,
+}
+
+impl FieldType {
+    fn describe(&self) -> &'static str {
+        match self {
+            FieldType::String | FieldType::OneOf(_) => "a string",
+            FieldType::Bool => "a boolean",
+            FieldType::Integer => "an integer",
+            FieldType::Float => "a number",
+            FieldType::Any => "any value",
+            FieldType::Table(_) | FieldType::Map(_) => "a table",
+            FieldType::Array(_) => "an array",
+            FieldType::Strings => "a string or an array of strings",
+        }
+    }
+}
+
+/// A known key within a table
+#[derive(Debug)]
+pub struct Field {
+    pub name: &'static str,
+    pub ty: FieldType,
+    pub required: bool,
+}
+
+const fn required(name: &'static str, ty: FieldType) -> Field {
+    Field { name, ty, required: true }
+}
+
+const fn optional(name: &'static str, ty: FieldType) -> Field {
+    Field { name, ty, required: false }
+}
+
+const PROVIDER_TYPES: &[&str] = &[
+    "kimi",
+    "open_ai_legacy",
+    "open_ai_responses",
+    "azure_open_ai",
+    "anthropic",
+    "gemini",
+    "vertex_ai",
+    "ollama",
+];
+
+const MODEL_FIELDS: &[Field] = &[
+    required("name", FieldType::String),
+    required("provider", FieldType::String),
+    optional("max_tokens", FieldType::Integer),
+    optional("temperature", FieldType::Float),
+    optional("pricing", FieldType::Table(PRICING_FIELDS)),
+];
+
+const PRICING_FIELDS: &[Field] = &[
+    required("input", FieldType::Float),
+    required("output", FieldType::Float),
+];
+
+const OAUTH_FIELDS: &[Field] = &[
+    required("storage", FieldType::String),
+    required("key", FieldType::String),
+];
+
+const PROVIDER_FIELDS: &[Field] = &[
+    required("provider_type", FieldType::OneOf(PROVIDER_TYPES)),
+    required("base_url", FieldType::String),
+    optional("api_key", FieldType::String),
+    optional("env", FieldType::Map(&FieldType::String)),
+    optional("custom_headers", FieldType::Map(&FieldType::String)),
+    optional("proxy", FieldType::String),
+    optional("api_version", FieldType::String),
+    optional("oauth", FieldType::Table(OAUTH_FIELDS)),
+    optional("api_key_ref", FieldType::String),
+    optional("safety_settings", FieldType::Map(&FieldType::String)),
+    optional("keep_alive", FieldType::String),
+];
+
+const LOOP_CONTROL_FIELDS: &[Field] = &[
+    required("max_iterations", FieldType::Integer),
+    required("timeout_seconds", FieldType::Integer),
+    optional("max_steps_per_turn", FieldType::Integer),
+    optional("max_tool_calls_per_turn", FieldType::Integer),
+    optional("max_identical_tool_calls", FieldType::Integer),
+];
+
+const SERVICES_FIELDS: &[Field] = &[
+    required("enabled", FieldType::Array(&FieldType::String)),
+    required("config", FieldType::Map(&FieldType::Any)),
+];
+
+const MCP_SERVER_FIELDS: &[Field] = &[
+    required("name", FieldType::String),
+    required("command", FieldType::String),
+    required("args", FieldType::Array(&FieldType::String)),
+    optional("env", FieldType::Map(&FieldType::String)),
+];
+
+const MCP_FIELDS: &[Field] = &[
+    required("servers", FieldType::Array(&FieldType::Table(MCP_SERVER_FIELDS))),
+    optional("enabled_tools", FieldType::Array(&FieldType::String)),
+];
+
+const AGENT_FIELDS: &[Field] = &[
+    optional("description", FieldType::String),
+    optional("system_prompt", FieldType::String),
+    optional("model", FieldType::String),
+    optional("tools", FieldType::Array(&FieldType::String)),
+    optional("tool_profile", FieldType::String),
+    optional("max_iterations", FieldType::Integer),
+];
+
+const TELEMETRY_FIELDS: &[Field] = &[
+    optional("otlp_endpoint", FieldType::String),
+    optional("service_name", FieldType::String),
+];
+
+const PROMPTS_FIELDS: &[Field] = &[
+    optional("system", FieldType::String),
+    optional("init", FieldType::String),
+    optional("compact", FieldType::String),
+    optional("commit", FieldType::String),
+];
+
+const PLATFORM_OAUTH_FIELDS: &[Field] = &[
+    required("host", FieldType::String),
+    required("client_id", FieldType::String),
+    optional("scopes", FieldType::Array(&FieldType::String)),
+    optional("device_authorization_path", FieldType::String),
+    optional("token_path", FieldType::String),
+    optional("device_headers", FieldType::Bool),
+];
+
+const PLATFORM_FIELDS: &[Field] = &[
+    required("name", FieldType::String),
+    required("base_url", FieldType::String),
+    optional("search_url", FieldType::String),
+    optional("fetch_url", FieldType::String),
+    optional("allowed_prefixes", FieldType::Array(&FieldType::String)),
+    optional("oauth", FieldType::Table(PLATFORM_OAUTH_FIELDS)),
+];
+
+const RAG_FIELDS: &[Field] = &[
+    optional("provider", FieldType::String),
+    optional("model", FieldType::String),
+    optional("top_k", FieldType::Integer),
+    optional("min_score", FieldType::Float),
+];
+
+const THEME_COLOR_FIELDS: &[Field] = &[
+    optional("prompt", FieldType::String),
+    optional("shell_prompt", FieldType::String),
+    optional("input", FieldType::String),
+    optional("agent", FieldType::String),
+    optional("heading", FieldType::String),
+    optional("section", FieldType::String),
+    optional("command", FieldType::String),
+    optional("accent", FieldType::String),
+    optional("muted", FieldType::String),
+    optional("emphasis", FieldType::String),
+    optional("success", FieldType::String),
+    optional("warning", FieldType::String),
+    optional("error", FieldType::String),
+    optional("tool", FieldType::String),
+    optional("skill", FieldType::String),
+    optional("code", FieldType::String),
+    optional("link", FieldType::String),
+    optional("diff_added", FieldType::String),
+    optional("diff_removed", FieldType::String),
+    optional("diff_hunk", FieldType::String),
+];
+
+const THEME_FIELDS: &[Field] = &[
+    optional("preset", FieldType::OneOf(&["dark", "light"])),
+    optional("colors", FieldType::Table(THEME_COLOR_FIELDS)),
+];
+
+const NOTIFICATIONS_FIELDS: &[Field] = &[
+    optional("method", FieldType::OneOf(&["bell", "terminal", "desktop", "off"])),
+    optional("after_seconds", FieldType::Integer),
+];
+
+const UPDATES_FIELDS: &[Field] = &[optional("check", FieldType::Bool)];
+
+const CACHE_FIELDS: &[Field] = &[
+    optional("enabled", FieldType::Bool),
+    optional("ttl_seconds", FieldType::Integer),
+    optional("max_size_mb", FieldType::Integer),
+];
+
+const COMPACTION_FIELDS: &[Field] = &[optional("strategy", FieldType::OneOf(&["trim", "smart"]))];
+
+const APPROVAL_FIELDS: &[Field] = &[
+    optional("tools", FieldType::Map(&FieldType::OneOf(&["always", "ask", "never"]))),
+    optional("commands", FieldType::Array(&FieldType::String)),
+];
+
+const WEB_PROFILE_FIELDS: &[Field] = &[
+    required("base_url", FieldType::String),
+    optional("headers", FieldType::Map(&FieldType::String)),
+    optional("token_ref", FieldType::String),
+    optional("token_header", FieldType::String),
+];
+
+const WEB_FIELDS: &[Field] = &[
+    optional("allowed_domains", FieldType::Array(&FieldType::String)),
+    optional("blocked_domains", FieldType::Array(&FieldType::String)),
+    optional("requests_per_minute", FieldType::Integer),
+    optional("rate_limits", FieldType::Map(&FieldType::Integer)),
+    optional("max_response_kb", FieldType::Integer),
+    optional("respect_robots_txt", FieldType::Bool),
+    optional("profiles", FieldType::Map(&FieldType::Table(WEB_PROFILE_FIELDS))),
+];
+
+/// Schema of the top-level configuration table
+pub const CONFIG_SCHEMA: &[Field] = &[
+    required("default_model", FieldType::String),
+    required("default_thinking", FieldType::Bool),
+    required("default_yolo", FieldType::Bool),
+    optional("default_permission_mode", FieldType::String),
+    optional("fallback_model", FieldType::Strings),
+    optional("transcript_file", FieldType::String),
+    required("models", FieldType::Map(&FieldType::Table(MODEL_FIELDS))),
+    required("providers", FieldType::Map(&FieldType::Table(PROVIDER_FIELDS))),
+    required("loop_control", FieldType::Table(LOOP_CONTROL_FIELDS)),
+    required("services", FieldType::Table(SERVICES_FIELDS)),
+    required("mcp", FieldType::Table(MCP_FIELDS)),
+    optional("agents", FieldType::Map(&FieldType::Table(AGENT_FIELDS))),
+    optional("tool_profiles", FieldType::Map(&FieldType::Array(&FieldType::String))),
+    optional("telemetry", FieldType::Table(TELEMETRY_FIELDS)),
+    optional("prompts", FieldType::Table(PROMPTS_FIELDS)),
+    optional("platforms", FieldType::Map(&FieldType::Table(PLATFORM_FIELDS))),
+    optional("rag", FieldType::Table(RAG_FIELDS)),
+    optional("theme", FieldType::Table(THEME_FIELDS)),
+    optional("notifications", FieldType::Table(NOTIFICATIONS_FIELDS)),
+    optional("updates", FieldType::Table(UPDATES_FIELDS)),
+    optional("cache", FieldType::Table(CACHE_FIELDS)),
+    optional("compaction", FieldType::Table(COMPACTION_FIELDS)),
+    optional("approval", FieldType::Table(APPROVAL_FIELDS)),
+    optional("web", FieldType::Table(WEB_FIELDS)),
+];
+
+/// Category of a configuration problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssueKind {
+    UnknownKey,
+    MissingKey,
+    TypeMismatch,
+    MissingReference,
+}
+
+/// A single problem found while validating a configuration
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub kind: IssueKind,
+    /// Dotted path of the offending key, e.g. `models.kimi-k2.provider`
+    pub key: String,
+    /// 1-based line in the source file, when known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// Check a parsed TOML document against [`CONFIG_SCHEMA`]
+///
+/// `source` is the original text and is only used to attach line numbers.
+pub fn validate_document(source: &str, doc: &toml::Table) -> Vec<ConfigIssue> {
+    let index = LineIndex::new(source);
+    let mut issues = Vec::new();
+    check_table(doc, CONFIG_SCHEMA, &mut Vec::new(), &index, &mut issues);
+    issues
+}
+
+/// The type of the value at the dotted `path`, if the schema knows the key
+pub fn field_type(path: &[&str]) -> Option<&'static FieldType> {
+    static ROOT: FieldType = FieldType::Table(CONFIG_SCHEMA);
+    let mut ty = &ROOT;
+    for key in path {
+        ty = match ty {
+            FieldType::Table(fields) => &fields.iter().find(|field| field.name == *key)?.ty,
+            FieldType::Map(inner) => inner,
+            _ => return None,
+        };
+    }
+    Some(ty)
+}
+
+/// Check that every model and provider referenced in `config` exists
+///
+/// When `source` is given, issues carry the line of the referencing key.
+pub fn validate_references(config: &Config, source: Option<&str>) -> Vec<ConfigIssue> {
+    let index = source.map(LineIndex::new);
+    let line_of = |path: &[&str]| {
+        let path: Vec<String> = path.iter().map(|s| s.to_string()).collect();
+        index.as_ref().and_then(|i| i.locate(&path))
+    };
+    let mut issues = Vec::new();
+
+    if !config.default_model.is_empty() && !config.models.contains_key(&config.default_model) {
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingReference,
+            key: "default_model".to_string(),
+            line: line_of(&["default_model"]),
+            message: format!(
+                "`default_model` refers to model `{}`, which is not defined under [models]{}",
+                config.default_model,
+                available(config.models.keys()),
+            ),
+        });
+    }
+
+    for model in config.fallback_model.iter().filter(|m| !config.models.contains_key(*m)) {
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingReference,
+            key: "fallback_model".to_string(),
+            line: line_of(&["fallback_model"]),
+            message: format!(
+                "`fallback_model` refers to model `{}`, which is not defined under [models]{}",
+                model,
+                available(config.models.keys()),
+            ),
+        });
+    }
+
+    let mut models: Vec<_> = config.models.iter().collect();
+    models.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, model) in models {
+        if !config.providers.contains_key(&model.provider) {
+            let path = ["models", key.as_str(), "provider"];
+            issues.push(ConfigIssue {
+                kind: IssueKind::MissingReference,
+                key: display_path(&path),
+                line: line_of(&path),
+                message: format!(
+                    "`{}` refers to provider `{}`, which is not defined under [providers]{}",
+                    display_path(&path),
+                    model.provider,
+                    available(config.providers.keys()),
+                ),
+            });
+        }
+    }
+
+    let mut agents: Vec<_> = config.agents.iter().collect();
+    agents.sort_by(|a, b| a.0.cmp(b.0));
+    for (key, agent) in &agents {
+        let Some(model) = agent.model.as_ref().filter(|m| !config.models.contains_key(*m)) else {
+            continue;
+        };
+        let path = ["agents", key.as_str(), "model"];
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingReference,
+            key: display_path(&path),
+            line: line_of(&path),
+            message: format!(
+                "`{}` refers to model `{}`, which is not defined under [models]{}",
+                display_path(&path),
+                model,
+                available(config.models.keys()),
+            ),
+        });
+    }
+
+    for (key, agent) in &agents {
+        let Some(profile) = agent
+            .tool_profile
+            .as_ref()
+            .filter(|p| !ToolProfiles::is_builtin(p) && !config.tool_profiles.contains_key(*p))
+        else {
+            continue;
+        };
+        let path = ["agents", key.as_str(), "tool_profile"];
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingReference,
+            key: display_path(&path),
+            line: line_of(&path),
+            message: format!(
+                "`{}` refers to tool profile `{}`, which is not built in or defined under [tool_profiles]",
+                display_path(&path),
+                profile,
+            ),
+        });
+    }
+
+    if let Some(provider) = config.rag.provider.as_ref().filter(|p| !config.providers.contains_key(*p)) {
+        let path = ["rag", "provider"];
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingReference,
+            key: display_path(&path),
+            line: line_of(&path),
+            message: format!(
+                "`{}` refers to provider `{}`, which is not defined under [providers]{}",
+                display_path(&path),
+                provider,
+                available(config.providers.keys()),
+            ),
+        });
+    }
+
+    issues
+}
+
+fn available<'a>(keys: impl Iterator<Item = &'a String>) -> String {
+    let mut keys: Vec<_> = keys.map(|k| format!("`{}`", k)).collect();
+    if keys.is_empty() {
+        return " (none are defined)".to_string();
+    }
+    keys.sort();
+    format!(" (available: {})", keys.join(", "))
+}
+
+fn check_table(
+    table: &toml::Table,
+    fields: &[Field],
+    path: &mut Vec<String>,
+    index: &LineIndex,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    let mut keys: Vec<_> = table.keys().collect();
+    keys.sort_by_key(|k| {
+        let mut p = path.clone();
+        p.push((*k).clone());
+        index.locate(&p).unwrap_or(usize::MAX)
+    });
+
+    for key in keys {
+        path.push(key.clone());
+        match fields.iter().find(|f| f.name == key) {
+            Some(field) => check_value(&table[key], &field.ty, path, index, issues),
+            None => {
+                let hint = suggest(key, fields.iter().map(|f| f.name))
+                    .map(|s| format!("; did you mean `{}`?", s))
+                    .unwrap_or_default();
+                issues.push(ConfigIssue {
+                    kind: IssueKind::UnknownKey,
+                    key: display_path(path),
+                    line: index.locate(path),
+                    message: format!("unknown key `{}`{}", display_path(path), hint),
+                });
+            }
+        }
+        path.pop();
+    }
+
+    for field in fields.iter().filter(|f| f.required && !table.contains_key(f.name)) {
+        path.push(field.name.to_string());
+        let key = display_path(path);
+        path.pop();
+        issues.push(ConfigIssue {
+            kind: IssueKind::MissingKey,
+            line: index.locate(path),
+            message: format!("missing required key `{}` ({})", key, field.ty.describe()),
+            key,
+        });
+    }
+}
+
+fn check_value(
+    value: &toml::Value,
+    ty: &FieldType,
+    path: &mut Vec<String>,
+    index: &LineIndex,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    use toml::Value;
+
+    let matches = match (ty, value) {
+        (FieldType::Any, _) => true,
+        (FieldType::String, Value::String(_)) => true,
+        (FieldType::Bool, Value::Boolean(_)) => true,
+        (FieldType::Integer, Value::Integer(_)) => true,
+        (FieldType::Float, Value::Float(_) | Value::Integer(_)) => true,
+        (FieldType::OneOf(allowed), Value::String(s)) => {
+            if !allowed.contains(&s.as_str()) {
+                issues.push(ConfigIssue {
+                    kind: IssueKind::TypeMismatch,
+                    key: display_path(path),
+                    line: index.locate(path),
+                    message: format!(
+                        "`{}` must be one of {}, found \"{}\"",
+                        display_path(path),
+                        allowed.iter().map(|a| format!("\"{}\"", a)).collect::<Vec<_>>().join(", "),
+                        s,
+                    ),
+                });
+            }
+            true
+        }
+        (FieldType::Table(fields), Value::Table(table)) => {
+            check_table(table, fields, path, index, issues);
+            true
+        }
+        (FieldType::Map(item), Value::Table(table)) => {
+            for (key, value) in table {
+                path.push(key.clone());
+                check_value(value, item, path, index, issues);
+                path.pop();
+            }
+            true
+        }
+        (FieldType::Array(item), Value::Array(items)) => {
+            for (i, value) in items.iter().enumerate() {
+                path.push(i.to_string());
+                check_value(value, item, path, index, issues);
+                path.pop();
+            }
+            true
+        }
+        (FieldType::Strings, Value::String(_)) => true,
+        (FieldType::Strings, Value::Array(_)) => {
+            check_value(value, &FieldType::Array(&FieldType::String), path, index, issues);
+            true
+        }
+        _ => false,
+    };
+
+    if !matches {
+        issues.push(ConfigIssue {
+            kind: IssueKind::TypeMismatch,
+            key: display_path(path),
+            line: index.locate(path),
+            message: format!(
+                "`{}` should be {}, found {}",
+                display_path(path),
+                ty.describe(),
+                describe_value(value),
+            ),
+        });
+    }
+}
+
+fn describe_value(value: &toml::Value) -> &'static str {
+    match value {
+        toml::Value::String(_) => "a string",
+        toml::Value::Integer(_) => "an integer",
+        toml::Value::Float(_) => "a float",
+        toml::Value::Boolean(_) => "a boolean",
+        toml::Value::Datetime(_) => "a datetime",
+        toml::Value::Array(_) => "an array",
+        toml::Value::Table(_) => "a table",
+    }
+}
+
+/// Render a key path the way it would be written in TOML
+fn display_path<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter()
+        .map(|segment| {
+            let s = segment.as_ref();
+            let bare = !s.is_empty()
+                && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+            if bare { s.to_string() } else { format!("\"{}\"", s) }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Pick the closest known key for a likely typo
+fn suggest<'a>(key: &str, candidates: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    candidates
+        .map(|c| (edit_distance(key, c), c))
+        .filter(|(d, c)| *d <= 2.max(c.len() / 4))
+        .min_by_key(|(d, _)| *d)
+        .map(|(_, c)| c)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur.push((prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1));
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+/// Maps key paths to the line where they are defined
+///
+/// This is a line-oriented scan rather than a full parser; it understands
+/// table headers, arrays of tables, dotted and quoted keys, and skips the
+/// bodies of multi-line arrays and strings.
+struct LineIndex {
+    entries: Vec<(Vec<String>, usize)>,
+}
+
+impl LineIndex {
+    fn new(source: &str) -> Self {
+        let mut entries = Vec::new();
+        let mut table: Vec<String> = Vec::new();
+        let mut array_counts: std::collections::HashMap<Vec<String>, usize> =
+            std::collections::HashMap::new();
+        let mut depth = 0usize;
+        let mut in_multiline_string = false;
+
+        for (i, raw) in source.lines().enumerate() {
+            let line_no = i + 1;
+            let line = raw.trim();
+
+            if in_multiline_string {
+                if line.contains("\"\"\"") || line.contains("'''") {
+                    in_multiline_string = false;
+                }
+                continue;
+            }
+            if depth > 0 {
+                depth = (depth as isize + bracket_delta(line)).max(0) as usize;
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some(header) = line.strip_prefix("[[").and_then(|l| l.split("]]").next()) {
+                let path = split_key(header);
+                let count = array_counts.entry(path.clone()).or_insert(0);
+                table = path;
+                table.push(count.to_string());
+                *count += 1;
+                entries.push((table.clone(), line_no));
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.split(']').next()) {
+                table = split_key(header);
+                entries.push((table.clone(), line_no));
+                continue;
+            }
+
+            if let Some(eq) = find_unquoted(line, '=') {
+                let mut path = table.clone();
+                path.extend(split_key(&line[..eq]));
+                entries.push((path, line_no));
+
+                let value = &line[eq + 1..];
+                if value.matches("\"\"\"").count() == 1 || value.matches("'''").count() == 1 {
+                    in_multiline_string = true;
+                }
+                depth = bracket_delta(value).max(0) as usize;
+            }
+        }
+
+        Self { entries }
+    }
+
+    /// Line of the longest recorded path that is a prefix of `path`
+    fn locate(&self, path: &[String]) -> Option<usize> {
+        self.entries
+            .iter()
+            .filter(|(p, _)| !p.is_empty() && path.starts_with(p))
+            .max_by_key(|(p, line)| (p.len(), std::cmp::Reverse(*line)))
+            .map(|(_, line)| *line)
+    }
+}
+
+/// Split a (possibly dotted and quoted) TOML key into its segments
+fn split_key(key: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+
+    for c in key.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '.') => segments.push(std::mem::take(&mut current).trim().to_string()),
+            (None, c) => current.push(c),
+        }
+    }
+    segments.push(current.trim().to_string());
+    segments
+}
+
+fn find_unquoted(line: &str, target: char) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == target => return Some(i),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Net change in `[`/`{` nesting over a line, ignoring quoted text and comments
+fn bracket_delta(line: &str) -> isize {
+    let mut delta = 0;
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => delta += 1,
+                ']' | '}' => delta -= 1,
+                '#' => break,
+                _ => {}
+            },
+        }
+    }
+    delta
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"
+default_model = "kimi-k2"
+default_thinking = false
+default_yolo = false
+
+[models.kimi-k2]
+name = "kimi-k2"
+provider = "kimi"
+max_tokens = 8192
+temperature = 1
+
+[providers.kimi]
+provider_type = "kimi"
+base_url = "https://api.moonshot.cn/v1"
+oauth = { storage = "file", key = "oauth/kimi-code" }
+
+[loop_control]
+max_iterations = 100
+timeout_seconds = 300
+
+[services]
+enabled = []
+config = {}
+
+[mcp]
+servers = [
+    { name = "fs", command = "npx", args = ["-y", "server-fs"] },
+]
+"#;
+
+    fn issues_for(source: &str) -> Vec<ConfigIssue> {
+        let doc: toml::Table = source.parse().unwrap();
+        validate_document(source, &doc)
+    }
+
+    #[test]
+    fn test_valid_config_has_no_issues() {
+        assert!(issues_for(VALID).is_empty());
+        let config = Config::from_toml_str(VALID).unwrap();
+        assert!(validate_references(&config, Some(VALID)).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_key_with_suggestion() {
+        let source = VALID.replace("max_tokens = 8192", "max_token = 8192");
+        let issues = issues_for(&source);
+
+        let unknown = issues.iter().find(|i| i.kind == IssueKind::UnknownKey).unwrap();
+        assert_eq!(unknown.key, "models.kimi-k2.max_token");
+        assert_eq!(unknown.line, Some(9));
+        assert!(unknown.message.contains("did you mean `max_tokens`?"));
+    }
+
+    #[test]
+    fn test_type_mismatch_and_missing_key() {
+        let source = VALID
+            .replace("default_yolo = false", "default_yolo = \"no\"")
+            .replace("timeout_seconds = 300\n", "");
+        let issues = issues_for(&source);
+
+        let mismatch = issues.iter().find(|i| i.kind == IssueKind::TypeMismatch).unwrap();
+        assert_eq!(mismatch.key, "default_yolo");
+        assert_eq!(mismatch.line, Some(4));
+        assert_eq!(mismatch.to_string(), "line 4: `default_yolo` should be a boolean, found a string");
+
+        let missing = issues.iter().find(|i| i.kind == IssueKind::MissingKey).unwrap();
+        assert_eq!(missing.key, "loop_control.timeout_seconds");
+        assert_eq!(missing.line, Some(17));
+    }
+
+    #[test]
+    fn test_invalid_provider_type() {
+        let source = VALID.replace("provider_type = \"kimi\"", "provider_type = \"openai\"");
+        let issues = issues_for(&source);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].key, "providers.kimi.provider_type");
+        assert_eq!(issues[0].line, Some(13));
+    }
+
+    #[test]
+    fn test_nested_inline_values_are_located() {
+        let source = VALID.replace("key = \"oauth/kimi-code\"", "key = 1");
+        let issues = issues_for(&source);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].key, "providers.kimi.oauth.key");
+        assert_eq!(issues[0].line, Some(15));
+
+        let source = VALID.replace("args = [\"-y\", \"server-fs\"]", "args = \"-y\"");
+        let issues = issues_for(&source);
+        assert_eq!(issues[0].key, "mcp.servers.0.args");
+        assert_eq!(issues[0].line, Some(26));
+    }
+
+    #[test]
+    fn test_missing_references() {
+        let source = VALID
+            .replace("default_model = \"kimi-k2\"", "default_model = \"kimi-k3\"")
+            .replace("default_yolo = false", "default_yolo = false\nfallback_model = [\"kimi-k2\", \"kimi-k9\"]")
+            .replace("provider = \"kimi\"", "provider = \"moonshot\"");
+        let err = Config::from_toml_str(&source).unwrap_err();
+        let issues = match err {
+            super::super::ConfigError::Invalid { issues, .. } => issues,
+            other => panic!("unexpected error: {other}"),
+        };
+
+        assert_eq!(issues.len(), 3);
+        assert_eq!(issues[0].key, "default_model");
+        assert_eq!(issues[0].line, Some(2));
+        assert!(issues[0].message.contains("(available: `kimi-k2`)"));
+        assert_eq!(issues[1].key, "fallback_model");
+        assert_eq!(issues[1].line, Some(5));
+        assert!(issues[1].message.contains("model `kimi-k9`"));
+        assert_eq!(issues[2].key, "models.kimi-k2.provider");
+        assert_eq!(issues[2].line, Some(9));
+        assert!(issues[2].message.contains("(available: `kimi`)"));
+    }
+
+    #[test]
+    fn test_quoted_keys() {
+        let segments = split_key(r#"providers."managed:kimi-code".oauth"#);
+        assert_eq!(segments, vec!["providers", "managed:kimi-code", "oauth"]);
+        assert_eq!(display_path(&segments), r#"providers."managed:kimi-code".oauth"#);
+    }
+}
@@ -1,18 +1,36 @@
 //! Context management for conversation history
 
-use crate::types::{Checkpoint, Message};
+use crate::snapshot::FileSnapshot;
+use crate::tokenizer::{self, Tokenizer};
+use crate::types::{Checkpoint, Message, Role};
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use thiserror::Error;
 use tracing::{debug, info, warn};
 
+/// Tokens a message takes beyond its text, for its role and delimiters
+pub const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+
 /// Manages conversation context including messages and checkpoints
+///
+/// Each message's tokens are counted when it is added, with the
+/// [heuristic](tokenizer::HeuristicTokenizer) until
+/// [`set_tokenizer`](Self::set_tokenizer) gives the model's tokenizer.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Context {
     messages: Vec<Message>,
     checkpoints: Vec<Checkpoint>,
-    token_count: usize,
     context_file: PathBuf,
+    /// IDs of messages kept when the context is trimmed
+    #[serde(default)]
+    pinned: BTreeSet<String>,
+    #[serde(default)]
+    next_message_id: u64,
+    #[serde(skip, default = "tokenizer::heuristic")]
+    tokenizer: Arc<dyn Tokenizer>,
 }
 
 impl Context {
@@ -21,8 +39,10 @@ impl Context {
         Self {
             messages: Vec::new(),
             checkpoints: Vec::new(),
-            token_count: 0,
             context_file,
+            pinned: BTreeSet::new(),
+            next_message_id: 0,
+            tokenizer: tokenizer::heuristic(),
         }
     }
 
@@ -34,7 +54,18 @@ impl Context {
         }
 
         let content = std::fs::read_to_string(&context_file)?;
-        let context: Context = serde_json::from_str(&content)?;
+        let mut context: Context = serde_json::from_str(&content)?;
+        // Contexts saved before messages had IDs or token counts
+        for index in 0..context.messages.len() {
+            if context.messages[index].id().is_none() {
+                let id = context.next_id();
+                context.messages[index].set_id(id);
+            }
+            if context.messages[index].tokens().is_none() {
+                let tokens = context.count_tokens(&context.messages[index]);
+                context.messages[index].set_tokens(tokens);
+            }
+        }
         Ok(context)
     }
 
@@ -49,11 +80,148 @@ impl Context {
         Ok(())
     }
 
-    /// Add a message to the context
-    pub fn add_message(&mut self, message: Message) {
+    /// Add a message to the context, assigning it an ID if it has none
+    pub fn add_message(&mut self, mut message: Message) {
+        if message.id().is_none() {
+            message.set_id(self.next_id());
+        }
+        message.set_tokens(self.count_tokens(&message));
         self.messages.push(message);
     }
 
+    /// Tokens `message` takes in a request: its text, its tool calls and
+    /// [`MESSAGE_OVERHEAD_TOKENS`]
+    pub fn count_tokens(&self, message: &Message) -> usize {
+        let tool_calls = message
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.get("tool_calls"))
+            .map_or(0, |calls| self.tokenizer.count(&calls.to_string()));
+        self.tokenizer.count(&message.content) + tool_calls + MESSAGE_OVERHEAD_TOKENS
+    }
+
+    /// Count tokens with `tokenizer` from now on, recounting the messages
+    pub fn set_tokenizer(&mut self, tokenizer: Arc<dyn Tokenizer>) {
+        self.tokenizer = tokenizer;
+        for index in 0..self.messages.len() {
+            let tokens = self.count_tokens(&self.messages[index]);
+            self.messages[index].set_tokens(tokens);
+        }
+    }
+
+    /// The tokenizer messages are counted with
+    pub fn tokenizer(&self) -> &dyn Tokenizer {
+        self.tokenizer.as_ref()
+    }
+
+    fn next_id(&mut self) -> String {
+        self.next_message_id += 1;
+        self.next_message_id.to_string()
+    }
+
+    /// Get a message by ID
+    pub fn message(&self, id: &str) -> Option<&Message> {
+        self.messages.iter().find(|m| m.id() == Some(id))
+    }
+
+    /// Pin a message so trimming the context keeps it
+    pub fn pin_message(&mut self, message_id: &str) -> Result<(), ContextError> {
+        if self.message(message_id).is_none() {
+            return Err(ContextError::UnknownMessage(message_id.to_string()));
+        }
+        self.pinned.insert(message_id.to_string());
+        Ok(())
+    }
+
+    /// Unpin a message; returns whether it was pinned
+    pub fn unpin_message(&mut self, message_id: &str) -> bool {
+        self.pinned.remove(message_id)
+    }
+
+    /// Pin a message; the same as [`pin_message`](Self::pin_message)
+    pub fn pin(&mut self, message_id: &str) -> Result<(), ContextError> {
+        self.pin_message(message_id)
+    }
+
+    /// Unpin a message; the same as [`unpin_message`](Self::unpin_message)
+    pub fn unpin(&mut self, message_id: &str) -> bool {
+        self.unpin_message(message_id)
+    }
+
+    /// Whether a message is pinned
+    pub fn is_pinned(&self, message: &Message) -> bool {
+        message.id().is_some_and(|id| self.pinned.contains(id))
+    }
+
+    /// Remove the messages in `range` that are not pinned
+    ///
+    /// An assistant message with tool calls and the tool results after it
+    /// are removed together or not at all, so calls stay paired with their
+    /// results: they are kept if any of them is pinned or they reach outside
+    /// `range`. Checkpoints are shifted as with
+    /// [`replace_messages`](Self::replace_messages). Returns the number of
+    /// messages removed.
+    pub fn remove_unpinned(&mut self, range: Range<usize>) -> usize {
+        let mut removed = 0;
+        for group in self.message_groups().into_iter().rev() {
+            if group.start < range.start || group.end > range.end {
+                continue;
+            }
+            if self.messages[group.clone()].iter().any(|m| self.is_pinned(m)) {
+                continue;
+            }
+            removed += group.len();
+            self.replace_messages(group, Vec::new());
+        }
+        removed
+    }
+
+    /// The messages in order, each assistant message with tool calls
+    /// grouped with the tool results following it
+    fn message_groups(&self) -> Vec<Range<usize>> {
+        let mut groups = Vec::new();
+        let mut start = 0;
+        while start < self.messages.len() {
+            let mut end = start + 1;
+            if !self.messages[start].tool_calls().is_empty() {
+                while end < self.messages.len() && matches!(self.messages[end].role, Role::Tool) {
+                    end += 1;
+                }
+            }
+            groups.push(start..end);
+            start = end;
+        }
+        groups
+    }
+
+    /// Pinned messages still in the context, oldest first
+    pub fn pinned_messages(&self) -> Vec<&Message> {
+        self.messages.iter().filter(|m| self.is_pinned(m)).collect()
+    }
+
+    /// Replace the messages in `range` with `replacement`
+    ///
+    /// Checkpoints after the range are shifted so they still point at the
+    /// same messages; checkpoints inside it move to the end of the replacement.
+    pub fn replace_messages(&mut self, range: Range<usize>, mut replacement: Vec<Message>) {
+        for message in &mut replacement {
+            if message.id().is_none() {
+                message.set_id(self.next_id());
+            }
+            message.set_tokens(self.count_tokens(message));
+        }
+        let inserted = replacement.len();
+        let (start, end) = (range.start, range.end);
+        self.messages.splice(range, replacement);
+        for checkpoint in &mut self.checkpoints {
+            if checkpoint.message_index >= end {
+                checkpoint.message_index = checkpoint.message_index - (end - start) + inserted;
+            } else if checkpoint.message_index > start {
+                checkpoint.message_index = start + inserted;
+            }
+        }
+    }
+
     /// Get all messages
     pub fn messages(&self) -> &[Message] {
         &self.messages
@@ -77,7 +245,7 @@ impl Context {
     /// Clear all messages
     pub fn clear_messages(&mut self) {
         self.messages.clear();
-        self.token_count = 0;
+        self.pinned.clear();
     }
 
     /// Create a checkpoint at the current message index
@@ -85,8 +253,10 @@ impl Context {
         let checkpoint = Checkpoint {
             id: uuid::Uuid::new_v4().to_string(),
             message_index: self.messages.len(),
-            token_count: self.token_count,
+            token_count: self.token_count(),
             summary,
+            created_at: Some(chrono::Utc::now()),
+            files: Vec::new(),
         };
         self.checkpoints.push(checkpoint);
         self.checkpoints.last().unwrap()
@@ -102,22 +272,20 @@ impl Context {
         self.checkpoints.last()
     }
 
-    /// Compact context to the last checkpoint
+    /// Compact context to the last checkpoint, keeping pinned messages
+    /// after it
     pub fn compact_to_last_checkpoint(&mut self) -> Option<usize> {
-        let checkpoint = self.checkpoints.last()?;
-        let removed = checkpoint.message_index;
-        self.messages.truncate(removed);
-        self.token_count = checkpoint.token_count;
+        let index = self.checkpoints.last()?.message_index;
+        let removed = self.remove_unpinned(index..self.messages.len());
         warn!("Context compacted to checkpoint, removed {} messages", removed);
         Some(removed)
     }
 
-    /// Compact context to a specific checkpoint by ID
+    /// Compact context to a specific checkpoint by ID, keeping pinned
+    /// messages after it
     pub fn compact_to_checkpoint(&mut self, checkpoint_id: &str) -> Option<usize> {
-        let checkpoint = self.checkpoints.iter().find(|c| c.id == checkpoint_id)?;
-        let removed = checkpoint.message_index;
-        self.messages.truncate(removed);
-        self.token_count = checkpoint.token_count;
+        let index = self.checkpoints.iter().find(|c| c.id == checkpoint_id)?.message_index;
+        let removed = self.remove_unpinned(index..self.messages.len());
         warn!(
             "Context compacted to checkpoint {}, removed {} messages",
             checkpoint_id, removed
@@ -125,14 +293,85 @@ impl Context {
         Some(removed)
     }
 
-    /// Get current token count
-    pub fn token_count(&self) -> usize {
-        self.token_count
+    /// Restore the context to the checkpoint at `index`
+    ///
+    /// Messages after the checkpoint are removed, along with the checkpoint
+    /// itself and every later one. Returns the removed checkpoint.
+    pub fn rewind_to_checkpoint(&mut self, index: usize) -> Option<Checkpoint> {
+        let checkpoint = self.checkpoints.get(index)?.clone();
+        let removed = self.messages.len().saturating_sub(checkpoint.message_index);
+        self.messages.truncate(checkpoint.message_index);
+        self.checkpoints.truncate(index);
+        info!("Context rewound to checkpoint {}, removed {} messages", index, removed);
+        Some(checkpoint)
+    }
+
+    /// Whether `path` still needs a snapshot on the latest checkpoint
+    pub fn needs_snapshot(&self, path: &Path) -> bool {
+        self.checkpoints
+            .last()
+            .is_some_and(|c| !c.files.iter().any(|f| f.path == path))
+    }
+
+    /// Record a file's state on the latest checkpoint
+    pub fn record_snapshot(&mut self, snapshot: FileSnapshot) {
+        if let Some(checkpoint) = self.checkpoints.last_mut() {
+            if !checkpoint.files.iter().any(|f| f.path == snapshot.path) {
+                checkpoint.files.push(snapshot);
+            }
+        }
+    }
+
+    /// Files changed since the checkpoint at `index`, as they were then
+    ///
+    /// Each file appears once, with its state from the earliest checkpoint
+    /// that recorded it.
+    pub fn snapshots_since(&self, index: usize) -> Vec<FileSnapshot> {
+        let mut snapshots: Vec<FileSnapshot> = Vec::new();
+        for checkpoint in self.checkpoints.iter().skip(index) {
+            for file in &checkpoint.files {
+                if !snapshots.iter().any(|s| s.path == file.path) {
+                    snapshots.push(file.clone());
+                }
+            }
+        }
+        snapshots
+    }
+
+    /// Compare the checkpoints at `from` and `to`
+    ///
+    /// Covers the messages added after checkpoint `from` up to checkpoint
+    /// `to`; `to == checkpoints().len()` means the end of the conversation.
+    pub fn diff_checkpoints(&self, from: usize, to: usize) -> Option<CheckpointDiff> {
+        let start = self.checkpoints.get(from)?.message_index;
+        let end = if to == self.checkpoints.len() {
+            self.messages.len()
+        } else {
+            self.checkpoints.get(to)?.message_index
+        };
+        if start > end {
+            return None;
+        }
+        Some(CheckpointDiff::from_messages(from, to, &self.messages[start..end]))
+    }
+
+    /// Messages rewinding to the checkpoint at `index` would discard
+    pub fn messages_since_checkpoint(&self, index: usize) -> Option<&[Message]> {
+        let start = self.checkpoints.get(index)?.message_index;
+        self.messages.get(start..)
     }
 
-    /// Update token count
-    pub fn set_token_count(&mut self, count: usize) {
-        self.token_count = count;
+    /// What happened between the checkpoint at `index` and the next one
+    pub fn checkpoint_changes(&self, index: usize) -> Option<CheckpointDiff> {
+        self.diff_checkpoints(index, index + 1)
+    }
+
+    /// Tokens the messages take, as counted when they were added
+    pub fn token_count(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|message| message.tokens().unwrap_or_else(|| self.count_tokens(message)))
+            .sum()
     }
 
     /// Get context file path
@@ -142,7 +381,7 @@ impl Context {
 
     /// Check if context needs compaction based on token limit
     pub fn needs_compaction(&self, max_tokens: usize) -> bool {
-        self.token_count > max_tokens
+        self.token_count() > max_tokens
     }
 
     /// Get messages since the last checkpoint
@@ -156,6 +395,88 @@ impl Context {
     }
 }
 
+/// Tools whose `path` argument names a file they read or write
+const FILE_TOOLS: &[&str] = &["ReadFile", "WriteFile", "StrReplaceFile"];
+
+/// Changes between two checkpoints
+#[derive(Debug, Clone, PartialEq)]
+pub struct CheckpointDiff {
+    pub from: usize,
+    pub to: usize,
+    /// Number of messages added
+    pub messages: usize,
+    /// Tools called, with how often, in order of first use
+    pub tools: Vec<(String, usize)>,
+    /// Files read or written by tools, in order of first use
+    pub files: Vec<String>,
+}
+
+impl CheckpointDiff {
+    fn from_messages(from: usize, to: usize, messages: &[Message]) -> Self {
+        let mut diff = Self {
+            from,
+            to,
+            messages: messages.len(),
+            tools: Vec::new(),
+            files: Vec::new(),
+        };
+        let calls = messages
+            .iter()
+            .filter_map(|m| m.metadata.as_ref()?.get("tool_calls")?.as_array())
+            .flatten();
+        for call in calls {
+            let Some(name) = call["function"]["name"].as_str() else {
+                continue;
+            };
+            match diff.tools.iter_mut().find(|(tool, _)| tool == name) {
+                Some((_, count)) => *count += 1,
+                None => diff.tools.push((name.to_string(), 1)),
+            }
+
+            if !FILE_TOOLS.contains(&name) {
+                continue;
+            }
+            let arguments = call["function"]["arguments"].as_str().unwrap_or("{}");
+            let path = serde_json::from_str::<serde_json::Value>(arguments)
+                .ok()
+                .and_then(|args| args["path"].as_str().map(str::to_string));
+            if let Some(path) = path.filter(|p| !diff.files.contains(p)) {
+                diff.files.push(path);
+            }
+        }
+        diff
+    }
+
+    /// Number of tool calls
+    pub fn tool_calls(&self) -> usize {
+        self.tools.iter().map(|(_, count)| count).sum()
+    }
+
+    /// One-line summary, e.g. `4 messages; tools: Shell x2, ReadFile; files: src/main.rs`
+    pub fn render(&self) -> String {
+        let mut parts = vec![format!(
+            "{} message{}",
+            self.messages,
+            if self.messages == 1 { "" } else { "s" }
+        )];
+        if !self.tools.is_empty() {
+            let tools: Vec<String> = self
+                .tools
+                .iter()
+                .map(|(name, count)| match count {
+                    1 => name.clone(),
+                    n => format!("{} x{}", name, n),
+                })
+                .collect();
+            parts.push(format!("tools: {}", tools.join(", ")));
+        }
+        if !self.files.is_empty() {
+            parts.push(format!("files: {}", self.files.join(", ")));
+        }
+        parts.join("; ")
+    }
+}
+
 /// Context-related errors
 #[derive(Debug, Error)]
 pub enum ContextError {
@@ -163,6 +484,8 @@ pub enum ContextError {
     Io(#[from] std::io::Error),
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
+    #[error("No message with ID {0}")]
+    UnknownMessage(String),
 }
 
 #[cfg(test)]
@@ -212,11 +535,198 @@ mod tests {
         assert_eq!(context.message_count(), 2);
     }
 
+    #[test]
+    fn test_compacting_keeps_pinned_messages() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        context.add_message(create_test_message(Role::User, "Message 1"));
+        context.create_checkpoint(None);
+        for n in 2..=4 {
+            context.add_message(create_test_message(Role::User, &format!("Message {}", n)));
+        }
+        let decision = context.messages()[2].id().unwrap().to_string();
+        context.pin_message(&decision).unwrap();
+
+        assert_eq!(context.compact_to_last_checkpoint(), Some(2));
+        let contents: Vec<_> = context.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["Message 1", "Message 3"]);
+        assert_eq!(context.remove_unpinned(0..2), 1);
+        assert_eq!(context.messages()[0].id(), Some(decision.as_str()));
+    }
+
+    #[test]
+    fn test_tool_calls_are_removed_with_their_results() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        let tool_turn = |context: &mut Context, id: &str| {
+            let call = kosong_rs::ToolCall::new(id, "ReadFile", "{}");
+            let mut metadata = std::collections::HashMap::new();
+            metadata.insert(Message::TOOL_CALLS_KEY.to_string(), serde_json::json!([call]));
+            context.add_message(Message { role: Role::Assistant, content: String::new(), metadata: Some(metadata) });
+            context.add_message(create_test_message(Role::Tool, &format!("Result {}", id)).with_tool_call_id(id));
+        };
+        context.add_message(create_test_message(Role::User, "Read the files"));
+        tool_turn(&mut context, "call_1");
+        tool_turn(&mut context, "call_2");
+        context.add_message(create_test_message(Role::Assistant, "Done."));
+
+        // A pinned result keeps its call, and a call is not split from its result
+        let result = context.messages()[2].id().unwrap().to_string();
+        context.pin(&result).unwrap();
+        assert_eq!(context.remove_unpinned(0..4), 1);
+        let contents: Vec<_> = context.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["", "Result call_1", "", "Result call_2", "Done."]);
+
+        assert!(context.unpin(&result));
+        assert_eq!(context.remove_unpinned(0..4), 4);
+        assert_eq!(context.messages()[0].content, "Done.");
+    }
+
+    #[test]
+    fn test_rewind_to_checkpoint() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        for n in 1..=3 {
+            context.create_checkpoint(Some(format!("Message {}", n)));
+            context.add_message(create_test_message(Role::User, &format!("Message {}", n)));
+            context.add_message(create_test_message(Role::Assistant, &format!("Response {}", n)));
+        }
+
+        let checkpoint = context.rewind_to_checkpoint(1).unwrap();
+        assert_eq!(checkpoint.summary.as_deref(), Some("Message 2"));
+        assert_eq!(context.message_count(), 2);
+        assert_eq!(context.checkpoints().len(), 1);
+        assert!(context.rewind_to_checkpoint(1).is_none());
+    }
+
+    #[test]
+    fn test_diff_checkpoints() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        context.create_checkpoint(Some("Fix the bug".to_string()));
+        context.add_message(create_test_message(Role::User, "Fix the bug"));
+        let tool_call = |name: &str, args: serde_json::Value| {
+            serde_json::json!({"id": "call", "type": "function", "function": {"name": name, "arguments": args.to_string()}})
+        };
+        context.add_message(Message {
+            role: Role::Assistant,
+            content: String::new(),
+            metadata: Some(std::collections::HashMap::from([(
+                "tool_calls".to_string(),
+                serde_json::json!([
+                    tool_call("ReadFile", serde_json::json!({"path": "src/lib.rs"})),
+                    tool_call("Shell", serde_json::json!({"command": "cargo test"})),
+                    tool_call("StrReplaceFile", serde_json::json!({"path": "src/lib.rs"})),
+                    tool_call("Shell", serde_json::json!({"command": "cargo test"})),
+                ]),
+            )])),
+        });
+        context.add_message(create_test_message(Role::Tool, "ok"));
+        context.add_message(create_test_message(Role::Assistant, "Fixed"));
+        context.create_checkpoint(Some("Thanks".to_string()));
+        context.add_message(create_test_message(Role::User, "Thanks"));
+
+        let diff = context.checkpoint_changes(0).unwrap();
+        assert_eq!(diff.messages, 4);
+        assert_eq!(diff.tool_calls(), 4);
+        assert_eq!(diff.files, vec!["src/lib.rs"]);
+        assert_eq!(
+            diff.render(),
+            "4 messages; tools: ReadFile, Shell x2, StrReplaceFile; files: src/lib.rs"
+        );
+
+        assert_eq!(context.checkpoint_changes(1).unwrap().render(), "1 message");
+        assert_eq!(context.diff_checkpoints(0, 2).unwrap().messages, 5);
+        assert!(context.diff_checkpoints(1, 0).is_none());
+        assert!(context.checkpoint_changes(2).is_none());
+    }
+
+    #[test]
+    fn test_snapshots_since() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        let snapshot = |path: &str, hash: &str| FileSnapshot {
+            path: PathBuf::from(path),
+            hash: Some(hash.to_string()),
+        };
+        assert!(!context.needs_snapshot(Path::new("/a")));
+
+        context.create_checkpoint(None);
+        context.record_snapshot(snapshot("/a", "a1"));
+        context.record_snapshot(snapshot("/a", "a2"));
+        assert!(!context.needs_snapshot(Path::new("/a")));
+        context.create_checkpoint(None);
+        assert!(context.needs_snapshot(Path::new("/a")));
+        context.record_snapshot(snapshot("/a", "a3"));
+        context.record_snapshot(snapshot("/b", "b3"));
+
+        assert_eq!(context.snapshots_since(0), vec![snapshot("/a", "a1"), snapshot("/b", "b3")]);
+        assert_eq!(context.snapshots_since(1), vec![snapshot("/a", "a3"), snapshot("/b", "b3")]);
+        assert!(context.snapshots_since(2).is_empty());
+    }
+
+    #[test]
+    fn test_pin_and_replace_messages() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut context = Context::new(temp.path().join("context.json"));
+        for n in 0..4 {
+            context.create_checkpoint(None);
+            context.add_message(create_test_message(Role::User, &format!("Message {}", n)));
+        }
+        let ids: Vec<String> = context.messages().iter().map(|m| m.id().unwrap().to_string()).collect();
+        assert_eq!(ids, vec!["1", "2", "3", "4"]);
+
+        context.pin_message("2").unwrap();
+        assert!(matches!(context.pin_message("9"), Err(ContextError::UnknownMessage(_))));
+        assert_eq!(context.pinned_messages()[0].content, "Message 1");
+
+        // Pins and IDs survive a reload
+        context.save().unwrap();
+        let mut context = Context::load(context.context_file().clone()).unwrap();
+        assert!(context.is_pinned(context.message("2").unwrap()));
+        context.add_message(create_test_message(Role::User, "Message 4"));
+        assert_eq!(context.last_message().unwrap().id(), Some("5"));
+
+        context.replace_messages(1..3, vec![create_test_message(Role::System, "Summary")]);
+        let indices: Vec<usize> = context.checkpoints().iter().map(|c| c.message_index).collect();
+        assert_eq!(indices, vec![0, 1, 2, 2]);
+        assert!(context.pinned_messages().is_empty());
+        assert!(context.unpin_message("2"));
+        assert!(!context.unpin_message("2"));
+    }
+
     #[test]
     fn test_needs_compaction() {
         let mut context = Context::new(PathBuf::from("/tmp/test.json"));
-        context.set_token_count(1000);
+        context.add_message(create_test_message(Role::User, &"x".repeat(3984)));
+        assert_eq!(context.token_count(), 1000);
         assert!(context.needs_compaction(500));
         assert!(!context.needs_compaction(2000));
     }
+
+    #[test]
+    fn test_messages_are_counted_when_added() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut context = Context::new(temp.path().join("context.json"));
+        context.add_message(create_test_message(Role::User, "Hello, world"));
+        assert_eq!(context.messages()[0].tokens(), Some(3 + MESSAGE_OVERHEAD_TOKENS));
+
+        let mut reply = create_test_message(Role::Assistant, "");
+        reply.metadata = Some(std::collections::HashMap::from([(
+            "tool_calls".to_string(),
+            serde_json::json!([{"id": "1", "function": {"name": "Shell", "arguments": "{}"}}]),
+        )]));
+        context.add_message(reply);
+        assert!(context.messages()[1].tokens().unwrap() > MESSAGE_OVERHEAD_TOKENS);
+        let total = context.token_count();
+
+        // Counts survive saving, and contexts saved without them are counted on load
+        context.save().unwrap();
+        assert_eq!(Context::load(context.context_file.clone()).unwrap().token_count(), total);
+        let mut json: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&context.context_file).unwrap()).unwrap();
+        json["messages"][0]["metadata"].as_object_mut().unwrap().remove(Message::TOKENS_KEY);
+        std::fs::write(&context.context_file, json.to_string()).unwrap();
+        assert_eq!(Context::load(context.context_file.clone()).unwrap().token_count(), total);
+
+        // A rewind drops the counts of the removed messages
+        context.create_checkpoint(None);
+        context.add_message(create_test_message(Role::User, "More"));
+        context.rewind_to_checkpoint(0);
+        assert_eq!(context.token_count(), total);
+    }
 }
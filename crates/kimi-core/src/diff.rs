@@ -0,0 +1,290 @@
+//! Line-based text diffing
+//!
+//! A small Myers diff over lines, with unified-diff rendering. Used to report
+//! what the agent changed in files such as AGENTS.md.
+
+/// A single line-level edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp<'a> {
+    /// Line present in both texts
+    Equal(&'a str),
+    /// Line only present in the old text
+    Delete(&'a str),
+    /// Line only present in the new text
+    Insert(&'a str),
+}
+
+/// Number of inserted and deleted lines in a diff
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStat {
+    pub insertions: usize,
+    pub deletions: usize,
+}
+
+impl DiffStat {
+    /// Count the changes in a list of edits
+    pub fn from_ops(ops: &[DiffOp<'_>]) -> Self {
+        let mut stat = Self::default();
+        for op in ops {
+            match op {
+                DiffOp::Insert(_) => stat.insertions += 1,
+                DiffOp::Delete(_) => stat.deletions += 1,
+                DiffOp::Equal(_) => {}
+            }
+        }
+        stat
+    }
+
+    /// Count the changed lines of a unified diff, e.g. one from
+    /// [`unified_diff`]
+    ///
+    /// Lines are counted by the hunk headers, so removed lines that look
+    /// like file headers, e.g. `--- title`, still count.
+    pub fn from_unified(diff: &str) -> Self {
+        let mut stat = Self::default();
+        let (mut old_left, mut new_left) = (0usize, 0usize);
+        for line in diff.lines() {
+            if old_left == 0 && new_left == 0 {
+                if let Some((old, new)) = hunk_lengths(line) {
+                    (old_left, new_left) = (old, new);
+                }
+                continue;
+            }
+            match line.chars().next() {
+                Some('-') => {
+                    stat.deletions += 1;
+                    old_left = old_left.saturating_sub(1);
+                }
+                Some('+') => {
+                    stat.insertions += 1;
+                    new_left = new_left.saturating_sub(1);
+                }
+                _ => {
+                    old_left = old_left.saturating_sub(1);
+                    new_left = new_left.saturating_sub(1);
+                }
+            }
+        }
+        stat
+    }
+
+    /// Whether the texts were identical
+    pub fn is_empty(&self) -> bool {
+        self.insertions == 0 && self.deletions == 0
+    }
+}
+
+/// Old and new line counts of a hunk header such as `@@ -1,3 +1,4 @@`
+fn hunk_lengths(line: &str) -> Option<(usize, usize)> {
+    let ranges = line.strip_prefix("@@ ")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(' ')?;
+    let length = |range: &str| match range.split_once(',') {
+        Some((_, length)) => length.parse().ok(),
+        None => Some(1),
+    };
+    Some((length(old.strip_prefix('-')?)?, length(new.strip_prefix('+')?)?))
+}
+
+/// Compute the shortest line edit script turning `old` into `new`
+pub fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<DiffOp<'a>> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let offset = max;
+    let at = |k: isize| (k + offset) as usize;
+
+    // Forward pass, keeping the furthest-reaching x per diagonal for each d
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        let mut k = -d;
+        while k <= d {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    // Walk the trace backwards to recover the edits
+    let mut ops = Vec::with_capacity(a.len().max(b.len()));
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+
+        while x > prev_x && y > prev_y {
+            ops.push(DiffOp::Equal(a[(x - 1) as usize]));
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            if x == prev_x {
+                ops.push(DiffOp::Insert(b[(y - 1) as usize]));
+            } else {
+                ops.push(DiffOp::Delete(a[(x - 1) as usize]));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+
+    ops.reverse();
+    ops
+}
+
+/// Render a unified diff between two texts
+///
+/// Returns an empty string when the texts have the same lines.
+pub fn unified_diff(old: &str, new: &str, old_label: &str, new_label: &str, context: usize) -> String {
+    let ops = diff_lines(old, new);
+    let changed: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal(_)))
+        .map(|(i, _)| i)
+        .collect();
+    if changed.is_empty() {
+        return String::new();
+    }
+
+    // Group changes whose context windows overlap into hunks
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &changed {
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(ops.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.1 => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    // Line numbers (0-based) in old/new text at the start of each op
+    let mut positions = Vec::with_capacity(ops.len());
+    let (mut old_line, mut new_line) = (0usize, 0usize);
+    for op in &ops {
+        positions.push((old_line, new_line));
+        match op {
+            DiffOp::Equal(_) => {
+                old_line += 1;
+                new_line += 1;
+            }
+            DiffOp::Delete(_) => old_line += 1,
+            DiffOp::Insert(_) => new_line += 1,
+        }
+    }
+
+    let mut out = format!("--- {}\n+++ {}\n", old_label, new_label);
+    for (start, end) in hunks {
+        let slice = &ops[start..end];
+        let old_len = slice.iter().filter(|op| !matches!(op, DiffOp::Insert(_))).count();
+        let new_len = slice.iter().filter(|op| !matches!(op, DiffOp::Delete(_))).count();
+        let (old_start, new_start) = positions[start];
+        let old_start = if old_len == 0 { old_start } else { old_start + 1 };
+        let new_start = if new_len == 0 { new_start } else { new_start + 1 };
+
+        out.push_str(&format!("@@ -{},{} +{},{} @@\n", old_start, old_len, new_start, new_len));
+        for op in slice {
+            let (prefix, line) = match op {
+                DiffOp::Equal(l) => (' ', l),
+                DiffOp::Delete(l) => ('-', l),
+                DiffOp::Insert(l) => ('+', l),
+            };
+            out.push(prefix);
+            out.push_str(line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_lines_minimal_script() {
+        let ops = diff_lines("a\nb\nc\n", "a\nx\nc\nd\n");
+        assert_eq!(
+            ops,
+            vec![
+                DiffOp::Equal("a"),
+                DiffOp::Delete("b"),
+                DiffOp::Insert("x"),
+                DiffOp::Equal("c"),
+                DiffOp::Insert("d"),
+            ]
+        );
+        assert_eq!(DiffStat::from_ops(&ops), DiffStat { insertions: 2, deletions: 1 });
+    }
+
+    #[test]
+    fn test_diff_stat_from_unified() {
+        let diff = unified_diff("title\n-- draft\nbody\n", "title\nbody\nend\n", "a/x", "b/x", 3);
+        assert_eq!(DiffStat::from_unified(&diff), DiffStat { insertions: 1, deletions: 1 });
+        let diff = unified_diff("", "one\ntwo\n", "a/x", "b/x", 3);
+        assert_eq!(DiffStat::from_unified(&diff), DiffStat { insertions: 2, deletions: 0 });
+        assert!(DiffStat::from_unified("").is_empty());
+    }
+
+    #[test]
+    fn test_diff_lines_edge_cases() {
+        assert!(diff_lines("", "").is_empty());
+        assert_eq!(diff_lines("", "a"), vec![DiffOp::Insert("a")]);
+        assert_eq!(diff_lines("a", ""), vec![DiffOp::Delete("a")]);
+        assert!(DiffStat::from_ops(&diff_lines("same\n", "same")).is_empty());
+    }
+
+    #[test]
+    fn test_unified_diff_hunks() {
+        let old: String = (1..=20).map(|i| format!("line {}\n", i)).collect();
+        let new = old.replace("line 3\n", "line three\n").replace("line 18\n", "");
+
+        let diff = unified_diff(&old, &new, "a/file", "b/file", 2);
+        let expected = "\
+--- a/file
++++ b/file
+@@ -1,5 +1,5 @@
+ line 1
+ line 2
+-line 3
++line three
+ line 4
+ line 5
+@@ -16,5 +16,4 @@
+ line 16
+ line 17
+-line 18
+ line 19
+ line 20
+";
+        assert_eq!(diff, expected);
+    }
+
+    #[test]
+    fn test_unified_diff_new_file() {
+        let diff = unified_diff("", "one\ntwo\n", "/dev/null", "b/AGENTS.md", 3);
+        assert_eq!(diff, "--- /dev/null\n+++ b/AGENTS.md\n@@ -0,0 +1,2 @@\n+one\n+two\n");
+        assert_eq!(unified_diff("same", "same", "a", "b", 3), "");
+    }
+}
@@ -0,0 +1,349 @@
+//! Append-only session event log
+//!
+//! Every wire message of a session is appended to the session's `wire.jsonl`
+//! as one JSON object per line: the versioned wire format plus a `timestamp`.
+//! When the file grows past a size limit it is rotated to `wire.1.jsonl`,
+//! `wire.2.jsonl`, ..., keeping a fixed number of old files. [`read_events`]
+//! reads them back oldest first, for postmortem debugging, transcript
+//! reconstruction and replay; [`exchanges`] rebuilds the conversation from
+//! them for `/history`.
+
+use crate::wire::WireMessage;
+use chrono::{DateTime, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tracing::{debug, warn};
+
+/// Size at which the log is rotated
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated files kept besides the current one
+pub const DEFAULT_MAX_FILES: usize = 3;
+
+/// A logged wire message
+#[derive(Debug, Clone)]
+pub struct LoggedEvent {
+    pub timestamp: DateTime<Utc>,
+    pub message: WireMessage,
+}
+
+/// A user message and the reply to it, rebuilt from the log
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exchange {
+    /// When the message was sent
+    pub timestamp: DateTime<Utc>,
+    pub prompt: String,
+    /// The streamed reply, all steps together
+    pub reply: String,
+    /// Tools called while answering, in order
+    pub tools: Vec<String>,
+}
+
+impl Exchange {
+    /// Whether the message or the reply contains `query`, ignoring case
+    pub fn matches(&self, query: &str) -> bool {
+        let query = query.to_lowercase();
+        self.prompt.to_lowercase().contains(&query) || self.reply.to_lowercase().contains(&query)
+    }
+}
+
+/// Handle to a session's event log
+///
+/// Clones share the same file, so a UI and the soul can both record to it.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    inner: Arc<Mutex<LogFile>>,
+}
+
+#[derive(Debug)]
+struct LogFile {
+    path: PathBuf,
+    file: Option<File>,
+    size: u64,
+    max_bytes: u64,
+    max_files: usize,
+}
+
+impl EventLog {
+    /// Open the log at `path` for appending, creating it if needed
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self, EventLogError> {
+        Self::with_rotation(path, DEFAULT_MAX_BYTES, DEFAULT_MAX_FILES)
+    }
+
+    /// Open the log, rotating it at `max_bytes` and keeping `max_files` old files
+    pub fn with_rotation(path: impl Into<PathBuf>, max_bytes: u64, max_files: usize) -> Result<Self, EventLogError> {
+        let mut log = LogFile {
+            path: path.into(),
+            file: None,
+            size: 0,
+            max_bytes,
+            max_files,
+        };
+        log.reopen()?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(log)),
+        })
+    }
+
+    /// Path of the current log file
+    pub fn path(&self) -> PathBuf {
+        self.lock().path.clone()
+    }
+
+    /// Append a message to the log
+    pub fn append(&self, message: &WireMessage) -> Result<(), EventLogError> {
+        let mut line = serde_json::from_str::<serde_json::Value>(&message.to_json()?)?;
+        line["timestamp"] = serde_json::Value::String(Utc::now().to_rfc3339());
+        let mut line = serde_json::to_string(&line)?;
+        line.push('\n');
+
+        let mut log = self.lock();
+        if log.size > 0 && log.size + line.len() as u64 > log.max_bytes {
+            log.rotate()?;
+        }
+        let file = match &mut log.file {
+            Some(file) => file,
+            None => return Err(EventLogError::Closed),
+        };
+        file.write_all(line.as_bytes())?;
+        log.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// Append a message, logging instead of failing
+    ///
+    /// A broken log should not interrupt the session it records.
+    pub fn record(&self, message: &WireMessage) {
+        if let Err(e) = self.append(message) {
+            warn!("Failed to record event: {}", e);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LogFile> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl LogFile {
+    fn reopen(&mut self) -> Result<(), EventLogError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        self.size = file.metadata()?.len();
+        self.file = Some(file);
+        Ok(())
+    }
+
+    /// Shift `wire.N.jsonl` to `wire.N+1.jsonl`, dropping the oldest
+    fn rotate(&mut self) -> Result<(), EventLogError> {
+        self.file = None;
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            let oldest = rotated_path(&self.path, self.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)?;
+            }
+            for n in (1..self.max_files).rev() {
+                let from = rotated_path(&self.path, n);
+                if from.exists() {
+                    std::fs::rename(&from, rotated_path(&self.path, n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+        debug!("Rotated event log {:?}", self.path);
+        self.reopen()
+    }
+}
+
+/// Path of the `n`th rotated file: `wire.jsonl` becomes `wire.<n>.jsonl`
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, n, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, n),
+    };
+    path.with_file_name(name)
+}
+
+/// Read the log at `path` and its rotated files, oldest first
+///
+/// Lines that cannot be parsed, such as one cut short by a crash, are
+/// skipped with a warning.
+pub fn read_events(path: &Path) -> Result<Vec<LoggedEvent>, EventLogError> {
+    let mut files: Vec<PathBuf> = (1..)
+        .map(|n| rotated_path(path, n))
+        .take_while(|p| p.exists())
+        .collect();
+    files.reverse();
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+
+    let mut events = Vec::new();
+    for file in files {
+        for (number, line) in std::fs::read_to_string(&file)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_line(line) {
+                Ok(event) => events.push(event),
+                Err(e) => warn!("Skipping line {} of {:?}: {}", number + 1, file, e),
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// The exchanges in logged events, oldest first
+///
+/// Each `TurnBegin` starts one; events before the first are skipped, as are
+/// those of sub-agents.
+pub fn exchanges(events: &[LoggedEvent]) -> Vec<Exchange> {
+    let mut exchanges: Vec<Exchange> = Vec::new();
+    for event in events {
+        if let WireMessage::TurnBegin { user_input } = &event.message {
+            exchanges.push(Exchange {
+                timestamp: event.timestamp,
+                prompt: user_input.text.clone(),
+                reply: String::new(),
+                tools: Vec::new(),
+            });
+            continue;
+        }
+        let Some(exchange) = exchanges.last_mut() else {
+            continue;
+        };
+        match &event.message {
+            WireMessage::TextPart { text } => exchange.reply.push_str(text),
+            WireMessage::ToolBegin { name, .. } => exchange.tools.push(name.clone()),
+            _ => {}
+        }
+    }
+    exchanges
+}
+
+fn parse_line(line: &str) -> Result<LoggedEvent, EventLogError> {
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    let timestamp = value
+        .get("timestamp")
+        .and_then(|t| t.as_str())
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+        .ok_or(EventLogError::MissingTimestamp)?;
+    Ok(LoggedEvent {
+        timestamp,
+        message: WireMessage::from_json(line)?,
+    })
+}
+
+/// Event log errors
+#[derive(Debug, Error)]
+pub enum EventLogError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Event has no timestamp")]
+    MissingTimestamp,
+    #[error("Event log is closed")]
+    Closed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::UserInput;
+
+    fn text(text: &str) -> WireMessage {
+        WireMessage::TextPart { text: text.to_string() }
+    }
+
+    fn texts(events: &[LoggedEvent]) -> Vec<String> {
+        events
+            .iter()
+            .map(|e| match &e.message {
+                WireMessage::TextPart { text } => text.clone(),
+                other => format!("{:?}", other),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_append_and_read() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("sessions/s1/wire.jsonl");
+        let log = EventLog::open(&path).unwrap();
+        log.append(&text("hello")).unwrap();
+        log.clone().append(&WireMessage::TurnEnd).unwrap();
+
+        // Reopening appends rather than truncating
+        EventLog::open(&path).unwrap().append(&text("again")).unwrap();
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"{\"version\":1,\"type\":\"TextPa")
+            .unwrap();
+
+        let events = read_events(&path).unwrap();
+        assert_eq!(texts(&events), vec!["hello", "TurnEnd", "again"]);
+        assert!(events[0].timestamp <= events[2].timestamp);
+        let first = std::fs::read_to_string(&path).unwrap().lines().next().unwrap().to_string();
+        let first: serde_json::Value = serde_json::from_str(&first).unwrap();
+        assert_eq!(first["version"], 1);
+        assert_eq!(first["type"], "TextPart");
+    }
+
+    #[test]
+    fn test_exchanges() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("wire.jsonl");
+        let log = EventLog::open(&path).unwrap();
+        let turn = |text: &str| WireMessage::TurnBegin { user_input: UserInput { text: text.to_string(), attachments: Vec::new() } };
+        log.append(&text("before any turn")).unwrap();
+        log.append(&turn("List the files")).unwrap();
+        log.append(&text("Let me ")).unwrap();
+        log.append(&WireMessage::ToolBegin { name: "Shell".to_string(), arguments: "{}".to_string() }).unwrap();
+        log.append(&text("check. Two files.")).unwrap();
+        log.append(&WireMessage::TurnEnd).unwrap();
+        log.append(&turn("Thanks")).unwrap();
+
+        let exchanges = exchanges(&read_events(&path).unwrap());
+        assert_eq!(exchanges.len(), 2);
+        assert_eq!(exchanges[0].prompt, "List the files");
+        assert_eq!(exchanges[0].reply, "Let me check. Two files.");
+        assert_eq!(exchanges[0].tools, ["Shell"]);
+        assert!(exchanges[0].matches("two FILES"));
+        assert!(!exchanges[0].matches("thanks"));
+        assert_eq!(exchanges[1].reply, "");
+    }
+
+    #[test]
+    fn test_rotation() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("wire.jsonl");
+        let log = EventLog::with_rotation(&path, 300, 2).unwrap();
+        for n in 0..8 {
+            log.append(&text(&format!("message {}", n))).unwrap();
+        }
+
+        assert_eq!(rotated_path(&path, 1), temp.path().join("wire.1.jsonl"));
+        assert!(rotated_path(&path, 2).exists());
+        assert!(!rotated_path(&path, 3).exists());
+        assert!(std::fs::metadata(&path).unwrap().len() <= 300);
+
+        // The oldest messages were dropped with the oldest file
+        let events = texts(&read_events(&path).unwrap());
+        assert!(events.len() < 8);
+        assert_eq!(events.last().unwrap(), "message 7");
+        let mut sorted = events.clone();
+        sorted.sort();
+        assert_eq!(events, sorted);
+    }
+}
@@ -0,0 +1,204 @@
+//! Reading changes from and committing to a git repository
+//!
+//! Used by `/commit`, which runs the `git` command line in the working
+//! directory rather than linking a git library, so the user's own hooks,
+//! signing setup and identity apply to the commits it makes.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use thiserror::Error;
+
+/// Hash of git's empty tree, the base to diff a root commit against
+const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/// Number of recent commit subjects gathered as examples of the log's style
+const RECENT_SUBJECTS: usize = 10;
+
+/// The changes a commit would record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changes {
+    /// Whether the changes are staged; when nothing is staged, the commit
+    /// takes every change to tracked files instead
+    pub staged: bool,
+    /// `git diff --stat` summary of the changes
+    pub stat: String,
+    /// The changes as a unified diff
+    pub diff: String,
+    /// Subjects of the most recent commits, newest first
+    pub recent_subjects: Vec<String>,
+}
+
+/// How to make a commit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommitOptions {
+    /// Replace the last commit instead of adding one (`--amend`)
+    pub amend: bool,
+    /// Add a `Signed-off-by` trailer (`--signoff`)
+    pub signoff: bool,
+    /// Commit every change to tracked files, not just staged ones (`--all`)
+    pub all: bool,
+}
+
+/// The changes committing in `work_dir` would record
+///
+/// Staged changes are preferred; when there are none, the changes to
+/// tracked files are returned with `staged` unset. With `amend`, the
+/// changes are those of the last commit together with the staged ones,
+/// as the amended commit will hold both.
+pub fn changes(work_dir: &Path, amend: bool) -> Result<Changes, GitError> {
+    git(work_dir, &["rev-parse", "--git-dir"]).map_err(|_| GitError::NotARepository)?;
+
+    let mut args = vec!["diff"];
+    let staged = if amend {
+        let base = match git(work_dir, &["rev-parse", "--verify", "--quiet", "HEAD~1"]) {
+            Ok(_) => "HEAD~1",
+            Err(_) => EMPTY_TREE,
+        };
+        args.extend(["--cached", base]);
+        true
+    } else if git(work_dir, &["diff", "--cached", "--quiet"]).is_err() {
+        // `--quiet` fails when there are differences
+        args.push("--cached");
+        true
+    } else if has_head(work_dir) {
+        args.push("HEAD");
+        false
+    } else {
+        return Err(GitError::NothingToCommit);
+    };
+    let diff = git(work_dir, &args)?;
+    if diff.trim().is_empty() {
+        return Err(GitError::NothingToCommit);
+    }
+    args.insert(1, "--stat");
+    let stat = git(work_dir, &args)?;
+
+    let recent_subjects = match has_head(work_dir) {
+        true => git(work_dir, &["log", "-n", &RECENT_SUBJECTS.to_string(), "--format=%s"])?
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        false => Vec::new(),
+    };
+
+    Ok(Changes {
+        staged,
+        stat: stat.trim_end().to_string(),
+        diff,
+        recent_subjects,
+    })
+}
+
+/// Commit in `work_dir` with `message`, returning git's summary of the commit
+pub fn commit(work_dir: &Path, message: &str, options: &CommitOptions) -> Result<String, GitError> {
+    let mut args = vec!["commit", "--file=-"];
+    if options.amend {
+        args.push("--amend");
+    }
+    if options.signoff {
+        args.push("--signoff");
+    }
+    if options.all {
+        args.push("--all");
+    }
+    let mut child = Command::new("git")
+        .args(&args)
+        .current_dir(work_dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(message.as_bytes())?;
+    }
+    let output = check(child.wait_with_output()?)?;
+    Ok(output.trim_end().to_string())
+}
+
+/// Whether the repository has any commits yet
+fn has_head(work_dir: &Path) -> bool {
+    git(work_dir, &["rev-parse", "--verify", "--quiet", "HEAD"]).is_ok()
+}
+
+/// Run git in `work_dir` and return its output
+fn git(work_dir: &Path, args: &[&str]) -> Result<String, GitError> {
+    let output = Command::new("git").args(args).current_dir(work_dir).output()?;
+    check(output)
+}
+
+fn check(output: Output) -> Result<String, GitError> {
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let message = if stderr.trim().is_empty() { stdout } else { stderr };
+    Err(GitError::Failed(message.trim().to_string()))
+}
+
+/// Errors running git
+#[derive(Debug, Error)]
+pub enum GitError {
+    #[error("Failed to run git: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("git failed: {0}")]
+    Failed(String),
+    #[error("Not inside a git repository")]
+    NotARepository,
+    #[error("Nothing to commit")]
+    NothingToCommit,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn setup(dir: &Path) {
+        for args in [
+            &["init", "--quiet"][..],
+            &["config", "user.name", "Test"],
+            &["config", "user.email", "test@example.com"],
+            &["config", "commit.gpgsign", "false"],
+        ] {
+            git(dir, args).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_changes_and_commit() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        assert!(matches!(changes(dir, false), Err(GitError::NotARepository)));
+
+        setup(dir);
+        assert!(matches!(changes(dir, false), Err(GitError::NothingToCommit)));
+
+        std::fs::write(dir.join("a.txt"), "one\n").unwrap();
+        git(dir, &["add", "a.txt"]).unwrap();
+        let staged = changes(dir, false).unwrap();
+        assert!(staged.staged);
+        assert!(staged.diff.contains("+one"));
+        assert!(staged.stat.contains("a.txt"));
+        assert!(staged.recent_subjects.is_empty());
+        commit(dir, "feat: add a\n", &CommitOptions::default()).unwrap();
+
+        // Nothing staged: the tracked changes are committed with --all
+        std::fs::write(dir.join("a.txt"), "two\n").unwrap();
+        let tracked = changes(dir, false).unwrap();
+        assert!(!tracked.staged);
+        assert!(tracked.diff.contains("+two"));
+        assert_eq!(tracked.recent_subjects, ["feat: add a"]);
+        let options = CommitOptions { all: true, signoff: true, ..Default::default() };
+        commit(dir, "fix: change a", &options).unwrap();
+        let message = git(dir, &["log", "-n", "1", "--format=%B"]).unwrap();
+        assert!(message.starts_with("fix: change a\n\nSigned-off-by: Test <test@example.com>"));
+
+        // Amending diffs against the parent of the last commit
+        let amend = changes(dir, true).unwrap();
+        assert!(amend.diff.contains("-one") && amend.diff.contains("+two"));
+        commit(dir, "fix: rewrite a", &CommitOptions { amend: true, ..Default::default() }).unwrap();
+        let subjects = git(dir, &["log", "--format=%s"]).unwrap();
+        assert_eq!(subjects.lines().collect::<Vec<_>>(), ["fix: rewrite a", "feat: add a"]);
+    }
+}
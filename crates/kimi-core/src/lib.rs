@@ -1,30 +1,68 @@
 //! kimi-core - Core types and wire protocol for the agent system
 
 pub mod approval;
+pub mod attachment;
 pub mod auth;
 pub mod config;
 pub mod context;
+pub mod diff;
+pub mod event_log;
+pub mod git;
 pub mod llm;
+pub mod mcp;
+pub mod memory;
 pub mod prompts;
+pub mod rag;
+pub mod redact;
 pub mod session;
 pub mod skill;
+pub mod snapshot;
 pub mod soul;
+pub mod stats;
+pub mod telemetry;
+pub mod tokenizer;
+pub mod transcript;
 pub mod types;
+pub mod update;
 pub mod wire;
+pub mod workspace;
 
-pub use approval::{Approval, ApprovalError};
-pub use config::{Config, ConfigError, LlmProvider, ProviderType};
-pub use context::{Context, ContextError};
-pub use session::{Session, SessionError};
+pub use approval::{Approval, ApprovalError, ToolRule};
+pub use attachment::AttachmentError;
+pub use config::{AgentPersona, ApprovalConfig, CacheConfig, CompactionConfig, Config, ConfigDocument, ConfigError, LlmProvider, NotificationsConfig, PromptsConfig, ProviderType, RagConfig, TelemetryConfig, ThemeConfig, UpdatesConfig, WebAuthProfile, WebConfig};
+pub use context::{CheckpointDiff, Context, ContextError};
+pub use event_log::{EventLog, EventLogError, Exchange, LoggedEvent};
+pub use mcp::{McpClient, McpError, McpTool, McpToolInfo};
+pub use memory::ProjectMemory;
+pub use prompts::{PromptSection, SystemPromptBuilder};
+pub use rag::{RagError, Retriever, SearchHit, WorkspaceIndex};
+pub use redact::Redactor;
+pub use session::{Session, SessionError, SessionManager, SessionSummary};
+pub use snapshot::{FileSnapshot, SnapshotError, SnapshotStore};
+pub use stats::{ModelPricing, ModelUsage, StatsError, UsageStats, UsageStore};
+pub use tokenizer::Tokenizer;
+pub use transcript::Transcript;
 pub use types::*;
-pub use wire::WireMessage;
+pub use update::{UpdateCheck, UpdateChecker, UpdateError};
+pub use wire::{OutputStream, WireMessage, WIRE_PROTOCOL_VERSION};
+pub use workspace::{Workspace, WorkspaceError};
 
 // Re-export soul types for convenience
 pub use soul::{
     kimisoul::{KimiSoul, SoulError, TurnOutcome, StepOutcome},
-    agent::{Agent, AgentState, AgentConfig, Runtime, RuntimeStats, Task, TaskStatus, LaborMarket, MarketTask},
-    compaction::{Compaction, SimpleCompaction, CompactionError, AggressiveCompaction, SmartCompaction},
+    agent::{Agent, AgentState, AgentConfig, Runtime, RuntimeStats, SchedulerConfig, SchedulerHandle, Task, TaskExecutor, TaskStatus, LaborMarket, MarketTask},
+    compaction::{Compaction, SimpleCompaction, CompactionError, CompactionStrategy, AggressiveCompaction, SmartCompaction},
+    custom_commands::{CommandScope, CustomCommand},
+    delegation::{DelegatedTask, SoulTaskExecutor},
     denwarenji::{DenwaRenji, DMail},
+    flow_runner::{FlowReport, FlowRunner},
+    init::InitReport,
+    persona::AgentFactory,
+    rewind::{checkpoint_label, Rewind},
     slash::{SlashCommand, SlashCommandRegistry, parse_slash_command},
-    toolset::{KimiToolset, Tool, ToolError, ToolResult, McpServerInfo, ToolCall, ToolCallResult, SimpleTool},
+    subagent::{SpawnAgentTool, SubagentResult, SubagentSpec},
+    tool_profiles::ToolProfiles,
+    toolset::{KimiToolset, OutputSink, Tool, ToolError, ToolResult, McpServerInfo, ToolCall, ToolCallResult, SimpleTool},
+    undo::{FileEdit, Undo, UndoEditTool},
+    watch::{FileChange, WatchPathTool},
 };
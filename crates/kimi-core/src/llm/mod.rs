@@ -1,12 +1,28 @@
 //! LLM provider factory and integration
 //!
 //! This module provides a factory function to create LLM providers from configuration,
-//! with support for OAuth token resolution.
+//! with support for OAuth tokens that are refreshed while the provider is in use.
 
-use kosong_rs::{ChatProvider, KimiProvider};
-use crate::auth::{load_token, oauth::refresh_token, storage::save_token, OAuthRef};
-use crate::config::{Config, ProviderType};
+use kosong_rs::chat_provider::gemini::{HarmBlockThreshold, HarmCategory, SafetySetting};
+use kosong_rs::chat_provider::ChatOptions;
+use kosong_rs::{
+    CachingProvider, ChatProvider, EmbeddingProvider, FailoverHandler, FallbackProvider, GeminiProvider, KimiProvider, ModelCapability, OllamaProvider, OpenAiEmbeddings,
+    OpenAiProvider, ResponseCache, StaticToken, TokenSource,
+};
+use crate::auth::{
+    load_token, oauth::refresh_token, storage::{get_share_dir, save_token}, OAuthRef, OAuthTokenSource, PlatformRegistry,
+    SecretsManager,
+};
+use crate::config::{Config, LlmProvider, ProviderType, RagConfig};
+use crate::types::LlmModel;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use secrecy::ExposeSecret;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Azure OpenAI API version used when a provider sets none
+const AZURE_API_VERSION: &str = "2024-10-21";
 
 /// Error type for LLM operations
 #[derive(Debug, thiserror::Error)]
@@ -51,40 +67,10 @@ pub async fn create_provider(
     let provider_config = config.providers.get(&model.provider)
         .ok_or(LlmError::NoProvider)?;
     
-    // Resolve API key (from OAuth or direct)
-    let api_key = if let Some(oauth_ref) = &provider_config.oauth {
-        let mut token = load_token(oauth_ref)
-            .ok_or(LlmError::MissingToken)?;
-        
-        // Check if token needs refresh (expired or about to expire)
-        if token.is_expired() || token.needs_refresh() {
-            tracing::info!("OAuth token expired or needs refresh, refreshing...");
-            token = refresh_token(&token.refresh_token).await
-                .map_err(|e| LlmError::ProviderError(format!("Failed to refresh token: {}", e)))?;
-            // Save the refreshed token
-            save_token(oauth_ref, &token);
-        }
-        
-        token.access_token
-    } else {
-        provider_config.api_key.expose_secret().to_string()
-    };
+    // Resolve the token source (OAuth or direct API key)
+    let token = token_source(config, provider_config).await?;
     
-    // Create provider based on type
-    match provider_config.provider_type {
-        ProviderType::Kimi => {
-            let provider = KimiProvider::with_base_url(
-                api_key,
-                model.name.clone(),  // model name
-                provider_config.base_url.clone(),
-            ).map_err(|e| LlmError::ProviderError(e.to_string()))?;
-            
-            Ok(Box::new(provider))
-        }
-        _ => Err(LlmError::UnsupportedProvider(
-            format!("{:?}", provider_config.provider_type)
-        )),
-    }
+    build_provider(model, provider_config, token).map(|provider| with_cache(config, provider))
 }
 
 /// Create a chat provider for a specific model
@@ -108,34 +94,84 @@ pub async fn create_provider_for_model(
     let provider_config = config.providers.get(&model.provider)
         .ok_or(LlmError::NoProvider)?;
     
-    // Resolve API key (from OAuth or direct)
-    let api_key = if let Some(oauth_ref) = &provider_config.oauth {
-        let mut token = load_token(oauth_ref)
-            .ok_or(LlmError::MissingToken)?;
-        
-        // Check if token needs refresh (expired or about to expire)
-        if token.is_expired() || token.needs_refresh() {
-            tracing::info!("OAuth token expired or needs refresh, refreshing...");
-            token = refresh_token(&token.refresh_token).await
-                .map_err(|e| LlmError::ProviderError(format!("Failed to refresh token: {}", e)))?;
-            // Save the refreshed token
-            save_token(oauth_ref, &token);
-        }
-        
-        token.access_token
-    } else {
-        provider_config.api_key.expose_secret().to_string()
-    };
+    // Resolve the token source (OAuth or direct API key)
+    let token = token_source(config, provider_config).await?;
     
-    // Create provider based on type
+    build_provider(model, provider_config, token).map(|provider| with_cache(config, provider))
+}
+
+/// Create the chat provider for `model` on `provider_config`, sending the
+/// tokens from `token`
+fn build_provider(
+    model: &LlmModel,
+    provider_config: &LlmProvider,
+    token: Arc<dyn TokenSource>,
+) -> Result<Box<dyn ChatProvider>, LlmError> {
     match provider_config.provider_type {
         ProviderType::Kimi => {
-            let provider = KimiProvider::with_base_url(
-                api_key,
+            let mut provider = KimiProvider::with_base_url(
+                String::new(),
                 model.name.clone(),  // model name
                 provider_config.base_url.clone(),
-            ).map_err(|e| LlmError::ProviderError(e.to_string()))?;
-            
+            ).map_err(|e| LlmError::ProviderError(e.to_string()))?
+            .with_token_source(token)
+            .with_headers(custom_headers(provider_config)?);
+            if let Some(proxy) = &provider_config.proxy {
+                provider = provider.with_proxy(proxy).map_err(|e| LlmError::ProviderError(e.to_string()))?;
+            }
+
+            Ok(Box::new(provider))
+        }
+        ProviderType::OpenAiLegacy | ProviderType::AzureOpenAi => {
+            let mut provider = OpenAiProvider::with_base_url(
+                String::new(),
+                model.name.clone(),
+                provider_config.base_url.clone(),
+            ).map_err(|e| LlmError::ProviderError(e.to_string()))?
+            .with_token_source(token)
+            .with_headers(custom_headers(provider_config)?);
+            if matches!(provider_config.provider_type, ProviderType::AzureOpenAi) {
+                let api_version = provider_config.api_version.as_deref().unwrap_or(AZURE_API_VERSION);
+                provider = provider.with_azure_deployment(&provider_config.base_url, &model.name, api_version);
+            }
+            if let Some(proxy) = &provider_config.proxy {
+                provider = provider.with_proxy(proxy).map_err(|e| LlmError::ProviderError(e.to_string()))?;
+            }
+            // A model's max_tokens is its context window, not a limit on
+            // the response, so only the temperature is passed on
+            let mut options = ChatOptions::new();
+            if let Some(temperature) = model.temperature {
+                options = options.with_temperature(temperature as f32);
+            }
+            provider.set_options(options);
+
+            Ok(Box::new(provider))
+        }
+        ProviderType::Gemini => {
+            let provider = GeminiProvider::with_base_url(
+                String::new(),
+                model.name.clone(),
+                provider_config.base_url.clone(),
+            ).map_err(|e| LlmError::ProviderError(e.to_string()))?
+            .with_token_source(token)
+            .with_safety_settings(safety_settings(provider_config)?);
+
+            Ok(Box::new(provider))
+        }
+        ProviderType::Ollama => {
+            let mut provider = OllamaProvider::with_base_url(
+                model.name.clone(),
+                provider_config.base_url.clone(),
+            ).map_err(|e| LlmError::ProviderError(e.to_string()))?
+            .with_token_source(token);
+            // Ollama loads models with a small context unless told otherwise
+            if let Some(max_tokens) = model.max_tokens {
+                provider = provider.with_num_ctx(u32::try_from(max_tokens).unwrap_or(u32::MAX));
+            }
+            if let Some(keep_alive) = &provider_config.keep_alive {
+                provider = provider.with_keep_alive(keep_alive.clone());
+            }
+
             Ok(Box::new(provider))
         }
         _ => Err(LlmError::UnsupportedProvider(
@@ -144,6 +180,173 @@ pub async fn create_provider_for_model(
     }
 }
 
+/// `provider`, answering repeated requests from the response cache when
+/// `[cache]` enables it
+fn with_cache(config: &Config, provider: Box<dyn ChatProvider>) -> Box<dyn ChatProvider> {
+    if !config.cache.is_enabled() {
+        return provider;
+    }
+    let mut cache = ResponseCache::new(response_cache_dir());
+    if let Some(seconds) = config.cache.ttl_seconds {
+        cache = cache.with_ttl(Duration::from_secs(seconds));
+    }
+    if let Some(megabytes) = config.cache.max_size_mb {
+        cache = cache.with_max_bytes(megabytes.saturating_mul(1024 * 1024));
+    }
+    Box::new(CachingProvider::new(provider, cache))
+}
+
+/// Directory of cached model responses, under the kimi data directory
+pub fn response_cache_dir() -> PathBuf {
+    get_share_dir().join("cache").join("responses")
+}
+
+/// The provider's `custom_headers`, as request headers
+fn custom_headers(provider_config: &LlmProvider) -> Result<HeaderMap, LlmError> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in provider_config.custom_headers.iter().flatten() {
+        let header = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|e| LlmError::ProviderError(format!("Invalid custom header name '{}': {}", name, e)))?;
+        let value = HeaderValue::from_str(value)
+            .map_err(|e| LlmError::ProviderError(format!("Invalid value for custom header '{}': {}", name, e)))?;
+        headers.insert(header, value);
+    }
+    Ok(headers)
+}
+
+/// The provider's `safety_settings`, parsed
+fn safety_settings(provider_config: &LlmProvider) -> Result<Vec<SafetySetting>, LlmError> {
+    let mut settings = provider_config
+        .safety_settings
+        .iter()
+        .flatten()
+        .map(|(category, threshold)| {
+            let category = category.parse::<HarmCategory>().map_err(|e| LlmError::ProviderError(e.to_string()))?;
+            let threshold = threshold.parse::<HarmBlockThreshold>().map_err(|e| LlmError::ProviderError(e.to_string()))?;
+            Ok(SafetySetting::new(category, threshold))
+        })
+        .collect::<Result<Vec<_>, LlmError>>()?;
+    // The config's map has no order of its own
+    settings.sort_by_key(|setting| setting.category.as_str());
+    Ok(settings)
+}
+
+/// Capabilities of a configured model, as its provider infers them from
+/// the model name
+///
+/// Empty when the model or its provider is not configured.
+pub fn model_capabilities(config: &Config, model_name: &str) -> Vec<ModelCapability> {
+    let Some(model) = config.models.get(model_name) else {
+        return Vec::new();
+    };
+    match config.providers.get(&model.provider).map(|p| &p.provider_type) {
+        Some(ProviderType::Kimi) => KimiProvider::infer_capabilities(&model.name),
+        Some(ProviderType::Gemini) => GeminiProvider::infer_capabilities(&model.name),
+        Some(ProviderType::Ollama) => OllamaProvider::infer_capabilities(&model.name),
+        Some(_) => OpenAiProvider::infer_capabilities(&model.name),
+        None => Vec::new(),
+    }
+}
+
+/// Create the provider that failing steps are retried on
+///
+/// Returns `None` unless `fallback_model` is set. With several models, the
+/// provider fails over along them in order, calling `on_failover` with each
+/// hop.
+pub async fn create_fallback_provider(
+    config: &Config,
+    on_failover: FailoverHandler,
+) -> Result<Option<Arc<dyn ChatProvider>>, LlmError> {
+    let mut providers = Vec::with_capacity(config.fallback_model.len());
+    for model in &config.fallback_model {
+        providers.push(create_provider_for_model(config, model).await?);
+    }
+    if providers.len() < 2 {
+        return Ok(providers.pop().map(Arc::from));
+    }
+    let chain = FallbackProvider::new(providers)
+        .map_err(|e| LlmError::ProviderError(e.to_string()))?
+        .with_failover_handler(move |failover| on_failover(failover));
+    Ok(Some(Arc::new(chain)))
+}
+
+/// Create the embedding provider used for workspace retrieval
+///
+/// Returns `None` unless `[rag]` names a provider. The provider's
+/// OpenAI-compatible `/embeddings` endpoint is used with its API key.
+pub async fn create_embedding_provider(
+    config: &Config,
+) -> Result<Option<Arc<dyn EmbeddingProvider>>, LlmError> {
+    let Some(name) = &config.rag.provider else {
+        return Ok(None);
+    };
+    let provider_config = config.providers.get(name)
+        .ok_or(LlmError::NoProvider)?;
+    let api_key = resolve_api_key(config, provider_config).await?;
+    let model = config.rag.model.clone().unwrap_or_else(|| RagConfig::DEFAULT_MODEL.to_string());
+    let provider = OpenAiEmbeddings::with_base_url(api_key, model, provider_config.base_url.clone())
+        .map_err(|e| LlmError::ProviderError(e.to_string()))?;
+    Ok(Some(Arc::new(provider)))
+}
+
+/// The source of a chat provider's bearer token
+///
+/// OAuth providers get an [`OAuthTokenSource`] that reads the stored token
+/// per request, refreshing it when it expires soon or is rejected; the
+/// [`OAuthManager`](crate::auth::OAuthManager)'s background task refreshes
+/// it too. Others use their API key.
+async fn token_source(config: &Config, provider_config: &LlmProvider) -> Result<Arc<dyn TokenSource>, LlmError> {
+    let Some(oauth_ref) = &provider_config.oauth else {
+        let api_key = provider_config
+            .resolve_api_key(&SecretsManager::new())
+            .map_err(|e| LlmError::ProviderError(e.to_string()))?;
+        return Ok(Arc::new(StaticToken::new(api_key.expose_secret())));
+    };
+    if load_token(oauth_ref).is_none() {
+        return Err(LlmError::MissingToken);
+    }
+    let registry = PlatformRegistry::from_config(config);
+    let oauth = registry.oauth_for(oauth_ref).ok_or_else(|| {
+        LlmError::ProviderError(format!("No OAuth platform for {}", oauth_ref.key))
+    })?;
+
+    let source = Arc::new(OAuthTokenSource::new(oauth_ref.clone(), oauth.clone()));
+    source.access_token().await
+        .map_err(|e| LlmError::ProviderError(format!("Failed to refresh token: {}", e)))?;
+    Ok(source)
+}
+
+/// Resolve a provider's API key, from its OAuth token if it has one and
+/// otherwise from the config or the secrets store
+///
+/// An expired or expiring token is refreshed with its platform's token
+/// endpoint and saved first.
+async fn resolve_api_key(config: &Config, provider_config: &LlmProvider) -> Result<String, LlmError> {
+    let Some(oauth_ref) = &provider_config.oauth else {
+        let api_key = provider_config
+            .resolve_api_key(&SecretsManager::new())
+            .map_err(|e| LlmError::ProviderError(e.to_string()))?;
+        return Ok(api_key.expose_secret().to_string());
+    };
+    let mut token = load_token(oauth_ref)
+        .ok_or(LlmError::MissingToken)?;
+
+    // Check if token needs refresh (expired or about to expire)
+    if token.is_expired() || token.needs_refresh() {
+        tracing::info!("OAuth token expired or needs refresh, refreshing...");
+        let registry = PlatformRegistry::from_config(config);
+        let oauth = registry.oauth_for(oauth_ref).ok_or_else(|| {
+            LlmError::ProviderError(format!("No OAuth platform for {}", oauth_ref.key))
+        })?;
+        token = refresh_token(oauth, &token.refresh_token).await
+            .map_err(|e| LlmError::ProviderError(format!("Failed to refresh token: {}", e)))?;
+        // Save the refreshed token
+        save_token(oauth_ref, &token);
+    }
+
+    Ok(token.access_token)
+}
+
 /// Get the OAuth reference for a provider if configured
 ///
 /// # Arguments
@@ -163,8 +366,6 @@ pub fn get_oauth_ref<'a>(config: &'a Config, provider_name: &str) -> Option<&'a
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::LlmProvider;
-    use crate::types::LlmModel;
     use secrecy::SecretString;
     use std::collections::HashMap;
 
@@ -177,6 +378,7 @@ mod tests {
                 provider: "test-provider".to_string(),
                 max_tokens: Some(128000),
                 temperature: None,
+                pricing: None,
             },
         );
 
@@ -189,7 +391,12 @@ mod tests {
                 api_key: SecretString::new("test-api-key".to_string()),
                 env: None,
                 custom_headers: None,
+                proxy: None,
+                api_version: None,
                 oauth: None,
+                api_key_ref: None,
+                safety_settings: None,
+                keep_alive: None,
             },
         );
 
@@ -197,11 +404,27 @@ mod tests {
             default_model: "test-model".to_string(),
             default_thinking: false,
             default_yolo: false,
+            default_permission_mode: None,
+            fallback_model: Vec::new(),
+            transcript_file: None,
             models,
             providers,
             loop_control: crate::types::LoopControl::default(),
             services: crate::types::Services::default(),
             mcp: crate::types::McpConfig::default(),
+            agents: HashMap::new(),
+            tool_profiles: HashMap::new(),
+            telemetry: Default::default(),
+            prompts: Default::default(),
+            platforms: HashMap::new(),
+            rag: Default::default(),
+            theme: Default::default(),
+            notifications: Default::default(),
+            updates: Default::default(),
+            cache: Default::default(),
+            compaction: Default::default(),
+            approval: Default::default(),
+            web: Default::default(),
             is_from_default_location: false,
         }
     }
@@ -215,6 +438,137 @@ mod tests {
         assert!(oauth_ref.is_none());
     }
 
+    #[tokio::test]
+    async fn test_create_fallback_provider() {
+        let mut config = create_test_config();
+        let ignore: FailoverHandler = Arc::new(|_| {});
+        assert!(create_fallback_provider(&config, ignore.clone()).await.unwrap().is_none());
+
+        config.fallback_model = vec!["test-model".to_string()];
+        let provider = create_fallback_provider(&config, ignore.clone()).await.unwrap().unwrap();
+        assert_eq!(provider.model_name(), "kimi-test-model");
+
+        config.fallback_model = vec!["test-model".to_string(), "missing".to_string()];
+        assert!(create_fallback_provider(&config, ignore.clone()).await.is_err());
+        config.fallback_model[1] = "test-model".to_string();
+        let chain = create_fallback_provider(&config, ignore).await.unwrap().unwrap();
+        assert_eq!(chain.model_name(), "kimi-test-model");
+    }
+
+    #[tokio::test]
+    async fn test_create_embedding_provider() {
+        let mut config = create_test_config();
+        assert!(create_embedding_provider(&config).await.unwrap().is_none());
+
+        config.rag.provider = Some("test-provider".to_string());
+        let provider = create_embedding_provider(&config).await.unwrap().unwrap();
+        assert_eq!(provider.model_name(), RagConfig::DEFAULT_MODEL);
+
+        config.rag.provider = Some("missing".to_string());
+        assert!(matches!(create_embedding_provider(&config).await, Err(LlmError::NoProvider)));
+    }
+
+    #[tokio::test]
+    async fn test_create_gemini_provider() {
+        let mut config = create_test_config();
+        let provider = LlmProvider::new(ProviderType::Gemini, ProviderType::Gemini.default_base_url(), "key")
+            .with_safety_settings(HashMap::from([("dangerous_content".to_string(), "block_none".to_string())]));
+        config.providers.insert("gemini".to_string(), provider);
+        config.models.insert(
+            "gemini-pro".to_string(),
+            LlmModel {
+                name: "gemini-2.5-pro".to_string(),
+                provider: "gemini".to_string(),
+                max_tokens: None,
+                temperature: None,
+                pricing: None,
+            },
+        );
+
+        let provider = create_provider_for_model(&config, "gemini-pro").await.unwrap();
+        assert_eq!(provider.model_name(), "gemini-2.5-pro");
+        assert!(model_capabilities(&config, "gemini-pro").contains(&ModelCapability::Thinking));
+
+        let gemini = config.providers.get_mut("gemini").unwrap();
+        gemini.safety_settings = Some(HashMap::from([("violence".to_string(), "block_none".to_string())]));
+        assert!(matches!(create_provider_for_model(&config, "gemini-pro").await, Err(LlmError::ProviderError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_ollama_provider() {
+        let mut config = create_test_config();
+        let provider = LlmProvider::new(ProviderType::Ollama, "http://localhost:11434/v1", "").with_keep_alive("30m");
+        config.providers.insert("ollama".to_string(), provider);
+        config.models.insert(
+            "deepseek".to_string(),
+            LlmModel {
+                name: "deepseek-r1:14b".to_string(),
+                provider: "ollama".to_string(),
+                max_tokens: Some(32768),
+                temperature: None,
+                pricing: None,
+            },
+        );
+
+        let provider = create_provider_for_model(&config, "deepseek").await.unwrap();
+        assert_eq!(provider.model_name(), "deepseek-r1:14b");
+        assert!(model_capabilities(&config, "deepseek").contains(&ModelCapability::Thinking));
+    }
+
+    #[tokio::test]
+    async fn test_create_openai_providers() {
+        let mut config = create_test_config();
+        config.providers.insert(
+            "openai".to_string(),
+            LlmProvider::new(ProviderType::OpenAiLegacy, "https://api.openai.com/v1", "sk-test"),
+        );
+        config.providers.insert(
+            "azure".to_string(),
+            LlmProvider::new(ProviderType::AzureOpenAi, "https://my-resource.openai.azure.com", "azure-key"),
+        );
+        for (name, model, provider) in [("gpt", "gpt-4o", "openai"), ("azure-gpt", "gpt4o-prod", "azure")] {
+            config.models.insert(
+                name.to_string(),
+                LlmModel {
+                    name: model.to_string(),
+                    provider: provider.to_string(),
+                    max_tokens: Some(128000),
+                    temperature: Some(0.2),
+                    pricing: None,
+                },
+            );
+        }
+
+        let provider = create_provider_for_model(&config, "gpt").await.unwrap();
+        assert_eq!(provider.model_name(), "gpt-4o");
+        assert!(model_capabilities(&config, "gpt").contains(&ModelCapability::Vision));
+        let provider = create_provider_for_model(&config, "azure-gpt").await.unwrap();
+        assert_eq!(provider.model_name(), "gpt4o-prod");
+
+        config.providers.get_mut("azure").unwrap().proxy = Some("not a url".to_string());
+        assert!(matches!(create_provider_for_model(&config, "azure-gpt").await, Err(LlmError::ProviderError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_custom_headers_and_proxy() {
+        let mut config = create_test_config();
+        let kimi = config.providers.get_mut("test-provider").unwrap();
+        kimi.custom_headers = Some(HashMap::from([("X-Gateway-Team".to_string(), "platform".to_string())]));
+        kimi.proxy = Some("http://proxy.internal:8080".to_string());
+        let headers = custom_headers(&config.providers["test-provider"]).unwrap();
+        assert_eq!(headers["x-gateway-team"], "platform");
+        assert!(create_provider(&config).await.is_ok());
+
+        let kimi = config.providers.get_mut("test-provider").unwrap();
+        kimi.proxy = Some("not a url".to_string());
+        assert!(matches!(create_provider(&config).await, Err(LlmError::ProviderError(_))));
+        let kimi = config.providers.get_mut("test-provider").unwrap();
+        kimi.proxy = None;
+        kimi.custom_headers = Some(HashMap::from([("Bad Header".to_string(), "x".to_string())]));
+        let err = create_provider(&config).await.err().unwrap().to_string();
+        assert!(err.contains("Invalid custom header name 'Bad Header'"), "{}", err);
+    }
+
     #[test]
     fn test_llm_error_display() {
         let err = LlmError::NoProvider;
@@ -0,0 +1,437 @@
+//! Client for MCP (Model Context Protocol) servers
+//!
+//! An MCP server is a process speaking JSON-RPC 2.0 over its stdin and
+//! stdout, one message per line. [`McpClient::connect`] starts it and
+//! performs the `initialize` handshake; the client can then list the
+//! server's tools and call them. [`McpTool`] wraps one of those tools so
+//! the toolset can offer it to the model like a built-in one.
+//!
+//! Requests the server sends the client are answered with an error, except
+//! `ping`: sampling and roots are not supported. The server is killed when
+//! the client is dropped.
+
+use crate::soul::toolset::{McpServerInfo, Tool, ToolError, ToolResult};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, Command};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// MCP revision the client speaks
+pub const PROTOCOL_VERSION: &str = "2024-11-05";
+
+/// How long a server may take to start and answer the handshake
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// JSON-RPC error code for methods the client does not implement
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Errors talking to an MCP server
+#[derive(Debug, Error)]
+pub enum McpError {
+    #[error("Failed to start `{command}`: {source}")]
+    Spawn { command: String, source: std::io::Error },
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("The server exited")]
+    Closed,
+
+    #[error("The server did not answer within {} seconds", .0.as_secs())]
+    Timeout(Duration),
+
+    #[error("Server error {code}: {message}")]
+    Server { code: i64, message: String },
+
+    #[error("Invalid response: {0}")]
+    Protocol(String),
+}
+
+/// A tool offered by an MCP server
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct McpToolInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default = "empty_object_schema")]
+    pub input_schema: Value,
+}
+
+fn empty_object_schema() -> Value {
+    json!({"type": "object", "properties": {}})
+}
+
+/// The result of a tool call, with its content flattened to text
+#[derive(Debug, Clone, PartialEq)]
+pub struct McpToolOutput {
+    pub text: String,
+    /// Whether the tool reported a failure
+    pub is_error: bool,
+}
+
+/// Requests awaiting a response, by id; `None` once the server has exited
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value, McpError>>>>>>;
+
+/// A connection to a running MCP server
+pub struct McpClient {
+    name: String,
+    /// `name version` the server reported in the handshake
+    server_info: Option<String>,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    reader: JoinHandle<()>,
+    /// Killed when the client is dropped
+    _child: Child,
+}
+
+impl std::fmt::Debug for McpClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("McpClient")
+            .field("name", &self.name)
+            .field("server_info", &self.server_info)
+            .field("connected", &self.is_connected())
+            .finish_non_exhaustive()
+    }
+}
+
+impl McpClient {
+    /// Start `server` and perform the handshake
+    ///
+    /// Gives up after [`CONNECT_TIMEOUT`].
+    pub async fn connect(server: &McpServerInfo) -> Result<Self, McpError> {
+        let mut command = Command::new(&server.command);
+        command
+            .args(&server.args)
+            .envs(server.env.iter().flatten())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        let mut child = command.spawn().map_err(|source| McpError::Spawn {
+            command: server.command.clone(),
+            source,
+        })?;
+
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stdin = Arc::new(tokio::sync::Mutex::new(stdin));
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+
+        // Servers log to stderr; keep it out of the terminal
+        let name = server.name.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                debug!("MCP server {}: {}", name, line);
+            }
+        });
+        let reader = tokio::spawn(read_messages(server.name.clone(), stdout, stdin.clone(), pending.clone()));
+
+        let mut client = Self {
+            name: server.name.clone(),
+            server_info: None,
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+            reader,
+            _child: child,
+        };
+        let handshake = client.request(
+            "initialize",
+            json!({
+                "protocolVersion": PROTOCOL_VERSION,
+                "capabilities": {},
+                "clientInfo": {"name": "kimi-cli", "version": env!("CARGO_PKG_VERSION")},
+            }),
+        );
+        let result = tokio::time::timeout(CONNECT_TIMEOUT, handshake)
+            .await
+            .map_err(|_| McpError::Timeout(CONNECT_TIMEOUT))??;
+        client.server_info = result.get("serverInfo").map(|info| {
+            let name = info.get("name").and_then(Value::as_str).unwrap_or(&server.name);
+            match info.get("version").and_then(Value::as_str) {
+                Some(version) => format!("{} {}", name, version),
+                None => name.to_string(),
+            }
+        });
+        client.notify("notifications/initialized", json!({})).await?;
+        Ok(client)
+    }
+
+    /// Name of the server in the configuration
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// `name version` the server reported, if any
+    pub fn server_info(&self) -> Option<&str> {
+        self.server_info.as_deref()
+    }
+
+    /// Whether the server is still running
+    pub fn is_connected(&self) -> bool {
+        self.pending.lock().unwrap().is_some()
+    }
+
+    /// List the server's tools
+    pub async fn list_tools(&self) -> Result<Vec<McpToolInfo>, McpError> {
+        let mut tools = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let params = match &cursor {
+                Some(cursor) => json!({"cursor": cursor}),
+                None => json!({}),
+            };
+            let mut result = self.request("tools/list", params).await?;
+            let page: Vec<McpToolInfo> = serde_json::from_value(result["tools"].take())
+                .map_err(|e| McpError::Protocol(format!("tools/list: {}", e)))?;
+            tools.extend(page);
+            match result.get("nextCursor").and_then(Value::as_str) {
+                Some(next) => cursor = Some(next.to_string()),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    /// Call the tool `name` with `arguments`
+    pub async fn call_tool(&self, name: &str, arguments: Value) -> Result<McpToolOutput, McpError> {
+        let result = self.request("tools/call", json!({"name": name, "arguments": arguments})).await?;
+        let content = result.get("content").and_then(Value::as_array).map(Vec::as_slice).unwrap_or_default();
+        let text = content.iter().map(content_text).collect::<Vec<_>>().join("\n");
+        let is_error = result.get("isError").and_then(Value::as_bool).unwrap_or(false);
+        Ok(McpToolOutput { text, is_error })
+    }
+
+    /// Send a request and wait for its result
+    async fn request(&self, method: &str, params: Value) -> Result<Value, McpError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        match self.pending.lock().unwrap().as_mut() {
+            Some(pending) => pending.insert(id, tx),
+            None => return Err(McpError::Closed),
+        };
+        let message = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        if let Err(e) = write_message(&self.stdin, &message).await {
+            if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+                pending.remove(&id);
+            }
+            return Err(e);
+        }
+        rx.await.map_err(|_| McpError::Closed)?
+    }
+
+    /// Send a notification, which has no response
+    async fn notify(&self, method: &str, params: Value) -> Result<(), McpError> {
+        write_message(&self.stdin, &json!({"jsonrpc": "2.0", "method": method, "params": params})).await
+    }
+}
+
+impl Drop for McpClient {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+async fn write_message(stdin: &tokio::sync::Mutex<ChildStdin>, message: &Value) -> Result<(), McpError> {
+    let mut line = serde_json::to_vec(message).map_err(|e| McpError::Protocol(e.to_string()))?;
+    line.push(b'\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(&line).await?;
+    stdin.flush().await?;
+    Ok(())
+}
+
+/// Dispatch the server's messages until it exits, then fail the requests
+/// still waiting
+async fn read_messages(
+    name: String,
+    stdout: tokio::process::ChildStdout,
+    stdin: Arc<tokio::sync::Mutex<ChildStdin>>,
+    pending: Pending,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            warn!("MCP server {} sent a line that is not JSON: {}", name, line);
+            continue;
+        };
+        match (message.get("id"), message.get("method").and_then(Value::as_str)) {
+            // A response to one of our requests
+            (Some(id), None) => {
+                let sender = id.as_u64().and_then(|id| pending.lock().unwrap().as_mut()?.remove(&id));
+                let Some(sender) = sender else {
+                    debug!("MCP server {} answered an unknown request {}", name, id);
+                    continue;
+                };
+                let result = match message.get("error") {
+                    Some(error) => Err(McpError::Server {
+                        code: error.get("code").and_then(Value::as_i64).unwrap_or(0),
+                        message: error.get("message").and_then(Value::as_str).unwrap_or("unknown error").to_string(),
+                    }),
+                    None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+                };
+                let _ = sender.send(result);
+            }
+            // A request from the server
+            (Some(id), Some(method)) => {
+                let reply = if method == "ping" {
+                    json!({"jsonrpc": "2.0", "id": id, "result": {}})
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": {"code": METHOD_NOT_FOUND, "message": format!("Method not supported: {}", method)},
+                    })
+                };
+                if write_message(&stdin, &reply).await.is_err() {
+                    break;
+                }
+            }
+            (None, Some(method)) => debug!("MCP server {} sent {}", name, method),
+            (None, None) => warn!("MCP server {} sent an invalid message: {}", name, line),
+        }
+    }
+    debug!("MCP server {} exited", name);
+    // Dropping the senders fails the waiting requests with `Closed`
+    pending.lock().unwrap().take();
+}
+
+/// The text of a content item of a tool result
+fn content_text(item: &Value) -> String {
+    let text = |key: &str| item.get(key).and_then(Value::as_str).unwrap_or_default();
+    match text("type") {
+        "text" => text("text").to_string(),
+        "image" | "audio" => format!("[{} content: {}]", text("type"), text("mimeType")),
+        "resource" => {
+            let resource = &item["resource"];
+            match resource.get("text").and_then(Value::as_str) {
+                Some(contents) => contents.to_string(),
+                None => format!("[resource: {}]", resource.get("uri").and_then(Value::as_str).unwrap_or_default()),
+            }
+        }
+        other => format!("[{} content]", other),
+    }
+}
+
+/// A tool of an MCP server, called through its client
+#[derive(Debug)]
+pub struct McpTool {
+    client: Arc<McpClient>,
+    info: McpToolInfo,
+}
+
+impl McpTool {
+    pub fn new(client: Arc<McpClient>, info: McpToolInfo) -> Self {
+        Self { client, info }
+    }
+
+    /// Name of the server providing the tool
+    pub fn server(&self) -> &str {
+        self.client.name()
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn name(&self) -> &str {
+        &self.info.name
+    }
+
+    fn description(&self) -> &str {
+        &self.info.description
+    }
+
+    fn parameters_schema(&self) -> Value {
+        self.info.input_schema.clone()
+    }
+
+    async fn execute(&self, params: Value) -> ToolResult {
+        let output = self
+            .client
+            .call_tool(&self.info.name, params)
+            .await
+            .map_err(|e| ToolError::McpServer(format!("{}: {}", self.client.name(), e)))?;
+        if output.is_error {
+            return Err(ToolError::Execution(output.text));
+        }
+        Ok(Value::String(output.text))
+    }
+}
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use super::*;
+
+    /// A shell script answering like an MCP server with one tool, `echo`,
+    /// whose result is its `text` argument
+    pub(crate) fn fake_server() -> McpServerInfo {
+        const SCRIPT: &str = r#"
+while IFS= read -r line; do
+  id=$(printf '%s' "$line" | sed -n 's/.*"id":\([0-9]*\).*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2024-11-05","capabilities":{"tools":{}},"serverInfo":{"name":"fake","version":"1.0"}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","method":"notifications/message","params":{}}\n'
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echo text","inputSchema":{"type":"object","properties":{"text":{"type":"string"}}}}]}}\n' "$id" ;;
+    *'"name":"fail"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"it broke"}],"isError":true}}\n' "$id" ;;
+    *'"method":"tools/call"'*)
+      text=$(printf '%s' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$text" ;;
+    *'"method":"exit"'*)
+      exit 0 ;;
+    *'"id":'*)
+      printf '{"jsonrpc":"2.0","id":%s,"error":{"code":-32601,"message":"Method not found"}}\n' "$id" ;;
+  esac
+done
+"#;
+        McpServerInfo::new("fake", "sh").with_args(vec!["-c".to_string(), SCRIPT.to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_tools() {
+        let client = McpClient::connect(&fake_server()).await.unwrap();
+        assert_eq!(client.server_info(), Some("fake 1.0"));
+
+        let tools = client.list_tools().await.unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].name, "echo");
+        assert_eq!(tools[0].input_schema["properties"]["text"]["type"], "string");
+
+        let tool = McpTool::new(Arc::new(client), tools[0].clone());
+        assert_eq!(tool.execute(json!({"text": "hello"})).await.unwrap(), json!("hello"));
+
+        let failing = McpTool::new(tool.client.clone(), McpToolInfo { name: "fail".to_string(), ..tools[0].clone() });
+        assert!(matches!(failing.execute(json!({})).await, Err(ToolError::Execution(text)) if text == "it broke"));
+
+        let err = tool.client.request("resources/list", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Server { code: -32601, .. }));
+    }
+
+    #[tokio::test]
+    async fn test_server_exit_fails_requests() {
+        let client = McpClient::connect(&fake_server()).await.unwrap();
+        // The fake server exits without answering
+        let err = client.request("exit", json!({})).await.unwrap_err();
+        assert!(matches!(err, McpError::Closed));
+        assert!(!client.is_connected());
+        assert!(matches!(client.list_tools().await, Err(McpError::Closed)));
+
+        let missing = McpServerInfo::new("missing", "/nonexistent/mcp-server");
+        assert!(matches!(McpClient::connect(&missing).await, Err(McpError::Spawn { .. })));
+    }
+}
@@ -0,0 +1,519 @@
+//! Project memory loaded from AGENTS.md / KIMI.md
+//!
+//! Memory files are plain markdown written for coding agents (see the `/init`
+//! command). They are collected from three levels and appended to the agent's
+//! system prompt in a fixed order, most general first:
+//!
+//! 1. Global: `~/.kimi/AGENTS.md`, `~/.kimi/KIMI.md`
+//! 2. Project: the repository root
+//! 3. Subdirectory: each directory between the root and the working directory
+//!
+//! Within a directory, `AGENTS.md` is read before `KIMI.md`. A directory with
+//! neither falls back to the files other coding agents read, `CLAUDE.md` and
+//! `GEMINI.md`, so existing project instructions apply too. A line of the form
+//! `@import <path>` is replaced by the contents of that file, resolved relative
+//! to the importing file (`~/` expands to the home directory). Project and
+//! subdirectory memory may only import files inside the project root, and
+//! is skipped when it is a link to a file outside it; only global memory may
+//! import from elsewhere.
+
+use crate::workspace::Workspace;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+/// File names recognised as project memory, in the order they are read
+pub const MEMORY_FILE_NAMES: &[&str] = &["AGENTS.md", "KIMI.md"];
+
+/// Other agents' memory files, read in a directory without any of
+/// [`MEMORY_FILE_NAMES`]
+pub const FALLBACK_MEMORY_FILE_NAMES: &[&str] = &["CLAUDE.md", "GEMINI.md"];
+
+/// Default maximum size of a single memory file, in bytes
+pub const DEFAULT_MAX_FILE_BYTES: usize = 32 * 1024;
+
+/// Default maximum combined size of all memory files, in bytes
+pub const DEFAULT_MAX_TOTAL_BYTES: usize = 64 * 1024;
+
+/// Maximum nesting depth of `@import` directives
+pub const MAX_IMPORT_DEPTH: usize = 5;
+
+const TRUNCATION_MARKER: &str = "\n\n[... truncated ...]";
+
+/// Where a memory file was found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryScope {
+    /// The user's global `~/.kimi` directory
+    Global,
+    /// The repository root (or the working directory outside a repository)
+    Project,
+    /// A directory below the project root
+    Subdirectory,
+}
+
+impl fmt::Display for MemoryScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryScope::Global => write!(f, "global"),
+            MemoryScope::Project => write!(f, "project"),
+            MemoryScope::Subdirectory => write!(f, "subdirectory"),
+        }
+    }
+}
+
+/// A single loaded memory file
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryFile {
+    pub path: PathBuf,
+    pub scope: MemoryScope,
+    /// Content with all imports expanded
+    pub content: String,
+    /// Files pulled in through `@import`, in the order they were expanded
+    pub imports: Vec<PathBuf>,
+    /// Whether the content was cut to fit the size limits
+    pub truncated: bool,
+}
+
+/// Size limits applied while loading memory files
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryLimits {
+    pub max_file_bytes: usize,
+    pub max_total_bytes: usize,
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        Self {
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_total_bytes: DEFAULT_MAX_TOTAL_BYTES,
+        }
+    }
+}
+
+/// Memory files that apply to a working directory
+#[derive(Debug, Clone, Default)]
+pub struct ProjectMemory {
+    /// Loaded files in merge order: global, project, then subdirectories
+    pub files: Vec<MemoryFile>,
+}
+
+impl ProjectMemory {
+    /// Discover memory files for `work_dir` using `~/.kimi` and the default limits
+    pub fn discover(work_dir: &Path) -> Self {
+        let global = global_dir();
+        Self::discover_with(work_dir, global.as_deref(), MemoryLimits::default())
+    }
+
+    /// Discover memory files for `work_dir`
+    ///
+    /// The directory and its ancestors are searched up to and including the
+    /// repository root (the first directory containing `.git`). Outside a
+    /// repository only `work_dir` itself is searched. When the total budget
+    /// runs out, files closest to `work_dir` are kept in preference to
+    /// outer ones, and global files are dropped first.
+    pub fn discover_with(work_dir: &Path, global_dir: Option<&Path>, limits: MemoryLimits) -> Self {
+        let mut dirs: Vec<(PathBuf, MemoryScope)> = project_dirs(work_dir);
+        let project_root = dirs.last().and_then(|(root, _)| Workspace::new(root).ok());
+        if let Some(global) = global_dir {
+            dirs.push((global.to_path_buf(), MemoryScope::Global));
+        }
+
+        let mut files = Vec::new();
+        let mut remaining = limits.max_total_bytes;
+
+        for (dir, scope) in dirs {
+            // Within a directory, keep AGENTS.md before KIMI.md after the final reverse
+            for name in memory_file_names(&dir).iter().rev() {
+                let path = dir.join(name);
+                if !path.is_file() {
+                    continue;
+                }
+                if remaining == 0 {
+                    warn!("Skipping {:?}: project memory size limit reached", path);
+                    continue;
+                }
+
+                let confined = match (scope, &project_root) {
+                    (MemoryScope::Global, _) => None,
+                    (_, Some(root)) => Some(root),
+                    (_, None) => {
+                        warn!("Skipping {:?}: cannot resolve the project root", path);
+                        continue;
+                    }
+                };
+                // A project file linking out of the project is not read either
+                if confined.is_some_and(|root| root.resolve(&path).is_err()) {
+                    warn!("Skipping {:?}: outside the project", path);
+                    continue;
+                }
+                let mut imports = Vec::new();
+                let mut stack = HashSet::new();
+                let content = match load_expanded(&path, confined, 0, &mut stack, &mut imports) {
+                    Ok(content) => content,
+                    Err(e) => {
+                        warn!("Failed to read {:?}: {}", path, e);
+                        continue;
+                    }
+                };
+                if content.trim().is_empty() {
+                    continue;
+                }
+
+                let limit = limits.max_file_bytes.min(remaining);
+                let (content, truncated) = truncate(content, limit);
+                remaining = remaining.saturating_sub(content.len());
+                debug!("Loaded {} memory from {:?} ({} bytes)", scope, path, content.len());
+                files.push(MemoryFile { path, scope, content, imports, truncated });
+            }
+        }
+
+        files.reverse();
+        Self { files }
+    }
+
+    /// Whether no memory files were found
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Total size of the loaded content, in bytes
+    pub fn total_bytes(&self) -> usize {
+        self.files.iter().map(|f| f.content.len()).sum()
+    }
+
+    /// Render the memory files as a system prompt section
+    pub fn render(&self) -> String {
+        let mut out = String::from(
+            "# Project Memory\n\n\
+             The following instructions were loaded from memory files. \
+             Later sections are more specific and take precedence over earlier ones.",
+        );
+        for file in &self.files {
+            out.push_str(&format!(
+                "\n\n## {} ({})\n\n{}",
+                file.path.display(),
+                file.scope,
+                file.content.trim_end()
+            ));
+            if file.truncated {
+                out.push_str(TRUNCATION_MARKER);
+            }
+        }
+        out
+    }
+
+    /// Append the rendered memory to a base system prompt
+    pub fn apply_to(&self, system_prompt: &str) -> String {
+        if self.is_empty() {
+            return system_prompt.to_string();
+        }
+        format!("{}\n\n{}", system_prompt.trim_end(), self.render())
+    }
+}
+
+/// The global memory directory, `~/.kimi`
+pub fn global_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".kimi"))
+}
+
+/// The memory files to read in `dir`, in order
+fn memory_file_names(dir: &Path) -> &'static [&'static str] {
+    if MEMORY_FILE_NAMES.iter().any(|name| dir.join(name).is_file()) {
+        MEMORY_FILE_NAMES
+    } else {
+        FALLBACK_MEMORY_FILE_NAMES
+    }
+}
+
+/// Project directories to search, closest first, with their scope
+fn project_dirs(work_dir: &Path) -> Vec<(PathBuf, MemoryScope)> {
+    let mut dirs = Vec::new();
+    for dir in work_dir.ancestors() {
+        if dir.join(".git").exists() {
+            dirs.push((dir.to_path_buf(), MemoryScope::Project));
+            return dirs;
+        }
+        dirs.push((dir.to_path_buf(), MemoryScope::Subdirectory));
+    }
+    // Not inside a repository: do not wander up into unrelated directories
+    vec![(work_dir.to_path_buf(), MemoryScope::Project)]
+}
+
+/// Read a memory file, expanding `@import` lines recursively
+///
+/// Imports inside fenced code blocks are left untouched. With `confined`,
+/// files outside it are not imported. Missing or outside files, cycles, and
+/// imports nested deeper than [`MAX_IMPORT_DEPTH`] are replaced by a short
+/// HTML comment so the model can see something was skipped.
+fn load_expanded(
+    path: &Path,
+    confined: Option<&Workspace>,
+    depth: usize,
+    stack: &mut HashSet<PathBuf>,
+    imports: &mut Vec<PathBuf>,
+) -> std::io::Result<String> {
+    let content = std::fs::read_to_string(path)?;
+    let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    stack.insert(key.clone());
+
+    let base = path.parent().unwrap_or_else(|| Path::new("."));
+    let mut out = String::with_capacity(content.len());
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+        }
+
+        let target = if in_fence { None } else { parse_import(trimmed) };
+        let Some(target) = target else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+
+        let resolved = resolve_import(base, target);
+        let resolved_key = resolved.canonicalize().unwrap_or_else(|_| resolved.clone());
+        if confined.is_some_and(|root| root.resolve(&resolved).is_err()) {
+            warn!("Skipping import of {:?}: outside the project", resolved);
+            out.push_str(&format!("<!-- @import {}: outside the project -->\n", target));
+        } else if depth + 1 > MAX_IMPORT_DEPTH {
+            warn!("Skipping import of {:?}: nesting too deep", resolved);
+            out.push_str(&format!("<!-- @import {}: nesting too deep -->\n", target));
+        } else if stack.contains(&resolved_key) {
+            warn!("Skipping import of {:?}: import cycle", resolved);
+            out.push_str(&format!("<!-- @import {}: import cycle -->\n", target));
+        } else {
+            match load_expanded(&resolved, confined, depth + 1, stack, imports) {
+                Ok(imported) => {
+                    imports.push(resolved);
+                    out.push_str(&imported);
+                    if !imported.ends_with('\n') {
+                        out.push('\n');
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to import {:?}: {}", resolved, e);
+                    out.push_str(&format!("<!-- @import {}: not found -->\n", target));
+                }
+            }
+        }
+    }
+
+    stack.remove(&key);
+    Ok(out)
+}
+
+/// Extract the target of an `@import <path>` line
+fn parse_import(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("@import")?;
+    if !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let target = rest.trim().trim_matches(|c| c == '"' || c == '\'');
+    (!target.is_empty()).then_some(target)
+}
+
+fn resolve_import(base: &Path, target: &str) -> PathBuf {
+    if let Some(rest) = target.strip_prefix("~/") {
+        if let Some(home) = dirs::home_dir() {
+            return home.join(rest);
+        }
+    }
+    base.join(target)
+}
+
+/// Cut `content` to at most `limit` bytes on a character boundary
+fn truncate(mut content: String, limit: usize) -> (String, bool) {
+    if content.len() <= limit {
+        return (content, false);
+    }
+    let mut end = limit;
+    while !content.is_char_boundary(end) {
+        end -= 1;
+    }
+    content.truncate(end);
+    (content, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn repo_with_nested_dir() -> (tempfile::TempDir, PathBuf) {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp.path().join(".git")).unwrap();
+        let nested = temp.path().join("crates").join("core");
+        std::fs::create_dir_all(&nested).unwrap();
+        (temp, nested)
+    }
+
+    fn discover(work_dir: &Path, global: Option<&Path>) -> ProjectMemory {
+        ProjectMemory::discover_with(work_dir, global, MemoryLimits::default())
+    }
+
+    #[test]
+    fn test_discover_walks_up_to_repo_root() {
+        let (temp, nested) = repo_with_nested_dir();
+        std::fs::write(temp.path().join("AGENTS.md"), "root rules").unwrap();
+        std::fs::write(nested.join("KIMI.md"), "nested rules").unwrap();
+
+        let memory = discover(&nested, None);
+        assert_eq!(memory.files.len(), 2);
+        assert_eq!(memory.files[0].content.trim(), "root rules");
+        assert_eq!(memory.files[0].scope, MemoryScope::Project);
+        assert_eq!(memory.files[1].content.trim(), "nested rules");
+        assert_eq!(memory.files[1].scope, MemoryScope::Subdirectory);
+
+        let prompt = memory.apply_to("base prompt");
+        assert!(prompt.starts_with("base prompt\n\n# Project Memory"));
+        assert!(prompt.find("root rules").unwrap() < prompt.find("nested rules").unwrap());
+    }
+
+    #[test]
+    fn test_discover_outside_repo_only_reads_work_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let nested = temp.path().join("sub");
+        std::fs::create_dir(&nested).unwrap();
+        std::fs::write(temp.path().join("AGENTS.md"), "parent rules").unwrap();
+
+        assert!(discover(&nested, None).is_empty());
+        assert_eq!(discover(temp.path(), None).files.len(), 1);
+    }
+
+    #[test]
+    fn test_merge_order_is_global_project_subdirectory() {
+        let (temp, nested) = repo_with_nested_dir();
+        let global = tempfile::tempdir().unwrap();
+        std::fs::write(global.path().join("KIMI.md"), "global").unwrap();
+        std::fs::write(temp.path().join("KIMI.md"), "project kimi").unwrap();
+        std::fs::write(temp.path().join("AGENTS.md"), "project agents").unwrap();
+        std::fs::write(nested.join("AGENTS.md"), "nested").unwrap();
+
+        let memory = discover(&nested, Some(global.path()));
+        let contents: Vec<_> = memory.files.iter().map(|f| f.content.trim()).collect();
+        assert_eq!(contents, vec!["global", "project agents", "project kimi", "nested"]);
+        assert_eq!(memory.files[0].scope, MemoryScope::Global);
+    }
+
+    #[test]
+    fn test_fallback_files_only_without_agents_md() {
+        let (temp, nested) = repo_with_nested_dir();
+        std::fs::write(temp.path().join("CLAUDE.md"), "claude rules").unwrap();
+        std::fs::write(temp.path().join("GEMINI.md"), "gemini rules").unwrap();
+        std::fs::write(nested.join("CLAUDE.md"), "nested claude rules").unwrap();
+        std::fs::write(nested.join("AGENTS.md"), "nested rules").unwrap();
+
+        let memory = discover(&nested, None);
+        let contents: Vec<_> = memory.files.iter().map(|f| f.content.trim()).collect();
+        assert_eq!(contents, vec!["claude rules", "gemini rules", "nested rules"]);
+    }
+
+    #[test]
+    fn test_imports_are_expanded_relative_to_importer() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp.path().join("docs")).unwrap();
+        std::fs::write(temp.path().join("docs").join("style.md"), "use tabs\n@import ../shared.md").unwrap();
+        std::fs::write(temp.path().join("shared.md"), "be kind").unwrap();
+        std::fs::write(
+            temp.path().join("AGENTS.md"),
+            "intro\n@import docs/style.md\n```\n@import skipped.md\n```\n@import missing.md\n",
+        )
+        .unwrap();
+
+        let memory = discover(temp.path(), None);
+        let file = &memory.files[0];
+        assert!(file.content.starts_with("intro\nuse tabs\nbe kind\n"));
+        assert!(file.content.contains("```\n@import skipped.md\n```"));
+        assert!(file.content.contains("<!-- @import missing.md: not found -->"));
+        assert_eq!(file.imports.len(), 2);
+        assert!(file.imports[0].ends_with("shared.md"));
+        assert!(file.imports[1].ends_with("style.md"));
+    }
+
+    #[test]
+    fn test_import_cycles_are_broken() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("AGENTS.md"), "a\n@import other.md").unwrap();
+        std::fs::write(temp.path().join("other.md"), "b\n@import AGENTS.md").unwrap();
+
+        let memory = discover(temp.path(), None);
+        assert_eq!(
+            memory.files[0].content,
+            "a\nb\n<!-- @import AGENTS.md: import cycle -->\n"
+        );
+    }
+
+    #[test]
+    fn test_project_imports_stay_in_the_project() {
+        let temp = tempfile::tempdir().unwrap();
+        let (project, global) = (temp.path().join("project"), temp.path().join("global"));
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+        std::fs::create_dir(&global).unwrap();
+        let secret = temp.path().join("secret.txt");
+        std::fs::write(&secret, "hunter2").unwrap();
+        std::fs::write(
+            project.join("AGENTS.md"),
+            format!("@import ../secret.txt\n@import {}\n@import ~/.ssh/id_rsa\n", secret.display()),
+        )
+        .unwrap();
+        std::fs::write(global.join("AGENTS.md"), "@import ../secret.txt").unwrap();
+
+        let memory = discover(&project, Some(&global));
+        assert_eq!(memory.files.len(), 2);
+        assert_eq!(memory.files[0].content.trim(), "hunter2");
+        let project_file = &memory.files[1];
+        assert!(!project_file.content.contains("hunter2"));
+        assert_eq!(project_file.content.matches("outside the project").count(), 3);
+        assert!(project_file.imports.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_project_files_linking_out_are_skipped() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join("project");
+        std::fs::create_dir_all(project.join(".git")).unwrap();
+        let secret = temp.path().join("secret.md");
+        std::fs::write(&secret, "hunter2").unwrap();
+        std::os::unix::fs::symlink(&secret, project.join("AGENTS.md")).unwrap();
+        std::fs::write(project.join("KIMI.md"), "Use tabs.").unwrap();
+
+        let memory = discover(&project, None);
+        let contents: Vec<_> = memory.files.iter().map(|f| f.content.trim()).collect();
+        assert_eq!(contents, ["Use tabs."]);
+    }
+
+    #[test]
+    fn test_size_limits_prefer_closest_files() {
+        let (temp, nested) = repo_with_nested_dir();
+        std::fs::write(temp.path().join("AGENTS.md"), "r".repeat(100)).unwrap();
+        std::fs::write(nested.join("AGENTS.md"), "n".repeat(100)).unwrap();
+
+        let limits = MemoryLimits { max_file_bytes: 80, max_total_bytes: 120 };
+        let memory = ProjectMemory::discover_with(&nested, None, limits);
+
+        assert_eq!(memory.files.len(), 2);
+        assert_eq!(memory.files[1].content.len(), 80);
+        assert!(memory.files[1].truncated);
+        assert_eq!(memory.files[0].content.len(), 40);
+        assert!(memory.render().contains("[... truncated ...]"));
+    }
+
+    #[test]
+    fn test_parse_import() {
+        assert_eq!(parse_import("@import docs/a.md"), Some("docs/a.md"));
+        assert_eq!(parse_import("@import \"with space.md\""), Some("with space.md"));
+        assert_eq!(parse_import("@imports a.md"), None);
+        assert_eq!(parse_import("@import"), None);
+        assert_eq!(parse_import("see @import a.md"), None);
+    }
+
+    #[test]
+    fn test_empty_memory_leaves_prompt_unchanged() {
+        let memory = ProjectMemory::default();
+        assert_eq!(memory.apply_to("base prompt"), "base prompt");
+    }
+}
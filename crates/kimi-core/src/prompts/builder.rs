@@ -0,0 +1,238 @@
+//! Assembly of the final system prompt
+//!
+//! The system prompt is made of sections that always appear in the order of
+//! [`PromptSection::ORDER`]: the base prompt, environment info, project
+//! memory, active skills, retrieved code and tool guidance. Each section has a token budget;
+//! content over budget is cut at a line boundary and marked as truncated, so
+//! one oversized section cannot crowd out the others.
+
+use super::PromptVars;
+use crate::memory::ProjectMemory;
+use crate::rag::{render_hits, SearchHit};
+use crate::skill::activation::skill_prompt;
+use crate::skill::Skill;
+use crate::soul::KimiToolset;
+use std::collections::BTreeMap;
+
+/// Marker appended to a section cut to its budget
+const TRUNCATION_MARKER: &str = "\n\n[... truncated ...]";
+
+/// Rough number of characters per token, as used for compaction
+const CHARS_PER_TOKEN: usize = 4;
+
+/// A section of the system prompt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PromptSection {
+    /// The agent's rendered system prompt template
+    Base,
+    /// OS, working directory, date and model
+    Environment,
+    /// Loaded memory files such as AGENTS.md
+    Memory,
+    /// Instructions of the active skills
+    Skills,
+    /// Workspace excerpts retrieved for the current message
+    Retrieval,
+    /// How to use the available tools
+    Tools,
+}
+
+impl PromptSection {
+    /// Order of the sections in the assembled prompt
+    pub const ORDER: [PromptSection; 6] = [
+        PromptSection::Base,
+        PromptSection::Environment,
+        PromptSection::Memory,
+        PromptSection::Skills,
+        PromptSection::Retrieval,
+        PromptSection::Tools,
+    ];
+
+    /// Token budget used unless [`SystemPromptBuilder::budget`] sets one
+    pub fn default_budget(self) -> usize {
+        match self {
+            PromptSection::Base => 8 * 1024,
+            PromptSection::Environment => 256,
+            // Matches the memory loader's own 64 KiB limit
+            PromptSection::Memory => 16 * 1024,
+            PromptSection::Skills => 4 * 1024,
+            PromptSection::Retrieval => 4 * 1024,
+            PromptSection::Tools => 2 * 1024,
+        }
+    }
+}
+
+/// Estimate the number of tokens in `text`
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Builds the system prompt from its sections
+///
+/// Sections may be added in any order; [`build`](Self::build) lays them out
+/// in [`PromptSection::ORDER`] and skips empty ones.
+#[derive(Debug, Clone, Default)]
+pub struct SystemPromptBuilder {
+    sections: BTreeMap<PromptSection, Vec<String>>,
+    budgets: BTreeMap<PromptSection, usize>,
+}
+
+impl SystemPromptBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append raw text to a section
+    pub fn section(mut self, section: PromptSection, text: impl Into<String>) -> Self {
+        let text = text.into();
+        if !text.trim().is_empty() {
+            self.sections.entry(section).or_default().push(text);
+        }
+        self
+    }
+
+    /// Set the token budget of a section
+    pub fn budget(mut self, section: PromptSection, tokens: usize) -> Self {
+        self.budgets.insert(section, tokens);
+        self
+    }
+
+    /// The base prompt, already rendered
+    pub fn base(self, prompt: impl Into<String>) -> Self {
+        self.section(PromptSection::Base, prompt)
+    }
+
+    /// Environment info from the `os`, `cwd`, `date` and `model` variables
+    pub fn environment(self, vars: &PromptVars) -> Self {
+        let lines: Vec<String> = [
+            ("os", "Operating system"),
+            ("cwd", "Working directory"),
+            ("date", "Date"),
+            ("model", "Model"),
+        ]
+        .into_iter()
+        .filter_map(|(name, label)| {
+            vars.get(name)
+                .filter(|value| !value.is_empty())
+                .map(|value| format!("- {}: {}", label, value))
+        })
+        .collect();
+        if lines.is_empty() {
+            return self;
+        }
+        self.section(PromptSection::Environment, format!("# Environment\n\n{}", lines.join("\n")))
+    }
+
+    /// The loaded project memory
+    pub fn memory(self, memory: &ProjectMemory) -> Self {
+        if memory.is_empty() {
+            return self;
+        }
+        self.section(PromptSection::Memory, memory.render())
+    }
+
+    /// An active skill
+    pub fn skill(self, skill: &Skill) -> Self {
+        self.section(PromptSection::Skills, skill_prompt(skill))
+    }
+
+    /// Workspace chunks retrieved for the current message
+    pub fn retrieval(self, hits: &[SearchHit]) -> Self {
+        self.section(PromptSection::Retrieval, render_hits(hits))
+    }
+
+    /// Guidance listing the tools in `toolset` with a short description each
+    pub fn tools(self, toolset: &KimiToolset) -> Self {
+        let mut names: Vec<&String> = toolset.tool_names().collect();
+        if names.is_empty() {
+            return self;
+        }
+        names.sort();
+        let mut out = String::from(
+            "# Tools\n\n\
+             Use a tool whenever it gives a more accurate answer than guessing. \
+             Read files before changing them, and prefer the dedicated file tools over shell commands.\n",
+        );
+        for name in names {
+            let summary = toolset
+                .get(name)
+                .and_then(|tool| tool.description().lines().next().map(str::to_string))
+                .unwrap_or_default();
+            out.push_str(&format!("\n- `{}`: {}", name, summary.trim()));
+        }
+        self.section(PromptSection::Tools, out)
+    }
+
+    /// Assemble the sections in order, each cut to its budget
+    pub fn build(&self) -> String {
+        PromptSection::ORDER
+            .iter()
+            .filter_map(|section| {
+                let parts = self.sections.get(section)?;
+                let text = parts.iter().map(|part| part.trim_end()).collect::<Vec<_>>().join("\n\n");
+                let budget = self.budgets.get(section).copied().unwrap_or(section.default_budget());
+                Some(truncate_to_budget(&text, budget))
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+}
+
+/// Cut `text` to about `tokens` tokens, preferring a line boundary
+fn truncate_to_budget(text: &str, tokens: usize) -> String {
+    if estimate_tokens(text) <= tokens {
+        return text.to_string();
+    }
+    let max_chars = (tokens * CHARS_PER_TOKEN).saturating_sub(TRUNCATION_MARKER.len());
+    let end = text.char_indices().nth(max_chars).map_or(text.len(), |(i, _)| i);
+    let cut = &text[..end];
+    let cut = match cut.rfind('\n') {
+        Some(line_end) if line_end >= end / 2 => &cut[..line_end],
+        _ => cut,
+    };
+    format!("{}{}", cut.trim_end(), TRUNCATION_MARKER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sections_in_order() {
+        let vars = PromptVars::default().with("os", "linux").with("model", "kimi-k2").with("cwd", "");
+        let prompt = SystemPromptBuilder::new()
+            .section(PromptSection::Skills, "# Active Skill: review")
+            .environment(&vars)
+            .base("You are Kimi.\n")
+            .memory(&ProjectMemory::default())
+            .tools(&KimiToolset::new())
+            .build();
+        assert_eq!(
+            prompt,
+            "You are Kimi.\n\n# Environment\n\n- Operating system: linux\n- Model: kimi-k2\n\n# Active Skill: review"
+        );
+        assert_eq!(SystemPromptBuilder::new().base("  ").build(), "");
+    }
+
+    #[test]
+    fn test_budgets() {
+        let long = (0..100).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        let prompt = SystemPromptBuilder::new()
+            .base("Base")
+            .section(PromptSection::Memory, long.clone())
+            .section(PromptSection::Tools, "# Tools")
+            .budget(PromptSection::Memory, 50)
+            .build();
+        let (memory, tools) = prompt
+            .strip_prefix("Base\n\n")
+            .unwrap()
+            .split_once("\n\n[... truncated ...]\n\n")
+            .unwrap();
+        assert_eq!(tools, "# Tools");
+        assert!(long.starts_with(memory) && memory.ends_with(char::is_numeric));
+        assert!(estimate_tokens(memory) <= 50);
+
+        let untouched = SystemPromptBuilder::new().section(PromptSection::Memory, long.clone()).build();
+        assert_eq!(untouched, long);
+    }
+}
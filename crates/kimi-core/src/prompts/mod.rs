@@ -1,15 +1,194 @@
 //! Prompts for the agent system
 //!
-//! This module contains system prompts used by the agent for various tasks.
+//! This module contains the built-in prompt templates used by the agent and
+//! the small template engine that fills them in. Templates refer to
+//! variables as `{{name}}`; [`render`] substitutes the values from a
+//! [`PromptVars`]. The built-in templates can be overridden per prompt name
+//! (see [`PROMPT_NAMES`]) from the `[prompts]` config table or from
+//! `.kimi/prompts/<name>.md` in the project, which takes precedence.
+//! [`SystemPromptBuilder`] assembles the final system prompt around them.
+
+pub mod builder;
+
+pub use builder::{PromptSection, SystemPromptBuilder};
+
+use crate::config::PromptsConfig;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
 
 /// The INIT prompt used by the `/init` slash command.
 /// This prompt instructs the agent to analyze the project and create an AGENTS.md file.
 pub const INIT: &str = include_str!("init.md");
 
+/// Appended to the INIT prompt when the analysis runs with read-only tools.
+/// The reply is written to AGENTS.md by the caller.
+pub const INIT_OUTPUT: &str = "You only have read-only tools in this session, so do not try to \
+write the file yourself. When you are done exploring, reply with the complete new content of \
+`AGENTS.md` and nothing else.";
+
+/// The COMPACT prompt used by the `/compact` slash command.
+/// It asks the model for a structured summary of the conversation so far.
+pub const COMPACT: &str = include_str!("compact.md");
+
+/// The COMMIT prompt used by the `/commit` slash command.
+/// It asks the model for a Conventional Commits message describing a diff.
+pub const COMMIT: &str = include_str!("commit.md");
+
 /// The DEFAULT_SYSTEM prompt used as the default system prompt for the agent.
-pub const DEFAULT_SYSTEM: &str = "You are Kimi, a helpful AI assistant. \
-You have access to various tools to help users with their tasks. \
-Use the tools when appropriate to provide accurate and helpful responses.";
+pub const DEFAULT_SYSTEM: &str = include_str!("system.md");
+
+/// Names of the prompts that can be overridden
+pub const PROMPT_NAMES: &[&str] = &["system", "init", "compact", "commit"];
+
+/// Values for the `{{name}}` placeholders in prompt templates
+///
+/// The agent provides `os`, `cwd`, `date`, `model` and `tools`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PromptVars {
+    values: BTreeMap<String, String>,
+}
+
+impl PromptVars {
+    /// Variables describing the environment: `os`, `cwd` and `date`
+    pub fn from_env() -> Self {
+        let cwd = std::env::current_dir()
+            .map(|dir| dir.display().to_string())
+            .unwrap_or_default();
+        Self::default()
+            .with("os", std::env::consts::OS)
+            .with("cwd", cwd)
+            .with("date", chrono::Local::now().format("%Y-%m-%d").to_string())
+    }
+
+    /// Set a variable
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<String>) {
+        self.values.insert(name.into(), value.into());
+    }
+
+    /// Set a variable, builder-style
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Value of a variable
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+}
+
+/// Replace `{{name}}` placeholders with their values from `vars`
+///
+/// Whitespace inside the braces is ignored. Placeholders for unknown
+/// variables are left as they are, so prompts may contain literal braces.
+pub fn render(template: &str, vars: &PromptVars) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let value = after
+            .find("}}")
+            .and_then(|end| vars.get(after[..end].trim()).map(|value| (value, end)));
+        match value {
+            Some((value, end)) => {
+                out.push_str(value);
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// The prompt templates in use, with overrides applied
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PromptTemplates {
+    /// System prompt of the default agent
+    pub system: String,
+    /// Analysis prompt for `/init`
+    pub init: String,
+    /// Summarization prompt for `/compact`
+    pub compact: String,
+    /// Commit message prompt for `/commit`
+    pub commit: String,
+}
+
+impl Default for PromptTemplates {
+    fn default() -> Self {
+        Self {
+            system: DEFAULT_SYSTEM.to_string(),
+            init: INIT.to_string(),
+            compact: COMPACT.to_string(),
+            commit: COMMIT.to_string(),
+        }
+    }
+}
+
+impl PromptTemplates {
+    /// The built-in templates with overrides from `config` and the project
+    ///
+    /// A `.kimi/prompts/<name>.md` file in `work_dir` wins over the config.
+    pub fn load(config: &PromptsConfig, work_dir: &Path) -> Self {
+        let mut templates = Self::default();
+        for name in PROMPT_NAMES {
+            let configured = match *name {
+                "system" => config.system.clone(),
+                "init" => config.init.clone(),
+                "compact" => config.compact.clone(),
+                "commit" => config.commit.clone(),
+                _ => None,
+            };
+            let file = Self::project_dir(work_dir).join(format!("{}.md", name));
+            let project = match std::fs::read_to_string(&file) {
+                Ok(content) => {
+                    info!("Using the {} prompt from {:?}", name, file);
+                    Some(content)
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    warn!("Failed to read prompt override {:?}: {}", file, e);
+                    None
+                }
+            };
+            if let Some(template) = project.or(configured) {
+                *templates.get_mut(name).expect("known prompt name") = template;
+            }
+        }
+        templates
+    }
+
+    /// Directory of a project's prompt overrides
+    pub fn project_dir(work_dir: &Path) -> PathBuf {
+        work_dir.join(".kimi").join("prompts")
+    }
+
+    /// Template for a prompt name
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match name {
+            "system" => Some(&self.system),
+            "init" => Some(&self.init),
+            "compact" => Some(&self.compact),
+            "commit" => Some(&self.commit),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, name: &str) -> Option<&mut String> {
+        match name {
+            "system" => Some(&mut self.system),
+            "init" => Some(&mut self.init),
+            "compact" => Some(&mut self.compact),
+            "commit" => Some(&mut self.commit),
+            _ => None,
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -27,4 +206,42 @@ mod tests {
         assert!(!DEFAULT_SYSTEM.is_empty());
         assert!(DEFAULT_SYSTEM.contains("Kimi"));
     }
+
+    #[test]
+    fn test_render() {
+        let vars = PromptVars::default().with("os", "linux").with("model", "kimi-k2");
+        assert_eq!(render("On {{os}}, using {{ model }}.", &vars), "On linux, using kimi-k2.");
+        assert_eq!(render("{{unknown}} and {{os", &vars), "{{unknown}} and {{os");
+        assert_eq!(render("{{{os}}}", &vars), "{{{os}}}");
+
+        let env = PromptVars::from_env();
+        assert_eq!(env.get("os"), Some(std::env::consts::OS));
+        let init = render(INIT, &env);
+        assert!(!init.contains("{{cwd}}"));
+    }
+
+    #[test]
+    fn test_load_overrides() {
+        let temp = tempfile::tempdir().unwrap();
+        let config = PromptsConfig {
+            system: Some("You are {{model}}.".to_string()),
+            init: Some("Configured init".to_string()),
+            compact: None,
+            commit: None,
+        };
+        let templates = PromptTemplates::load(&config, temp.path());
+        assert_eq!(templates.system, "You are {{model}}.");
+        assert_eq!(templates.init, "Configured init");
+        assert_eq!(templates.compact, COMPACT);
+
+        let dir = PromptTemplates::project_dir(temp.path());
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("init.md"), "Project init").unwrap();
+        let templates = PromptTemplates::load(&config, temp.path());
+        assert_eq!(templates.get("init"), Some("Project init"));
+        assert_eq!(templates.get("system"), Some("You are {{model}}."));
+
+        let templates = PromptTemplates::load(&PromptsConfig::default(), tempfile::tempdir().unwrap().path());
+        assert_eq!(templates, PromptTemplates::default());
+    }
 }
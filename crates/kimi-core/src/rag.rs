@@ -0,0 +1,462 @@
+//! Retrieval over the workspace
+//!
+//! [`WorkspaceIndex`] splits the text files of a workspace into overlapping
+//! line chunks, embeds them with an [`EmbeddingProvider`] and stores the
+//! vectors in `.kimi/index/embeddings.json`. Refreshing only re-embeds files
+//! whose size or modification time changed. A [`Retriever`] refreshes the
+//! index and returns the chunks most similar to a query; the soul injects
+//! them into the system prompt for each user message, and the agent can
+//! search on its own with the `SearchCodebase` tool.
+
+use kosong_rs::embedding::cosine_similarity;
+use kosong_rs::{ChatError, EmbeddingProvider};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::UNIX_EPOCH;
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// Lines per chunk
+pub const CHUNK_LINES: usize = 40;
+
+/// Lines shared by consecutive chunks
+pub const CHUNK_OVERLAP: usize = 8;
+
+/// Largest file indexed
+pub const MAX_FILE_BYTES: u64 = 256 * 1024;
+
+/// Chunks embedded per request
+const EMBED_BATCH: usize = 64;
+
+/// Directories never indexed, besides hidden ones
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "dist", "build", "vendor", "__pycache__"];
+
+/// A chunk of a file returned by a search
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    /// Path relative to the workspace root
+    pub path: PathBuf,
+    /// First line, starting at 1
+    pub start_line: usize,
+    /// Last line, inclusive
+    pub end_line: usize,
+    pub text: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
+
+impl SearchHit {
+    /// The hit as a fenced block headed by its location
+    pub fn render(&self) -> String {
+        format!(
+            "{}:{}-{}\n```\n{}\n```",
+            self.path.display(),
+            self.start_line,
+            self.end_line,
+            self.text.trim_end()
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Chunk {
+    start_line: usize,
+    end_line: usize,
+    text: String,
+    vector: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FileEntry {
+    size: u64,
+    modified: u64,
+    chunks: Vec<Chunk>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexData {
+    model: String,
+    files: BTreeMap<PathBuf, FileEntry>,
+}
+
+/// What a refresh changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RefreshStats {
+    /// Files embedded again
+    pub updated: usize,
+    /// Files dropped from the index
+    pub removed: usize,
+}
+
+/// Embeddings of a workspace's files, stored on disk
+#[derive(Debug)]
+pub struct WorkspaceIndex {
+    root: PathBuf,
+    path: PathBuf,
+    data: IndexData,
+}
+
+impl WorkspaceIndex {
+    /// Open the index of `root` stored at `path`
+    ///
+    /// A missing index, or one built with another model, starts out empty.
+    pub fn open(root: impl Into<PathBuf>, path: impl Into<PathBuf>, model: &str) -> Result<Self, RagError> {
+        let path = path.into();
+        let data = match std::fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str::<IndexData>(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => IndexData::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let data = if data.model == model {
+            data
+        } else {
+            IndexData {
+                model: model.to_string(),
+                files: BTreeMap::new(),
+            }
+        };
+        Ok(Self {
+            root: root.into(),
+            path,
+            data,
+        })
+    }
+
+    /// Where the index of `root` is stored by default
+    pub fn default_path(root: &Path) -> PathBuf {
+        root.join(".kimi").join("index").join("embeddings.json")
+    }
+
+    /// Number of indexed chunks
+    pub fn chunk_count(&self) -> usize {
+        self.data.files.values().map(|f| f.chunks.len()).sum()
+    }
+
+    /// Re-embed changed files, drop deleted ones and save the index
+    pub async fn refresh(&mut self, provider: &dyn EmbeddingProvider) -> Result<RefreshStats, RagError> {
+        let mut stats = RefreshStats::default();
+        let mut seen = Vec::new();
+        for path in workspace_files(&self.root) {
+            let Ok(metadata) = std::fs::metadata(&path) else {
+                continue;
+            };
+            let relative = path.strip_prefix(&self.root).unwrap_or(&path).to_path_buf();
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_secs());
+            seen.push(relative.clone());
+            let unchanged = self
+                .data
+                .files
+                .get(&relative)
+                .is_some_and(|entry| entry.size == metadata.len() && entry.modified == modified);
+            if unchanged {
+                continue;
+            }
+            // Binary files are skipped
+            let Ok(text) = std::fs::read_to_string(&path) else {
+                self.data.files.remove(&relative);
+                continue;
+            };
+            let mut chunks = chunk_lines(&text);
+            let texts: Vec<String> = chunks
+                .iter()
+                .map(|c| format!("{}\n{}", relative.display(), c.text))
+                .collect();
+            for (batch, texts) in chunks.chunks_mut(EMBED_BATCH).zip(texts.chunks(EMBED_BATCH)) {
+                let vectors = provider.embed(texts).await?;
+                for (chunk, vector) in batch.iter_mut().zip(vectors) {
+                    chunk.vector = vector;
+                }
+            }
+            debug!("Indexed {:?} as {} chunks", relative, chunks.len());
+            self.data.files.insert(
+                relative,
+                FileEntry {
+                    size: metadata.len(),
+                    modified,
+                    chunks,
+                },
+            );
+            stats.updated += 1;
+        }
+
+        let before = self.data.files.len();
+        self.data.files.retain(|path, _| seen.contains(path));
+        stats.removed = before - self.data.files.len();
+        if stats != RefreshStats::default() {
+            info!(
+                "Workspace index refreshed: {} files updated, {} removed",
+                stats.updated, stats.removed
+            );
+            self.save()?;
+        }
+        Ok(stats)
+    }
+
+    /// The `top_k` chunks most similar to `query`
+    pub async fn search(
+        &self,
+        provider: &dyn EmbeddingProvider,
+        query: &str,
+        top_k: usize,
+    ) -> Result<Vec<SearchHit>, RagError> {
+        if self.data.files.is_empty() || top_k == 0 {
+            return Ok(Vec::new());
+        }
+        let query = provider
+            .embed(&[query.to_string()])
+            .await?
+            .pop()
+            .ok_or_else(|| RagError::Embedding(ChatError::Parse("No embedding returned".to_string())))?;
+        let mut hits: Vec<SearchHit> = self
+            .data
+            .files
+            .iter()
+            .flat_map(|(path, entry)| {
+                entry.chunks.iter().map(|chunk| SearchHit {
+                    path: path.clone(),
+                    start_line: chunk.start_line,
+                    end_line: chunk.end_line,
+                    text: chunk.text.clone(),
+                    score: cosine_similarity(&query, &chunk.vector),
+                })
+            })
+            .collect();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+
+    fn save(&self) -> Result<(), RagError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string(&self.data)?)?;
+        Ok(())
+    }
+}
+
+/// Shared access to a workspace index for retrieval
+#[derive(Clone)]
+pub struct Retriever {
+    index: Arc<Mutex<WorkspaceIndex>>,
+    provider: Arc<dyn EmbeddingProvider>,
+    /// Chunks returned per query
+    pub top_k: usize,
+    /// Chunks scoring below this are left out of the injected context
+    pub min_score: f32,
+}
+
+impl std::fmt::Debug for Retriever {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Retriever")
+            .field("model", &self.provider.model_name())
+            .field("top_k", &self.top_k)
+            .field("min_score", &self.min_score)
+            .finish()
+    }
+}
+
+impl Retriever {
+    pub fn new(index: WorkspaceIndex, provider: Arc<dyn EmbeddingProvider>) -> Self {
+        Self {
+            index: Arc::new(Mutex::new(index)),
+            provider,
+            top_k: 5,
+            min_score: 0.3,
+        }
+    }
+
+    /// Refresh the index and search it for `query`
+    pub async fn search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>, RagError> {
+        let mut index = self.index.lock().await;
+        index.refresh(self.provider.as_ref()).await?;
+        index.search(self.provider.as_ref(), query, top_k).await
+    }
+
+    /// The relevant chunks to inject for a user message
+    pub async fn retrieve(&self, query: &str) -> Result<Vec<SearchHit>, RagError> {
+        let mut hits = self.search(query, self.top_k).await?;
+        hits.retain(|hit| hit.score >= self.min_score);
+        Ok(hits)
+    }
+}
+
+/// Render retrieved chunks as a system prompt section
+pub fn render_hits(hits: &[SearchHit]) -> String {
+    if hits.is_empty() {
+        return String::new();
+    }
+    let mut out = String::from(
+        "# Relevant Code\n\n\
+         These workspace excerpts may be relevant to the user's request. \
+         They may be out of date, so read the files before relying on details.",
+    );
+    for hit in hits {
+        out.push_str("\n\n");
+        out.push_str(&hit.render());
+    }
+    out
+}
+
+/// Split `text` into chunks of [`CHUNK_LINES`] lines overlapping by [`CHUNK_OVERLAP`]
+fn chunk_lines(text: &str) -> Vec<Chunk> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let end = (start + CHUNK_LINES).min(lines.len());
+        let text = lines[start..end].join("\n");
+        if !text.trim().is_empty() {
+            chunks.push(Chunk {
+                start_line: start + 1,
+                end_line: end,
+                text,
+                vector: Vec::new(),
+            });
+        }
+        if end == lines.len() {
+            break;
+        }
+        start = end - CHUNK_OVERLAP;
+    }
+    chunks
+}
+
+/// Files of the workspace that are worth indexing
+fn workspace_files(root: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') {
+                continue;
+            }
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if !SKIP_DIRS.contains(&name.as_ref()) {
+                    dirs.push(entry.path());
+                }
+            } else if file_type.is_file() && entry.metadata().is_ok_and(|m| m.len() <= MAX_FILE_BYTES) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Retrieval errors
+#[derive(Debug, Error)]
+pub enum RagError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Embedding error: {0}")]
+    Embedding(#[from] ChatError),
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Embeds texts as counts of a few keywords, and counts embedded texts
+    #[derive(Debug, Default)]
+    pub struct KeywordEmbeddings {
+        pub embedded: AtomicUsize,
+    }
+
+    const KEYWORDS: &[&str] = &["parser", "network", "database"];
+
+    #[async_trait::async_trait]
+    impl EmbeddingProvider for KeywordEmbeddings {
+        fn model_name(&self) -> &str {
+            "keywords"
+        }
+
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, ChatError> {
+            self.embedded.fetch_add(inputs.len(), Ordering::SeqCst);
+            Ok(inputs
+                .iter()
+                .map(|text| KEYWORDS.iter().map(|k| text.matches(k).count() as f32).collect())
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::KeywordEmbeddings;
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_chunk_lines() {
+        let text = (1..=100).map(|n| format!("line {}", n)).collect::<Vec<_>>().join("\n");
+        let ranges: Vec<(usize, usize)> = chunk_lines(&text).iter().map(|c| (c.start_line, c.end_line)).collect();
+        assert_eq!(ranges, vec![(1, 40), (33, 72), (65, 100)]);
+        assert!(chunk_lines("\n\n").is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_refresh_and_search() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join("src/parser.rs"), "// parser\nfn parse() {}\n").unwrap();
+        std::fs::write(root.join("src/net.rs"), "// network client\n").unwrap();
+        std::fs::write(root.join("target/parser.rs"), "parser parser").unwrap();
+        std::fs::write(root.join(".hidden"), "parser").unwrap();
+
+        let provider = KeywordEmbeddings::default();
+        let path = WorkspaceIndex::default_path(root);
+        let mut index = WorkspaceIndex::open(root, &path, "keywords").unwrap();
+        let stats = index.refresh(&provider).await.unwrap();
+        assert_eq!(stats, RefreshStats { updated: 2, removed: 0 });
+        assert_eq!(index.chunk_count(), 2);
+
+        let hits = index.search(&provider, "how does the network work", 1).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].path, PathBuf::from("src/net.rs"));
+        assert_eq!((hits[0].start_line, hits[0].end_line), (1, 1));
+        assert!(render_hits(&hits).ends_with("src/net.rs:1-1\n```\n// network client\n```"));
+
+        // Unchanged files are not embedded again, and the index is reloaded from disk
+        let mut index = WorkspaceIndex::open(root, &path, "keywords").unwrap();
+        assert_eq!(index.chunk_count(), 2);
+        std::fs::remove_file(root.join("src/net.rs")).unwrap();
+        let before = provider.embedded.load(Ordering::SeqCst);
+        let stats = index.refresh(&provider).await.unwrap();
+        assert_eq!(stats, RefreshStats { updated: 0, removed: 1 });
+        assert_eq!(provider.embedded.load(Ordering::SeqCst), before);
+
+        // Another model starts from scratch
+        assert_eq!(WorkspaceIndex::open(root, &path, "other").unwrap().chunk_count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retriever_filters_by_score() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("db.rs"), "// database\n").unwrap();
+        let index = WorkspaceIndex::open(temp.path(), temp.path().join("index.json"), "keywords").unwrap();
+        let retriever = Retriever::new(index, Arc::new(KeywordEmbeddings::default()));
+        assert_eq!(retriever.retrieve("the database schema").await.unwrap().len(), 1);
+        assert!(retriever.retrieve("the parser").await.unwrap().is_empty());
+    }
+}
@@ -1,5 +1,6 @@
 //! Session management for agent conversations
 
+use crate::types::Role;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
@@ -15,6 +16,10 @@ pub struct Session {
     pub context_file: PathBuf,
     pub wire_file: PathBuf,
     pub created_at: DateTime<Utc>,
+    /// Name given with `/session new` or `--session`; `None` for sessions
+    /// known only by their ID
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
 }
 
 impl Session {
@@ -29,6 +34,7 @@ impl Session {
             context_file: session_dir.join("context.json"),
             wire_file: session_dir.join("wire.jsonl"),
             created_at: Utc::now(),
+            name: None,
         }
     }
 
@@ -42,6 +48,7 @@ impl Session {
             context_file: session_dir.join("context.json"),
             wire_file: session_dir.join("wire.jsonl"),
             created_at: Utc::now(),
+            name: None,
         }
     }
 
@@ -83,6 +90,12 @@ impl Session {
             .join(self.id.to_string())
     }
 
+    /// Journal of the file changes tools made in the session, for undoing
+    /// them after it is resumed
+    pub fn edits_file(&self) -> PathBuf {
+        self.session_dir().join("edits.jsonl")
+    }
+
     /// Get the session ID as a string
     pub fn id_string(&self) -> String {
         self.id.to_string()
@@ -93,6 +106,11 @@ impl Session {
         self.id.to_string()[..8].to_string()
     }
 
+    /// The session's name, or its short ID if it has none
+    pub fn display_name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.short_id())
+    }
+
     /// Check if the session directory exists
     pub fn exists(&self) -> bool {
         self.session_dir().exists()
@@ -155,10 +173,137 @@ impl Session {
         }
 
         // Sort by creation time, newest first
-        sessions.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        sessions.sort_by_key(|s| std::cmp::Reverse(s.created_at));
         
         Ok(sessions)
     }
+
+    /// Summaries of all sessions in the working directory, most recently
+    /// active first
+    pub fn list_summaries(work_dir: &Path) -> Result<Vec<SessionSummary>, SessionError> {
+        let mut summaries: Vec<SessionSummary> = Self::list_all(work_dir)?.iter().map(Self::summary).collect();
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(summaries)
+    }
+
+    /// Find a session by its full ID or a unique prefix of it, such as
+    /// the short ID
+    pub fn find(work_dir: &Path, id: &str) -> Result<Self, SessionError> {
+        if let Ok(uuid) = Uuid::parse_str(id) {
+            return Self::load(work_dir.to_path_buf(), uuid);
+        }
+        let mut matches = Self::list_all(work_dir)?
+            .into_iter()
+            .filter(|session| !id.is_empty() && session.id_string().starts_with(id));
+        match (matches.next(), matches.next()) {
+            (Some(session), None) => Ok(session),
+            (Some(_), Some(_)) => Err(SessionError::Ambiguous(id.to_string())),
+            (None, _) => Err(SessionError::NotFound(id.to_string())),
+        }
+    }
+
+    /// What a session list shows about this session
+    ///
+    /// A context that cannot be read counts as empty.
+    pub fn summary(&self) -> SessionSummary {
+        let context = crate::context::Context::load(self.context_file.clone()).ok();
+        let title = context.as_ref().and_then(|context| {
+            context
+                .messages()
+                .iter()
+                .find(|message| matches!(message.role, Role::User))
+                .and_then(|message| message.content.lines().find(|line| !line.trim().is_empty()))
+                .map(|line| line.trim().to_string())
+        });
+        let updated_at = std::fs::metadata(&self.context_file)
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or(self.created_at);
+        SessionSummary {
+            session: self.clone(),
+            title,
+            message_count: context.map_or(0, |context| context.message_count()),
+            updated_at,
+        }
+    }
+}
+
+/// Named sessions of a working directory
+///
+/// Sessions are looked up by name first and then, like [`Session::find`],
+/// by their full ID or a unique prefix of it, so unnamed sessions can be
+/// switched to and deleted too.
+#[derive(Debug, Clone)]
+pub struct SessionManager {
+    work_dir: PathBuf,
+}
+
+impl SessionManager {
+    /// Create a manager for the sessions of `work_dir`
+    pub fn new(work_dir: impl Into<PathBuf>) -> Self {
+        Self { work_dir: work_dir.into() }
+    }
+
+    /// The working directory whose sessions are managed
+    pub fn work_dir(&self) -> &Path {
+        &self.work_dir
+    }
+
+    /// All sessions, newest first
+    pub fn list(&self) -> Result<Vec<Session>, SessionError> {
+        Session::list_all(&self.work_dir)
+    }
+
+    /// Create and initialize a session called `name`
+    pub fn create(&self, name: &str) -> Result<Session, SessionError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(SessionError::InvalidName(name.to_string()));
+        }
+        if self.list()?.iter().any(|session| session.name.as_deref() == Some(name)) {
+            return Err(SessionError::NameTaken(name.to_string()));
+        }
+        let mut session = Session::new(self.work_dir.clone());
+        session.name = Some(name.to_string());
+        session.initialize()?;
+        info!("Session {} created as {}", session.id, name);
+        Ok(session)
+    }
+
+    /// The session called `name`, or else the one `name` is the ID or a
+    /// unique ID prefix of
+    pub fn get(&self, name: &str) -> Result<Session, SessionError> {
+        if let Some(session) = self.list()?.into_iter().find(|session| session.name.as_deref() == Some(name)) {
+            return Ok(session);
+        }
+        Session::find(&self.work_dir, name)
+    }
+
+    /// The session called `name`, created if there is none
+    pub fn get_or_create(&self, name: &str) -> Result<Session, SessionError> {
+        match self.get(name) {
+            Err(SessionError::NotFound(_)) => self.create(name),
+            result => result,
+        }
+    }
+
+    /// Delete the session `name` refers to, returning it
+    pub fn delete(&self, name: &str) -> Result<Session, SessionError> {
+        let session = self.get(name)?;
+        session.delete()?;
+        Ok(session)
+    }
+}
+
+/// What a session list shows about a session
+#[derive(Debug, Clone)]
+pub struct SessionSummary {
+    pub session: Session,
+    /// First line of the first user message
+    pub title: Option<String>,
+    pub message_count: usize,
+    /// When the conversation was last saved
+    pub updated_at: DateTime<Utc>,
 }
 
 /// Session-related errors
@@ -170,11 +315,18 @@ pub enum SessionError {
     Json(#[from] serde_json::Error),
     #[error("Session not found: {0}")]
     NotFound(String),
+    #[error("Session ID {0} matches more than one session")]
+    Ambiguous(String),
+    #[error("A session named {0} already exists")]
+    NameTaken(String),
+    #[error("Invalid session name: '{0}'; names cannot be empty or contain spaces")]
+    InvalidName(String),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::types::Message;
 
     #[test]
     fn test_session_new() {
@@ -209,4 +361,53 @@ mod tests {
         assert!(dir.to_string_lossy().contains(".kimi/sessions"));
         assert!(dir.to_string_lossy().contains(&session.id.to_string()));
     }
+
+    #[test]
+    fn test_find_and_summarize() {
+        let temp = tempfile::tempdir().unwrap();
+        let work_dir = temp.path().to_path_buf();
+        let session = Session::new(work_dir.clone());
+        session.initialize().unwrap();
+        let empty = Session::new(work_dir.clone());
+        empty.initialize().unwrap();
+
+        let mut context = crate::context::Context::load(session.context_file.clone()).unwrap();
+        let message = |role, content: &str| Message { role, content: content.to_string(), metadata: None };
+        context.add_message(message(Role::User, "\nFix the flaky login test\nIt fails on CI"));
+        context.add_message(message(Role::Assistant, "On it."));
+        context.save().unwrap();
+
+        let summary = Session::find(&work_dir, &session.short_id()).unwrap().summary();
+        assert_eq!(summary.title.as_deref(), Some("Fix the flaky login test"));
+        assert_eq!(summary.message_count, 2);
+        assert_eq!(empty.summary().title, None);
+        assert_eq!(Session::find(&work_dir, &session.id_string()).unwrap().id, session.id);
+        assert!(matches!(Session::find(&work_dir, "zzz"), Err(SessionError::NotFound(_))));
+        assert!(matches!(Session::find(&work_dir, ""), Err(SessionError::NotFound(_))));
+        assert_eq!(Session::list_summaries(&work_dir).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_session_manager() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = SessionManager::new(temp.path());
+        let api = manager.create("api").unwrap();
+        manager.create("docs").unwrap();
+        let unnamed = Session::new(temp.path().to_path_buf());
+        unnamed.initialize().unwrap();
+
+        assert_eq!(manager.list().unwrap().len(), 3);
+        assert_eq!(manager.get("api").unwrap().id, api.id);
+        assert_eq!(manager.get("api").unwrap().display_name(), "api");
+        assert_eq!(manager.get(&unnamed.short_id()).unwrap().display_name(), unnamed.short_id());
+        assert!(matches!(manager.create("api"), Err(SessionError::NameTaken(_))));
+        assert!(matches!(manager.create("my task"), Err(SessionError::InvalidName(_))));
+        assert_eq!(manager.get_or_create("api").unwrap().id, api.id);
+        assert_eq!(manager.get_or_create("ops").unwrap().name.as_deref(), Some("ops"));
+
+        assert_eq!(manager.delete("api").unwrap().id, api.id);
+        assert!(!api.exists());
+        assert!(matches!(manager.get("api"), Err(SessionError::NotFound(_))));
+        assert_eq!(manager.list().unwrap().len(), 3);
+    }
 }
@@ -0,0 +1,190 @@
+//! Automatic skill activation
+//!
+//! Scores discovered skills against the user's request by keyword overlap
+//! with each skill's name and description, and picks the best match so its
+//! instructions can be added to the system prompt for that turn.
+
+use std::collections::HashSet;
+
+use super::{Skill, SkillType};
+
+/// Words too common to say anything about relevance
+const STOPWORDS: &[&str] = &[
+    "about", "after", "all", "and", "any", "are", "but", "can", "could", "for", "from", "get",
+    "has", "have", "how", "into", "its", "just", "let", "make", "more", "not", "now", "off",
+    "one", "only", "our", "out", "please", "should", "some", "than", "that", "the", "them",
+    "then", "there", "these", "this", "use", "used", "uses", "using", "want", "was", "what",
+    "when", "where", "which", "while", "will", "with", "would", "you", "your",
+];
+
+/// A skill selected for a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillMatch {
+    /// Index into the skill list that was searched
+    pub index: usize,
+    /// Relevance between 0.0 and 1.0
+    pub score: f64,
+}
+
+/// Keyword matcher that picks the most relevant skill for a request
+#[derive(Debug, Clone)]
+pub struct SkillMatcher {
+    /// Minimum score for a skill to activate
+    pub min_score: f64,
+    /// Minimum number of keywords shared with the request
+    pub min_keywords: usize,
+}
+
+impl Default for SkillMatcher {
+    fn default() -> Self {
+        Self {
+            min_score: 0.3,
+            min_keywords: 2,
+        }
+    }
+}
+
+impl SkillMatcher {
+    /// Create a matcher with the default thresholds
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Score one skill against a request
+    ///
+    /// The score is the fraction of the skill's keywords that appear in the
+    /// request. Mentioning the skill by name counts as a full match.
+    pub fn score(&self, skill: &Skill, request: &str) -> f64 {
+        let request_words = keywords(request);
+        if request_words.is_empty() {
+            return 0.0;
+        }
+
+        let name_words = keywords(&skill.name);
+        if !name_words.is_empty() && name_words.is_subset(&request_words) {
+            return 1.0;
+        }
+
+        let skill_words: HashSet<String> = name_words
+            .into_iter()
+            .chain(keywords(&skill.description))
+            .collect();
+        if skill_words.is_empty() {
+            return 0.0;
+        }
+
+        let shared = skill_words.intersection(&request_words).count();
+        if shared < self.min_keywords {
+            return 0.0;
+        }
+        shared as f64 / skill_words.len() as f64
+    }
+
+    /// Pick the best standard skill for a request, if any clears the threshold
+    ///
+    /// Flow skills are never activated automatically; they are run with `/flow:<name>`.
+    pub fn select(&self, skills: &[Skill], request: &str) -> Option<SkillMatch> {
+        skills
+            .iter()
+            .enumerate()
+            .filter(|(_, skill)| skill.skill_type == SkillType::Standard)
+            .map(|(index, skill)| SkillMatch {
+                index,
+                score: self.score(skill, request),
+            })
+            .filter(|m| m.score >= self.min_score)
+            // Earlier skills win ties, since project skills are discovered first
+            .fold(None, |best: Option<SkillMatch>, m| match best {
+                Some(best) if best.score >= m.score => Some(best),
+                _ => Some(m),
+            })
+    }
+}
+
+/// Render a skill's instructions as a system prompt section
+pub fn skill_prompt(skill: &Skill) -> String {
+    format!(
+        "# Active Skill: {}\n\n{}\n\nThe skill's files are in `{}`.\n\n{}",
+        skill.name,
+        skill.description,
+        skill.dir.display(),
+        skill.instructions
+    )
+}
+
+/// Lowercased words of three or more letters, minus stopwords and plural `s`
+fn keywords(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .map(str::to_lowercase)
+        .filter(|word| word.len() >= 3 && !STOPWORDS.contains(&word.as_str()))
+        .map(|word| match word.strip_suffix('s') {
+            Some(stem) if stem.len() >= 3 && !stem.ends_with('s') => stem.to_string(),
+            _ => word,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::skill::SkillDiscovery;
+    use std::path::PathBuf;
+
+    fn skill(name: &str, description: &str) -> Skill {
+        let content = format!("---\nname: {name}\ndescription: {description}\n---\n\nSteps for {name}.");
+        SkillDiscovery::parse_skill(name, &content, PathBuf::from("/skills").join(name)).unwrap()
+    }
+
+    #[test]
+    fn test_keywords() {
+        let words = keywords("Write the release notes for THIS crate's changes");
+        assert!(words.contains("release"));
+        assert!(words.contains("note"));
+        assert!(words.contains("change"));
+        assert!(!words.contains("the"));
+        assert!(!words.contains("for"));
+    }
+
+    #[test]
+    fn test_select_by_description() {
+        let skills = vec![
+            skill("pdf", "Extract text and tables from PDF documents"),
+            skill("changelog", "Write release notes from git commits"),
+        ];
+        let matcher = SkillMatcher::new();
+
+        let selected = matcher.select(&skills, "Can you write release notes for the last commits?").unwrap();
+        assert_eq!(selected.index, 1);
+        assert!(selected.score >= matcher.min_score);
+
+        assert!(matcher.select(&skills, "Fix the failing unit test").is_none());
+    }
+
+    #[test]
+    fn test_select_by_name() {
+        let skills = vec![skill("terraform", "Infrastructure changes with plan review")];
+        let selected = SkillMatcher::new().select(&skills, "update the terraform module").unwrap();
+        assert_eq!(selected.score, 1.0);
+    }
+
+    #[test]
+    fn test_single_shared_word_does_not_activate() {
+        let skills = vec![skill("docs", "Documentation style guide")];
+        assert!(SkillMatcher::new().select(&skills, "what is our style?").is_none());
+    }
+
+    #[test]
+    fn test_flow_skills_are_skipped() {
+        let content = "---\nname: deploy\ndescription: Deploy the service\ntype: flow\n---\n\n```mermaid\nflowchart TD\n    Begin([Begin]) --> End([End])\n```\n";
+        let flow = SkillDiscovery::parse_skill("deploy", content, PathBuf::from("/skills/deploy")).unwrap();
+        assert!(SkillMatcher::new().select(&[flow], "deploy the service").is_none());
+    }
+
+    #[test]
+    fn test_skill_prompt() {
+        let prompt = skill_prompt(&skill("pdf", "Extract text from PDF documents"));
+        assert!(prompt.starts_with("# Active Skill: pdf\n\nExtract text from PDF documents"));
+        assert!(prompt.contains("/skills/pdf"));
+        assert!(prompt.ends_with("Steps for pdf."));
+    }
+}
@@ -123,6 +123,7 @@ impl SkillDiscovery {
             skill_type,
             dir,
             flow,
+            instructions: rest.trim().to_string(),
         })
     }
 }
@@ -148,6 +149,7 @@ This is a test skill.
         assert_eq!(skill.description, "A test skill");
         assert_eq!(skill.skill_type, SkillType::Standard);
         assert!(skill.flow.is_none());
+        assert_eq!(skill.instructions, "# Test Skill\n\nThis is a test skill.");
     }
 
     #[test]
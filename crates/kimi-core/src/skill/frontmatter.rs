@@ -1,5 +1,6 @@
 //! YAML frontmatter parser for SKILL.md files
 
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
 /// Parsed frontmatter from a SKILL.md file
@@ -24,24 +25,35 @@ pub struct Frontmatter {
 /// # Content
 /// ```
 pub fn parse_frontmatter(content: &str) -> Result<(Frontmatter, &str), String> {
+    parse_frontmatter_as(content)
+}
+
+/// Parse YAML frontmatter into any deserializable type
+///
+/// Used by other markdown-with-frontmatter files, such as custom slash commands.
+/// Returns `T::default()` and the whole content when there is no frontmatter.
+pub fn parse_frontmatter_as<T>(content: &str) -> Result<(T, &str), String>
+where
+    T: DeserializeOwned + Default,
+{
     let trimmed = content.trim_start();
     
     // Check if content starts with frontmatter delimiter
     if !trimmed.starts_with("---") {
-        return Ok((Frontmatter::default(), content));
+        return Ok((T::default(), content));
     }
     
     // Find the end of frontmatter
     let after_open = &trimmed[3..];
     let Some(end_pos) = after_open.find("---") else {
-        return Ok((Frontmatter::default(), content));
+        return Ok((T::default(), content));
     };
     
     let yaml_content = &after_open[..end_pos].trim();
     let rest = &after_open[end_pos + 3..];
     
     // Parse YAML
-    let frontmatter: Frontmatter = serde_yaml::from_str(yaml_content)
+    let frontmatter: T = serde_yaml::from_str(yaml_content)
         .map_err(|e| format!("Failed to parse YAML frontmatter: {}", e))?;
     
     Ok((frontmatter, rest))
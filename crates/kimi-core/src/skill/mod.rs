@@ -2,13 +2,17 @@
 
 use std::path::PathBuf;
 
+pub mod activation;
 pub mod discovery;
 pub mod flow;
 pub mod frontmatter;
+pub mod validate;
 
+pub use activation::{SkillMatch, SkillMatcher};
 pub use discovery::SkillDiscovery;
 pub use flow::{Flow, FlowEdge, FlowNode, NodeType};
 pub use frontmatter::Frontmatter;
+pub use validate::{Severity, SkillCheck, SkillIssue};
 
 /// A discovered skill
 #[derive(Debug, Clone, PartialEq)]
@@ -18,6 +22,8 @@ pub struct Skill {
     pub skill_type: SkillType,
     pub dir: PathBuf,
     pub flow: Option<Flow>,
+    /// Markdown body of SKILL.md after the frontmatter
+    pub instructions: String,
 }
 
 /// Type of skill
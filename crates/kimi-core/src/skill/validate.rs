@@ -0,0 +1,340 @@
+//! Skill validation
+//!
+//! Checks a skill for problems that would only show up at runtime: missing
+//! frontmatter fields, flows that cannot be walked from Begin to End, and
+//! links to files that are not in the skill directory.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use super::frontmatter::parse_frontmatter;
+use super::{Flow, NodeType, Skill, SkillDiscovery, SkillError};
+
+/// How serious a validation issue is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The skill will not load or run correctly
+    Error,
+    /// The skill works but is probably not what the author intended
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a skill
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkillIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl SkillIssue {
+    fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for SkillIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+/// Result of checking one skill directory
+#[derive(Debug, Clone)]
+pub struct SkillCheck {
+    /// Directory containing SKILL.md
+    pub dir: PathBuf,
+    /// The parsed skill, if SKILL.md could be parsed
+    pub skill: Option<Skill>,
+    pub issues: Vec<SkillIssue>,
+}
+
+impl SkillCheck {
+    /// Whether any issue is an error
+    pub fn has_errors(&self) -> bool {
+        self.issues.iter().any(|i| i.severity == Severity::Error)
+    }
+}
+
+impl Skill {
+    /// Check the skill for problems
+    ///
+    /// Returns an empty list when the skill is valid.
+    pub fn validate(&self) -> Vec<SkillIssue> {
+        let mut issues = Vec::new();
+
+        if self.name.trim().is_empty() {
+            issues.push(SkillIssue::error("name is empty"));
+        }
+        if self.description.trim().is_empty() {
+            issues.push(SkillIssue::error("description is empty"));
+        }
+        if let Some(flow) = &self.flow {
+            issues.extend(validate_flow(flow));
+        } else if self.instructions.trim().is_empty() {
+            issues.push(SkillIssue::warning("SKILL.md has no instructions after the frontmatter"));
+        }
+
+        for target in linked_paths(&self.instructions) {
+            let path = self.dir.join(&target);
+            if !path.exists() {
+                issues.push(SkillIssue::error(format!("linked file {} does not exist", target)));
+            }
+        }
+
+        issues
+    }
+}
+
+impl SkillDiscovery {
+    /// Parse and validate the skill in `dir`
+    ///
+    /// Parse failures are reported as issues rather than errors, so a
+    /// broken skill still produces a report.
+    pub fn check(dir: &Path) -> SkillCheck {
+        let mut check = SkillCheck {
+            dir: dir.to_path_buf(),
+            skill: None,
+            issues: Vec::new(),
+        };
+
+        let content = match std::fs::read_to_string(dir.join("SKILL.md")) {
+            Ok(content) => content,
+            Err(e) => {
+                check.issues.push(SkillIssue::error(format!("cannot read SKILL.md: {}", e)));
+                return check;
+            }
+        };
+        let name = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+
+        if let Ok((frontmatter, _)) = parse_frontmatter(&content) {
+            if !content.trim_start().starts_with("---") {
+                check.issues.push(SkillIssue::error("SKILL.md has no YAML frontmatter"));
+            }
+            if let Some(kind) = frontmatter.skill_type.as_deref().filter(|k| *k != "flow" && *k != "standard") {
+                check.issues.push(SkillIssue::warning(format!(
+                    "unknown type '{}', treating the skill as standard",
+                    kind
+                )));
+            }
+        }
+
+        match Self::parse_skill(name, &content, dir.to_path_buf()) {
+            Ok(skill) => {
+                check.issues.extend(skill.validate());
+                check.skill = Some(skill);
+            }
+            Err(SkillError::MissingField(field)) => {
+                check.issues.push(SkillIssue::error(format!("frontmatter is missing required field '{}'", field)));
+            }
+            Err(e) => check.issues.push(SkillIssue::error(e.to_string())),
+        }
+        check
+    }
+}
+
+/// Check that a flow can be walked from Begin to End by the flow runner
+fn validate_flow(flow: &Flow) -> Vec<SkillIssue> {
+    let mut issues = Vec::new();
+    let mut outgoing: HashMap<&str, Vec<&super::FlowEdge>> = HashMap::new();
+    for edge in &flow.edges {
+        for id in [&edge.from, &edge.to] {
+            if !flow.nodes.contains_key(id) {
+                issues.push(SkillIssue::error(format!("edge refers to unknown node {}", id)));
+            }
+        }
+        outgoing.entry(edge.from.as_str()).or_default().push(edge);
+    }
+
+    let reachable = reachable_from(&flow.begin_id, &outgoing);
+    if !reachable.contains(flow.end_id.as_str()) {
+        issues.push(SkillIssue::error(format!(
+            "End node {} is not reachable from Begin node {}",
+            flow.end_id, flow.begin_id
+        )));
+    }
+
+    let mut ids: Vec<&String> = flow.nodes.keys().collect();
+    ids.sort();
+    for id in ids {
+        let node = &flow.nodes[id];
+        if !reachable.contains(id.as_str()) {
+            issues.push(SkillIssue::error(format!("node {} is not reachable from Begin", id)));
+        }
+
+        let edges = outgoing.get(id.as_str()).map(Vec::as_slice).unwrap_or_default();
+        match node.node_type {
+            NodeType::End => {}
+            _ if edges.is_empty() => {
+                issues.push(SkillIssue::error(format!("node {} has no outgoing edges", id)));
+            }
+            NodeType::Begin | NodeType::Task if edges.len() > 1 => {
+                issues.push(SkillIssue::error(format!(
+                    "node {} has {} outgoing edges; only decision nodes may branch",
+                    id,
+                    edges.len()
+                )));
+            }
+            NodeType::Decision if edges.len() > 1 => {
+                let unlabelled = edges.iter().any(|e| e.label.as_deref().is_none_or(|l| l.trim().is_empty()));
+                if unlabelled {
+                    issues.push(SkillIssue::error(format!("decision node {} has an unlabelled branch", id)));
+                }
+            }
+            NodeType::Decision => {
+                issues.push(SkillIssue::warning(format!("decision node {} has only one branch", id)));
+            }
+            _ => {}
+        }
+    }
+
+    issues
+}
+
+/// Ids of all nodes reachable from `start`, including itself
+fn reachable_from<'a>(start: &'a str, outgoing: &HashMap<&'a str, Vec<&'a super::FlowEdge>>) -> HashSet<&'a str> {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(id) = queue.pop_front() {
+        for edge in outgoing.get(id).into_iter().flatten() {
+            if seen.insert(edge.to.as_str()) {
+                queue.push_back(edge.to.as_str());
+            }
+        }
+    }
+    seen
+}
+
+/// Relative file paths linked from markdown, e.g. `[script](scripts/run.sh)`
+fn linked_paths(markdown: &str) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut rest = markdown;
+    while let Some(start) = rest.find("](") {
+        rest = &rest[start + 2..];
+        let Some(end) = rest.find(')') else {
+            break;
+        };
+        let target = rest[..end].split_whitespace().next().unwrap_or("");
+        let target = target.split('#').next().unwrap_or("");
+        let external = target.contains("://") || target.starts_with("mailto:");
+        if !target.is_empty() && !external && !paths.iter().any(|p| p == target) {
+            paths.push(target.to_string());
+        }
+        rest = &rest[end..];
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow_skill(diagram: &str) -> Skill {
+        let content = format!(
+            "---\nname: deploy\ndescription: Deploy\ntype: flow\n---\n\n```mermaid\nflowchart TD\n{}\n```\n",
+            diagram
+        );
+        SkillDiscovery::parse_skill("deploy", &content, PathBuf::from("/skills/deploy")).unwrap()
+    }
+
+    fn messages(issues: &[SkillIssue]) -> Vec<String> {
+        issues.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_valid_flow() {
+        let skill = flow_skill(
+            "    Begin([Begin]) --> Build[Build it]\n    Build --> Check{Passed?}\n    Check -->|Yes| End([End])\n    Check -->|No| Build",
+        );
+        assert!(skill.validate().is_empty());
+    }
+
+    #[test]
+    fn test_flow_end_unreachable() {
+        let skill = flow_skill("    Begin([Begin]) --> Build[Build it]\n    Build --> Build\n    Other[Other] --> End([End])");
+        let issues = messages(&skill.validate());
+        assert!(issues.contains(&"error: End node End is not reachable from Begin node Begin".to_string()));
+        assert!(issues.contains(&"error: node Other is not reachable from Begin".to_string()));
+    }
+
+    #[test]
+    fn test_flow_branching_rules() {
+        let skill = flow_skill(
+            "    Begin([Begin]) --> A[Do A]\n    Begin --> B[Do B]\n    A --> Check{Ok?}\n    B --> Check\n    Check --> End([End])\n    Check -->|No| A",
+        );
+        let issues = messages(&skill.validate());
+        assert!(issues.contains(&"error: node Begin has 2 outgoing edges; only decision nodes may branch".to_string()));
+        assert!(issues.contains(&"error: decision node Check has an unlabelled branch".to_string()));
+    }
+
+    #[test]
+    fn test_linked_paths() {
+        let markdown = "Run [the script](scripts/run.sh), see [docs](https://example.com) \
+                        and ![diagram](img/flow.png \"Flow\") or [section](#usage).";
+        assert_eq!(linked_paths(markdown), vec!["scripts/run.sh", "img/flow.png"]);
+    }
+
+    #[test]
+    fn test_check_reports_missing_links_and_fields() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("pdf");
+        std::fs::create_dir_all(dir.join("scripts")).unwrap();
+        std::fs::write(dir.join("scripts/extract.py"), "").unwrap();
+        std::fs::write(
+            dir.join("SKILL.md"),
+            "---\ndescription: Extract PDF text\n---\n\nUse [extract](scripts/extract.py) or [ocr](scripts/ocr.py).",
+        )
+        .unwrap();
+
+        let check = SkillDiscovery::check(&dir);
+        assert!(check.has_errors());
+        assert_eq!(messages(&check.issues), vec!["error: linked file scripts/ocr.py does not exist"]);
+
+        std::fs::write(dir.join("SKILL.md"), "---\nname: pdf\ntype: flowchart\n---\n\nBody").unwrap();
+        let check = SkillDiscovery::check(&dir);
+        assert!(check.skill.is_none());
+        assert_eq!(
+            messages(&check.issues),
+            vec![
+                "warning: unknown type 'flowchart', treating the skill as standard",
+                "error: frontmatter is missing required field 'description'",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_without_frontmatter() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("SKILL.md"), "# Just markdown").unwrap();
+        let check = SkillDiscovery::check(temp.path());
+        assert_eq!(
+            messages(&check.issues),
+            vec![
+                "error: SKILL.md has no YAML frontmatter",
+                "error: frontmatter is missing required field 'description'",
+            ]
+        );
+    }
+}
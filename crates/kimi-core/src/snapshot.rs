@@ -0,0 +1,164 @@
+//! File snapshots for checkpoints
+//!
+//! Before a tool first modifies a file after a checkpoint, the file's content
+//! is copied into a content-addressed store (keyed by SHA-256) and recorded on
+//! the checkpoint. Rewinding to the checkpoint writes those contents back, so
+//! the working tree returns to where it was when the checkpoint was taken.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::debug;
+
+/// Tools that modify the file named by their `path` argument
+pub const FILE_WRITE_TOOLS: &[&str] = &["WriteFile", "StrReplaceFile", "EditNotebookCell"];
+
+/// A file's state at a checkpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileSnapshot {
+    /// Absolute path of the file
+    pub path: PathBuf,
+    /// Hash of the stored content, or `None` if the file did not exist
+    pub hash: Option<String>,
+}
+
+/// Content-addressed store of file contents
+#[derive(Debug, Clone)]
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    /// Create a store that keeps contents in `dir`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The store for a project, under `.kimi/snapshots`
+    pub fn for_work_dir(work_dir: &Path) -> Self {
+        Self::new(work_dir.join(".kimi").join("snapshots"))
+    }
+
+    /// The store for a session, in its directory, so its checkpoints and
+    /// undo journal can still be restored when it is resumed
+    pub fn for_session(session: &crate::session::Session) -> Self {
+        Self::new(session.session_dir().join("snapshots"))
+    }
+
+    /// Directory holding the stored contents
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Store the current content of `path`
+    ///
+    /// Identical contents are stored once. A missing file is recorded as
+    /// such, so restoring the snapshot removes it again.
+    pub fn snapshot(&self, path: &Path) -> Result<FileSnapshot, SnapshotError> {
+        let path = std::path::absolute(path)?;
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(FileSnapshot { path, hash: None });
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let hash = format!("{:x}", Sha256::digest(&content));
+        let object = self.dir.join(&hash);
+        if !object.exists() {
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(&object, &content)?;
+            debug!("Stored snapshot of {:?} as {}", path, hash);
+        }
+        Ok(FileSnapshot { path, hash: Some(hash) })
+    }
+
+    /// The content recorded by `snapshot`, or `None` if the file did not
+    /// exist
+    pub fn read(&self, snapshot: &FileSnapshot) -> Result<Option<Vec<u8>>, SnapshotError> {
+        let Some(hash) = &snapshot.hash else {
+            return Ok(None);
+        };
+        match std::fs::read(self.dir.join(hash)) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(SnapshotError::Missing(hash.clone())),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Put a file back the way it was when `snapshot` was taken
+    pub fn restore(&self, snapshot: &FileSnapshot) -> Result<(), SnapshotError> {
+        match &snapshot.hash {
+            Some(hash) => {
+                let object = self.dir.join(hash);
+                if !object.exists() {
+                    return Err(SnapshotError::Missing(hash.clone()));
+                }
+                if let Some(parent) = snapshot.path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::copy(&object, &snapshot.path)?;
+            }
+            None => match std::fs::remove_file(&snapshot.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot-related errors
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Snapshot {0} is missing from the store")]
+    Missing(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_and_restore() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::for_work_dir(temp.path());
+        let file = temp.path().join("src/main.rs");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "fn main() {}").unwrap();
+
+        let snapshot = store.snapshot(&file).unwrap();
+        assert_eq!(store.snapshot(&file).unwrap(), snapshot);
+        assert_eq!(std::fs::read_dir(store.dir()).unwrap().count(), 1);
+
+        std::fs::write(&file, "fn main() { panic!() }").unwrap();
+        assert_eq!(store.read(&snapshot).unwrap().unwrap(), b"fn main() {}");
+        store.restore(&snapshot).unwrap();
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "fn main() {}");
+    }
+
+    #[test]
+    fn test_restore_missing_file_removes_it() {
+        let temp = tempfile::tempdir().unwrap();
+        let store = SnapshotStore::for_work_dir(temp.path());
+        let file = temp.path().join("new.txt");
+
+        let snapshot = store.snapshot(&file).unwrap();
+        assert_eq!(snapshot.hash, None);
+        assert_eq!(store.read(&snapshot).unwrap(), None);
+        std::fs::write(&file, "created later").unwrap();
+        store.restore(&snapshot).unwrap();
+        assert!(!file.exists());
+        store.restore(&snapshot).unwrap();
+
+        let lost = FileSnapshot {
+            path: file,
+            hash: Some("0".repeat(64)),
+        };
+        assert!(matches!(store.restore(&lost), Err(SnapshotError::Missing(_))));
+    }
+}
@@ -5,10 +5,13 @@
 //! for agent-to-agent task delegation.
 
 use crate::types::{Message, Role};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify, RwLock};
+use tokio::task::{JoinHandle, JoinSet};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
@@ -59,6 +62,13 @@ pub struct AgentConfig {
     pub temperature: Option<f64>,
     /// Maximum tokens to generate
     pub max_tokens: Option<usize>,
+    /// Skills (tags) this agent takes on in the labor market
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Tool profile limiting the tools this agent gets; `None` gives it
+    /// every tool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_profile: Option<String>,
 }
 
 impl Default for AgentConfig {
@@ -70,6 +80,8 @@ impl Default for AgentConfig {
             model: "default".to_string(),
             temperature: None,
             max_tokens: None,
+            capabilities: Vec::new(),
+            tool_profile: None,
         }
     }
 }
@@ -134,6 +146,24 @@ impl Agent {
         matches!(self.state().await, AgentState::Working { .. })
     }
 
+    /// Check if the agent can take on a new task
+    ///
+    /// Agents that finished or failed their last task are available again.
+    pub async fn is_available(&self) -> bool {
+        !matches!(self.state().await, AgentState::Working { .. } | AgentState::Paused)
+    }
+
+    /// Check if the agent has any of the skills a task requires
+    ///
+    /// Tasks without required skills can be taken by any agent.
+    pub fn can_take(&self, task: &MarketTask) -> bool {
+        task.required_skills.is_empty()
+            || task
+                .required_skills
+                .iter()
+                .any(|skill| self.config.capabilities.iter().any(|c| c.eq_ignore_ascii_case(skill)))
+    }
+
     /// Get the configuration
     pub fn config(&self) -> &AgentConfig {
         &self.config
@@ -163,6 +193,47 @@ pub struct Runtime {
     task_queue: Arc<Mutex<Vec<Task>>>,
     /// Execution statistics
     stats: Arc<RwLock<RuntimeStats>>,
+    /// Tasks taken off the queue, by ID
+    dispatched: Arc<RwLock<HashMap<String, Task>>>,
+    /// Wakes the scheduler when tasks or agents are added
+    wakeup: Arc<Notify>,
+}
+
+/// Runs runtime tasks on behalf of an agent
+#[async_trait]
+pub trait TaskExecutor: Send + Sync {
+    /// Execute `task` as `agent`, returning its result or an error message
+    async fn execute(&self, agent: &Agent, task: &Task) -> Result<String, String>;
+}
+
+/// Scheduler settings
+#[derive(Debug, Clone)]
+pub struct SchedulerConfig {
+    /// Maximum number of tasks running at the same time
+    pub max_concurrent: usize,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self { max_concurrent: 4 }
+    }
+}
+
+/// Handle to a scheduler started with [`Runtime::start`]
+#[derive(Debug)]
+pub struct SchedulerHandle {
+    shutdown: Arc<Notify>,
+    handle: JoinHandle<()>,
+}
+
+impl SchedulerHandle {
+    /// Stop dispatching new tasks and wait for running ones to finish
+    pub async fn stop(self) {
+        self.shutdown.notify_one();
+        if let Err(e) = self.handle.await {
+            warn!("Scheduler task failed: {}", e);
+        }
+    }
 }
 
 /// A task to be executed by an agent
@@ -208,6 +279,8 @@ impl Runtime {
             agents: Arc::new(RwLock::new(HashMap::new())),
             task_queue: Arc::new(Mutex::new(Vec::new())),
             stats: Arc::new(RwLock::new(RuntimeStats::default())),
+            dispatched: Arc::new(RwLock::new(HashMap::new())),
+            wakeup: Arc::new(Notify::new()),
         }
     }
 
@@ -217,6 +290,7 @@ impl Runtime {
         let mut agents = self.agents.write().await;
         agents.insert(agent_id.clone(), agent);
         debug!("Registered agent: {}", agent_id);
+        self.wakeup.notify_one();
     }
 
     /// Unregister an agent
@@ -237,6 +311,22 @@ impl Runtime {
         agents.values().cloned().collect()
     }
 
+    /// Find an available agent, other than `exclude`, that can take `task`
+    pub async fn find_agent_for(&self, task: &MarketTask, exclude: &str) -> Option<Agent> {
+        let mut candidates: Vec<Agent> = self.list_agents().await
+            .into_iter()
+            .filter(|agent| agent.id != exclude && agent.can_take(task))
+            .collect();
+        // Registration order is lost in the map; keep the choice stable
+        candidates.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        for agent in candidates {
+            if agent.is_available().await {
+                return Some(agent);
+            }
+        }
+        None
+    }
+
     /// Get idle agents
     pub async fn get_idle_agents(&self) -> Vec<Agent> {
         let agents = self.agents.read().await;
@@ -265,9 +355,10 @@ impl Runtime {
         queue.push(task);
         
         // Sort by priority (descending)
-        queue.sort_by(|a, b| b.priority.cmp(&a.priority));
+        queue.sort_by_key(|t| std::cmp::Reverse(t.priority));
         
         info!("Submitted task: {}", task_id);
+        self.wakeup.notify_one();
         task_id
     }
 
@@ -291,9 +382,10 @@ impl Runtime {
         queue.push(task);
         
         // Sort by priority (descending)
-        queue.sort_by(|a, b| b.priority.cmp(&a.priority));
+        queue.sort_by_key(|t| std::cmp::Reverse(t.priority));
         
         info!("Submitted task with priority {}: {}", priority, task_id);
+        self.wakeup.notify_one();
         task_id
     }
 
@@ -327,6 +419,140 @@ impl Runtime {
         let mut stats = self.stats.write().await;
         f(&mut stats);
     }
+
+    /// Get a task by ID, whether queued, running or finished
+    pub async fn get_task(&self, task_id: &str) -> Option<Task> {
+        if let Some(task) = self.dispatched.read().await.get(task_id) {
+            return Some(task.clone());
+        }
+        let queue = self.task_queue.lock().await;
+        queue.iter().find(|t| t.id == task_id).cloned()
+    }
+
+    /// Run queued tasks until the queue is empty or no agent can take more
+    ///
+    /// Returns the tasks that finished during this call, in completion order.
+    pub async fn run_until_idle(&self, executor: Arc<dyn TaskExecutor>, config: &SchedulerConfig) -> Vec<Task> {
+        let mut running = JoinSet::new();
+        let mut finished = Vec::new();
+        loop {
+            self.dispatch(&executor, config, &mut running).await;
+            match running.join_next().await {
+                Some(Ok(task)) => finished.push(task),
+                Some(Err(e)) => warn!("Task execution panicked: {}", e),
+                None => break,
+            }
+        }
+        finished
+    }
+
+    /// Start a scheduler that keeps dispatching tasks as they are submitted
+    pub fn start(&self, executor: Arc<dyn TaskExecutor>, config: SchedulerConfig) -> SchedulerHandle {
+        let runtime = self.clone();
+        let shutdown = Arc::new(Notify::new());
+        let stop = Arc::clone(&shutdown);
+        let handle = tokio::spawn(async move {
+            let mut running = JoinSet::new();
+            loop {
+                runtime.dispatch(&executor, &config, &mut running).await;
+                tokio::select! {
+                    _ = stop.notified() => break,
+                    _ = runtime.wakeup.notified() => {}
+                    Some(result) = running.join_next(), if !running.is_empty() => {
+                        if let Err(e) = result {
+                            warn!("Task execution panicked: {}", e);
+                        }
+                    }
+                }
+            }
+            while running.join_next().await.is_some() {}
+            info!("Scheduler stopped");
+        });
+        SchedulerHandle { shutdown, handle }
+    }
+
+    /// Start queued tasks on available agents, up to the concurrency limit
+    async fn dispatch(
+        &self,
+        executor: &Arc<dyn TaskExecutor>,
+        config: &SchedulerConfig,
+        running: &mut JoinSet<Task>,
+    ) {
+        while running.len() < config.max_concurrent.max(1) {
+            let Some(agent) = self.next_available_agent().await else {
+                break;
+            };
+            let Some(mut task) = self.next_task().await else {
+                break;
+            };
+
+            task.assigned_agent = Some(agent.id.clone());
+            task.status = TaskStatus::InProgress;
+            agent.set_state(AgentState::Working {
+                task_id: task.id.clone(),
+                started_at: chrono::Utc::now().to_rfc3339(),
+            })
+            .await;
+            self.dispatched.write().await.insert(task.id.clone(), task.clone());
+            self.update_stats(|stats| {
+                stats.total_tasks += 1;
+                stats.active_agents += 1;
+            })
+            .await;
+            info!("Dispatching task {} to agent {}", task.id, agent.name);
+
+            let runtime = self.clone();
+            let executor = Arc::clone(executor);
+            running.spawn(async move { runtime.execute(executor, agent, task).await });
+        }
+    }
+
+    /// Execute one task with its agent's timeout and record the outcome
+    async fn execute(&self, executor: Arc<dyn TaskExecutor>, agent: Agent, mut task: Task) -> Task {
+        let timeout = Duration::from_secs(agent.config().timeout_seconds);
+        let outcome = match tokio::time::timeout(timeout, executor.execute(&agent, &task)).await {
+            Ok(outcome) => outcome,
+            Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+        };
+
+        let (status, state) = match outcome {
+            Ok(result) => (
+                TaskStatus::Completed(result.clone()),
+                AgentState::Completed { task_id: task.id.clone(), result },
+            ),
+            Err(error) => {
+                warn!("Task {} failed: {}", task.id, error);
+                (TaskStatus::Failed(error.clone()), AgentState::Error { error })
+            }
+        };
+        let failed = matches!(status, TaskStatus::Failed(_));
+        task.status = status;
+        agent.set_state(state).await;
+        self.dispatched.write().await.insert(task.id.clone(), task.clone());
+        self.update_stats(|stats| {
+            if failed {
+                stats.failed_tasks += 1;
+            } else {
+                stats.completed_tasks += 1;
+            }
+            stats.active_agents -= 1;
+        })
+        .await;
+        self.wakeup.notify_one();
+        task
+    }
+
+    /// The first available agent, by name
+    async fn next_available_agent(&self) -> Option<Agent> {
+        let mut agents = self.list_agents().await;
+        agents.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.id.cmp(&b.id)));
+        for agent in agents {
+            if agent.is_available().await {
+                return Some(agent);
+            }
+        }
+        None
+    }
 }
 
 impl Default for Runtime {
@@ -344,6 +570,12 @@ pub struct LaborMarket {
     available_tasks: Arc<RwLock<HashMap<String, MarketTask>>>,
     /// Task assignments
     assignments: Arc<RwLock<HashMap<String, String>>>, // task_id -> agent_id
+    /// Tasks that were accepted, kept so results can reach the offering agent
+    accepted_tasks: Arc<RwLock<HashMap<String, MarketTask>>>,
+    /// Final status of finished tasks
+    outcomes: Arc<RwLock<HashMap<String, TaskStatus>>>,
+    /// Wakes up callers waiting for a task to finish
+    finished: Arc<Notify>,
 }
 
 /// A task available on the labor market
@@ -369,6 +601,9 @@ impl LaborMarket {
         Self {
             available_tasks: Arc::new(RwLock::new(HashMap::new())),
             assignments: Arc::new(RwLock::new(HashMap::new())),
+            accepted_tasks: Arc::new(RwLock::new(HashMap::new())),
+            outcomes: Arc::new(RwLock::new(HashMap::new())),
+            finished: Arc::new(Notify::new()),
         }
     }
 
@@ -401,8 +636,9 @@ impl LaborMarket {
     pub async fn accept_task(&self, task_id: &str, agent_id: &str) -> bool {
         let mut tasks = self.available_tasks.write().await;
         
-        if tasks.contains_key(task_id) {
-            tasks.remove(task_id);
+        if let Some(task) = tasks.remove(task_id) {
+            let mut accepted = self.accepted_tasks.write().await;
+            accepted.insert(task_id.to_string(), task);
             
             let mut assignments = self.assignments.write().await;
             assignments.insert(task_id.to_string(), agent_id.to_string());
@@ -442,6 +678,65 @@ impl LaborMarket {
         let assignments = self.assignments.read().await;
         assignments.get(task_id).cloned()
     }
+
+    /// Record the final status of an accepted task and release its assignment
+    ///
+    /// `status` should be [`TaskStatus::Completed`], [`TaskStatus::Failed`]
+    /// or [`TaskStatus::Cancelled`]. Returns false if the task was never accepted.
+    pub async fn finish_task(&self, task_id: &str, status: TaskStatus) -> bool {
+        if !self.accepted_tasks.read().await.contains_key(task_id) {
+            warn!("Task {} was not accepted on the labor market", task_id);
+            return false;
+        }
+        self.complete_task(task_id).await;
+        self.outcomes.write().await.insert(task_id.to_string(), status);
+        self.finished.notify_waiters();
+        info!("Task {} finished", task_id);
+        true
+    }
+
+    /// Current status of a task
+    ///
+    /// Offered tasks are pending, accepted tasks are in progress until finished.
+    pub async fn task_status(&self, task_id: &str) -> Option<TaskStatus> {
+        if let Some(status) = self.outcomes.read().await.get(task_id) {
+            return Some(status.clone());
+        }
+        if self.assignments.read().await.contains_key(task_id) {
+            return Some(TaskStatus::InProgress);
+        }
+        if self.available_tasks.read().await.contains_key(task_id) {
+            return Some(TaskStatus::Pending);
+        }
+        None
+    }
+
+    /// Wait until a task finishes and return its final status
+    ///
+    /// Returns `None` for unknown tasks.
+    pub async fn wait_for(&self, task_id: &str) -> Option<TaskStatus> {
+        loop {
+            // Register before checking so a finish in between is not missed
+            let finished = self.finished.notified();
+            match self.task_status(task_id).await? {
+                TaskStatus::Pending | TaskStatus::InProgress => finished.await,
+                status => return Some(status),
+            }
+        }
+    }
+
+    /// Finished tasks offered by an agent, with their final status
+    pub async fn results_for(&self, offered_by: &str) -> Vec<(MarketTask, TaskStatus)> {
+        let accepted = self.accepted_tasks.read().await;
+        let outcomes = self.outcomes.read().await;
+        let mut results: Vec<(MarketTask, TaskStatus)> = accepted
+            .values()
+            .filter(|task| task.offered_by == offered_by)
+            .filter_map(|task| outcomes.get(&task.id).map(|status| (task.clone(), status.clone())))
+            .collect();
+        results.sort_by(|a, b| a.0.offered_at.cmp(&b.0.offered_at));
+        results
+    }
 }
 
 impl Default for LaborMarket {
@@ -537,6 +832,79 @@ mod tests {
         assert_eq!(task.unwrap().description, "High priority");
     }
 
+    /// Executor that echoes the task, failing tasks that mention "fail"
+    struct EchoExecutor {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl TaskExecutor for EchoExecutor {
+        async fn execute(&self, agent: &Agent, task: &Task) -> Result<String, String> {
+            tokio::time::sleep(self.delay).await;
+            if task.description.contains("fail") {
+                Err(format!("{} could not {}", agent.name, task.description))
+            } else {
+                Ok(format!("{} did {}", agent.name, task.description))
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_runtime_run_until_idle() {
+        let runtime = Runtime::new();
+        runtime.register_agent(Agent::new("A", "Worker")).await;
+        runtime.register_agent(Agent::new("B", "Worker")).await;
+        let ok = runtime.submit_task("build").await;
+        let bad = runtime.submit_task("fail the lint").await;
+        let third = runtime.submit_task("test").await;
+
+        let executor = Arc::new(EchoExecutor { delay: Duration::from_millis(10) });
+        let finished = runtime.run_until_idle(executor, &SchedulerConfig { max_concurrent: 2 }).await;
+
+        assert_eq!(finished.len(), 3);
+        assert!(matches!(runtime.get_task(&ok).await.unwrap().status, TaskStatus::Completed(ref r) if r == "A did build"));
+        assert!(matches!(runtime.get_task(&bad).await.unwrap().status, TaskStatus::Failed(_)));
+        assert!(runtime.get_task(&third).await.unwrap().assigned_agent.is_some());
+        assert_eq!(runtime.queue_length().await, 0);
+
+        let stats = runtime.stats().await;
+        assert_eq!((stats.total_tasks, stats.completed_tasks, stats.failed_tasks, stats.active_agents), (3, 2, 1, 0));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_task_timeout() {
+        let runtime = Runtime::new();
+        let mut agent = Agent::new("Slow", "Worker");
+        agent.config_mut().timeout_seconds = 0;
+        runtime.register_agent(agent.clone()).await;
+        let id = runtime.submit_task("wait").await;
+
+        let executor = Arc::new(EchoExecutor { delay: Duration::from_secs(5) });
+        runtime.run_until_idle(executor, &SchedulerConfig::default()).await;
+
+        assert!(matches!(runtime.get_task(&id).await.unwrap().status, TaskStatus::Failed(ref e) if e.contains("timed out")));
+        assert!(matches!(agent.state().await, AgentState::Error { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_scheduler_runs_submitted_tasks() {
+        let runtime = Runtime::new();
+        let executor = Arc::new(EchoExecutor { delay: Duration::ZERO });
+        let scheduler = runtime.start(executor, SchedulerConfig::default());
+
+        let id = runtime.submit_task("deploy").await;
+        runtime.register_agent(Agent::new("Late", "Worker")).await;
+        for _ in 0..100 {
+            if matches!(runtime.get_task(&id).await.unwrap().status, TaskStatus::Completed(_)) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        scheduler.stop().await;
+
+        assert!(matches!(runtime.get_task(&id).await.unwrap().status, TaskStatus::Completed(ref r) if r == "Late did deploy"));
+    }
+
     #[tokio::test]
     async fn test_labor_market_offer_and_accept() {
         let market = LaborMarket::new();
@@ -583,4 +951,57 @@ mod tests {
         assert_eq!(math_tasks.len(), 1);
         assert_eq!(math_tasks[0].description, "Math task");
     }
+
+    #[tokio::test]
+    async fn test_labor_market_status_transitions() {
+        let market = LaborMarket::new();
+        let task_id = market.offer_task("Summarize logs", "agent-1", vec![], 0).await;
+        assert!(matches!(market.task_status(&task_id).await, Some(TaskStatus::Pending)));
+        assert!(!market.finish_task(&task_id, TaskStatus::Completed("early".to_string())).await);
+
+        market.accept_task(&task_id, "agent-2").await;
+        assert!(matches!(market.task_status(&task_id).await, Some(TaskStatus::InProgress)));
+
+        let waiter = {
+            let market = market.clone();
+            let task_id = task_id.clone();
+            tokio::spawn(async move { market.wait_for(&task_id).await })
+        };
+        tokio::task::yield_now().await;
+        assert!(market.finish_task(&task_id, TaskStatus::Completed("3 errors".to_string())).await);
+
+        assert!(matches!(waiter.await.unwrap(), Some(TaskStatus::Completed(ref r)) if r == "3 errors"));
+        assert_eq!(market.get_assignment(&task_id).await, None);
+        let results = market.results_for("agent-1").await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.description, "Summarize logs");
+        assert!(market.results_for("agent-2").await.is_empty());
+        assert!(market.wait_for("unknown").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_runtime_find_agent_for() {
+        let runtime = Runtime::new();
+        let config = AgentConfig {
+            capabilities: vec!["Rust".to_string()],
+            ..AgentConfig::default()
+        };
+        let coder = Agent::new("Coder", "Writes code").with_config(config);
+        let writer = Agent::new("Writer", "Writes docs");
+        runtime.register_agent(coder.clone()).await;
+        runtime.register_agent(writer.clone()).await;
+
+        let market = LaborMarket::new();
+        let id = market.offer_task("Fix the borrow error", "lead", vec!["rust".to_string()], 0).await;
+        let task = market.get_available_tasks().await.into_iter().find(|t| t.id == id).unwrap();
+
+        assert_eq!(runtime.find_agent_for(&task, "lead").await.unwrap().id, coder.id);
+        assert!(runtime.find_agent_for(&task, &coder.id).await.is_none());
+
+        coder.set_state(AgentState::Working {
+            task_id: id.clone(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+        }).await;
+        assert!(runtime.find_agent_for(&task, "lead").await.is_none());
+    }
 }
@@ -3,33 +3,155 @@
 //! This module handles the actual chat processing between the user and the LLM,
 //! including message building, streaming responses, tool calling, and wire protocol integration.
 
+use crate::approval::Approval;
+use crate::attachment;
 use crate::context::Context;
-use crate::soul::{KimiSoul, SoulError, WireSoulSide};
-use crate::types::UserInput;
+use crate::prompts::builder::estimate_tokens;
+use crate::soul::compaction::CompactionStrategy;
+use crate::soul::context_window::ContextWindow;
+use crate::soul::limits::ToolCallBudget;
+use crate::soul::proposed_edit::ProposedEdit;
+use crate::soul::subagent::SPAWN_AGENT_TOOL;
+use crate::soul::undo::UNDO_EDIT_TOOL;
+use crate::soul::watch::{MAX_CHANGED_FILES, WATCH_PATH_TOOL};
+use crate::soul::{system_message, Interrupt, KimiSoul, OutputSink, SoulError, TurnLimit, WireSoulSide};
+use crate::stats::TurnUsage;
+use crate::telemetry::record_error;
+use crate::types::{TokenUsage, UserInput};
 use crate::wire::WireMessage;
 use futures::StreamExt;
-use kosong_rs::{ChatProvider, Message as KosongMessage, Role as KosongRole};
+use kosong_rs::{ChatError, ChatProvider, ContentPart, Failover, Message as KosongMessage, Role as KosongRole};
 use kosong_rs::chat_provider::ToolDefinition;
-use tracing::{debug, info, warn};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::field::Empty;
+use tracing::{debug, info, info_span, instrument, warn, Instrument, Span};
+
+/// Tool result recorded for a tool call the user interrupted
+const INTERRUPTED_TOOL_RESULT: &str = "Interrupted by the user";
 
 /// Process a user message through the LLM with tool support
+///
+/// The turn may make up to `loop_control.max_steps_per_turn` LLM calls.
 pub async fn process_message(
     soul: &mut KimiSoul,
     provider: &dyn ChatProvider,
     user_input: UserInput,
     wire: &WireSoulSide,
 ) -> Result<String, SoulError> {
-    // Add user message to context
-    soul.context.add_message(crate::types::Message {
-        role: crate::types::Role::User,
-        content: user_input.text.clone(),
-        metadata: None,
-    });
+    let max_steps = soul.loop_control.max_steps_per_turn;
+    process_message_with_limit(soul, provider, user_input, wire, max_steps).await
+}
+
+/// Process a user message, allowing up to `max_iterations` LLM calls
+#[instrument(
+    name = "turn",
+    skip_all,
+    fields(agent = %soul.agent.name, otel.status_code = Empty, otel.status_message = Empty)
+)]
+pub async fn process_message_with_limit(
+    soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
+    user_input: UserInput,
+    wire: &WireSoulSide,
+    max_iterations: usize,
+) -> Result<String, SoulError> {
+    // Check the attachments load before the message goes into the context
+    if let Err(e) = attachment::load_parts(&user_input.attachments, provider.supports_vision()) {
+        record_error(&e);
+        return Err(e.into());
+    }
+
+    // An interrupt only applies to the turn it was sent during
+    soul.interrupt.reset();
+
+    // Add user message to context, with a checkpoint to rewind to
+    soul.add_user_input(&user_input.text, &user_input.attachments);
+
+    // Pull in the most relevant skill's instructions for this turn only
+    if let Some(skill) = soul.activate_skill_for(&user_input.text) {
+        info!("Activated skill {}", skill.name);
+        let message = WireMessage::SkillActivated { name: skill.name.clone() };
+        if let Err(e) = wire.send(message).await {
+            soul.deactivate_skill();
+            return Err(SoulError::Wire(e.to_string()));
+        }
+    }
+
+    // Inject the workspace code most relevant to the message
+    match soul.retrieve_for(&user_input.text).await {
+        Ok(0) => {}
+        Ok(count) => info!("Retrieved {} workspace chunks", count),
+        Err(e) => warn!("Workspace retrieval failed: {}", e),
+    }
+
+    let mut usage = TurnUsage::default();
+    let result = run_iterations(soul, provider, wire, max_iterations, &mut usage).await;
+    record_usage(soul, usage);
+    soul.deactivate_skill();
+    soul.clear_retrieved();
+    if let Err(e) = &result {
+        if !matches!(e, SoulError::Cancelled) {
+            record_error(e);
+        }
+    }
+    result
+}
+
+/// Add a turn's usage to the soul's usage statistics, if it keeps them
+fn record_usage(soul: &KimiSoul, usage: TurnUsage) {
+    if let Some(store) = &soul.usage {
+        if let Err(e) = store.record_turn(chrono::Local::now().date_naive(), usage) {
+            warn!("Failed to record usage statistics: {}", e);
+        }
+    }
+}
 
-    // Process with potential tool call loops (max 5 iterations)
-    let max_iterations = 5;
+/// Call the LLM until it answers without tool calls or `max_iterations` is reached
+///
+/// Tool calls are counted against the limits in the soul's
+/// [`LoopControl`](crate::types::LoopControl). A turn that reaches a limit
+/// sends [`WireMessage::TurnLimitReached`] and ends with
+/// [`SoulError::LimitReached`].
+///
+/// A step that fails with a retryable provider error is retried once on the
+/// soul's fallback provider, which then serves the rest of the turn. An
+/// interrupted step sends [`WireMessage::StepInterrupted`] and ends the turn
+/// with [`SoulError::Cancelled`].
+async fn run_iterations(
+    soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
+    wire: &WireSoulSide,
+    max_iterations: usize,
+    usage: &mut TurnUsage,
+) -> Result<String, SoulError> {
+    let fallback = soul.fallback_provider.clone();
+    let mut provider = provider;
+    let mut failed_over = false;
+    let mut budget = ToolCallBudget::new(&soul.loop_control);
+
+    // Process with potential tool call loops
     for iteration in 0..max_iterations {
-        let result = process_single_turn(soul, provider, wire).await?;
+        wire.send(WireMessage::StepBegin { n: iteration + 1 }).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+        let result = loop {
+            let step = info_span!("step", n = iteration + 1, otel.status_code = Empty, otel.status_message = Empty);
+            let result = process_single_turn(soul, provider, wire, &mut budget, usage).instrument(step.clone()).await;
+            match (result, fallback.as_deref()) {
+                (Err(e), Some(fallback)) if e.is_retryable() && !failed_over => {
+                    step.in_scope(|| record_error(&e));
+                    warn!("{} failed, retrying the step on {}: {}", provider.model_name(), fallback.model_name(), e);
+                    let notice = WireMessage::ProviderFailover {
+                        from: provider.model_name().to_string(),
+                        to: fallback.model_name().to_string(),
+                        reason: e.to_string(),
+                    };
+                    wire.send(notice).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+                    provider = fallback;
+                    failed_over = true;
+                }
+                (result, _) => break result.inspect_err(|e| step.in_scope(|| record_error(e)))?,
+            }
+        };
         
         match result {
             TurnResult::Complete(response) => {
@@ -40,10 +162,37 @@ pub async fn process_message(
                 debug!("Tool calls executed, continuing to iteration {}", iteration + 1);
                 continue;
             }
+            TurnResult::Interrupted => {
+                info!("Turn interrupted in step {}", iteration + 1);
+                wire.send(WireMessage::StepInterrupted).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+                return Err(SoulError::Cancelled);
+            }
+            TurnResult::LimitReached(limit) => {
+                return Err(limit_reached(wire, limit).await);
+            }
         }
     }
     
-    Err(SoulError::MaxIterations)
+    Err(limit_reached(wire, TurnLimit::Steps(max_iterations)).await)
+}
+
+/// Tell the frontend the turn stopped at `limit`, returning the turn's error
+async fn limit_reached(wire: &WireSoulSide, limit: TurnLimit) -> SoulError {
+    warn!("Turn limit reached: {}", limit);
+    let message = WireMessage::TurnLimitReached {
+        setting: limit.setting().to_string(),
+        message: limit.to_string(),
+    };
+    match wire.send(message).await {
+        Ok(()) => SoulError::LimitReached(limit),
+        Err(e) => SoulError::Wire(e.to_string()),
+    }
+}
+
+/// The step's share of the context window, and once the step is answered
+/// its token usage
+fn status_update(context_usage: f64, token_usage: Option<TokenUsage>) -> WireMessage {
+    WireMessage::StatusUpdate { context_usage: Some(context_usage), token_usage, message_id: None }
 }
 
 /// Result of a single turn
@@ -52,6 +201,10 @@ enum TurnResult {
     Complete(String),
     /// Tool calls were executed, need another turn
     ToolCallsExecuted,
+    /// The user interrupted the turn; the context holds what was done so far
+    Interrupted,
+    /// A tool call would have exceeded a per-turn limit
+    LimitReached(TurnLimit),
 }
 
 /// Process a single turn (one LLM call)
@@ -59,55 +212,91 @@ async fn process_single_turn(
     soul: &mut KimiSoul,
     provider: &dyn ChatProvider,
     wire: &WireSoulSide,
+    budget: &mut ToolCallBudget,
+    usage: &mut TurnUsage,
 ) -> Result<TurnResult, SoulError> {
+    // Trim or summarize the middle of the conversation if it no longer fits
+    if soul.context_window.is_over(&soul.context) {
+        wire.send(WireMessage::CompactionBegin).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+        if soul.context_window.strategy == CompactionStrategy::Smart {
+            let prompt = soul.render_prompt(&soul.prompts.compact);
+            let compaction = soul.context_window.smart_compaction();
+            if let Err(e) = compaction.summarize(&mut soul.context, provider, &prompt).await {
+                warn!("Summarizing the context failed, trimming it instead: {}", e);
+            }
+        }
+        soul.context_window.trim(&mut soul.context);
+        wire.send(WireMessage::CompactionEnd).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+    }
+
+    // Tell the model about changes to the paths it watches
+    for change in soul.file_watches.take_changes() {
+        wire.send(WireMessage::FileChanged {
+            path: change.watched.display().to_string(),
+            changed: change.changed.iter().take(MAX_CHANGED_FILES).map(|p| p.display().to_string()).collect(),
+        })
+        .await
+        .map_err(|e| SoulError::Wire(e.to_string()))?;
+        soul.context.add_message(system_message(change.note()));
+    }
+
     // Build messages from context
-    let messages = build_messages(&soul.context);
+    let messages = build_messages(&soul.context, provider.supports_vision());
 
-    // Get system prompt from agent
-    let system_prompt = if soul.agent.system_prompt.is_empty() {
+    // Get system prompt from agent, with memory files appended
+    let system_prompt = soul.system_prompt();
+    let system_prompt = if system_prompt.is_empty() {
         None
     } else {
-        Some(soul.agent.system_prompt.as_str())
+        Some(system_prompt.as_str())
     };
 
-    // Convert toolset to ToolDefinitions
+    // Convert toolset to ToolDefinitions, leaving out those the permission mode refuses
     let tools = if soul.toolset.tool_count() > 0 {
-        Some(build_tool_definitions(&soul.toolset))
+        Some(build_tool_definitions(&soul.toolset, &soul.approval))
     } else {
         None
     };
 
-    // Generate response with tools
-    let mut stream = provider
-        .generate_with_tools(system_prompt, &messages, tools.as_deref())
-        .await
-        .map_err(|e| SoulError::Llm(e.to_string()))?;
+    let input_tokens = ContextWindow::estimate(&soul.context)
+        + system_prompt.map_or(0, estimate_tokens)
+        + tools.as_ref().map_or(0, |tools| estimate_tokens(&serde_json::to_string(tools).unwrap_or_default()));
 
-    // Stream response back through wire and collect full text
-    let mut full_response = String::new();
-    let mut pending_tool_calls = Vec::new();
+    let max_tokens = soul.context_window.max_tokens.max(1) as f64;
+    let context_usage = input_tokens as f64 / max_tokens;
+    wire.send(status_update(context_usage, None)).await.map_err(|e| SoulError::Wire(e.to_string()))?;
 
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            Ok(kosong_rs::StreamChunk::Text(text)) => {
-                wire.send(WireMessage::TextPart { text: text.clone() })
-                    .await
-                    .map_err(|e| SoulError::Wire(e.to_string()))?;
-                full_response.push_str(&text);
-            }
-            Ok(kosong_rs::StreamChunk::ToolCall(tool_call)) => {
-                debug!("Received tool call: {:?}", tool_call);
-                pending_tool_calls.push(tool_call);
-            }
-            Ok(kosong_rs::StreamChunk::ToolCallPart(_part)) => {
-                // Tool call parts are accumulated by the provider
-                // We only receive complete ToolCalls, so we can ignore parts here
-                debug!("Received tool call part (accumulated by provider)");
-            }
-            Err(e) => {
-                return Err(SoulError::Llm(e.to_string()));
-            }
+    let interrupt = soul.interrupt.clone();
+    let failovers = soul.failovers.clone();
+    let streamed =
+        stream_response(provider, system_prompt, &messages, tools.as_deref(), wire, &interrupt, &failovers).await?;
+    // Prefer the provider's counts; estimate when it reports none
+    let (input_tokens, output_tokens, context_usage) = match streamed.usage {
+        Some(reported) => {
+            let input_tokens = reported.prompt_tokens as usize;
+            (input_tokens, reported.completion_tokens as usize, input_tokens as f64 / max_tokens)
+        }
+        None => {
+            let output_tokens = estimate_tokens(&streamed.text)
+                + streamed.tool_calls.iter().map(|call| estimate_tokens(&call.function.arguments)).sum::<usize>();
+            (input_tokens, output_tokens, context_usage)
         }
+    };
+    usage.add_step(provider.model_name(), input_tokens, output_tokens);
+    let tokens = TokenUsage { input_tokens, output_tokens, total_tokens: input_tokens + output_tokens };
+    wire.send(status_update(context_usage, Some(tokens))).await.map_err(|e| SoulError::Wire(e.to_string()))?;
+    let (full_response, pending_tool_calls) = (streamed.text, streamed.tool_calls);
+
+    // Keep the text received before an interrupt; its tool calls are not run
+    if streamed.interrupted {
+        if !full_response.is_empty() {
+            soul.context.add_message(crate::types::Message {
+                role: crate::types::Role::Assistant,
+                content: full_response,
+                metadata: None,
+            });
+        }
+        return Ok(TurnResult::Interrupted);
     }
 
     // Process any tool calls
@@ -120,23 +309,70 @@ async fn process_single_turn(
             content: full_response.clone(),
             metadata: Some({
                 let mut map = std::collections::HashMap::new();
-                map.insert("tool_calls".to_string(), serde_json::json!(pending_tool_calls));
+                map.insert(crate::types::Message::TOOL_CALLS_KEY.to_string(), serde_json::json!(pending_tool_calls));
                 map
             }),
         });
 
-        // Execute tool calls and collect results
+        // Execute tool calls and collect results. Every call gets a result,
+        // so after an interrupt, a reached limit or an error the remaining
+        // ones are marked as not run.
+        let mut interrupted = false;
+        let mut limit = None;
+        let mut failed = None;
         for tool_call in pending_tool_calls {
-            let result = execute_tool_call(soul, &tool_call, wire).await?;
+            if !interrupted && limit.is_none() {
+                if let Err(reached) = budget.record(&tool_call.function.name, &tool_call.function.arguments) {
+                    limit = Some(reached);
+                }
+            }
+            let result = if interrupted {
+                INTERRUPTED_TOOL_RESULT.to_string()
+            } else if let Some(error) = &failed {
+                format!("Not run: {}", error)
+            } else if let Some(limit) = &limit {
+                format!("Not run: {}", limit)
+            } else {
+                usage.add_tool_call();
+                tokio::select! {
+                    biased;
+                    _ = interrupt.interrupted() => {
+                        interrupted = true;
+                        // Drop an approval request the user will not answer now
+                        let _ = soul.approval.cancel().await;
+                        INTERRUPTED_TOOL_RESULT.to_string()
+                    }
+                    result = execute_tool_call(soul, provider, &tool_call, wire) => match result {
+                        Ok(result) => result,
+                        Err(e) => {
+                            let result = format!("Tool execution failed: {}", e);
+                            failed = Some(e);
+                            result
+                        }
+                    },
+                }
+            };
             
             // Add tool result to context
-            soul.context.add_message(crate::types::Message {
-                role: crate::types::Role::Tool,
-                content: result,
-                metadata: None,
-            });
+            soul.context.add_message(
+                crate::types::Message {
+                    role: crate::types::Role::Tool,
+                    content: result,
+                    metadata: None,
+                }
+                .with_tool_call_id(tool_call.id),
+            );
         }
 
+        if let Some(e) = failed {
+            return Err(e);
+        }
+        if let Some(limit) = limit {
+            return Ok(TurnResult::LimitReached(limit));
+        }
+        if interrupted {
+            return Ok(TurnResult::Interrupted);
+        }
         return Ok(TurnResult::ToolCallsExecuted);
     }
 
@@ -150,8 +386,120 @@ async fn process_single_turn(
     Ok(TurnResult::Complete(full_response))
 }
 
-/// Build tool definitions from the soul's toolset
-fn build_tool_definitions(toolset: &crate::soul::KimiToolset) -> Vec<ToolDefinition> {
+/// What one streamed LLM request produced
+struct StreamedResponse {
+    /// Text received, complete unless interrupted
+    text: String,
+    /// Tool calls the model made
+    tool_calls: Vec<kosong_rs::ToolCall>,
+    /// Token counts, if the provider reported them
+    usage: Option<kosong_rs::TokenUsage>,
+    /// Whether the stream was abandoned because of an interrupt
+    interrupted: bool,
+}
+
+/// Make one streamed LLM request, forwarding text to the wire
+///
+/// The generation is cancelled as soon as `interrupt` fires, keeping what
+/// was received. Failovers a fallback chain logged to `failovers` while
+/// making the request are sent as [`WireMessage::ProviderFailover`].
+#[instrument(
+    name = "llm.request",
+    skip_all,
+    fields(
+        model = %provider.model_name(),
+        ttft_ms = Empty,
+        output_chars = Empty,
+        tool_calls = Empty,
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+    )
+)]
+async fn stream_response(
+    provider: &dyn ChatProvider,
+    system_prompt: Option<&str>,
+    messages: &[KosongMessage],
+    tools: Option<&[ToolDefinition]>,
+    wire: &WireSoulSide,
+    interrupt: &Interrupt,
+    failovers: &Mutex<Vec<Failover>>,
+) -> Result<StreamedResponse, SoulError> {
+    let started = Instant::now();
+    // Stream response back through wire and collect full text
+    let mut full_response = String::new();
+    let mut pending_tool_calls = Vec::new();
+    let mut first_chunk = true;
+    let mut usage = None;
+
+    // An interrupt cancels the generation, closing the HTTP stream
+    let (request, generation) = provider.generate_cancellable(system_prompt, messages, tools);
+    interrupt.cancel_on_interrupt(generation.clone());
+    let request = request.await;
+    let hops = std::mem::take(&mut *failovers.lock().unwrap());
+    for Failover { from, to, reason } in hops {
+        wire.send(WireMessage::ProviderFailover { from, to, reason })
+            .await
+            .map_err(|e| SoulError::Wire(e.to_string()))?;
+    }
+    let mut stream = match request {
+        Err(ChatError::Cancelled) => {
+            return Ok(StreamedResponse { text: full_response, tool_calls: pending_tool_calls, usage, interrupted: true });
+        }
+        stream => stream.map_err(SoulError::Provider).inspect_err(|e| record_error(e))?,
+    };
+
+    while let Some(chunk) = stream.next().await {
+        if first_chunk {
+            first_chunk = false;
+            Span::current().record("ttft_ms", started.elapsed().as_millis() as u64);
+        }
+        match chunk {
+            Ok(kosong_rs::StreamChunk::Text(text)) => {
+                wire.send(WireMessage::TextPart { text: text.clone() })
+                    .await
+                    .map_err(|e| SoulError::Wire(e.to_string()))?;
+                full_response.push_str(&text);
+            }
+            Ok(kosong_rs::StreamChunk::Thinking(text)) => {
+                // Shown to the user, but not kept in the context
+                wire.send(WireMessage::ThinkPart { text })
+                    .await
+                    .map_err(|e| SoulError::Wire(e.to_string()))?;
+            }
+            Ok(kosong_rs::StreamChunk::ToolCall(tool_call)) => {
+                debug!("Received tool call: {:?}", tool_call);
+                pending_tool_calls.push(tool_call);
+            }
+            Ok(kosong_rs::StreamChunk::ToolCallPart(_part)) => {
+                // Tool call parts are accumulated by the provider
+                // We only receive complete ToolCalls, so we can ignore parts here
+                debug!("Received tool call part (accumulated by provider)");
+            }
+            Ok(kosong_rs::StreamChunk::Usage(reported)) => {
+                debug!("Received usage: {:?}", reported);
+                usage = Some(reported);
+            }
+            Err(e) => {
+                record_error(&e);
+                return Err(SoulError::Provider(e));
+            }
+        }
+    }
+
+    let interrupted = generation.is_cancelled();
+    let span = Span::current();
+    span.record("output_chars", full_response.chars().count() as u64);
+    span.record("tool_calls", pending_tool_calls.len() as u64);
+    Ok(StreamedResponse {
+        text: full_response,
+        tool_calls: pending_tool_calls,
+        usage,
+        interrupted,
+    })
+}
+
+/// Build tool definitions from the soul's toolset for the tools `approval` offers
+fn build_tool_definitions(toolset: &crate::soul::KimiToolset, approval: &Approval) -> Vec<ToolDefinition> {
     toolset
         .schemas()
         .iter()
@@ -160,6 +508,9 @@ fn build_tool_definitions(toolset: &crate::soul::KimiToolset) -> Vec<ToolDefinit
             // { "type": "function", "function": { "name": "...", "description": "...", "parameters": {...} } }
             let function = schema.get("function")?;
             let name = function.get("name")?.as_str()?.to_string();
+            if !approval.offers(&name) {
+                return None;
+            }
             let description = function.get("description")?.as_str()?.to_string();
             let parameters = function.get("parameters")?.clone();
 
@@ -169,8 +520,20 @@ fn build_tool_definitions(toolset: &crate::soul::KimiToolset) -> Vec<ToolDefinit
 }
 
 /// Execute a tool call and return the result
+#[instrument(
+    name = "tool",
+    skip_all,
+    fields(
+        tool = %tool_call.function.name,
+        call_id = %tool_call.id,
+        approval = Empty,
+        otel.status_code = Empty,
+        otel.status_message = Empty,
+    )
+)]
 async fn execute_tool_call(
     soul: &mut KimiSoul,
+    provider: &dyn ChatProvider,
     tool_call: &kosong_rs::ToolCall,
     wire: &WireSoulSide,
 ) -> Result<String, SoulError> {
@@ -181,29 +544,78 @@ async fn execute_tool_call(
     if !soul.toolset.contains(tool_name) {
         let error_msg = format!("Tool not found: {}", tool_name);
         warn!("{}", error_msg);
+        record_error(&error_msg);
         return Ok(error_msg);
     }
 
     // Parse arguments for approval description
-    let params: serde_json::Value = serde_json::from_str(&tool_call.function.arguments)
-        .map_err(|e| SoulError::Tool(format!("Invalid tool arguments: {}", e)))?;
+    let mut params: serde_json::Value = match serde_json::from_str(&tool_call.function.arguments) {
+        Ok(params) => params,
+        Err(e) => {
+            let error_msg = format!("Invalid tool arguments: {}", e);
+            warn!("{}", error_msg);
+            record_error(&error_msg);
+            return Ok(error_msg);
+        }
+    };
 
     // Build approval description based on tool and params
     let description = build_approval_description(tool_name, &params);
 
-    // Request approval (unless in yolo mode)
+    // Request approval, unless the permission mode answers for the user
     let request_id = uuid::Uuid::new_v4().to_string();
-    let approval_request = crate::types::Request {
+    let mut approval_request = crate::types::Request {
         id: request_id.clone(),
         tool_call_id: tool_call.id.clone(),
         sender: "kimi".to_string(),
         action: tool_name.clone(),
         description: description.clone(),
+        edit: None,
+        command_pattern: None,
     };
+    if tool_name == crate::approval::SHELL_TOOL {
+        approval_request.command_pattern = params
+            .get("command")
+            .and_then(|c| c.as_str())
+            .and_then(crate::approval::command_pattern);
+    }
+
+    // Let frontends know an answer is needed, showing file edits as a diff
+    let decided = soul.approval.decide(&approval_request);
+    let by_mode = decided.is_some();
+    if !by_mode {
+        approval_request.edit = ProposedEdit::from_tool_call(tool_name, &params);
+        wire.send(WireMessage::ApprovalRequest {
+            id: request_id.clone(),
+            tool_call_id: tool_call.id.clone(),
+            sender: approval_request.sender.clone(),
+            action: tool_name.clone(),
+            description: description.clone(),
+            diff: approval_request.edit.as_ref().map(ProposedEdit::diff),
+        })
+        .await
+        .map_err(|e| SoulError::Wire(e.to_string()))?;
+    }
+
+    let (approval_kind, edited) = match decided {
+        Some(kind) => (kind, None),
+        None => soul.approval.request_with_edits(approval_request).await,
+    };
+    Span::current().record("approval", tracing::field::debug(&approval_kind));
+    if !by_mode {
+        wire.send(WireMessage::ApprovalResponse {
+            request_id,
+            response: approval_kind.clone(),
+        })
+        .await
+        .map_err(|e| SoulError::Wire(e.to_string()))?;
+    }
 
-    let approval_kind = soul.approval.request(approval_request).await;
-    
     match approval_kind {
+        crate::types::ApprovalKind::Reject if by_mode => {
+            info!("Tool {} refused in {} mode or by its approval rule", tool_name, soul.approval.mode());
+            return Ok(soul.approval.refusal(tool_name));
+        }
         crate::types::ApprovalKind::Reject => {
             info!("Tool {} rejected by user", tool_name);
             return Ok(format!("Tool '{}' was rejected by user approval", tool_name));
@@ -211,19 +623,62 @@ async fn execute_tool_call(
         crate::types::ApprovalKind::ApproveOnce => {
             // ApproveOnce is treated as Approve for a single tool call
         }
-        crate::types::ApprovalKind::Approve => {
+        crate::types::ApprovalKind::Approve | crate::types::ApprovalKind::ApproveAlways => {
             // Continue with execution
         }
     }
 
+    // Run with the arguments the user edited, if they did
+    let user_edited = edited.is_some();
+    let arguments = match edited {
+        Some(edited) => {
+            info!("Running {} with arguments edited by the user", tool_name);
+            params = edited;
+            params.to_string()
+        }
+        None => tool_call.function.arguments.clone(),
+    };
+
     // Send tool begin message
     wire.send(WireMessage::ToolBegin {
         name: tool_name.clone(),
-        arguments: tool_call.function.arguments.clone(),
+        arguments,
     }).await.map_err(|e| SoulError::Wire(e.to_string()))?;
 
-    // Execute the tool
-    let result = match soul.toolset.execute(tool_name, params).await {
+    // Sub-agents run here, as they need the soul and the provider, and
+    // undoing and watching need the soul's record of file changes and watches
+    let executed = if tool_name == SPAWN_AGENT_TOOL {
+        soul.spawn_agent(provider, &tool_call.id, params, wire).await
+    } else if tool_name == UNDO_EDIT_TOOL {
+        soul.undo_edit(params)
+    } else if tool_name == WATCH_PATH_TOOL {
+        soul.watch_path(params)
+    } else {
+        // Execute the tool, keeping a copy of any file it is about to change
+        // and passing on its output while it runs
+        let before = soul.snapshot_before_tool(tool_name, &params);
+        let (output, mut parts) = OutputSink::channel();
+        let executed = {
+            let run = soul.toolset.execute_streaming(tool_name, params, output);
+            tokio::pin!(run);
+            loop {
+                tokio::select! {
+                    biased;
+                    Some((stream, text)) = parts.recv() => {
+                        wire.send(WireMessage::ToolOutputPart { tool_call_id: tool_call.id.clone(), stream, text }).await?;
+                    }
+                    executed = &mut run => break executed,
+                }
+            }
+        };
+        // Output sent just before the tool finished
+        while let Ok((stream, text)) = parts.try_recv() {
+            wire.send(WireMessage::ToolOutputPart { tool_call_id: tool_call.id.clone(), stream, text }).await?;
+        }
+        soul.record_edit(tool_name, before);
+        executed
+    };
+    let result = match executed {
         Ok(output) => {
             let output_str = serde_json::to_string(&output)
                 .unwrap_or_else(|_| output.to_string());
@@ -233,6 +688,7 @@ async fn execute_tool_call(
         Err(e) => {
             let error_msg = format!("Tool execution failed: {}", e);
             warn!("{}", error_msg);
+            record_error(&error_msg);
             error_msg
         }
     };
@@ -243,11 +699,14 @@ async fn execute_tool_call(
         result: result.clone(),
     }).await.map_err(|e| SoulError::Wire(e.to_string()))?;
 
+    if user_edited {
+        return Ok(format!("{}\nThe user edited the change before it was applied; read the file for its current content.", result));
+    }
     Ok(result)
 }
 
 /// Build a human-readable description for approval request
-fn build_approval_description(tool_name: &str, params: &serde_json::Value) -> String {
+pub(crate) fn build_approval_description(tool_name: &str, params: &serde_json::Value) -> String {
     match tool_name {
         "WriteFile" => {
             let path = params.get("path").and_then(|p| p.as_str()).unwrap_or("unknown");
@@ -258,6 +717,12 @@ fn build_approval_description(tool_name: &str, params: &serde_json::Value) -> St
             let path = params.get("path").and_then(|p| p.as_str()).unwrap_or("unknown");
             format!("Edit file '{}'", path)
         }
+        "EditNotebookCell" => {
+            let path = params.get("path").and_then(|p| p.as_str()).unwrap_or("unknown");
+            let mode = params.get("edit_mode").and_then(|m| m.as_str()).unwrap_or("replace");
+            let index = params.get("cell_index").and_then(|i| i.as_u64()).unwrap_or(0);
+            format!("Edit notebook '{}' ({} cell {})", path, mode, index)
+        }
         "Shell" => {
             let command = params.get("command").and_then(|c| c.as_str()).unwrap_or("unknown");
             // Truncate long commands
@@ -280,16 +745,31 @@ fn build_approval_description(tool_name: &str, params: &serde_json::Value) -> St
             let pattern = params.get("pattern").and_then(|p| p.as_str()).unwrap_or("unknown");
             format!("Search for pattern '{}'", pattern)
         }
-        "Task" => {
+        "HttpRequest" => {
+            let method = params.get("method").and_then(|m| m.as_str()).unwrap_or("GET");
+            let url = params.get("url").and_then(|u| u.as_str()).unwrap_or("unknown");
+            match params.get("profile").and_then(|p| p.as_str()) {
+                Some(profile) => format!("Send {} request to '{}' with profile '{}'", method.to_uppercase(), url, profile),
+                None => format!("Send {} request to '{}'", method.to_uppercase(), url),
+            }
+        }
+        WATCH_PATH_TOOL => {
+            let path = params.get("path").and_then(|p| p.as_str()).unwrap_or("unknown");
+            format!("Watch '{}' for changes", path)
+        }
+        SPAWN_AGENT_TOOL => {
             let desc = params.get("description").and_then(|d| d.as_str()).unwrap_or("unknown");
-            format!("Spawn subagent task: {}", desc)
+            format!("Spawn sub-agent: {}", desc)
         }
         _ => format!("Execute tool '{}'", tool_name),
     }
 }
 
 /// Build message history from context
-fn build_messages(context: &Context) -> Vec<KosongMessage> {
+///
+/// Attachments are loaded again for every request; one that can no longer be
+/// loaded is replaced by a note saying so.
+fn build_messages(context: &Context, vision: bool) -> Vec<KosongMessage> {
     // Convert context messages to kosong messages
     context
         .messages()
@@ -301,7 +781,24 @@ fn build_messages(context: &Context) -> Vec<KosongMessage> {
                 crate::types::Role::System => KosongRole::System,
                 crate::types::Role::Tool => KosongRole::Tool,
             };
-            KosongMessage::new(role, msg.content.clone())
+            let attachments = msg.attachments();
+            let mut message = if attachments.is_empty() {
+                KosongMessage::new(role, msg.content.clone())
+            } else {
+                let mut parts = vec![ContentPart::text(msg.content.clone())];
+                for attachment in &attachments {
+                    parts.push(attachment::load_part(attachment, vision).unwrap_or_else(|e| {
+                        warn!("Dropping attachment: {}", e);
+                        ContentPart::text(format!("[Attachment unavailable: {}]", e))
+                    }));
+                }
+                KosongMessage::with_parts(role, parts)
+            };
+            // Providers pair each tool result with the call it answers
+            let tool_calls = msg.tool_calls();
+            message.tool_calls = (!tool_calls.is_empty()).then_some(tool_calls);
+            message.tool_call_id = msg.tool_call_id().map(str::to_string);
+            message
         })
         .collect()
 }
@@ -309,6 +806,7 @@ fn build_messages(context: &Context) -> Vec<KosongMessage> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::soul::testing::test_soul;
     use crate::types::Role;
     use std::path::PathBuf;
 
@@ -335,7 +833,7 @@ mod tests {
     #[test]
     fn test_build_messages() {
         let context = create_test_context();
-        let messages = build_messages(&context);
+        let messages = build_messages(&context, false);
 
         assert_eq!(messages.len(), 3);
         assert_eq!(messages[0].role, KosongRole::System);
@@ -349,7 +847,622 @@ mod tests {
     #[test]
     fn test_build_messages_empty_context() {
         let context = Context::new(PathBuf::from("/tmp/test_empty.json"));
-        let messages = build_messages(&context);
+        let messages = build_messages(&context, false);
         assert!(messages.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_retryable_failure_fails_over_to_fallback() {
+        use crate::soul::testing::ScriptedProvider;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let input = |text: &str| UserInput { text: text.to_string(), attachments: Vec::new() };
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        // Without a fallback the error surfaces
+        let outage = ScriptedProvider::failing(503);
+        let err = process_message(&mut soul, &outage, input("Hello"), &wire).await.unwrap_err();
+        assert!(err.is_retryable());
+
+        let fallback = Arc::new(ScriptedProvider::new(["Hello from the fallback."]));
+        soul.fallback_provider = Some(fallback.clone());
+        let reply = process_message(&mut soul, &outage, input("Hello again"), &wire).await.unwrap();
+        assert_eq!(reply, "Hello from the fallback.");
+        assert_eq!(outage.requests().len(), 2);
+        assert_eq!(fallback.requests()[0].last_text(), "Hello again");
+
+        // Errors a retry cannot fix are not failed over
+        let rejected = ScriptedProvider::failing(400);
+        let err = process_message(&mut soul, &rejected, input("Bad request"), &wire).await.unwrap_err();
+        assert!(!err.is_retryable());
+        assert_eq!(fallback.requests().len(), 1);
+
+        // Each hop along a fallback chain is reported
+        let chain: Vec<Box<dyn ChatProvider>> = vec![
+            Box::new(ScriptedProvider::failing(502)),
+            Box::new(ScriptedProvider::new(["Hello from the backup."])),
+        ];
+        let chain = kosong_rs::FallbackProvider::new(chain).unwrap().with_failover_handler({
+            let handler = soul.failover_handler();
+            move |failover| handler(failover)
+        });
+        soul.fallback_provider = Some(Arc::new(chain));
+        let reply = process_message(&mut soul, &outage, input("Anyone there?"), &wire).await.unwrap();
+        assert_eq!(reply, "Hello from the backup.");
+
+        drop(wire);
+        let mut notices = Vec::new();
+        while let Some(message) = rx.recv().await {
+            if let WireMessage::ProviderFailover { from, to, .. } = message {
+                notices.push((from, to));
+            }
+        }
+        // The failover to the chain, then the hop within it
+        assert_eq!(notices.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_during_tools_leaves_context_consistent() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::{Tool, ToolResult};
+        use kosong_rs::StreamChunk;
+        use std::sync::Arc;
+        use std::time::Duration;
+        use tokio::sync::mpsc;
+
+        /// A tool that never finishes
+        #[derive(Debug)]
+        struct Hang;
+
+        #[async_trait::async_trait]
+        impl Tool for Hang {
+            fn name(&self) -> &str {
+                "Hang"
+            }
+            fn description(&self) -> &str {
+                "Never returns"
+            }
+            fn parameters_schema(&self) -> serde_json::Value {
+                serde_json::json!({"type": "object"})
+            }
+            async fn execute(&self, _params: serde_json::Value) -> ToolResult {
+                std::future::pending().await
+            }
+        }
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.register_tool(Arc::new(Hang));
+        let provider = ScriptedProvider::with_chunks([
+            vec![
+                StreamChunk::Text("Running both.".to_string()),
+                StreamChunk::ToolCall(kosong_rs::ToolCall::new("call_1", "Hang", "{}")),
+                StreamChunk::ToolCall(kosong_rs::ToolCall::new("call_2", "Hang", "{}")),
+            ],
+            vec![StreamChunk::Text("Understood.".to_string())],
+        ]);
+        let input = |text: &str| UserInput { text: text.to_string(), attachments: Vec::new() };
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let interrupt = soul.interrupt_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            interrupt.interrupt();
+        });
+        let err = process_message(&mut soul, &provider, input("Run it"), &wire).await.unwrap_err();
+        assert!(matches!(err, SoulError::Cancelled));
+
+        // Both calls have a result, so the correction can follow at once
+        let messages = soul.context.messages();
+        assert!(matches!(messages[1].role, Role::Assistant));
+        assert!(messages[2..].iter().all(|m| matches!(m.role, Role::Tool)));
+        let contents: Vec<_> = messages[1..].iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["Running both.", INTERRUPTED_TOOL_RESULT, INTERRUPTED_TOOL_RESULT]);
+        let reply = process_message(&mut soul, &provider, input("Stop, do this instead"), &wire).await.unwrap();
+        assert_eq!(reply, "Understood.");
+        assert_eq!(provider.requests()[1].messages.len(), 5);
+
+        drop(wire);
+        let mut interrupted = 0;
+        while let Some(message) = rx.recv().await {
+            if matches!(message, WireMessage::StepInterrupted) {
+                interrupted += 1;
+            }
+        }
+        assert_eq!(interrupted, 1);
+    }
+
+    #[tokio::test]
+    async fn test_interrupt_cancels_the_stream() {
+        use crate::soul::testing::ScriptedProvider;
+        use std::time::Duration;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["Partial answer"]).stalling();
+        let (tx, _rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let interrupt = soul.interrupt_handle();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            interrupt.interrupt();
+        });
+        let input = UserInput { text: "Explain".to_string(), attachments: Vec::new() };
+        let turn = process_message(&mut soul, &provider, input, &wire);
+        let err = tokio::time::timeout(Duration::from_secs(1), turn).await.unwrap().unwrap_err();
+        assert!(matches!(err, SoulError::Cancelled));
+        assert_eq!(soul.context.messages().last().unwrap().content, "Partial answer");
+    }
+
+    #[tokio::test]
+    async fn test_edit_reviewed_by_the_user_is_applied() {
+        use crate::approval::Approval;
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::SimpleTool;
+        use kosong_rs::StreamChunk;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let approval = Arc::new(Approval::new());
+        let mut soul = test_soul(temp.path());
+        soul.approval = approval.clone();
+        soul.register_tool(Arc::new(SimpleTool::new("WriteFile", "Write", serde_json::json!({"type": "object"}), |params| {
+            std::fs::write(params["path"].as_str().unwrap(), params["content"].as_str().unwrap()).unwrap();
+            Ok(serde_json::json!("written"))
+        })));
+        let path = temp.path().join("notes.md");
+        let arguments = serde_json::json!({"path": path, "content": "draft\n"}).to_string();
+        let provider = ScriptedProvider::with_chunks([
+            vec![StreamChunk::ToolCall(kosong_rs::ToolCall::new("call", "WriteFile", &arguments))],
+            vec![StreamChunk::Text("Done.".to_string())],
+        ]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        // The frontend shows the diff and answers with the user's version
+        let frontend = tokio::spawn(async move {
+            let mut diffs = Vec::new();
+            while let Some(message) = rx.recv().await {
+                if let WireMessage::ApprovalRequest { diff, .. } = message {
+                    diffs.push(diff);
+                    let edit = approval.get_pending().await.unwrap().edit.unwrap();
+                    approval.respond_with_arguments(edit.arguments_with("final\n")).await.unwrap();
+                }
+            }
+            diffs
+        });
+
+        let input = UserInput { text: "Write notes".to_string(), attachments: Vec::new() };
+        assert_eq!(process_message(&mut soul, &provider, input, &wire).await.unwrap(), "Done.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "final\n");
+        assert!(soul.context.messages()[2].content.contains("The user edited the change"));
+
+        drop(wire);
+        let diffs = frontend.await.unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert!(diffs[0].as_deref().unwrap().contains("+draft"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_output_is_streamed() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::{Tool, ToolResult};
+        use crate::wire::OutputStream;
+        use kosong_rs::StreamChunk;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+
+        #[derive(Debug)]
+        struct Build;
+
+        #[async_trait::async_trait]
+        impl Tool for Build {
+            fn name(&self) -> &str {
+                "Build"
+            }
+
+            fn description(&self) -> &str {
+                "Build"
+            }
+
+            fn parameters_schema(&self) -> serde_json::Value {
+                serde_json::json!({"type": "object"})
+            }
+
+            async fn execute(&self, _params: serde_json::Value) -> ToolResult {
+                Ok(serde_json::json!("built"))
+            }
+
+            async fn execute_streaming(&self, params: serde_json::Value, output: OutputSink) -> ToolResult {
+                output.send(OutputStream::Stderr, "Compiling\n");
+                output.send(OutputStream::Stdout, "Finished\n");
+                self.execute(params).await
+            }
+        }
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.register_tool(Arc::new(Build));
+        let provider = ScriptedProvider::with_chunks([
+            vec![StreamChunk::ToolCall(kosong_rs::ToolCall::new("call", "Build", "{}"))],
+            vec![StreamChunk::Text("Done.".to_string())],
+        ]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let input = UserInput { text: "Build it".to_string(), attachments: Vec::new() };
+        assert_eq!(process_message(&mut soul, &provider, input, &wire).await.unwrap(), "Done.");
+
+        // The output arrives between the tool's begin and end
+        drop(wire);
+        let mut events = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::ToolBegin { .. } => events.push("begin".to_string()),
+                WireMessage::ToolOutputPart { tool_call_id, stream, text } => {
+                    assert_eq!(tool_call_id, "call");
+                    events.push(format!("{:?}: {}", stream, text.trim_end()));
+                }
+                WireMessage::ToolEnd { .. } => events.push("end".to_string()),
+                _ => {}
+            }
+        }
+        assert_eq!(events, ["begin", "Stderr: Compiling", "Stdout: Finished", "end"]);
+    }
+
+    // The watcher's task runs while the tool blocks its thread
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_watched_file_changes_are_noted() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::SimpleTool;
+        use crate::soul::watch::WatchPathTool;
+        use kosong_rs::StreamChunk;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let log = temp.path().canonicalize().unwrap().join("build.log");
+        let mut soul = test_soul(temp.path());
+        soul.register_tool(Arc::new(WatchPathTool::new()));
+        let build_log = log.clone();
+        soul.register_tool(Arc::new(SimpleTool::new("Build", "Build", serde_json::json!({"type": "object"}), move |_| {
+            std::fs::write(&build_log, "error: build failed").unwrap();
+            // Long enough for the change to be reported
+            std::thread::sleep(std::time::Duration::from_secs(1));
+            Ok(serde_json::json!("started"))
+        })));
+        let watch = serde_json::json!({"path": log}).to_string();
+        let provider = ScriptedProvider::with_chunks([
+            vec![StreamChunk::ToolCall(kosong_rs::ToolCall::new("c1", "WatchPath", &watch))],
+            vec![StreamChunk::ToolCall(kosong_rs::ToolCall::new("c2", "Build", "{}"))],
+            vec![StreamChunk::Text("The build failed.".to_string())],
+        ]);
+        let (tx, mut rx) = mpsc::channel(64);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let input = UserInput { text: "Build and watch the log".to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input, &wire).await.unwrap();
+
+        drop(wire);
+        let mut changed = Vec::new();
+        while let Some(message) = rx.recv().await {
+            if let WireMessage::FileChanged { path, changed: files } = message {
+                changed.push((path, files));
+            }
+        }
+        let log = log.display().to_string();
+        assert_eq!(changed, [(log.clone(), vec![log.clone()])]);
+        assert!(soul.context.messages().iter().any(|m| m.content.starts_with("Files changed under the watched path")));
+    }
+
+    #[tokio::test]
+    async fn test_repeated_tool_call_stops_the_turn() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::SimpleTool;
+        use crate::types::LoopControl;
+        use kosong_rs::StreamChunk;
+        use std::sync::Arc;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.loop_control = LoopControl { max_identical_tool_calls: 2, ..LoopControl::default() };
+        soul.register_tool(Arc::new(SimpleTool::new("Ls", "List", serde_json::json!({"type": "object"}), |_| {
+            Ok(serde_json::json!("a.txt"))
+        })));
+        let stats = crate::stats::UsageStore::new(temp.path().join("stats.json"));
+        soul.usage = Some(stats.clone());
+        let ls = || vec![StreamChunk::ToolCall(kosong_rs::ToolCall::new("call", "Ls", "{}"))];
+        let provider = ScriptedProvider::with_chunks([ls(), ls(), ls()]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let input = UserInput { text: "List files".to_string(), attachments: Vec::new() };
+        let err = process_message(&mut soul, &provider, input, &wire).await.unwrap_err();
+        assert!(matches!(err, SoulError::LimitReached(TurnLimit::IdenticalToolCalls { count: 3, .. })));
+        let last = soul.context.messages().last().unwrap();
+        assert!(matches!(last.role, Role::Tool));
+        assert!(last.content.starts_with("Not run: Ls was called 3 times"));
+
+        // The stopped turn still counts, with the tool calls that ran
+        let usage = stats.load().unwrap().total(None);
+        assert_eq!((usage.turns, usage.tool_calls), (1, 2));
+        assert!(usage.input_tokens > 0);
+
+        drop(wire);
+        let mut settings = Vec::new();
+        while let Some(message) = rx.recv().await {
+            if let WireMessage::TurnLimitReached { setting, .. } = message {
+                settings.push(setting);
+            }
+        }
+        assert_eq!(settings, ["max_identical_tool_calls"]);
+    }
+
+    #[tokio::test]
+    async fn test_tool_results_are_paired_with_their_calls() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::SimpleTool;
+        use kosong_rs::{StreamChunk, ToolCall};
+        use std::sync::Arc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.register_tool(Arc::new(SimpleTool::new("Ls", "List", serde_json::json!({"type": "object"}), |_| {
+            Ok(serde_json::json!("a.txt"))
+        })));
+        let calls = vec![ToolCall::new("call_1", "Ls", "{}"), ToolCall::new("call_2", "Ls", r#"{"all":true}"#)];
+        let provider = ScriptedProvider::with_chunks([
+            calls.iter().cloned().map(StreamChunk::ToolCall).collect(),
+            vec![StreamChunk::Text("One file.".to_string())],
+        ]);
+        let wire = WireSoulSide::new();
+        let input = UserInput { text: "List files".to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input, &wire).await.unwrap();
+
+        // The second request carries the calls and the results answering them
+        let requests = provider.requests();
+        let messages = &requests[1].messages;
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(messages[1].role, KosongRole::Assistant));
+        assert_eq!(messages[1].tool_calls.as_deref(), Some(calls.as_slice()));
+        assert!(messages[2..].iter().all(|m| matches!(m.role, KosongRole::Tool)));
+        let ids: Vec<_> = messages[2..].iter().map(|m| m.tool_call_id.as_deref()).collect();
+        assert_eq!(ids, [Some("call_1"), Some("call_2")]);
+        assert!(messages[0].tool_calls.is_none() && messages[0].tool_call_id.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_invalid_tool_arguments_get_a_result() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::toolset::SimpleTool;
+        use kosong_rs::{StreamChunk, ToolCall};
+        use std::sync::Arc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.register_tool(Arc::new(SimpleTool::new("Ls", "List", serde_json::json!({"type": "object"}), |_| {
+            Ok(serde_json::json!("a.txt"))
+        })));
+        let provider = ScriptedProvider::with_chunks([
+            vec![
+                StreamChunk::ToolCall(ToolCall::new("call_1", "Ls", r#"{"all": tru"#)),
+                StreamChunk::ToolCall(ToolCall::new("call_2", "Ls", "{}")),
+            ],
+            vec![StreamChunk::Text("One file.".to_string())],
+        ]);
+        let input = UserInput { text: "List files".to_string(), attachments: Vec::new() };
+        let reply = process_message(&mut soul, &provider, input, &WireSoulSide::new()).await.unwrap();
+        assert_eq!(reply, "One file.");
+
+        // The model is told what was wrong, and the other call still runs
+        let messages = &provider.requests()[1].messages;
+        let results: Vec<_> = messages[2..].iter().map(|m| (m.tool_call_id.as_deref(), m.text().unwrap_or_default())).collect();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].0, Some("call_1"));
+        assert!(results[0].1.starts_with("Invalid tool arguments:"), "{}", results[0].1);
+        assert_eq!(results[1], (Some("call_2"), "\"a.txt\"".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_relevant_skill_is_active_for_one_turn() {
+        use crate::skill::SkillDiscovery;
+        use crate::soul::testing::ScriptedProvider;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.agent = soul.agent.with_system_prompt("You are a test agent.");
+        let content = "---\ndescription: Write release notes from git commits\n---\n\nGroup commits by type.";
+        soul.skills.push(SkillDiscovery::parse_skill("changelog", content, temp.path().join("changelog")).unwrap());
+
+        let provider = ScriptedProvider::new(["Notes.", "Hello."]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+        let input = |text: &str| UserInput { text: text.to_string(), attachments: Vec::new() };
+
+        process_message(&mut soul, &provider, input("Write release notes for these commits"), &wire).await.unwrap();
+        assert!(soul.active_skill().is_none());
+        process_message(&mut soul, &provider, input("Say hello"), &wire).await.unwrap();
+
+        let requests = provider.requests();
+        let first = requests[0].system_prompt.as_deref().unwrap();
+        assert!(first.starts_with("You are a test agent.\n\n# Environment"));
+        assert!(first.contains("\n\n# Active Skill: changelog"));
+        assert!(first.ends_with("Group commits by type."));
+        let second = requests[1].system_prompt.as_deref().unwrap();
+        assert!(second.starts_with("You are a test agent.\n\n# Environment"));
+        assert!(!second.contains("# Active Skill"));
+
+        drop(wire);
+        let mut activated = Vec::new();
+        while let Some(message) = rx.recv().await {
+            if let WireMessage::SkillActivated { name } = message {
+                activated.push(name);
+            }
+        }
+        assert_eq!(activated, vec!["changelog"]);
+    }
+
+    #[tokio::test]
+    async fn test_steps_report_status() {
+        use crate::soul::testing::ScriptedProvider;
+        use tokio::sync::mpsc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["Hello there."]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+        let input = UserInput { text: "Say hello".to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input, &wire).await.unwrap();
+
+        drop(wire);
+        let mut steps = Vec::new();
+        let mut updates = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::StepBegin { n } => steps.push(n),
+                WireMessage::StatusUpdate { context_usage, token_usage, .. } => updates.push((context_usage, token_usage)),
+                _ => {}
+            }
+        }
+        assert_eq!(steps, [1]);
+        // One update before the model answers, one with the step's usage after
+        assert_eq!(updates.len(), 2);
+        assert!(updates.iter().all(|(usage, _)| usage.is_some_and(|usage| usage > 0.0 && usage < 1.0)));
+        assert!(updates[0].1.is_none());
+        let tokens = updates[1].1.as_ref().unwrap();
+        assert_eq!(tokens.output_tokens, estimate_tokens("Hello there."));
+        assert_eq!(tokens.total_tokens, tokens.input_tokens + tokens.output_tokens);
+
+        // Usage the provider reports replaces the estimate, and thinking
+        // reaches the wire without entering the context
+        let provider = ScriptedProvider::with_chunks([vec![
+            kosong_rs::StreamChunk::Thinking("Greet back.".to_string()),
+            kosong_rs::StreamChunk::Text("Hi again.".to_string()),
+            kosong_rs::StreamChunk::Usage(kosong_rs::TokenUsage::new(2000, 5)),
+        ]]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+        let input = UserInput { text: "Again".to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input, &wire).await.unwrap();
+
+        drop(wire);
+        let mut updates = Vec::new();
+        let mut thinking = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::StatusUpdate { context_usage, token_usage: Some(tokens), .. } => {
+                    updates.push((context_usage, tokens))
+                }
+                WireMessage::ThinkPart { text } => thinking.push(text),
+                _ => {}
+            }
+        }
+        assert_eq!(thinking, ["Greet back."]);
+        assert_eq!(soul.context.messages().last().unwrap().content, "Hi again.");
+        let (context_usage, tokens) = &updates[0];
+        assert_eq!((tokens.input_tokens, tokens.output_tokens, tokens.total_tokens), (2000, 5, 2005));
+        assert_eq!(*context_usage, Some(2000.0 / soul.context_window.max_tokens as f64));
+    }
+
+    #[tokio::test]
+    async fn test_attachments_reach_the_provider() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::types::Attachment;
+
+        let temp = tempfile::tempdir().unwrap();
+        let notes = temp.path().join("notes.txt");
+        std::fs::write(&notes, "Ship on Friday").unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["Friday", "Still Friday"]);
+        let wire = WireSoulSide::new();
+        let input = |attachments| UserInput { text: "When do we ship?".to_string(), attachments };
+
+        let missing = vec![Attachment::File { path: temp.path().join("missing.txt") }];
+        let err = process_message(&mut soul, &provider, input(missing), &wire).await.unwrap_err();
+        assert!(matches!(err, SoulError::Attachment(_)));
+        assert_eq!(soul.context.message_count(), 0);
+
+        let attachments = vec![Attachment::File { path: notes.clone() }];
+        process_message(&mut soul, &provider, input(attachments.clone()), &wire).await.unwrap();
+        assert_eq!(soul.context.messages()[0].attachments(), attachments);
+
+        // The file is read again for later requests
+        std::fs::remove_file(&notes).unwrap();
+        process_message(&mut soul, &provider, input(Vec::new()), &wire).await.unwrap();
+
+        let requests = provider.requests();
+        let parts = |n: usize| requests[n].messages[0].content.clone().unwrap();
+        let first = parts(0).as_parts().unwrap().to_vec();
+        assert_eq!(first[0], ContentPart::text("When do we ship?"));
+        assert!(first[1].as_text().unwrap().ends_with("\nShip on Friday\n</file>"));
+        assert!(parts(1).to_text().contains("[Attachment unavailable: Cannot read"));
+        assert_eq!(requests[1].messages[2].text().unwrap(), "When do we ship?");
+    }
+
+    #[tokio::test]
+    async fn test_smart_strategy_summarizes_with_the_turn_provider() {
+        use crate::soul::testing::ScriptedProvider;
+        use crate::soul::{assistant_message, user_message};
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        for n in 0..4 {
+            soul.context.add_message(user_message(format!("Question {}", n)));
+            soul.context.add_message(assistant_message("x".repeat(400)));
+        }
+        soul.context_window = ContextWindow::new(300, 1);
+        soul.context_window.strategy = CompactionStrategy::Smart;
+        let provider = ScriptedProvider::new(["Three questions were answered.", "Done."]);
+        let input = UserInput { text: "Next".to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input, &WireSoulSide::new()).await.unwrap();
+
+        let requests = provider.requests();
+        assert!(requests[0].last_text().contains("## User\n\nQuestion 0"));
+        assert!(requests[1].messages[0].text().unwrap().ends_with("Three questions were answered."));
+        // Down to half the window: the summary replaces three of the turns
+        let contents: Vec<_> = soul.context.messages().iter().map(|m| m.content.lines().next().unwrap()).collect();
+        assert_eq!(contents[0], "[Summary of 6 earlier messages, compacted to fit the context window]");
+        assert_eq!(contents[1], "Question 3");
+        assert_eq!(contents[3..], ["Next", "Done."]);
+    }
+
+    #[tokio::test]
+    async fn test_retrieved_code_is_in_the_prompt_for_one_turn() {
+        use crate::rag::testing::KeywordEmbeddings;
+        use crate::rag::{Retriever, WorkspaceIndex};
+        use crate::soul::testing::ScriptedProvider;
+        use std::sync::Arc;
+
+        let temp = tempfile::tempdir().unwrap();
+        let workspace = temp.path().join("workspace");
+        std::fs::create_dir_all(&workspace).unwrap();
+        std::fs::write(workspace.join("db.rs"), "// database pool").unwrap();
+        let mut soul = test_soul(temp.path());
+        let index = WorkspaceIndex::open(&workspace, temp.path().join("index.json"), "keywords").unwrap();
+        soul.retriever = Some(Retriever::new(index, Arc::new(KeywordEmbeddings::default())));
+
+        let provider = ScriptedProvider::new(["It pools.", "Hello."]);
+        let wire = WireSoulSide::new();
+        let input = |text: &str| UserInput { text: text.to_string(), attachments: Vec::new() };
+        process_message(&mut soul, &provider, input("How does the database work?"), &wire).await.unwrap();
+        assert!(soul.retrieved().is_empty());
+        process_message(&mut soul, &provider, input("Say hello"), &wire).await.unwrap();
+
+        let requests = provider.requests();
+        let first = requests[0].system_prompt.as_deref().unwrap();
+        assert!(first.contains("# Relevant Code"));
+        assert!(first.contains("db.rs:1-1\n```\n// database pool\n```"));
+        assert!(!requests[1].system_prompt.as_deref().unwrap().contains("# Relevant Code"));
+    }
 }
@@ -0,0 +1,101 @@
+//! The `/commit` command
+//!
+//! Asks the LLM for a commit message describing the changes git would
+//! commit. The model sees the diff, its stat and the subjects of recent
+//! commits, so the message can follow the log's style; it does not see the
+//! conversation. The shell shows the message for approval before anything
+//! is committed.
+
+use super::kimisoul::{KimiSoul, SoulError};
+use crate::git::Changes;
+use kosong_rs::{ChatProvider, Message as KosongMessage, Role as KosongRole};
+use tracing::info;
+
+/// Longest diff sent to the model, in characters; the stat still covers
+/// every file
+const MAX_DIFF_CHARS: usize = 50_000;
+
+impl KimiSoul {
+    /// Write a commit message for `changes` with `provider`
+    pub async fn write_commit_message(
+        &self,
+        provider: &dyn ChatProvider,
+        changes: &Changes,
+    ) -> Result<String, SoulError> {
+        let prompt = self.render_prompt(&self.prompts.commit);
+        info!("Writing a commit message for {} bytes of diff", changes.diff.len());
+        let messages = [KosongMessage::new(KosongRole::User, commit_request(changes))];
+        let reply = provider.generate_complete(Some(&prompt), &messages, None).await?;
+
+        let message = clean_message(&reply.text());
+        if message.is_empty() {
+            return Err(SoulError::SlashCommand("The model returned an empty commit message".to_string()));
+        }
+        Ok(message)
+    }
+}
+
+/// The changes as the model's request
+fn commit_request(changes: &Changes) -> String {
+    let mut out = String::new();
+    if !changes.recent_subjects.is_empty() {
+        out.push_str("Recent commits, newest first:\n");
+        for subject in &changes.recent_subjects {
+            out.push_str(&format!("- {}\n", subject));
+        }
+        out.push('\n');
+    }
+    out.push_str(&format!("Files changed:\n{}\n\nDiff:\n", changes.stat));
+    match changes.diff.char_indices().nth(MAX_DIFF_CHARS) {
+        Some((cut, _)) => {
+            out.push_str(&changes.diff[..cut]);
+            out.push_str("\n[diff truncated]\n");
+        }
+        None => out.push_str(&changes.diff),
+    }
+    out
+}
+
+/// The message out of the model's reply, unwrapping a surrounding code fence
+fn clean_message(reply: &str) -> String {
+    let trimmed = reply.trim();
+    let lines: Vec<&str> = trimmed.lines().collect();
+    let fenced = lines.len() >= 2 && lines[0].starts_with("```") && lines[lines.len() - 1].trim() == "```";
+    let body = match fenced {
+        true => lines[1..lines.len() - 1].join("\n"),
+        false => trimmed.to_string(),
+    };
+    let body = body.trim();
+    match body.is_empty() {
+        true => String::new(),
+        false => format!("{}\n", body),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+
+    #[tokio::test]
+    async fn test_write_commit_message() {
+        let temp = tempfile::tempdir().unwrap();
+        let soul = test_soul(temp.path());
+        let changes = Changes {
+            staged: true,
+            stat: " src/lib.rs | 2 +-".to_string(),
+            diff: "-old\n+new\n".to_string(),
+            recent_subjects: vec!["feat(cli): add watch".to_string()],
+        };
+        let provider = ScriptedProvider::new(["```\nfix(core): use the new value\n\nDetails.\n```", "  "]);
+
+        let message = soul.write_commit_message(&provider, &changes).await.unwrap();
+        assert_eq!(message, "fix(core): use the new value\n\nDetails.\n");
+        let request = &provider.requests()[0];
+        assert!(request.system_prompt.as_deref().unwrap().contains("Conventional Commits"));
+        assert!(request.last_text().starts_with("Recent commits, newest first:\n- feat(cli): add watch\n"));
+        assert!(request.last_text().ends_with("Diff:\n-old\n+new\n"));
+
+        assert!(soul.write_commit_message(&provider, &changes).await.is_err());
+    }
+}
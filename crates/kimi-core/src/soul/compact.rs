@@ -0,0 +1,228 @@
+//! The `/compact` command
+//!
+//! Summarizes the conversation with the LLM and replaces the summarized
+//! messages with the summary, kept as a system note. System messages,
+//! pinned messages and the most recent turn stay as they are; turns are
+//! summarized whole, so tool calls stay paired with their results. A
+//! summary from an earlier `/compact` is folded into the new one.
+
+use super::kimisoul::{KimiSoul, SoulError};
+use crate::types::{Message, Role};
+use kosong_rs::{ChatProvider, Message as KosongMessage, Role as KosongRole};
+use std::collections::HashMap;
+use tracing::info;
+
+/// Number of most recent turns that are never summarized
+pub const COMPACT_KEEP_TURNS: usize = 1;
+
+/// Metadata key marking a `/compact` summary, holding the number of
+/// messages it stands for
+pub(crate) const COMPACTED_KEY: &str = "compacted";
+
+/// Longest tool result quoted in the transcript, in characters
+const MAX_TOOL_RESULT_CHARS: usize = 2000;
+
+/// Result of running `/compact`
+#[derive(Debug, Clone)]
+pub struct CompactReport {
+    /// Messages replaced by the summary
+    pub removed: usize,
+    /// The summary the model wrote
+    pub summary: String,
+}
+
+impl KimiSoul {
+    /// Summarize the conversation before the most recent turn with
+    /// `provider` and put the summary in its place
+    ///
+    /// `instructions` are passed on to the model, e.g. what to focus on.
+    /// Returns a report with nothing removed when there is nothing to
+    /// summarize; the model is not called then.
+    pub async fn compact_with_llm(
+        &mut self,
+        provider: &dyn ChatProvider,
+        instructions: Option<&str>,
+    ) -> Result<CompactReport, SoulError> {
+        let selected = self.compactable();
+        let indices: Vec<usize> = (0..selected.len()).filter(|&i| selected[i]).collect();
+        let Some(&first) = indices.first() else {
+            return Ok(CompactReport {
+                removed: 0,
+                summary: String::new(),
+            });
+        };
+
+        let messages = self.context.messages();
+        let transcript = transcript(indices.iter().map(|&i| &messages[i]));
+        let count: u64 = indices.iter().map(|&i| compacted_count(&messages[i]).unwrap_or(1)).sum();
+
+        let mut prompt = self.render_prompt(&self.prompts.compact);
+        if let Some(instructions) = instructions.map(str::trim).filter(|i| !i.is_empty()) {
+            prompt.push_str("\n\nAdditional instructions from the user: ");
+            prompt.push_str(instructions);
+        }
+        info!("Summarizing {} messages for /compact", indices.len());
+        let summary = summarize(provider, &prompt, transcript).await?;
+        if summary.trim().is_empty() {
+            return Err(SoulError::Compaction("The model returned an empty summary".to_string()));
+        }
+
+        // Drop the summarized messages back to front, then put the summary
+        // where the first of them was
+        for &index in indices.iter().skip(1).rev() {
+            self.context.replace_messages(index..index + 1, Vec::new());
+        }
+        self.context.replace_messages(first..first + 1, vec![summary_message(&summary, count, "with /compact")]);
+
+        Ok(CompactReport {
+            removed: indices.len(),
+            summary,
+        })
+    }
+
+    /// Which messages `/compact` summarizes: those in unpinned turns before
+    /// the recent ones, apart from system messages other than earlier
+    /// summaries
+    fn compactable(&self) -> Vec<bool> {
+        let messages = self.context.messages();
+        let starts: Vec<usize> = messages
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| matches!(m.role, Role::User))
+            .map(|(i, _)| i)
+            .collect();
+        let mut selected = vec![false; messages.len()];
+        if starts.len() <= COMPACT_KEEP_TURNS {
+            return selected;
+        }
+        let recent = starts[starts.len() - COMPACT_KEEP_TURNS];
+
+        for (n, &start) in starts.iter().enumerate() {
+            if start >= recent {
+                break;
+            }
+            let end = starts.get(n + 1).copied().unwrap_or(messages.len()).min(recent);
+            let turn = &messages[start..end];
+            if turn.iter().any(|m| self.context.is_pinned(m)) {
+                continue;
+            }
+            for (index, message) in turn.iter().enumerate() {
+                selected[start + index] = !matches!(message.role, Role::System);
+            }
+        }
+        for (index, message) in messages[..recent].iter().enumerate() {
+            if compacted_count(message).is_some() {
+                selected[index] = true;
+            }
+        }
+        selected
+    }
+}
+
+/// Ask the model for a summary of `transcript`
+pub(crate) async fn summarize(
+    provider: &dyn ChatProvider,
+    prompt: &str,
+    transcript: String,
+) -> Result<String, kosong_rs::ChatError> {
+    let messages = [KosongMessage::new(KosongRole::User, transcript)];
+    let response = provider.generate_complete(Some(prompt), &messages, None).await?;
+    Ok(response.text().trim().to_string())
+}
+
+/// The messages as plain text for the summarizer
+pub(crate) fn transcript<'a>(messages: impl Iterator<Item = &'a Message>) -> String {
+    let mut out = String::from("Conversation to summarize:\n");
+    for message in messages {
+        let (label, content) = match message.role {
+            Role::User => ("User", message.content.clone()),
+            Role::Assistant => ("Assistant", message.content.clone()),
+            Role::System => ("Summary of earlier messages", message.content.clone()),
+            Role::Tool => ("Tool result", truncate(&message.content)),
+        };
+        if !content.trim().is_empty() {
+            out.push_str(&format!("\n## {}\n\n{}\n", label, content.trim()));
+        }
+        for call in tool_calls(message) {
+            out.push_str(&format!("\n## Tool call\n\n{}\n", call));
+        }
+    }
+    out
+}
+
+/// Tool calls recorded on an assistant message, as `Name(arguments)`
+fn tool_calls(message: &Message) -> Vec<String> {
+    let Some(calls) = message.metadata.as_ref().and_then(|m| m.get("tool_calls")) else {
+        return Vec::new();
+    };
+    let Ok(calls) = serde_json::from_value::<Vec<kosong_rs::ToolCall>>(calls.clone()) else {
+        return Vec::new();
+    };
+    calls
+        .into_iter()
+        .map(|call| format!("{}({})", call.function.name, call.function.arguments))
+        .collect()
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_TOOL_RESULT_CHARS) {
+        Some((cut, _)) => format!("{}\n[truncated]", &text[..cut]),
+        None => text.to_string(),
+    }
+}
+
+fn compacted_count(message: &Message) -> Option<u64> {
+    message.metadata.as_ref()?.get(COMPACTED_KEY)?.as_u64()
+}
+
+/// The system note standing in for `count` summarized messages, compacted
+/// as `how` says
+pub(crate) fn summary_message(summary: &str, count: u64, how: &str) -> Message {
+    Message {
+        role: Role::System,
+        content: format!("[Summary of {} earlier messages, compacted {}]\n\n{}", count, how, summary),
+        metadata: Some(HashMap::from([(COMPACTED_KEY.to_string(), serde_json::json!(count))])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use crate::soul::{assistant_message, system_message, user_message};
+
+    #[tokio::test]
+    async fn test_compact_summarizes_older_turns() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["**Goal**: add an API.", "**Goal**: add and test an API."]);
+        assert_eq!(soul.compact_with_llm(&provider, None).await.unwrap().removed, 0);
+
+        soul.context.add_message(system_message("You are helpful."));
+        soul.context.add_message(user_message("Add an endpoint"));
+        soul.context.add_message(assistant_message("Added GET /items."));
+        soul.context.add_message(user_message("Now test it"));
+        soul.context.add_message(assistant_message("Tests pass."));
+
+        let report = soul.compact_with_llm(&provider, Some("focus on the API changes")).await.unwrap();
+        assert_eq!(report.removed, 2);
+        let request = &provider.requests()[0];
+        assert!(request.system_prompt.as_deref().unwrap().ends_with("Additional instructions from the user: focus on the API changes"));
+        assert!(request.last_text().contains("## User\n\nAdd an endpoint"));
+        assert!(!request.last_text().contains("Now test it"));
+
+        let contents: Vec<_> = soul.context.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents[0], "You are helpful.");
+        assert!(contents[1].ends_with("**Goal**: add an API."));
+        assert_eq!(contents[2..], ["Now test it", "Tests pass."]);
+
+        // The next compaction folds the earlier summary in
+        soul.context.add_message(user_message("Ship it"));
+        let report = soul.compact_with_llm(&provider, None).await.unwrap();
+        assert_eq!(report.removed, 3);
+        assert!(provider.requests()[1].last_text().contains("## Summary of earlier messages"));
+        let messages = soul.context.messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(compacted_count(&messages[1]), Some(4));
+    }
+}
@@ -3,7 +3,11 @@
 //! Provides mechanisms to reduce context size when approaching token limits,
 //! including summarization and checkpoint-based truncation.
 
+use super::compact::{summarize, summary_message, transcript, COMPACTED_KEY};
+use super::context_window::{message_tokens, oldest_turns, trimmed_count};
 use crate::context::Context;
+use kosong_rs::{ChatError, ChatProvider};
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::{info, warn};
 
@@ -14,6 +18,22 @@ pub enum CompactionError {
     NoCheckpoint,
     #[error("Context error: {0}")]
     Context(#[from] crate::context::ContextError),
+    #[error("Summarizing failed: {0}")]
+    Llm(#[from] ChatError),
+    #[error("The model returned an empty summary")]
+    EmptySummary,
+}
+
+/// How the context is shrunk when it outgrows the context window
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionStrategy {
+    /// Drop the oldest turns, listing what the user asked in them; see
+    /// [`ContextWindow`](super::context_window::ContextWindow)
+    #[default]
+    Trim,
+    /// Have the model summarize the oldest turns; see [`SmartCompaction`]
+    Smart,
 }
 
 /// Trait for context compaction strategies
@@ -23,7 +43,8 @@ pub trait Compaction: Send + Sync {
     /// Returns the number of messages removed, if any.
     fn compact(&self, context: &mut Context) -> Result<usize, CompactionError>;
 
-    /// Check if compaction is needed based on current token count
+    /// Check if compaction is needed: whether the context's tokens exceed
+    /// `max_tokens`, the model's context window
     fn is_needed(&self, context: &Context, max_tokens: usize) -> bool {
         context.token_count() > max_tokens
     }
@@ -81,7 +102,8 @@ impl Compaction for SimpleCompaction {
             return Ok(removed);
         }
 
-        // If no checkpoint exists, we need to create one and clear messages
+        // If no checkpoint exists, we need to create one and clear the
+        // messages that are not pinned
         warn!("No checkpoint available for compaction, creating emergency checkpoint");
         
         // Create a checkpoint with a summary
@@ -93,17 +115,12 @@ impl Compaction for SimpleCompaction {
         
         context.create_checkpoint(Some(summary));
         
-        // Clear all messages
-        let count = context.message_count();
-        context.clear_messages();
+        let count = context.remove_unpinned(0..context.message_count());
         
         info!("Created emergency checkpoint and cleared {} messages", count);
         Ok(count)
     }
 
-    fn is_needed(&self, context: &Context, _max_tokens: usize) -> bool {
-        context.needs_compaction(self.max_tokens)
-    }
 }
 
 /// Aggressive compaction that keeps only the most recent messages
@@ -135,18 +152,14 @@ impl Compaction for AggressiveCompaction {
             return Ok(0);
         }
 
-        // Keep only the most recent messages
+        // Keep only the most recent messages, and pinned ones
         let keep_index = total_messages - self.keep_recent;
-        let removed = keep_index;
+        let removed = context.remove_unpinned(0..keep_index);
         
-        // Create a checkpoint before truncation
+        // Create a checkpoint noting what was truncated
         let summary = format!("[Truncated {} older messages]", removed);
         context.create_checkpoint(Some(summary));
         
-        // Truncate messages (this requires mutable access)
-        let messages_mut = context.messages_mut();
-        messages_mut.drain(0..keep_index);
-        
         warn!(
             "Aggressively compacted context, removed {} messages, kept {}",
             removed, self.keep_recent
@@ -157,12 +170,20 @@ impl Compaction for AggressiveCompaction {
 }
 
 /// Smart compaction that uses summarization
+///
+/// [`summarize`](Self::summarize) has the model summarize the oldest
+/// turns into a single system note. Like trimming, it keeps system
+/// messages, pinned turns and the most recent turns, and takes turns whole
+/// so tool calls stay paired with their results. Without a provider,
+/// [`Compaction::compact`] drops the oldest messages instead.
 #[derive(Debug, Clone)]
 pub struct SmartCompaction {
     /// Maximum tokens allowed
     pub max_tokens: usize,
     /// Target token count after compaction
     pub target_tokens: usize,
+    /// Number of most recent turns that are never summarized
+    pub keep_turns: usize,
 }
 
 impl SmartCompaction {
@@ -171,13 +192,58 @@ impl SmartCompaction {
         Self {
             max_tokens,
             target_tokens: max_tokens / 2,
+            keep_turns: 4,
+        }
+    }
+
+    /// Summarize the oldest turns with `provider`, instructed by `prompt`,
+    /// until the context is estimated to fit in `target_tokens`
+    ///
+    /// An earlier summary before the summarized turns is folded into the
+    /// new one. Returns the number of messages replaced by the summary; the
+    /// model is not called when nothing needs summarizing.
+    pub async fn summarize(
+        &self,
+        context: &mut Context,
+        provider: &dyn ChatProvider,
+        prompt: &str,
+    ) -> Result<usize, CompactionError> {
+        let excess = context.token_count().saturating_sub(self.target_tokens);
+        let mut selected = oldest_turns(context, self.keep_turns, excess);
+        let Some(last) = selected.iter().rposition(|&s| s) else {
+            return Ok(0);
+        };
+        let messages = context.messages();
+        for (index, message) in messages[..last].iter().enumerate() {
+            if summarized_count(message).is_some() || trimmed_count(message).is_some() {
+                selected[index] = true;
+            }
         }
+        let indices: Vec<usize> = (0..selected.len()).filter(|&i| selected[i]).collect();
+        let count: u64 = indices
+            .iter()
+            .map(|&i| summarized_count(&messages[i]).or(trimmed_count(&messages[i])).unwrap_or(1))
+            .sum();
+
+        info!("Summarizing {} messages to fit the context window", indices.len());
+        let summary = summarize(provider, prompt, transcript(indices.iter().map(|&i| &messages[i]))).await?;
+        if summary.is_empty() {
+            return Err(CompactionError::EmptySummary);
+        }
+
+        // Drop the summarized messages back to front, then put the summary
+        // where the first of them was
+        for &index in indices.iter().skip(1).rev() {
+            context.replace_messages(index..index + 1, Vec::new());
+        }
+        let note = summary_message(&summary, count, "to fit the context window");
+        context.replace_messages(indices[0]..indices[0] + 1, vec![note]);
+        Ok(indices.len())
     }
 
-    /// Estimate token count for a message (rough approximation)
+    /// Tokens of a message as its context counted them
     fn estimate_tokens(&self, message: &crate::types::Message) -> usize {
-        // Rough estimate: 1 token ≈ 4 characters
-        message.content.len() / 4 + 10 // +10 for metadata
+        message_tokens(message)
     }
 }
 
@@ -210,30 +276,32 @@ impl Compaction for SmartCompaction {
             return Ok(0);
         }
 
-        // Create a summary checkpoint
-        let summary = format!(
-            "[Summarized {} messages, ~{} tokens]",
-            messages_to_summarize, accumulated_tokens
-        );
+        // Remove the summarized messages, apart from pinned ones
+        let removed = context.remove_unpinned(0..messages_to_summarize);
         
+        // Create a summary checkpoint
+        let summary = format!("[Summarized {} messages, ~{} tokens]", removed, accumulated_tokens);
         context.create_checkpoint(Some(summary));
         
-        // Remove the summarized messages
-        let messages_mut = context.messages_mut();
-        messages_mut.drain(0..messages_to_summarize);
-        
         info!(
             "Smart compacted context, summarized {} messages (~{} tokens)",
-            messages_to_summarize, accumulated_tokens
+            removed, accumulated_tokens
         );
         
-        Ok(messages_to_summarize)
+        Ok(removed)
     }
 }
 
+/// Number of messages an earlier summary stands for
+fn summarized_count(message: &crate::types::Message) -> Option<u64> {
+    message.metadata.as_ref()?.get(COMPACTED_KEY)?.as_u64()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::soul::testing::ScriptedProvider;
+    use crate::soul::{assistant_message, system_message, user_message};
     use crate::types::{Message, Role};
     use std::path::PathBuf;
 
@@ -275,8 +343,9 @@ mod tests {
         
         assert!(!compaction.is_needed(&context, 100));
         
-        context.set_token_count(150);
+        context.add_message(create_test_message(Role::User, &"x".repeat(600)));
         assert!(compaction.is_needed(&context, 100));
+        assert!(!compaction.is_needed(&context, 200));
     }
 
     #[test]
@@ -294,6 +363,31 @@ mod tests {
         assert_eq!(context.message_count(), 5); // Kept 5 messages
     }
 
+    #[test]
+    fn test_compaction_keeps_pinned_messages() {
+        let mut context = Context::new(PathBuf::from("/tmp/test.json"));
+        for i in 0..10 {
+            context.add_message(create_test_message(Role::User, &format!("Message {}", i)));
+        }
+        let tasks = context.messages()[1].id().unwrap().to_string();
+        context.pin_message(&tasks).unwrap();
+
+        assert_eq!(AggressiveCompaction::new(1000, 5).compact(&mut context).unwrap(), 4);
+        assert_eq!(context.messages()[0].content, "Message 1");
+        assert_eq!(context.message_count(), 6);
+        assert_eq!(context.last_checkpoint().unwrap().summary.as_deref(), Some("[Truncated 4 older messages]"));
+
+        // Without a checkpoint to go back to, only pinned messages stay
+        let mut fresh = Context::new(PathBuf::from("/tmp/test.json"));
+        fresh.add_message(create_test_message(Role::System, "Summary of AGENTS.md"));
+        fresh.add_message(create_test_message(Role::User, "Hello"));
+        let summary = fresh.messages()[0].id().unwrap().to_string();
+        fresh.pin_message(&summary).unwrap();
+        assert_eq!(SimpleCompaction::new(10).compact(&mut fresh).unwrap(), 1);
+        assert_eq!(fresh.messages()[0].content, "Summary of AGENTS.md");
+        assert_eq!(fresh.message_count(), 1);
+    }
+
     #[test]
     fn test_aggressive_compaction_not_enough_messages() {
         let compaction = AggressiveCompaction::new(1000, 10);
@@ -322,13 +416,65 @@ mod tests {
                 i
             )));
         }
-        
-        // Manually set token count to simulate high usage
-        context.set_token_count(500);
+        assert!(context.token_count() > compaction.target_tokens);
         
         let removed = compaction.compact(&mut context).unwrap();
         // Note: removed may be 0 if the estimated tokens don't exceed target
         // The test mainly verifies the compaction logic runs without error
         assert_eq!(context.checkpoints().len(), if removed > 0 { 1 } else { 0 });
     }
+
+    #[tokio::test]
+    async fn test_smart_compaction_summarizes_with_the_model() {
+        let mut context = Context::new(PathBuf::from("/tmp/test_smart_compaction.json"));
+        context.add_message(system_message("You are Kimi."));
+        for n in 0..6 {
+            context.add_message(user_message(format!("Question {}", n)));
+            context.add_message(assistant_message("x".repeat(400)));
+        }
+        let compaction = SmartCompaction { max_tokens: 700, target_tokens: 250, keep_turns: 2 };
+        let provider = ScriptedProvider::new(["The user asked four questions.", "Six questions so far."]);
+
+        assert_eq!(compaction.summarize(&mut context, &provider, "Summarize.").await.unwrap(), 8);
+        let request = &provider.requests()[0];
+        assert_eq!(request.system_prompt.as_deref(), Some("Summarize."));
+        assert!(request.last_text().contains("## User\n\nQuestion 3"));
+        assert!(!request.last_text().contains("Question 4"));
+        let contents: Vec<_> = context.messages().iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents[0], "You are Kimi.");
+        assert!(contents[1].starts_with("[Summary of 8 earlier messages, compacted to fit the context window]"));
+        assert!(contents[1].ends_with("The user asked four questions."));
+        assert_eq!(contents[2..], ["Question 4", &"x".repeat(400), "Question 5", &"x".repeat(400)]);
+
+        // The next summary folds the earlier one in
+        context.add_message(user_message("Question 6"));
+        context.add_message(assistant_message("x".repeat(400)));
+        assert_eq!(compaction.summarize(&mut context, &provider, "Summarize.").await.unwrap(), 3);
+        assert!(provider.requests()[1].last_text().contains("## Summary of earlier messages"));
+        assert_eq!(summarized_count(&context.messages()[1]), Some(10));
+
+        // Nothing to summarize: the model is not called
+        assert_eq!(compaction.summarize(&mut context, &provider, "Summarize.").await.unwrap(), 0);
+        assert_eq!(provider.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_smart_compaction_keeps_pinned_messages() {
+        let mut context = Context::new(PathBuf::from("/tmp/test_smart_compaction_pins.json"));
+        context.add_message(system_message("You are Kimi."));
+        for n in 0..6 {
+            context.add_message(user_message(format!("Question {}", n)));
+            context.add_message(assistant_message("x".repeat(400)));
+        }
+        let decision = context.messages()[3].id().unwrap().to_string();
+        context.pin_message(&decision).unwrap();
+        let compaction = SmartCompaction { max_tokens: 700, target_tokens: 250, keep_turns: 2 };
+        let provider = ScriptedProvider::new(["The user asked some questions."]);
+
+        assert!(compaction.summarize(&mut context, &provider, "Summarize.").await.unwrap() > 0);
+        assert!(!provider.requests()[0].last_text().contains("Question 1"));
+        let contents: Vec<_> = context.messages().iter().map(|m| m.content.as_str()).collect();
+        assert!(contents.contains(&"Question 1"));
+        assert_eq!(context.pinned_messages()[0].content, "Question 1");
+    }
 }
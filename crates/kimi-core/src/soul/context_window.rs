@@ -0,0 +1,287 @@
+//! Context window management
+//!
+//! When the conversation outgrows the model's context window, the oldest
+//! turns in the middle of it are dropped and replaced by a short summary
+//! listing what the user asked in them. System messages, pinned messages and
+//! the most recent turns are always kept. Turns are dropped whole, so tool
+//! calls stay paired with their results; a pinned message keeps its turn.
+//!
+//! With the [`Smart`](CompactionStrategy::Smart) strategy, the turns are
+//! summarized by the model instead, see [`SmartCompaction`].
+
+use super::compaction::{Compaction, CompactionError, CompactionStrategy, SmartCompaction};
+use super::rewind::checkpoint_label;
+use crate::context::{Context, MESSAGE_OVERHEAD_TOKENS};
+use crate::prompts::builder::estimate_tokens;
+use crate::types::{Message, Role};
+use std::collections::HashMap;
+use std::ops::Range;
+use tracing::info;
+
+/// Metadata key marking a summary of trimmed messages, holding their count
+const TRIMMED_KEY: &str = "trimmed";
+
+/// Keeps the context within a token budget by trimming its middle
+#[derive(Debug, Clone)]
+pub struct ContextWindow {
+    /// Estimated tokens the messages may use before trimming
+    pub max_tokens: usize,
+    /// Number of most recent turns that are never trimmed
+    pub keep_turns: usize,
+    /// Whether turns are trimmed or summarized by the model
+    pub strategy: CompactionStrategy,
+}
+
+impl Default for ContextWindow {
+    fn default() -> Self {
+        Self {
+            max_tokens: 96_000,
+            keep_turns: 4,
+            strategy: CompactionStrategy::default(),
+        }
+    }
+}
+
+impl ContextWindow {
+    pub fn new(max_tokens: usize, keep_turns: usize) -> Self {
+        Self { max_tokens, keep_turns, strategy: CompactionStrategy::default() }
+    }
+
+    /// Smart compaction for this window, summarizing down to half the
+    /// budget so the next summary is some turns away
+    pub fn smart_compaction(&self) -> SmartCompaction {
+        SmartCompaction {
+            max_tokens: self.max_tokens,
+            target_tokens: self.max_tokens / 2,
+            keep_turns: self.keep_turns,
+        }
+    }
+
+    /// Estimated tokens used by the context's messages
+    pub fn estimate(context: &Context) -> usize {
+        context.token_count()
+    }
+
+    /// Whether the context is over budget
+    pub fn is_over(&self, context: &Context) -> bool {
+        Self::estimate(context) > self.max_tokens
+    }
+
+    /// Drop the oldest unpinned turns before the recent ones until the
+    /// context fits, replacing each dropped run with a summary
+    ///
+    /// Returns the number of messages removed.
+    pub fn trim(&self, context: &mut Context) -> usize {
+        let excess = Self::estimate(context).saturating_sub(self.max_tokens);
+        let mut dropped = oldest_turns(context, self.keep_turns, excess);
+
+        // Earlier summaries before the last dropped message are folded into
+        // the new ones
+        let messages = context.messages();
+        if let Some(last) = dropped.iter().rposition(|&d| d) {
+            for (index, message) in messages[..last].iter().enumerate() {
+                if trimmed_count(message).is_some() {
+                    dropped[index] = true;
+                }
+            }
+        }
+
+        // Replace the runs back to front so earlier indices stay valid
+        let runs = runs(&dropped);
+        let mut removed = 0;
+        for range in runs.into_iter().rev() {
+            let summary = summarize(&context.messages()[range.clone()]);
+            removed += range.len();
+            context.replace_messages(range, vec![summary]);
+        }
+        info!("Trimmed {} messages from the middle of the context", removed);
+        removed
+    }
+}
+
+impl Compaction for ContextWindow {
+    fn compact(&self, context: &mut Context) -> Result<usize, CompactionError> {
+        Ok(self.trim(context))
+    }
+
+    fn is_needed(&self, context: &Context, _max_tokens: usize) -> bool {
+        self.is_over(context)
+    }
+}
+
+/// Which messages to drop to free `excess` tokens: those of the oldest
+/// unpinned turns before the `keep_turns` most recent ones, apart from
+/// system messages
+///
+/// Nothing is selected when `excess` is 0.
+pub(crate) fn oldest_turns(context: &Context, keep_turns: usize, mut excess: usize) -> Vec<bool> {
+    let messages = context.messages();
+    let mut selected = vec![false; messages.len()];
+    let starts: Vec<usize> = messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| matches!(m.role, Role::User))
+        .map(|(i, _)| i)
+        .collect();
+    if excess == 0 || starts.len() <= keep_turns {
+        return selected;
+    }
+    let recent = starts[starts.len() - keep_turns];
+
+    // Pick whole turns, oldest first
+    for (n, &start) in starts.iter().enumerate() {
+        if excess == 0 || start >= recent {
+            break;
+        }
+        let end = starts.get(n + 1).copied().unwrap_or(messages.len()).min(recent);
+        let turn = &messages[start..end];
+        if turn.iter().any(|m| context.is_pinned(m)) {
+            continue;
+        }
+        for (index, message) in turn.iter().enumerate() {
+            if matches!(message.role, Role::System) {
+                continue;
+            }
+            selected[start + index] = true;
+            excess = excess.saturating_sub(message_tokens(message));
+        }
+    }
+    selected
+}
+
+/// Tokens of a message as its context counted them, or a rough estimate
+/// including its metadata for a message not in a context
+pub(crate) fn message_tokens(message: &Message) -> usize {
+    message.tokens().unwrap_or_else(|| {
+        let metadata = message
+            .metadata
+            .as_ref()
+            .map(|m| m.values().map(|v| estimate_tokens(&v.to_string())).sum())
+            .unwrap_or(0);
+        estimate_tokens(&message.content) + metadata + MESSAGE_OVERHEAD_TOKENS
+    })
+}
+
+pub(crate) fn trimmed_count(message: &Message) -> Option<u64> {
+    message.metadata.as_ref()?.get(TRIMMED_KEY)?.as_u64()
+}
+
+/// Ranges of consecutive `true` entries
+fn runs(flags: &[bool]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, &flag) in flags.iter().chain(std::iter::once(&false)).enumerate() {
+        match (flag, start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                runs.push(s..i);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// A system message standing in for `dropped`
+fn summarize(dropped: &[Message]) -> Message {
+    let mut count = 0;
+    let mut requests = Vec::new();
+    for message in dropped {
+        match trimmed_count(message) {
+            Some(earlier) => {
+                count += earlier;
+                requests.extend(message.content.lines().filter(|l| l.starts_with("- ")).map(str::to_string));
+            }
+            None => {
+                count += 1;
+                if matches!(message.role, Role::User) {
+                    requests.push(format!("- {}", checkpoint_label(&message.content)));
+                }
+            }
+        }
+    }
+
+    let mut content = format!(
+        "[{} earlier messages were removed to fit the context window.",
+        count
+    );
+    if requests.is_empty() {
+        content.push(']');
+    } else {
+        content.push_str(" In them, the user asked:]\n");
+        content.push_str(&requests.join("\n"));
+    }
+    Message {
+        role: Role::System,
+        content,
+        metadata: Some(HashMap::from([(TRIMMED_KEY.to_string(), serde_json::json!(count))])),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::{assistant_message, system_message, user_message};
+    use std::path::PathBuf;
+
+    fn conversation(turns: usize) -> Context {
+        let mut context = Context::new(PathBuf::from("/tmp/test_context_window.json"));
+        context.add_message(system_message("You are Kimi."));
+        for n in 0..turns {
+            context.add_message(user_message(format!("Question {}", n)));
+            context.add_message(assistant_message("x".repeat(400)));
+        }
+        context
+    }
+
+    fn contents(context: &Context) -> Vec<String> {
+        context.messages().iter().map(|m| m.content.lines().next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_under_budget_is_untouched() {
+        let mut context = conversation(3);
+        let window = ContextWindow::new(10_000, 1);
+        assert!(!window.is_over(&context));
+        assert_eq!(window.trim(&mut context), 0);
+        assert_eq!(context.message_count(), 7);
+    }
+
+    #[test]
+    fn test_trim_keeps_system_pinned_and_recent() {
+        let mut context = conversation(6);
+        let pinned = context.messages()[5].id().unwrap().to_string();
+        assert_eq!(context.messages()[5].content, "Question 2");
+        context.pin_message(&pinned).unwrap();
+
+        let window = ContextWindow::new(350, 2);
+        assert!(window.is_over(&context));
+        assert_eq!(window.trim(&mut context), 6);
+        assert_eq!(
+            contents(&context),
+            vec![
+                "You are Kimi.",
+                "[4 earlier messages were removed to fit the context window. In them, the user asked:]",
+                "Question 2",
+                &"x".repeat(400),
+                "[2 earlier messages were removed to fit the context window. In them, the user asked:]",
+                "Question 4",
+                &"x".repeat(400),
+                "Question 5",
+                &"x".repeat(400),
+            ]
+        );
+        assert!(context.messages()[1].content.ends_with("- Question 0\n- Question 1"));
+
+        // A later trim folds the earlier summaries into one
+        context.unpin_message(&pinned);
+        ContextWindow::new(250, 1).trim(&mut context);
+        assert_eq!(context.message_count(), 4);
+        assert_eq!(
+            context.messages()[1].content,
+            "[10 earlier messages were removed to fit the context window. In them, the user asked:]\n\
+             - Question 0\n- Question 1\n- Question 2\n- Question 3\n- Question 4"
+        );
+    }
+}
@@ -0,0 +1,758 @@
+//! User-defined slash commands
+//!
+//! Custom commands are markdown files in `~/.kimi/commands/` (user scope) or
+//! `.kimi/commands/` in the working directory (project scope). The file name
+//! is the command name, files in subdirectories are namespaced as `dir:name`,
+//! and the markdown body is sent to the agent as a prompt when invoked.
+//! Bodies may use `$ARGUMENTS` and `$1`..`$n` placeholders, inline
+//! `` !`command` `` output and `@file` references.
+//!
+//! ```markdown
+//! ---
+//! description: Review the staged changes
+//! allowed-tools: [ReadFile, Grep, Shell]
+//! model: kimi-k2
+//! ---
+//!
+//! Review the staged changes and point out bugs. Focus on $ARGUMENTS.
+//!
+//! !`git diff --staged`
+//! ```
+
+use super::chat;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::WireSoulSide;
+use crate::approval::{command_pattern, PermissionMode, SHELL_TOOL};
+use crate::attachment;
+use crate::skill::frontmatter::parse_frontmatter_as;
+use crate::types::{ApprovalKind, Request, UserInput};
+use crate::wire::WireMessage;
+use crate::workspace::Workspace;
+use kosong_rs::ChatProvider;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Where a custom command was loaded from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandScope {
+    /// `~/.kimi/commands/`
+    User,
+    /// `.kimi/commands/` in the working directory
+    Project,
+}
+
+impl fmt::Display for CommandScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandScope::User => write!(f, "user"),
+            CommandScope::Project => write!(f, "project"),
+        }
+    }
+}
+
+/// A slash command defined in a markdown file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CustomCommand {
+    /// Command name (without the leading /)
+    pub name: String,
+    /// Description shown in help
+    pub description: String,
+    /// Tools the prompt may use; `None` keeps the full toolset
+    pub allowed_tools: Option<Vec<String>>,
+    /// Model to run the prompt with instead of the default
+    pub model: Option<String>,
+    /// Prompt template
+    pub body: String,
+    /// File the command was loaded from
+    pub path: PathBuf,
+    /// Where the file was found
+    pub scope: CommandScope,
+}
+
+/// Frontmatter keys recognised in command files
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CommandFrontmatter {
+    description: Option<String>,
+    #[serde(alias = "allowed_tools")]
+    allowed_tools: Option<ToolList>,
+    model: Option<String>,
+}
+
+/// `allowed-tools` may be a YAML list or a comma-separated string
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ToolList {
+    List(Vec<String>),
+    Csv(String),
+}
+
+impl ToolList {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            ToolList::List(tools) => tools,
+            ToolList::Csv(tools) => tools
+                .split(',')
+                .map(|tool| tool.trim().to_string())
+                .filter(|tool| !tool.is_empty())
+                .collect(),
+        }
+    }
+}
+
+impl CustomCommand {
+    /// Parse a command file
+    ///
+    /// Without a `description` in the frontmatter, the first line of the body is used.
+    pub fn parse(name: &str, content: &str, path: PathBuf, scope: CommandScope) -> Result<Self, String> {
+        let (frontmatter, body) = parse_frontmatter_as::<CommandFrontmatter>(content)?;
+        let body = body.trim().to_string();
+        if body.is_empty() {
+            return Err("command body is empty".to_string());
+        }
+
+        let description = frontmatter
+            .description
+            .filter(|d| !d.trim().is_empty())
+            .unwrap_or_else(|| {
+                body.lines()
+                    .next()
+                    .unwrap_or_default()
+                    .trim_start_matches('#')
+                    .trim()
+                    .to_string()
+            });
+
+        Ok(Self {
+            name: name.to_string(),
+            description,
+            allowed_tools: frontmatter.allowed_tools.map(ToolList::into_vec),
+            model: frontmatter.model.filter(|m| !m.trim().is_empty()),
+            body,
+            path,
+            scope,
+        })
+    }
+
+    /// Build the prompt for an invocation with `args`, substituting placeholders
+    ///
+    /// `$ARGUMENTS` is replaced by the whole argument string and `$1`..`$n` by
+    /// the individual arguments (missing ones become empty). When the body has
+    /// no placeholders, non-empty arguments are appended instead. Inline
+    /// `` !`command` `` and `@file` references are left untouched; see
+    /// [`KimiSoul::render_custom_command`].
+    pub fn render(&self, args: &str) -> String {
+        let args = Arguments::parse(args);
+        let (text, used) = args.substitute(&self.body, false);
+        args.finish(text, used)
+    }
+
+    /// Whether the command's `allowed-tools` includes `tool`
+    pub fn allows_tool(&self, tool: &str) -> bool {
+        self.allowed_tools
+            .as_ref()
+            .is_some_and(|tools| tools.iter().any(|t| t == tool))
+    }
+}
+
+/// Arguments of a custom command invocation
+struct Arguments<'a> {
+    raw: &'a str,
+    positional: Vec<String>,
+}
+
+impl<'a> Arguments<'a> {
+    fn parse(raw: &'a str) -> Self {
+        let raw = raw.trim();
+        Self {
+            raw,
+            positional: split_arguments(raw),
+        }
+    }
+
+    /// Replace `$ARGUMENTS` and `$N` in `template`, shell-quoting values when
+    /// `quote` is set; returns the text and whether any placeholder was found
+    fn substitute(&self, template: &str, quote: bool) -> (String, bool) {
+        let mut out = String::with_capacity(template.len());
+        let mut used = false;
+        let mut rest = template;
+
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            let after = &rest[pos + 1..];
+            if let Some(tail) = after.strip_prefix("ARGUMENTS") {
+                if quote {
+                    let quoted: Vec<String> = self.positional.iter().map(|a| shell_quote(a)).collect();
+                    out.push_str(&quoted.join(" "));
+                } else {
+                    out.push_str(self.raw);
+                }
+                used = true;
+                rest = tail;
+                continue;
+            }
+
+            let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            match after[..digits].parse::<usize>() {
+                Ok(n) if n > 0 => {
+                    let value = self.positional.get(n - 1).map(String::as_str).unwrap_or("");
+                    if quote {
+                        out.push_str(&shell_quote(value));
+                    } else {
+                        out.push_str(value);
+                    }
+                    used = true;
+                    rest = &after[digits..];
+                }
+                _ => {
+                    out.push('$');
+                    rest = after;
+                }
+            }
+        }
+        out.push_str(rest);
+        (out, used)
+    }
+
+    /// Append the arguments when the template did not place them itself
+    fn finish(&self, text: String, used: bool) -> String {
+        if used || self.raw.is_empty() {
+            text
+        } else {
+            format!("{}\n\nArguments: {}", text, self.raw)
+        }
+    }
+}
+
+/// Split an argument string on whitespace, keeping quoted words together
+pub fn split_arguments(args: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+
+    for c in args.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => current.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            None => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+    words
+}
+
+/// Quote a value for use as a single `sh` word
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// A piece of a command body
+enum Segment<'a> {
+    Text(&'a str),
+    /// The command inside `` !`...` ``
+    Shell(&'a str),
+}
+
+/// Split a body into plain text and inline `` !`command` `` segments
+fn split_inline_commands(body: &str) -> Vec<Segment<'_>> {
+    let mut segments = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("!`") {
+        let Some(len) = rest[start + 2..].find('`') else {
+            break;
+        };
+        segments.push(Segment::Text(&rest[..start]));
+        segments.push(Segment::Shell(&rest[start + 2..start + 2 + len]));
+        rest = &rest[start + 2 + len + 1..];
+    }
+    segments.push(Segment::Text(rest));
+    segments
+}
+
+/// How long an inline shell command may run
+const INLINE_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Run an inline command and return its output for the prompt
+async fn run_inline_command(command: &str, work_dir: &Path) -> String {
+    debug!("Running inline command: {}", command);
+    let output = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(work_dir)
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(INLINE_COMMAND_TIMEOUT, output).await {
+        Ok(Ok(output)) => {
+            let mut text = String::from_utf8_lossy(&output.stdout).trim_end().to_string();
+            if !output.status.success() {
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                if !stderr.trim().is_empty() {
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    text.push_str(stderr.trim_end());
+                }
+                text.push_str(&format!("\n({})", output.status));
+            }
+            text
+        }
+        Ok(Err(e)) => format!("(failed to run `{}`: {})", command, e),
+        Err(_) => format!("(`{}` timed out after {}s)", command, INLINE_COMMAND_TIMEOUT.as_secs()),
+    }
+}
+
+/// Largest file inlined by an `@file` mention
+const MAX_MENTION_BYTES: usize = 100 * 1024;
+
+/// Replace `@path` mentions of existing files with the path and append the contents
+fn expand_file_mentions(text: &str, work_dir: &Path, workspace: Option<&Workspace>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut files: Vec<(&str, String)> = Vec::new();
+    let mut copied = 0;
+
+    for mention in attachment::mentions(text) {
+        let known = files.iter().any(|(path, _)| *path == mention.path);
+        if !known {
+            let Some(content) = attachment::resolve_mention(mention.path, work_dir, workspace).and_then(|path| read_mention(&path))
+            else {
+                continue;
+            };
+            files.push((mention.path, content));
+        }
+        // Drop the `@`
+        out.push_str(&text[copied..mention.offset]);
+        copied = mention.offset + 1;
+    }
+    out.push_str(&text[copied..]);
+
+    for (path, content) in files {
+        out.push_str(&format!("\n\nContents of {}:\n````\n{}\n````", path, content.trim_end()));
+    }
+    out
+}
+
+/// Read a mentioned file, cut at [`MAX_MENTION_BYTES`]
+fn read_mention(path: &Path) -> Option<String> {
+    let bytes = std::fs::read(path).ok()?;
+    let mut content = String::from_utf8_lossy(&bytes[..bytes.len().min(MAX_MENTION_BYTES)]).into_owned();
+    if bytes.len() > MAX_MENTION_BYTES {
+        content.push_str("\n[... truncated ...]");
+    }
+    Some(content)
+}
+
+/// The user command directory, `~/.kimi/commands`
+pub fn user_commands_dir() -> Option<PathBuf> {
+    crate::memory::global_dir().map(|dir| dir.join("commands"))
+}
+
+/// The project command directory, `.kimi/commands` in `work_dir`
+pub fn project_commands_dir(work_dir: &Path) -> PathBuf {
+    work_dir.join(".kimi").join("commands")
+}
+
+/// Discover user and project commands for `work_dir`
+pub fn discover_commands(work_dir: &Path) -> Vec<CustomCommand> {
+    discover_commands_in(user_commands_dir().as_deref(), &project_commands_dir(work_dir))
+}
+
+/// Discover commands in explicit directories
+///
+/// Project commands replace user commands with the same name. The result is
+/// sorted by name.
+pub fn discover_commands_in(user_dir: Option<&Path>, project_dir: &Path) -> Vec<CustomCommand> {
+    let mut commands = BTreeMap::new();
+    if let Some(user_dir) = user_dir {
+        load_dir(user_dir, "", CommandScope::User, &mut commands);
+    }
+    load_dir(project_dir, "", CommandScope::Project, &mut commands);
+    commands.into_values().collect()
+}
+
+/// Load every `.md` file under `dir`, namespacing subdirectories with `prefix`
+fn load_dir(dir: &Path, prefix: &str, scope: CommandScope, out: &mut BTreeMap<String, CustomCommand>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    // Symlinked directories are not followed, so a link loop cannot recurse forever
+    let mut paths: Vec<(PathBuf, bool)> = entries
+        .flatten()
+        .map(|entry| (entry.path(), entry.file_type().is_ok_and(|t| t.is_dir())))
+        .collect();
+    paths.sort();
+
+    for (path, is_dir) in paths {
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+        if is_dir {
+            load_dir(&path, &format!("{}{}:", prefix, stem), scope, out);
+            continue;
+        }
+        if path.extension().and_then(|e| e.to_str()) != Some("md") {
+            continue;
+        }
+
+        let name = format!("{}{}", prefix, stem);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                warn!("Failed to read command {:?}: {}", path, e);
+                continue;
+            }
+        };
+        match CustomCommand::parse(&name, &content, path.clone(), scope) {
+            Ok(command) => {
+                debug!("Loaded {} command /{} from {:?}", scope, name, path);
+                out.insert(name, command);
+            }
+            Err(e) => warn!("Failed to parse command {:?}: {}", path, e),
+        }
+    }
+}
+
+impl KimiSoul {
+    /// Build a custom command's prompt like [`CustomCommand::render`], then
+    /// expand inline shell commands and file references relative to
+    /// `work_dir`
+    ///
+    /// `` !`command` `` runs the command with `sh -c` and inserts its output.
+    /// This requires `Shell` in the command's `allowed-tools`, and each
+    /// command is approved like a Shell tool call; none run in plan mode.
+    /// Arguments substituted into a shell command are quoted. `@path`
+    /// mentions of files in the soul's workspace are replaced by the path,
+    /// and the file contents are appended to the prompt.
+    pub async fn render_custom_command(
+        &self,
+        command: &CustomCommand,
+        args: &str,
+        work_dir: &Path,
+        wire: &WireSoulSide,
+    ) -> Result<String, SoulError> {
+        let args = Arguments::parse(args);
+        let mut text = String::new();
+        let mut used = false;
+
+        for segment in split_inline_commands(&command.body) {
+            match segment {
+                Segment::Text(raw) => {
+                    let (expanded, found) = args.substitute(raw, false);
+                    used |= found;
+                    text.push_str(&expanded);
+                }
+                Segment::Shell(raw) => {
+                    if !command.allows_tool(SHELL_TOOL) {
+                        return Err(SoulError::SlashCommand(format!(
+                            "/{} runs shell commands; add Shell to its allowed-tools",
+                            command.name
+                        )));
+                    }
+                    let (shell, found) = args.substitute(raw, true);
+                    used |= found;
+                    self.approve_inline_command(command, &shell, wire).await?;
+                    text.push_str(&run_inline_command(&shell, work_dir).await);
+                }
+            }
+        }
+
+        let text = args.finish(text, used);
+        Ok(expand_file_mentions(&text, work_dir, self.workspace.as_ref()))
+    }
+
+    /// Ask for approval to run `shell`, an inline command of `command`, as
+    /// for a Shell tool call
+    async fn approve_inline_command(
+        &self,
+        command: &CustomCommand,
+        shell: &str,
+        wire: &WireSoulSide,
+    ) -> Result<(), SoulError> {
+        let refused = |reason: String| SoulError::SlashCommand(format!("/{} cannot run `{}`: {}", command.name, shell, reason));
+        if self.approval.mode() == PermissionMode::Plan {
+            return Err(refused(self.approval.refusal(SHELL_TOOL)));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let request = Request {
+            id: id.clone(),
+            tool_call_id: id.clone(),
+            sender: "kimi".to_string(),
+            action: SHELL_TOOL.to_string(),
+            description: chat::build_approval_description(SHELL_TOOL, &serde_json::json!({ "command": shell })),
+            edit: None,
+            command_pattern: command_pattern(shell),
+        };
+        let decided = self.approval.decide(&request);
+        let by_mode = decided.is_some();
+        let kind = match decided {
+            Some(kind) => kind,
+            None => {
+                wire.send(WireMessage::ApprovalRequest {
+                    id: id.clone(),
+                    tool_call_id: id.clone(),
+                    sender: request.sender.clone(),
+                    action: request.action.clone(),
+                    description: request.description.clone(),
+                    diff: None,
+                })
+                .await?;
+                let kind = self.approval.request(request).await;
+                wire.send(WireMessage::ApprovalResponse {
+                    request_id: id,
+                    response: kind.clone(),
+                })
+                .await?;
+                kind
+            }
+        };
+
+        match kind {
+            ApprovalKind::Reject if by_mode => Err(refused(self.approval.refusal(SHELL_TOOL))),
+            ApprovalKind::Reject => Err(refused("rejected by user approval".to_string())),
+            ApprovalKind::Approve | ApprovalKind::ApproveOnce | ApprovalKind::ApproveAlways => Ok(()),
+        }
+    }
+
+    /// Run a custom command's prompt through the LLM
+    ///
+    /// The prompt is rendered with [`KimiSoul::render_custom_command`] in
+    /// `work_dir`. When the command lists `allowed-tools`, only those tools
+    /// are offered for this turn. Choosing a provider for the command's
+    /// `model` is up to the caller.
+    pub async fn run_custom_command(
+        &mut self,
+        provider: &dyn ChatProvider,
+        command: &CustomCommand,
+        args: &str,
+        work_dir: &Path,
+        wire: &WireSoulSide,
+    ) -> Result<String, SoulError> {
+        let input = UserInput {
+            text: self.render_custom_command(command, args, work_dir, wire).await?,
+            attachments: Vec::new(),
+        };
+        let full_toolset = command.allowed_tools.as_ref().map(|tools| {
+            let names: Vec<&str> = tools.iter().map(String::as_str).collect();
+            let subset = self.toolset.subset(&names);
+            std::mem::replace(&mut self.toolset, subset)
+        });
+        let result = chat::process_message(self, provider, input, wire).await;
+        if let Some(toolset) = full_toolset {
+            self.toolset = toolset;
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::{Approval, ToolRule};
+    use crate::soul::testing::test_soul;
+    use std::sync::Arc;
+
+    fn write(path: &Path, content: &str) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+
+    #[test]
+    fn test_parse_command_with_frontmatter() {
+        let content = "---\ndescription: Review code\nallowed-tools: [ReadFile, Grep]\nmodel: kimi-k2\n---\n\nReview the diff.\n";
+        let command = CustomCommand::parse("review", content, PathBuf::from("/c/review.md"), CommandScope::User).unwrap();
+
+        assert_eq!(command.name, "review");
+        assert_eq!(command.description, "Review code");
+        assert_eq!(command.allowed_tools, Some(vec!["ReadFile".to_string(), "Grep".to_string()]));
+        assert_eq!(command.model.as_deref(), Some("kimi-k2"));
+        assert_eq!(command.body, "Review the diff.");
+    }
+
+    #[test]
+    fn test_parse_command_defaults() {
+        let command = CustomCommand::parse("fix", "# Fix the build\n\nRun cargo build.", PathBuf::from("/c/fix.md"), CommandScope::Project).unwrap();
+        assert_eq!(command.description, "Fix the build");
+        assert_eq!(command.allowed_tools, None);
+        assert_eq!(command.model, None);
+
+        let content = "---\nallowed_tools: \"Shell, Glob\"\n---\nList files";
+        let command = CustomCommand::parse("ls", content, PathBuf::from("/c/ls.md"), CommandScope::Project).unwrap();
+        assert_eq!(command.allowed_tools, Some(vec!["Shell".to_string(), "Glob".to_string()]));
+
+        assert!(CustomCommand::parse("empty", "---\ndescription: x\n---\n", PathBuf::new(), CommandScope::User).is_err());
+    }
+
+    #[test]
+    fn test_render_appends_arguments() {
+        let command = CustomCommand::parse("review", "Review this PR.", PathBuf::new(), CommandScope::User).unwrap();
+        assert_eq!(command.render(""), "Review this PR.");
+        assert_eq!(command.render(" 42 "), "Review this PR.\n\nArguments: 42");
+    }
+
+    fn command(body: &str, allowed_tools: Option<Vec<&str>>) -> CustomCommand {
+        CustomCommand {
+            name: "test".to_string(),
+            description: String::new(),
+            allowed_tools: allowed_tools.map(|tools| tools.into_iter().map(String::from).collect()),
+            model: None,
+            body: body.to_string(),
+            path: PathBuf::new(),
+            scope: CommandScope::User,
+        }
+    }
+
+    #[test]
+    fn test_render_placeholders() {
+        let review = command("Review PR #$1 with focus on $2. Notes: $3. All: $ARGUMENTS", None);
+        assert_eq!(
+            review.render("42 \"error handling\""),
+            "Review PR #42 with focus on error handling. Notes: . All: 42 \"error handling\""
+        );
+        // `$` not followed by a placeholder is kept
+        assert_eq!(command("Costs $5 or $x, see $0", None).render(""), "Costs  or $x, see $0");
+    }
+
+    #[test]
+    fn test_split_arguments() {
+        assert_eq!(split_arguments("  a  'b c' \"d\"e \"\" "), vec!["a", "b c", "de", ""]);
+        assert!(split_arguments("   ").is_empty());
+    }
+
+    /// Render `cmd` in `dir` with a yolo-mode soul
+    async fn render_in(cmd: &CustomCommand, args: &str, dir: &Path) -> Result<String, SoulError> {
+        test_soul(dir).render_custom_command(cmd, args, dir, &WireSoulSide::new()).await
+    }
+
+    #[tokio::test]
+    async fn test_render_in_runs_inline_commands() {
+        let temp = tempfile::tempdir().unwrap();
+        let cmd = command("Branch: !`printf '%s' $1`. Done", Some(vec!["Shell"]));
+        let prompt = render_in(&cmd, "'it''s here'", temp.path()).await.unwrap();
+        assert_eq!(prompt, "Branch: its here. Done");
+
+        let cmd = command("Status: !`echo oops >&2; exit 3`", Some(vec!["Shell"]));
+        let prompt = render_in(&cmd, "", temp.path()).await.unwrap();
+        assert!(prompt.starts_with("Status: oops\n("));
+        assert!(prompt.contains('3'));
+
+        let cmd = command("Run !`ls`", Some(vec!["ReadFile"]));
+        assert!(matches!(render_in(&cmd, "", temp.path()).await, Err(SoulError::SlashCommand(_))));
+    }
+
+    #[tokio::test]
+    async fn test_render_in_quotes_arguments_in_shell() {
+        let temp = tempfile::tempdir().unwrap();
+        let cmd = command("!`echo $1`", Some(vec!["Shell"]));
+        let prompt = render_in(&cmd, "\"a; echo injected\"", temp.path()).await.unwrap();
+        assert_eq!(prompt, "a; echo injected");
+    }
+
+    #[tokio::test]
+    async fn test_render_in_expands_file_mentions() {
+        let temp = tempfile::tempdir().unwrap();
+        write(&temp.path().join("src").join("lib.rs"), "pub fn answer() -> u32 { 42 }\n");
+
+        let cmd = command("Explain @$1, not me@example.com or @missing.rs.", None);
+        let prompt = render_in(&cmd, "src/lib.rs", temp.path()).await.unwrap();
+        assert_eq!(
+            prompt,
+            "Explain src/lib.rs, not me@example.com or @missing.rs.\n\n\
+             Contents of src/lib.rs:\n````\npub fn answer() -> u32 { 42 }\n````"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inline_commands_are_approved() {
+        let temp = tempfile::tempdir().unwrap();
+        let cmd = command("!`touch ran`", Some(vec!["Shell"]));
+        let wire = WireSoulSide::new();
+        let mut soul = test_soul(temp.path());
+
+        // Refused in plan mode, even with a rule allowing Shell
+        soul.approval = Arc::new(
+            Approval::with_mode(PermissionMode::Plan).with_rules([(SHELL_TOOL.to_string(), ToolRule::Always)]),
+        );
+        let err = soul.render_custom_command(&cmd, "", temp.path(), &wire).await.unwrap_err();
+        assert!(err.to_string().contains("plan mode"), "{}", err);
+
+        // Rejected when no one can answer
+        soul.approval = Arc::new(Approval::new());
+        soul.approval.set_unattended(true);
+        let err = soul.render_custom_command(&cmd, "", temp.path(), &wire).await.unwrap_err();
+        assert!(err.to_string().contains("cannot run `touch ran`"), "{}", err);
+        assert!(!temp.path().join("ran").exists());
+
+        soul.approval = Arc::new(Approval::new().with_commands(command_pattern("touch ran")));
+        soul.render_custom_command(&cmd, "", temp.path(), &wire).await.unwrap();
+        assert!(temp.path().join("ran").exists());
+    }
+
+    #[tokio::test]
+    async fn test_mentions_outside_the_workspace_are_not_expanded() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join("project");
+        write(&project.join("notes.md"), "Notes");
+        write(&temp.path().join("secret.txt"), "Secret");
+
+        let mut soul = test_soul(&project);
+        soul.workspace = Some(Workspace::new(&project).unwrap());
+        let cmd = command("Read @notes.md and @../secret.txt", None);
+        let prompt = soul.render_custom_command(&cmd, "", &project, &WireSoulSide::new()).await.unwrap();
+        assert_eq!(prompt, "Read notes.md and @../secret.txt\n\nContents of notes.md:\n````\nNotes\n````");
+    }
+
+    #[test]
+    fn test_discover_commands_in() {
+        let temp = tempfile::tempdir().unwrap();
+        let user = temp.path().join("user");
+        let project = temp.path().join("project");
+        write(&user.join("review.md"), "User review");
+        write(&user.join("explain.md"), "Explain the code");
+        write(&user.join("notes.txt"), "Not a command");
+        write(&project.join("review.md"), "Project review");
+        write(&project.join("git").join("pr.md"), "Open a PR");
+        write(&project.join("broken.md"), "---\ndescription: [unclosed\n---\nBody");
+
+        let commands = discover_commands_in(Some(&user), &project);
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["explain", "git:pr", "review"]);
+
+        let review = commands.iter().find(|c| c.name == "review").unwrap();
+        assert_eq!(review.scope, CommandScope::Project);
+        assert_eq!(review.body, "Project review");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_directories_are_not_followed() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join("project");
+        write(&project.join("git").join("pr.md"), "Open a PR");
+        std::os::unix::fs::symlink(&project, project.join("git").join("loop")).unwrap();
+
+        let commands = discover_commands_in(None, &project);
+        let names: Vec<&str> = commands.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["git:pr"]);
+    }
+}
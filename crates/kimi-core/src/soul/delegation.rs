@@ -0,0 +1,255 @@
+//! Delegation of labor market tasks
+//!
+//! Matches tasks offered on a [`LaborMarket`] with available agents
+//! registered in a [`Runtime`], runs each accepted task as a sub-agent of
+//! this soul, and records the outcome on the market and in the agent's state
+//! so the offering agent can pick up the result. [`SoulTaskExecutor`] runs
+//! [`Runtime`] queue tasks the same way.
+
+use super::agent::{Agent, AgentState, LaborMarket, MarketTask, Runtime, Task, TaskExecutor, TaskStatus};
+use super::chat;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::subagent::{SubagentResult, SubagentSpec};
+use super::WireSoulSide;
+use crate::types::UserInput;
+use async_trait::async_trait;
+use kosong_rs::ChatProvider;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tracing::{debug, info};
+
+/// A market task that was assigned and executed
+#[derive(Debug, Clone)]
+pub struct DelegatedTask {
+    pub task: MarketTask,
+    /// Id of the agent that took the task
+    pub agent_id: String,
+    pub result: SubagentResult,
+}
+
+impl KimiSoul {
+    /// Assign open market tasks to available agents and run them
+    ///
+    /// Tasks are taken in priority order; each goes to the first available
+    /// agent whose capabilities match its required skills, and an agent takes
+    /// at most one task per call. Tasks without a matching agent stay on the
+    /// market. Up to `max_concurrent` tasks run at a time.
+    pub async fn delegate_market_tasks(
+        &mut self,
+        provider: &dyn ChatProvider,
+        market: &LaborMarket,
+        runtime: &Runtime,
+        max_concurrent: usize,
+        wire: &WireSoulSide,
+    ) -> Result<Vec<DelegatedTask>, SoulError> {
+        let mut offers = market.get_available_tasks().await;
+        offers.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.offered_at.cmp(&b.offered_at)));
+
+        let mut assigned: Vec<(MarketTask, Agent)> = Vec::new();
+        for task in offers {
+            let Some(agent) = runtime.find_agent_for(&task, &task.offered_by).await else {
+                debug!("No agent available for market task {}", task.id);
+                continue;
+            };
+            if !market.accept_task(&task.id, &agent.id).await {
+                continue;
+            }
+            agent.set_state(AgentState::Working {
+                task_id: task.id.clone(),
+                started_at: chrono::Utc::now().to_rfc3339(),
+            })
+            .await;
+            assigned.push((task, agent));
+        }
+        if assigned.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        info!("Delegating {} market tasks", assigned.len());
+        runtime.update_stats(|stats| {
+            stats.total_tasks += assigned.len();
+            stats.active_agents += assigned.len();
+        })
+        .await;
+
+        let specs = assigned
+            .iter()
+            .map(|(task, agent)| (task.id.clone(), SubagentSpec::new(agent.clone(), task.description.clone())))
+            .collect();
+        let results = self.run_subagent_tasks(provider, specs, max_concurrent, wire).await;
+
+        let mut delegated = Vec::new();
+        let results = match results {
+            Ok(results) => results,
+            Err(e) => {
+                // Release the agents and tasks so they are not stuck in progress
+                for (task, agent) in &assigned {
+                    market.finish_task(&task.id, TaskStatus::Failed(e.to_string())).await;
+                    agent.set_state(AgentState::Error { error: e.to_string() }).await;
+                }
+                runtime.update_stats(|stats| {
+                    stats.failed_tasks += assigned.len();
+                    stats.active_agents -= assigned.len();
+                })
+                .await;
+                return Err(e);
+            }
+        };
+
+        for ((task, agent), result) in assigned.into_iter().zip(results) {
+            let (status, state) = match &result.response {
+                Ok(reply) => (
+                    TaskStatus::Completed(reply.clone()),
+                    AgentState::Completed {
+                        task_id: task.id.clone(),
+                        result: result.summary.clone(),
+                    },
+                ),
+                Err(error) => (
+                    TaskStatus::Failed(error.clone()),
+                    AgentState::Error { error: error.clone() },
+                ),
+            };
+            market.finish_task(&task.id, status).await;
+            agent.set_state(state).await;
+            runtime.update_stats(|stats| {
+                if result.is_error() {
+                    stats.failed_tasks += 1;
+                } else {
+                    stats.completed_tasks += 1;
+                }
+                stats.active_agents -= 1;
+            })
+            .await;
+
+            delegated.push(DelegatedTask {
+                task,
+                agent_id: agent.id.clone(),
+                result,
+            });
+        }
+
+        Ok(delegated)
+    }
+}
+
+/// Executes runtime tasks as sub-agents of a soul, with an LLM provider
+pub struct SoulTaskExecutor {
+    parent: Mutex<KimiSoul>,
+    provider: Arc<dyn ChatProvider>,
+    wire: WireSoulSide,
+}
+
+impl SoulTaskExecutor {
+    /// Run tasks as children of `parent`, sharing its tools and approval
+    pub fn new(parent: KimiSoul, provider: Arc<dyn ChatProvider>, wire: WireSoulSide) -> Self {
+        Self {
+            parent: Mutex::new(parent),
+            provider,
+            wire,
+        }
+    }
+}
+
+#[async_trait]
+impl TaskExecutor for SoulTaskExecutor {
+    async fn execute(&self, agent: &Agent, task: &Task) -> Result<String, String> {
+        let spec = SubagentSpec::new(agent.clone(), task.description.clone());
+        let mut child = self.parent.lock().await.spawn_subagent(&spec, &task.id);
+        let input = UserInput {
+            text: task.description.clone(),
+            attachments: Vec::new(),
+        };
+        let max_iterations = child.loop_control.max_iterations;
+        let reply = chat::process_message_with_limit(
+            &mut child,
+            self.provider.as_ref(),
+            input,
+            &self.wire.for_subagent(&task.id),
+            max_iterations,
+        )
+        .await
+        .map_err(|e| e.to_string());
+        self.parent.lock().await.adopt_edits(std::mem::take(&mut child.edits));
+        reply
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::agent::AgentConfig;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_delegate_market_tasks() {
+        let temp = tempfile::tempdir().unwrap();
+        let lead = Agent::new("Lead", "Plans the work");
+        let mut soul = test_soul(temp.path());
+        soul.agent = lead.clone();
+
+        let runtime = Runtime::new();
+        let tester = Agent::new("Tester", "Runs tests").with_config(AgentConfig {
+            capabilities: vec!["testing".to_string()],
+            ..AgentConfig::default()
+        });
+        runtime.register_agent(tester.clone()).await;
+
+        let market = LaborMarket::new();
+        let low = market.offer_task("Check the docs build", &lead.id, vec!["testing".to_string()], 1).await;
+        let high = market.offer_task("Run the test suite", &lead.id, vec!["testing".to_string()], 5).await;
+        let unmatched = market.offer_task("Design a logo", &lead.id, vec!["design".to_string()], 9).await;
+
+        let provider = ScriptedProvider::new(["All 140 tests pass."]);
+        let delegated = soul
+            .delegate_market_tasks(&provider, &market, &runtime, 2, &WireSoulSide::new())
+            .await
+            .unwrap();
+
+        // The only agent takes the highest-priority task it can do
+        assert_eq!(delegated.len(), 1);
+        assert_eq!(delegated[0].task.id, high);
+        assert_eq!(delegated[0].agent_id, tester.id);
+        assert!(matches!(market.task_status(&high).await, Some(TaskStatus::Completed(ref r)) if r == "All 140 tests pass."));
+        assert!(matches!(market.task_status(&low).await, Some(TaskStatus::Pending)));
+        assert!(matches!(market.task_status(&unmatched).await, Some(TaskStatus::Pending)));
+        assert!(matches!(tester.state().await, AgentState::Completed { ref task_id, .. } if task_id == &high));
+
+        let results = market.results_for(&lead.id).await;
+        assert_eq!(results.len(), 1);
+        assert!(soul.context.messages()[0].content.contains("## Tester (done)"));
+
+        let stats = runtime.stats().await;
+        assert_eq!((stats.total_tasks, stats.completed_tasks, stats.active_agents), (1, 1, 0));
+
+        // The agent is available again, but the script has no reply left
+        let delegated = soul
+            .delegate_market_tasks(&provider, &market, &runtime, 2, &WireSoulSide::new())
+            .await
+            .unwrap();
+        assert_eq!(delegated[0].task.id, low);
+        assert!(matches!(market.task_status(&low).await, Some(TaskStatus::Failed(_))));
+        assert!(matches!(tester.state().await, AgentState::Error { .. }));
+        assert_eq!(runtime.stats().await.failed_tasks, 1);
+    }
+
+    #[tokio::test]
+    async fn test_soul_task_executor_runs_runtime_tasks() {
+        use crate::soul::agent::SchedulerConfig;
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.agent = Agent::new("Lead", "Plans the work");
+        let provider = Arc::new(ScriptedProvider::new(["Changelog drafted."]));
+        let executor = Arc::new(SoulTaskExecutor::new(soul, provider.clone(), WireSoulSide::new()));
+
+        let runtime = Runtime::new();
+        runtime.register_agent(Agent::new("Writer", "Writes docs")).await;
+        let id = runtime.submit_task("Draft the changelog").await;
+        runtime.run_until_idle(executor, &SchedulerConfig::default()).await;
+
+        assert!(matches!(runtime.get_task(&id).await.unwrap().status, TaskStatus::Completed(ref r) if r == "Changelog drafted."));
+        assert_eq!(provider.requests()[0].last_text(), "Draft the changelog");
+    }
+}
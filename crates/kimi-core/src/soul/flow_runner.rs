@@ -0,0 +1,371 @@
+//! Execution of flow-type skills
+//!
+//! A [`FlowRunner`] walks a parsed [`Flow`] from its Begin node to its End
+//! node. Each Task node is sent to the agent as a prompt in the soul's
+//! conversation, and each Decision node asks the model to pick one of the
+//! labelled outgoing edges with `<choice>label</choice>`.
+
+use super::chat;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::WireSoulSide;
+use crate::skill::{Flow, FlowEdge, FlowNode, NodeType, Skill};
+use crate::types::UserInput;
+use crate::wire::WireMessage;
+use kosong_rs::ChatProvider;
+use tracing::{debug, info};
+
+/// Default limit on node visits, to stop flows that loop forever
+pub const DEFAULT_MAX_MOVES: usize = 64;
+
+/// How many times a Decision node is asked before giving up
+pub const DEFAULT_DECISION_ATTEMPTS: usize = 3;
+
+/// Result of a completed flow
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FlowReport {
+    /// Node ids in visiting order, from Begin to End
+    pub path: Vec<String>,
+    /// Final replies of the Task nodes, in order
+    pub outputs: Vec<FlowOutput>,
+}
+
+/// The agent's reply to one Task node visit
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowOutput {
+    pub node_id: String,
+    pub response: String,
+}
+
+/// Runs a flow against a soul
+#[derive(Debug, Clone)]
+pub struct FlowRunner {
+    name: String,
+    flow: Flow,
+    max_moves: usize,
+    decision_attempts: usize,
+}
+
+impl FlowRunner {
+    /// Create a runner for a flow
+    pub fn new(name: impl Into<String>, flow: Flow) -> Self {
+        Self {
+            name: name.into(),
+            flow,
+            max_moves: DEFAULT_MAX_MOVES,
+            decision_attempts: DEFAULT_DECISION_ATTEMPTS,
+        }
+    }
+
+    /// Create a runner for a flow-type skill
+    pub fn from_skill(skill: &Skill) -> Option<Self> {
+        skill.flow.clone().map(|flow| Self::new(&skill.name, flow))
+    }
+
+    /// Set the limit on node visits
+    pub fn with_max_moves(mut self, max_moves: usize) -> Self {
+        self.max_moves = max_moves;
+        self
+    }
+
+    /// Set how many times a Decision node is asked before giving up
+    pub fn with_decision_attempts(mut self, attempts: usize) -> Self {
+        self.decision_attempts = attempts.max(1);
+        self
+    }
+
+    /// Name of the flow
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Walk the flow from Begin to End
+    ///
+    /// `args` are appended to the first Task prompt. Progress is reported
+    /// with [`WireMessage::FlowStep`], [`WireMessage::FlowDecision`] and
+    /// [`WireMessage::FlowEnd`]; the agent's replies stream as usual.
+    pub async fn run(
+        &self,
+        soul: &mut KimiSoul,
+        provider: &dyn ChatProvider,
+        args: &str,
+        wire: &WireSoulSide,
+    ) -> Result<FlowReport, SoulError> {
+        info!("Running flow {}", self.name);
+        let mut report = FlowReport::default();
+        let mut pending_args = Some(args.trim()).filter(|a| !a.is_empty());
+        let mut current = self.node(&self.flow.begin_id)?;
+
+        loop {
+            if report.path.len() >= self.max_moves {
+                return Err(SoulError::Flow(format!(
+                    "flow {} did not reach its End node within {} steps",
+                    self.name, self.max_moves
+                )));
+            }
+            report.path.push(current.id.clone());
+            self.send(wire, WireMessage::FlowStep {
+                flow: self.name.clone(),
+                node_id: current.id.clone(),
+                label: current.label.clone(),
+            })
+            .await?;
+
+            let edges = self.outgoing(&current.id);
+            let next = match current.node_type {
+                NodeType::End => break,
+                NodeType::Begin => self.single_edge(current, &edges)?,
+                NodeType::Task => {
+                    let mut prompt = current.label.clone();
+                    if let Some(args) = pending_args.take() {
+                        prompt.push_str(&format!("\n\nArguments: {}", args));
+                    }
+                    let response = self.ask(soul, provider, prompt, wire).await?;
+                    report.outputs.push(FlowOutput {
+                        node_id: current.id.clone(),
+                        response,
+                    });
+                    self.single_edge(current, &edges)?
+                }
+                NodeType::Decision => self.decide(soul, provider, current, &edges, wire).await?,
+            };
+            current = self.node(next)?;
+        }
+
+        self.send(wire, WireMessage::FlowEnd { flow: self.name.clone() }).await?;
+        Ok(report)
+    }
+
+    /// Ask a Decision node's question until the model picks a valid branch
+    async fn decide<'f>(
+        &self,
+        soul: &mut KimiSoul,
+        provider: &dyn ChatProvider,
+        node: &FlowNode,
+        edges: &[&'f FlowEdge],
+        wire: &WireSoulSide,
+    ) -> Result<&'f str, SoulError> {
+        if edges.len() == 1 {
+            return Ok(&edges[0].to);
+        }
+        if edges.is_empty() {
+            return Err(self.invalid(format!("decision node {} has no outgoing edges", node.id)));
+        }
+
+        let choices: Vec<&str> = edges.iter().map(|e| e.label.as_deref().unwrap_or("")).collect();
+        if choices.iter().any(|c| c.trim().is_empty()) {
+            return Err(self.invalid(format!("decision node {} has an unlabelled branch", node.id)));
+        }
+        let options = choices
+            .iter()
+            .map(|c| format!("<choice>{}</choice>", c))
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut prompt = format!(
+            "{}\n\nEnd your reply with exactly one of these choices: {}",
+            node.label, options
+        );
+        for attempt in 1..=self.decision_attempts {
+            let response = self.ask(soul, provider, prompt, wire).await?;
+            if let Some(index) = parse_choice(&response, &choices) {
+                debug!("Flow {} decision {} chose {}", self.name, node.id, choices[index]);
+                self.send(wire, WireMessage::FlowDecision {
+                    flow: self.name.clone(),
+                    node_id: node.id.clone(),
+                    choice: choices[index].to_string(),
+                })
+                .await?;
+                return Ok(&edges[index].to);
+            }
+            debug!("Flow {} decision {} attempt {} had no valid choice", self.name, node.id, attempt);
+            prompt = format!(
+                "Your reply did not contain a valid choice. Reply with exactly one of: {}",
+                options
+            );
+        }
+
+        Err(SoulError::Flow(format!(
+            "no valid choice for decision node {} after {} attempts",
+            node.id, self.decision_attempts
+        )))
+    }
+
+    /// Run one agent sub-turn with `prompt`
+    async fn ask(
+        &self,
+        soul: &mut KimiSoul,
+        provider: &dyn ChatProvider,
+        prompt: String,
+        wire: &WireSoulSide,
+    ) -> Result<String, SoulError> {
+        let input = UserInput {
+            text: prompt,
+            attachments: Vec::new(),
+        };
+        let max_iterations = soul.loop_control.max_iterations;
+        chat::process_message_with_limit(soul, provider, input, wire, max_iterations).await
+    }
+
+    fn node(&self, id: &str) -> Result<&FlowNode, SoulError> {
+        self.flow
+            .nodes
+            .get(id)
+            .ok_or_else(|| self.invalid(format!("unknown node {}", id)))
+    }
+
+    fn outgoing(&self, id: &str) -> Vec<&FlowEdge> {
+        self.flow.edges.iter().filter(|e| e.from == id).collect()
+    }
+
+    fn single_edge<'f>(&self, node: &FlowNode, edges: &[&'f FlowEdge]) -> Result<&'f str, SoulError> {
+        match edges {
+            [edge] => Ok(&edge.to),
+            [] => Err(self.invalid(format!("node {} has no outgoing edge", node.id))),
+            _ => Err(self.invalid(format!(
+                "node {} has {} outgoing edges; only decision nodes may branch",
+                node.id,
+                edges.len()
+            ))),
+        }
+    }
+
+    fn invalid(&self, message: String) -> SoulError {
+        SoulError::Flow(format!("invalid flow {}: {}", self.name, message))
+    }
+
+    async fn send(&self, wire: &WireSoulSide, message: WireMessage) -> Result<(), SoulError> {
+        wire.send(message).await
+    }
+}
+
+/// Find which of `choices` the model picked
+///
+/// Prefers the last `<choice>...</choice>` tag; otherwise accepts a last line
+/// that is exactly one of the choices. Matching ignores case.
+pub fn parse_choice(response: &str, choices: &[&str]) -> Option<usize> {
+    let find = |candidate: &str| {
+        let candidate = candidate.trim();
+        choices.iter().position(|c| c.trim().eq_ignore_ascii_case(candidate))
+    };
+
+    if let Some(start) = response.rfind("<choice>") {
+        let tail = &response[start + "<choice>".len()..];
+        if let Some(end) = tail.find("</choice>") {
+            return find(&tail[..end]);
+        }
+    }
+
+    let last_line = response.lines().rev().find(|l| !l.trim().is_empty())?;
+    find(last_line.trim_matches(|c: char| c == '*' || c == '`' || c == '.' || c.is_whitespace()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use tokio::sync::mpsc;
+
+    fn review_flow() -> Flow {
+        Flow::parse_mermaid(
+            "flowchart TD
+    Begin([Begin]) --> Fix[Fix the failing test]
+    Fix --> Check{Do all tests pass?}
+    Check -->|Yes| End([End])
+    Check -->|No| Fix",
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_run_follows_decisions_to_end() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new([
+            "Patched the parser.",
+            "Still failing.\n<choice>No</choice>",
+            "Patched the lexer too.",
+            "All green.\n**yes**",
+        ]);
+        let (tx, mut rx) = mpsc::channel(32);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let runner = FlowRunner::new("fix-tests", review_flow());
+        let report = runner.run(&mut soul, &provider, "test_parse", &wire).await.unwrap();
+
+        assert_eq!(report.path, vec!["Begin", "Fix", "Check", "Fix", "Check", "End"]);
+        let outputs: Vec<&str> = report.outputs.iter().map(|o| o.response.as_str()).collect();
+        assert_eq!(outputs, vec!["Patched the parser.", "Patched the lexer too."]);
+
+        let requests = provider.requests();
+        assert_eq!(requests[0].last_text(), "Fix the failing test\n\nArguments: test_parse");
+        assert!(requests[1].last_text().contains("<choice>Yes</choice>, <choice>No</choice>"));
+        assert_eq!(requests[2].last_text(), "Fix the failing test");
+        // Sub-turns share the soul's conversation
+        assert_eq!(soul.context.message_count(), 8);
+
+        drop(wire);
+        let mut decisions = Vec::new();
+        let mut ended = false;
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::FlowDecision { choice, .. } => decisions.push(choice),
+                WireMessage::FlowEnd { flow } => ended = flow == "fix-tests",
+                _ => {}
+            }
+        }
+        assert_eq!(decisions, vec!["No", "Yes"]);
+        assert!(ended);
+    }
+
+    #[tokio::test]
+    async fn test_run_retries_then_fails_without_choice() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["Done.", "Maybe?", "Not sure."]);
+
+        let runner = FlowRunner::new("fix-tests", review_flow()).with_decision_attempts(2);
+        let result = runner.run(&mut soul, &provider, "", &WireSoulSide::new()).await;
+
+        assert!(matches!(result, Err(SoulError::Flow(msg)) if msg.contains("after 2 attempts")));
+        assert!(provider.requests()[2].last_text().starts_with("Your reply did not contain a valid choice"));
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_endless_loops() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["ok", "<choice>No</choice>"].repeat(10));
+
+        let runner = FlowRunner::new("fix-tests", review_flow()).with_max_moves(5);
+        let result = runner.run(&mut soul, &provider, "", &WireSoulSide::new()).await;
+        assert!(matches!(result, Err(SoulError::Flow(msg)) if msg.contains("within 5 steps")));
+    }
+
+    #[tokio::test]
+    async fn test_run_rejects_branching_task() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let flow = Flow::parse_mermaid(
+            "flowchart TD
+    Begin([Begin]) --> A[Do A]
+    A --> B[Do B]
+    A --> End([End])
+    B --> End",
+        )
+        .unwrap();
+        let provider = ScriptedProvider::new(["done"]);
+
+        let result = FlowRunner::new("bad", flow).run(&mut soul, &provider, "", &WireSoulSide::new()).await;
+        assert!(matches!(result, Err(SoulError::Flow(msg)) if msg.contains("only decision nodes may branch")));
+    }
+
+    #[test]
+    fn test_parse_choice() {
+        let choices = ["Yes", "No"];
+        assert_eq!(parse_choice("Thinking <choice>no</choice>", &choices), Some(1));
+        assert_eq!(parse_choice("<choice>Yes</choice> then <choice>No</choice>", &choices), Some(1));
+        assert_eq!(parse_choice("Result:\n`Yes`.\n\n", &choices), Some(0));
+        assert_eq!(parse_choice("<choice>Perhaps</choice>", &choices), None);
+        assert_eq!(parse_choice("Yes, I think so", &choices), None);
+    }
+}
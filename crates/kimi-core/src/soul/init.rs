@@ -0,0 +1,248 @@
+//! The `/init` command
+//!
+//! Runs an analysis turn over the project with read-only tools using the
+//! INIT prompt, then writes the result to AGENTS.md and reports what changed.
+
+use super::chat;
+use super::compaction::SimpleCompaction;
+use super::denwarenji::DenwaRenji;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::{system_message, WireSoulSide};
+use crate::approval::Approval;
+use crate::context::Context;
+use crate::diff::{diff_lines, unified_diff, DiffStat};
+use crate::memory::ProjectMemory;
+use crate::prompts;
+use crate::types::UserInput;
+use kosong_rs::ChatProvider;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::info;
+
+/// Tools the analysis turn may use; it must not modify the project
+pub const INIT_TOOLS: &[&str] = &["ReadFile", "Glob", "Grep"];
+
+/// Result of running `/init`
+#[derive(Debug, Clone)]
+pub struct InitReport {
+    /// Path of the AGENTS.md file that was written
+    pub path: PathBuf,
+    /// Whether the file did not exist before
+    pub created: bool,
+    /// Unified diff between the previous and new contents
+    pub diff: String,
+    /// Number of inserted and deleted lines
+    pub stat: DiffStat,
+}
+
+impl KimiSoul {
+    /// Analyze the project in `work_dir` and write or refresh its AGENTS.md
+    ///
+    /// The analysis runs in a separate context with only [`INIT_TOOLS`]
+    /// available, so it never needs approval. Afterwards a note is added to
+    /// this soul's context and the memory files are reloaded.
+    pub async fn run_init(
+        &mut self,
+        provider: &dyn ChatProvider,
+        work_dir: &Path,
+        wire: &WireSoulSide,
+    ) -> Result<InitReport, SoulError> {
+        let path = work_dir.join("AGENTS.md");
+        let previous = match std::fs::read_to_string(&path) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => {
+                return Err(SoulError::SlashCommand(format!(
+                    "Failed to read {}: {}",
+                    path.display(),
+                    e
+                )));
+            }
+        };
+
+        let mut analyst = KimiSoul::new(
+            self.agent.clone(),
+            Context::new(self.context.context_file().with_extension("init.json")),
+            Arc::new(Approval::yolo()),
+            Arc::new(DenwaRenji::new()),
+            self.loop_control.clone(),
+            SimpleCompaction::new(self.compaction.max_tokens),
+        );
+        analyst.toolset = self.toolset.subset(INIT_TOOLS);
+        analyst.prompt_vars = self.prompt_vars.clone();
+
+        info!("Running /init analysis in {}", work_dir.display());
+        let input = UserInput {
+            text: init_prompt(&analyst.render_prompt(&self.prompts.init), previous.as_deref()),
+            attachments: Vec::new(),
+        };
+        let response = chat::process_message_with_limit(
+            &mut analyst,
+            provider,
+            input,
+            wire,
+            self.loop_control.max_iterations,
+        )
+        .await?;
+
+        let content = extract_document(&response);
+        if content.trim().is_empty() {
+            return Err(SoulError::SlashCommand(
+                "The analysis did not produce any AGENTS.md content".to_string(),
+            ));
+        }
+        std::fs::write(&path, &content).map_err(|e| {
+            SoulError::SlashCommand(format!("Failed to write {}: {}", path.display(), e))
+        })?;
+
+        let old = previous.as_deref().unwrap_or("");
+        let old_label = if previous.is_some() { "a/AGENTS.md" } else { "/dev/null" };
+        let diff = unified_diff(old, &content, old_label, "b/AGENTS.md", 3);
+        let stat = DiffStat::from_ops(&diff_lines(old, &content));
+
+        self.context.add_message(system_message(format!(
+            "The user just ran the `/init` slash command. The system analyzed the project \
+             and wrote `AGENTS.md` at: {}\n\nGenerated content:\n{}",
+            path.display(),
+            content
+        )));
+        self.memory = ProjectMemory::discover(work_dir);
+
+        Ok(InitReport {
+            path,
+            created: previous.is_none(),
+            diff,
+            stat,
+        })
+    }
+}
+
+/// Build the analysis prompt from the rendered template, including the
+/// current AGENTS.md when present
+fn init_prompt(template: &str, previous: Option<&str>) -> String {
+    let mut prompt = format!("{}\n\n{}", template.trim_end(), prompts::INIT_OUTPUT);
+    if let Some(previous) = previous {
+        prompt.push_str("\n\nThe current `AGENTS.md` is:\n\n````markdown\n");
+        prompt.push_str(previous.trim_end());
+        prompt.push_str("\n````");
+    }
+    prompt
+}
+
+/// Take the document out of the final reply, unwrapping a surrounding code fence
+fn extract_document(response: &str) -> String {
+    let trimmed = response.trim();
+    let fenced = trimmed
+        .lines()
+        .next()
+        .map(|first| {
+            let fence = first.trim_start_matches('`');
+            first.len() - fence.len() >= 3
+                && matches!(fence.trim(), "" | "markdown" | "md")
+                && trimmed.ends_with("```")
+        })
+        .unwrap_or(false);
+
+    let body = if fenced {
+        let inner: Vec<&str> = trimmed.lines().collect();
+        inner[1..inner.len() - 1].join("\n")
+    } else {
+        trimmed.to_string()
+    };
+    format!("{}\n", body.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use crate::soul::toolset::SimpleTool;
+    use crate::types::Role;
+
+    fn create_test_soul(dir: &Path) -> KimiSoul {
+        let mut soul = test_soul(dir);
+        soul.approval = Arc::new(Approval::new());
+        for name in ["ReadFile", "WriteFile", "Shell", "Glob"] {
+            soul.register_tool(Arc::new(SimpleTool::new(
+                name,
+                "A test tool",
+                serde_json::json!({"type": "object"}),
+                |_params| Ok(serde_json::json!({})),
+            )));
+        }
+        soul
+    }
+
+    #[tokio::test]
+    async fn test_run_init_creates_agents_md() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = create_test_soul(temp.path());
+        let provider = ScriptedProvider::new(["```markdown\n# Project\n\nBuild with `cargo build`.\n```"]);
+
+        let report = soul
+            .run_init(&provider, temp.path(), &WireSoulSide::new())
+            .await
+            .unwrap();
+
+        assert!(report.created);
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("AGENTS.md")).unwrap(),
+            "# Project\n\nBuild with `cargo build`.\n"
+        );
+        assert_eq!(report.stat, DiffStat { insertions: 3, deletions: 0 });
+        assert!(report.diff.starts_with("--- /dev/null\n+++ b/AGENTS.md\n"));
+
+        let mut offered = provider.requests()[0].tools.clone();
+        offered.sort();
+        assert_eq!(offered, vec!["Glob", "ReadFile"]);
+
+        assert_eq!(soul.context.message_count(), 1);
+        assert!(matches!(soul.context.messages()[0].role, Role::System));
+        assert!(soul.memory.files.iter().any(|file| file.path == report.path));
+    }
+
+    #[tokio::test]
+    async fn test_run_init_reports_diff_against_existing_file() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("AGENTS.md"), "# Project\n\nOld notes.\n").unwrap();
+        let mut soul = create_test_soul(temp.path());
+        let provider = ScriptedProvider::new(["# Project\n\nNew notes.\n"]);
+
+        let report = soul
+            .run_init(&provider, temp.path(), &WireSoulSide::new())
+            .await
+            .unwrap();
+
+        assert!(!report.created);
+        assert_eq!(report.stat, DiffStat { insertions: 1, deletions: 1 });
+        assert!(report.diff.contains("-Old notes.\n+New notes.\n"));
+    }
+
+    #[tokio::test]
+    async fn test_run_init_rejects_empty_reply() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = create_test_soul(temp.path());
+        let provider = ScriptedProvider::new(["  "]);
+
+        let result = soul.run_init(&provider, temp.path(), &WireSoulSide::new()).await;
+        assert!(matches!(result, Err(SoulError::SlashCommand(_))));
+        assert!(!temp.path().join("AGENTS.md").exists());
+    }
+
+    #[test]
+    fn test_init_prompt_includes_previous_file() {
+        let prompt = init_prompt(prompts::INIT, Some("# Existing"));
+        assert!(prompt.starts_with(prompts::INIT.trim_end()));
+        assert!(prompt.contains(prompts::INIT_OUTPUT));
+        assert!(prompt.contains("# Existing"));
+        assert!(!init_prompt(prompts::INIT, None).contains("current `AGENTS.md`"));
+    }
+
+    #[test]
+    fn test_extract_document() {
+        assert_eq!(extract_document("# Title\nbody"), "# Title\nbody\n");
+        assert_eq!(extract_document("```md\n# Title\n```"), "# Title\n");
+        assert_eq!(extract_document("````markdown\n# T\n```sh\nls\n```\n````"), "# T\n```sh\nls\n```\n");
+        assert_eq!(extract_document("```sh\nls\n```"), "```sh\nls\n```\n");
+    }
+}
@@ -0,0 +1,110 @@
+//! Interrupting a running turn
+//!
+//! An [`Interrupt`] is shared between the soul and whatever drives it. The
+//! soul registers the cancellation token of each generation with it and
+//! checks it while running tools; once it fires, the provider's HTTP stream
+//! is cancelled and any running tool is dropped, the context is closed off
+//! so the conversation can continue, and the turn ends with
+//! [`SoulError::Cancelled`](super::SoulError::Cancelled). The soul clears it
+//! when the next turn starts.
+
+use kosong_rs::CancellationToken;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Cloneable handle for interrupting a soul's current turn
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt {
+    inner: Arc<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    requested: AtomicBool,
+    notify: Notify,
+    /// Generations of the current turn, cancelled by an interrupt
+    generations: Mutex<Vec<CancellationToken>>,
+}
+
+impl Interrupt {
+    /// Create a handle that has not fired
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Interrupt the current turn
+    ///
+    /// Has no effect on a turn that starts afterwards.
+    pub fn interrupt(&self) {
+        self.inner.requested.store(true, Ordering::SeqCst);
+        for generation in self.generations().iter() {
+            generation.cancel();
+        }
+        self.inner.notify.notify_waiters();
+    }
+
+    /// Whether the current turn has been interrupted
+    pub fn is_interrupted(&self) -> bool {
+        self.inner.requested.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the current turn is interrupted
+    pub async fn interrupted(&self) {
+        loop {
+            let notified = self.inner.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+            if self.is_interrupted() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Cancel `generation` when the current turn is interrupted, at once if
+    /// it already has been
+    pub(crate) fn cancel_on_interrupt(&self, generation: CancellationToken) {
+        let mut generations = self.generations();
+        if self.is_interrupted() {
+            generation.cancel();
+        }
+        generations.push(generation);
+    }
+
+    /// Clear the interrupt before a new turn
+    pub(crate) fn reset(&self) {
+        self.generations().clear();
+        self.inner.requested.store(false, Ordering::SeqCst);
+    }
+
+    fn generations(&self) -> std::sync::MutexGuard<'_, Vec<CancellationToken>> {
+        self.inner.generations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_interrupt_wakes_waiters_until_reset() {
+        let interrupt = Interrupt::new();
+        let waiter = tokio::spawn({
+            let interrupt = interrupt.clone();
+            async move { interrupt.interrupted().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        interrupt.interrupt();
+        tokio::time::timeout(Duration::from_secs(1), waiter).await.unwrap().unwrap();
+
+        // Already interrupted: returns at once
+        interrupt.interrupted().await;
+
+        interrupt.reset();
+        assert!(!interrupt.is_interrupted());
+        let pending = tokio::time::timeout(Duration::from_millis(10), interrupt.interrupted()).await;
+        assert!(pending.is_err());
+    }
+}
@@ -10,35 +10,61 @@
 
 use crate::approval::Approval;
 use crate::context::Context;
-use crate::types::{ApprovalKind, LoopControl, Message, Request, UserInput};
+use crate::event_log::EventLog;
+use crate::transcript::Transcript;
+use crate::stats::UsageStore;
+use crate::memory::ProjectMemory;
+use crate::prompts::{self, PromptTemplates, PromptVars, SystemPromptBuilder};
+use crate::rag::{RagError, Retriever, SearchHit};
+use crate::session::Session;
+use crate::skill::{Skill, SkillMatcher};
+use crate::snapshot::SnapshotStore;
+use crate::telemetry::record_error;
+use crate::types::{ApprovalKind, LlmModel, LoopControl, Message, Request, UserInput};
 use crate::wire::WireMessage;
+use crate::workspace::Workspace;
 
 // Import from sibling modules directly to avoid circular dependencies
 use super::agent::Agent;
+use super::persona::{AgentFactory, SavedPersona};
+use super::tool_profiles::ToolProfiles;
 use super::chat;
 use super::compaction::{Compaction, SimpleCompaction};
+use super::context_window::ContextWindow;
 use super::denwarenji::DenwaRenji;
+use super::interrupt::Interrupt;
+use super::limits::{ToolCallBudget, TurnLimit};
 use super::slash::{parse_slash_command, SlashCommandRegistry};
 use super::toolset::{KimiToolset, ToolCall, ToolCallResult};
-use super::{user_message, WireSoulSide};
-use kosong_rs::ChatProvider;
-use std::sync::Arc;
+use super::undo::FileEdit;
+use super::watch::FileWatches;
+use super::WireSoulSide;
+use kosong_rs::{ChatProvider, Failover, FailoverHandler};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use thiserror::Error;
 
-use tracing::{debug, error, info, warn};
+use tracing::field::Empty;
+use tracing::{debug, error, info, instrument, warn};
+
+/// Longest command output [`KimiSoul::add_shell_output`] adds, in characters
+pub const MAX_SHELL_OUTPUT_CHARS: usize = 20_000;
 
 /// Errors that can occur in the soul
 #[derive(Debug, Error)]
 pub enum SoulError {
     #[error("Context error: {0}")]
     Context(#[from] crate::context::ContextError),
+    #[error("Attachment error: {0}")]
+    Attachment(#[from] crate::attachment::AttachmentError),
     #[error("Wire error: {0}")]
     Wire(String),
     #[error("Tool error: {0}")]
     Tool(String),
     #[error("LLM error: {0}")]
     Llm(String),
+    #[error("LLM error: {0}")]
+    Provider(#[from] kosong_rs::ChatError),
     #[error("Approval error: {0}")]
     Approval(String),
     #[error("Compaction error: {0}")]
@@ -49,10 +75,21 @@ pub enum SoulError {
     Cancelled,
     #[error("Max iterations reached")]
     MaxIterations,
+    #[error("Turn limit reached: {0}; raise loop_control.{setting} to allow more", setting = .0.setting())]
+    LimitReached(TurnLimit),
     #[error("Slash command error: {0}")]
     SlashCommand(String),
     #[error("D-Mail error: {0}")]
     DMail(String),
+    #[error("Flow error: {0}")]
+    Flow(String),
+}
+
+impl SoulError {
+    /// Whether the step may succeed against another provider
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, SoulError::Provider(e) if e.is_retryable())
+    }
 }
 
 /// Outcome of a turn
@@ -101,10 +138,63 @@ pub struct KimiSoul {
     pub loop_control: LoopControl,
     /// Context compaction strategy
     pub compaction: SimpleCompaction,
+    /// Trims the middle of the conversation when it outgrows the context window
+    pub context_window: ContextWindow,
     /// Slash command registry
     pub slash_commands: SlashCommandRegistry,
     /// Toolset for tool execution
     pub toolset: KimiToolset,
+    /// Memory files loaded into the agent's system prompt
+    pub memory: ProjectMemory,
+    /// Skills discovered for this session
+    pub skills: Vec<Skill>,
+    /// Matcher for activating skills by relevance; `None` disables activation
+    pub skill_matcher: Option<SkillMatcher>,
+    /// Index of the skill activated for the current turn
+    active_skill: Option<usize>,
+    /// Configured agent personas
+    pub agents: AgentFactory,
+    /// Tool profiles agents and sub-agents may be limited to
+    pub tool_profiles: ToolProfiles,
+    /// The original agent, saved while a persona is active
+    pub(crate) saved_persona: Option<SavedPersona>,
+    /// Store for file snapshots taken before tools modify files; `None`
+    /// disables snapshots, so rewinding only restores the conversation
+    pub snapshots: Option<SnapshotStore>,
+    /// File changes made by tools, for undoing them
+    pub(crate) edits: Vec<FileEdit>,
+    /// Paths the model is watching with `WatchPath`
+    pub file_watches: FileWatches,
+    /// Directories the soul's own file access is confined to, as for the
+    /// file tools; `None` leaves it unconfined
+    pub workspace: Option<Workspace>,
+    /// Prompt templates, with user overrides applied
+    pub prompts: PromptTemplates,
+    /// Values for prompt template variables; `model` and `tools` are
+    /// filled in from the agent and toolset when rendering
+    pub prompt_vars: PromptVars,
+    /// Session the context and event log belong to; `None` when they are
+    /// not kept in a session
+    pub session: Option<Session>,
+    /// Log the session's wire messages are recorded to; `None` disables it
+    pub event_log: Option<EventLog>,
+    /// Redacted, readable transcript of the session, from `--log-file` or
+    /// `transcript_file`
+    pub transcript: Option<Transcript>,
+    /// Store each turn's usage is added to; `None` disables usage statistics
+    pub usage: Option<UsageStore>,
+    /// Retrieves workspace code relevant to each user message; `None`
+    /// disables retrieval
+    pub retriever: Option<Retriever>,
+    /// Chunks retrieved for the current turn
+    retrieved: Vec<SearchHit>,
+    /// Provider a step is retried on when the turn's provider fails with a
+    /// retryable error; `None` disables failover
+    pub fallback_provider: Option<Arc<dyn ChatProvider>>,
+    /// Failovers within the fallback chain not yet sent to the wire
+    pub(crate) failovers: Arc<Mutex<Vec<Failover>>>,
+    /// Fired to stop the current turn
+    pub(crate) interrupt: Interrupt,
     /// Current iteration count
     iteration: usize,
     /// Turn start time
@@ -132,8 +222,31 @@ impl KimiSoul {
             denwa_renji,
             loop_control,
             compaction,
+            context_window: ContextWindow::default(),
             slash_commands: SlashCommandRegistry::with_defaults(),
             toolset: KimiToolset::new(),
+            memory: ProjectMemory::default(),
+            skills: Vec::new(),
+            skill_matcher: Some(SkillMatcher::default()),
+            active_skill: None,
+            agents: AgentFactory::default(),
+            tool_profiles: ToolProfiles::default(),
+            saved_persona: None,
+            snapshots: None,
+            edits: Vec::new(),
+            file_watches: FileWatches::default(),
+            workspace: None,
+            prompts: PromptTemplates::default(),
+            prompt_vars: PromptVars::from_env(),
+            session: None,
+            event_log: None,
+            transcript: None,
+            usage: None,
+            retriever: None,
+            retrieved: Vec::new(),
+            fallback_provider: None,
+            failovers: Arc::default(),
+            interrupt: Interrupt::new(),
             iteration: 0,
             turn_start: None,
             pending_tool_calls: Vec::new(),
@@ -180,6 +293,83 @@ impl KimiSoul {
         }
     }
 
+    /// The system prompt sent to the LLM, assembled by [`SystemPromptBuilder`]:
+    /// the agent's prompt rendered with [`KimiSoul::render_prompt`],
+    /// environment info, loaded memory files, the instructions of the skill
+    /// activated for this turn, code retrieved for it and tool guidance
+    pub fn system_prompt(&self) -> String {
+        let mut builder = SystemPromptBuilder::new()
+            .base(self.render_prompt(&self.agent.system_prompt))
+            .environment(&self.prompt_vars.clone().with("model", &self.agent.config().model))
+            .memory(&self.memory)
+            .retrieval(&self.retrieved)
+            .tools(&self.toolset);
+        if let Some(skill) = self.active_skill() {
+            builder = builder.skill(skill);
+        }
+        builder.build()
+    }
+
+    /// Fill in a prompt template with this soul's variables
+    ///
+    /// `model` is the agent's model and `tools` lists the tools it can use.
+    pub fn render_prompt(&self, template: &str) -> String {
+        let mut tools: Vec<&str> = self.toolset.tool_names().map(String::as_str).collect();
+        tools.sort();
+        let tools = if tools.is_empty() { "none".to_string() } else { tools.join(", ") };
+        let vars = self
+            .prompt_vars
+            .clone()
+            .with("model", &self.agent.config().model)
+            .with("tools", tools);
+        prompts::render(template, &vars)
+    }
+
+    /// The skill activated for the current turn, if any
+    pub fn active_skill(&self) -> Option<&Skill> {
+        self.active_skill.and_then(|index| self.skills.get(index))
+    }
+
+    /// Activate the skill most relevant to `request` for the coming turn
+    ///
+    /// Returns the activated skill, or `None` when nothing is relevant enough
+    /// or activation is disabled.
+    pub fn activate_skill_for(&mut self, request: &str) -> Option<&Skill> {
+        self.active_skill = self
+            .skill_matcher
+            .as_ref()
+            .and_then(|matcher| matcher.select(&self.skills, request))
+            .map(|m| m.index);
+        self.active_skill()
+    }
+
+    /// Drop the skill activated for the turn that just ended
+    pub fn deactivate_skill(&mut self) {
+        self.active_skill = None;
+    }
+
+    /// Retrieve the workspace chunks relevant to `request` for the coming turn
+    ///
+    /// Returns how many chunks were retrieved. Without a retriever this is a
+    /// no-op.
+    pub async fn retrieve_for(&mut self, request: &str) -> Result<usize, RagError> {
+        self.retrieved = match &self.retriever {
+            Some(retriever) => retriever.retrieve(request).await?,
+            None => Vec::new(),
+        };
+        Ok(self.retrieved.len())
+    }
+
+    /// Chunks retrieved for the current turn
+    pub fn retrieved(&self) -> &[SearchHit] {
+        &self.retrieved
+    }
+
+    /// Drop the chunks retrieved for the turn that just ended
+    pub fn clear_retrieved(&mut self) {
+        self.retrieved.clear();
+    }
+
     /// Get the toolset
     pub fn toolset(&self) -> &KimiToolset {
         &self.toolset
@@ -190,7 +380,27 @@ impl KimiSoul {
         &mut self.toolset
     }
 
+    /// Add the output of a command the user ran themselves with `!!`, so
+    /// the next turn can refer to it
+    ///
+    /// Long output is cut to its last [`MAX_SHELL_OUTPUT_CHARS`]
+    /// characters, where errors usually are.
+    pub fn add_shell_output(&mut self, command: &str, exit_code: Option<i32>, output: &str) {
+        let skip = output.chars().count().saturating_sub(MAX_SHELL_OUTPUT_CHARS);
+        let output = match skip {
+            0 => output.to_string(),
+            _ => format!("[{} characters cut]\n{}", skip, output.chars().skip(skip).collect::<String>()),
+        };
+        let status = match exit_code {
+            Some(0) => "succeeded".to_string(),
+            Some(code) => format!("failed with exit code {}", code),
+            None => "was killed".to_string(),
+        };
+        self.add_user_message(&format!("I ran `{}`, which {}:\n```\n{}\n```", command, status, output.trim_end()));
+    }
+
     /// Run a complete turn with user input
+    #[instrument(name = "turn", skip_all, fields(agent = %self.agent.name))]
     pub async fn run(
         &mut self,
         user_input: UserInput,
@@ -202,19 +412,25 @@ impl KimiSoul {
         self.send_wire(wire, WireMessage::TurnBegin { user_input: user_input.clone() }).await?;
         
         // Check for slash commands
-        if let Some((command, args)) = parse_slash_command(&user_input.text) {
+        let mut text = user_input.text;
+        if let Some((command, args)) = parse_slash_command(&text) {
             info!("Detected slash command: /{}", command);
             
             if let Some(cmd) = self.slash_commands.get(command).cloned() {
-                match cmd.execute(self, args) {
-                    Ok(()) => {
-                        self.send_wire(wire, WireMessage::TurnEnd).await?;
-                        return Ok(TurnOutcome::SlashCommandHandled);
-                    }
-                    Err(e) => {
-                        error!("Slash command error: {}", e);
-                        self.send_wire(wire, WireMessage::TurnEnd).await?;
-                        return Ok(TurnOutcome::Error(format!("Command error: {}", e)));
+                if let Some(custom) = &cmd.prompt {
+                    // User-defined command: its prompt becomes the user message
+                    text = custom.render(args);
+                } else {
+                    match cmd.execute(self, args) {
+                        Ok(()) => {
+                            self.send_wire(wire, WireMessage::TurnEnd).await?;
+                            return Ok(TurnOutcome::SlashCommandHandled);
+                        }
+                        Err(e) => {
+                            error!("Slash command error: {}", e);
+                            self.send_wire(wire, WireMessage::TurnEnd).await?;
+                            return Ok(TurnOutcome::Error(format!("Command error: {}", e)));
+                        }
                     }
                 }
             } else {
@@ -226,10 +442,7 @@ impl KimiSoul {
         }
         
         // Normal flow: create checkpoint and append user message
-        self.context.create_checkpoint(Some("User input".to_string()));
-        
-        let message = user_message(user_input.text);
-        self.context.add_message(message);
+        self.add_user_input(&text, &user_input.attachments);
         
         // Run the agent loop
         let outcome = self.agent_loop(wire).await;
@@ -257,24 +470,20 @@ impl KimiSoul {
     }
 
     /// Execute a single step in the agent loop
+    #[instrument(name = "step", skip_all, fields(n = self.iteration + 1))]
     pub async fn step(&mut self, wire: &WireSoulSide) -> Result<StepOutcome, SoulError> {
         // Check for D-Mail
         if self.denwa_renji.has_pending_dmail().await {
             info!("D-Mail detected, handling rollback");
             if let Some(dmail) = self.denwa_renji.receive_dmail().await {
-                // Rollback to checkpoint
-                if self.context.compact_to_checkpoint(&dmail.checkpoint_id.to_string()).is_none() {
-                    // If checkpoint not found, try to compact to last checkpoint
-                    self.context.compact_to_last_checkpoint();
-                }
-                // Append the D-Mail message
-                self.context.add_message(user_message(dmail.message));
+                // Rollback to the checkpoint and append the D-Mail message
+                self.apply_dmail(dmail);
                 return Ok(StepOutcome::Continue);
             }
         }
         
         // Check context size and compact if needed
-        if self.compaction.is_needed(&self.context, self.compaction.max_tokens) {
+        if self.compaction.is_needed(&self.context, self.context_window.max_tokens) {
             info!("Context needs compaction, compacting...");
             self.send_wire(wire, WireMessage::CompactionBegin).await?;
             
@@ -335,6 +544,7 @@ impl KimiSoul {
         info!("Starting agent loop");
         
         self.turn_start = Some(Instant::now());
+        let mut budget = ToolCallBudget::new(&self.loop_control);
         
         loop {
             // Check if we should stop
@@ -356,6 +566,20 @@ impl KimiSoul {
                     return Ok(TurnOutcome::Completed(response));
                 }
                 StepOutcome::ToolCalls(calls) => {
+                    for call in &calls {
+                        if let Err(limit) = budget.record(&call.name, &call.arguments) {
+                            warn!("Turn limit reached: {}", limit);
+                            self.send_wire(
+                                wire,
+                                WireMessage::TurnLimitReached {
+                                    setting: limit.setting().to_string(),
+                                    message: limit.to_string(),
+                                },
+                            ).await?;
+                            return Ok(TurnOutcome::Error(SoulError::LimitReached(limit).to_string()));
+                        }
+                    }
+
                     // Execute tool calls
                     let results = self.execute_tool_calls(calls, wire).await?;
                     
@@ -406,6 +630,20 @@ impl KimiSoul {
                 continue;
             }
             
+            // The permission mode or the tool's rule may answer without asking
+            match self.approval.decide_tool(&call.name) {
+                Some(ApprovalKind::Reject) => {
+                    results.push(ToolCallResult::error(&call.id, self.approval.refusal(&call.name)));
+                    continue;
+                }
+                Some(_) => {
+                    let result = self.execute_tool(&call).await?;
+                    results.push(result);
+                    continue;
+                }
+                None => {}
+            }
+            
             // Request approval if not in yolo mode
             if !self.approval.is_yolo() {
                 let request = Request {
@@ -414,6 +652,8 @@ impl KimiSoul {
                     sender: self.agent.name.clone(),
                     action: call.name.clone(),
                     description: format!("Execute {} with args: {}", call.name, call.arguments),
+                    edit: None,
+                    command_pattern: None,
                 };
                 
                 // Send approval request
@@ -425,11 +665,20 @@ impl KimiSoul {
                         sender: request.sender.clone(),
                         action: request.action.clone(),
                         description: request.description.clone(),
+                        diff: None,
                     },
                 ).await?;
                 
                 // Wait for approval
+                let request_id = request.id.clone();
                 let approval = self.approval.request(request).await;
+                self.send_wire(
+                    wire,
+                    WireMessage::ApprovalResponse {
+                        request_id,
+                        response: approval.clone(),
+                    },
+                ).await?;
                 
                 match approval {
                     ApprovalKind::Approve => {
@@ -437,7 +686,7 @@ impl KimiSoul {
                         let result = self.execute_tool(&call).await?;
                         results.push(result);
                     }
-                    ApprovalKind::ApproveOnce => {
+                    ApprovalKind::ApproveOnce | ApprovalKind::ApproveAlways => {
                         // Execute once
                         let result = self.execute_tool(&call).await?;
                         results.push(result);
@@ -457,17 +706,26 @@ impl KimiSoul {
     }
 
     /// Execute a single tool
-    async fn execute_tool(&self, call: &ToolCall) -> Result<ToolCallResult, SoulError> {
+    #[instrument(
+        name = "tool",
+        skip_all,
+        fields(tool = %call.name, call_id = %call.id, otel.status_code = Empty, otel.status_message = Empty)
+    )]
+    async fn execute_tool(&mut self, call: &ToolCall) -> Result<ToolCallResult, SoulError> {
         let params = call.parse_arguments()
             .map_err(|e| SoulError::Tool(format!("Invalid arguments: {}", e)))?;
+        let before = self.snapshot_before_tool(&call.name, &params);
+        let result = self.toolset.execute(&call.name, params).await;
+        self.record_edit(&call.name, before);
         
-        match self.toolset.execute(&call.name, params).await {
+        match result {
             Ok(output) => {
                 let output_str = serde_json::to_string(&output)
                     .unwrap_or_else(|_| output.to_string());
                 Ok(ToolCallResult::success(&call.id, output_str))
             }
             Err(e) => {
+                record_error(&e);
                 Ok(ToolCallResult::error(&call.id, e.to_string()))
             }
         }
@@ -504,6 +762,68 @@ impl KimiSoul {
         self.should_stop = true;
     }
 
+    /// Interrupt the current turn
+    ///
+    /// The provider's generation is cancelled, closing its HTTP stream, any
+    /// running tool is abandoned, and the turn ends with
+    /// [`SoulError::Cancelled`] once the context is consistent again.
+    pub fn interrupt(&self) {
+        self.interrupt.interrupt();
+    }
+
+    /// A handle that interrupts this soul's turns, for use while the soul is
+    /// borrowed by a running turn
+    pub fn interrupt_handle(&self) -> Interrupt {
+        self.interrupt.clone()
+    }
+
+    /// A handler for the failovers of a fallback chain, which the soul
+    /// reports on the wire as [`WireMessage::ProviderFailover`]
+    pub fn failover_handler(&self) -> FailoverHandler {
+        let failovers = self.failovers.clone();
+        Arc::new(move |failover| failovers.lock().unwrap().push(failover.clone()))
+    }
+
+    /// Fit the context to `model`: count tokens with its tokenizer, and
+    /// leave a quarter of its `max_tokens` for the system prompt and reply
+    pub fn use_model(&mut self, model: &LlmModel) {
+        let tokenizer = crate::tokenizer::for_model(&model.name);
+        if tokenizer.name() != self.context.tokenizer().name() {
+            self.context.set_tokenizer(tokenizer);
+        }
+        if let Some(context_length) = model.max_tokens {
+            self.context_window.max_tokens = context_length / 4 * 3;
+        }
+    }
+
+    /// Save the current conversation and continue with `session`'s
+    ///
+    /// The session's context, event log and file change journal replace
+    /// the current ones, so the file edits undone from then on are those of
+    /// the new session. Retrieved code is dropped, as it belongs to the old
+    /// conversation.
+    pub fn switch_session(&mut self, session: Session) -> Result<(), SoulError> {
+        self.context.save()?;
+        let context = Context::load(session.context_file.clone())?;
+        self.event_log = match EventLog::open(&session.wire_file) {
+            Ok(log) => Some(log),
+            Err(e) => {
+                warn!("Failed to open the session event log: {}", e);
+                None
+            }
+        };
+        info!("Switched to session {}", session.display_name());
+        self.context = context;
+        if self.snapshots.is_some() {
+            self.snapshots = Some(SnapshotStore::for_session(&session));
+        }
+        self.session = Some(session);
+        self.load_edits();
+        self.retrieved.clear();
+        self.iteration = 0;
+        Ok(())
+    }
+
     /// Get current iteration count
     pub fn iteration(&self) -> usize {
         self.iteration
@@ -515,6 +835,11 @@ impl KimiSoul {
     }
 
     /// Process a message with the LLM provider
+    ///
+    /// Each request is started with
+    /// [`ChatProvider::generate_cancellable`], and its token is registered
+    /// with the [`interrupt_handle`](Self::interrupt_handle), so interrupting
+    /// stops the response mid-stream.
     pub async fn process_with_llm(
         &mut self,
         provider: &dyn ChatProvider,
@@ -540,35 +865,42 @@ enum LlmResponse {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::soul::agent::Agent;
-    use std::path::PathBuf;
-
-    fn create_test_soul() -> KimiSoul {
-        let agent = Agent::new("TestAgent", "A test agent");
-        let context = Context::new(PathBuf::from("/tmp/test_context.json"));
-        let approval = Arc::new(Approval::yolo());
-        let denwa_renji = Arc::new(DenwaRenji::new());
-        let loop_control = LoopControl {
-            max_iterations: 10,
-            timeout_seconds: 60,
-        };
-        let compaction = SimpleCompaction::new(8000);
-
-        KimiSoul::new(agent, context, approval, denwa_renji, loop_control, compaction)
-    }
+    use crate::soul::testing::test_soul;
 
     #[tokio::test]
     async fn test_kimisoul_new() {
-        let soul = create_test_soul();
+        let temp = tempfile::tempdir().unwrap();
+        let soul = test_soul(temp.path());
         
-        assert_eq!(soul.agent.name, "TestAgent");
+        assert_eq!(soul.agent.name, "Kimi");
         assert_eq!(soul.iteration(), 0);
         assert!(!soul.should_stop());
     }
 
+    #[test]
+    fn test_switch_session() {
+        let temp = tempfile::tempdir().unwrap();
+        let manager = crate::session::SessionManager::new(temp.path());
+        let api = manager.create("api").unwrap();
+        let docs = manager.create("docs").unwrap();
+
+        let mut soul = test_soul(temp.path());
+        soul.context = Context::new(temp.path().join("scratch.json"));
+        soul.switch_session(api.clone()).unwrap();
+        soul.context.add_message(crate::soul::user_message("Add pagination to /users"));
+        soul.switch_session(docs).unwrap();
+        assert_eq!(soul.context.message_count(), 0);
+        assert_eq!(soul.session.as_ref().and_then(|s| s.name.as_deref()), Some("docs"));
+        assert!(soul.event_log.is_some());
+
+        soul.switch_session(api).unwrap();
+        assert_eq!(soul.context.messages()[0].content, "Add pagination to /users");
+    }
+
     #[tokio::test]
     async fn test_kimisoul_stop() {
-        let mut soul = create_test_soul();
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
         
         assert!(!soul.should_stop());
         soul.stop();
@@ -577,13 +909,28 @@ mod tests {
 
     #[tokio::test]
     async fn test_kimisoul_iteration() {
-        let mut soul = create_test_soul();
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
         
         assert_eq!(soul.iteration(), 0);
         soul.iteration = 5;
         assert_eq!(soul.iteration(), 5);
     }
 
+    #[test]
+    fn test_add_shell_output() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.add_shell_output("git status", Some(0), "On branch main\n");
+        let long = "x".repeat(MAX_SHELL_OUTPUT_CHARS + 5);
+        soul.add_shell_output("cargo build", Some(101), &long);
+
+        let messages = soul.context.messages();
+        assert_eq!(messages[0].content, "I ran `git status`, which succeeded:\n```\nOn branch main\n```");
+        assert!(messages[1].content.starts_with("I ran `cargo build`, which failed with exit code 101:\n```\n[5 characters cut]\n"));
+        assert_eq!(soul.rewind_points().len(), 2);
+    }
+
     #[test]
     fn test_turn_outcome_debug() {
         let outcome = TurnOutcome::Completed("Hello".to_string());
@@ -0,0 +1,115 @@
+//! Per-turn limits on steps and tool calls
+//!
+//! [`LoopControl`] caps how many LLM calls and tool calls one turn may make,
+//! and how many identical tool calls may follow each other before the turn
+//! is stopped as a loop. A turn that hits a limit sends
+//! [`WireMessage::TurnLimitReached`](crate::wire::WireMessage::TurnLimitReached)
+//! and ends with [`SoulError::LimitReached`](super::SoulError::LimitReached).
+
+use crate::types::LoopControl;
+use std::fmt;
+
+/// A per-turn limit that was reached
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TurnLimit {
+    /// The turn made its maximum number of LLM calls
+    Steps(usize),
+    /// The turn made its maximum number of tool calls
+    ToolCalls(usize),
+    /// The same tool was called with the same arguments too many times in
+    /// a row
+    IdenticalToolCalls { tool: String, count: usize },
+}
+
+impl TurnLimit {
+    /// Name of the `[loop_control]` setting behind the limit
+    pub fn setting(&self) -> &'static str {
+        match self {
+            TurnLimit::Steps(_) => "max_steps_per_turn",
+            TurnLimit::ToolCalls(_) => "max_tool_calls_per_turn",
+            TurnLimit::IdenticalToolCalls { .. } => "max_identical_tool_calls",
+        }
+    }
+}
+
+impl fmt::Display for TurnLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TurnLimit::Steps(max) => write!(f, "the turn made {} LLM calls without finishing", max),
+            TurnLimit::ToolCalls(max) => write!(f, "the turn made {} tool calls", max),
+            TurnLimit::IdenticalToolCalls { tool, count } => {
+                write!(f, "{} was called {} times in a row with the same arguments", tool, count)
+            }
+        }
+    }
+}
+
+/// Counts a turn's tool calls against its limits
+#[derive(Debug)]
+pub(crate) struct ToolCallBudget {
+    max_calls: usize,
+    max_identical: usize,
+    calls: usize,
+    last: Option<(String, String)>,
+    repeats: usize,
+}
+
+impl ToolCallBudget {
+    pub(crate) fn new(loop_control: &LoopControl) -> Self {
+        Self {
+            max_calls: loop_control.max_tool_calls_per_turn,
+            max_identical: loop_control.max_identical_tool_calls,
+            calls: 0,
+            last: None,
+            repeats: 0,
+        }
+    }
+
+    /// Count a call about to be made, or return the limit it would exceed
+    pub(crate) fn record(&mut self, tool: &str, arguments: &str) -> Result<(), TurnLimit> {
+        if self.calls >= self.max_calls {
+            return Err(TurnLimit::ToolCalls(self.max_calls));
+        }
+        let same = self
+            .last
+            .as_ref()
+            .is_some_and(|(name, args)| name == tool && args == arguments);
+        let repeats = if same { self.repeats + 1 } else { 1 };
+        if repeats > self.max_identical {
+            return Err(TurnLimit::IdenticalToolCalls {
+                tool: tool.to_string(),
+                count: repeats,
+            });
+        }
+        self.calls += 1;
+        self.repeats = repeats;
+        if !same {
+            self.last = Some((tool.to_string(), arguments.to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_budget_stops_loops_and_runaway_turns() {
+        let loop_control = LoopControl {
+            max_tool_calls_per_turn: 4,
+            max_identical_tool_calls: 2,
+            ..LoopControl::default()
+        };
+        let mut budget = ToolCallBudget::new(&loop_control);
+        budget.record("ReadFile", r#"{"path":"a"}"#).unwrap();
+        budget.record("ReadFile", r#"{"path":"a"}"#).unwrap();
+        let err = budget.record("ReadFile", r#"{"path":"a"}"#).unwrap_err();
+        assert_eq!(err, TurnLimit::IdenticalToolCalls { tool: "ReadFile".to_string(), count: 3 });
+        assert_eq!(err.setting(), "max_identical_tool_calls");
+
+        budget.record("ReadFile", r#"{"path":"b"}"#).unwrap();
+        budget.record("ReadFile", r#"{"path":"a"}"#).unwrap();
+        assert_eq!(budget.record("Glob", "{}").unwrap_err(), TurnLimit::ToolCalls(4));
+    }
+}
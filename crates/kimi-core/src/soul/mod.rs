@@ -4,24 +4,71 @@
 //! - KimiSoul: The main agent orchestrator
 //! - Agent: The agent runtime and execution context
 //! - Toolset: Tool management and execution
-//! - Compaction: Context compaction strategies
+//! - Compaction: Context compaction strategies, and LLM summaries behind `/compact`
 //! - DenwaRenji: D-Mail system for time-travel debugging
-//! - Slash commands: User command handling
+//! - FlowRunner: Execution of flow-type skills
+//! - Slash commands: User command handling, including markdown-defined custom commands
+//! - Commit: Commit messages written by the model for `/commit`
+//! - Init: Project analysis behind `/init`
+//! - Sub-agents: Child souls that run delegated tasks concurrently, or that
+//!   the model spawns with the `SpawnAgent` tool
+//! - Personas: Named agents from config, selectable with `/agent`
+//! - Tool profiles: Named subsets of the toolset for the session or an agent
+//! - Delegation: Executing labor market tasks with registered agents
+//! - Rewind: Restoring the conversation to a checkpoint with `/rewind`
+//! - Undo: Putting back the files tools changed with `/undo` or `UndoEdit`
+//! - Watch: Telling the model about changes to paths it watches
+//! - Interrupt: Stopping the current turn mid-stream or mid-tool
+//! - Limits: Per-turn caps on steps and tool calls, with loop detection
+//! - Proposed edits: File changes worked out for review before approval
 
 pub mod agent;
 pub mod chat;
+pub mod commit;
+pub mod compact;
 pub mod compaction;
+pub mod context_window;
+pub mod custom_commands;
+pub mod delegation;
 pub mod denwarenji;
+pub mod flow_runner;
+pub mod init;
+pub mod interrupt;
 pub mod kimisoul;
+pub mod limits;
+pub mod persona;
+pub mod proposed_edit;
+pub mod rewind;
 pub mod slash;
+pub mod subagent;
+#[cfg(test)]
+pub(crate) mod testing;
+pub mod tool_profiles;
 pub mod toolset;
+pub mod undo;
+pub mod watch;
 
-pub use agent::{Agent, AgentConfig, AgentState, LaborMarket, Runtime};
+pub use agent::{Agent, AgentConfig, AgentState, LaborMarket, MarketTask, Runtime, SchedulerConfig, SchedulerHandle, TaskExecutor, TaskStatus};
+pub use compact::CompactReport;
 pub use compaction::{Compaction, SimpleCompaction};
+pub use context_window::ContextWindow;
+pub use custom_commands::{CommandScope, CustomCommand};
+pub use delegation::{DelegatedTask, SoulTaskExecutor};
 pub use denwarenji::{DenwaRenji, DMail};
+pub use flow_runner::{FlowReport, FlowRunner};
+pub use init::InitReport;
+pub use interrupt::Interrupt;
 pub use kimisoul::{KimiSoul, SoulError, TurnOutcome, StepOutcome};
+pub use limits::TurnLimit;
+pub use persona::AgentFactory;
+pub use proposed_edit::ProposedEdit;
+pub use rewind::{checkpoint_label, Rewind};
 pub use slash::{SlashCommand, SlashCommandRegistry};
-pub use toolset::{KimiToolset, Tool, ToolError, ToolResult, McpServerInfo, ToolCall, ToolCallResult};
+pub use subagent::{SpawnAgentTool, SubagentResult, SubagentSpec, SPAWN_AGENT_TOOL};
+pub use tool_profiles::ToolProfiles;
+pub use undo::{FileEdit, Undo, UndoEditTool, UNDO_EDIT_TOOL};
+pub use watch::{FileChange, FileWatches, WatchPathTool, WATCH_PATH_TOOL};
+pub use toolset::{KimiToolset, OutputSink, Tool, ToolError, ToolResult, McpServerInfo, ToolCall, ToolCallResult};
 
 use crate::types::{Message, Role};
 use crate::wire::WireMessage;
@@ -35,6 +82,8 @@ use tokio::sync::mpsc;
 #[derive(Debug, Clone)]
 pub struct WireSoulSide {
     sender: Option<Arc<mpsc::Sender<WireMessage>>>,
+    /// Sub-agent task ids, outermost first, whose events this side wraps
+    subagents: Vec<String>,
 }
 
 impl WireSoulSide {
@@ -42,6 +91,7 @@ impl WireSoulSide {
     pub fn new() -> Self {
         Self {
             sender: None,
+            subagents: Vec::new(),
         }
     }
 
@@ -49,11 +99,26 @@ impl WireSoulSide {
     pub fn with_sender(sender: mpsc::Sender<WireMessage>) -> Self {
         Self {
             sender: Some(Arc::new(sender)),
+            subagents: Vec::new(),
         }
     }
 
+    /// A wire for a sub-agent that wraps every message in
+    /// [`WireMessage::SubagentEvent`] with `task_id`
+    pub fn for_subagent(&self, task_id: impl Into<String>) -> Self {
+        let mut wire = self.clone();
+        wire.subagents.push(task_id.into());
+        wire
+    }
+
     /// Send a message through the wire
     pub async fn send(&self, message: WireMessage) -> Result<(), SoulError> {
+        let message = self.subagents.iter().rev().fold(message, |event, task_id| {
+            WireMessage::SubagentEvent {
+                task_tool_call_id: task_id.clone(),
+                event: Box::new(event),
+            }
+        });
         if let Some(sender) = &self.sender {
             sender.send(message).await
                 .map_err(|e| SoulError::Wire(format!("Failed to send message: {}", e)))?;
@@ -0,0 +1,273 @@
+//! Agent personas
+//!
+//! Named agents from the `[agents]` config table. An [`AgentFactory`] turns
+//! them into [`Agent`]s and sub-agent specs, and a soul can switch its own
+//! persona with [`KimiSoul::use_persona`], restricting its tools and
+//! replacing its system prompt until [`KimiSoul::reset_persona`].
+
+use super::agent::Agent;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::subagent::SubagentSpec;
+use super::toolset::KimiToolset;
+use crate::config::{AgentPersona, Config};
+use crate::types::LoopControl;
+use std::collections::HashMap;
+use tracing::info;
+
+/// Builds agents from configured personas
+#[derive(Debug, Clone, Default)]
+pub struct AgentFactory {
+    personas: HashMap<String, AgentPersona>,
+}
+
+impl AgentFactory {
+    /// Create a factory for the given personas
+    pub fn new(personas: HashMap<String, AgentPersona>) -> Self {
+        Self { personas }
+    }
+
+    /// Create a factory for the personas in `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(config.agents.clone())
+    }
+
+    /// Persona names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.personas.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Look up a persona
+    pub fn get(&self, name: &str) -> Option<&AgentPersona> {
+        self.personas.get(name)
+    }
+
+    /// Whether no personas are configured
+    pub fn is_empty(&self) -> bool {
+        self.personas.is_empty()
+    }
+
+    /// Build the agent for `name`, starting from `base` for unset fields
+    pub fn create(&self, name: &str, base: &Agent) -> Result<Agent, SoulError> {
+        let persona = self.lookup(name)?;
+        let mut config = base.config().clone();
+        if let Some(model) = &persona.model {
+            config.model = model.clone();
+        }
+        if let Some(max_iterations) = persona.max_iterations {
+            config.max_iterations = max_iterations;
+        }
+        if let Some(profile) = &persona.tool_profile {
+            config.tool_profile = Some(profile.clone());
+        }
+        let role = persona.description.clone().unwrap_or_else(|| base.role.clone());
+        let system_prompt = persona
+            .system_prompt
+            .clone()
+            .unwrap_or_else(|| base.system_prompt.clone());
+
+        Ok(Agent::new(name, role)
+            .with_system_prompt(system_prompt)
+            .with_config(config))
+    }
+
+    /// Build a sub-agent task for `name` with the persona's tools
+    pub fn spec(&self, name: &str, base: &Agent, prompt: impl Into<String>) -> Result<SubagentSpec, SoulError> {
+        let spec = SubagentSpec::new(self.create(name, base)?, prompt);
+        Ok(match &self.lookup(name)?.tools {
+            Some(tools) => spec.with_tools(tools.iter().cloned()),
+            None => spec,
+        })
+    }
+
+    fn lookup(&self, name: &str) -> Result<&AgentPersona, SoulError> {
+        self.personas.get(name).ok_or_else(|| {
+            let names = self.names();
+            let available = if names.is_empty() {
+                "no agents are configured".to_string()
+            } else {
+                format!("available: {}", names.join(", "))
+            };
+            SoulError::SlashCommand(format!("Unknown agent '{}' ({})", name, available))
+        })
+    }
+}
+
+/// What a soul looked like before switching persona
+#[derive(Debug, Clone)]
+pub(crate) struct SavedPersona {
+    agent: Agent,
+    toolset: KimiToolset,
+    loop_control: LoopControl,
+}
+
+impl KimiSoul {
+    /// Switch this soul to the persona `name`
+    ///
+    /// The agent, allowed tools and iteration limit change; the conversation
+    /// is kept. Switching again starts from the original agent, not from the
+    /// current persona.
+    pub fn use_persona(&mut self, name: &str) -> Result<&Agent, SoulError> {
+        let saved = self.saved_persona.clone().unwrap_or_else(|| SavedPersona {
+            agent: self.agent.clone(),
+            toolset: self.toolset.clone(),
+            loop_control: self.loop_control.clone(),
+        });
+        let agent = self.agents.create(name, &saved.agent)?;
+        let persona = self.agents.lookup(name)?;
+
+        self.toolset = match (&persona.tools, &persona.tool_profile) {
+            (Some(tools), _) => {
+                let names: Vec<&str> = tools.iter().map(String::as_str).collect();
+                saved.toolset.subset(&names)
+            }
+            (None, Some(profile)) => self.tool_profiles.apply(profile, &saved.toolset)?,
+            (None, None) => saved.toolset.clone(),
+        };
+        self.loop_control = LoopControl {
+            max_iterations: persona.max_iterations.unwrap_or(saved.loop_control.max_iterations),
+            ..saved.loop_control.clone()
+        };
+        self.agent = agent;
+        self.saved_persona = Some(saved);
+        info!("Switched to agent persona {}", name);
+        Ok(&self.agent)
+    }
+
+    /// Go back to the agent the soul started with
+    pub fn reset_persona(&mut self) {
+        if let Some(saved) = self.saved_persona.take() {
+            self.agent = saved.agent;
+            self.toolset = saved.toolset;
+            self.loop_control = saved.loop_control;
+        }
+    }
+
+    /// Name of the active persona, if one was selected
+    pub fn persona(&self) -> Option<&str> {
+        self.saved_persona.as_ref().map(|_| self.agent.name.as_str())
+    }
+
+    /// Model configured for the active persona, if it overrides the default
+    pub fn persona_model(&self) -> Option<&str> {
+        self.persona()
+            .and_then(|name| self.agents.get(name))
+            .and_then(|persona| persona.model.as_deref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::test_soul;
+    use crate::soul::toolset::SimpleTool;
+    use std::sync::Arc;
+
+    fn factory() -> AgentFactory {
+        AgentFactory::new(HashMap::from([
+            (
+                "reviewer".to_string(),
+                AgentPersona {
+                    description: Some("Reviews diffs".to_string()),
+                    system_prompt: Some("You review code.".to_string()),
+                    model: Some("kimi-k2".to_string()),
+                    tools: Some(vec!["ReadFile".to_string()]),
+                    tool_profile: None,
+                    max_iterations: Some(10),
+                },
+            ),
+            ("plain".to_string(), AgentPersona::default()),
+            (
+                "planner".to_string(),
+                AgentPersona {
+                    tool_profile: Some("read-only".to_string()),
+                    ..AgentPersona::default()
+                },
+            ),
+            (
+                "broken".to_string(),
+                AgentPersona {
+                    tool_profile: Some("missing".to_string()),
+                    ..AgentPersona::default()
+                },
+            ),
+        ]))
+    }
+
+    fn create_test_soul(dir: &std::path::Path) -> KimiSoul {
+        let mut soul = test_soul(dir);
+        soul.agent = soul.agent.with_system_prompt("You are Kimi.");
+        for name in ["ReadFile", "WriteFile", "Shell"] {
+            soul.register_tool(Arc::new(SimpleTool::new(
+                name,
+                "A test tool",
+                serde_json::json!({"type": "object"}),
+                |_params| Ok(serde_json::json!({})),
+            )));
+        }
+        soul.agents = factory();
+        soul
+    }
+
+    #[test]
+    fn test_factory_create() {
+        let base = Agent::new("Kimi", "Default agent").with_system_prompt("You are Kimi.");
+        let factory = factory();
+        assert_eq!(factory.names(), vec!["broken", "plain", "planner", "reviewer"]);
+
+        let reviewer = factory.create("reviewer", &base).unwrap();
+        assert_eq!(reviewer.name, "reviewer");
+        assert_eq!(reviewer.role, "Reviews diffs");
+        assert_eq!(reviewer.system_prompt, "You review code.");
+        assert_eq!(reviewer.config().model, "kimi-k2");
+        assert_eq!(reviewer.config().max_iterations, 10);
+
+        let plain = factory.create("plain", &base).unwrap();
+        assert_eq!(plain.system_prompt, "You are Kimi.");
+        assert_eq!(plain.config().max_iterations, base.config().max_iterations);
+
+        let spec = factory.spec("reviewer", &base, "Review the diff").unwrap();
+        assert_eq!(spec.tools, Some(vec!["ReadFile".to_string()]));
+        assert_eq!(factory.spec("plain", &base, "Go").unwrap().tools, None);
+
+        let err = factory.create("missing", &base).unwrap_err();
+        assert!(err.to_string().contains("available: broken, plain, planner, reviewer"));
+
+        let planner = factory.create("planner", &base).unwrap();
+        assert_eq!(planner.config().tool_profile.as_deref(), Some("read-only"));
+    }
+
+    #[test]
+    fn test_use_and_reset_persona() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = create_test_soul(temp.path());
+        assert_eq!(soul.persona(), None);
+
+        soul.use_persona("reviewer").unwrap();
+        assert_eq!(soul.persona(), Some("reviewer"));
+        assert_eq!(soul.persona_model(), Some("kimi-k2"));
+        assert!(soul.system_prompt().starts_with("You review code.\n\n# Environment"));
+        assert_eq!(soul.toolset.tool_names().collect::<Vec<_>>(), vec!["ReadFile"]);
+        assert_eq!(soul.loop_control.max_iterations, 10);
+
+        // Switching again starts from the original tools
+        soul.use_persona("plain").unwrap();
+        assert_eq!(soul.toolset.tool_count(), 3);
+        assert!(soul.system_prompt().starts_with("You are Kimi.\n\n# Environment"));
+        assert_eq!(soul.persona_model(), None);
+
+        soul.use_persona("planner").unwrap();
+        assert_eq!(soul.toolset.tool_names().collect::<Vec<_>>(), vec!["ReadFile"]);
+
+        assert!(soul.use_persona("missing").is_err());
+        let err = soul.use_persona("broken").unwrap_err();
+        assert!(err.to_string().contains("Unknown tool profile 'missing'"), "{}", err);
+        assert_eq!(soul.persona(), Some("planner"));
+
+        soul.reset_persona();
+        assert_eq!(soul.persona(), None);
+        assert_eq!(soul.agent.name, "Kimi");
+        assert_eq!(soul.loop_control.max_iterations, LoopControl::default().max_iterations);
+    }
+}
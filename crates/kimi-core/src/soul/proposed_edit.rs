@@ -0,0 +1,135 @@
+//! Review of file edits before they are applied
+//!
+//! When a `WriteFile` or `StrReplaceFile` call needs approval, the change it
+//! would make is worked out up front, so the frontend can show it as a diff
+//! and let the user rewrite the result before it is applied.
+
+use crate::diff::unified_diff;
+use serde::{Deserialize, Serialize};
+
+/// Lines of context around each change in [`ProposedEdit::diff`]
+const DIFF_CONTEXT: usize = 3;
+
+/// The change a file edit tool call would make
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposedEdit {
+    /// Tool that makes the change
+    pub tool: String,
+    /// File path as the tool was given it
+    pub path: String,
+    /// Current content; empty for a new file
+    pub old: String,
+    /// Content after the change
+    pub new: String,
+}
+
+impl ProposedEdit {
+    /// The change `tool` would make with `params`
+    ///
+    /// `None` for tools that do not edit files and for edits that would
+    /// fail, e.g. a replacement whose old string is not in the file.
+    pub fn from_tool_call(tool: &str, params: &serde_json::Value) -> Option<Self> {
+        let path = params.get("path")?.as_str()?;
+        let current = std::fs::read_to_string(path);
+        let new = match tool {
+            "WriteFile" => {
+                let content = params.get("content")?.as_str()?;
+                match params.get("mode").and_then(|m| m.as_str()) {
+                    Some("append") => format!("{}{}", current.as_deref().unwrap_or(""), content),
+                    _ => content.to_string(),
+                }
+            }
+            "StrReplaceFile" => {
+                let mut content = current.as_ref().ok()?.clone();
+                let edits = match params.get("edit") {
+                    Some(edits) => edits.as_array()?.iter().collect(),
+                    None => vec![params],
+                };
+                for edit in edits {
+                    let old = edit.get("old")?.as_str()?;
+                    let new = edit.get("new")?.as_str()?;
+                    if !content.contains(old) {
+                        return None;
+                    }
+                    if edit.get("replace_all").and_then(|r| r.as_bool()).unwrap_or(false) {
+                        content = content.replace(old, new);
+                    } else {
+                        content = content.replacen(old, new, 1);
+                    }
+                }
+                content
+            }
+            _ => return None,
+        };
+        Some(Self {
+            tool: tool.to_string(),
+            path: path.to_string(),
+            old: current.unwrap_or_default(),
+            new,
+        })
+    }
+
+    /// Unified diff of the change
+    pub fn diff(&self) -> String {
+        unified_diff(
+            &self.old,
+            &self.new,
+            &format!("a/{}", self.path),
+            &format!("b/{}", self.path),
+            DIFF_CONTEXT,
+        )
+    }
+
+    /// Arguments for the same tool that leave the file with `content`
+    /// instead, for applying a change the user edited
+    pub fn arguments_with(&self, content: &str) -> serde_json::Value {
+        match self.tool.as_str() {
+            "StrReplaceFile" => serde_json::json!({
+                "path": self.path,
+                "old": self.old,
+                "new": content,
+            }),
+            _ => serde_json::json!({
+                "path": self.path,
+                "content": content,
+                "mode": "overwrite",
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proposed_edits() {
+        let temp = tempfile::tempdir().unwrap();
+        let file = temp.path().join("notes.txt");
+        std::fs::write(&file, "one\ntwo\nthree\n").unwrap();
+        let path = file.to_str().unwrap();
+
+        let params = serde_json::json!({"path": path, "old": "two", "new": "2"});
+        let edit = ProposedEdit::from_tool_call("StrReplaceFile", &params).unwrap();
+        assert_eq!(edit.new, "one\n2\nthree\n");
+        assert!(edit.diff().contains("-two\n+2\n"));
+
+        // A change the user rewrote replaces the whole file
+        let edited = edit.arguments_with("one\ntwo-ish\nthree\n");
+        assert_eq!(edited["old"], "one\ntwo\nthree\n");
+
+        let params = serde_json::json!({"path": path, "edit": [{"old": "e", "new": "E", "replace_all": true}]});
+        let edit = ProposedEdit::from_tool_call("StrReplaceFile", &params).unwrap();
+        assert_eq!(edit.new, "onE\ntwo\nthrEE\n");
+        let params = serde_json::json!({"path": path, "old": "four", "new": "4"});
+        assert_eq!(ProposedEdit::from_tool_call("StrReplaceFile", &params), None);
+
+        let new_file = temp.path().join("new.txt");
+        let params = serde_json::json!({"path": new_file.to_str().unwrap(), "content": "hello\n"});
+        let edit = ProposedEdit::from_tool_call("WriteFile", &params).unwrap();
+        assert_eq!((edit.old.as_str(), edit.new.as_str()), ("", "hello\n"));
+        let params = serde_json::json!({"path": path, "content": "four\n", "mode": "append"});
+        assert!(ProposedEdit::from_tool_call("WriteFile", &params).unwrap().new.ends_with("three\nfour\n"));
+        assert_eq!(ProposedEdit::from_tool_call("Shell", &serde_json::json!({"command": "ls"})), None);
+    }
+}
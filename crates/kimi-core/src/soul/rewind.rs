@@ -0,0 +1,303 @@
+//! Rewinding the conversation
+//!
+//! A checkpoint is taken before every user message, labelled with the start
+//! of that message. [`KimiSoul::rewind`] restores the context to one of them,
+//! dropping the message and everything after it, so a revised instruction
+//! can be sent in its place. When the soul has a [`SnapshotStore`], files
+//! that tools changed since the checkpoint are put back as well. D-Mails
+//! received from the [`DenwaRenji`](super::DenwaRenji) during a turn rewind
+//! the same way.
+//!
+//! [`SnapshotStore`]: crate::snapshot::SnapshotStore
+
+use super::denwarenji::DMail;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::user_message;
+use crate::snapshot::{FileSnapshot, FILE_WRITE_TOOLS};
+use crate::types::{Attachment, Checkpoint};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+/// Longest checkpoint label, in characters
+const MAX_LABEL_CHARS: usize = 60;
+
+/// What a rewind undid
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    /// The checkpoint that was rewound to, now removed
+    pub checkpoint: Checkpoint,
+    /// Files put back as they were at the checkpoint
+    pub restored: Vec<PathBuf>,
+    /// Files that could not be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl KimiSoul {
+    /// Checkpoints the conversation can be rewound to, oldest first
+    pub fn rewind_points(&self) -> &[Checkpoint] {
+        self.context.checkpoints()
+    }
+
+    /// Restore the conversation to the checkpoint at `index`
+    ///
+    /// The user message the checkpoint was taken for is removed too, so the
+    /// next message takes its place. Files snapshotted since the checkpoint
+    /// are restored; a file that fails to restore does not stop the others.
+    pub fn rewind(&mut self, index: usize) -> Result<Rewind, SoulError> {
+        let count = self.context.checkpoints().len();
+        let snapshots = self.context.snapshots_since(index);
+        let checkpoint = self.context.rewind_to_checkpoint(index).ok_or_else(|| {
+            SoulError::DMail(format!(
+                "No checkpoint {} (the conversation has {})",
+                index + 1,
+                count
+            ))
+        })?;
+
+        // Changes made since are undone by the rewind
+        self.edits.retain(|edit| edit.checkpoint < index);
+        self.save_edits();
+
+        let mut rewind = Rewind {
+            checkpoint,
+            restored: Vec::new(),
+            failed: Vec::new(),
+        };
+        let Some(store) = &self.snapshots else {
+            return Ok(rewind);
+        };
+        for snapshot in snapshots {
+            match store.restore(&snapshot) {
+                Ok(()) => rewind.restored.push(snapshot.path),
+                Err(e) => {
+                    warn!("Failed to restore {:?}: {}", snapshot.path, e);
+                    rewind.failed.push((snapshot.path, e.to_string()));
+                }
+            }
+        }
+        Ok(rewind)
+    }
+
+    /// Snapshot the file a tool is about to modify, recording it on the
+    /// checkpoint the first time
+    ///
+    /// The snapshot is returned for [`record_edit`](Self::record_edit) once
+    /// the tool has run.
+    pub(crate) fn snapshot_before_tool(&mut self, tool_name: &str, params: &serde_json::Value) -> Option<FileSnapshot> {
+        let store = self.snapshots.as_ref()?;
+        if !FILE_WRITE_TOOLS.contains(&tool_name) {
+            return None;
+        }
+        let path = params.get("path").and_then(|p| p.as_str())?;
+        let path = std::path::absolute(Path::new(path)).ok()?;
+        match store.snapshot(&path) {
+            Ok(snapshot) => {
+                if self.context.needs_snapshot(&path) {
+                    self.context.record_snapshot(snapshot.clone());
+                }
+                Some(snapshot)
+            }
+            Err(e) => {
+                warn!("Failed to snapshot {:?} before {}: {}", path, tool_name, e);
+                None
+            }
+        }
+    }
+
+    /// Take a checkpoint for `text` and add it to the context as a user message
+    pub(crate) fn add_user_message(&mut self, text: &str) {
+        self.add_user_input(text, &[]);
+    }
+
+    /// Like [`add_user_message`](Self::add_user_message), keeping the
+    /// message's attachments in its metadata
+    pub(crate) fn add_user_input(&mut self, text: &str, attachments: &[Attachment]) {
+        self.context.create_checkpoint(Some(checkpoint_label(text)));
+        self.context.add_message(user_message(text).with_attachments(attachments));
+    }
+
+    /// Rewind to the checkpoint of the pending D-Mail, if one was sent
+    ///
+    /// Unlike a D-Mail received during a turn, the message is not added to
+    /// the context: it is returned with the D-Mail for the caller to send as
+    /// the next user message, if it is not empty.
+    pub async fn deliver_dmail(&mut self) -> Result<Option<(Rewind, DMail)>, SoulError> {
+        let Some(dmail) = self.denwa_renji.receive_dmail().await else {
+            return Ok(None);
+        };
+        let rewind = self.rewind(dmail.checkpoint_id)?;
+        info!("D-Mail rewound the conversation to checkpoint {}", dmail.checkpoint_id + 1);
+        Ok(Some((rewind, dmail)))
+    }
+
+    /// Rewind to the D-Mail's checkpoint and send its message from there
+    ///
+    /// An unknown checkpoint falls back to the most recent one.
+    pub(crate) fn apply_dmail(&mut self, dmail: DMail) {
+        let index = if dmail.checkpoint_id < self.context.checkpoints().len() {
+            dmail.checkpoint_id
+        } else {
+            self.context.checkpoints().len().saturating_sub(1)
+        };
+        if self.rewind(index).is_ok() {
+            info!("D-Mail rewound the conversation to checkpoint {}", index + 1);
+        }
+        self.add_user_message(&dmail.message);
+    }
+}
+
+/// Label for a checkpoint: the first line of the message, shortened
+pub fn checkpoint_label(text: &str) -> String {
+    let line = text.trim().lines().next().unwrap_or("").trim();
+    match line.char_indices().nth(MAX_LABEL_CHARS) {
+        Some((cut, _)) => format!("{}...", line[..cut].trim_end()),
+        None => line.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use crate::soul::{chat, WireSoulSide};
+    use crate::types::UserInput;
+    use std::sync::Arc;
+
+    fn input(text: &str) -> UserInput {
+        UserInput {
+            text: text.to_string(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_label() {
+        assert_eq!(checkpoint_label("  Fix the build\n\nIt fails on CI"), "Fix the build");
+        let long = "word ".repeat(20);
+        let label = checkpoint_label(&long);
+        assert!(label.ends_with("..."));
+        assert!(label.chars().count() <= MAX_LABEL_CHARS + 3);
+    }
+
+    #[tokio::test]
+    async fn test_rewind_and_revise() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        let provider = ScriptedProvider::new(["Hi!", "Rewritten in Go.", "Rewritten in Rust."]);
+        let wire = WireSoulSide::new();
+        chat::process_message(&mut soul, &provider, input("Hello"), &wire).await.unwrap();
+        chat::process_message(&mut soul, &provider, input("Rewrite it in Go"), &wire).await.unwrap();
+
+        let labels: Vec<_> = soul.rewind_points().iter().map(|c| c.summary.clone().unwrap()).collect();
+        assert_eq!(labels, vec!["Hello", "Rewrite it in Go"]);
+        assert!(soul.rewind(2).unwrap_err().to_string().contains("No checkpoint 3"));
+
+        let rewind = soul.rewind(1).unwrap();
+        assert_eq!(rewind.checkpoint.summary.as_deref(), Some("Rewrite it in Go"));
+        assert_eq!(soul.context.message_count(), 2);
+
+        chat::process_message(&mut soul, &provider, input("Rewrite it in Rust"), &wire).await.unwrap();
+        let history: Vec<_> = provider.requests()[2].messages.iter().map(|m| m.text().unwrap_or_default()).collect();
+        assert_eq!(history, vec!["Hello", "Hi!", "Rewrite it in Rust"]);
+        assert_eq!(soul.rewind_points().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_rewind_restores_files() {
+        use crate::snapshot::SnapshotStore;
+        use crate::soul::toolset::SimpleTool;
+        use kosong_rs::message::FunctionCall;
+        use kosong_rs::{StreamChunk, ToolCall};
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.snapshots = Some(SnapshotStore::for_work_dir(temp.path()));
+        soul.register_tool(Arc::new(SimpleTool::new(
+            "WriteFile",
+            "Write a file",
+            serde_json::json!({"type": "object"}),
+            |params| {
+                std::fs::write(params["path"].as_str().unwrap(), params["content"].as_str().unwrap())
+                    .map_err(|e| crate::soul::ToolError::new(e.to_string()))?;
+                Ok(serde_json::json!({"ok": true}))
+            },
+        )));
+
+        let notes = temp.path().join("notes.md");
+        let draft = temp.path().join("draft.md");
+        std::fs::write(&notes, "original").unwrap();
+        let write = |id: &str, path: &Path, content: &str| {
+            StreamChunk::ToolCall(ToolCall {
+                id: id.to_string(),
+                call_type: "function".to_string(),
+                function: FunctionCall {
+                    name: "WriteFile".to_string(),
+                    arguments: serde_json::json!({"path": path, "content": content}).to_string(),
+                },
+            })
+        };
+        let provider = ScriptedProvider::with_chunks([
+            vec![write("1", &notes, "first edit"), write("2", &draft, "draft")],
+            vec![StreamChunk::Text("Done.".to_string())],
+            vec![write("3", &notes, "second edit")],
+            vec![StreamChunk::Text("Done again.".to_string())],
+        ]);
+        let wire = WireSoulSide::new();
+        chat::process_message(&mut soul, &provider, input("Edit the notes"), &wire).await.unwrap();
+        chat::process_message(&mut soul, &provider, input("Edit them again"), &wire).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "second edit");
+
+        assert_eq!(soul.file_edits().len(), 3);
+        let rewind = soul.rewind(1).unwrap();
+        assert_eq!(rewind.restored, vec![notes.clone()]);
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "first edit");
+        assert_eq!(soul.file_edits().len(), 2);
+
+        let rewind = soul.rewind(0).unwrap();
+        assert_eq!(rewind.restored, vec![notes.clone(), draft.clone()]);
+        assert!(rewind.failed.is_empty());
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "original");
+        assert!(!draft.exists());
+        assert!(soul.changed_files().is_empty());
+    }
+
+    #[test]
+    fn test_apply_dmail() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.add_user_message("First");
+        soul.add_user_message("Second");
+
+        soul.apply_dmail(DMail::new(0, "Instead, do this"));
+        assert_eq!(soul.context.message_count(), 1);
+        assert_eq!(soul.context.messages()[0].content, "Instead, do this");
+
+        // Unknown checkpoints fall back to the latest one
+        soul.apply_dmail(DMail::new(7, "And then this"));
+        assert_eq!(soul.context.messages()[0].content, "And then this");
+    }
+
+    #[tokio::test]
+    async fn test_deliver_dmail() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        assert!(soul.deliver_dmail().await.unwrap().is_none());
+
+        soul.add_user_message("First");
+        soul.add_user_message("Second");
+        soul.add_user_message("Third");
+        assert!(soul.rewind_points().iter().all(|c| c.created_at.is_some()));
+        assert_eq!(soul.context.messages_since_checkpoint(1).unwrap().len(), 2);
+
+        soul.denwa_renji.send_dmail(DMail::new(1, "Try again")).await;
+        let (rewind, dmail) = soul.deliver_dmail().await.unwrap().unwrap();
+        assert_eq!(rewind.checkpoint.summary.as_deref(), Some("Second"));
+        assert_eq!(dmail.message, "Try again");
+        // The message is left for the caller to send
+        assert_eq!(soul.context.message_count(), 1);
+        assert!(!soul.denwa_renji.has_pending_dmail().await);
+
+        soul.denwa_renji.send_dmail(DMail::new(5, "")).await;
+        assert!(soul.deliver_dmail().await.unwrap_err().to_string().contains("No checkpoint 6"));
+    }
+}
@@ -9,6 +9,7 @@ use std::sync::Arc;
 // Forward declarations to avoid circular dependencies
 // These will be resolved when the modules are compiled together
 use super::compaction::Compaction;
+use super::custom_commands::CustomCommand;
 use super::kimisoul::{KimiSoul, SoulError};
 
 /// A slash command handler
@@ -23,6 +24,8 @@ pub struct SlashCommand {
     pub description: String,
     /// Handler function
     pub handler: SlashHandler,
+    /// Prompt to send to the agent, for user-defined commands
+    pub prompt: Option<Arc<CustomCommand>>,
 }
 
 impl SlashCommand {
@@ -35,6 +38,21 @@ impl SlashCommand {
             name: name.into(),
             description: description.into(),
             handler: Arc::new(handler),
+            prompt: None,
+        }
+    }
+
+    /// Create a command that sends a user-defined prompt to the agent
+    pub fn custom(command: CustomCommand) -> Self {
+        Self {
+            name: command.name.clone(),
+            description: command.description.clone(),
+            handler: Arc::new(|_soul, _args| {
+                Err(SoulError::SlashCommand(
+                    "Custom commands run as prompts; use KimiSoul::run_custom_command".to_string(),
+                ))
+            }),
+            prompt: Some(Arc::new(command)),
         }
     }
 
@@ -49,6 +67,7 @@ impl std::fmt::Debug for SlashCommand {
         f.debug_struct("SlashCommand")
             .field("name", &self.name)
             .field("description", &self.description)
+            .field("prompt", &self.prompt.as_ref().map(|p| &p.path))
             .finish_non_exhaustive()
     }
 }
@@ -79,6 +98,35 @@ impl SlashCommandRegistry {
         self.commands.insert(command.name.clone(), command);
     }
 
+    /// Register user-defined commands
+    ///
+    /// Built-in commands keep priority; custom commands that would shadow one
+    /// are skipped. Returns the number of commands registered.
+    pub fn register_custom(&mut self, commands: Vec<CustomCommand>) -> usize {
+        let mut registered = 0;
+        for command in commands {
+            if self.get(&command.name).is_some_and(|existing| existing.prompt.is_none()) {
+                tracing::warn!(
+                    "Custom command /{} from {:?} shadows a built-in command; skipping",
+                    command.name,
+                    command.path
+                );
+                continue;
+            }
+            self.register(SlashCommand::custom(command));
+            registered += 1;
+        }
+        registered
+    }
+
+    /// User-defined commands, sorted by name
+    pub fn custom_commands(&self) -> Vec<&CustomCommand> {
+        let mut commands: Vec<&CustomCommand> =
+            self.commands.values().filter_map(|c| c.prompt.as_deref()).collect();
+        commands.sort_by(|a, b| a.name.cmp(&b.name));
+        commands
+    }
+
     /// Get a command by name
     pub fn get(&self, name: &str) -> Option<&SlashCommand> {
         self.commands.get(name)
@@ -138,57 +186,52 @@ impl SlashCommandRegistry {
         ));
 
         // Init command - analyze project and create AGENTS.md
-        // Note: This is a simplified version that creates a template AGENTS.md
-        // A full implementation would spawn a subagent to analyze the codebase
+        // The analysis needs an LLM provider, which sync handlers do not have,
+        // so frontends call `KimiSoul::run_init` directly.
         self.register(SlashCommand::new(
             "init",
             "Analyze the codebase and generate an AGENTS.md file",
-            |soul, _args| {
-                use crate::types::{Message, Role};
-                
-                // Create a template AGENTS.md content
-                let agents_md_content = format!(
-                    "# AGENTS.md\n\n\
-                    This file was generated by the `/init` command.\n\n\
-                    ## Project Overview\n\n\
-                    Project analyzed at: {}\n\n\
-                    ## Build and Test Commands\n\n\
-                    - Build: `cargo build`\n\
-                    - Test: `cargo test`\n\n\
-                    ## Code Style Guidelines\n\n\
-                    Follow Rust best practices and the existing code style.\n",
-                    chrono::Utc::now().to_rfc3339()
-                );
-                
-                // Write AGENTS.md to the current directory using std::fs (synchronous)
-                let work_dir = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-                let agents_md_path = work_dir.join("AGENTS.md");
-                
-                match std::fs::write(&agents_md_path, &agents_md_content) {
-                    Ok(_) => {
-                        // Add a system message to the current context
-                        let system_msg = Message {
-                            role: Role::System,
-                            content: format!(
-                                "The user just ran `/init` slash command. \
-                                 The system has generated an `AGENTS.md` file at: {}\n\n\
-                                 Note: This is a template. Please review and customize it \
-                                 with project-specific details.\n\n\
-                                 Generated content:\n{}",
-                                agents_md_path.display(),
-                                agents_md_content
-                            ),
-                            metadata: None,
-                        };
-                        soul.context.add_message(system_msg);
-                        Ok(())
-                    }
-                    Err(e) => {
-                        Err(SoulError::SlashCommand(format!(
-                            "Failed to write AGENTS.md: {}", e
-                        )))
+            |_soul, _args| {
+                Err(SoulError::SlashCommand(
+                    "/init needs an LLM provider; run it through KimiSoul::run_init".to_string(),
+                ))
+            },
+        ));
+
+        // Agent command - switch to a configured persona
+        // Listing personas is left to frontends, since handlers cannot print.
+        self.register(SlashCommand::new(
+            "agent",
+            "Switch to a configured agent persona (`default` to go back)",
+            |soul, args| {
+                match args.trim() {
+                    "" => {}
+                    "default" => soul.reset_persona(),
+                    name => {
+                        soul.use_persona(name)?;
                     }
                 }
+                Ok(())
+            },
+        ));
+
+        // Rewind command - restore the conversation to a checkpoint
+        // Frontends list the checkpoints and send any revised instruction.
+        self.register(SlashCommand::new(
+            "rewind",
+            "Rewind the conversation to checkpoint n",
+            |soul, args| {
+                let arg = args.split_whitespace().next().unwrap_or("");
+                if arg.is_empty() {
+                    return Ok(());
+                }
+                let n: usize = arg
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| SoulError::SlashCommand(format!("Invalid checkpoint number: {}", arg)))?;
+                soul.rewind(n - 1)?;
+                Ok(())
             },
         ));
 
@@ -264,6 +307,24 @@ mod tests {
         assert!(!registry.contains("test"));
     }
 
+    #[test]
+    fn test_registry_register_custom() {
+        use super::super::custom_commands::CommandScope;
+        use std::path::PathBuf;
+
+        let mut registry = SlashCommandRegistry::with_defaults();
+        let commands = ["review", "compact"]
+            .into_iter()
+            .map(|name| CustomCommand::parse(name, "Do it", PathBuf::new(), CommandScope::User).unwrap())
+            .collect();
+
+        assert_eq!(registry.register_custom(commands), 1);
+        assert!(registry.get("review").unwrap().prompt.is_some());
+        assert!(registry.get("compact").unwrap().prompt.is_none());
+        let names: Vec<&str> = registry.custom_commands().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["review"]);
+    }
+
     #[test]
     fn test_parse_slash_command() {
         assert_eq!(
@@ -0,0 +1,617 @@
+//! Sub-agent orchestration
+//!
+//! A soul can hand tasks to child souls. Each child has its own [`Agent`],
+//! a toolset restricted to the tools it was granted, and a fresh context, so
+//! it sees only its task. Children run concurrently up to a limit; their
+//! events are forwarded to the parent wire wrapped in
+//! [`WireMessage::SubagentEvent`], and their summarized replies are added to
+//! the parent's context once all of them finish. Children snapshot files
+//! into the parent's store, and the changes they make are handed back to the
+//! parent, so `/undo`, `/diff` and rewinding cover them.
+//!
+//! The model spawns sub-agents itself with the [`SpawnAgentTool`]: each call
+//! runs one child to completion and returns its reply as the tool result.
+//! Children cannot spawn sub-agents of their own.
+
+use super::agent::Agent;
+use super::chat;
+use super::compaction::SimpleCompaction;
+use super::denwarenji::DenwaRenji;
+use super::kimisoul::{KimiSoul, SoulError};
+use super::toolset::{KimiToolset, Tool, ToolError, ToolResult};
+use super::undo::FileEdit;
+use super::{system_message, WireSoulSide};
+use crate::context::Context;
+use crate::types::{LoopControl, UserInput};
+use crate::wire::WireMessage;
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use kosong_rs::ChatProvider;
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Default number of sub-agents that run at the same time
+pub const DEFAULT_MAX_CONCURRENT: usize = 4;
+
+/// Replies longer than this are cut down before going back to the parent
+pub const MAX_SUMMARY_CHARS: usize = 2000;
+
+/// Name of the tool the model spawns sub-agents with
+pub const SPAWN_AGENT_TOOL: &str = "SpawnAgent";
+
+/// A task for a sub-agent
+#[derive(Debug, Clone)]
+pub struct SubagentSpec {
+    /// The child's agent; its config sets the iteration limit
+    pub agent: Agent,
+    /// The task, sent as the child's first user message
+    pub prompt: String,
+    /// Tools the child may use; `None` grants the parent's tools allowed
+    /// by the agent's tool profile, or all of them without one
+    pub tools: Option<Vec<String>>,
+}
+
+impl SubagentSpec {
+    /// Create a task for `agent` with all of the parent's tools
+    pub fn new(agent: Agent, prompt: impl Into<String>) -> Self {
+        Self {
+            agent,
+            prompt: prompt.into(),
+            tools: None,
+        }
+    }
+
+    /// Restrict the child to the named tools
+    pub fn with_tools<S: Into<String>>(mut self, tools: impl IntoIterator<Item = S>) -> Self {
+        self.tools = Some(tools.into_iter().map(Into::into).collect());
+        self
+    }
+}
+
+/// Outcome of one sub-agent task
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubagentResult {
+    /// Id used to tag the child's wire events
+    pub task_id: String,
+    /// Name of the child agent
+    pub name: String,
+    /// The child's final reply, or the error that stopped it
+    pub response: Result<String, String>,
+    /// Shortened reply that was reported to the parent
+    pub summary: String,
+}
+
+impl SubagentResult {
+    /// Whether the child failed
+    pub fn is_error(&self) -> bool {
+        self.response.is_err()
+    }
+}
+
+impl KimiSoul {
+    /// Create a child soul for a sub-agent task
+    ///
+    /// The child shares this soul's approval, so tool approvals still reach
+    /// the user, and its snapshot store, but gets a fresh context and only
+    /// the granted tools. A child whose tool profile is unknown gets no
+    /// tools.
+    pub fn spawn_subagent(&self, spec: &SubagentSpec, task_id: &str) -> KimiSoul {
+        let context_file = self
+            .context
+            .context_file()
+            .with_extension(format!("subagent-{}.json", task_id));
+        let mut child = KimiSoul::new(
+            spec.agent.clone(),
+            Context::new(context_file),
+            Arc::clone(&self.approval),
+            Arc::new(DenwaRenji::new()),
+            LoopControl {
+                max_iterations: spec.agent.config().max_iterations,
+                ..self.loop_control.clone()
+            },
+            SimpleCompaction::new(self.compaction.max_tokens),
+        );
+        child.toolset = match (&spec.tools, &spec.agent.config().tool_profile) {
+            (Some(tools), _) => {
+                let names: Vec<&str> = tools.iter().map(String::as_str).collect();
+                self.toolset.subset(&names)
+            }
+            (None, Some(profile)) => self.tool_profiles.apply(profile, &self.toolset).unwrap_or_else(|e| {
+                warn!("Sub-agent {} gets no tools: {}", spec.agent.name, e);
+                KimiToolset::new()
+            }),
+            (None, None) => self.toolset.clone(),
+        };
+        child.tool_profiles = self.tool_profiles.clone();
+        child.toolset.unregister(SPAWN_AGENT_TOOL);
+        child.memory = self.memory.clone();
+        child.prompt_vars = self.prompt_vars.clone();
+        child.workspace = self.workspace.clone();
+        child.snapshots = self.snapshots.clone();
+        child.skill_matcher = None;
+        child
+    }
+
+    /// Run sub-agent tasks, at most `max_concurrent` at a time
+    ///
+    /// Results are returned in the order of `specs`. A failing child does
+    /// not stop the others; its error is reported in its result. Once all
+    /// children finish, their summaries are added to this soul's context
+    /// and each is announced with [`WireMessage::SubagentResult`].
+    pub async fn run_subagents(
+        &mut self,
+        provider: &dyn ChatProvider,
+        specs: Vec<SubagentSpec>,
+        max_concurrent: usize,
+        wire: &WireSoulSide,
+    ) -> Result<Vec<SubagentResult>, SoulError> {
+        let tasks = specs
+            .into_iter()
+            .map(|spec| (Uuid::new_v4().to_string(), spec))
+            .collect();
+        self.run_subagent_tasks(provider, tasks, max_concurrent, wire).await
+    }
+
+    /// Like [`KimiSoul::run_subagents`], with caller-chosen task ids
+    pub(crate) async fn run_subagent_tasks(
+        &mut self,
+        provider: &dyn ChatProvider,
+        tasks: Vec<(String, SubagentSpec)>,
+        max_concurrent: usize,
+        wire: &WireSoulSide,
+    ) -> Result<Vec<SubagentResult>, SoulError> {
+        let children: Vec<(String, SubagentSpec, KimiSoul)> = tasks
+            .into_iter()
+            .map(|(task_id, spec)| {
+                let child = self.spawn_subagent(&spec, &task_id);
+                (task_id, spec, child)
+            })
+            .collect();
+        info!("Running {} sub-agents, {} at a time", children.len(), max_concurrent.max(1));
+
+        let finished: Vec<(SubagentResult, Vec<FileEdit>)> = futures::stream::iter(children)
+            .map(|(task_id, spec, child)| run_child(provider, task_id, spec, child, wire))
+            .buffered(max_concurrent.max(1))
+            .collect()
+            .await;
+        let mut results = Vec::with_capacity(finished.len());
+        for (result, edits) in finished {
+            self.adopt_edits(edits);
+            results.push(result);
+        }
+
+        for result in &results {
+            wire.send(WireMessage::SubagentResult {
+                task_id: result.task_id.clone(),
+                name: result.name.clone(),
+                summary: result.summary.clone(),
+                is_error: result.is_error(),
+            })
+            .await?;
+        }
+        if !results.is_empty() {
+            self.context.add_message(system_message(merge_results(&results)));
+        }
+
+        Ok(results)
+    }
+
+    /// Run the sub-agent a [`SpawnAgentTool`] call asks for
+    ///
+    /// The child is a persona when the call names one, otherwise a copy of
+    /// this soul's agent; either way it gets a fresh context. Its events are
+    /// tagged with the tool call's id, and its reply, shortened to
+    /// [`MAX_SUMMARY_CHARS`], is the tool result.
+    ///
+    /// The future is boxed, as the child's turn runs the agent loop that
+    /// calls this.
+    pub(crate) fn spawn_agent<'a>(
+        &'a mut self,
+        provider: &'a dyn ChatProvider,
+        tool_call_id: &'a str,
+        params: serde_json::Value,
+        wire: &'a WireSoulSide,
+    ) -> BoxFuture<'a, ToolResult> {
+        Box::pin(self.run_spawned_agent(provider, tool_call_id, params, wire))
+    }
+
+    async fn run_spawned_agent(
+        &mut self,
+        provider: &dyn ChatProvider,
+        tool_call_id: &str,
+        params: serde_json::Value,
+        wire: &WireSoulSide,
+    ) -> ToolResult {
+        let params: SpawnAgentParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        let mut spec = match &params.agent {
+            Some(name) => self
+                .agents
+                .spec(name, &self.agent, params.prompt.as_str())
+                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?,
+            None => {
+                let agent = Agent::new("Sub-agent", params.description.as_str())
+                    .with_system_prompt(self.agent.system_prompt.clone())
+                    .with_config(self.agent.config().clone());
+                SubagentSpec::new(agent, params.prompt.as_str())
+            }
+        };
+        if let Some(profile) = params.tool_profile {
+            self.tool_profiles
+                .lookup(&profile)
+                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+            spec.agent.config_mut().tool_profile = Some(profile);
+            spec.tools = None;
+        }
+        if let Some(tools) = params.tools {
+            spec = spec.with_tools(tools);
+        }
+
+        info!("Spawning sub-agent {} for: {}", spec.agent.name, params.description);
+        let child = self.spawn_subagent(&spec, tool_call_id);
+        let (result, edits) = run_child(provider, tool_call_id.to_string(), spec, child, wire).await;
+        self.adopt_edits(edits);
+        wire.send(WireMessage::SubagentResult {
+            task_id: result.task_id.clone(),
+            name: result.name.clone(),
+            summary: result.summary.clone(),
+            is_error: result.is_error(),
+        })
+        .await
+        .map_err(|e| ToolError::Execution(e.to_string()))?;
+        match result.response {
+            Ok(_) => Ok(serde_json::json!(result.summary)),
+            Err(e) => Err(ToolError::Execution(format!("Sub-agent {} failed: {}", result.name, e))),
+        }
+    }
+}
+
+/// Run a child soul's task to completion, reporting its events on `wire`
+/// tagged with `task_id`
+///
+/// The file changes the child made are returned with its result.
+async fn run_child(
+    provider: &dyn ChatProvider,
+    task_id: String,
+    spec: SubagentSpec,
+    mut child: KimiSoul,
+    wire: &WireSoulSide,
+) -> (SubagentResult, Vec<FileEdit>) {
+    let child_wire = wire.for_subagent(&task_id);
+    let input = UserInput {
+        text: spec.prompt.clone(),
+        attachments: Vec::new(),
+    };
+    let max_iterations = child.loop_control.max_iterations;
+    let response = match child_wire.send(WireMessage::TurnBegin { user_input: input.clone() }).await {
+        Ok(()) => chat::process_message_with_limit(&mut child, provider, input, &child_wire, max_iterations)
+            .await
+            .map_err(|e| e.to_string()),
+        Err(e) => Err(e.to_string()),
+    };
+    if let Err(e) = &response {
+        warn!("Sub-agent {} failed: {}", spec.agent.name, e);
+    }
+    // The child's own turn is over even if it failed
+    let _ = child_wire.send(WireMessage::TurnEnd).await;
+
+    let summary = match &response {
+        Ok(reply) => summarize(reply, MAX_SUMMARY_CHARS),
+        Err(e) => format!("Failed: {}", e),
+    };
+    let result = SubagentResult {
+        task_id,
+        name: spec.agent.name.clone(),
+        response,
+        summary,
+    };
+    (result, std::mem::take(&mut child.edits))
+}
+
+/// Parameters of a [`SpawnAgentTool`] call
+#[derive(Debug, Deserialize)]
+struct SpawnAgentParams {
+    description: String,
+    prompt: String,
+    #[serde(default)]
+    agent: Option<String>,
+    #[serde(default)]
+    tools: Option<Vec<String>>,
+    #[serde(default)]
+    tool_profile: Option<String>,
+}
+
+/// Lets the model hand a task to a sub-agent
+///
+/// The tool only describes itself; calls are run by the agent loop with
+/// [`KimiSoul::spawn_agent`], as they need the soul and its provider.
+#[derive(Debug, Default)]
+pub struct SpawnAgentTool;
+
+impl SpawnAgentTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for SpawnAgentTool {
+    fn name(&self) -> &str {
+        SPAWN_AGENT_TOOL
+    }
+
+    fn description(&self) -> &str {
+        "Hand a self-contained task to a sub-agent and get its final answer back. \
+         The sub-agent starts with a fresh context, without this conversation, so \
+         the prompt must say everything it needs to know. Use it for research or \
+         edits that would otherwise fill the context with intermediate steps."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "description": {
+                    "type": "string",
+                    "description": "A short (3-5 word) description of the task"
+                },
+                "prompt": {
+                    "type": "string",
+                    "description": "The complete task for the sub-agent"
+                },
+                "agent": {
+                    "type": "string",
+                    "description": "A configured agent to run the task, instead of a copy of this one"
+                },
+                "tools": {
+                    "type": "array",
+                    "items": {"type": "string"},
+                    "description": "Tools the sub-agent may use; all of this agent's tools by default"
+                },
+                "tool_profile": {
+                    "type": "string",
+                    "description": "A tool profile to limit the sub-agent to, e.g. 'read-only' or 'web-only'"
+                }
+            },
+            "required": ["description", "prompt"]
+        })
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> ToolResult {
+        Err(ToolError::Execution(format!("{} only runs in the agent loop", SPAWN_AGENT_TOOL)))
+    }
+}
+
+/// Cut `text` down to at most `max_chars` characters, noting how much was left out
+pub fn summarize(text: &str, max_chars: usize) -> String {
+    let text = text.trim();
+    match text.char_indices().nth(max_chars) {
+        Some((cut, _)) => format!(
+            "{}\n[... {} more characters]",
+            text[..cut].trim_end(),
+            text[cut..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+/// The context note that reports sub-agent results to the parent
+fn merge_results(results: &[SubagentResult]) -> String {
+    let mut note = format!("{} sub-agent task(s) finished:", results.len());
+    for result in results {
+        let status = if result.is_error() { "failed" } else { "done" };
+        note.push_str(&format!("\n\n## {} ({})\n\n{}", result.name, status, result.summary));
+    }
+    note
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::agent::AgentConfig;
+    use crate::soul::testing::{test_soul, ScriptedProvider};
+    use crate::soul::toolset::SimpleTool;
+    use tokio::sync::mpsc;
+
+    fn create_test_soul(dir: &std::path::Path) -> KimiSoul {
+        let mut soul = test_soul(dir);
+        soul.agent = Agent::new("Parent", "The parent agent");
+        for name in ["ReadFile", "WriteFile", "Shell"] {
+            soul.register_tool(Arc::new(SimpleTool::new(
+                name,
+                "A test tool",
+                serde_json::json!({"type": "object"}),
+                |_params| Ok(serde_json::json!({})),
+            )));
+        }
+        soul
+    }
+
+    #[test]
+    fn test_spawn_subagent_is_isolated() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut parent = create_test_soul(temp.path());
+        parent.context.add_message(system_message("parent history"));
+
+        let agent = Agent::new("Reader", "Reads files").with_config(AgentConfig {
+            max_iterations: 7,
+            ..AgentConfig::default()
+        });
+        let spec = SubagentSpec::new(agent, "Read the README").with_tools(["ReadFile"]);
+        let child = parent.spawn_subagent(&spec, "task-1");
+
+        assert_eq!(child.agent.name, "Reader");
+        assert_eq!(child.context.message_count(), 0);
+        assert_ne!(child.context.context_file(), parent.context.context_file());
+        assert_eq!(child.toolset.tool_count(), 1);
+        assert_eq!(child.loop_control.max_iterations, 7);
+
+        // Without a tool list, the agent's tool profile decides
+        let agent = Agent::new("Planner", "Plans").with_config(AgentConfig {
+            tool_profile: Some("read-only".to_string()),
+            ..AgentConfig::default()
+        });
+        let child = parent.spawn_subagent(&SubagentSpec::new(agent, "Plan it"), "task-2");
+        assert_eq!(child.toolset.tool_names().collect::<Vec<_>>(), vec!["ReadFile"]);
+    }
+
+    #[tokio::test]
+    async fn test_run_subagents_merges_results() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut parent = create_test_soul(temp.path());
+        // One at a time, so the scripted replies line up with the specs
+        let provider = ScriptedProvider::new(["Found 3 TODOs."]);
+        let (tx, mut rx) = mpsc::channel(64);
+        let wire = WireSoulSide::with_sender(tx);
+
+        let specs = vec![
+            SubagentSpec::new(Agent::new("Scanner", "Finds TODOs"), "List the TODOs").with_tools(["ReadFile"]),
+            SubagentSpec::new(Agent::new("Broken", "Has no reply"), "Do something"),
+        ];
+        let results = parent.run_subagents(&provider, specs, 1, &wire).await.unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].response, Ok("Found 3 TODOs.".to_string()));
+        assert!(results[1].is_error());
+        assert_eq!(provider.requests()[0].tools, vec!["ReadFile"]);
+        assert_eq!(provider.requests()[0].last_text(), "List the TODOs");
+
+        assert_eq!(parent.context.message_count(), 1);
+        let note = &parent.context.messages()[0].content;
+        assert!(note.starts_with("2 sub-agent task(s) finished:"));
+        assert!(note.contains("## Scanner (done)\n\nFound 3 TODOs."));
+        assert!(note.contains("## Broken (failed)"));
+
+        drop(wire);
+        let mut wrapped = 0;
+        let mut reported = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::SubagentEvent { task_tool_call_id, .. } => {
+                    assert!(results.iter().any(|r| r.task_id == task_tool_call_id));
+                    wrapped += 1;
+                }
+                WireMessage::SubagentResult { name, is_error, .. } => reported.push((name, is_error)),
+                other => panic!("unexpected unwrapped message {:?}", other),
+            }
+        }
+        assert!(wrapped >= 5);
+        assert_eq!(reported, vec![("Scanner".to_string(), false), ("Broken".to_string(), true)]);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_agent_tool() {
+        use kosong_rs::{StreamChunk, ToolCall};
+
+        let temp = tempfile::tempdir().unwrap();
+        let mut parent = create_test_soul(temp.path());
+        parent.register_tool(Arc::new(SpawnAgentTool::new()));
+        let spawn = |id: &str, arguments: serde_json::Value| {
+            vec![StreamChunk::ToolCall(ToolCall::new(id, SPAWN_AGENT_TOOL, &arguments.to_string()))]
+        };
+        let provider = ScriptedProvider::with_chunks([
+            spawn(
+                "call_1",
+                serde_json::json!({"description": "Find TODOs", "prompt": "List the TODOs", "tools": ["ReadFile", SPAWN_AGENT_TOOL]}),
+            ),
+            vec![StreamChunk::Text("Found 3 TODOs.".to_string())],
+            spawn("call_2", serde_json::json!({"description": "Review", "prompt": "Review it", "agent": "reviewer"})),
+            vec![StreamChunk::Text("There are 3 TODOs.".to_string())],
+        ]);
+        let (tx, mut rx) = mpsc::channel(256);
+        let wire = WireSoulSide::with_sender(tx);
+        let input = UserInput { text: "How many TODOs?".to_string(), attachments: Vec::new() };
+        let reply = chat::process_message(&mut parent, &provider, input, &wire).await.unwrap();
+        assert_eq!(reply, "There are 3 TODOs.");
+
+        // The child saw only its task and the tools it was given, less SpawnAgent
+        let requests = provider.requests();
+        assert_eq!(requests[1].last_text(), "List the TODOs");
+        assert_eq!(requests[1].tools, vec!["ReadFile"]);
+        assert!(requests[0].tools.contains(&SPAWN_AGENT_TOOL.to_string()));
+
+        let results: Vec<&str> = parent
+            .context
+            .messages()
+            .iter()
+            .filter(|m| matches!(m.role, crate::types::Role::Tool))
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(results[0], "\"Found 3 TODOs.\"");
+        assert!(results[1].contains("Unknown agent 'reviewer'"));
+
+        drop(wire);
+        let mut tagged = false;
+        let mut reported = Vec::new();
+        while let Some(message) = rx.recv().await {
+            match message {
+                WireMessage::SubagentEvent { task_tool_call_id, .. } => tagged |= task_tool_call_id == "call_1",
+                WireMessage::SubagentResult { task_id, is_error, .. } => reported.push((task_id, is_error)),
+                _ => {}
+            }
+        }
+        assert!(tagged);
+        assert_eq!(reported, vec![("call_1".to_string(), false)]);
+    }
+
+    #[tokio::test]
+    async fn test_subagent_edits_can_be_undone() {
+        use kosong_rs::{StreamChunk, ToolCall};
+
+        let temp = tempfile::tempdir().unwrap();
+        let notes = temp.path().join("notes.md");
+        std::fs::write(&notes, "original").unwrap();
+        let mut parent = test_soul(temp.path());
+        parent.snapshots = Some(crate::snapshot::SnapshotStore::for_work_dir(temp.path()));
+        parent.register_tool(Arc::new(SimpleTool::new(
+            "WriteFile",
+            "A test tool",
+            serde_json::json!({"type": "object"}),
+            |params| {
+                std::fs::write(params["path"].as_str().unwrap(), params["content"].as_str().unwrap()).unwrap();
+                Ok(serde_json::json!({}))
+            },
+        )));
+        let arguments = serde_json::json!({"path": notes, "content": "edited"}).to_string();
+        let provider = ScriptedProvider::with_chunks([
+            vec![StreamChunk::ToolCall(ToolCall::new("call_1", "WriteFile", &arguments))],
+            vec![StreamChunk::Text("Edited the notes.".to_string())],
+        ]);
+
+        let spec = SubagentSpec::new(Agent::new("Editor", "Edits files"), "Edit the notes");
+        parent.run_subagents(&provider, vec![spec], 1, &WireSoulSide::new()).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "edited");
+        assert_eq!(parent.changed_files(), [notes.as_path()]);
+
+        let undo = parent.undo(1);
+        assert_eq!(undo.restored, [notes.as_path()]);
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "original");
+    }
+
+    #[tokio::test]
+    async fn test_nested_subagent_wire() {
+        let (tx, mut rx) = mpsc::channel(4);
+        let wire = WireSoulSide::with_sender(tx).for_subagent("outer").for_subagent("inner");
+        wire.send(WireMessage::TurnEnd).await.unwrap();
+
+        let WireMessage::SubagentEvent { task_tool_call_id, event } = rx.recv().await.unwrap() else {
+            panic!("expected a subagent event");
+        };
+        assert_eq!(task_tool_call_id, "outer");
+        assert!(matches!(
+            *event,
+            WireMessage::SubagentEvent { ref task_tool_call_id, ref event }
+                if task_tool_call_id == "inner" && matches!(**event, WireMessage::TurnEnd)
+        ));
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(summarize("  short  ", 10), "short");
+        assert_eq!(summarize("abcdefghij", 4), "abcd\n[... 6 more characters]");
+        assert_eq!(summarize("héllo", 2), "hé\n[... 3 more characters]");
+    }
+}
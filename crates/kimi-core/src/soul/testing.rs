@@ -0,0 +1,135 @@
+//! Test helpers for driving the soul without a real LLM
+
+use crate::approval::Approval;
+use crate::context::Context;
+use crate::soul::agent::Agent;
+use crate::soul::compaction::SimpleCompaction;
+use crate::soul::denwarenji::DenwaRenji;
+use crate::soul::KimiSoul;
+use crate::types::LoopControl;
+use async_trait::async_trait;
+use futures::StreamExt;
+use kosong_rs::chat_provider::ToolDefinition;
+use kosong_rs::{ChatError, ChatProvider, GenerateStream, Message, ModelCapability, StreamChunk, ThinkingEffort};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A yolo-mode soul whose context lives in `dir`
+pub fn test_soul(dir: &Path) -> KimiSoul {
+    KimiSoul::new(
+        Agent::new("Kimi", "Default agent"),
+        Context::new(dir.join("context.json")),
+        Arc::new(Approval::yolo()),
+        Arc::new(DenwaRenji::new()),
+        LoopControl::default(),
+        SimpleCompaction::new(8000),
+    )
+}
+
+/// A request seen by [`ScriptedProvider`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub system_prompt: Option<String>,
+    pub messages: Vec<Message>,
+    pub tools: Vec<String>,
+}
+
+impl RecordedRequest {
+    /// Text of the last message in the request
+    pub fn last_text(&self) -> String {
+        self.messages.last().and_then(|m| m.text()).unwrap_or_default()
+    }
+}
+
+/// Provider that replays scripted responses, one per request
+pub struct ScriptedProvider {
+    replies: Mutex<VecDeque<Vec<StreamChunk>>>,
+    requests: Mutex<Vec<RecordedRequest>>,
+    /// Status every request fails with, if any
+    failure: Option<u16>,
+    /// Whether streams stay open after their chunks
+    stalling: bool,
+}
+
+impl ScriptedProvider {
+    /// Reply to each request with the next text
+    pub fn new<S: Into<String>>(replies: impl IntoIterator<Item = S>) -> Self {
+        Self::with_chunks(replies.into_iter().map(|r| vec![StreamChunk::Text(r.into())]))
+    }
+
+    /// Reply to each request with the next list of chunks
+    pub fn with_chunks(replies: impl IntoIterator<Item = Vec<StreamChunk>>) -> Self {
+        Self {
+            replies: Mutex::new(replies.into_iter().collect()),
+            requests: Mutex::new(Vec::new()),
+            failure: None,
+            stalling: false,
+        }
+    }
+
+    /// Fail every request with an API error of the given status
+    pub fn failing(status: u16) -> Self {
+        Self {
+            failure: Some(status),
+            ..Self::new(Vec::<String>::new())
+        }
+    }
+
+    /// Keep each stream open after its chunks, like a model that is still
+    /// generating
+    pub fn stalling(self) -> Self {
+        Self { stalling: true, ..self }
+    }
+
+    /// Requests received so far
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl ChatProvider for ScriptedProvider {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        self.requests.lock().unwrap().push(RecordedRequest {
+            system_prompt: system_prompt.map(str::to_string),
+            messages: messages.to_vec(),
+            tools: tools
+                .unwrap_or_default()
+                .iter()
+                .map(|t| t.function.name.clone())
+                .collect(),
+        });
+        if let Some(status) = self.failure {
+            return Err(ChatError::Api { status, message: "scripted failure".to_string() });
+        }
+        let chunks = self
+            .replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(ChatError::StreamEnded)?;
+        let stream = futures::stream::iter(chunks.into_iter().map(Ok));
+        match self.stalling {
+            true => Ok(Box::pin(stream.chain(futures::stream::pending()))),
+            false => Ok(Box::pin(stream)),
+        }
+    }
+
+    fn model_name(&self) -> &str {
+        "scripted"
+    }
+
+    fn with_thinking(&self, _effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        unimplemented!("scripted provider has no thinking modes")
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        &[]
+    }
+}
@@ -0,0 +1,143 @@
+//! Tool profiles
+//!
+//! A tool profile is a named subset of the toolset, selected for the session
+//! with `--tools <profile>` or for an agent with its `tool_profile`. Three
+//! are built in: `full` with every tool, `read-only` with the tools plan
+//! mode allows, and `web-only` for searching and fetching. The
+//! `[tool_profiles]` table adds profiles or redefines the built-in ones.
+
+use super::kimisoul::SoulError;
+use super::toolset::KimiToolset;
+use crate::approval::READ_ONLY_TOOLS;
+use crate::config::Config;
+use std::collections::HashMap;
+
+/// Entry of a profile that matches every tool
+pub const ALL_TOOLS: &str = "*";
+
+/// The profiles every config has
+const BUILTIN_PROFILES: &[(&str, &[&str])] = &[
+    ("full", &[ALL_TOOLS]),
+    ("read-only", READ_ONLY_TOOLS),
+    ("web-only", &["SearchWeb", "FetchURL"]),
+];
+
+/// Named tool subsets, by profile name
+#[derive(Debug, Clone)]
+pub struct ToolProfiles {
+    profiles: HashMap<String, Vec<String>>,
+}
+
+impl ToolProfiles {
+    /// The built-in profiles, with `configured` ones added or replacing them
+    pub fn new(configured: &HashMap<String, Vec<String>>) -> Self {
+        let mut profiles: HashMap<String, Vec<String>> = BUILTIN_PROFILES
+            .iter()
+            .map(|(name, tools)| (name.to_string(), tools.iter().map(|t| t.to_string()).collect()))
+            .collect();
+        profiles.extend(configured.iter().map(|(name, tools)| (name.clone(), tools.clone())));
+        Self { profiles }
+    }
+
+    /// The built-in profiles and those under `[tool_profiles]` in `config`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.tool_profiles)
+    }
+
+    /// Whether a profile is built in; such a name is always defined
+    pub fn is_builtin(name: &str) -> bool {
+        BUILTIN_PROFILES.iter().any(|(builtin, _)| *builtin == name)
+    }
+
+    /// Profile names, sorted
+    pub fn names(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Tools of the profile `name`
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.profiles.get(name).map(Vec::as_slice)
+    }
+
+    /// The tools of `toolset` the profile `name` allows
+    ///
+    /// MCP servers stay registered, so they are still listed, but only the
+    /// tools of theirs the profile names are kept.
+    pub fn apply(&self, name: &str, toolset: &KimiToolset) -> Result<KimiToolset, SoulError> {
+        let tools = self.lookup(name)?;
+        let mut toolset = toolset.clone();
+        toolset.retain(|tool| tools.iter().any(|t| t == ALL_TOOLS || t == tool));
+        Ok(toolset)
+    }
+
+    /// Like [`get`](Self::get), failing with the available profiles when
+    /// `name` is unknown
+    pub fn lookup(&self, name: &str) -> Result<&[String], SoulError> {
+        self.get(name).ok_or_else(|| {
+            SoulError::Tool(format!(
+                "Unknown tool profile '{}' (available: {})",
+                name,
+                self.names().join(", ")
+            ))
+        })
+    }
+}
+
+impl Default for ToolProfiles {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::soul::toolset::SimpleTool;
+    use std::sync::Arc;
+
+    fn toolset() -> KimiToolset {
+        let mut toolset = KimiToolset::new();
+        for name in ["ReadFile", "WriteFile", "Shell", "SearchWeb", "FetchURL"] {
+            toolset.register(Arc::new(SimpleTool::new(
+                name,
+                "A test tool",
+                serde_json::json!({"type": "object"}),
+                |_params| Ok(serde_json::json!({})),
+            )));
+        }
+        toolset
+    }
+
+    fn names(toolset: &KimiToolset) -> Vec<String> {
+        let mut names: Vec<String> = toolset.tool_names().cloned().collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_builtin_profiles() {
+        let profiles = ToolProfiles::default();
+        assert_eq!(profiles.names(), vec!["full", "read-only", "web-only"]);
+        assert_eq!(profiles.apply("full", &toolset()).unwrap().tool_count(), 5);
+        assert_eq!(names(&profiles.apply("read-only", &toolset()).unwrap()), ["FetchURL", "ReadFile", "SearchWeb"]);
+        assert_eq!(names(&profiles.apply("web-only", &toolset()).unwrap()), ["FetchURL", "SearchWeb"]);
+
+        let err = profiles.apply("coding", &toolset()).unwrap_err().to_string();
+        assert!(err.contains("Unknown tool profile 'coding' (available: full, read-only, web-only)"), "{}", err);
+    }
+
+    #[test]
+    fn test_configured_profiles() {
+        let configured = HashMap::from([
+            ("coding".to_string(), vec!["ReadFile".to_string(), "WriteFile".to_string(), "Missing".to_string()]),
+            ("web-only".to_string(), vec!["FetchURL".to_string()]),
+        ]);
+        let profiles = ToolProfiles::new(&configured);
+        assert_eq!(names(&profiles.apply("coding", &toolset()).unwrap()), ["ReadFile", "WriteFile"]);
+        assert_eq!(names(&profiles.apply("web-only", &toolset()).unwrap()), ["FetchURL"]);
+        assert!(ToolProfiles::is_builtin("web-only"));
+        assert!(!ToolProfiles::is_builtin("coding"));
+    }
+}
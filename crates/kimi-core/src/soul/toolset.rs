@@ -3,6 +3,8 @@
 //! Provides tool registration, schema generation, and execution capabilities
 //! with support for both built-in tools and MCP (Model Context Protocol) servers.
 
+use crate::mcp::{McpClient, McpError, McpTool, CONNECT_TIMEOUT};
+use crate::wire::OutputStream;
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -10,7 +12,8 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::Arc;
 use thiserror::Error;
-use tracing::{debug, info};
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
 
 /// Errors that can occur during tool execution
 #[derive(Debug, Error, Clone)]
@@ -43,9 +46,50 @@ impl ToolError {
     }
 }
 
+/// Lets tools parse typed parameters with `ToolSchema::from_params(params)?`
+impl From<kosong_rs::tooling::ToolError> for ToolError {
+    fn from(error: kosong_rs::tooling::ToolError) -> Self {
+        use kosong_rs::tooling::ToolError as Kosong;
+        match error {
+            Kosong::InvalidParameters(message) => ToolError::InvalidParameters(message),
+            Kosong::NotFound(name) => ToolError::NotFound(name),
+            Kosong::Timeout => ToolError::Timeout,
+            Kosong::Execution(message) | Kosong::Other(message) => ToolError::Execution(message),
+        }
+    }
+}
+
 /// Result type for tool execution
 pub type ToolResult = Result<Value, ToolError>;
 
+/// Where a running tool sends output as it is produced, for showing it
+/// live; the complete output is still the tool's result
+#[derive(Debug, Clone, Default)]
+pub struct OutputSink {
+    sender: Option<mpsc::UnboundedSender<(OutputStream, String)>>,
+}
+
+impl OutputSink {
+    /// A sink and the receiver of what is sent to it
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<(OutputStream, String)>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self { sender: Some(sender) }, receiver)
+    }
+
+    /// A sink that drops everything, for running tools without showing
+    /// their output
+    pub fn discard() -> Self {
+        Self::default()
+    }
+
+    /// Send `text` from `stream`; dropped if no one is receiving
+    pub fn send(&self, stream: OutputStream, text: impl Into<String>) {
+        if let Some(sender) = &self.sender {
+            let _ = sender.send((stream, text.into()));
+        }
+    }
+}
+
 /// Tool trait for implementing tools
 #[async_trait]
 pub trait Tool: Send + Sync + Debug {
@@ -60,6 +104,13 @@ pub trait Tool: Send + Sync + Debug {
 
     /// Execute the tool with the given parameters
     async fn execute(&self, params: Value) -> ToolResult;
+
+    /// Execute the tool, sending output to `output` while it runs; tools
+    /// that have nothing to show before they finish need not override it
+    async fn execute_streaming(&self, params: Value, output: OutputSink) -> ToolResult {
+        let _ = output;
+        self.execute(params).await
+    }
 }
 
 /// Information about an MCP server
@@ -73,11 +124,20 @@ pub struct McpServerInfo {
     pub args: Vec<String>,
     /// Environment variables
     pub env: Option<HashMap<String, String>>,
+    /// Tools of the server to offer; `None` offers all of them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled_tools: Option<Vec<String>>,
     /// Available tools from this server
     pub tools: Vec<String>,
     /// Whether the server is connected
     #[serde(skip)]
     pub connected: bool,
+    /// Why connecting to the server, or listing its tools, failed
+    #[serde(skip)]
+    pub error: Option<String>,
+    /// Connection to the running server
+    #[serde(skip)]
+    client: Option<Arc<McpClient>>,
 }
 
 impl McpServerInfo {
@@ -88,8 +148,11 @@ impl McpServerInfo {
             command: command.into(),
             args: Vec::new(),
             env: None,
+            enabled_tools: None,
             tools: Vec::new(),
             connected: false,
+            error: None,
+            client: None,
         }
     }
 
@@ -104,6 +167,21 @@ impl McpServerInfo {
         self.env = Some(env);
         self
     }
+
+    /// Offer only the named tools of the server
+    pub fn with_enabled_tools(mut self, tools: Vec<String>) -> Self {
+        self.enabled_tools = Some(tools);
+        self
+    }
+
+    /// The connection to the server, while connected
+    pub fn client(&self) -> Option<&Arc<McpClient>> {
+        self.client.as_ref()
+    }
+
+    fn offers(&self, tool: &str) -> bool {
+        self.enabled_tools.as_ref().is_none_or(|enabled| enabled.iter().any(|name| name == tool))
+    }
 }
 
 /// The main toolset for managing and executing tools
@@ -158,6 +236,15 @@ impl KimiToolset {
         tool
     }
 
+    /// Keep only the tools whose names `keep` accepts
+    pub fn retain(&mut self, mut keep: impl FnMut(&str) -> bool) {
+        let count = self.tools.len();
+        self.tools.retain(|name, _| keep(name));
+        if self.tools.len() != count {
+            self.refresh_schemas();
+        }
+    }
+
     /// Get a tool by name
     pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
         self.tools.get(name).cloned()
@@ -178,6 +265,17 @@ impl KimiToolset {
         tool.execute(params).await
     }
 
+    /// Like [`execute`](Self::execute), with the tool sending output to
+    /// `output` while it runs
+    pub async fn execute_streaming(&self, name: &str, params: Value, output: OutputSink) -> ToolResult {
+        debug!("Executing tool: {} with params: {:?}", name, params);
+
+        let tool = self.tools.get(name)
+            .ok_or_else(|| ToolError::NotFound(name.to_string()))?;
+
+        tool.execute_streaming(params, output).await
+    }
+
     /// Get all tool schemas
     pub fn schemas(&self) -> &[Value] {
         &self.schemas
@@ -193,6 +291,17 @@ impl KimiToolset {
         self.tools.len()
     }
 
+    /// Create a toolset containing only the named tools that are registered here
+    pub fn subset(&self, names: &[&str]) -> Self {
+        let mut toolset = Self::new();
+        for name in names {
+            if let Some(tool) = self.get(name) {
+                toolset.register(tool);
+            }
+        }
+        toolset
+    }
+
     /// Register an MCP server
     pub fn register_mcp_server(&mut self, server: McpServerInfo) {
         info!("Registering MCP server: {}", server.name);
@@ -214,9 +323,96 @@ impl KimiToolset {
         self.mcp_servers.contains_key(name)
     }
 
-    /// Remove an MCP server
+    /// Remove an MCP server, with its tools
     pub fn remove_mcp_server(&mut self, name: &str) -> Option<McpServerInfo> {
-        self.mcp_servers.remove(name)
+        let server = self.mcp_servers.remove(name)?;
+        for tool in &server.tools {
+            self.unregister(tool);
+        }
+        Some(server)
+    }
+
+    /// Start the MCP servers, registering their tools
+    ///
+    /// The servers are started concurrently. A server that fails to start
+    /// is registered without tools, with the error kept for `/mcp`. A tool
+    /// whose name is already taken is left out.
+    pub async fn connect_mcp_servers(&mut self, servers: Vec<McpServerInfo>) {
+        let connections = futures::future::join_all(servers.into_iter().map(|server| async move {
+            let connection = connect(&server).await;
+            (server, connection)
+        }))
+        .await;
+        for (mut server, connection) in connections {
+            match connection {
+                Ok((client, tools)) => {
+                    info!("Connected to MCP server {} with {} tool(s)", server.name, tools.len());
+                    let name = server.name.clone();
+                    server.client = Some(client);
+                    self.register_mcp_server(server);
+                    self.update_mcp_tools(&name, tools);
+                }
+                Err(e) => {
+                    warn!("Failed to connect to MCP server {}: {}", server.name, e);
+                    server.error = Some(e.to_string());
+                    self.register_mcp_server(server);
+                }
+            }
+        }
+    }
+
+    /// List the tools of the connected MCP servers again, registering new
+    /// ones and dropping removed ones, and mark servers that have exited
+    pub async fn refresh_mcp_servers(&mut self) {
+        let clients: Vec<(String, Arc<McpClient>)> = self
+            .mcp_servers
+            .values()
+            .filter_map(|server| Some((server.name.clone(), server.client.clone()?)))
+            .collect();
+        for (name, client) in clients {
+            match client.list_tools().await {
+                Ok(tools) => {
+                    let tools = tools.into_iter().map(|info| McpTool::new(client.clone(), info)).collect();
+                    self.update_mcp_tools(&name, tools);
+                }
+                Err(e) => {
+                    warn!("Failed to list the tools of MCP server {}: {}", name, e);
+                    let server = self.mcp_servers.get_mut(&name).expect("server is registered");
+                    if !client.is_connected() {
+                        server.connected = false;
+                        server.client = None;
+                    }
+                    server.error = Some(e.to_string());
+                }
+            }
+        }
+    }
+
+    /// Replace the registered tools of the MCP server `name` with `tools`,
+    /// leaving out those it does not offer or whose names are taken
+    fn update_mcp_tools(&mut self, name: &str, tools: Vec<McpTool>) {
+        let Some(server) = self.mcp_servers.get(name) else {
+            return;
+        };
+        let previous = server.tools.clone();
+        let tools: Vec<McpTool> = tools.into_iter().filter(|tool| server.offers(tool.name())).collect();
+        for tool in &previous {
+            self.unregister(tool);
+        }
+        let mut registered = Vec::new();
+        for tool in tools {
+            let tool_name = tool.name().to_string();
+            if self.contains(&tool_name) {
+                warn!("MCP server {}: a tool named {} already exists; leaving it out", name, tool_name);
+                continue;
+            }
+            self.register(Arc::new(tool));
+            registered.push(tool_name);
+        }
+        let server = self.mcp_servers.get_mut(name).expect("server is registered");
+        server.tools = registered;
+        server.connected = true;
+        server.error = None;
     }
 
     /// Refresh the schemas cache
@@ -243,6 +439,16 @@ impl KimiToolset {
     }
 }
 
+/// Start an MCP server and list its tools
+async fn connect(server: &McpServerInfo) -> Result<(Arc<McpClient>, Vec<McpTool>), McpError> {
+    let client = Arc::new(McpClient::connect(server).await?);
+    let tools = tokio::time::timeout(CONNECT_TIMEOUT, client.list_tools())
+        .await
+        .map_err(|_| McpError::Timeout(CONNECT_TIMEOUT))??;
+    let tools = tools.into_iter().map(|info| McpTool::new(client.clone(), info)).collect();
+    Ok((client, tools))
+}
+
 impl Default for KimiToolset {
     fn default() -> Self {
         Self::new()
@@ -441,6 +647,25 @@ mod tests {
         assert!(matches!(result.unwrap_err(), ToolError::NotFound(_)));
     }
 
+    #[test]
+    fn test_toolset_subset() {
+        let mut toolset = KimiToolset::new();
+        for name in ["ReadFile", "WriteFile", "Glob"] {
+            toolset.register(Arc::new(SimpleTool::new(
+                name,
+                "A test tool",
+                serde_json::json!({"type": "object"}),
+                |_params| Ok(serde_json::json!({})),
+            )));
+        }
+
+        let subset = toolset.subset(&["ReadFile", "Glob", "Missing"]);
+        assert_eq!(subset.tool_count(), 2);
+        assert!(subset.contains("ReadFile"));
+        assert!(!subset.contains("WriteFile"));
+        assert_eq!(subset.schemas().len(), 2);
+    }
+
     #[tokio::test]
     async fn test_toolset_unregister() {
         let mut toolset = KimiToolset::new();
@@ -515,4 +740,38 @@ mod tests {
         assert!(retrieved.is_some());
         assert_eq!(retrieved.unwrap().name, "test-server");
     }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_connect_mcp_servers() {
+        use crate::mcp::tests::fake_server;
+
+        let mut toolset = KimiToolset::new();
+        toolset.register(Arc::new(SimpleTool::new("echo", "Built in", serde_json::json!({}), |_| Ok(Value::Null))));
+        let missing = McpServerInfo::new("missing", "/nonexistent/mcp-server");
+        toolset.connect_mcp_servers(vec![fake_server(), missing]).await;
+
+        // The built-in tool keeps its name
+        let fake = toolset.get_mcp_server("fake").unwrap();
+        assert!(fake.connected);
+        assert!(fake.tools.is_empty());
+        assert_eq!(toolset.get("echo").unwrap().description(), "Built in");
+        let missing = toolset.get_mcp_server("missing").unwrap();
+        assert!(!missing.connected);
+        assert!(missing.error.as_ref().unwrap().contains("/nonexistent/mcp-server"));
+
+        toolset.unregister("echo");
+        toolset.refresh_mcp_servers().await;
+        assert_eq!(toolset.get_mcp_server("fake").unwrap().tools, ["echo"]);
+        let result = toolset.execute("echo", serde_json::json!({"text": "hi"})).await.unwrap();
+        assert_eq!(result, serde_json::json!("hi"));
+
+        toolset.remove_mcp_server("fake");
+        assert!(!toolset.contains("echo"));
+
+        // Tools the server is not set to offer are left out
+        toolset.connect_mcp_servers(vec![fake_server().with_enabled_tools(Vec::new())]).await;
+        assert!(toolset.get_mcp_server("fake").unwrap().connected);
+        assert_eq!(toolset.tool_count(), 0);
+    }
 }
@@ -0,0 +1,367 @@
+//! Undoing file changes
+//!
+//! Every change a tool makes to a file is recorded with a snapshot of the
+//! file before it. [`KimiSoul::undo`] puts the most recent changes back,
+//! newest first, leaving the conversation as it is; `/undo` and the
+//! [`UndoEditTool`] are built on it. [`KimiSoul::edits_diff`] shows the net
+//! effect of the recorded changes for `/diff`. Changes are only recorded
+//! when the soul has a [`SnapshotStore`].
+//!
+//! In a session, the record is kept in the session's
+//! [`edits_file`](crate::session::Session::edits_file), one change per
+//! line, so changes can still be undone after the session is resumed.
+//!
+//! [`SnapshotStore`]: crate::snapshot::SnapshotStore
+
+use super::kimisoul::KimiSoul;
+use super::toolset::{Tool, ToolError, ToolResult};
+use crate::diff::unified_diff;
+use crate::snapshot::FileSnapshot;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Name of the [`UndoEditTool`]
+pub const UNDO_EDIT_TOOL: &str = "UndoEdit";
+
+/// Lines of context around each change in [`KimiSoul::edits_diff`]
+const DIFF_CONTEXT: usize = 3;
+
+/// A change a tool made to a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileEdit {
+    /// Tool that made the change
+    pub tool: String,
+    /// The file before the change
+    pub before: FileSnapshot,
+    /// Index of the checkpoint the change was made after
+    pub checkpoint: usize,
+}
+
+/// What an undo put back
+#[derive(Debug, Clone, Default)]
+pub struct Undo {
+    /// Number of changes undone
+    pub undone: usize,
+    /// Files put back as they were before the undone changes
+    pub restored: Vec<PathBuf>,
+    /// Files that could not be restored, with the reason
+    pub failed: Vec<(PathBuf, String)>,
+}
+
+impl KimiSoul {
+    /// File changes made by tools and not undone, oldest first
+    pub fn file_edits(&self) -> &[FileEdit] {
+        &self.edits
+    }
+
+    /// Files changed by tools and not undone, in the order they were first
+    /// changed
+    pub fn changed_files(&self) -> Vec<&Path> {
+        let mut files: Vec<&Path> = Vec::new();
+        for edit in &self.edits {
+            if !files.contains(&edit.before.path.as_path()) {
+                files.push(&edit.before.path);
+            }
+        }
+        files
+    }
+
+    /// Unified diff of the files tools changed, from before their first
+    /// change to their current content
+    ///
+    /// Paths are shown relative to `work_dir`; files that are back to how
+    /// they were are left out.
+    pub fn edits_diff(&self, work_dir: &Path) -> String {
+        let Some(store) = &self.snapshots else {
+            return String::new();
+        };
+        let mut out = String::new();
+        for path in self.changed_files() {
+            let Some(first) = self.edits.iter().find(|edit| edit.before.path == path) else {
+                continue;
+            };
+            let old = match store.read(&first.before) {
+                Ok(content) => content,
+                Err(e) => {
+                    warn!("Cannot diff {:?}: {}", path, e);
+                    continue;
+                }
+            };
+            let new = std::fs::read(path).ok();
+            let name = path.strip_prefix(work_dir).unwrap_or(path).display();
+            let label = |side: &str, content: &Option<Vec<u8>>| match content {
+                Some(_) => format!("{}/{}", side, name),
+                None => "/dev/null".to_string(),
+            };
+            let text = |content: &Option<Vec<u8>>| String::from_utf8_lossy(content.as_deref().unwrap_or_default()).into_owned();
+            out.push_str(&unified_diff(&text(&old), &text(&new), &label("a", &old), &label("b", &new), DIFF_CONTEXT));
+        }
+        out
+    }
+
+    /// Undo the last `n` file changes, newest first
+    ///
+    /// A file that fails to restore does not stop the others; its change is
+    /// dropped from the record either way.
+    pub fn undo(&mut self, n: usize) -> Undo {
+        let mut undo = Undo::default();
+        let Some(store) = &self.snapshots else {
+            return undo;
+        };
+        let keep = self.edits.len().saturating_sub(n);
+        for edit in self.edits.drain(keep..).rev() {
+            undo.undone += 1;
+            let path = edit.before.path.clone();
+            match store.restore(&edit.before) {
+                Ok(()) => {
+                    undo.failed.retain(|(failed, _)| failed != &path);
+                    if !undo.restored.contains(&path) {
+                        undo.restored.push(path);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to undo {} on {:?}: {}", edit.tool, path, e);
+                    undo.failed.push((path, e.to_string()));
+                }
+            }
+        }
+        self.save_edits();
+        undo
+    }
+
+    /// Run an [`UndoEditTool`] call
+    pub(crate) fn undo_edit(&mut self, params: serde_json::Value) -> ToolResult {
+        let params: UndoEditParams = serde_json::from_value(params)
+            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        if self.edits.is_empty() {
+            return Err(ToolError::Execution("No file changes to undo".to_string()));
+        }
+        let undo = self.undo(params.count.unwrap_or(1).max(1));
+        Ok(serde_json::json!({
+            "undone": undo.undone,
+            "restored": undo.restored,
+            "failed": undo.failed
+                .iter()
+                .map(|(path, error)| serde_json::json!({"path": path, "error": error}))
+                .collect::<Vec<_>>(),
+        }))
+    }
+
+    /// Read the session's journal of file changes, replacing the record
+    ///
+    /// Lines that do not parse are skipped.
+    pub fn load_edits(&mut self) {
+        self.edits.clear();
+        let Some(session) = &self.session else {
+            return;
+        };
+        let content = match std::fs::read_to_string(session.edits_file()) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!("Failed to read the file change journal: {}", e);
+                return;
+            }
+        };
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match serde_json::from_str(line) {
+                Ok(edit) => self.edits.push(edit),
+                Err(e) => warn!("Skipping a file change journal entry: {}", e),
+            }
+        }
+    }
+
+    /// Write the record of file changes to the session's journal
+    pub(crate) fn save_edits(&self) {
+        let Some(session) = &self.session else {
+            return;
+        };
+        let mut journal = String::new();
+        for edit in &self.edits {
+            match serde_json::to_string(edit) {
+                Ok(line) => {
+                    journal.push_str(&line);
+                    journal.push('\n');
+                }
+                Err(e) => warn!("Failed to serialize a file change: {}", e),
+            }
+        }
+        let path = session.edits_file();
+        let written = path
+            .parent()
+            .map_or(Ok(()), std::fs::create_dir_all)
+            .and_then(|()| std::fs::write(&path, journal));
+        if let Err(e) = written {
+            warn!("Failed to write the file change journal {:?}: {}", path, e);
+        }
+    }
+
+    /// Record the change `tool_name` made to the file it was about to
+    /// modify, given the snapshot taken before it ran
+    ///
+    /// Nothing is recorded when the file is unchanged, e.g. because the
+    /// tool failed.
+    pub(crate) fn record_edit(&mut self, tool_name: &str, before: Option<FileSnapshot>) {
+        let (Some(store), Some(before)) = (&self.snapshots, before) else {
+            return;
+        };
+        match store.snapshot(&before.path) {
+            Ok(after) if after.hash == before.hash => {}
+            Ok(_) => {
+                self.edits.push(FileEdit {
+                    tool: tool_name.to_string(),
+                    before,
+                    checkpoint: self.context.checkpoints().len().saturating_sub(1),
+                });
+                self.save_edits();
+            }
+            Err(e) => warn!("Failed to snapshot {:?} after {}: {}", before.path, tool_name, e),
+        }
+    }
+
+    /// Take over the file changes a sub-agent made, as if this soul's tools
+    /// had made them since the latest checkpoint
+    pub(crate) fn adopt_edits(&mut self, edits: Vec<FileEdit>) {
+        if edits.is_empty() {
+            return;
+        }
+        let checkpoint = self.context.checkpoints().len().saturating_sub(1);
+        for mut edit in edits {
+            if self.context.needs_snapshot(&edit.before.path) {
+                self.context.record_snapshot(edit.before.clone());
+            }
+            edit.checkpoint = checkpoint;
+            self.edits.push(edit);
+        }
+        self.save_edits();
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct UndoEditParams {
+    #[serde(default)]
+    count: Option<usize>,
+}
+
+/// Lets the model revert the file changes its tools made
+///
+/// The tool only describes itself; calls are run by the agent loop with
+/// [`KimiSoul::undo`], as they need the soul's record of changes.
+#[derive(Debug, Default)]
+pub struct UndoEditTool;
+
+impl UndoEditTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for UndoEditTool {
+    fn name(&self) -> &str {
+        UNDO_EDIT_TOOL
+    }
+
+    fn description(&self) -> &str {
+        "Revert the most recent file changes made with WriteFile, StrReplaceFile or EditNotebookCell, \
+         newest first, putting each file back as it was before the change. A file \
+         the change created is removed again. Use it to back out an edit that went wrong."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "count": {
+                    "type": "integer",
+                    "minimum": 1,
+                    "description": "Number of changes to revert; 1 by default"
+                }
+            }
+        })
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> ToolResult {
+        Err(ToolError::Execution(format!("{} only runs in the agent loop", UNDO_EDIT_TOOL)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::Session;
+    use crate::snapshot::SnapshotStore;
+    use crate::soul::testing::test_soul;
+
+    fn write(soul: &mut KimiSoul, path: &Path, content: &str) {
+        let params = serde_json::json!({"path": path, "content": content});
+        let before = soul.snapshot_before_tool("WriteFile", &params);
+        std::fs::write(path, content).unwrap();
+        soul.record_edit("WriteFile", before);
+    }
+
+    #[test]
+    fn test_undo_file_edits() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut soul = test_soul(temp.path());
+        soul.snapshots = Some(SnapshotStore::for_work_dir(temp.path()));
+        let notes = temp.path().join("notes.md");
+        let draft = temp.path().join("draft.md");
+        std::fs::write(&notes, "original").unwrap();
+        write(&mut soul, &notes, "first edit");
+        write(&mut soul, &draft, "draft");
+        write(&mut soul, &notes, "second edit");
+        write(&mut soul, &notes, "second edit");
+        assert_eq!(soul.file_edits().len(), 3);
+        assert_eq!(soul.changed_files(), [notes.as_path(), draft.as_path()]);
+        assert_eq!(
+            soul.edits_diff(temp.path()),
+            "--- a/notes.md\n+++ b/notes.md\n@@ -1,1 +1,1 @@\n-original\n+second edit\n\
+             --- /dev/null\n+++ b/draft.md\n@@ -0,0 +1,1 @@\n+draft\n"
+        );
+
+        let undo = soul.undo(2);
+        assert_eq!(undo.undone, 2);
+        assert_eq!(undo.restored, [notes.clone(), draft.clone()]);
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "first edit");
+        assert!(!draft.exists());
+        assert_eq!(soul.changed_files(), [notes.as_path()]);
+
+        let undo = soul.undo(5);
+        assert_eq!(undo.undone, 1);
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "original");
+        assert!(soul.changed_files().is_empty());
+        assert_eq!(soul.undo(1).undone, 0);
+    }
+
+    #[test]
+    fn test_edit_journal() {
+        let temp = tempfile::tempdir().unwrap();
+        let session = Session::new(temp.path().to_path_buf());
+        let mut soul = test_soul(temp.path());
+        soul.snapshots = Some(SnapshotStore::for_session(&session));
+        soul.session = Some(session.clone());
+        let notes = temp.path().join("notes.md");
+        std::fs::write(&notes, "original").unwrap();
+        write(&mut soul, &notes, "first edit");
+        write(&mut soul, &notes, "second edit");
+        assert_eq!(std::fs::read_to_string(session.edits_file()).unwrap().lines().count(), 2);
+
+        // A resumed session can undo the changes made before, with the tool
+        let mut resumed = test_soul(temp.path());
+        resumed.snapshots = Some(SnapshotStore::for_session(&session));
+        resumed.session = Some(session.clone());
+        resumed.load_edits();
+        assert_eq!(resumed.file_edits(), soul.file_edits());
+        let result = resumed.undo_edit(serde_json::json!({"count": 2})).unwrap();
+        assert_eq!(result["undone"], 2);
+        assert_eq!(result["restored"], serde_json::json!([notes]));
+        assert_eq!(std::fs::read_to_string(&notes).unwrap(), "original");
+        assert_eq!(std::fs::read_to_string(session.edits_file()).unwrap(), "");
+
+        let err = resumed.undo_edit(serde_json::json!({})).unwrap_err();
+        assert!(err.to_string().contains("No file changes to undo"), "{}", err);
+    }
+}
@@ -0,0 +1,275 @@
+//! Watching files during long turns
+//!
+//! The `WatchPath` tool lets the model watch a file or directory, e.g. a
+//! build's output or a log, and be told when it changes. Each watched path
+//! has a task forwarding the changes [`kaos_rs::watch_path`] reports; before
+//! each step, the agent loop takes what arrived, sends a
+//! [`WireMessage::FileChanged`](crate::wire::WireMessage::FileChanged) for
+//! each path and notes the changes in the context, so the model sees them
+//! whether they happened during the turn or between turns.
+
+use super::kimisoul::KimiSoul;
+use super::toolset::{Tool, ToolError, ToolResult};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::info;
+
+/// Name of the [`WatchPathTool`]
+pub const WATCH_PATH_TOOL: &str = "WatchPath";
+
+/// Paths watched at once, at most
+const MAX_WATCHES: usize = 8;
+
+/// Changed files listed for a path, at most; the rest are counted
+pub const MAX_CHANGED_FILES: usize = 20;
+
+/// Files that changed under a watched path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChange {
+    /// The watched file or directory
+    pub watched: PathBuf,
+    /// The files that changed, sorted
+    pub changed: Vec<PathBuf>,
+}
+
+impl FileChange {
+    /// The note added to the context for the change
+    pub fn note(&self) -> String {
+        let mut note = format!("Files changed under the watched path {}:", self.watched.display());
+        for path in self.changed.iter().take(MAX_CHANGED_FILES) {
+            note.push_str(&format!("\n- {}", path.display()));
+        }
+        if self.changed.len() > MAX_CHANGED_FILES {
+            note.push_str(&format!("\n- ... and {} more", self.changed.len() - MAX_CHANGED_FILES));
+        }
+        note
+    }
+}
+
+/// The paths being watched, with the changes reported for them
+#[derive(Debug)]
+pub struct FileWatches {
+    tasks: BTreeMap<PathBuf, JoinHandle<()>>,
+    sender: mpsc::UnboundedSender<FileChange>,
+    changes: mpsc::UnboundedReceiver<FileChange>,
+}
+
+impl Default for FileWatches {
+    fn default() -> Self {
+        let (sender, changes) = mpsc::unbounded_channel();
+        Self { tasks: BTreeMap::new(), sender, changes }
+    }
+}
+
+impl Drop for FileWatches {
+    fn drop(&mut self) {
+        for task in self.tasks.values() {
+            task.abort();
+        }
+    }
+}
+
+impl FileWatches {
+    /// Start watching `path`, an absolute path; watching it again is a no-op
+    pub fn watch(&mut self, path: PathBuf) -> Result<(), ToolError> {
+        if self.tasks.contains_key(&path) {
+            return Ok(());
+        }
+        if self.tasks.len() >= MAX_WATCHES {
+            return Err(ToolError::Execution(format!(
+                "Already watching {} paths; stop watching one first",
+                MAX_WATCHES
+            )));
+        }
+        let mut watcher = kaos_rs::watch_path(&path)
+            .map_err(|e| ToolError::Execution(format!("Cannot watch {}: {}", path.display(), e)))?;
+        let sender = self.sender.clone();
+        let watched = path.clone();
+        let task = tokio::spawn(async move {
+            while let Some(changed) = watcher.changes().await {
+                if sender.send(FileChange { watched: watched.clone(), changed }).is_err() {
+                    break;
+                }
+            }
+        });
+        info!("Watching {}", path.display());
+        self.tasks.insert(path, task);
+        Ok(())
+    }
+
+    /// Stop watching `path`, returning whether it was watched
+    pub fn unwatch(&mut self, path: &Path) -> bool {
+        match self.tasks.remove(path) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The watched paths, sorted
+    pub fn paths(&self) -> Vec<&Path> {
+        self.tasks.keys().map(PathBuf::as_path).collect()
+    }
+
+    /// Changes reported since the last call, merged by watched path
+    ///
+    /// Changes to paths no longer watched are dropped.
+    pub fn take_changes(&mut self) -> Vec<FileChange> {
+        let mut merged: BTreeMap<PathBuf, Vec<PathBuf>> = BTreeMap::new();
+        while let Ok(change) = self.changes.try_recv() {
+            if self.tasks.contains_key(&change.watched) {
+                merged.entry(change.watched).or_default().extend(change.changed);
+            }
+        }
+        merged
+            .into_iter()
+            .map(|(watched, mut changed)| {
+                changed.sort();
+                changed.dedup();
+                FileChange { watched, changed }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchPathParams {
+    path: String,
+    #[serde(default)]
+    stop: bool,
+}
+
+impl KimiSoul {
+    /// Run a [`WatchPathTool`] call
+    ///
+    /// With a workspace, only paths inside it can be watched.
+    pub(crate) fn watch_path(&mut self, params: serde_json::Value) -> ToolResult {
+        let params: WatchPathParams = serde_json::from_value(params)
+            .map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        let path = match &self.workspace {
+            Some(workspace) => workspace
+                .resolve(&params.path)
+                .map_err(|e| ToolError::InvalidParameters(e.to_string()))?,
+            None => std::path::absolute(&params.path)
+                .map_err(|e| ToolError::InvalidParameters(format!("Invalid path '{}': {}", params.path, e)))?,
+        };
+        let message = if params.stop {
+            if !self.file_watches.unwatch(&path) {
+                return Err(ToolError::Execution(format!("{} is not being watched", params.path)));
+            }
+            format!("Stopped watching {}", params.path)
+        } else {
+            self.file_watches.watch(path)?;
+            format!("Watching {}; changes will be noted before your next steps", params.path)
+        };
+        Ok(serde_json::json!({
+            "message": message,
+            "watching": self.file_watches.paths(),
+        }))
+    }
+}
+
+/// Lets the model watch a file or directory for changes
+///
+/// The tool only describes itself; calls are run by the agent loop with
+/// the soul's [`FileWatches`], as the changes are delivered through it.
+#[derive(Debug, Default)]
+pub struct WatchPathTool;
+
+impl WatchPathTool {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl Tool for WatchPathTool {
+    fn name(&self) -> &str {
+        WATCH_PATH_TOOL
+    }
+
+    fn description(&self) -> &str {
+        "Watch a file or directory, e.g. a build's output or a log, and be told when it \
+         changes: the changed files are noted before your next step, in this turn or a \
+         later one. A file may not exist yet. Stop watching with stop set to true."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "path": {
+                    "type": "string",
+                    "description": "The file or directory to watch; directories are watched recursively"
+                },
+                "stop": {
+                    "type": "boolean",
+                    "description": "Stop watching the path instead"
+                }
+            },
+            "required": ["path"]
+        })
+    }
+
+    async fn execute(&self, _params: serde_json::Value) -> ToolResult {
+        Err(ToolError::Execution(format!("{} only runs in the agent loop", WATCH_PATH_TOOL)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_file_watches() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().canonicalize().unwrap();
+        let log = dir.join("build.log");
+        let mut watches = FileWatches::default();
+        watches.watch(log.clone()).unwrap();
+        watches.watch(log.clone()).unwrap();
+        assert_eq!(watches.paths(), [log.as_path()]);
+
+        std::fs::write(&log, "error[E0308]: mismatched types").unwrap();
+        let mut changes = Vec::new();
+        for _ in 0..50 {
+            changes = watches.take_changes();
+            if !changes.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(changes, [FileChange { watched: log.clone(), changed: vec![log.clone()] }]);
+        assert_eq!(
+            changes[0].note(),
+            format!("Files changed under the watched path {}:\n- {}", log.display(), log.display())
+        );
+
+        assert!(watches.unwatch(&log));
+        assert!(!watches.unwatch(&log));
+        assert!(watches.paths().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_watch_path_stays_in_the_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let project = temp.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+
+        let mut soul = crate::soul::testing::test_soul(temp.path());
+        soul.workspace = Some(crate::workspace::Workspace::new(&project).unwrap());
+        let result = soul.watch_path(serde_json::json!({ "path": "../context.json" }));
+        assert!(matches!(result, Err(ToolError::InvalidParameters(_))));
+
+        soul.watch_path(serde_json::json!({ "path": "build.log" })).unwrap();
+        let root = soul.workspace.as_ref().unwrap().roots()[0].clone();
+        assert_eq!(soul.file_watches.paths(), [root.join("build.log").as_path()]);
+    }
+}
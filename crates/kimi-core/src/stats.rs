@@ -0,0 +1,288 @@
+//! Persistent usage statistics
+//!
+//! Every turn adds what it used — the turn itself, tokens, cost and tool
+//! calls — to a per-day, per-model tally in `stats.json` under the data
+//! directory, so users on metered API keys can keep an eye on their
+//! consumption. Token counts are those the provider reports, or estimated
+//! from the text sent to and received from the model when it reports none.
+//! Cost is computed from the `pricing` of the model in the config and stays
+//! zero for models without one.
+
+use crate::auth::storage::get_share_dir;
+use crate::config::Config;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Usage of one model, over a day or any longer period
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ModelUsage {
+    pub turns: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub tool_calls: u64,
+    /// Cost in USD
+    pub cost: f64,
+}
+
+impl ModelUsage {
+    /// Add `other` to this usage
+    pub fn add(&mut self, other: &ModelUsage) {
+        self.turns += other.turns;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.tool_calls += other.tool_calls;
+        self.cost += other.cost;
+    }
+
+    /// Input and output tokens together
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+/// Price of a model in USD per million tokens
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ModelPricing {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPricing {
+    /// Cost of the given tokens in USD
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Usage of one turn, per model
+///
+/// A turn normally runs on one model; after a failover to the fallback
+/// model its steps are split between the two. The turn itself is counted
+/// for the model that took the last step.
+#[derive(Debug, Clone, Default)]
+pub struct TurnUsage {
+    models: BTreeMap<String, ModelUsage>,
+    last_model: Option<String>,
+}
+
+impl TurnUsage {
+    /// Record one LLM request to `model`
+    pub fn add_step(&mut self, model: &str, input_tokens: usize, output_tokens: usize) {
+        let usage = self.models.entry(model.to_string()).or_default();
+        usage.input_tokens += input_tokens as u64;
+        usage.output_tokens += output_tokens as u64;
+        self.last_model = Some(model.to_string());
+    }
+
+    /// Record a tool call the last model made
+    pub fn add_tool_call(&mut self) {
+        if let Some(model) = &self.last_model {
+            self.models.entry(model.clone()).or_default().tool_calls += 1;
+        }
+    }
+
+    /// Whether no LLM request was made
+    pub fn is_empty(&self) -> bool {
+        self.models.is_empty()
+    }
+
+    /// Usage per model, with the turn counted for the last model
+    pub fn into_models(mut self) -> BTreeMap<String, ModelUsage> {
+        if let Some(model) = &self.last_model {
+            self.models.entry(model.clone()).or_default().turns += 1;
+        }
+        self.models
+    }
+}
+
+/// Usage per day and model
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageStats {
+    /// Usage keyed by day (`YYYY-MM-DD`), then by model name
+    #[serde(default)]
+    pub days: BTreeMap<String, BTreeMap<String, ModelUsage>>,
+}
+
+impl UsageStats {
+    /// Add `usage` of `model` to the tally of `day`
+    pub fn record(&mut self, day: NaiveDate, model: &str, usage: &ModelUsage) {
+        self.days
+            .entry(day.format("%Y-%m-%d").to_string())
+            .or_default()
+            .entry(model.to_string())
+            .or_default()
+            .add(usage);
+    }
+
+    /// Total usage per day, oldest first, from `since` on
+    pub fn by_day(&self, since: Option<NaiveDate>) -> Vec<(String, ModelUsage)> {
+        self.days_since(since)
+            .map(|(day, models)| {
+                let mut total = ModelUsage::default();
+                models.values().for_each(|usage| total.add(usage));
+                (day.clone(), total)
+            })
+            .collect()
+    }
+
+    /// Total usage per model, from `since` on
+    pub fn by_model(&self, since: Option<NaiveDate>) -> BTreeMap<String, ModelUsage> {
+        let mut totals = BTreeMap::<String, ModelUsage>::new();
+        for (_, models) in self.days_since(since) {
+            for (model, usage) in models {
+                totals.entry(model.clone()).or_default().add(usage);
+            }
+        }
+        totals
+    }
+
+    /// Total usage from `since` on
+    pub fn total(&self, since: Option<NaiveDate>) -> ModelUsage {
+        let mut total = ModelUsage::default();
+        for (_, usage) in self.by_day(since) {
+            total.add(&usage);
+        }
+        total
+    }
+
+    fn days_since(&self, since: Option<NaiveDate>) -> impl Iterator<Item = (&String, &BTreeMap<String, ModelUsage>)> {
+        let first = since.map(|day| day.format("%Y-%m-%d").to_string()).unwrap_or_default();
+        self.days.range(first..)
+    }
+}
+
+/// The file usage statistics are kept in
+#[derive(Debug, Clone)]
+pub struct UsageStore {
+    path: PathBuf,
+    /// Pricing by model name, for computing the cost of recorded usage
+    pricing: HashMap<String, ModelPricing>,
+}
+
+impl UsageStore {
+    /// Store at `path`, without pricing
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            pricing: HashMap::new(),
+        }
+    }
+
+    /// Store at the default location, pricing models as configured
+    pub fn from_config(config: &Config) -> Self {
+        let pricing = config
+            .models
+            .values()
+            .filter_map(|model| model.pricing.map(|pricing| (model.name.clone(), pricing)))
+            .collect();
+        Self::new(Self::default_path()).with_pricing(pricing)
+    }
+
+    /// Default location of the statistics file
+    pub fn default_path() -> PathBuf {
+        get_share_dir().join("stats.json")
+    }
+
+    /// Set the pricing by model name
+    pub fn with_pricing(mut self, pricing: HashMap<String, ModelPricing>) -> Self {
+        self.pricing = pricing;
+        self
+    }
+
+    /// Path of the statistics file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the statistics; a missing file means no usage yet
+    pub fn load(&self) -> Result<UsageStats, StatsError> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(serde_json::from_str(&content)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(UsageStats::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Add a turn's usage to the tally of `day`, pricing it on the way
+    pub fn record_turn(&self, day: NaiveDate, turn: TurnUsage) -> Result<(), StatsError> {
+        if turn.is_empty() {
+            return Ok(());
+        }
+        let mut stats = self.load()?;
+        for (model, mut usage) in turn.into_models() {
+            if let Some(pricing) = self.pricing.get(&model) {
+                usage.cost = pricing.cost(usage.input_tokens, usage.output_tokens);
+            }
+            stats.record(day, &model, &usage);
+        }
+        self.save(&stats)
+    }
+
+    /// Write the statistics, replacing the file in one step
+    fn save(&self, stats: &UsageStats) -> Result<(), StatsError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = self.path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(stats)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        Ok(())
+    }
+}
+
+/// Errors reading or writing usage statistics
+#[derive(Debug, Error)]
+pub enum StatsError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid statistics file: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_report() {
+        let temp = tempfile::tempdir().unwrap();
+        let pricing = HashMap::from([("kimi-k2".to_string(), ModelPricing { input: 1.0, output: 4.0 })]);
+        let store = UsageStore::new(temp.path().join("stats.json")).with_pricing(pricing);
+        assert_eq!(store.load().unwrap(), UsageStats::default());
+
+        let monday = NaiveDate::from_ymd_opt(2026, 3, 2).unwrap();
+        let tuesday = monday.succ_opt().unwrap();
+
+        // A turn that failed over is counted for the model that finished it
+        let mut turn = TurnUsage::default();
+        turn.add_step("kimi-k2", 1_000_000, 250_000);
+        turn.add_tool_call();
+        turn.add_step("gpt-4o", 2000, 100);
+        store.record_turn(monday, turn).unwrap();
+
+        let mut turn = TurnUsage::default();
+        turn.add_step("kimi-k2", 500_000, 0);
+        store.record_turn(tuesday, turn).unwrap();
+        store.record_turn(tuesday, TurnUsage::default()).unwrap();
+
+        let stats = store.load().unwrap();
+        let models = stats.by_model(None);
+        assert_eq!(
+            models["kimi-k2"],
+            ModelUsage { turns: 1, input_tokens: 1_500_000, output_tokens: 250_000, tool_calls: 1, cost: 2.5 }
+        );
+        assert_eq!(models["gpt-4o"].turns, 1);
+        assert_eq!(models["gpt-4o"].cost, 0.0);
+
+        let days = stats.by_day(None);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, "2026-03-02");
+        assert_eq!(days[0].1.total_tokens(), 1_252_100);
+        assert_eq!(stats.total(Some(tuesday)).input_tokens, 500_000);
+        assert_eq!(stats.total(None).turns, 2);
+    }
+}
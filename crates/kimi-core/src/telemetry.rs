@@ -0,0 +1,26 @@
+//! Tracing spans for agent runs
+//!
+//! Turns, steps, LLM requests and tool executions are wrapped in `tracing`
+//! spans that follow OpenTelemetry conventions, so any subscriber can pick
+//! them up and an OTLP exporter can ship them to a tracing backend:
+//!
+//! - `turn`: one user message, from input to final answer (`agent`)
+//! - `step`: one LLM call and the tool calls it made (`n`)
+//! - `llm.request`: a streamed provider request (`model`, `ttft_ms`,
+//!   `output_chars`, `tool_calls`)
+//! - `tool`: one tool execution (`tool`, `call_id`, `approval`)
+//!
+//! Failed spans carry `otel.status_code = "ERROR"` and an
+//! `otel.status_message`.
+
+use std::fmt::Display;
+use tracing::Span;
+
+/// Mark the current span as failed with `error`
+///
+/// The span must declare `otel.status_code` and `otel.status_message`.
+pub fn record_error(error: impl Display) {
+    let span = Span::current();
+    span.record("otel.status_code", "ERROR");
+    span.record("otel.status_message", error.to_string());
+}
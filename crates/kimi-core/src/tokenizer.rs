@@ -0,0 +1,126 @@
+//! Token counting
+//!
+//! A [`Tokenizer`] counts the tokens a model sees in a text, for deciding
+//! when the context needs compacting. With the `tiktoken` feature, on by
+//! default, [`for_model`] returns a byte-pair encoding: `cl100k_base` for
+//! GPT-4 and GPT-3.5, and `o200k_base` for every other model, Kimi and
+//! open-weight models included, as it is close to their vocabularies.
+//! Without the feature, counts fall back to [`HeuristicTokenizer`], a token
+//! per four characters.
+//!
+//! The encodings are compiled in and loaded on first use, which takes a
+//! moment, so a [`Context`](crate::context::Context) counts with the
+//! heuristic until it is given a model's tokenizer.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Counts tokens the way a model does, or close to it
+pub trait Tokenizer: Debug + Send + Sync {
+    /// Name of the encoding, e.g. `o200k_base`
+    fn name(&self) -> &str;
+
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+}
+
+/// A token per four characters, as a rough estimate for any model
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn name(&self) -> &str {
+        "heuristic"
+    }
+
+    fn count(&self, text: &str) -> usize {
+        crate::prompts::builder::estimate_tokens(text)
+    }
+}
+
+/// A tiktoken byte-pair encoding
+#[cfg(feature = "tiktoken")]
+pub struct BpeTokenizer {
+    name: &'static str,
+    bpe: fn() -> &'static tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl BpeTokenizer {
+    /// The `o200k_base` encoding of GPT-4o and later models
+    pub fn o200k() -> Self {
+        Self { name: "o200k_base", bpe: tiktoken_rs::o200k_base_singleton }
+    }
+
+    /// The `cl100k_base` encoding of GPT-4 and GPT-3.5
+    pub fn cl100k() -> Self {
+        Self { name: "cl100k_base", bpe: tiktoken_rs::cl100k_base_singleton }
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Debug for BpeTokenizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BpeTokenizer").field("name", &self.name).finish()
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl Tokenizer for BpeTokenizer {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    /// Special tokens such as `<|endoftext|>` count as the text they are
+    fn count(&self, text: &str) -> usize {
+        (self.bpe)().encode_ordinary(text).len()
+    }
+}
+
+/// The tokenizer for `model`, a model name such as `kimi-k2-turbo-preview`
+pub fn for_model(model: &str) -> Arc<dyn Tokenizer> {
+    #[cfg(feature = "tiktoken")]
+    {
+        use tiktoken_rs::tokenizer::{get_tokenizer, Tokenizer as Encoding};
+        match get_tokenizer(model) {
+            Some(Encoding::Cl100kBase) => Arc::new(BpeTokenizer::cl100k()),
+            _ => Arc::new(BpeTokenizer::o200k()),
+        }
+    }
+    #[cfg(not(feature = "tiktoken"))]
+    {
+        let _ = model;
+        heuristic()
+    }
+}
+
+/// The heuristic tokenizer
+pub fn heuristic() -> Arc<dyn Tokenizer> {
+    Arc::new(HeuristicTokenizer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heuristic() {
+        assert_eq!(HeuristicTokenizer.count(""), 0);
+        assert_eq!(HeuristicTokenizer.count("Hello, world"), 3);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_encoding_for_model() {
+        assert_eq!(for_model("gpt-4").name(), "cl100k_base");
+        assert_eq!(for_model("gpt-4o-mini").name(), "o200k_base");
+        assert_eq!(for_model("kimi-k2-turbo-preview").name(), "o200k_base");
+
+        let tokenizer = for_model("kimi-k2-turbo-preview");
+        assert_eq!(tokenizer.count("Hello, world!"), 4);
+        assert_eq!(tokenizer.count("<|endoftext|>"), 7);
+        // Code is denser in tokens than four characters each
+        let code = "fn main() { println!(\"{}\", x.iter().map(|y| y * 2).sum::<i32>()); }";
+        assert!(tokenizer.count(code) > HeuristicTokenizer.count(code));
+    }
+}
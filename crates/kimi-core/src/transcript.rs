@@ -0,0 +1,190 @@
+//! Readable transcripts of sessions, with secrets redacted
+//!
+//! Unlike the [event log](crate::event_log), which keeps every wire message
+//! for replay, a transcript is meant to be read and kept: the user's
+//! messages, the replies, tool calls and their results, and approval
+//! decisions, each under a timestamped heading. Everything written goes
+//! through a [`Redactor`] first. Streamed text is gathered and written as
+//! one entry once something else happens.
+
+use crate::redact::Redactor;
+use crate::types::{ApprovalKind, Attachment};
+use crate::wire::WireMessage;
+use chrono::{DateTime, Utc};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Handle to a transcript file
+///
+/// Clones share the same file, like [`EventLog`](crate::EventLog) handles.
+#[derive(Debug, Clone)]
+pub struct Transcript {
+    inner: Arc<Mutex<TranscriptFile>>,
+}
+
+#[derive(Debug)]
+struct TranscriptFile {
+    path: PathBuf,
+    file: File,
+    redactor: Redactor,
+    /// Reply text streamed since the last entry, with when it began
+    reply: Option<(DateTime<Utc>, String)>,
+}
+
+impl Transcript {
+    /// Open the transcript at `path` for appending, creating it if needed,
+    /// and write a heading for `session`
+    pub fn open(path: impl Into<PathBuf>, session: &str, redactor: Redactor) -> std::io::Result<Self> {
+        let path = path.into();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+        writeln!(file, "=== Session {} started {} ===\n", session, timestamp(Utc::now()))?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(TranscriptFile {
+                path,
+                file,
+                redactor,
+                reply: None,
+            })),
+        })
+    }
+
+    /// Path of the transcript file
+    pub fn path(&self) -> PathBuf {
+        self.lock().path.clone()
+    }
+
+    /// Add a wire message to the transcript, logging instead of failing
+    pub fn record(&self, message: &WireMessage) {
+        if let Err(e) = self.lock().record(message, Utc::now()) {
+            warn!("Failed to write the transcript: {}", e);
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TranscriptFile> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl TranscriptFile {
+    fn record(&mut self, message: &WireMessage, now: DateTime<Utc>) -> std::io::Result<()> {
+        if let WireMessage::TextPart { text } = message {
+            self.reply.get_or_insert_with(|| (now, String::new())).1.push_str(text);
+            return Ok(());
+        }
+        self.flush_reply()?;
+        let (heading, body) = match message {
+            WireMessage::TurnBegin { user_input } => {
+                let attachments: Vec<String> = user_input.attachments.iter().map(|attachment| match attachment {
+                    Attachment::File { path } | Attachment::Image { path } => format!("[Attached {}]", path.display()),
+                    Attachment::Url { url } => format!("[Attached {}]", url),
+                }).collect();
+                ("USER".to_string(), [vec![user_input.text.clone()], attachments].concat().join("\n"))
+            }
+            WireMessage::ToolBegin { name, arguments } => (format!("TOOL CALL {}", name), arguments.clone()),
+            WireMessage::ToolEnd { name, result } => (format!("TOOL RESULT {}", name), result.clone()),
+            WireMessage::ApprovalRequest { action, description, .. } => (format!("APPROVAL REQUESTED {}", action), description.clone()),
+            WireMessage::ApprovalResponse { response, .. } => {
+                let decision = match response {
+                    ApprovalKind::Approve => "APPROVED",
+                    ApprovalKind::ApproveOnce => "APPROVED ONCE",
+                    ApprovalKind::ApproveAlways => "APPROVED ALWAYS",
+                    ApprovalKind::Reject => "REJECTED",
+                };
+                (decision.to_string(), String::new())
+            }
+            WireMessage::TurnLimitReached { message, .. } => ("TURN LIMIT".to_string(), message.clone()),
+            WireMessage::StepInterrupted => ("INTERRUPTED".to_string(), String::new()),
+            WireMessage::SubagentResult { name, summary, is_error, .. } => {
+                let status = if *is_error { " (failed)" } else { "" };
+                (format!("SUB-AGENT {}{}", name, status), summary.clone())
+            }
+            _ => return Ok(()),
+        };
+        self.write_entry(now, &heading, &body)
+    }
+
+    /// Write the reply streamed so far as one entry
+    fn flush_reply(&mut self) -> std::io::Result<()> {
+        match self.reply.take() {
+            Some((started, text)) if !text.trim().is_empty() => self.write_entry(started, "ASSISTANT", &text),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_entry(&mut self, at: DateTime<Utc>, heading: &str, body: &str) -> std::io::Result<()> {
+        let mut entry = format!("[{}] {}\n", timestamp(at), heading);
+        let body = body.trim();
+        if !body.is_empty() {
+            entry.push_str(&self.redactor.redact(body));
+            entry.push('\n');
+        }
+        entry.push('\n');
+        self.file.write_all(entry.as_bytes())?;
+        self.file.flush()
+    }
+}
+
+impl Drop for TranscriptFile {
+    fn drop(&mut self) {
+        // A reply cut off by the end of the session is still worth keeping
+        let _ = self.flush_reply();
+    }
+}
+
+fn timestamp(at: DateTime<Utc>) -> String {
+    at.format("%Y-%m-%d %H:%M:%S UTC").to_string()
+}
+
+/// Where a transcript path from the config or `--log-file` points: `~` is
+/// the home directory and relative paths are under `work_dir`
+pub fn resolve_path(path: &Path, work_dir: &Path) -> PathBuf {
+    if let (Ok(rest), Some(home)) = (path.strip_prefix("~"), dirs::home_dir()) {
+        return home.join(rest);
+    }
+    work_dir.join(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::UserInput;
+
+    #[test]
+    fn test_transcript() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("logs/transcript.log");
+        let transcript = Transcript::open(&path, "abc123", Redactor::new(["sk-moonshot-secret".to_string()])).unwrap();
+        let messages = [
+            WireMessage::TurnBegin { user_input: UserInput { text: "Use key sk-moonshot-secret".to_string(), attachments: Vec::new() } },
+            WireMessage::StepBegin { n: 1 },
+            WireMessage::TextPart { text: "Checking ".to_string() },
+            WireMessage::TextPart { text: "the config.".to_string() },
+            WireMessage::ToolBegin { name: "ReadFile".to_string(), arguments: r#"{"path":".env"}"#.to_string() },
+            WireMessage::ToolEnd { name: "ReadFile".to_string(), result: "API_KEY=abcdefgh12345678".to_string() },
+            WireMessage::TextPart { text: "Done.".to_string() },
+        ];
+        for message in &messages {
+            transcript.record(message);
+        }
+        drop(transcript);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let headings: Vec<&str> = text.lines().filter(|line| line.starts_with('[') || line.starts_with("===")).collect();
+        assert!(headings[0].starts_with("=== Session abc123 started "));
+        let headings: Vec<&str> = headings[1..].iter().map(|line| line.split_once("] ").unwrap().1).collect();
+        assert_eq!(headings, ["USER", "ASSISTANT", "TOOL CALL ReadFile", "TOOL RESULT ReadFile", "ASSISTANT"]);
+        assert!(text.contains("Use key [REDACTED]\n"));
+        assert!(text.contains("Checking the config.\n"));
+        assert!(text.contains("API_KEY=[REDACTED]\n"));
+        assert!(text.ends_with("Done.\n\n"));
+
+        assert_eq!(resolve_path(Path::new("logs/t.log"), Path::new("/work")), PathBuf::from("/work/logs/t.log"));
+        assert_eq!(resolve_path(Path::new("/var/log/t.log"), Path::new("/work")), PathBuf::from("/var/log/t.log"));
+    }
+}
@@ -1,5 +1,6 @@
 //! Shared types for the kimi-core crate
 
+use crate::snapshot::FileSnapshot;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -8,14 +9,34 @@ use std::path::PathBuf;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInput {
     pub text: String,
+    #[serde(default)]
     pub attachments: Vec<Attachment>,
 }
 
 /// Attachment to user input
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Attachment {
-    pub path: PathBuf,
-    pub mime_type: String,
+///
+/// See [`crate::attachment`] for how each kind reaches the model.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    /// A text file whose content is added to the message
+    File { path: PathBuf },
+    /// An image file sent to models with vision support
+    Image { path: PathBuf },
+    /// A web resource, sent as an image when it points at one
+    Url { url: String },
+}
+
+impl Attachment {
+    /// An image or file attachment, depending on the path's extension
+    pub fn from_path(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        if crate::attachment::image_mime_type(&path.to_string_lossy()).is_some() {
+            Attachment::Image { path }
+        } else {
+            Attachment::File { path }
+        }
+    }
 }
 
 /// Token usage statistics
@@ -33,6 +54,9 @@ pub enum ApprovalKind {
     Approve,
     Reject,
     ApproveOnce,
+    /// Approve, and stop asking for the tool, or for the Shell command's
+    /// pattern, for the rest of the session
+    ApproveAlways,
 }
 
 /// Message in the context
@@ -43,6 +67,88 @@ pub struct Message {
     pub metadata: Option<HashMap<String, serde_json::Value>>,
 }
 
+impl Message {
+    /// Metadata key holding the ID a [`Context`](crate::context::Context) assigns
+    pub const ID_KEY: &'static str = "id";
+
+    /// ID of the message within its context
+    pub fn id(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get(Self::ID_KEY)?.as_str()
+    }
+
+    /// Metadata key holding a user message's attachments
+    pub const ATTACHMENTS_KEY: &'static str = "attachments";
+
+    /// Attachments of the message
+    pub fn attachments(&self) -> Vec<Attachment> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(Self::ATTACHMENTS_KEY))
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Attach `attachments` to the message
+    pub fn with_attachments(mut self, attachments: &[Attachment]) -> Self {
+        if !attachments.is_empty() {
+            self.metadata.get_or_insert_with(HashMap::new).insert(
+                Self::ATTACHMENTS_KEY.to_string(),
+                serde_json::to_value(attachments).unwrap_or_default(),
+            );
+        }
+        self
+    }
+
+    /// Metadata key holding the tool calls of an assistant message
+    pub const TOOL_CALLS_KEY: &'static str = "tool_calls";
+
+    /// Tool calls the assistant made in the message
+    pub fn tool_calls(&self) -> Vec<kosong_rs::ToolCall> {
+        self.metadata
+            .as_ref()
+            .and_then(|m| m.get(Self::TOOL_CALLS_KEY))
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    /// Metadata key holding the ID of the tool call a tool message answers
+    pub const TOOL_CALL_ID_KEY: &'static str = "tool_call_id";
+
+    /// ID of the tool call the message is the result of
+    pub fn tool_call_id(&self) -> Option<&str> {
+        self.metadata.as_ref()?.get(Self::TOOL_CALL_ID_KEY)?.as_str()
+    }
+
+    /// Mark the message as the result of the tool call `id`
+    pub fn with_tool_call_id(mut self, id: impl Into<String>) -> Self {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(Self::TOOL_CALL_ID_KEY.to_string(), serde_json::Value::String(id.into()));
+        self
+    }
+
+    /// Metadata key holding the tokens a [`Context`](crate::context::Context)
+    /// counted in the message
+    pub const TOKENS_KEY: &'static str = "tokens";
+
+    /// Tokens counted in the message when it was added to its context
+    pub fn tokens(&self) -> Option<usize> {
+        self.metadata.as_ref()?.get(Self::TOKENS_KEY)?.as_u64().map(|n| n as usize)
+    }
+
+    pub(crate) fn set_tokens(&mut self, tokens: usize) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(Self::TOKENS_KEY.to_string(), serde_json::json!(tokens));
+    }
+
+    pub(crate) fn set_id(&mut self, id: String) {
+        self.metadata
+            .get_or_insert_with(HashMap::new)
+            .insert(Self::ID_KEY.to_string(), serde_json::Value::String(id));
+    }
+}
+
 /// Role of a message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -60,6 +166,13 @@ pub struct Checkpoint {
     pub message_index: usize,
     pub token_count: usize,
     pub summary: Option<String>,
+    /// When the checkpoint was taken; unknown for contexts saved before
+    /// checkpoints were timestamped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Files modified after the checkpoint, as they were before the change
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileSnapshot>,
 }
 
 /// LLM Model configuration
@@ -69,6 +182,9 @@ pub struct LlmModel {
     pub provider: String,
     pub max_tokens: Option<usize>,
     pub temperature: Option<f64>,
+    /// Price per million tokens, for the cost in usage statistics
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<crate::stats::ModelPricing>,
 }
 
 /// Loop control configuration
@@ -76,6 +192,30 @@ pub struct LlmModel {
 pub struct LoopControl {
     pub max_iterations: usize,
     pub timeout_seconds: u64,
+    /// LLM calls allowed in one turn
+    #[serde(default = "LoopControl::default_max_steps_per_turn")]
+    pub max_steps_per_turn: usize,
+    /// Tool calls allowed in one turn
+    #[serde(default = "LoopControl::default_max_tool_calls_per_turn")]
+    pub max_tool_calls_per_turn: usize,
+    /// Identical tool calls allowed in a row before the turn is stopped as
+    /// a loop
+    #[serde(default = "LoopControl::default_max_identical_tool_calls")]
+    pub max_identical_tool_calls: usize,
+}
+
+impl LoopControl {
+    fn default_max_steps_per_turn() -> usize {
+        25
+    }
+
+    fn default_max_tool_calls_per_turn() -> usize {
+        100
+    }
+
+    fn default_max_identical_tool_calls() -> usize {
+        3
+    }
 }
 
 impl Default for LoopControl {
@@ -83,6 +223,9 @@ impl Default for LoopControl {
         Self {
             max_iterations: 100,
             timeout_seconds: 300,
+            max_steps_per_turn: Self::default_max_steps_per_turn(),
+            max_tool_calls_per_turn: Self::default_max_tool_calls_per_turn(),
+            max_identical_tool_calls: Self::default_max_identical_tool_calls(),
         }
     }
 }
@@ -118,4 +261,11 @@ pub struct Request {
     pub sender: String,
     pub action: String,
     pub description: String,
+    /// The file change the tool call would make, for reviewing edits
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit: Option<crate::soul::ProposedEdit>,
+    /// The pattern of the Shell command, e.g. `git status`, which an
+    /// [`ApprovalKind::ApproveAlways`] answer approves from then on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command_pattern: Option<String>,
 }
@@ -0,0 +1,179 @@
+//! Checking GitHub releases for a newer version
+//!
+//! The shell should not wait on the network to start, so the check is split
+//! in two: the latest release seen is cached in `update_check.json` under the
+//! data directory and read at startup, while a refresh runs in the
+//! background at most once a day. A release found by a refresh is announced
+//! the next time the shell starts.
+
+use crate::auth::storage::get_share_dir;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Owner of the GitHub repository releases are published in
+pub const REPO_OWNER: &str = "creativebastard";
+
+/// Name of the GitHub repository releases are published in
+pub const REPO_NAME: &str = "kimi-rcli";
+
+/// How long a check stays fresh before the next refresh
+const CHECK_INTERVAL_HOURS: i64 = 24;
+
+/// How long a refresh may take before it is given up
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// The outcome of the last check
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UpdateCheck {
+    pub checked_at: DateTime<Utc>,
+    /// Version of the latest release, without the `v` of its tag
+    pub latest_version: String,
+}
+
+/// Reads and refreshes the cached update check
+#[derive(Debug, Clone)]
+pub struct UpdateChecker {
+    path: PathBuf,
+    current_version: String,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+impl UpdateChecker {
+    /// A checker caching at `path`, comparing releases to `current_version`
+    pub fn new(path: impl Into<PathBuf>, current_version: impl Into<String>) -> Self {
+        Self { path: path.into(), current_version: current_version.into() }
+    }
+
+    /// Default location of the cached check
+    pub fn default_path() -> PathBuf {
+        get_share_dir().join("update_check.json")
+    }
+
+    /// Path of the cached check
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The last check, if there was one and the cache is readable
+    pub fn cached(&self) -> Option<UpdateCheck> {
+        let content = std::fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str(&content).ok()
+    }
+
+    /// Version of a newer release found by the last check
+    pub fn available(&self) -> Option<String> {
+        self.cached()
+            .map(|check| check.latest_version)
+            .filter(|latest| is_newer(latest, &self.current_version))
+    }
+
+    /// Whether the last check is older than a day, or there was none
+    pub fn is_stale(&self, now: DateTime<Utc>) -> bool {
+        self.cached()
+            .is_none_or(|check| now - check.checked_at >= Duration::hours(CHECK_INTERVAL_HOURS))
+    }
+
+    /// Ask GitHub for the latest release and cache the answer
+    pub async fn refresh(&self) -> Result<UpdateCheck, UpdateError> {
+        let url = format!("https://api.github.com/repos/{}/{}/releases/latest", REPO_OWNER, REPO_NAME);
+        let release: Release = reqwest::Client::new()
+            .get(url)
+            .header(reqwest::header::USER_AGENT, format!("kimi-cli/{}", self.current_version))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let check = UpdateCheck {
+            checked_at: Utc::now(),
+            latest_version: release.tag_name.trim_start_matches('v').to_string(),
+        };
+        self.save(&check)?;
+        Ok(check)
+    }
+
+    fn save(&self, check: &UpdateCheck) -> Result<(), UpdateError> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(check)?)?;
+        Ok(())
+    }
+}
+
+/// Whether version `candidate` is newer than `current`
+///
+/// Versions compare by their numeric `major.minor.patch` parts; a version
+/// with a pre-release suffix such as `-rc.1` is older than the release
+/// itself. Versions that do not parse are never newer.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    match (parse_version(candidate), parse_version(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// The numeric parts of a version, and whether it is a full release
+fn parse_version(version: &str) -> Option<([u64; 3], bool)> {
+    let version = version.trim().trim_start_matches('v');
+    let (numbers, pre_release) = match version.split_once('-') {
+        Some((numbers, _)) => (numbers, true),
+        None => (version, false),
+    };
+    let numbers = numbers.split('+').next()?;
+    let mut parts = [0; 3];
+    for (i, part) in numbers.split('.').enumerate() {
+        *parts.get_mut(i)? = part.parse().ok()?;
+    }
+    Some((parts, !pre_release))
+}
+
+/// Errors checking for updates
+#[derive(Debug, Error)]
+pub enum UpdateError {
+    #[error("Failed to fetch the latest release: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid update check: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_check_cache() {
+        assert!(is_newer("0.6.0", "0.5.0"));
+        assert!(is_newer("v0.5.1", "0.5.0"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(is_newer("0.5.0", "0.5.0-rc.1"));
+        assert!(!is_newer("0.5.0-rc.1", "0.5.0"));
+        assert!(!is_newer("0.5.0", "0.5.0"));
+        assert!(!is_newer("0.4.9", "0.5.0"));
+        assert!(!is_newer("nightly", "0.5.0"));
+
+        let temp = tempfile::tempdir().unwrap();
+        let checker = UpdateChecker::new(temp.path().join("update_check.json"), "0.5.0");
+        let now = Utc::now();
+        assert!(checker.is_stale(now));
+        assert_eq!(checker.available(), None);
+
+        checker.save(&UpdateCheck { checked_at: now, latest_version: "0.6.0".to_string() }).unwrap();
+        assert!(!checker.is_stale(now + Duration::hours(23)));
+        assert!(checker.is_stale(now + Duration::hours(24)));
+        assert_eq!(checker.available().as_deref(), Some("0.6.0"));
+
+        let current = UpdateChecker::new(checker.path(), "0.6.0");
+        assert_eq!(current.available(), None);
+    }
+}
@@ -1,8 +1,18 @@
 //! Wire protocol for agent communication
+//!
+//! Messages are serialized as `{"version": 1, "type": "TextPart", "payload": {...}}`.
+//! Adding a variant or an optional field keeps the version; renaming or
+//! removing a variant or field, or changing a field's type, must bump
+//! [`WIRE_PROTOCOL_VERSION`]. The compatibility tests below pin the JSON of
+//! every variant, so such a change shows up as a failing test.
 
 use crate::types::{ApprovalKind, TokenUsage, UserInput};
+use serde::de::Error as _;
 use serde::{Deserialize, Serialize};
 
+/// Version of the wire protocol written by [`WireMessage::to_json`]
+pub const WIRE_PROTOCOL_VERSION: u32 = 1;
+
 /// Wire message types for agent communication
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "payload")]
@@ -15,6 +25,8 @@ pub enum WireMessage {
     StepBegin { n: usize },
     /// Step was interrupted
     StepInterrupted,
+    /// Request from a client to interrupt the current turn
+    Interrupt,
     /// Begin context compaction
     CompactionBegin,
     /// End context compaction
@@ -34,6 +46,19 @@ pub enum WireMessage {
         name: String,
         arguments: String,
     },
+    /// Output a running tool produced, e.g. lines a shell command printed
+    ToolOutputPart {
+        tool_call_id: String,
+        stream: OutputStream,
+        text: String,
+    },
+    /// Files changed under a path the agent is watching with `WatchPath`
+    FileChanged {
+        /// The watched file or directory
+        path: String,
+        /// The files that changed, at most a few dozen
+        changed: Vec<String>,
+    },
     /// Tool execution completed
     ToolEnd {
         name: String,
@@ -64,6 +89,9 @@ pub enum WireMessage {
         sender: String,
         action: String,
         description: String,
+        /// Unified diff of the file change the tool call would make
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        diff: Option<String>,
     },
     /// Response to an approval request
     ApprovalResponse {
@@ -76,22 +104,95 @@ pub enum WireMessage {
         token_usage: Option<TokenUsage>,
         message_id: Option<String>,
     },
+    /// A skill was activated for the current turn
+    SkillActivated { name: String },
+    /// The turn stopped at one of its `[loop_control]` limits
+    TurnLimitReached { setting: String, message: String },
+    /// A step failed on one model and is being retried on the fallback model
+    ProviderFailover {
+        from: String,
+        to: String,
+        reason: String,
+    },
+    /// A flow skill entered a node
+    FlowStep {
+        flow: String,
+        node_id: String,
+        label: String,
+    },
+    /// A flow decision node took a branch
+    FlowDecision {
+        flow: String,
+        node_id: String,
+        choice: String,
+    },
+    /// A flow skill reached its End node
+    FlowEnd { flow: String },
     /// Event from a subagent
     SubagentEvent {
         task_tool_call_id: String,
         event: Box<WireMessage>,
     },
+    /// A subagent finished its task
+    SubagentResult {
+        task_id: String,
+        name: String,
+        summary: String,
+        is_error: bool,
+    },
+}
+
+/// The stream a running tool's output came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputStream {
+    Stdout,
+    Stderr,
+}
+
+/// A message as written to JSON, tagged with the protocol version
+#[derive(Serialize)]
+struct Versioned<'a> {
+    version: u32,
+    #[serde(flatten)]
+    message: &'a WireMessage,
+}
+
+/// A message as read from JSON; messages without a version are version 1
+#[derive(Deserialize)]
+struct VersionedOwned {
+    #[serde(default = "first_version")]
+    version: u32,
+    #[serde(flatten)]
+    message: WireMessage,
+}
+
+fn first_version() -> u32 {
+    1
 }
 
 impl WireMessage {
-    /// Serialize the message to JSON
+    /// Serialize the message to JSON, with the protocol version
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string(self)
+        serde_json::to_string(&Versioned {
+            version: WIRE_PROTOCOL_VERSION,
+            message: self,
+        })
     }
 
     /// Deserialize a message from JSON
+    ///
+    /// Messages from a newer protocol version are rejected rather than
+    /// misread.
     pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+        let versioned: VersionedOwned = serde_json::from_str(json)?;
+        if versioned.version > WIRE_PROTOCOL_VERSION {
+            return Err(serde_json::Error::custom(format!(
+                "unsupported wire protocol version {} (this build speaks {})",
+                versioned.version, WIRE_PROTOCOL_VERSION
+            )));
+        }
+        Ok(versioned.message)
     }
 }
 
@@ -139,4 +240,161 @@ mod tests {
             "test content"
         );
     }
+
+    /// One message of every variant, with the JSON it must serialize to
+    fn fixtures() -> Vec<(WireMessage, &'static str)> {
+        let text = |text: &str| WireMessage::TextPart { text: text.to_string() };
+        vec![
+            (
+                WireMessage::TurnBegin {
+                    user_input: UserInput { text: "Hi".to_string(), attachments: vec![] },
+                },
+                r#"{"version":1,"type":"TurnBegin","payload":{"user_input":{"text":"Hi","attachments":[]}}}"#,
+            ),
+            (WireMessage::TurnEnd, r#"{"version":1,"type":"TurnEnd"}"#),
+            (WireMessage::StepBegin { n: 2 }, r#"{"version":1,"type":"StepBegin","payload":{"n":2}}"#),
+            (WireMessage::StepInterrupted, r#"{"version":1,"type":"StepInterrupted"}"#),
+            (WireMessage::Interrupt, r#"{"version":1,"type":"Interrupt"}"#),
+            (WireMessage::CompactionBegin, r#"{"version":1,"type":"CompactionBegin"}"#),
+            (WireMessage::CompactionEnd, r#"{"version":1,"type":"CompactionEnd"}"#),
+            (text("Hello"), r#"{"version":1,"type":"TextPart","payload":{"text":"Hello"}}"#),
+            (
+                WireMessage::ThinkPart { text: "Hmm".to_string() },
+                r#"{"version":1,"type":"ThinkPart","payload":{"text":"Hmm"}}"#,
+            ),
+            (
+                WireMessage::ImageUrlPart { url: "a.png".to_string() },
+                r#"{"version":1,"type":"ImageUrlPart","payload":{"url":"a.png"}}"#,
+            ),
+            (
+                WireMessage::AudioUrlPart { url: "a.mp3".to_string() },
+                r#"{"version":1,"type":"AudioUrlPart","payload":{"url":"a.mp3"}}"#,
+            ),
+            (
+                WireMessage::VideoUrlPart { url: "a.mp4".to_string() },
+                r#"{"version":1,"type":"VideoUrlPart","payload":{"url":"a.mp4"}}"#,
+            ),
+            (
+                WireMessage::ToolBegin { name: "Shell".to_string(), arguments: "{}".to_string() },
+                r#"{"version":1,"type":"ToolBegin","payload":{"name":"Shell","arguments":"{}"}}"#,
+            ),
+            (
+                WireMessage::ToolOutputPart {
+                    tool_call_id: "c1".to_string(),
+                    stream: OutputStream::Stderr,
+                    text: "Compiling kimi\n".to_string(),
+                },
+                r#"{"version":1,"type":"ToolOutputPart","payload":{"tool_call_id":"c1","stream":"stderr","text":"Compiling kimi\n"}}"#,
+            ),
+            (
+                WireMessage::ToolEnd { name: "Shell".to_string(), result: "ok".to_string() },
+                r#"{"version":1,"type":"ToolEnd","payload":{"name":"Shell","result":"ok"}}"#,
+            ),
+            (
+                WireMessage::ToolCall { id: "c1".to_string(), name: "Shell".to_string(), arguments: "{}".to_string() },
+                r#"{"version":1,"type":"ToolCall","payload":{"id":"c1","name":"Shell","arguments":"{}"}}"#,
+            ),
+            (
+                WireMessage::ToolCallPart { id: "c1".to_string(), name: "Shell".to_string(), arguments: "{".to_string() },
+                r#"{"version":1,"type":"ToolCallPart","payload":{"id":"c1","name":"Shell","arguments":"{"}}"#,
+            ),
+            (
+                WireMessage::ToolResult { tool_call_id: "c1".to_string(), output: "ok".to_string(), is_error: false },
+                r#"{"version":1,"type":"ToolResult","payload":{"tool_call_id":"c1","output":"ok","is_error":false}}"#,
+            ),
+            (
+                WireMessage::ApprovalRequest {
+                    id: "r1".to_string(),
+                    tool_call_id: "c1".to_string(),
+                    sender: "kimi".to_string(),
+                    action: "Shell".to_string(),
+                    description: "Run ls".to_string(),
+                    diff: None,
+                },
+                r#"{"version":1,"type":"ApprovalRequest","payload":{"id":"r1","tool_call_id":"c1","sender":"kimi","action":"Shell","description":"Run ls"}}"#,
+            ),
+            (
+                WireMessage::ApprovalResponse { request_id: "r1".to_string(), response: ApprovalKind::ApproveOnce },
+                r#"{"version":1,"type":"ApprovalResponse","payload":{"request_id":"r1","response":"approve_once"}}"#,
+            ),
+            (
+                WireMessage::StatusUpdate {
+                    context_usage: Some(0.5),
+                    token_usage: Some(TokenUsage { input_tokens: 1, output_tokens: 2, total_tokens: 3 }),
+                    message_id: None,
+                },
+                r#"{"version":1,"type":"StatusUpdate","payload":{"context_usage":0.5,"token_usage":{"input_tokens":1,"output_tokens":2,"total_tokens":3},"message_id":null}}"#,
+            ),
+            (
+                WireMessage::SkillActivated { name: "pdf".to_string() },
+                r#"{"version":1,"type":"SkillActivated","payload":{"name":"pdf"}}"#,
+            ),
+            (
+                WireMessage::TurnLimitReached {
+                    setting: "max_steps_per_turn".to_string(),
+                    message: "the turn made 25 LLM calls without finishing".to_string(),
+                },
+                r#"{"version":1,"type":"TurnLimitReached","payload":{"setting":"max_steps_per_turn","message":"the turn made 25 LLM calls without finishing"}}"#,
+            ),
+            (
+                WireMessage::ProviderFailover {
+                    from: "kimi-k2".to_string(),
+                    to: "gpt-4o".to_string(),
+                    reason: "API error: overloaded".to_string(),
+                },
+                r#"{"version":1,"type":"ProviderFailover","payload":{"from":"kimi-k2","to":"gpt-4o","reason":"API error: overloaded"}}"#,
+            ),
+            (
+                WireMessage::FlowStep { flow: "deploy".to_string(), node_id: "A".to_string(), label: "Build".to_string() },
+                r#"{"version":1,"type":"FlowStep","payload":{"flow":"deploy","node_id":"A","label":"Build"}}"#,
+            ),
+            (
+                WireMessage::FlowDecision { flow: "deploy".to_string(), node_id: "B".to_string(), choice: "Yes".to_string() },
+                r#"{"version":1,"type":"FlowDecision","payload":{"flow":"deploy","node_id":"B","choice":"Yes"}}"#,
+            ),
+            (
+                WireMessage::FlowEnd { flow: "deploy".to_string() },
+                r#"{"version":1,"type":"FlowEnd","payload":{"flow":"deploy"}}"#,
+            ),
+            (
+                WireMessage::FileChanged { path: "target".to_string(), changed: vec!["target/build.log".to_string()] },
+                r#"{"version":1,"type":"FileChanged","payload":{"path":"target","changed":["target/build.log"]}}"#,
+            ),
+            (
+                WireMessage::SubagentEvent { task_tool_call_id: "t1".to_string(), event: Box::new(text("Hi")) },
+                r#"{"version":1,"type":"SubagentEvent","payload":{"task_tool_call_id":"t1","event":{"type":"TextPart","payload":{"text":"Hi"}}}}"#,
+            ),
+            (
+                WireMessage::SubagentResult {
+                    task_id: "t1".to_string(),
+                    name: "Tester".to_string(),
+                    summary: "Done".to_string(),
+                    is_error: false,
+                },
+                r#"{"version":1,"type":"SubagentResult","payload":{"task_id":"t1","name":"Tester","summary":"Done","is_error":false}}"#,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_wire_format_is_stable() {
+        for (message, expected) in fixtures() {
+            assert_eq!(message.to_json().unwrap(), expected);
+            let parsed = WireMessage::from_json(expected).unwrap();
+            assert_eq!(parsed.to_json().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_wire_versions() {
+        // Messages written before the version field was added still parse
+        let legacy = WireMessage::from_json(r#"{"type":"TextPart","payload":{"text":"old"}}"#).unwrap();
+        assert!(matches!(legacy, WireMessage::TextPart { ref text } if text == "old"));
+
+        let newer = format!(r#"{{"version":{},"type":"TurnEnd"}}"#, WIRE_PROTOCOL_VERSION + 1);
+        let err = WireMessage::from_json(&newer).unwrap_err();
+        assert!(err.to_string().contains("unsupported wire protocol version"));
+
+        assert!(WireMessage::from_json(r#"{"version":1,"type":"NoSuchMessage"}"#).is_err());
+    }
 }
@@ -0,0 +1,157 @@
+//! Workspace boundary for file tools
+//!
+//! A [`Workspace`] holds the directories the agent's file tools may touch:
+//! the working directory and any added with `--add-dir`. Paths are resolved
+//! against the first root and checked after following symlinks, so neither
+//! `../` nor a link pointing out of the workspace reaches other files. Each
+//! component is resolved in turn, so a file that does not exist yet is
+//! checked through its existing ancestors.
+
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Errors resolving a path in the workspace
+#[derive(Debug, Error)]
+pub enum WorkspaceError {
+    #[error("Path '{path}' is outside the workspace ({roots}); use --add-dir to allow it")]
+    Outside { path: String, roots: String },
+    #[error("Workspace root '{path}' is not accessible: {source}")]
+    Root {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The directories file tools may read and write
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Workspace {
+    roots: Vec<PathBuf>,
+}
+
+impl Workspace {
+    /// A workspace of `root` alone
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, WorkspaceError> {
+        Self::with_roots([root])
+    }
+
+    /// A workspace of `roots`; relative paths resolve against the first
+    ///
+    /// Panics if `roots` is empty.
+    pub fn with_roots<P: AsRef<Path>>(roots: impl IntoIterator<Item = P>) -> Result<Self, WorkspaceError> {
+        let roots = roots
+            .into_iter()
+            .map(|root| {
+                let root = root.as_ref();
+                root.canonicalize().map_err(|source| WorkspaceError::Root {
+                    path: root.display().to_string(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(!roots.is_empty(), "a workspace needs a root");
+        Ok(Self { roots })
+    }
+
+    /// The roots, canonicalized; the first is the working directory
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Whether `path`, already resolved, is in one of the roots
+    pub fn contains(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    /// The real path `path` names, relative ones taken from the working
+    /// directory, failing if it is outside the workspace
+    pub fn resolve(&self, path: impl AsRef<Path>) -> Result<PathBuf, WorkspaceError> {
+        let path = path.as_ref();
+        let resolved = real_path(&self.roots[0].join(path))?;
+        if self.contains(&resolved) {
+            Ok(resolved)
+        } else {
+            Err(WorkspaceError::Outside {
+                path: path.display().to_string(),
+                roots: self.roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", "),
+            })
+        }
+    }
+}
+
+/// `path` with symlinks followed and `.` and `..` applied, one component
+/// at a time, so a `..` after a missing directory cannot step into a link
+fn real_path(path: &Path) -> Result<PathBuf, WorkspaceError> {
+    let mut resolved = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            Component::Normal(name) => {
+                resolved.push(name);
+                match resolved.symlink_metadata() {
+                    // Also fails for a link to nothing, which a write would follow
+                    Ok(_) => resolved = resolved.canonicalize()?,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            component => resolved.push(component),
+        }
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {}").unwrap();
+        let workspace = Workspace::new(&root).unwrap();
+        let root = root.canonicalize().unwrap();
+
+        assert_eq!(workspace.resolve("src/main.rs").unwrap(), root.join("src/main.rs"));
+        assert_eq!(workspace.resolve(root.join("src/../Cargo.toml")).unwrap(), root.join("Cargo.toml"));
+        // Files and directories that do not exist yet
+        assert_eq!(workspace.resolve("docs/new/guide.md").unwrap(), root.join("docs/new/guide.md"));
+
+        let err = workspace.resolve("../secrets.txt").unwrap_err();
+        assert!(err.to_string().contains("'../secrets.txt' is outside the workspace"), "{}", err);
+        assert!(workspace.resolve("new/../../secrets.txt").is_err());
+        assert!(workspace.resolve("/etc/passwd").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_out_of_the_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("project");
+        let outside = temp.path().join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        let workspace = Workspace::new(&root).unwrap();
+        assert!(workspace.resolve("link/notes.md").is_err());
+        // Nor through a directory that does not exist
+        assert!(workspace.resolve("nope/../link/evil.txt").is_err());
+        std::os::unix::fs::symlink(outside.join("missing.txt"), root.join("dangling")).unwrap();
+        assert!(workspace.resolve("dangling").is_err());
+
+        // Unless the target is a root too
+        let workspace = Workspace::with_roots([&root, &outside]).unwrap();
+        assert_eq!(
+            workspace.resolve("link/notes.md").unwrap(),
+            outside.canonicalize().unwrap().join("notes.md")
+        );
+    }
+}
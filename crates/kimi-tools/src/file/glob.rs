@@ -1,9 +1,11 @@
 //! Glob tool - find files using glob patterns.
 
+use super::{in_workspace, resolve_path};
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
+use kimi_core::Workspace;
 use serde::Deserialize;
-use std::path::Path;
+use std::path::PathBuf;
 
 /// Parameters for the Glob tool.
 #[derive(Debug, Deserialize)]
@@ -23,19 +25,21 @@ fn default_include_dirs() -> bool {
 }
 
 /// Tool for finding files using glob patterns.
-#[derive(Debug)]
-pub struct GlobTool;
+#[derive(Debug, Default)]
+pub struct GlobTool {
+    workspace: Option<Workspace>,
+}
 
 impl GlobTool {
     /// Create a new GlobTool instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
-}
 
-impl Default for GlobTool {
-    fn default() -> Self {
-        Self::new()
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
     }
 }
 
@@ -75,12 +79,12 @@ impl Tool for GlobTool {
         let params: GlobParams = serde_json::from_value(params)
             .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
 
-        // Determine the base directory
-        let base_dir = params
-            .directory
-            .as_deref()
-            .map(Path::new)
-            .unwrap_or_else(|| Path::new("."));
+        // Determine the base directory, the workspace's when there is one
+        let base_dir = match (&params.directory, &self.workspace) {
+            (Some(dir), workspace) => resolve_path(workspace.as_ref(), dir)?,
+            (None, Some(workspace)) => workspace.roots()[0].clone(),
+            (None, None) => PathBuf::from("."),
+        };
 
         // Validate that the base directory exists
         if !base_dir.exists() {
@@ -91,8 +95,8 @@ impl Tool for GlobTool {
         }
 
         // Build the full pattern
-        let full_pattern = if let Some(dir) = &params.directory {
-            format!("{}/{}", dir.trim_end_matches('/'), params.pattern)
+        let full_pattern = if params.directory.is_some() || self.workspace.is_some() {
+            format!("{}/{}", base_dir.display().to_string().trim_end_matches('/'), params.pattern)
         } else {
             params.pattern.clone()
         };
@@ -107,6 +111,11 @@ impl Tool for GlobTool {
         for entry in glob_matches {
             match entry {
                 Ok(path) => {
+                    // Leave out what the pattern reaches outside the workspace
+                    if !in_workspace(self.workspace.as_ref(), &path) {
+                        continue;
+                    }
+
                     // Check if we should include this entry
                     let is_dir = path.is_dir();
                     if is_dir && !params.include_dirs {
@@ -148,4 +157,23 @@ mod tests {
         assert_eq!(tool.name(), "Glob");
         assert!(!tool.description().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_glob_stays_in_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::write(root.join("src/lib.rs"), "").unwrap();
+        std::fs::write(temp.path().join("secret.rs"), "").unwrap();
+        let workspace = Workspace::new(&root).unwrap();
+        let tool = GlobTool::new().with_workspace(workspace.clone());
+
+        let output = tool.execute(serde_json::json!({"pattern": "**/*.rs"})).await.unwrap();
+        assert_eq!(output, serde_json::json!(workspace.roots()[0].join("src/lib.rs").display().to_string()));
+
+        let output = tool.execute(serde_json::json!({"pattern": "../*.rs"})).await.unwrap();
+        assert_eq!(output, "No files found matching the pattern.");
+        let params = serde_json::json!({"pattern": "*.rs", "directory": temp.path()});
+        assert!(tool.execute(params).await.is_err());
+    }
 }
@@ -1,14 +1,29 @@
 //! Grep tool - search file contents using regex.
+//!
+//! The search works like ripgrep, on the same crates: files are walked with
+//! `ignore`, so `.gitignore` rules and hidden files are respected, and each
+//! is searched with `grep-searcher`, which skips binary files. Matches are
+//! returned as JSON, with file, line, column and a preview of the line.
 
+use super::{in_workspace, resolve_path};
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
-use regex::Regex;
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::path::Path;
+use grep_matcher::Matcher;
+use grep_regex::{RegexMatcher, RegexMatcherBuilder};
+use grep_searcher::{BinaryDetection, Searcher, SearcherBuilder, Sink, SinkContext, SinkContextKind, SinkMatch};
+use ignore::overrides::OverrideBuilder;
+use ignore::types::TypesBuilder;
+use ignore::WalkBuilder;
+use kimi_core::Workspace;
+use kosong_rs::tooling::ToolSchema;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Longest preview of a matched or context line, in characters
+const MAX_PREVIEW_CHARS: usize = 500;
 
 /// Output mode for grep results.
-#[derive(Debug, Default, Deserialize)]
+#[derive(Debug, Default, Deserialize, ToolSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum OutputMode {
     /// Show matching lines.
@@ -21,7 +36,7 @@ pub enum OutputMode {
 }
 
 /// Parameters for the Grep tool.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToolSchema)]
 pub struct GrepParams {
     /// The regular expression pattern to search for.
     pub pattern: String,
@@ -43,9 +58,15 @@ pub struct GrepParams {
     /// Case insensitive search.
     #[serde(default)]
     pub case_insensitive: bool,
+    /// Let the pattern match across lines, e.g. with \n.
+    #[serde(default)]
+    pub multiline: bool,
     /// Limit output to first N lines.
     #[serde(default)]
     pub head_limit: Option<usize>,
+    /// Stop searching a file after N matches.
+    #[serde(default)]
+    pub max_count: Option<usize>,
     /// Output mode.
     #[serde(default)]
     pub output_mode: OutputMode,
@@ -54,129 +75,210 @@ pub struct GrepParams {
     pub file_type: Option<String>,
 }
 
+/// A line around a match.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct ContextLine {
+    line: u64,
+    text: String,
+}
+
 /// A single match result.
-#[derive(Debug)]
-struct MatchResult {
-    file_path: String,
-    line_number: usize,
-    line_content: String,
-    before_lines: Vec<(usize, String)>,
-    after_lines: Vec<(usize, String)>,
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct GrepMatch {
+    file: String,
+    /// Line the match starts on, from 1
+    line: u64,
+    /// Character in the line the match starts at, from 1
+    column: usize,
+    /// The matched line, or lines for a multiline match
+    preview: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    before: Vec<ContextLine>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    after: Vec<ContextLine>,
+}
+
+/// Collects the matches in one file.
+struct MatchSink<'a> {
+    matcher: &'a RegexMatcher,
+    file: &'a str,
+    matches: Vec<GrepMatch>,
+    /// Context lines waiting for the match they come before
+    before: Vec<ContextLine>,
+    /// Matches after which the search of the file stops
+    limit: usize,
+}
+
+impl Sink for MatchSink<'_> {
+    type Error = std::io::Error;
+
+    fn matched(&mut self, _searcher: &Searcher, mat: &SinkMatch<'_>) -> Result<bool, Self::Error> {
+        let bytes = mat.bytes();
+        let start = self
+            .matcher
+            .find(bytes)
+            .ok()
+            .flatten()
+            .map_or(0, |m| m.start());
+        let line_start = bytes[..start].iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        self.matches.push(GrepMatch {
+            file: self.file.to_string(),
+            line: mat.line_number().unwrap_or(0) + bytes[..line_start].iter().filter(|&&b| b == b'\n').count() as u64,
+            column: String::from_utf8_lossy(&bytes[line_start..start]).chars().count() + 1,
+            preview: preview(bytes),
+            before: std::mem::take(&mut self.before),
+            after: Vec::new(),
+        });
+        Ok(self.matches.len() < self.limit)
+    }
+
+    fn context(&mut self, _searcher: &Searcher, context: &SinkContext<'_>) -> Result<bool, Self::Error> {
+        let line = ContextLine {
+            line: context.line_number().unwrap_or(0),
+            text: preview(context.bytes()),
+        };
+        match (context.kind(), self.matches.last_mut()) {
+            (SinkContextKind::After, Some(last)) => last.after.push(line),
+            (SinkContextKind::Before, _) => self.before.push(line),
+            _ => {}
+        }
+        Ok(true)
+    }
+}
+
+/// `bytes` as text without the line terminator, cut to
+/// [`MAX_PREVIEW_CHARS`]
+fn preview(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(bytes);
+    let text = text.trim_end_matches(['\n', '\r']);
+    match text.char_indices().nth(MAX_PREVIEW_CHARS) {
+        Some((cut, _)) => format!("{}...", &text[..cut]),
+        None => text.to_string(),
+    }
 }
 
 /// Tool for searching file contents using regex.
-#[derive(Debug)]
-pub struct GrepTool;
+#[derive(Debug, Default)]
+pub struct GrepTool {
+    workspace: Option<Workspace>,
+}
 
 impl GrepTool {
     /// Create a new GrepTool instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
 
-    /// Get the glob pattern from file type or provided glob.
-    fn get_glob_pattern(&self, params: &GrepParams) -> Option<String> {
-        if let Some(ref glob) = params.glob {
-            return Some(glob.clone());
-        }
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
 
-        params.file_type.as_ref().map(|ft| match ft.as_str() {
-            "rs" | "rust" => "*.rs".to_string(),
-            "py" | "python" => "*.py".to_string(),
-            "js" => "*.js".to_string(),
-            "ts" | "typescript" => "*.{ts,tsx}".to_string(),
-            "go" => "*.go".to_string(),
-            "java" => "*.java".to_string(),
-            _ => format!("*.{ft}"),
-        })
+    /// The file or directory to search.
+    fn search_path(&self, params: &GrepParams) -> Result<PathBuf, ToolError> {
+        let path = match (&params.path, &self.workspace) {
+            (Some(path), workspace) => resolve_path(workspace.as_ref(), path)?,
+            (None, Some(workspace)) => workspace.roots()[0].clone(),
+            (None, None) => PathBuf::from("."),
+        };
+        if !path.exists() {
+            return Err(ToolError::new(format!("Path does not exist: {}", path.display())));
+        }
+        Ok(path)
     }
 
-    /// Search a single file for matches.
-    async fn search_file(
-        &self,
-        file_path: &Path,
-        regex: &Regex,
-        params: &GrepParams,
-    ) -> Result<Vec<MatchResult>, ToolError> {
-        let content = tokio::fs::read_to_string(file_path).await.map_err(|e| {
-            ToolError::new(format!("Failed to read file '{}': {e}", file_path.display()))
-        })?;
-
-        let lines: Vec<&str> = content.lines().collect();
-        let mut matches = Vec::new();
+    /// Files to search under `search_path`, sorted, with ignored, hidden
+    /// and filtered out ones left out.
+    fn collect_files(&self, search_path: &Path, params: &GrepParams) -> Result<Vec<PathBuf>, ToolError> {
+        let mut walker = WalkBuilder::new(search_path);
+        walker.sort_by_file_name(|a, b| a.cmp(b));
+
+        if let Some(glob) = &params.glob {
+            let overrides = OverrideBuilder::new(search_path)
+                .add(glob)
+                .and_then(|builder| builder.build())
+                .map_err(|e| ToolError::new(format!("Invalid glob pattern '{glob}': {e}")))?;
+            walker.overrides(overrides);
+        }
 
-        let before_ctx = params.context.or(params.before_context).unwrap_or(0);
-        let after_ctx = params.context.or(params.after_context).unwrap_or(0);
-
-        for (i, line) in lines.iter().enumerate() {
-            if regex.is_match(line) {
-                let line_number = i + 1;
-
-                // Get context lines
-                let before_start = i.saturating_sub(before_ctx);
-                let before_lines: Vec<(usize, String)> = (before_start..i)
-                    .map(|j| (j + 1, lines[j].to_string()))
-                    .collect();
-
-                let after_end = (i + 1 + after_ctx).min(lines.len());
-                let after_lines: Vec<(usize, String)> = (i + 1..after_end)
-                    .map(|j| (j + 1, lines[j].to_string()))
-                    .collect();
-
-                matches.push(MatchResult {
-                    file_path: file_path.to_string_lossy().to_string(),
-                    line_number,
-                    line_content: line.to_string(),
-                    before_lines,
-                    after_lines,
-                });
+        if let Some(file_type) = &params.file_type {
+            let mut types = TypesBuilder::new();
+            types.add_defaults();
+            // A type ripgrep does not know is taken as a file extension
+            if !types.definitions().iter().any(|def| def.name() == file_type) {
+                types
+                    .add(file_type, &format!("*.{file_type}"))
+                    .map_err(|e| ToolError::new(format!("Invalid file type '{file_type}': {e}")))?;
             }
+            let types = types
+                .select(file_type)
+                .build()
+                .map_err(|e| ToolError::new(format!("Invalid file type '{file_type}': {e}")))?;
+            walker.types(types);
         }
 
-        Ok(matches)
+        Ok(walker
+            .build()
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .map(|entry| entry.into_path())
+            .filter(|path| in_workspace(self.workspace.as_ref(), path))
+            .collect())
     }
 
-    /// Collect all files to search.
-    async fn collect_files(&self, params: &GrepParams) -> Result<Vec<std::path::PathBuf>, ToolError> {
-        let search_path = params
-            .path
-            .as_deref()
-            .map(Path::new)
-            .unwrap_or_else(|| Path::new("."));
-
-        let mut files = Vec::new();
-
-        if search_path.is_file() {
-            files.push(search_path.to_path_buf());
-        } else if search_path.is_dir() {
-            let glob_pattern = self.get_glob_pattern(params);
-
-            let pattern = if let Some(g) = glob_pattern {
-                format!("{}/{}", search_path.display(), g)
-            } else {
-                format!("{}/**/*", search_path.display())
+    /// Search `files`, stopping once `limit` matches are found; whether
+    /// the limit was reached is returned with the matches.
+    fn search(files: &[PathBuf], params: &GrepParams, limit: Option<usize>) -> Result<(Vec<GrepMatch>, bool), ToolError> {
+        let mut builder = RegexMatcherBuilder::new();
+        builder.case_insensitive(params.case_insensitive).multi_line(params.multiline);
+        if !params.multiline {
+            builder.line_terminator(Some(b'\n'));
+        }
+        let matcher = builder
+            .build(&params.pattern)
+            .map_err(|e| ToolError::new(format!("Invalid regex pattern '{}': {e}", params.pattern)))?;
+
+        let mut searcher = SearcherBuilder::new()
+            .binary_detection(BinaryDetection::quit(b'\x00'))
+            .line_number(true)
+            .multi_line(params.multiline)
+            .before_context(params.context.or(params.before_context).unwrap_or(0))
+            .after_context(params.context.or(params.after_context).unwrap_or(0))
+            .build();
+
+        let per_file = match params.output_mode {
+            // One match is enough to list the file
+            OutputMode::FilesWithMatches => 1,
+            _ => params.max_count.unwrap_or(usize::MAX),
+        };
+        // Only matches are limited here; files and counts are cut afterwards
+        let limit = match params.output_mode {
+            OutputMode::Content => limit,
+            _ => None,
+        };
+        let mut matches = Vec::new();
+        for file in files {
+            let remaining = match limit {
+                Some(limit) if matches.len() >= limit => return Ok((matches, true)),
+                Some(limit) => limit - matches.len(),
+                None => usize::MAX,
             };
-
-            let glob_matches = glob::glob(&pattern).map_err(|e| {
-                ToolError::new(format!("Invalid glob pattern: {e}"))
-            })?;
-
-            for entry in glob_matches {
-                if let Ok(path) = entry {
-                    if path.is_file() {
-                        files.push(path);
-                    }
-                }
+            let name = file.strip_prefix(".").unwrap_or(file).to_string_lossy();
+            let mut sink = MatchSink {
+                matcher: &matcher,
+                file: &name,
+                matches: Vec::new(),
+                before: Vec::new(),
+                limit: per_file.min(remaining),
+            };
+            // Files that cannot be read are skipped
+            if searcher.search_path(&matcher, file, &mut sink).is_ok() {
+                matches.extend(sink.matches);
             }
         }
-
-        Ok(files)
-    }
-}
-
-impl Default for GrepTool {
-    fn default() -> Self {
-        Self::new()
+        let reached = limit.is_some_and(|limit| matches.len() >= limit);
+        Ok((matches, reached))
     }
 }
 
@@ -187,147 +289,67 @@ impl Tool for GrepTool {
     }
 
     fn description(&self) -> &str {
-        "Search file contents using regular expressions. Based on ripgrep with support for context lines, file filtering, and multiple output modes."
+        "Search file contents using regular expressions, the way ripgrep does: files \
+         ignored by .gitignore, hidden files and binary files are skipped. Supports \
+         context lines, file filtering, multiline patterns and multiple output modes. \
+         Matches are returned as JSON with file, line, column and a preview of the line."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
-        serde_json::json!({
-            "type": "object",
-            "properties": {
-                "pattern": {
-                    "type": "string",
-                    "description": "The regular expression pattern to search for"
-                },
-                "path": {
-                    "type": "string",
-                    "description": "File or directory to search in (defaults to current working directory)"
-                },
-                "glob": {
-                    "type": "string",
-                    "description": "Glob pattern to filter files (e.g. *.js, *.{ts,tsx})"
-                },
-                "before_context": {
-                    "type": "integer",
-                    "description": "Number of lines to show before each match"
-                },
-                "after_context": {
-                    "type": "integer",
-                    "description": "Number of lines to show after each match"
-                },
-                "context": {
-                    "type": "integer",
-                    "description": "Number of lines to show before and after each match"
-                },
-                "case_insensitive": {
-                    "type": "boolean",
-                    "description": "Case insensitive search"
-                },
-                "head_limit": {
-                    "type": "integer",
-                    "description": "Limit output to first N lines"
-                },
-                "output_mode": {
-                    "type": "string",
-                    "enum": ["content", "files_with_matches", "count_matches"],
-                    "description": "Output mode"
-                },
-                "file_type": {
-                    "type": "string",
-                    "description": "File type to search (e.g., py, rust, js)"
-                }
-            },
-            "required": ["pattern"]
-        })
+        GrepParams::schema()
     }
 
     async fn execute(&self, params: serde_json::Value) -> ToolResult {
-        let params: GrepParams = serde_json::from_value(params)
-            .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
-
-        // Build the regex
-        let mut regex_builder = regex::RegexBuilder::new(&params.pattern);
-        regex_builder.case_insensitive(params.case_insensitive);
-
-        let regex = regex_builder.build().map_err(|e| {
-            ToolError::new(format!("Invalid regex pattern '{}': {e}", params.pattern))
-        })?;
-
-        // Collect files to search
-        let files = self.collect_files(&params).await?;
-
-        // Search all files
-        let mut all_matches: Vec<MatchResult> = Vec::new();
-        let mut file_match_counts: HashMap<String, usize> = HashMap::new();
-
-        for file in files {
-            match self.search_file(&file, &regex, &params).await {
-                Ok(matches) => {
-                    if !matches.is_empty() {
-                        file_match_counts.insert(file.to_string_lossy().to_string(), matches.len());
-                        all_matches.extend(matches);
+        let params = GrepParams::from_params(params)?;
+        let search_path = self.search_path(&params)?;
+        let files = self.collect_files(&search_path, &params)?;
+
+        // Searching blocks, so it runs off the async threads
+        tokio::task::spawn_blocking(move || {
+            let limit = params.head_limit;
+            let (matches, truncated) = Self::search(&files, &params, limit)?;
+
+            let mut output = match params.output_mode {
+                OutputMode::Content => serde_json::json!({ "matches": matches }),
+                OutputMode::FilesWithMatches => {
+                    let mut files: Vec<&str> = matches.iter().map(|m| m.file.as_str()).collect();
+                    files.dedup();
+                    let truncated = limit.is_some_and(|limit| files.len() > limit);
+                    files.truncate(limit.unwrap_or(usize::MAX));
+                    let mut output = serde_json::json!({ "files": files });
+                    if truncated {
+                        output["truncated"] = serde_json::json!(true);
                     }
+                    output
                 }
-                Err(_) => {
-                    // Skip files that can't be read (e.g., binary files)
-                    continue;
-                }
-            }
-        }
-
-        // Apply head limit if specified
-        if let Some(limit) = params.head_limit {
-            all_matches.truncate(limit);
-        }
-
-        // Format output based on mode
-        let output = match params.output_mode {
-            OutputMode::FilesWithMatches => {
-                let mut files: Vec<String> = file_match_counts.keys().cloned().collect();
-                files.sort();
-                files.join("\n")
-            }
-            OutputMode::CountMatches => {
-                let mut entries: Vec<(String, usize)> = file_match_counts.into_iter().collect();
-                entries.sort_by(|a, b| a.0.cmp(&b.0));
-                entries
-                    .into_iter()
-                    .map(|(file, count)| format!("{count}:{file}"))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            }
-            OutputMode::Content => {
-                let mut lines = Vec::new();
-                let mut current_file: Option<&str> = None;
-
-                for m in &all_matches {
-                    // Print file header if changed
-                    if current_file != Some(&m.file_path) {
-                        if current_file.is_some() {
-                            lines.push(String::new());
+                OutputMode::CountMatches => {
+                    let mut counts: Vec<(&str, usize)> = Vec::new();
+                    for m in &matches {
+                        match counts.last_mut() {
+                            Some((file, count)) if *file == m.file => *count += 1,
+                            _ => counts.push((&m.file, 1)),
                         }
-                        lines.push(format!("{}", m.file_path));
-                        current_file = Some(&m.file_path);
-                    }
-
-                    // Print before context
-                    for (line_num, content) in &m.before_lines {
-                        lines.push(format!("{line_num}-{content}"));
                     }
-
-                    // Print match
-                    lines.push(format!("{}:{}", m.line_number, m.line_content));
-
-                    // Print after context
-                    for (line_num, content) in &m.after_lines {
-                        lines.push(format!("{line_num}-{content}"));
+                    let truncated = limit.is_some_and(|limit| counts.len() > limit);
+                    counts.truncate(limit.unwrap_or(usize::MAX));
+                    let counts: Vec<_> = counts
+                        .into_iter()
+                        .map(|(file, count)| serde_json::json!({ "file": file, "count": count }))
+                        .collect();
+                    let mut output = serde_json::json!({ "counts": counts });
+                    if truncated {
+                        output["truncated"] = serde_json::json!(true);
                     }
+                    output
                 }
-
-                lines.join("\n")
+            };
+            if truncated {
+                output["truncated"] = serde_json::json!(true);
             }
-        };
-
-        Ok(serde_json::json!(output))
+            Ok(output)
+        })
+        .await
+        .map_err(|e| ToolError::new(format!("Search failed: {e}")))?
     }
 }
 
@@ -341,4 +363,96 @@ mod tests {
         assert_eq!(tool.name(), "Grep");
         assert!(!tool.description().is_empty());
     }
+
+    #[test]
+    fn test_parameters_schema() {
+        let schema = GrepTool::new().parameters_schema();
+        assert_eq!(schema["required"], serde_json::json!(["pattern"]));
+        assert_eq!(
+            schema["properties"]["output_mode"]["enum"],
+            serde_json::json!(["content", "files_with_matches", "count_matches"])
+        );
+        assert_eq!(schema["properties"]["head_limit"]["description"], "Limit output to first N lines");
+    }
+
+    fn project() -> tempfile::TempDir {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path();
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(root.join("src/main.rs"), "fn main() {\n    let answer = 42;\n    println!(\"{answer}\");\n}\n").unwrap();
+        std::fs::write(root.join("src/lib.rs"), "// TODO: answer\npub fn answer() -> u32 {\n    42\n}\n").unwrap();
+        std::fs::write(root.join("target/out.rs"), "let answer = 0;\n").unwrap();
+        std::fs::write(root.join("data.bin"), b"answer\x00\x01\x02").unwrap();
+        // Only a repository's .gitignore applies
+        std::fs::create_dir(root.join(".git")).unwrap();
+        temp
+    }
+
+    async fn grep(root: &Path, params: serde_json::Value) -> serde_json::Value {
+        let tool = GrepTool::new().with_workspace(Workspace::new(root).unwrap());
+        tool.execute(params).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_structured_matches() {
+        let temp = project();
+        let output = grep(temp.path(), serde_json::json!({"pattern": "answer", "glob": "*.rs", "before_context": 1})).await;
+        let root = temp.path().canonicalize().unwrap();
+        let file = |name: &str| root.join(name).to_string_lossy().to_string();
+        let matches = output["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 4, "{output}");
+        assert_eq!(
+            matches[0],
+            serde_json::json!({"file": file("src/lib.rs"), "line": 1, "column": 10, "preview": "// TODO: answer"})
+        );
+        assert_eq!(
+            matches[2],
+            serde_json::json!({
+                "file": file("src/main.rs"),
+                "line": 2,
+                "column": 9,
+                "preview": "    let answer = 42;",
+                "before": [{"line": 1, "text": "fn main() {"}],
+            })
+        );
+        // Ignored and binary files are skipped
+        assert!(!output.to_string().contains("target") && !output.to_string().contains("data.bin"));
+    }
+
+    #[tokio::test]
+    async fn test_output_modes_and_limits() {
+        let temp = project();
+        let output = grep(temp.path(), serde_json::json!({"pattern": "ANSWER", "case_insensitive": true, "output_mode": "count_matches"})).await;
+        assert_eq!(output["counts"].as_array().unwrap().len(), 2);
+        assert_eq!(output["counts"][1]["count"], 2);
+
+        let output = grep(temp.path(), serde_json::json!({"pattern": "42", "file_type": "rust", "output_mode": "files_with_matches"})).await;
+        assert_eq!(output["files"].as_array().unwrap().len(), 2);
+
+        let output = grep(temp.path(), serde_json::json!({"pattern": "answer", "head_limit": 1})).await;
+        assert_eq!(output["matches"].as_array().unwrap().len(), 1);
+        assert_eq!(output["truncated"], true);
+
+        let output = grep(temp.path(), serde_json::json!({"pattern": "answer", "max_count": 1})).await;
+        assert_eq!(output["matches"].as_array().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_multiline() {
+        let temp = project();
+        let line_mode = GrepTool::new()
+            .with_workspace(Workspace::new(temp.path()).unwrap())
+            .execute(serde_json::json!({"pattern": "\\{\\n\\s+42"}))
+            .await;
+        assert!(line_mode.is_err());
+
+        let output = grep(temp.path(), serde_json::json!({"pattern": "\\{\\n\\s+42", "multiline": true})).await;
+        let matches = output["matches"].as_array().unwrap();
+        assert_eq!(matches.len(), 1, "{output}");
+        assert_eq!(matches[0]["line"], 2);
+        assert_eq!(matches[0]["column"], 24);
+        assert_eq!(matches[0]["preview"], "pub fn answer() -> u32 {\n    42");
+    }
 }
@@ -1,13 +1,42 @@
 //! File operation tools.
+//!
+//! Given a [`Workspace`] with `with_workspace`, the tools refuse paths
+//! outside it, symlinks included, and leave such files out of search
+//! results. Without one they go wherever the user can.
 
 pub mod glob;
 pub mod grep;
+pub mod notebook;
 pub mod read;
 pub mod replace;
+pub mod search;
 pub mod write;
 
 pub use glob::GlobTool;
 pub use grep::GrepTool;
+pub use notebook::EditNotebookCellTool;
 pub use read::ReadFileTool;
 pub use replace::StrReplaceFileTool;
+pub use search::SearchCodebaseTool;
 pub use write::WriteFileTool;
+
+use crate::ToolError;
+use kimi_core::Workspace;
+use std::path::{Path, PathBuf};
+
+/// The path a file tool works on, checked against `workspace` if there is
+/// one
+pub(crate) fn resolve_path(workspace: Option<&Workspace>, path: &str) -> Result<PathBuf, ToolError> {
+    match workspace {
+        Some(workspace) => workspace.resolve(path).map_err(|e| ToolError::new(e.to_string())),
+        None => Ok(PathBuf::from(path)),
+    }
+}
+
+/// Whether `path`, found by a search, may be reported
+pub(crate) fn in_workspace(workspace: Option<&Workspace>, path: &Path) -> bool {
+    match workspace {
+        Some(workspace) => path.canonicalize().is_ok_and(|path| workspace.contains(&path)),
+        None => true,
+    }
+}
@@ -0,0 +1,387 @@
+//! Jupyter notebook support.
+//!
+//! `ReadFile` shows an `.ipynb` file through [`render_notebook`]: each cell
+//! under a header with its index and type, code in fenced blocks in the
+//! notebook's language, followed by its text outputs. `EditNotebookCell`
+//! replaces, inserts or deletes a cell by that index, editing the notebook
+//! as JSON and writing it back the way Jupyter does, with sorted keys and
+//! one-space indentation.
+
+use super::resolve_path;
+use crate::{Tool, ToolError, ToolResult};
+use async_trait::async_trait;
+use kimi_core::Workspace;
+use kosong_rs::tooling::ToolSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Longest output of a cell shown by [`render_notebook`], in characters
+const MAX_OUTPUT_CHARS: usize = 2000;
+
+/// Whether `path` names a Jupyter notebook
+pub(crate) fn is_notebook(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("ipynb"))
+}
+
+/// Text of a notebook field stored as a string or a list of lines
+fn text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// `text` as Jupyter stores sources, a list of lines that keep their `\n`
+fn lines(text: &str) -> Value {
+    json!(text.split_inclusive('\n').collect::<Vec<_>>())
+}
+
+/// Parse `content` as a notebook, failing unless it has a list of cells
+fn parse(content: &str, path: &str) -> Result<Value, ToolError> {
+    let notebook: Value = serde_json::from_str(content)
+        .map_err(|e| ToolError::new(format!("'{path}' is not a valid notebook: {e}")))?;
+    if !notebook.get("cells").is_some_and(Value::is_array) {
+        return Err(ToolError::new(format!("'{path}' is not a valid notebook: it has no cells")));
+    }
+    Ok(notebook)
+}
+
+/// Cut `text` to [`MAX_OUTPUT_CHARS`], noting how much was left out
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((cut, _)) => format!("{}\n[... {} more characters]", &text[..cut], text[cut..].chars().count()),
+        None => text.to_string(),
+    }
+}
+
+/// The notebook in `content` as markdown, for reading
+pub fn render_notebook(content: &str, path: &str) -> Result<String, ToolError> {
+    let notebook = parse(content, path)?;
+    let language = notebook["metadata"]["kernelspec"]["language"]
+        .as_str()
+        .or_else(|| notebook["metadata"]["language_info"]["name"].as_str())
+        .unwrap_or("python");
+    let cells = notebook["cells"].as_array().map(Vec::as_slice).unwrap_or_default();
+
+    let mut out = format!("Jupyter notebook with {} cells ({language})\n", cells.len());
+    for (index, cell) in cells.iter().enumerate() {
+        let cell_type = cell["cell_type"].as_str().unwrap_or("unknown");
+        let source = text(&cell["source"]);
+        out.push_str(&format!("\n## Cell {index} [{cell_type}]\n\n"));
+        match cell_type {
+            "markdown" => out.push_str(source.trim_end()),
+            "code" => out.push_str(&format!("```{language}\n{}\n```", source.trim_end())),
+            _ => out.push_str(&format!("```\n{}\n```", source.trim_end())),
+        }
+        out.push('\n');
+
+        for output in cell["outputs"].as_array().map(Vec::as_slice).unwrap_or_default() {
+            let shown = match output["output_type"].as_str() {
+                Some("stream") => text(&output["text"]),
+                Some("error") => format!(
+                    "{}: {}",
+                    output["ename"].as_str().unwrap_or("Error"),
+                    output["evalue"].as_str().unwrap_or_default()
+                ),
+                _ => match output["data"].get("text/plain") {
+                    Some(plain) => text(plain),
+                    None => {
+                        let kinds: Vec<&str> = output["data"]
+                            .as_object()
+                            .map(|data| data.keys().map(String::as_str).collect())
+                            .unwrap_or_default();
+                        format!("[{} output]", kinds.join(", "))
+                    }
+                },
+            };
+            out.push_str(&format!("\nOutput:\n```\n{}\n```\n", truncate(shown.trim_end())));
+        }
+    }
+    Ok(out)
+}
+
+/// Type of a notebook cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToolSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CellType {
+    /// Code run by the kernel.
+    Code,
+    /// Markdown text.
+    Markdown,
+    /// Raw text, left as it is.
+    Raw,
+}
+
+/// How to edit the cell.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, ToolSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EditMode {
+    /// Replace the cell's source.
+    #[default]
+    Replace,
+    /// Insert a new cell at the index, before the cell there.
+    Insert,
+    /// Delete the cell.
+    Delete,
+}
+
+/// Parameters for the EditNotebookCell tool.
+#[derive(Debug, Deserialize, ToolSchema)]
+pub struct EditNotebookCellParams {
+    /// The path to the .ipynb notebook.
+    pub path: String,
+    /// Index of the cell, from 0, as ReadFile shows it.
+    pub cell_index: usize,
+    /// The new source of the cell; not needed to delete it.
+    #[serde(default)]
+    pub new_source: Option<String>,
+    /// Type of the cell; a replaced cell keeps its type and an inserted one is code by default.
+    #[serde(default)]
+    pub cell_type: Option<CellType>,
+    /// Whether to replace, insert or delete the cell.
+    #[serde(default)]
+    pub edit_mode: EditMode,
+}
+
+/// A new, empty cell of `cell_type` with `source`
+fn new_cell(cell_type: CellType, source: &str, with_id: bool) -> Value {
+    let mut cell = json!({
+        "cell_type": cell_type,
+        "metadata": {},
+        "source": lines(source),
+    });
+    if cell_type == CellType::Code {
+        cell["execution_count"] = Value::Null;
+        cell["outputs"] = json!([]);
+    }
+    if with_id {
+        cell["id"] = json!(uuid::Uuid::new_v4().simple().to_string()[..8]);
+    }
+    cell
+}
+
+/// Apply `params` to `notebook`, returning what was done
+fn edit_cell(notebook: &mut Value, params: &EditNotebookCellParams) -> Result<String, ToolError> {
+    // Cell IDs are required from nbformat 4.5 on
+    let with_id = notebook["nbformat"].as_u64() == Some(4) && notebook["nbformat_minor"].as_u64().unwrap_or(0) >= 5;
+    let cells = notebook["cells"].as_array_mut().expect("parse checks the notebook has cells");
+    let index = params.cell_index;
+    let count = cells.len();
+    let out_of_range = || ToolError::new(format!("Cell {index} does not exist; the notebook has {count} cells"));
+    let source = || {
+        params.new_source.as_deref().ok_or_else(|| {
+            ToolError::InvalidParameters(format!("new_source is needed to {:?} a cell", params.edit_mode).to_lowercase())
+        })
+    };
+
+    match params.edit_mode {
+        EditMode::Replace => {
+            let cell = cells.get_mut(index).ok_or_else(out_of_range)?;
+            let source = source()?;
+            let current = cell["cell_type"].as_str().unwrap_or_default().to_string();
+            match params.cell_type {
+                Some(cell_type) if serde_json::to_value(cell_type).ok() != Some(json!(current)) => {
+                    // A different type starts the cell afresh, keeping its ID
+                    let id = cell.get("id").cloned();
+                    *cell = new_cell(cell_type, source, false);
+                    if let Some(id) = id {
+                        cell["id"] = id;
+                    }
+                }
+                _ => {
+                    cell["source"] = lines(source);
+                    // What the old code printed no longer applies
+                    if current == "code" {
+                        cell["outputs"] = json!([]);
+                        cell["execution_count"] = Value::Null;
+                    }
+                }
+            }
+            Ok(format!("Replaced the source of cell {index}"))
+        }
+        EditMode::Insert => {
+            if index > count {
+                return Err(out_of_range());
+            }
+            let cell_type = params.cell_type.unwrap_or(CellType::Code);
+            cells.insert(index, new_cell(cell_type, source()?, with_id));
+            Ok(format!("Inserted a new cell at {index}"))
+        }
+        EditMode::Delete => {
+            if index >= count {
+                return Err(out_of_range());
+            }
+            cells.remove(index);
+            Ok(format!("Deleted cell {index}"))
+        }
+    }
+}
+
+/// `notebook` as Jupyter writes it: sorted keys, one-space indentation
+/// and a final newline
+fn to_notebook_json(notebook: &Value) -> Result<String, ToolError> {
+    let mut out = Vec::new();
+    let mut serializer = serde_json::Serializer::with_formatter(&mut out, serde_json::ser::PrettyFormatter::with_indent(b" "));
+    notebook
+        .serialize(&mut serializer)
+        .map_err(|e| ToolError::new(format!("Failed to serialize the notebook: {e}")))?;
+    let mut json = String::from_utf8(out).map_err(|e| ToolError::new(e.to_string()))?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// Tool for editing the cells of Jupyter notebooks.
+#[derive(Debug, Default)]
+pub struct EditNotebookCellTool {
+    workspace: Option<Workspace>,
+}
+
+impl EditNotebookCellTool {
+    /// Create a new EditNotebookCellTool instance.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
+    }
+}
+
+#[async_trait]
+impl Tool for EditNotebookCellTool {
+    fn name(&self) -> &str {
+        "EditNotebookCell"
+    }
+
+    fn description(&self) -> &str {
+        "Edit a cell of a Jupyter notebook (.ipynb) by its index, as ReadFile shows it: \
+         replace its source, insert a new cell before it, or delete it. The rest of the \
+         notebook is left as it is; a replaced code cell loses its outputs."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        EditNotebookCellParams::schema()
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> ToolResult {
+        let params = EditNotebookCellParams::from_params(params)?;
+        let path = resolve_path(self.workspace.as_ref(), &params.path)?;
+        if !is_notebook(&path) {
+            return Err(ToolError::new(format!("'{}' is not a Jupyter notebook (.ipynb)", params.path)));
+        }
+
+        let content = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(|e| ToolError::new(format!("Failed to read file '{}': {e}", params.path)))?;
+        let mut notebook = parse(&content, &params.path)?;
+        let message = edit_cell(&mut notebook, &params)?;
+        tokio::fs::write(&path, to_notebook_json(&notebook)?)
+            .await
+            .map_err(|e| ToolError::new(format!("Failed to write to file '{}': {e}", params.path)))?;
+
+        Ok(json!({
+            "output": "",
+            "message": format!("{message} of {}", params.path)
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A notebook as Jupyter saves it
+    const NOTEBOOK: &str = r##"{
+ "cells": [
+  {
+   "cell_type": "markdown",
+   "id": "a1b2c3d4",
+   "metadata": {},
+   "source": [
+    "# Analysis\n",
+    "Loading the data"
+   ]
+  },
+  {
+   "cell_type": "code",
+   "execution_count": 1,
+   "id": "e5f6a7b8",
+   "metadata": {},
+   "outputs": [
+    {
+     "name": "stdout",
+     "output_type": "stream",
+     "text": [
+      "3\n"
+     ]
+    }
+   ],
+   "source": [
+    "x = 1 + 2\n",
+    "print(x)"
+   ]
+  }
+ ],
+ "metadata": {
+  "kernelspec": {
+   "display_name": "Python 3",
+   "language": "python",
+   "name": "python3"
+  }
+ },
+ "nbformat": 4,
+ "nbformat_minor": 5
+}
+"##;
+
+    #[test]
+    fn test_render_notebook() {
+        assert_eq!(
+            render_notebook(NOTEBOOK, "analysis.ipynb").unwrap(),
+            "Jupyter notebook with 2 cells (python)\n\
+             \n## Cell 0 [markdown]\n\n# Analysis\nLoading the data\n\
+             \n## Cell 1 [code]\n\n```python\nx = 1 + 2\nprint(x)\n```\n\
+             \nOutput:\n```\n3\n```\n"
+        );
+        assert!(render_notebook("{}", "empty.ipynb").unwrap_err().to_string().contains("it has no cells"));
+    }
+
+    #[tokio::test]
+    async fn test_edit_notebook_cell() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("analysis.ipynb");
+        std::fs::write(&path, NOTEBOOK).unwrap();
+        let tool = EditNotebookCellTool::new().with_workspace(Workspace::new(temp.path()).unwrap());
+        let edit = |params: Value| tool.execute(params);
+        let path_str = path.to_string_lossy().to_string();
+
+        // Saving an unchanged notebook writes it back byte for byte
+        let mut notebook = parse(NOTEBOOK, &path_str).unwrap();
+        assert_eq!(to_notebook_json(&notebook).unwrap(), NOTEBOOK);
+
+        edit(json!({"path": path_str, "cell_index": 1, "new_source": "x = 40 + 2\nprint(x)"})).await.unwrap();
+        notebook = parse(&std::fs::read_to_string(&path).unwrap(), &path_str).unwrap();
+        let cell = &notebook["cells"][1];
+        assert_eq!(cell["source"], json!(["x = 40 + 2\n", "print(x)"]));
+        assert_eq!(cell["outputs"], json!([]));
+        assert_eq!(cell["execution_count"], Value::Null);
+        assert_eq!(cell["id"], "e5f6a7b8");
+
+        edit(json!({"path": path_str, "cell_index": 1, "new_source": "## Result", "cell_type": "markdown", "edit_mode": "insert"})).await.unwrap();
+        edit(json!({"path": path_str, "cell_index": 0, "edit_mode": "delete"})).await.unwrap();
+        let rendered = render_notebook(&std::fs::read_to_string(&path).unwrap(), &path_str).unwrap();
+        assert!(rendered.starts_with("Jupyter notebook with 2 cells (python)\n\n## Cell 0 [markdown]\n\n## Result\n"), "{rendered}");
+        notebook = parse(&std::fs::read_to_string(&path).unwrap(), &path_str).unwrap();
+        assert_eq!(notebook["cells"][0]["id"].as_str().unwrap().len(), 8);
+        assert_eq!(notebook["metadata"]["kernelspec"]["name"], "python3");
+
+        let err = edit(json!({"path": path_str, "cell_index": 5, "new_source": "x"})).await.unwrap_err();
+        assert!(err.to_string().contains("Cell 5 does not exist; the notebook has 2 cells"), "{err}");
+        let err = edit(json!({"path": path_str, "cell_index": 0, "edit_mode": "insert"})).await.unwrap_err();
+        assert!(err.to_string().contains("new_source is needed to insert a cell"), "{err}");
+    }
+}
@@ -1,37 +1,87 @@
 //! ReadFile tool - reads text content from a file.
-
+//!
+//! Large files are read a page at a time: without a range, the first
+//! [`DEFAULT_LIMIT`] lines are returned, and whenever only part of a file is
+//! shown a note after it says which lines and how many there are, so the
+//! model can ask for the next page with `offset`. Jupyter notebooks are
+//! shown as markdown, a cell at a time, and paged the same way.
+
+use super::notebook::{is_notebook, render_notebook};
+use super::resolve_path;
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
+use kimi_core::Workspace;
+use kosong_rs::tooling::ToolSchema;
 use serde::Deserialize;
-use std::path::Path;
+
+/// Lines read when no limit is given
+pub const DEFAULT_LIMIT: usize = 2000;
 
 /// Parameters for the ReadFile tool.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, ToolSchema)]
 pub struct ReadFileParams {
     /// The path to the file to read.
     pub path: String,
     /// The line number to start reading from (1-indexed).
+    #[serde(default, alias = "line_offset")]
+    pub offset: Option<usize>,
+    /// The number of lines to read (2000 by default).
+    #[serde(default, alias = "n_lines")]
+    pub limit: Option<usize>,
+    /// Lines to read as start-end, e.g. 120-180, instead of offset and limit.
     #[serde(default)]
-    pub line_offset: Option<usize>,
-    /// The number of lines to read.
-    #[serde(default)]
-    pub n_lines: Option<usize>,
+    pub line_range: Option<String>,
+}
+
+impl ReadFileParams {
+    /// The 1-indexed first line to read and the number of lines
+    fn range(&self) -> Result<(usize, usize), ToolError> {
+        let Some(range) = &self.line_range else {
+            return Ok((self.offset.unwrap_or(1).max(1), self.limit.unwrap_or(DEFAULT_LIMIT)));
+        };
+        let invalid = || ToolError::InvalidParameters(format!("Invalid line_range '{range}', expected start-end, e.g. 120-180"));
+        let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+        let start: usize = start.trim().parse().map_err(|_| invalid())?;
+        let end: usize = match end.trim() {
+            "" => usize::MAX,
+            end => end.parse().map_err(|_| invalid())?,
+        };
+        if start == 0 || end < start {
+            return Err(invalid());
+        }
+        Ok((start, end - start + 1))
+    }
+}
+
+/// `n` with its digits grouped by thousands, e.g. 5,431
+fn group_digits(n: usize) -> String {
+    let digits = n.to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+    grouped
 }
 
 /// Tool for reading files.
-#[derive(Debug)]
-pub struct ReadFileTool;
+#[derive(Debug, Default)]
+pub struct ReadFileTool {
+    workspace: Option<Workspace>,
+}
 
 impl ReadFileTool {
     /// Create a new ReadFileTool instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
-}
 
-impl Default for ReadFileTool {
-    fn default() -> Self {
-        Self::new()
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
     }
 }
 
@@ -42,35 +92,20 @@ impl Tool for ReadFileTool {
     }
 
     fn description(&self) -> &str {
-        "Read text content from a file. Supports reading specific line ranges."
+        "Read text content from a file. Reads the first 2000 lines unless given a range; \
+         when only part of the file is shown, a note at the end says which lines, so \
+         large files can be read a page at a time with offset and limit, or line_range. \
+         Jupyter notebooks are shown as markdown with the index of each cell."
     }
 
     fn parameters_schema(&self) -> serde_json::Value {
-        serde_json::json!({
-            "type": "object",
-            "properties": {
-                "path": {
-                    "type": "string",
-                    "description": "The path to the file to read"
-                },
-                "line_offset": {
-                    "type": "integer",
-                    "description": "The line number to start reading from (1-indexed)"
-                },
-                "n_lines": {
-                    "type": "integer",
-                    "description": "The number of lines to read"
-                }
-            },
-            "required": ["path"]
-        })
+        ReadFileParams::schema()
     }
 
     async fn execute(&self, params: serde_json::Value) -> ToolResult {
-        let params: ReadFileParams = serde_json::from_value(params)
-            .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
+        let params = ReadFileParams::from_params(params)?;
 
-        let path = Path::new(&params.path);
+        let path = resolve_path(self.workspace.as_ref(), &params.path)?;
 
         // Check if file exists
         if !path.exists() {
@@ -89,33 +124,45 @@ impl Tool for ReadFileTool {
         }
 
         // Read the file content
-        let content = tokio::fs::read_to_string(path).await.map_err(|e| {
+        let content = tokio::fs::read_to_string(&path).await.map_err(|e| {
             ToolError::new(format!("Failed to read file '{}': {e}", params.path))
         })?;
-
-        // Handle line range if specified
-        let output = if params.line_offset.is_some() || params.n_lines.is_some() {
-            let lines: Vec<&str> = content.lines().collect();
-            let start = params.line_offset.unwrap_or(1).saturating_sub(1);
-            let end = if let Some(n) = params.n_lines {
-                (start + n).min(lines.len())
-            } else {
-                lines.len()
-            };
-
-            if start >= lines.len() {
-                return Err(ToolError::new(format!(
-                    "Line offset {} exceeds file length of {} lines",
-                    start + 1,
-                    lines.len()
-                )));
-            }
-
-            lines[start..end].join("\n")
+        let content = if is_notebook(&path) {
+            render_notebook(&content, &params.path)?
         } else {
             content
         };
 
+        let (first, count) = params.range()?;
+        let lines: Vec<&str> = content.lines().collect();
+        let total = lines.len();
+        if first == 1 && count >= total {
+            return Ok(serde_json::json!(content));
+        }
+        if first > total {
+            return Err(ToolError::new(format!(
+                "Line offset {} exceeds file length of {} lines",
+                first,
+                total
+            )));
+        }
+
+        // Only part of the file, with a note saying which
+        let last = first.saturating_add(count - 1).min(total);
+        let mut output = lines[first - 1..last].join("\n");
+        if first > 1 || last < total {
+            output.push_str(&format!(
+                "\n\n[Showing lines {}-{} of {}.{}]",
+                group_digits(first),
+                group_digits(last),
+                group_digits(total),
+                if last < total {
+                    format!(" Use offset {} to read on.", last + 1)
+                } else {
+                    String::new()
+                }
+            ));
+        }
         Ok(serde_json::json!(output))
     }
 }
@@ -123,6 +170,7 @@ impl Tool for ReadFileTool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
 
     #[tokio::test]
     async fn test_read_file() {
@@ -130,4 +178,45 @@ mod tests {
         assert_eq!(tool.name(), "ReadFile");
         assert!(!tool.description().is_empty());
     }
+
+    #[test]
+    fn test_group_digits() {
+        assert_eq!(group_digits(7), "7");
+        assert_eq!(group_digits(5431), "5,431");
+        assert_eq!(group_digits(1234567), "1,234,567");
+    }
+
+    async fn read(path: &Path, mut params: serde_json::Value) -> ToolResult {
+        params["path"] = serde_json::json!(path);
+        ReadFileTool::new().execute(params).await
+    }
+
+    #[tokio::test]
+    async fn test_read_pages() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("big.txt");
+        let content: Vec<String> = (1..=2500).map(|n| format!("line {n}")).collect();
+        std::fs::write(&path, content.join("\n")).unwrap();
+
+        let output = read(&path, serde_json::json!({})).await.unwrap();
+        let output = output.as_str().unwrap();
+        assert!(output.starts_with("line 1\nline 2\n"));
+        assert!(output.ends_with("line 2000\n\n[Showing lines 1-2,000 of 2,500. Use offset 2001 to read on.]"), "{output}");
+
+        let output = read(&path, serde_json::json!({"offset": 2001})).await.unwrap();
+        assert!(output.as_str().unwrap().ends_with("line 2500\n\n[Showing lines 2,001-2,500 of 2,500.]"));
+
+        let output = read(&path, serde_json::json!({"line_range": "10-12"})).await.unwrap();
+        assert_eq!(output, "line 10\nline 11\nline 12\n\n[Showing lines 10-12 of 2,500. Use offset 13 to read on.]");
+        // The names the parameters had before
+        let output = read(&path, serde_json::json!({"line_offset": 10, "n_lines": 3})).await.unwrap();
+        assert!(output.as_str().unwrap().starts_with("line 10\nline 11\nline 12\n\n"));
+
+        assert!(read(&path, serde_json::json!({"line_range": "12-10"})).await.is_err());
+        assert!(read(&path, serde_json::json!({"offset": 3000})).await.is_err());
+
+        // Small files come back whole
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        assert_eq!(read(&path, serde_json::json!({})).await.unwrap(), "one\ntwo\n");
+    }
 }
@@ -1,9 +1,10 @@
 //! StrReplaceFile tool - replace strings within a file.
 
+use super::resolve_path;
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
+use kimi_core::Workspace;
 use serde::Deserialize;
-use std::path::Path;
 
 /// A single edit operation.
 #[derive(Debug, Deserialize)]
@@ -43,19 +44,21 @@ pub enum StrReplaceFileParams {
 }
 
 /// Tool for replacing strings in files.
-#[derive(Debug)]
-pub struct StrReplaceFileTool;
+#[derive(Debug, Default)]
+pub struct StrReplaceFileTool {
+    workspace: Option<Workspace>,
+}
 
 impl StrReplaceFileTool {
     /// Create a new StrReplaceFileTool instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
-}
 
-impl Default for StrReplaceFileTool {
-    fn default() -> Self {
-        Self::new()
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
     }
 }
 
@@ -128,7 +131,8 @@ impl Tool for StrReplaceFileTool {
             StrReplaceFileParams::Multiple { path, edit } => (path, edit),
         };
 
-        let file_path = Path::new(&path);
+        let file_path = resolve_path(self.workspace.as_ref(), &path)?;
+        let file_path = file_path.as_path();
 
         // Check if file exists
         if !file_path.exists() {
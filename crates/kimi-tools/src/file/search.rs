@@ -0,0 +1,116 @@
+//! SearchCodebase tool - semantic search over the workspace index.
+
+use crate::{Tool, ToolError, ToolResult};
+use async_trait::async_trait;
+use kimi_core::Retriever;
+use serde::Deserialize;
+
+/// Parameters for the SearchCodebase tool.
+#[derive(Debug, Deserialize)]
+pub struct SearchCodebaseParams {
+    /// What to look for, in natural language.
+    pub query: String,
+    /// Maximum number of chunks to return.
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+fn default_limit() -> usize {
+    5
+}
+
+/// Tool for finding code by meaning rather than by exact text.
+#[derive(Debug, Clone)]
+pub struct SearchCodebaseTool {
+    retriever: Retriever,
+}
+
+impl SearchCodebaseTool {
+    /// Create a new SearchCodebaseTool searching with `retriever`.
+    pub fn new(retriever: Retriever) -> Self {
+        Self { retriever }
+    }
+}
+
+#[async_trait]
+impl Tool for SearchCodebaseTool {
+    fn name(&self) -> &str {
+        "SearchCodebase"
+    }
+
+    fn description(&self) -> &str {
+        "Search the workspace for code related to a natural-language query. Returns the most relevant file excerpts with their line ranges. Use Grep instead when you know the exact text."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for, e.g. 'where are retries configured'"
+                },
+                "limit": {
+                    "type": "integer",
+                    "description": "Maximum number of excerpts to return",
+                    "default": 5
+                }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> ToolResult {
+        let params: SearchCodebaseParams = serde_json::from_value(params)
+            .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
+        if params.query.trim().is_empty() {
+            return Err(ToolError::new("Query must not be empty"));
+        }
+
+        let hits = self
+            .retriever
+            .search(&params.query, params.limit.clamp(1, 20))
+            .await
+            .map_err(|e| ToolError::new(format!("Search failed: {e}")))?;
+
+        let output = if hits.is_empty() {
+            "No matching code found.".to_string()
+        } else {
+            hits.iter().map(|hit| hit.render()).collect::<Vec<_>>().join("\n\n")
+        };
+        Ok(serde_json::json!(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kimi_core::WorkspaceIndex;
+    use kosong_rs::{ChatError, EmbeddingProvider};
+    use std::sync::Arc;
+
+    struct LengthEmbeddings;
+
+    #[async_trait]
+    impl EmbeddingProvider for LengthEmbeddings {
+        fn model_name(&self) -> &str {
+            "length"
+        }
+
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, ChatError> {
+            Ok(inputs.iter().map(|text| vec![1.0, text.len() as f32]).collect())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_search_codebase() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("lib.rs"), "pub fn answer() -> u32 { 42 }").unwrap();
+        let index = WorkspaceIndex::open(temp.path(), temp.path().join(".kimi/index.json"), "length").unwrap();
+        let tool = SearchCodebaseTool::new(Retriever::new(index, Arc::new(LengthEmbeddings)));
+
+        let output = tool.execute(serde_json::json!({"query": "the answer"})).await.unwrap();
+        assert!(output.as_str().unwrap().starts_with("lib.rs:1-1\n```\npub fn answer()"));
+        assert!(tool.execute(serde_json::json!({"query": " "})).await.is_err());
+    }
+}
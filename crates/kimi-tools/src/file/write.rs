@@ -1,9 +1,10 @@
 //! WriteFile tool - writes content to a file.
 
+use super::resolve_path;
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
+use kimi_core::Workspace;
 use serde::Deserialize;
-use std::path::Path;
 
 /// Parameters for the WriteFile tool.
 #[derive(Debug, Deserialize)]
@@ -22,19 +23,21 @@ fn default_mode() -> String {
 }
 
 /// Tool for writing files.
-#[derive(Debug)]
-pub struct WriteFileTool;
+#[derive(Debug, Default)]
+pub struct WriteFileTool {
+    workspace: Option<Workspace>,
+}
 
 impl WriteFileTool {
     /// Create a new WriteFileTool instance.
     pub fn new() -> Self {
-        Self
+        Self::default()
     }
-}
 
-impl Default for WriteFileTool {
-    fn default() -> Self {
-        Self::new()
+    /// Limit the tool to the paths in `workspace`
+    pub fn with_workspace(mut self, workspace: Workspace) -> Self {
+        self.workspace = Some(workspace);
+        self
     }
 }
 
@@ -74,7 +77,8 @@ impl Tool for WriteFileTool {
         let params: WriteFileParams = serde_json::from_value(params)
             .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
 
-        let path = Path::new(&params.path);
+        let path = resolve_path(self.workspace.as_ref(), &params.path)?;
+        let path = path.as_path();
 
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
@@ -130,4 +134,31 @@ mod tests {
         assert_eq!(tool.name(), "WriteFile");
         assert!(!tool.description().is_empty());
     }
+
+    #[tokio::test]
+    async fn test_write_stays_in_workspace() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().join("project");
+        std::fs::create_dir_all(&root).unwrap();
+        let tool = WriteFileTool::new().with_workspace(Workspace::new(&root).unwrap());
+
+        // Relative paths are taken from the workspace root
+        let params = serde_json::json!({"path": "notes/todo.md", "content": "- ship\n"});
+        tool.execute(params).await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("notes/todo.md")).unwrap(), "- ship\n");
+
+        let outside = temp.path().join("outside.txt");
+        let params = serde_json::json!({"path": outside, "content": "pwned"});
+        let err = tool.execute(params).await.unwrap_err();
+        assert!(err.to_string().contains("is outside the workspace"), "{}", err);
+        assert!(!outside.exists());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(temp.path(), root.join("up")).unwrap();
+            let params = serde_json::json!({"path": "up/outside.txt", "content": "pwned"});
+            assert!(tool.execute(params).await.is_err());
+            assert!(!outside.exists());
+        }
+    }
 }
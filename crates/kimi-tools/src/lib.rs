@@ -13,11 +13,11 @@ pub mod web;
 pub use kimi_core::{Tool, ToolError, ToolResult};
 
 // Re-export all tools
-pub use file::{GlobTool, GrepTool, ReadFileTool, StrReplaceFileTool, WriteFileTool};
+pub use file::{EditNotebookCellTool, GlobTool, GrepTool, ReadFileTool, SearchCodebaseTool, StrReplaceFileTool, WriteFileTool};
 pub use shell::ShellTool;
 pub use task::{TaskTool, Subagent};
 pub use todo::SetTodoListTool;
-pub use web::{FetchURLTool, SearchWebTool};
+pub use web::{AuthProfile, FetchURLTool, HttpRequestTool, SearchWebTool, WebClient, WebPolicy};
 
 use serde_json::Value;
 
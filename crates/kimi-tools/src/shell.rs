@@ -1,9 +1,14 @@
 //! Shell tool - execute shell commands.
+//!
+//! Output is sent line by line to the tool's [`OutputSink`] while the
+//! command runs, so long builds and test suites show progress.
 
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
+use kimi_core::{OutputSink, OutputStream};
 use serde::Deserialize;
 use std::process::Stdio;
+use tokio::io::{AsyncBufReadExt, AsyncRead, BufReader};
 use tokio::process::Command;
 
 /// Parameters for the Shell tool.
@@ -55,29 +60,40 @@ impl ShellTool {
         }
     }
 
-    /// Execute a command with timeout.
-    async fn execute_command(&self, command: &str, timeout_secs: u64) -> Result<(String, String, i32), ToolError> {
+    /// Execute a command with timeout, sending its output to `output` line
+    /// by line as well as collecting it.
+    async fn execute_command(
+        &self,
+        command: &str,
+        timeout_secs: u64,
+        output: &OutputSink,
+    ) -> Result<(String, String, i32), ToolError> {
         let mut cmd = Command::new(&self.shell);
         cmd.arg(&self.shell_arg)
             .arg(command)
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
 
-        let child = cmd.spawn().map_err(|e| {
+        let mut child = cmd.spawn().map_err(|e| {
             ToolError::new(format!("Failed to spawn shell process: {e}"))
         })?;
-
-        // Set up timeout
+        let stdout = child.stdout.take().map(|stdout| read_lines(stdout, OutputStream::Stdout, output));
+        let stderr = child.stderr.take().map(|stderr| read_lines(stderr, OutputStream::Stderr, output));
+
+        let run = async {
+            let (stdout, stderr) = tokio::join!(
+                async { if let Some(stdout) = stdout { stdout.await } else { Ok(String::new()) } },
+                async { if let Some(stderr) = stderr { stderr.await } else { Ok(String::new()) } },
+            );
+            let status = child.wait().await?;
+            Ok::<_, std::io::Error>((stdout?, stderr?, status.code().unwrap_or(-1)))
+        };
+
+        // Set up timeout; the command is killed if it runs out
         let timeout = tokio::time::Duration::from_secs(timeout_secs);
-        let result = tokio::time::timeout(timeout, child.wait_with_output()).await;
-
-        match result {
-            Ok(Ok(output)) => {
-                let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-                let stderr = String::from_utf8_lossy(&output.stderr).to_string();
-                let exit_code = output.status.code().unwrap_or(-1);
-                Ok((stdout, stderr, exit_code))
-            }
+        match tokio::time::timeout(timeout, run).await {
+            Ok(Ok(result)) => Ok(result),
             Ok(Err(e)) => Err(ToolError::new(format!("Failed to execute command: {e}"))),
             Err(_) => Err(ToolError::new(format!(
                 "Command timed out after {timeout_secs} seconds"
@@ -86,6 +102,22 @@ impl ShellTool {
     }
 }
 
+/// Read `reader` to the end, sending each line to `output` as it arrives
+async fn read_lines(
+    reader: impl AsyncRead + Unpin,
+    stream: OutputStream,
+    output: &OutputSink,
+) -> std::io::Result<String> {
+    let mut reader = BufReader::new(reader);
+    let mut collected = Vec::new();
+    let mut line = Vec::new();
+    while reader.read_until(b'\n', &mut line).await? > 0 {
+        output.send(stream, String::from_utf8_lossy(&line));
+        collected.append(&mut line);
+    }
+    Ok(String::from_utf8_lossy(&collected).into_owned())
+}
+
 impl Default for ShellTool {
     fn default() -> Self {
         Self::new()
@@ -123,11 +155,15 @@ impl Tool for ShellTool {
     }
 
     async fn execute(&self, params: serde_json::Value) -> ToolResult {
+        self.execute_streaming(params, OutputSink::discard()).await
+    }
+
+    async fn execute_streaming(&self, params: serde_json::Value, output: OutputSink) -> ToolResult {
         let params: ShellParams = serde_json::from_value(params)
             .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
 
         let (stdout, stderr, exit_code) = self
-            .execute_command(&params.command, params.timeout)
+            .execute_command(&params.command, params.timeout, &output)
             .await?;
 
         // Combine stdout and stderr
@@ -175,4 +211,41 @@ mod tests {
         let output = value.as_str().unwrap_or("");
         assert!(output.contains("Hello World"));
     }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_shell_streams_output() {
+        let tool = ShellTool::new();
+        let (sink, mut parts) = OutputSink::channel();
+        let params = serde_json::json!({
+            "command": "echo one; echo two >&2; printf three",
+            "timeout": 10
+        });
+
+        let value = tool.execute_streaming(params, sink).await.unwrap();
+        assert_eq!(value.as_str().unwrap(), "one\nthree\ntwo\n");
+
+        let mut streamed = Vec::new();
+        while let Ok(part) = parts.try_recv() {
+            streamed.push(part);
+        }
+        streamed.sort_by_key(|(stream, _)| *stream == OutputStream::Stderr);
+        assert_eq!(
+            streamed,
+            [
+                (OutputStream::Stdout, "one\n".to_string()),
+                (OutputStream::Stdout, "three".to_string()),
+                (OutputStream::Stderr, "two\n".to_string()),
+            ]
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[tokio::test]
+    async fn test_shell_timeout() {
+        let tool = ShellTool::new();
+        let params = serde_json::json!({"command": "echo started; sleep 5", "timeout": 1});
+        let err = tool.execute(params).await.unwrap_err();
+        assert!(err.to_string().contains("timed out after 1 seconds"), "{}", err);
+    }
 }
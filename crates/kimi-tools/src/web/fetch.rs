@@ -1,8 +1,10 @@
 //! FetchURL tool - fetch a web page and extract main text content.
 
+use super::WebClient;
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::sync::Arc;
 
 /// Parameters for the FetchURL tool.
 #[derive(Debug, Deserialize)]
@@ -14,21 +16,23 @@ pub struct FetchURLParams {
 /// Tool for fetching web pages.
 #[derive(Debug)]
 pub struct FetchURLTool {
-    client: reqwest::Client,
+    client: Arc<WebClient>,
 }
 
 impl FetchURLTool {
-    /// Create a new FetchURLTool instance.
+    /// Create a new FetchURLTool instance with the default web policy.
     pub fn new() -> Self {
         Self {
-            client: reqwest::Client::builder()
-                .user_agent("Mozilla/5.0 (compatible; Kimi-CLI/1.0)")
-                .timeout(std::time::Duration::from_secs(30))
-                .build()
-                .unwrap_or_default(),
+            client: Arc::new(WebClient::default()),
         }
     }
 
+    /// Make the tool's requests through `client`.
+    pub fn with_client(mut self, client: Arc<WebClient>) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Extract main text content from HTML.
     fn extract_text(&self, html: &str) -> String {
         // Simple HTML to text extraction
@@ -71,40 +75,22 @@ impl FetchURLTool {
 
     /// Fetch content from a URL.
     async fn fetch(&self, url: &str) -> Result<String, ToolError> {
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| ToolError::new(format!("Failed to fetch URL '{url}': {e}")))?;
-
-        // Check status
-        if !response.status().is_success() {
-            return Err(ToolError::new(format!(
-                "HTTP error {} for URL: {url}",
-                response.status()
-            )));
-        }
-
-        let content_type = response
-            .headers()
-            .get("content-type")
-            .and_then(|v| v.to_str().ok())
-            .unwrap_or("text/html")
-            .to_string();
-
-        let body = response
-            .text()
-            .await
-            .map_err(|e| ToolError::new(format!("Failed to read response body: {e}")))?;
+        let page = self.client.get(url).await?;
 
         // Extract text if HTML
-        if content_type.contains("text/html") {
-            Ok(self.extract_text(&body))
+        let mut content = if page.content_type.contains("text/html") {
+            self.extract_text(&page.body)
         } else {
             // Return as-is for other content types
-            Ok(body)
+            page.body
+        };
+        if page.truncated {
+            content.push_str(&format!(
+                "\n\n[Cut off at {} KB, the web policy's maximum response size]",
+                self.client.policy().max_response_bytes() / 1024
+            ));
         }
+        Ok(content)
     }
 }
 
@@ -141,14 +127,7 @@ impl Tool for FetchURLTool {
         let params: FetchURLParams = serde_json::from_value(params)
             .map_err(|e| ToolError::new(format!("Invalid parameters: {e}")))?;
 
-        // Validate URL
-        if !params.url.starts_with("http://") && !params.url.starts_with("https://") {
-            return Err(ToolError::new(
-                "URL must start with http:// or https://".to_string(),
-            ));
-        }
-
-        // Fetch the content
+        // Fetch the content; the URL is checked against the web policy
         let content = self.fetch(&params.url).await?;
 
         Ok(serde_json::json!(content))
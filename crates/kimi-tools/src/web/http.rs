@@ -0,0 +1,359 @@
+//! HttpRequest tool - call REST APIs.
+//!
+//! Requests go through the shared [`WebClient`], so the web policy's
+//! domains, rate limits and response size apply. A call may name an auth
+//! profile from `[web.profiles]`: its headers and token are added to the
+//! request, and only for URLs under its base URL, so the model uses the
+//! credentials without ever seeing them. Such requests do not follow
+//! redirects to other origins.
+
+use super::{WebClient, WebError};
+use crate::{Tool, ToolError, ToolResult};
+use async_trait::async_trait;
+use kimi_core::WebAuthProfile;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, SET_COOKIE};
+use reqwest::{Method, Url};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Parameters for the HttpRequest tool.
+#[derive(Debug, Deserialize)]
+pub struct HttpRequestParams {
+    /// GET, POST, PUT, PATCH or DELETE.
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// The URL, or with a profile, a path under its base URL.
+    pub url: String,
+    /// Headers to send.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// A JSON body.
+    pub json: Option<serde_json::Value>,
+    /// A text body, when not sending JSON.
+    pub body: Option<String>,
+    /// The auth profile to use.
+    pub profile: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+/// An auth profile, ready to be added to requests
+#[derive(Debug, Clone)]
+pub struct AuthProfile {
+    base_url: Url,
+    headers: HeaderMap,
+}
+
+impl AuthProfile {
+    /// The profile `config` describes, with its `token` loaded
+    pub fn from_config(config: &WebAuthProfile, token: Option<SecretString>) -> Result<Self, WebError> {
+        let invalid = |reason: String| WebError::InvalidUrl { url: config.base_url.clone(), reason };
+        let base_url = Url::parse(&config.base_url).map_err(|e| invalid(e.to_string()))?;
+        let mut headers = to_header_map(&config.headers).map_err(invalid)?;
+        if let Some(token) = token {
+            let (name, value) = match &config.token_header {
+                Some(name) => (name.as_str(), token.expose_secret().to_string()),
+                None => (AUTHORIZATION.as_str(), format!("Bearer {}", token.expose_secret())),
+            };
+            let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| invalid(e.to_string()))?;
+            let mut value = HeaderValue::from_str(&value).map_err(|_| invalid("the token is not a valid header value".to_string()))?;
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+        Ok(Self { base_url, headers })
+    }
+
+    /// The absolute URL for `url`, which must be under the base URL
+    fn resolve(&self, url: &str) -> Result<String, ToolError> {
+        let resolved = if url.contains("://") {
+            url.to_string()
+        } else {
+            format!("{}/{}", self.base_url.as_str().trim_end_matches('/'), url.trim_start_matches('/'))
+        };
+        let parsed = Url::parse(&resolved).map_err(|e| ToolError::InvalidParameters(format!("Invalid URL '{}': {}", url, e)))?;
+        let base_path = self.base_url.path().trim_end_matches('/');
+        let under = parsed.origin() == self.base_url.origin()
+            && parsed.path().strip_prefix(base_path).is_some_and(|rest| rest.is_empty() || rest.starts_with('/'));
+        if !under {
+            return Err(ToolError::InvalidParameters(format!(
+                "URL '{}' is not under the profile's base URL {}",
+                url, self.base_url
+            )));
+        }
+        Ok(resolved)
+    }
+}
+
+/// `headers` as a header map
+fn to_header_map(headers: &HashMap<String, String>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|e| format!("header '{}': {}", name, e))?;
+        let value = HeaderValue::from_str(value).map_err(|e| format!("header '{}': {}", name, e))?;
+        map.insert(name, value);
+    }
+    Ok(map)
+}
+
+/// Tool for calling REST APIs.
+#[derive(Debug)]
+pub struct HttpRequestTool {
+    client: Arc<WebClient>,
+    profiles: HashMap<String, AuthProfile>,
+}
+
+impl HttpRequestTool {
+    /// Create a new HttpRequestTool with the default web policy and no
+    /// auth profiles.
+    pub fn new() -> Self {
+        Self {
+            client: Arc::new(WebClient::default()),
+            profiles: HashMap::new(),
+        }
+    }
+
+    /// Make the tool's requests through `client`.
+    pub fn with_client(mut self, client: Arc<WebClient>) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Offer `profile` under `name`.
+    pub fn with_profile(mut self, name: impl Into<String>, profile: AuthProfile) -> Self {
+        self.profiles.insert(name.into(), profile);
+        self
+    }
+}
+
+impl Default for HttpRequestTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Tool for HttpRequestTool {
+    fn name(&self) -> &str {
+        "HttpRequest"
+    }
+
+    fn description(&self) -> &str {
+        "Send an HTTP request to a REST API and get the status, headers and body back; \
+         JSON responses are parsed. Use a profile for APIs that need credentials: its token \
+         is added for you, and the url may then be a path under the profile's base URL."
+    }
+
+    fn parameters_schema(&self) -> serde_json::Value {
+        let mut profiles: Vec<&String> = self.profiles.keys().collect();
+        profiles.sort();
+        let mut profile = serde_json::json!({
+            "type": "string",
+            "description": "The auth profile to use"
+        });
+        if !profiles.is_empty() {
+            profile["enum"] = serde_json::json!(profiles);
+        }
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "method": {
+                    "type": "string",
+                    "enum": ["GET", "POST", "PUT", "PATCH", "DELETE"],
+                    "default": "GET"
+                },
+                "url": {
+                    "type": "string",
+                    "description": "The URL, or with a profile, a path under its base URL"
+                },
+                "headers": {
+                    "type": "object",
+                    "additionalProperties": {"type": "string"},
+                    "description": "Headers to send"
+                },
+                "json": {
+                    "description": "A JSON body; sets Content-Type to application/json"
+                },
+                "body": {
+                    "type": "string",
+                    "description": "A text body, when not sending JSON"
+                },
+                "profile": profile
+            },
+            "required": ["url"]
+        })
+    }
+
+    async fn execute(&self, params: serde_json::Value) -> ToolResult {
+        let params: HttpRequestParams =
+            serde_json::from_value(params).map_err(|e| ToolError::InvalidParameters(e.to_string()))?;
+        let method = match params.method.to_uppercase().as_str() {
+            "GET" => Method::GET,
+            "POST" => Method::POST,
+            "PUT" => Method::PUT,
+            "PATCH" => Method::PATCH,
+            "DELETE" => Method::DELETE,
+            other => return Err(ToolError::InvalidParameters(format!("Unsupported method '{}'", other))),
+        };
+        let mut headers = HeaderMap::new();
+        let url = match &params.profile {
+            Some(name) => {
+                let profile = self.profiles.get(name).ok_or_else(|| {
+                    ToolError::InvalidParameters(format!("Unknown auth profile '{}'", name))
+                })?;
+                headers.extend(profile.headers.clone());
+                profile.resolve(&params.url)?
+            }
+            None => params.url.clone(),
+        };
+        // The call's headers come after the profile's, but never replace
+        // its token
+        for (name, value) in to_header_map(&params.headers).map_err(ToolError::InvalidParameters)? {
+            if let Some(name) = name {
+                if !headers.get(&name).is_some_and(HeaderValue::is_sensitive) {
+                    headers.insert(name, value);
+                }
+            }
+        }
+        let body = match (params.json, params.body) {
+            (Some(_), Some(_)) => {
+                return Err(ToolError::InvalidParameters("Send either json or body, not both".to_string()));
+            }
+            (Some(json), None) => {
+                headers.entry(CONTENT_TYPE).or_insert(HeaderValue::from_static("application/json"));
+                Some(serde_json::to_vec(&json).map_err(|e| ToolError::InvalidParameters(e.to_string()))?)
+            }
+            (None, body) => body.map(String::into_bytes),
+        };
+
+        let page = match params.profile {
+            Some(_) => self.client.send_with_credentials(method, &url, headers, body).await?,
+            None => self.client.send(method, &url, headers, body).await?,
+        };
+        let response_headers: BTreeMap<&str, &str> = page
+            .headers
+            .iter()
+            .filter(|(name, _)| *name != SET_COOKIE)
+            .filter_map(|(name, value)| Some((name.as_str(), value.to_str().ok()?)))
+            .collect();
+        let body = if page.content_type.contains("json") {
+            serde_json::from_str(&page.body).unwrap_or_else(|_| serde_json::json!(page.body))
+        } else {
+            serde_json::json!(page.body)
+        };
+        let mut output = serde_json::json!({
+            "status": page.status.as_u16(),
+            "headers": response_headers,
+            "body": body,
+        });
+        if page.truncated {
+            output["truncated"] = serde_json::json!(true);
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_auth_profiles() {
+        let config = WebAuthProfile {
+            base_url: "https://api.example.com/v1".to_string(),
+            headers: HashMap::from([("Accept".to_string(), "application/json".to_string())]),
+            token_ref: Some("example".to_string()),
+            token_header: None,
+        };
+        let profile = AuthProfile::from_config(&config, Some(SecretString::new("tok-123".to_string()))).unwrap();
+        assert_eq!(profile.headers[AUTHORIZATION], "Bearer tok-123");
+        assert!(profile.headers[AUTHORIZATION].is_sensitive());
+        assert_eq!(profile.headers["accept"], "application/json");
+        // The token is left out of debug output
+        assert!(!format!("{:?}", profile).contains("tok-123"));
+
+        assert_eq!(profile.resolve("users/1").unwrap(), "https://api.example.com/v1/users/1");
+        assert_eq!(profile.resolve("/users").unwrap(), "https://api.example.com/v1/users");
+        assert_eq!(profile.resolve("https://api.example.com/v1").unwrap(), "https://api.example.com/v1");
+        for url in ["https://evil.example.com/v1/users", "https://api.example.com/v10", "http://api.example.com/v1"] {
+            let err = profile.resolve(url).unwrap_err();
+            assert!(err.to_string().contains("is not under the profile's base URL"), "{}", err);
+        }
+
+        let config = WebAuthProfile { token_header: Some("X-API-Key".to_string()), ..config };
+        let profile = AuthProfile::from_config(&config, Some(SecretString::new("tok-123".to_string()))).unwrap();
+        assert_eq!(profile.headers["x-api-key"], "tok-123");
+        assert!(!profile.headers.contains_key(AUTHORIZATION));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_requests() {
+        let tool = HttpRequestTool::new();
+        assert_eq!(tool.name(), "HttpRequest");
+        let err = tool.execute(serde_json::json!({"method": "TRACE", "url": "https://example.com"})).await.unwrap_err();
+        assert!(err.to_string().contains("Unsupported method 'TRACE'"), "{}", err);
+        let err = tool
+            .execute(serde_json::json!({"url": "https://example.com", "profile": "github"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Unknown auth profile 'github'"), "{}", err);
+        let err = tool
+            .execute(serde_json::json!({"method": "POST", "url": "https://example.com", "json": {}, "body": "x"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("either json or body"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_profile_requests_stay_in_the_origin() {
+        use std::io::{BufRead, BufReader, Write};
+        use std::sync::Mutex;
+
+        // Redirects /v1/same within the origin and /v1/away to another host
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let hosts = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&hosts);
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines().map_while(Result::ok);
+                let path = lines.next().unwrap_or_default().split(' ').nth(1).unwrap_or_default().to_string();
+                for line in lines.by_ref().take_while(|line| !line.is_empty()) {
+                    if let Some((name, host)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("host") {
+                            seen.lock().unwrap().push(host.trim().to_string());
+                        }
+                    }
+                }
+                let response = match path.as_str() {
+                    "/v1/same" => "HTTP/1.1 302 Found\r\nLocation: /v1/landing\r\nContent-Length: 0\r\n\r\n".to_string(),
+                    "/v1/away" => format!(
+                        "HTTP/1.1 302 Found\r\nLocation: http://localhost:{}/v1/landing\r\nContent-Length: 0\r\n\r\n",
+                        port
+                    ),
+                    _ => "HTTP/1.1 200 OK\r\nContent-Length: 6\r\nConnection: close\r\n\r\nlanded".to_string(),
+                };
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+
+        let config = WebAuthProfile {
+            base_url: format!("http://127.0.0.1:{}/v1", port),
+            headers: HashMap::new(),
+            token_ref: Some("example".to_string()),
+            token_header: Some("X-API-Key".to_string()),
+        };
+        let profile = AuthProfile::from_config(&config, Some(SecretString::new("tok-123".to_string()))).unwrap();
+        let tool = HttpRequestTool::new().with_profile("example", profile);
+
+        let output = tool.execute(serde_json::json!({"url": "same", "profile": "example"})).await.unwrap();
+        assert_eq!(output["body"], "landed");
+        let err = tool.execute(serde_json::json!({"url": "away", "profile": "example"})).await.unwrap_err();
+        assert!(err.to_string().contains("redirect to another origin"), "{}", err);
+        assert!(hosts.lock().unwrap().iter().all(|host| host.starts_with("127.0.0.1")));
+    }
+}
@@ -1,7 +1,12 @@
 //! Web operation tools.
 
 pub mod fetch;
+pub mod http;
+pub mod policy;
+pub mod robots;
 pub mod search;
 
 pub use fetch::FetchURLTool;
+pub use http::{AuthProfile, HttpRequestTool};
+pub use policy::{WebClient, WebError, WebPage, WebPolicy};
 pub use search::SearchWebTool;
@@ -0,0 +1,448 @@
+//! The web policy and the client enforcing it
+//!
+//! Every request the web tools make goes through one [`WebClient`], which
+//! applies the [`WebPolicy`] from the `[web]` table: the URL's domain must
+//! be allowed and not blocked, redirects included; requests to a domain are
+//! spaced out to its rate limit; the site's robots.txt is followed; and no
+//! more than the maximum response size is read.
+
+use super::robots::{Robots, ROBOTS_AGENT};
+use crate::ToolError;
+use kimi_core::WebConfig;
+use reqwest::header::HeaderMap;
+use reqwest::{Method, StatusCode, Url};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+/// The user agent web requests are made with
+const USER_AGENT: &str = "Mozilla/5.0 (compatible; Kimi-CLI/1.0)";
+
+/// Redirects followed for a request, at most
+const MAX_REDIRECTS: usize = 10;
+
+/// Bytes of a robots.txt that are read, as RFC 9309 asks of crawlers
+const MAX_ROBOTS_BYTES: usize = 500 * 1024;
+
+/// Errors of web requests
+#[derive(Debug, Error)]
+pub enum WebError {
+    #[error("Invalid URL '{url}': {reason}")]
+    InvalidUrl { url: String, reason: String },
+    #[error("Domain '{host}' is blocked by the web policy")]
+    Blocked { host: String },
+    #[error("Domain '{host}' is not among the web policy's allowed domains")]
+    NotAllowed { host: String },
+    #[error("The robots.txt of {host} disallows fetching {url}")]
+    Robots { host: String, url: String },
+    #[error("HTTP error {status} for URL: {url}")]
+    Status { status: StatusCode, url: String },
+    #[error("Failed to fetch URL '{url}': {reason}")]
+    Request { url: String, reason: String },
+}
+
+impl From<WebError> for ToolError {
+    fn from(e: WebError) -> Self {
+        ToolError::Execution(e.to_string())
+    }
+}
+
+/// Which domains may be accessed, how often and how much of each response
+/// is read
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebPolicy {
+    allowed_domains: Vec<String>,
+    blocked_domains: Vec<String>,
+    requests_per_minute: u32,
+    rate_limits: Vec<(String, u32)>,
+    max_response_bytes: usize,
+    respect_robots_txt: bool,
+}
+
+impl WebPolicy {
+    /// The policy of the `[web]` table
+    pub fn from_config(config: &WebConfig) -> Self {
+        let domains = |domains: &[String]| domains.iter().map(|d| normalize(d)).collect();
+        let mut rate_limits: Vec<(String, u32)> =
+            config.rate_limits.iter().map(|(domain, limit)| (normalize(domain), *limit)).collect();
+        // The most specific domain first
+        rate_limits.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+        Self {
+            allowed_domains: domains(&config.allowed_domains),
+            blocked_domains: domains(&config.blocked_domains),
+            requests_per_minute: config.requests_per_minute(),
+            rate_limits,
+            max_response_bytes: config.max_response_bytes(),
+            respect_robots_txt: config.respects_robots_txt(),
+        }
+    }
+
+    /// Check that `url` is an http(s) URL on a domain that may be accessed
+    pub fn check(&self, url: &Url) -> Result<(), WebError> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(WebError::InvalidUrl {
+                url: url.to_string(),
+                reason: "URL must start with http:// or https://".to_string(),
+            });
+        }
+        let host = host(url)?;
+        if self.blocked_domains.iter().any(|domain| in_domain(&host, domain)) {
+            return Err(WebError::Blocked { host });
+        }
+        if !self.allowed_domains.is_empty() && !self.allowed_domains.iter().any(|domain| in_domain(&host, domain)) {
+            return Err(WebError::NotAllowed { host });
+        }
+        Ok(())
+    }
+
+    /// Requests a minute to `host`
+    pub fn rate_limit(&self, host: &str) -> u32 {
+        self.rate_limits
+            .iter()
+            .find(|(domain, _)| in_domain(host, domain))
+            .map_or(self.requests_per_minute, |(_, limit)| *limit)
+    }
+
+    /// Bytes of a response that are read
+    pub fn max_response_bytes(&self) -> usize {
+        self.max_response_bytes
+    }
+}
+
+impl Default for WebPolicy {
+    fn default() -> Self {
+        Self::from_config(&WebConfig::default())
+    }
+}
+
+/// A domain from the config, lowercased, without a leading `*.` or `.`
+fn normalize(domain: &str) -> String {
+    domain.trim().trim_start_matches("*.").trim_start_matches('.').to_lowercase()
+}
+
+/// Whether `host` is `domain` or one of its subdomains
+fn in_domain(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// The lowercased host of `url`
+fn host(url: &Url) -> Result<String, WebError> {
+    url.host_str().map(str::to_lowercase).ok_or_else(|| WebError::InvalidUrl {
+        url: url.to_string(),
+        reason: "URL has no host".to_string(),
+    })
+}
+
+/// `e` with the errors that caused it, which reqwest leaves out of its
+/// message
+/// An HTTP client following the redirects `policy` allows, only within
+/// the origin with `same_origin`
+fn http_client(policy: Arc<WebPolicy>, same_origin: bool) -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(30))
+        .redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(format!("stopped after {} redirects", MAX_REDIRECTS));
+            }
+            if same_origin && attempt.previous().first().is_some_and(|first| first.origin() != attempt.url().origin()) {
+                let error = format!("not following a redirect to another origin, {}", attempt.url());
+                return attempt.error(error);
+            }
+            match policy.check(attempt.url()) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        }))
+        .build()
+        .unwrap_or_default()
+}
+
+fn describe(e: &reqwest::Error) -> String {
+    let mut reason = e.to_string();
+    let mut source = std::error::Error::source(e);
+    while let Some(cause) = source {
+        reason.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    reason
+}
+
+/// A response, with as much of its body as the policy lets be read
+#[derive(Debug, Clone)]
+pub struct WebPage {
+    /// The URL the body came from, after redirects
+    pub url: Url,
+    /// The status of the response
+    pub status: StatusCode,
+    /// The headers of the response
+    pub headers: HeaderMap,
+    /// The `Content-Type`, `text/html` when the server sent none
+    pub content_type: String,
+    /// The body, decoded lossily as UTF-8
+    pub body: String,
+    /// Whether the body was cut off at the maximum response size
+    pub truncated: bool,
+}
+
+/// The HTTP client of the web tools, enforcing a [`WebPolicy`]
+///
+/// Share one between the tools, so that they share the rate limits and the
+/// robots.txt files fetched.
+#[derive(Debug)]
+pub struct WebClient {
+    policy: Arc<WebPolicy>,
+    http: reqwest::Client,
+    /// For requests with credentials: follows redirects only within the
+    /// origin
+    same_origin_http: reqwest::Client,
+    /// When the next request to each host may be made
+    next_request: Mutex<HashMap<String, Instant>>,
+    /// robots.txt rules by origin
+    robots: Mutex<HashMap<String, Arc<Robots>>>,
+}
+
+impl WebClient {
+    /// A client enforcing `policy`
+    pub fn new(policy: WebPolicy) -> Self {
+        let policy = Arc::new(policy);
+        Self {
+            http: http_client(Arc::clone(&policy), false),
+            same_origin_http: http_client(Arc::clone(&policy), true),
+            policy,
+            next_request: Mutex::new(HashMap::new()),
+            robots: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The policy enforced
+    pub fn policy(&self) -> &WebPolicy {
+        &self.policy
+    }
+
+    /// GET `url` once the policy allows it, failing unless the status is
+    /// a success
+    pub async fn get(&self, url: &str) -> Result<WebPage, WebError> {
+        let robots = self.policy.respect_robots_txt;
+        let page = self.fetch(&self.http, Method::GET, url, HeaderMap::new(), None, robots).await?;
+        if !page.status.is_success() {
+            return Err(WebError::Status { status: page.status, url: url.to_string() });
+        }
+        Ok(page)
+    }
+
+    /// Send a `method` request to `url` once the policy allows it,
+    /// whatever the status of the response
+    ///
+    /// robots.txt is for crawlers and is not consulted: APIs commonly
+    /// disallow everything there while serving their clients.
+    pub async fn send(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<WebPage, WebError> {
+        self.fetch(&self.http, method, url, headers, body, false).await
+    }
+
+    /// Like [`WebClient::send`], for a request carrying credentials
+    ///
+    /// Redirects are only followed within `url`'s origin: on other hops
+    /// only `Authorization` and cookies are dropped, so a token in another
+    /// header would reach the other origin.
+    pub async fn send_with_credentials(
+        &self,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<WebPage, WebError> {
+        self.fetch(&self.same_origin_http, method, url, headers, body, false).await
+    }
+
+    /// Make a request with `http` once the policy allows it, checking
+    /// robots.txt first when `robots` is set
+    async fn fetch(
+        &self,
+        http: &reqwest::Client,
+        method: Method,
+        url: &str,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+        robots: bool,
+    ) -> Result<WebPage, WebError> {
+        let parsed = Url::parse(url).map_err(|e| WebError::InvalidUrl { url: url.to_string(), reason: e.to_string() })?;
+        self.policy.check(&parsed)?;
+        let host = host(&parsed)?;
+        if robots {
+            let robots = self.robots(&parsed).await;
+            let path = match parsed.query() {
+                Some(query) => format!("{}?{}", parsed.path(), query),
+                None => parsed.path().to_string(),
+            };
+            if !robots.allows(&path) {
+                return Err(WebError::Robots { host, url: url.to_string() });
+            }
+        }
+        self.wait_turn(&host).await;
+
+        let mut request = http.request(method, parsed).headers(headers);
+        if let Some(body) = body {
+            request = request.body(body);
+        }
+        let response = request
+            .send()
+            .await
+            .map_err(|e| WebError::Request { url: url.to_string(), reason: describe(&e) })?;
+        self.read(response, self.policy.max_response_bytes).await
+    }
+
+    /// Read `response`'s body up to `max_bytes`
+    async fn read(&self, mut response: reqwest::Response, max_bytes: usize) -> Result<WebPage, WebError> {
+        let url = response.url().clone();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("text/html")
+            .to_string();
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| WebError::Request { url: url.to_string(), reason: describe(&e) })?
+        {
+            if body.len() + chunk.len() > max_bytes {
+                body.extend_from_slice(&chunk[..max_bytes - body.len()]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        Ok(WebPage {
+            url,
+            status,
+            headers,
+            content_type,
+            body: String::from_utf8_lossy(&body).into_owned(),
+            truncated,
+        })
+    }
+
+    /// The robots.txt rules for `url`'s origin, fetched the first time
+    ///
+    /// A site whose robots.txt cannot be fetched is taken to allow
+    /// everything.
+    async fn robots(&self, url: &Url) -> Arc<Robots> {
+        let origin = url.origin().ascii_serialization();
+        if let Some(robots) = self.robots.lock().unwrap().get(&origin) {
+            return Arc::clone(robots);
+        }
+        let robots = match url.join("/robots.txt") {
+            Ok(robots_url) => {
+                if let Ok(host) = host(url) {
+                    self.wait_turn(&host).await;
+                }
+                match self.http.get(robots_url).send().await {
+                    Ok(response) if response.status().is_success() => match self.read(response, MAX_ROBOTS_BYTES).await {
+                        Ok(page) => Robots::parse(&page.body, ROBOTS_AGENT),
+                        Err(_) => Robots::allow_all(),
+                    },
+                    _ => Robots::allow_all(),
+                }
+            }
+            Err(_) => Robots::allow_all(),
+        };
+        let robots = Arc::new(robots);
+        self.robots.lock().unwrap().insert(origin, Arc::clone(&robots));
+        robots
+    }
+
+    /// Wait until a request to `host` keeps within its rate limit
+    async fn wait_turn(&self, host: &str) {
+        let wait = self.reserve(host, Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Reserve the next request slot for `host`, returning how long from
+    /// `now` until it comes
+    fn reserve(&self, host: &str, now: Instant) -> Duration {
+        let limit = self.policy.rate_limit(host);
+        if limit == 0 {
+            return Duration::ZERO;
+        }
+        let interval = Duration::from_secs(60) / limit;
+        let mut next_request = self.next_request.lock().unwrap();
+        let slot = next_request.get(host).map_or(now, |next| (*next).max(now));
+        next_request.insert(host.to_string(), slot + interval);
+        slot - now
+    }
+}
+
+impl Default for WebClient {
+    fn default() -> Self {
+        Self::new(WebPolicy::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn domains(domains: &[&str]) -> Vec<String> {
+        domains.iter().map(|d| d.to_string()).collect()
+    }
+
+    #[test]
+    fn test_domains() {
+        let url = |url: &str| Url::parse(url).unwrap();
+        let open = WebPolicy::default();
+        assert!(open.check(&url("https://docs.rs/serde")).is_ok());
+        let err = open.check(&url("file:///etc/passwd")).unwrap_err();
+        assert!(err.to_string().contains("must start with http:// or https://"), "{}", err);
+
+        let policy = WebPolicy::from_config(&WebConfig {
+            allowed_domains: domains(&["docs.rs", "*.github.com"]),
+            blocked_domains: domains(&["gist.github.com"]),
+            ..Default::default()
+        });
+        assert!(policy.check(&url("https://docs.rs/serde")).is_ok());
+        assert!(policy.check(&url("https://api.GitHub.com/repos")).is_ok());
+        assert!(policy.check(&url("https://github.com/")).is_ok());
+        let err = policy.check(&url("https://notdocs.rs/")).unwrap_err();
+        assert_eq!(err.to_string(), "Domain 'notdocs.rs' is not among the web policy's allowed domains");
+        let err = policy.check(&url("https://gist.github.com/someone")).unwrap_err();
+        assert_eq!(err.to_string(), "Domain 'gist.github.com' is blocked by the web policy");
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let policy = WebPolicy::from_config(&WebConfig {
+            requests_per_minute: Some(60),
+            rate_limits: HashMap::from([("github.com".to_string(), 6), ("api.github.com".to_string(), 0)]),
+            ..Default::default()
+        });
+        assert_eq!(policy.rate_limit("docs.rs"), 60);
+        assert_eq!(policy.rate_limit("raw.github.com"), 6);
+        assert_eq!(policy.rate_limit("api.github.com"), 0);
+
+        let client = WebClient::new(policy);
+        let now = Instant::now();
+        assert_eq!(client.reserve("docs.rs", now), Duration::ZERO);
+        assert_eq!(client.reserve("docs.rs", now), Duration::from_secs(1));
+        assert_eq!(client.reserve("docs.rs", now), Duration::from_secs(2));
+        // Slots that passed are not saved up
+        assert_eq!(client.reserve("docs.rs", now + Duration::from_secs(10)), Duration::ZERO);
+        assert_eq!(client.reserve("github.com", now), Duration::ZERO);
+        assert_eq!(client.reserve("github.com", now), Duration::from_secs(10));
+        assert_eq!(client.reserve("api.github.com", now), Duration::ZERO);
+        assert_eq!(client.reserve("api.github.com", now), Duration::ZERO);
+    }
+}
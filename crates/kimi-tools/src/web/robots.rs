@@ -0,0 +1,136 @@
+//! robots.txt rules
+//!
+//! Only what decides whether a path may be fetched is read: the group for
+//! our user agent, or the `*` group when there is none, and its `Allow` and
+//! `Disallow` lines. The longest matching rule wins, `Allow` on a tie, and
+//! `*` and a trailing `$` work as in RFC 9309.
+
+/// The product token matched against `User-agent` lines
+pub const ROBOTS_AGENT: &str = "kimi-cli";
+
+/// An `Allow` (true) or `Disallow` rule and its path pattern
+type Rule = (bool, String);
+
+/// The rules of a robots.txt for one user agent
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Robots {
+    /// The rules, in file order
+    rules: Vec<Rule>,
+}
+
+impl Robots {
+    /// Rules allowing everything, for sites without a robots.txt
+    pub fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// The rules in `text` for the user agent `agent`
+    pub fn parse(text: &str, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        // `(user agents, rules)` of each group
+        let mut groups: Vec<(Vec<String>, Vec<Rule>)> = Vec::new();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_lowercase().as_str() {
+                "user-agent" => match groups.last_mut() {
+                    Some((agents, rules)) if rules.is_empty() => agents.push(value.to_lowercase()),
+                    _ => groups.push((vec![value.to_lowercase()], Vec::new())),
+                },
+                directive @ ("allow" | "disallow") => {
+                    if let Some((_, rules)) = groups.last_mut() {
+                        rules.push((directive == "allow", value.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        let ours = groups.iter().any(|(agents, _)| agents.contains(&agent));
+        let wanted = if ours { agent.as_str() } else { "*" };
+        let rules = groups
+            .into_iter()
+            .filter(|(agents, _)| agents.iter().any(|a| a == wanted))
+            .flat_map(|(_, rules)| rules);
+        // An empty Disallow allows everything
+        Self { rules: rules.filter(|(_, pattern)| !pattern.is_empty()).collect() }
+    }
+
+    /// Whether `path`, with its query, may be fetched
+    pub fn allows(&self, path: &str) -> bool {
+        let mut best: Option<(usize, bool)> = None;
+        for (allow, pattern) in &self.rules {
+            if matches(pattern, path) {
+                let len = pattern.len();
+                best = match best {
+                    Some((best_len, best_allow)) if best_len > len || (best_len == len && best_allow) => {
+                        Some((best_len, best_allow))
+                    }
+                    _ => Some((len, *allow)),
+                };
+            }
+        }
+        best.is_none_or(|(_, allow)| allow)
+    }
+}
+
+/// Whether the robots.txt `pattern` matches the start of `path`
+fn matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    let Some((last, middle)) = parts[1..].split_last() else {
+        return !anchored || rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(at) => rest = &rest[at + part.len()..],
+            None => return false,
+        }
+    }
+    if anchored { rest.ends_with(last) } else { rest.contains(last) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_robots() {
+        let robots = Robots::parse(
+            "# Example\n\
+             User-agent: *\n\
+             Disallow: /private/\n\
+             Disallow: /*.pdf$\n\
+             Allow: /private/readme\n\
+             \n\
+             User-agent: GPTBot\n\
+             Disallow: /\n",
+            ROBOTS_AGENT,
+        );
+        assert!(robots.allows("/"));
+        assert!(robots.allows("/docs/guide.html?page=2"));
+        assert!(!robots.allows("/private/keys"));
+        assert!(robots.allows("/private/readme.md"));
+        assert!(!robots.allows("/papers/paper.pdf"));
+        assert!(robots.allows("/papers/paper.pdf.html"));
+
+        // A group for us replaces the `*` one
+        let robots = Robots::parse(
+            "User-agent: *\nDisallow: /\n\nUser-agent: Googlebot\nUser-agent: kimi-cli\nDisallow: /search\n",
+            ROBOTS_AGENT,
+        );
+        assert!(robots.allows("/docs"));
+        assert!(!robots.allows("/search?q=rust"));
+
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", ROBOTS_AGENT).allows("/anything"));
+        assert!(Robots::allow_all().allows("/anything"));
+    }
+}
@@ -1,8 +1,10 @@
 //! SearchWeb tool - search the internet for information.
 
+use super::WebClient;
 use crate::{Tool, ToolError, ToolResult};
 use async_trait::async_trait;
 use serde::Deserialize;
+use std::sync::Arc;
 
 /// Parameters for the SearchWeb tool.
 #[derive(Debug, Deserialize)]
@@ -33,7 +35,7 @@ pub struct SearchResult {
 /// Tool for searching the web.
 #[derive(Debug)]
 pub struct SearchWebTool {
-    client: reqwest::Client,
+    client: Arc<WebClient>,
     #[allow(dead_code)]
     api_endpoint: Option<String>,
     #[allow(dead_code)]
@@ -44,7 +46,7 @@ impl SearchWebTool {
     /// Create a new SearchWebTool with default settings.
     pub fn new() -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: Arc::new(WebClient::default()),
             api_endpoint: None,
             api_key: None,
         }
@@ -53,12 +55,18 @@ impl SearchWebTool {
     /// Create a new SearchWebTool with API configuration.
     pub fn with_api(endpoint: impl Into<String>, api_key: impl Into<String>) -> Self {
         Self {
-            client: reqwest::Client::new(),
+            client: Arc::new(WebClient::default()),
             api_endpoint: Some(endpoint.into()),
             api_key: Some(api_key.into()),
         }
     }
 
+    /// Make the tool's requests through `client`.
+    pub fn with_client(mut self, client: Arc<WebClient>) -> Self {
+        self.client = client;
+        self
+    }
+
     /// Perform a web search using a search API.
     async fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>, ToolError> {
         // This is a placeholder implementation
@@ -84,17 +92,7 @@ impl SearchWebTool {
 
     /// Fetch content from a URL.
     async fn fetch_content(&self, url: &str) -> Result<String, ToolError> {
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .map_err(|e| ToolError::new(format!("Failed to fetch URL: {e}")))?;
-
-        let content = response
-            .text()
-            .await
-            .map_err(|e| ToolError::new(format!("Failed to read response: {e}")))?;
+        let content = self.client.get(url).await?.body;
 
         // TODO: Extract main text content from HTML
         // This would typically use a library like readability-rs or similar
@@ -0,0 +1,223 @@
+//! # kosong-derive
+//!
+//! Derive macros for [kosong-rs](https://docs.rs/kosong-rs). Use them
+//! through the re-exports in `kosong_rs::tooling` rather than directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, LitStr, Result};
+
+/// Derives `kosong_rs::tooling::ToolSchema`, the JSON schema of a tool's
+/// parameters.
+///
+/// Structs with named fields become objects: each field is a property
+/// described by its doc comment, and required unless it is an `Option` or
+/// has `#[serde(default)]`. Enums whose variants carry no data become
+/// strings restricted to the variant names. The `rename`, `rename_all`,
+/// `default` and `skip` serde attributes are honoured, so the schema
+/// matches what deserializing accepts.
+///
+/// ```rust,ignore
+/// use kosong_rs::tooling::ToolSchema;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize, ToolSchema)]
+/// struct Params {
+///     /// The path to the file to read
+///     path: String,
+///     /// The number of lines to read
+///     limit: Option<u32>,
+/// }
+/// ```
+#[proc_macro_derive(ToolSchema)]
+pub fn derive_tool_schema(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream2> {
+    let container = SerdeAttrs::parse(&input.attrs)?;
+    let body = match &input.data {
+        Data::Struct(data) => object_schema(&data.fields, &container)?,
+        Data::Enum(data) => {
+            let mut names = Vec::new();
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return Err(Error::new_spanned(variant, "ToolSchema enums can only have variants without data"));
+                }
+                let attrs = SerdeAttrs::parse(&variant.attrs)?;
+                if attrs.skip {
+                    continue;
+                }
+                names.push(attrs.rename.unwrap_or_else(|| {
+                    rename(&variant.ident.to_string(), container.rename_all.as_deref(), Case::Pascal)
+                }));
+            }
+            quote! { __private::string_enum(&[#(#names),*]) }
+        }
+        Data::Union(_) => return Err(Error::new_spanned(&input.ident, "ToolSchema cannot be derived for unions")),
+    };
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(syn::parse_quote!(::kosong_rs::tooling::ToolSchema));
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::kosong_rs::tooling::ToolSchema for #name #ty_generics #where_clause {
+            fn schema() -> ::kosong_rs::tooling::schema::__private::serde_json::Value {
+                use ::kosong_rs::tooling::schema::__private;
+                #body
+            }
+        }
+    })
+}
+
+/// The schema of a struct's fields, as an object.
+fn object_schema(fields: &Fields, container: &SerdeAttrs) -> Result<TokenStream2> {
+    let Fields::Named(fields) = fields else {
+        return Err(Error::new_spanned(fields, "ToolSchema structs need named fields"));
+    };
+    let mut properties = Vec::new();
+    for field in &fields.named {
+        let attrs = SerdeAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = field.ident.as_ref().expect("named field");
+        let name = attrs.rename.unwrap_or_else(|| {
+            rename(&ident.to_string(), container.rename_all.as_deref(), Case::Snake)
+        });
+        let description = match doc_comment(&field.attrs) {
+            Some(doc) => quote! { Some(#doc) },
+            None => quote! { None },
+        };
+        let ty = &field.ty;
+        let defaulted = attrs.default || container.default;
+        properties.push(quote! {
+            __private::Property {
+                name: #name,
+                schema: <#ty as ::kosong_rs::tooling::ToolSchema>::schema(),
+                description: #description,
+                required: !#defaulted && !<#ty as ::kosong_rs::tooling::ToolSchema>::OPTIONAL,
+            }
+        });
+    }
+    Ok(quote! { __private::object(::std::vec![#(#properties),*]) })
+}
+
+/// The doc comment in `attrs`, as one line without its final full stop.
+fn doc_comment(attrs: &[Attribute]) -> Option<String> {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("doc"))
+        .filter_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(doc) => match &doc.value {
+                syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(line), .. }) => Some(line.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .filter(|line| !line.is_empty())
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    let doc = lines.join(" ");
+    Some(doc.strip_suffix('.').unwrap_or(&doc).to_string())
+}
+
+/// The serde attributes that change which names and fields are accepted.
+#[derive(Default)]
+struct SerdeAttrs {
+    rename: Option<String>,
+    rename_all: Option<String>,
+    default: bool,
+    skip: bool,
+}
+
+impl SerdeAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut parsed = Self::default();
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta.path.get_ident().map(ToString::to_string).unwrap_or_default();
+                match key.as_str() {
+                    "rename" if meta.input.peek(syn::Token![=]) => {
+                        parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    }
+                    "rename_all" if meta.input.peek(syn::Token![=]) => {
+                        parsed.rename_all = Some(meta.value()?.parse::<LitStr>()?.value());
+                    }
+                    "default" => {
+                        parsed.default = true;
+                        skip_value(&meta)?;
+                    }
+                    "skip" | "skip_deserializing" => parsed.skip = true,
+                    _ => skip_value(&meta)?,
+                }
+                Ok(())
+            })?;
+        }
+        Ok(parsed)
+    }
+}
+
+/// Consumes the value of a serde attribute this macro does not need:
+/// `= value` or a parenthesized list.
+fn skip_value(meta: &syn::meta::ParseNestedMeta) -> Result<()> {
+    if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<syn::Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(|nested| skip_value(&nested))?;
+    }
+    Ok(())
+}
+
+/// How Rust names a field or variant.
+#[derive(Clone, Copy)]
+enum Case {
+    Snake,
+    Pascal,
+}
+
+/// `name` as serde's `rename_all = rule` would spell it.
+fn rename(name: &str, rule: Option<&str>, case: Case) -> String {
+    let Some(rule) = rule else {
+        return name.to_string();
+    };
+    let words: Vec<String> = match case {
+        Case::Snake => name.split('_').map(str::to_lowercase).collect(),
+        Case::Pascal => {
+            let mut words = Vec::new();
+            for c in name.chars() {
+                if c.is_uppercase() || words.is_empty() {
+                    words.push(String::new());
+                }
+                words.last_mut().expect("a word").extend(c.to_lowercase());
+            }
+            words
+        }
+    };
+    let capitalize = |word: &String| {
+        let mut chars = word.chars();
+        chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+    };
+    match rule {
+        "lowercase" => words.concat(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "PascalCase" => words.iter().map(capitalize).collect(),
+        "camelCase" => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| if i == 0 { word.clone() } else { capitalize(word) })
+            .collect(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        _ => name.to_string(),
+    }
+}
@@ -0,0 +1,432 @@
+//! Caching generated responses on disk.
+//!
+//! A [`CachingProvider`] wraps another provider and keys each request by a
+//! SHA-256 hash of the model, system prompt, messages, tools and any
+//! thinking or response format set on it. A request seen before within the
+//! cache's time to live is answered from a [`ResponseCache`] directory
+//! without contacting the model; anything else goes to the wrapped provider,
+//! and its response is stored once it has been received in full. Streams
+//! that fail or are dropped part way are not stored.
+//!
+//! Cached responses are replayed as one chunk each for the thinking, the
+//! text, every tool call and the usage, so they lose their original
+//! chunking. Failing to read or write the cache never fails a request; the
+//! problem is logged and the request goes to the provider.
+
+use super::{
+    ChatError, ChatProvider, CompletionResponse, GenerateStream, ModelCapability, ResponseFormat, StreamChunk,
+    ThinkingEffort, TokenUsage, ToolDefinition,
+};
+use crate::message::{Message, ToolCall};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Directory of cached responses, with its expiry and size limits.
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+    max_bytes: u64,
+}
+
+impl ResponseCache {
+    /// How long a response is served from the cache by default: one day.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+    /// Largest the cache grows by default: 100 MiB.
+    pub const DEFAULT_MAX_BYTES: u64 = 100 * 1024 * 1024;
+
+    /// Creates a cache in `dir`, which is created when the first response
+    /// is stored.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl: Self::DEFAULT_TTL,
+            max_bytes: Self::DEFAULT_MAX_BYTES,
+        }
+    }
+
+    /// Sets how long a stored response is served.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Sets the size the cache is kept under; the oldest responses are
+    /// removed to make room for new ones.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// Returns the cache directory.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Removes every cached response.
+    pub fn clear(&self) -> std::io::Result<()> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    /// The response stored under `key`, unless it is missing or expired.
+    fn get(&self, key: &str) -> Option<CachedResponse> {
+        let path = self.path(key);
+        let content = std::fs::read(&path).ok()?;
+        let cached: CachedResponse = match serde_json::from_slice(&content) {
+            Ok(cached) => cached,
+            Err(e) => {
+                tracing::warn!("Ignoring unreadable cached response {}: {}", path.display(), e);
+                return None;
+            }
+        };
+        if now().saturating_sub(cached.created_at) >= self.ttl.as_secs() {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        Some(cached)
+    }
+
+    /// Stores `response` under `key`, then trims the cache to its size.
+    fn put(&self, key: &str, response: &CachedResponse) {
+        let stored = std::fs::create_dir_all(&self.dir)
+            .and_then(|_| {
+                // Written aside and renamed, so readers never see half a file
+                let temp = self.dir.join(format!("{}.tmp", key));
+                std::fs::write(&temp, serde_json::to_vec(response)?)?;
+                std::fs::rename(&temp, self.path(key))
+            })
+            .and_then(|_| self.trim());
+        if let Err(e) = stored {
+            tracing::warn!("Failed to cache a response in {}: {}", self.dir.display(), e);
+        }
+    }
+
+    /// Removes the oldest responses until the cache fits its size.
+    fn trim(&self) -> std::io::Result<()> {
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                entries.push((metadata.modified()?, metadata.len(), entry.path()));
+            }
+        }
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+        entries.sort();
+        for (_, len, path) in entries {
+            if total <= self.max_bytes {
+                break;
+            }
+            std::fs::remove_file(path)?;
+            total -= len;
+        }
+        Ok(())
+    }
+}
+
+/// A complete response as stored in the cache.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedResponse {
+    /// Unix time the response was stored at
+    created_at: u64,
+    text: String,
+    #[serde(default)]
+    thinking: String,
+    #[serde(default)]
+    tool_calls: Vec<ToolCall>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
+}
+
+impl CachedResponse {
+    fn from_completion(completion: &CompletionResponse) -> Self {
+        Self {
+            created_at: now(),
+            text: completion.text(),
+            thinking: completion.thinking().unwrap_or_default().to_string(),
+            tool_calls: completion.tool_calls().to_vec(),
+            usage: completion.usage,
+        }
+    }
+
+    fn record(&mut self, chunk: &StreamChunk) {
+        match chunk {
+            StreamChunk::Text(text) => self.text.push_str(text),
+            StreamChunk::Thinking(thinking) => self.thinking.push_str(thinking),
+            StreamChunk::ToolCall(call) => self.tool_calls.push(call.clone()),
+            StreamChunk::ToolCallPart(_) => {}
+            StreamChunk::Usage(usage) => self.usage = Some(*usage),
+        }
+    }
+
+    fn into_completion(self) -> CompletionResponse {
+        CompletionResponse {
+            usage: self.usage,
+            ..CompletionResponse::new(self.text, self.tool_calls).with_thinking(self.thinking)
+        }
+    }
+
+    fn into_stream(self) -> GenerateStream {
+        let mut chunks = Vec::new();
+        if !self.thinking.is_empty() {
+            chunks.push(StreamChunk::Thinking(self.thinking));
+        }
+        if !self.text.is_empty() {
+            chunks.push(StreamChunk::Text(self.text));
+        }
+        chunks.extend(self.tool_calls.into_iter().map(StreamChunk::ToolCall));
+        chunks.extend(self.usage.map(StreamChunk::Usage));
+        Box::pin(futures::stream::iter(chunks.into_iter().map(Ok)))
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// A provider that answers repeated requests from a [`ResponseCache`].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use kosong_rs::{CachingProvider, KimiProvider, ResponseCache};
+/// use std::time::Duration;
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let cache = ResponseCache::new("/tmp/kosong-cache").with_ttl(Duration::from_secs(3600));
+/// let provider = CachingProvider::new(KimiProvider::new("key", "kimi-k2-turbo-preview", None)?, cache);
+/// # Ok(())
+/// # }
+/// ```
+pub struct CachingProvider<P> {
+    inner: P,
+    cache: Arc<ResponseCache>,
+    /// Thinking effort and response format set through this wrapper, which
+    /// change the response without showing in the request
+    thinking: Option<ThinkingEffort>,
+    response_format: Option<ResponseFormat>,
+}
+
+impl<P: ChatProvider> CachingProvider<P> {
+    /// Wraps `inner`, caching its responses in `cache`.
+    pub fn new(inner: P, cache: ResponseCache) -> Self {
+        Self {
+            inner,
+            cache: Arc::new(cache),
+            thinking: None,
+            response_format: None,
+        }
+    }
+
+    /// Returns the cache responses are stored in.
+    pub fn cache(&self) -> &ResponseCache {
+        &self.cache
+    }
+
+    /// The cache key of a request.
+    fn key(&self, system_prompt: Option<&str>, messages: &[Message], tools: Option<&[ToolDefinition]>) -> String {
+        let request = serde_json::json!({
+            "model": self.inner.model_name(),
+            "thinking": self.thinking.map(|effort| effort.as_str()),
+            "response_format": self.response_format.as_ref().map(ResponseFormat::to_json),
+            "system_prompt": system_prompt,
+            "messages": messages,
+            "tools": tools,
+        });
+        format!("{:x}", Sha256::digest(request.to_string()))
+    }
+
+    /// A wrapper around `inner` sharing this one's cache.
+    fn rewrap(&self, inner: Box<dyn ChatProvider>) -> CachingProvider<Box<dyn ChatProvider>> {
+        CachingProvider {
+            inner,
+            cache: self.cache.clone(),
+            thinking: self.thinking,
+            response_format: self.response_format.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: ChatProvider> ChatProvider for CachingProvider<P> {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        let key = self.key(system_prompt, messages, tools);
+        if let Some(cached) = self.cache.get(&key) {
+            tracing::debug!("Serving response {} from the cache", key);
+            return Ok(cached.into_stream());
+        }
+
+        let stream = self.inner.generate_with_tools(system_prompt, messages, tools).await?;
+        let recording = Some((self.cache.clone(), key, CachedResponse::default()));
+        let stream = futures::stream::unfold((stream, recording), |(mut stream, mut recording)| async move {
+            match stream.next().await {
+                Some(chunk) => {
+                    match (&chunk, &mut recording) {
+                        (Ok(chunk), Some((_, _, response))) => response.record(chunk),
+                        // A failed stream is not worth replaying
+                        (Err(_), _) => recording = None,
+                        (Ok(_), None) => {}
+                    }
+                    Some((chunk, (stream, recording)))
+                }
+                None => {
+                    if let Some((cache, key, response)) = recording {
+                        cache.put(&key, &CachedResponse { created_at: now(), ..response });
+                    }
+                    None
+                }
+            }
+        });
+        Ok(Box::pin(stream))
+    }
+
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        let key = self.key(system_prompt, messages, tools);
+        if let Some(cached) = self.cache.get(&key) {
+            tracing::debug!("Serving response {} from the cache", key);
+            return Ok(cached.into_completion());
+        }
+
+        let completion = self.inner.generate_complete(system_prompt, messages, tools).await?;
+        self.cache.put(&key, &CachedResponse::from_completion(&completion));
+        Ok(completion)
+    }
+
+    fn model_name(&self) -> &str {
+        self.inner.model_name()
+    }
+
+    fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        let mut provider = self.rewrap(self.inner.with_thinking(effort));
+        provider.thinking = Some(effort);
+        Box::new(provider)
+    }
+
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let mut provider = self.rewrap(self.inner.with_response_format(format.clone())?);
+        provider.response_format = Some(format);
+        Some(Box::new(provider))
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        self.inner.capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers every request with the same chunks, counting the requests.
+    struct Counting {
+        requests: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl ChatProvider for Counting {
+        async fn generate_with_tools(
+            &self,
+            _system_prompt: Option<&str>,
+            _messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+        ) -> Result<GenerateStream, ChatError> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let chunks = [
+                StreamChunk::Text("Hel".to_string()),
+                StreamChunk::Text("lo".to_string()),
+                StreamChunk::ToolCall(ToolCall::new("call_1", "Ls", "{}")),
+                StreamChunk::Usage(TokenUsage::new(10, 2)),
+            ];
+            Ok(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))))
+        }
+
+        fn model_name(&self) -> &str {
+            "counting"
+        }
+
+        fn with_thinking(&self, _effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+            Box::new(Counting { requests: AtomicUsize::new(0) })
+        }
+
+        fn capabilities(&self) -> &[ModelCapability] {
+            &[]
+        }
+    }
+
+    async fn chunks(provider: &dyn ChatProvider, text: &str) -> Vec<StreamChunk> {
+        let stream = provider.generate(None, &[Message::user(text)]).await.unwrap();
+        stream.map(Result::unwrap).collect().await
+    }
+
+    #[tokio::test]
+    async fn test_repeated_requests_are_served_from_the_cache() {
+        let temp = std::env::temp_dir().join(format!("kosong-cache-{}", uuid::Uuid::new_v4()));
+        let provider = CachingProvider::new(Counting { requests: AtomicUsize::new(0) }, ResponseCache::new(&temp));
+
+        let first = chunks(&provider, "Hi").await;
+        assert_eq!(first.len(), 4);
+        let second = chunks(&provider, "Hi").await;
+        assert_eq!(
+            second,
+            [
+                StreamChunk::Text("Hello".to_string()),
+                StreamChunk::ToolCall(ToolCall::new("call_1", "Ls", "{}")),
+                StreamChunk::Usage(TokenUsage::new(10, 2)),
+            ]
+        );
+        let completion = provider.generate_complete(None, &[Message::user("Hi")], None).await.unwrap();
+        assert_eq!(completion.text(), "Hello");
+        assert_eq!(provider.inner.requests.load(Ordering::SeqCst), 1);
+
+        // Another request, or an expired entry, goes to the provider
+        chunks(&provider, "Bye").await;
+        assert_eq!(provider.inner.requests.load(Ordering::SeqCst), 2);
+        let expired = CachingProvider::new(
+            Counting { requests: AtomicUsize::new(0) },
+            ResponseCache::new(&temp).with_ttl(Duration::ZERO),
+        );
+        chunks(&expired, "Hi").await;
+        assert_eq!(expired.inner.requests.load(Ordering::SeqCst), 1);
+
+        provider.cache().clear().unwrap();
+        assert!(!temp.exists());
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_trimmed_to_its_size() {
+        let temp = std::env::temp_dir().join(format!("kosong-cache-{}", uuid::Uuid::new_v4()));
+        let provider = CachingProvider::new(
+            Counting { requests: AtomicUsize::new(0) },
+            ResponseCache::new(&temp).with_max_bytes(1),
+        );
+        chunks(&provider, "Hi").await;
+        chunks(&provider, "Hi").await;
+        assert_eq!(provider.inner.requests.load(Ordering::SeqCst), 2);
+        assert_eq!(std::fs::read_dir(&temp).unwrap().count(), 0);
+        provider.cache().clear().unwrap();
+    }
+}
@@ -0,0 +1,82 @@
+//! Cancelling a generation in flight.
+//!
+//! Dropping a [`GenerateStream`] already closes its connection, but the
+//! code that wants to stop a generation, such as a Ctrl+C handler, is rarely
+//! the code reading the stream. A [`CancellationToken`] can be handed to it
+//! instead: cancelling makes the reader stop at its next poll, whether it is
+//! still waiting for the response or already streaming it.
+
+use super::{ChatError, GenerateStream};
+use futures::future::{AbortHandle, Abortable, Aborted, BoxFuture};
+
+/// Cloneable handle that cancels one generation.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    request: AbortHandle,
+    stream: AbortHandle,
+}
+
+impl CancellationToken {
+    /// Makes `request` cancellable, returning it with its token.
+    ///
+    /// A request cancelled before the response arrives fails with
+    /// [`ChatError::Cancelled`]; a stream cancelled after that ends early,
+    /// keeping the chunks already read.
+    pub(crate) fn wrap(
+        request: BoxFuture<'_, Result<GenerateStream, ChatError>>,
+    ) -> (BoxFuture<'_, Result<GenerateStream, ChatError>>, Self) {
+        let (request_handle, request_registration) = AbortHandle::new_pair();
+        let (stream_handle, stream_registration) = AbortHandle::new_pair();
+        let future = async move {
+            match Abortable::new(request, request_registration).await {
+                Ok(stream) => Ok(Box::pin(Abortable::new(stream?, stream_registration)) as GenerateStream),
+                Err(Aborted) => Err(ChatError::Cancelled),
+            }
+        };
+        let token = Self { request: request_handle, stream: stream_handle };
+        (Box::pin(future), token)
+    }
+
+    /// Cancels the generation.
+    ///
+    /// Has no effect once the stream has ended.
+    pub fn cancel(&self) {
+        self.request.abort();
+        self.stream.abort();
+    }
+
+    /// Whether the generation has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.stream.is_aborted()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_provider::StreamChunk;
+    use futures::StreamExt;
+
+    fn stream_then_hang() -> BoxFuture<'static, Result<GenerateStream, ChatError>> {
+        let first = futures::stream::iter([Ok(StreamChunk::Text("Hel".to_string()))]);
+        let stream: GenerateStream = Box::pin(first.chain(futures::stream::pending()));
+        Box::pin(async move { Ok(stream) })
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_waiting() {
+        let (request, token) = CancellationToken::wrap(Box::pin(futures::future::pending()));
+        token.clone().cancel();
+        assert!(token.is_cancelled());
+        assert!(matches!(request.await, Err(ChatError::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_while_streaming() {
+        let (request, token) = CancellationToken::wrap(stream_then_hang());
+        let mut stream = request.await.unwrap();
+        assert_eq!(stream.next().await.unwrap().unwrap(), StreamChunk::Text("Hel".to_string()));
+        token.cancel();
+        assert!(stream.next().await.is_none());
+    }
+}
@@ -0,0 +1,264 @@
+//! Failing over between providers.
+//!
+//! A [`FallbackProvider`] sends each request to the first of its providers
+//! and, when that fails with an error a retry may fix (see
+//! [`ChatError::is_retryable`]: server errors, rate limits, timeouts and
+//! connection failures), to the next one, until one of them answers. Every
+//! request starts again with the first provider, so the primary is used as
+//! soon as it recovers.
+//!
+//! Failover happens before a response starts; a stream that breaks part way
+//! fails as it would without the wrapper, as its chunks have already been
+//! passed on.
+
+use super::{
+    ChatError, ChatProvider, CompletionResponse, GenerateStream, ModelCapability, ResponseFormat, ThinkingEffort,
+    ToolDefinition,
+};
+use crate::message::Message;
+use async_trait::async_trait;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A request that failed on one provider and is sent to the next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failover {
+    /// Model of the provider that failed.
+    pub from: String,
+    /// Model of the provider tried next.
+    pub to: String,
+    /// Why the request failed.
+    pub reason: String,
+}
+
+/// Called with each failover.
+pub type FailoverHandler = Arc<dyn Fn(&Failover) + Send + Sync>;
+
+/// A provider that fails over to the next of an ordered list of providers.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use kosong_rs::{ChatProvider, FallbackProvider, KimiProvider, OllamaProvider};
+///
+/// # fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let primary: Box<dyn ChatProvider> = Box::new(KimiProvider::new("key", "kimi-k2-turbo-preview", None)?);
+/// let backup: Box<dyn ChatProvider> = Box::new(OllamaProvider::new("qwen3:14b")?);
+/// let provider = FallbackProvider::new(vec![primary, backup])?
+///     .with_failover_handler(|failover| eprintln!("{} failed, using {}", failover.from, failover.to));
+/// # Ok(())
+/// # }
+/// ```
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn ChatProvider>>,
+    on_failover: Option<FailoverHandler>,
+    /// Index of the provider that answered the last request
+    served: AtomicUsize,
+}
+
+impl FallbackProvider {
+    /// Creates a provider trying `providers` in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChatError::Config`] if `providers` is empty.
+    pub fn new(providers: Vec<Box<dyn ChatProvider>>) -> Result<Self, ChatError> {
+        if providers.is_empty() {
+            return Err(ChatError::Config("A fallback chain needs at least one provider".to_string()));
+        }
+        Ok(Self {
+            providers,
+            on_failover: None,
+            served: AtomicUsize::new(0),
+        })
+    }
+
+    /// Calls `handler` each time a request moves on to the next provider.
+    pub fn with_failover_handler(mut self, handler: impl Fn(&Failover) + Send + Sync + 'static) -> Self {
+        self.on_failover = Some(Arc::new(handler));
+        self
+    }
+
+    /// Returns the providers, in the order they are tried.
+    pub fn providers(&self) -> &[Box<dyn ChatProvider>] {
+        &self.providers
+    }
+
+    /// Returns the provider that answered the last request, the first one
+    /// before any request.
+    pub fn served_by(&self) -> &dyn ChatProvider {
+        self.providers[self.served.load(Ordering::SeqCst)].as_ref()
+    }
+
+    /// Runs `request` on each provider in turn until one succeeds or fails
+    /// with an error that is not retryable.
+    async fn first_answer<'a, T, F, Fut>(&'a self, request: F) -> Result<T, ChatError>
+    where
+        F: Fn(&'a dyn ChatProvider) -> Fut,
+        Fut: Future<Output = Result<T, ChatError>>,
+    {
+        let mut index = 0;
+        loop {
+            let provider = self.providers[index].as_ref();
+            match request(provider).await {
+                Err(e) if e.is_retryable() && index + 1 < self.providers.len() => {
+                    let failover = Failover {
+                        from: provider.model_name().to_string(),
+                        to: self.providers[index + 1].model_name().to_string(),
+                        reason: e.to_string(),
+                    };
+                    tracing::warn!("{} failed, failing over to {}: {}", failover.from, failover.to, failover.reason);
+                    if let Some(handler) = &self.on_failover {
+                        handler(&failover);
+                    }
+                    index += 1;
+                }
+                result => {
+                    if result.is_ok() {
+                        self.served.store(index, Ordering::SeqCst);
+                    }
+                    return result;
+                }
+            }
+        }
+    }
+
+    /// A chain of the providers `f` returns for each of these, keeping the
+    /// failover handler; `None` if any provider returns `None`.
+    fn map(&self, f: impl Fn(&dyn ChatProvider) -> Option<Box<dyn ChatProvider>>) -> Option<Self> {
+        let providers = self.providers.iter().map(|provider| f(provider.as_ref())).collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            providers,
+            on_failover: self.on_failover.clone(),
+            served: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl ChatProvider for FallbackProvider {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        self.first_answer(|provider| provider.generate_with_tools(system_prompt, messages, tools)).await
+    }
+
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        self.first_answer(|provider| provider.generate_complete(system_prompt, messages, tools)).await
+    }
+
+    /// Returns the model of the provider that answered the last request.
+    fn model_name(&self) -> &str {
+        self.served_by().model_name()
+    }
+
+    fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        let chain = self.map(|provider| Some(provider.with_thinking(effort)));
+        Box::new(chain.expect("every provider has a thinking variant"))
+    }
+
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let chain = self.map(|provider| provider.with_response_format(format.clone()))?;
+        Some(Box::new(chain))
+    }
+
+    /// Returns the capabilities of the first provider.
+    fn capabilities(&self) -> &[ModelCapability] {
+        self.providers[0].capabilities()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_provider::StreamChunk;
+    use futures::StreamExt;
+    use std::sync::Mutex;
+
+    /// Fails with `status` if set, else answers with its name.
+    struct Stub {
+        name: &'static str,
+        status: Option<u16>,
+    }
+
+    #[async_trait]
+    impl ChatProvider for Stub {
+        async fn generate_with_tools(
+            &self,
+            _system_prompt: Option<&str>,
+            _messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+        ) -> Result<GenerateStream, ChatError> {
+            match self.status {
+                Some(status) => Err(ChatError::Api { status, message: "down".to_string() }),
+                None => Ok(Box::pin(futures::stream::iter([Ok(StreamChunk::Text(self.name.to_string()))]))),
+            }
+        }
+
+        fn model_name(&self) -> &str {
+            self.name
+        }
+
+        fn with_thinking(&self, _effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+            Box::new(Stub { name: self.name, status: self.status })
+        }
+
+        fn capabilities(&self) -> &[ModelCapability] {
+            &[]
+        }
+    }
+
+    fn chain(stubs: &[(&'static str, Option<u16>)]) -> FallbackProvider {
+        let providers = stubs
+            .iter()
+            .map(|&(name, status)| Box::new(Stub { name, status }) as Box<dyn ChatProvider>)
+            .collect();
+        FallbackProvider::new(providers).unwrap()
+    }
+
+    async fn answer(provider: &dyn ChatProvider) -> Result<String, ChatError> {
+        let mut stream = provider.generate(None, &[Message::user("Hi")]).await?;
+        match stream.next().await {
+            Some(Ok(StreamChunk::Text(text))) => Ok(text),
+            other => panic!("unexpected chunk: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fails_over_on_retryable_errors() {
+        let failovers = Arc::new(Mutex::new(Vec::new()));
+        let provider = chain(&[("kimi", Some(503)), ("flaky", Some(429)), ("ollama", None)]).with_failover_handler({
+            let failovers = failovers.clone();
+            move |failover| failovers.lock().unwrap().push(failover.clone())
+        });
+        assert_eq!(provider.model_name(), "kimi");
+
+        assert_eq!(answer(&provider).await.unwrap(), "ollama");
+        assert_eq!(provider.model_name(), "ollama");
+        let failovers = failovers.lock().unwrap();
+        let hops: Vec<_> = failovers.iter().map(|f| (f.from.as_str(), f.to.as_str())).collect();
+        assert_eq!(hops, [("kimi", "flaky"), ("flaky", "ollama")]);
+        assert!(failovers[0].reason.contains("down"));
+    }
+
+    #[tokio::test]
+    async fn test_other_errors_are_not_failed_over() {
+        let provider = chain(&[("kimi", Some(400)), ("ollama", None)]);
+        assert!(matches!(answer(&provider).await, Err(ChatError::Api { status: 400, .. })));
+
+        // The last provider's error is returned
+        let provider = chain(&[("kimi", Some(503)), ("ollama", Some(502))]);
+        assert!(matches!(answer(&provider).await, Err(ChatError::Api { status: 502, .. })));
+
+        assert!(FallbackProvider::new(Vec::new()).is_err());
+    }
+}
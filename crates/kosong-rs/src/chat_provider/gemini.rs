@@ -0,0 +1,837 @@
+//! Google Gemini chat provider implementation.
+//!
+//! This module provides a [`ChatProvider`] implementation for the Gemini
+//! API's `generateContent` endpoints. Messages are mapped onto Gemini's
+//! `contents`: assistant messages become `model` turns, tool calls become
+//! `functionCall` parts and tool results `functionResponse` parts. System
+//! messages are folded into the `systemInstruction`.
+//!
+//! Gemini sends each function call whole rather than in fragments, so the
+//! stream yields complete [`StreamChunk::ToolCall`]s. Responses blocked by
+//! the safety filters end the stream with an error; the filters can be
+//! tuned with [`GeminiProvider::with_safety_settings`].
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use kosong_rs::{ChatProvider, GeminiProvider, Message, StreamChunk};
+//! use futures::StreamExt;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let provider = GeminiProvider::new("your-api-key", "gemini-2.5-flash")?;
+//!
+//! let messages = vec![Message::user("Hello!")];
+//! let mut stream = provider.generate(None, &messages).await?;
+//!
+//! while let Some(chunk) = stream.next().await {
+//!     if let StreamChunk::Text(text) = chunk? {
+//!         print!("{}", text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::token::{send_authorized, StaticToken, TokenSource};
+use super::{
+    ChatError, ChatOptions, ChatProvider, GenerateStream, ModelCapability, ResponseFormat, StreamChunk, ThinkingEffort,
+    TokenUsage,
+};
+use crate::message::{ContentPart, Message, Role, ToolCall};
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// The base URL for the Gemini API.
+pub const GEMINI_API_BASE: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// JSON Schema keywords the Gemini API rejects in function parameters.
+const UNSUPPORTED_SCHEMA_KEYS: &[&str] = &["$schema", "additionalProperties"];
+
+/// Google Gemini chat provider.
+#[derive(Debug, Clone)]
+pub struct GeminiProvider {
+    client: reqwest::Client,
+    token: Arc<dyn TokenSource>,
+    model: String,
+    base_url: String,
+    options: ChatOptions,
+    thinking_effort: Option<ThinkingEffort>,
+    capabilities: Vec<ModelCapability>,
+    safety_settings: Vec<SafetySetting>,
+}
+
+/// A category of harmful content the Gemini safety filters rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HarmCategory {
+    /// Negative or harmful comments targeting identity or protected attributes.
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    /// Content that is rude, disrespectful, or profane.
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    /// References to sexual acts or other lewd content.
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    /// Content that promotes or facilitates harmful acts.
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
+    /// Content that may be used to harm civic integrity.
+    #[serde(rename = "HARM_CATEGORY_CIVIC_INTEGRITY")]
+    CivicIntegrity,
+}
+
+impl HarmCategory {
+    /// Returns the name of the category in the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HarmCategory::Harassment => "HARM_CATEGORY_HARASSMENT",
+            HarmCategory::HateSpeech => "HARM_CATEGORY_HATE_SPEECH",
+            HarmCategory::SexuallyExplicit => "HARM_CATEGORY_SEXUALLY_EXPLICIT",
+            HarmCategory::DangerousContent => "HARM_CATEGORY_DANGEROUS_CONTENT",
+            HarmCategory::CivicIntegrity => "HARM_CATEGORY_CIVIC_INTEGRITY",
+        }
+    }
+}
+
+impl FromStr for HarmCategory {
+    type Err = ChatError;
+
+    /// Parses a category by its API name or the name without the
+    /// `HARM_CATEGORY_` prefix, ignoring case (e.g. `dangerous_content`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let name = s.trim().to_ascii_uppercase();
+        let name = name.strip_prefix("HARM_CATEGORY_").unwrap_or(&name);
+        match name {
+            "HARASSMENT" => Ok(HarmCategory::Harassment),
+            "HATE_SPEECH" => Ok(HarmCategory::HateSpeech),
+            "SEXUALLY_EXPLICIT" => Ok(HarmCategory::SexuallyExplicit),
+            "DANGEROUS_CONTENT" => Ok(HarmCategory::DangerousContent),
+            "CIVIC_INTEGRITY" => Ok(HarmCategory::CivicIntegrity),
+            _ => Err(ChatError::Config(format!("Unknown harm category: {}", s))),
+        }
+    }
+}
+
+/// How likely to be harmful content must be before it is blocked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HarmBlockThreshold {
+    /// Block content with a low or higher probability of harm.
+    #[serde(rename = "BLOCK_LOW_AND_ABOVE")]
+    BlockLowAndAbove,
+    /// Block content with a medium or higher probability of harm.
+    #[serde(rename = "BLOCK_MEDIUM_AND_ABOVE")]
+    BlockMediumAndAbove,
+    /// Block only content with a high probability of harm.
+    #[serde(rename = "BLOCK_ONLY_HIGH")]
+    BlockOnlyHigh,
+    /// Rate content but never block it.
+    #[serde(rename = "BLOCK_NONE")]
+    BlockNone,
+    /// Turn the filter off.
+    #[serde(rename = "OFF")]
+    Off,
+}
+
+impl HarmBlockThreshold {
+    /// Returns the name of the threshold in the API.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HarmBlockThreshold::BlockLowAndAbove => "BLOCK_LOW_AND_ABOVE",
+            HarmBlockThreshold::BlockMediumAndAbove => "BLOCK_MEDIUM_AND_ABOVE",
+            HarmBlockThreshold::BlockOnlyHigh => "BLOCK_ONLY_HIGH",
+            HarmBlockThreshold::BlockNone => "BLOCK_NONE",
+            HarmBlockThreshold::Off => "OFF",
+        }
+    }
+}
+
+impl FromStr for HarmBlockThreshold {
+    type Err = ChatError;
+
+    /// Parses a threshold by its API name, ignoring case (e.g. `block_only_high`).
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_uppercase().as_str() {
+            "BLOCK_LOW_AND_ABOVE" => Ok(HarmBlockThreshold::BlockLowAndAbove),
+            "BLOCK_MEDIUM_AND_ABOVE" => Ok(HarmBlockThreshold::BlockMediumAndAbove),
+            "BLOCK_ONLY_HIGH" => Ok(HarmBlockThreshold::BlockOnlyHigh),
+            "BLOCK_NONE" => Ok(HarmBlockThreshold::BlockNone),
+            "OFF" => Ok(HarmBlockThreshold::Off),
+            _ => Err(ChatError::Config(format!("Unknown harm block threshold: {}", s))),
+        }
+    }
+}
+
+/// A safety filter setting sent with each request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SafetySetting {
+    /// The category the setting applies to.
+    pub category: HarmCategory,
+    /// The threshold at which content in the category is blocked.
+    pub threshold: HarmBlockThreshold,
+}
+
+impl SafetySetting {
+    /// Creates a new safety setting.
+    pub fn new(category: HarmCategory, threshold: HarmBlockThreshold) -> Self {
+        Self { category, threshold }
+    }
+}
+
+/// A response from `generateContent`, or one event of `streamGenerateContent`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GenerateContentResponse {
+    #[serde(default)]
+    candidates: Vec<Candidate>,
+    prompt_feedback: Option<PromptFeedback>,
+    error: Option<ApiError>,
+    usage_metadata: Option<UsageMetadata>,
+}
+
+/// Token counts; each streamed event has those so far
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UsageMetadata {
+    #[serde(default)]
+    prompt_token_count: u32,
+    #[serde(default)]
+    candidates_token_count: u32,
+    #[serde(default)]
+    thoughts_token_count: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Candidate {
+    content: Option<CandidateContent>,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CandidateContent {
+    #[serde(default)]
+    parts: Vec<ResponsePart>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ResponsePart {
+    text: Option<String>,
+    /// Set on parts holding the model's thinking rather than its answer
+    #[serde(default)]
+    thought: bool,
+    function_call: Option<FunctionCallPart>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FunctionCallPart {
+    id: Option<String>,
+    name: String,
+    #[serde(default)]
+    args: Value,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PromptFeedback {
+    block_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiError {
+    #[serde(default)]
+    code: u16,
+    #[serde(default)]
+    message: String,
+}
+
+impl GeminiProvider {
+    /// Creates a new Gemini provider with the given API key and model.
+    ///
+    /// # Arguments
+    ///
+    /// * `api_key` - Your Gemini API key.
+    /// * `model` - The model name (e.g., "gemini-2.5-pro", "gemini-2.5-flash").
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new<S: Into<String>>(api_key: S, model: S) -> Result<Self, ChatError> {
+        Self::with_options(api_key, model, ChatOptions::default())
+    }
+
+    /// Creates a new Gemini provider with custom options.
+    pub fn with_options<S: Into<String>>(
+        api_key: S,
+        model: S,
+        options: ChatOptions,
+    ) -> Result<Self, ChatError> {
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| ChatError::Config(format!("Failed to create HTTP client: {}", e)))?;
+
+        let model_str = model.into();
+        let capabilities = Self::infer_capabilities(&model_str);
+
+        Ok(Self {
+            client,
+            token: Arc::new(StaticToken::new(api_key)),
+            model: model_str,
+            base_url: GEMINI_API_BASE.to_string(),
+            options,
+            thinking_effort: None,
+            capabilities,
+            safety_settings: Vec::new(),
+        })
+    }
+
+    /// Creates a new Gemini provider with a custom base URL.
+    pub fn with_base_url<S: Into<String>>(
+        api_key: S,
+        model: S,
+        base_url: S,
+    ) -> Result<Self, ChatError> {
+        let mut provider = Self::new(api_key, model)?;
+        provider.base_url = base_url.into().trim_end_matches('/').to_string();
+        Ok(provider)
+    }
+
+    /// Uses `source` for the API key of each request instead of the one
+    /// given at construction.
+    pub fn with_token_source(mut self, source: Arc<dyn TokenSource>) -> Self {
+        self.token = source;
+        self
+    }
+
+    /// Sets the safety filters sent with each request; categories left out
+    /// keep the API's default threshold.
+    pub fn with_safety_settings(mut self, settings: Vec<SafetySetting>) -> Self {
+        self.safety_settings = settings;
+        self
+    }
+
+    /// Returns the safety filters sent with each request.
+    pub fn safety_settings(&self) -> &[SafetySetting] {
+        &self.safety_settings
+    }
+
+    /// Returns the API base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Sets the chat options.
+    pub fn set_options(&mut self, options: ChatOptions) {
+        self.options = options;
+    }
+
+    /// Infers model capabilities based on the model name.
+    pub fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
+        let mut caps = vec![
+            ModelCapability::Streaming,
+            ModelCapability::ToolCalling,
+            ModelCapability::JsonMode,
+        ];
+
+        // Every Gemini model takes images, apart from the embedding models
+        if !model.contains("embedding") {
+            caps.push(ModelCapability::Vision);
+        }
+
+        // 2.5 and later models think before answering
+        if model.contains("gemini-2.5") || model.contains("gemini-3") || model.contains("thinking") {
+            caps.push(ModelCapability::Thinking);
+        }
+
+        caps
+    }
+
+    fn build_headers(&self, token: &str) -> Result<HeaderMap, ChatError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-goog-api-key",
+            HeaderValue::from_str(token).map_err(|e| ChatError::Config(format!("Invalid API key: {}", e)))?,
+        );
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Ok(headers)
+    }
+
+    fn build_request_body(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Value {
+        let (system, contents) = convert_messages(system_prompt, messages);
+        let mut body = json!({ "contents": contents });
+
+        if !system.is_empty() {
+            body["systemInstruction"] = json!({ "parts": [{ "text": system.join("\n\n") }] });
+        }
+
+        if let Some(tools) = tools.filter(|tools| !tools.is_empty()) {
+            let declarations: Vec<Value> = tools
+                .iter()
+                .map(|tool| {
+                    json!({
+                        "name": tool.function.name,
+                        "description": tool.function.description,
+                        "parameters": clean_schema(&tool.function.parameters),
+                    })
+                })
+                .collect();
+            body["tools"] = json!([{ "functionDeclarations": declarations }]);
+        }
+
+        let mut config = serde_json::Map::new();
+        if let Some(max_tokens) = self.options.max_tokens {
+            config.insert("maxOutputTokens".to_string(), max_tokens.into());
+        }
+        if let Some(temperature) = self.options.temperature {
+            config.insert("temperature".to_string(), temperature.into());
+        }
+        if let Some(top_p) = self.options.top_p {
+            config.insert("topP".to_string(), top_p.into());
+        }
+        if let Some(penalty) = self.options.frequency_penalty {
+            config.insert("frequencyPenalty".to_string(), penalty.into());
+        }
+        if let Some(penalty) = self.options.presence_penalty {
+            config.insert("presencePenalty".to_string(), penalty.into());
+        }
+        if let Some(stop) = &self.options.stop {
+            config.insert("stopSequences".to_string(), stop.clone().into());
+        }
+        match &self.options.response_format {
+            Some(ResponseFormat::JsonObject) => {
+                config.insert("responseMimeType".to_string(), "application/json".into());
+            }
+            Some(ResponseFormat::JsonSchema { schema, .. }) => {
+                config.insert("responseMimeType".to_string(), "application/json".into());
+                config.insert("responseJsonSchema".to_string(), schema.clone());
+            }
+            Some(ResponseFormat::Text) | None => {}
+        }
+        if let Some(effort) = self.thinking_effort.filter(|_| self.has_capability(ModelCapability::Thinking)) {
+            config.insert(
+                "thinkingConfig".to_string(),
+                json!({ "thinkingBudget": thinking_budget(effort), "includeThoughts": true }),
+            );
+        }
+        if !config.is_empty() {
+            body["generationConfig"] = Value::Object(config);
+        }
+
+        if !self.safety_settings.is_empty() {
+            body["safetySettings"] = serde_json::to_value(&self.safety_settings).unwrap_or_default();
+        }
+
+        body
+    }
+}
+
+#[async_trait]
+impl ChatProvider for GeminiProvider {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        let body = self.build_request_body(system_prompt, messages, tools);
+        let url = match self.options.stream {
+            true => format!("{}/models/{}:streamGenerateContent?alt=sse", self.base_url, self.model),
+            false => format!("{}/models/{}:generateContent", self.base_url, self.model),
+        };
+
+        tracing::debug!("Sending request to {}", url);
+
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(&body))
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ChatError::Api {
+                status,
+                message: error_text,
+            });
+        }
+
+        if !self.options.stream {
+            let text = response.text().await?;
+            let chunks = parse_response(&text);
+            return Ok(Box::pin(futures::stream::iter(chunks)));
+        }
+
+        // Events are buffered until complete; each may hold several parts
+        let stream = futures::stream::unfold(
+            (response.bytes_stream(), Vec::new(), VecDeque::new(), 0usize),
+            |(mut byte_stream, mut buffer, mut pending, mut calls)| async move {
+                loop {
+                    if let Some(chunk) = pending.pop_front() {
+                        return Some((chunk, (byte_stream, buffer, pending, calls)));
+                    }
+                    if let Some(line) = super::take_line(&mut buffer) {
+                        if let Some(data) = line.trim_end().strip_prefix("data:") {
+                            pending.extend(parse_event(data.trim_start(), &mut calls));
+                        }
+                        continue;
+                    }
+                    match byte_stream.next().await {
+                        Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+                        Some(Err(e)) => {
+                            return Some((Err(ChatError::Request(e)), (byte_stream, buffer, pending, calls)));
+                        }
+                        None => {
+                            // A final event without a trailing newline
+                            let rest = String::from_utf8_lossy(&std::mem::take(&mut buffer)).into_owned();
+                            match rest.trim_end().strip_prefix("data:") {
+                                Some(data) => pending.extend(parse_event(data.trim_start(), &mut calls)),
+                                None => return None,
+                            }
+                        }
+                    }
+                }
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        let mut new_provider = self.clone();
+        new_provider.thinking_effort = Some(effort);
+        Box::new(new_provider)
+    }
+
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let mut new_provider = self.clone();
+        new_provider.options.response_format = Some(format);
+        Some(Box::new(new_provider))
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        &self.capabilities
+    }
+}
+
+/// Token budget for thinking at each effort level.
+fn thinking_budget(effort: ThinkingEffort) -> u32 {
+    match effort {
+        ThinkingEffort::Low => 1024,
+        ThinkingEffort::Medium => 8192,
+        ThinkingEffort::High => 24576,
+    }
+}
+
+/// Splits messages into system instructions and Gemini `contents`.
+///
+/// Consecutive messages with the same Gemini role are merged into one turn,
+/// so the results of parallel tool calls are answered together.
+fn convert_messages(system_prompt: Option<&str>, messages: &[Message]) -> (Vec<String>, Vec<Value>) {
+    let mut system: Vec<String> = system_prompt.map(str::to_string).into_iter().collect();
+    let mut contents: Vec<Value> = Vec::new();
+    // Tool results name their call by ID; Gemini wants the function's name
+    let mut call_names: HashMap<String, String> = HashMap::new();
+
+    for msg in messages {
+        let (role, parts) = match msg.role {
+            Role::System => {
+                if let Some(text) = msg.text().filter(|text| !text.is_empty()) {
+                    system.push(text);
+                }
+                continue;
+            }
+            Role::User => ("user", content_parts(msg)),
+            Role::Assistant => {
+                let mut parts = content_parts(msg);
+                for call in msg.tool_calls.iter().flatten() {
+                    call_names.insert(call.id.clone(), call.function.name.clone());
+                    let args = serde_json::from_str::<Value>(&call.function.arguments)
+                        .ok()
+                        .filter(Value::is_object)
+                        .unwrap_or_else(|| json!({}));
+                    parts.push(json!({ "functionCall": { "name": call.function.name, "args": args } }));
+                }
+                ("model", parts)
+            }
+            Role::Tool => {
+                let name = msg
+                    .tool_call_id
+                    .as_ref()
+                    .and_then(|id| call_names.get(id).cloned())
+                    .or_else(|| msg.name.clone())
+                    .unwrap_or_default();
+                let response = json!({ "content": msg.text().unwrap_or_default() });
+                ("user", vec![json!({ "functionResponse": { "name": name, "response": response } })])
+            }
+        };
+        if parts.is_empty() {
+            continue;
+        }
+        match contents.last_mut() {
+            Some(last) if last["role"] == role => {
+                if let Some(existing) = last["parts"].as_array_mut() {
+                    existing.extend(parts);
+                }
+            }
+            _ => contents.push(json!({ "role": role, "parts": parts })),
+        }
+    }
+
+    (system, contents)
+}
+
+/// The text and media of a message as Gemini parts, without thinking.
+fn content_parts(msg: &Message) -> Vec<Value> {
+    let Some(content) = &msg.content else {
+        return Vec::new();
+    };
+    let Some(parts) = content.as_parts() else {
+        let text = content.to_text();
+        return match text.is_empty() {
+            true => Vec::new(),
+            false => vec![json!({ "text": text })],
+        };
+    };
+    parts
+        .iter()
+        .filter_map(|part| match part {
+            ContentPart::Text { text } => Some(json!({ "text": text })),
+            ContentPart::Think { .. } => None,
+            ContentPart::ImageUrl { image_url } => Some(media_part(&image_url.url, "image/jpeg")),
+            ContentPart::AudioUrl { audio_url } => Some(media_part(&audio_url.url, "audio/mpeg")),
+            ContentPart::VideoUrl { video_url } => Some(media_part(&video_url.url, "video/mp4")),
+        })
+        .collect()
+}
+
+/// A media part: `data:` URLs are sent inline, other URLs by reference.
+fn media_part(url: &str, default_mime: &str) -> Value {
+    if let Some((header, data)) = url.strip_prefix("data:").and_then(|rest| rest.split_once(',')) {
+        let mime = header.strip_suffix(";base64").unwrap_or(header);
+        return json!({ "inlineData": { "mimeType": mime, "data": data } });
+    }
+    json!({ "fileData": { "mimeType": mime_from_extension(url).unwrap_or(default_mime), "fileUri": url } })
+}
+
+fn mime_from_extension(url: &str) -> Option<&'static str> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    let extension = path.rsplit_once('.')?.1.to_ascii_lowercase();
+    Some(match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        _ => return None,
+    })
+}
+
+/// A copy of a parameters schema without the keywords Gemini rejects.
+fn clean_schema(schema: &Value) -> Value {
+    match schema {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .filter(|(key, _)| !UNSUPPORTED_SCHEMA_KEYS.contains(&key.as_str()))
+                .map(|(key, value)| (key.clone(), clean_schema(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(clean_schema).collect()),
+        other => other.clone(),
+    }
+}
+
+/// Parses a complete (non-streaming) response body.
+fn parse_response(text: &str) -> Vec<Result<StreamChunk, ChatError>> {
+    let mut calls = 0;
+    parse_event(text, &mut calls).into_iter().collect()
+}
+
+/// Parses one response event into chunks.
+///
+/// `calls` counts the function calls seen so far in the response, to give
+/// calls without an ID one that is unique within it.
+fn parse_event(data: &str, calls: &mut usize) -> Vec<Result<StreamChunk, ChatError>> {
+    let response: GenerateContentResponse = match serde_json::from_str(data) {
+        Ok(response) => response,
+        Err(e) => return vec![Err(ChatError::Parse(format!("Failed to parse chunk: {} - {}", e, data)))],
+    };
+    if let Some(error) = response.error {
+        return vec![Err(ChatError::Api { status: error.code, message: error.message })];
+    }
+    if let Some(reason) = response.prompt_feedback.and_then(|feedback| feedback.block_reason) {
+        return vec![Err(ChatError::Other(format!("The prompt was blocked by Gemini ({})", reason)))];
+    }
+
+    let mut chunks = Vec::new();
+    let Some(candidate) = response.candidates.into_iter().next() else {
+        return chunks;
+    };
+    for part in candidate.content.map(|content| content.parts).unwrap_or_default() {
+        if let Some(call) = part.function_call {
+            *calls += 1;
+            let id = call.id.unwrap_or_else(|| format!("call_{}_{}", call.name, calls));
+            let args = match call.args {
+                Value::Null => "{}".to_string(),
+                args => args.to_string(),
+            };
+            chunks.push(Ok(StreamChunk::ToolCall(ToolCall::new(id, call.name, args))));
+        } else if let Some(text) = part.text.filter(|text| !text.is_empty()) {
+            match part.thought {
+                true => chunks.push(Ok(StreamChunk::Thinking(text))),
+                false => chunks.push(Ok(StreamChunk::Text(text))),
+            }
+        }
+    }
+    if let Some(reason @ ("SAFETY" | "RECITATION" | "BLOCKLIST" | "PROHIBITED_CONTENT" | "SPII")) =
+        candidate.finish_reason.as_deref()
+    {
+        chunks.push(Err(ChatError::Other(format!("The response was blocked by Gemini ({})", reason))));
+    }
+    // The event with the finish reason has the final counts
+    if candidate.finish_reason.is_some() {
+        if let Some(usage) = response.usage_metadata {
+            let completion_tokens = usage.candidates_token_count + usage.thoughts_token_count;
+            chunks.push(Ok(StreamChunk::Usage(TokenUsage::new(usage.prompt_token_count, completion_tokens))));
+        }
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_provider::ToolDefinition;
+
+    #[test]
+    fn test_infer_capabilities() {
+        let caps = GeminiProvider::infer_capabilities("gemini-2.5-pro");
+        assert!(caps.contains(&ModelCapability::ToolCalling));
+        assert!(caps.contains(&ModelCapability::Vision));
+        assert!(caps.contains(&ModelCapability::Thinking));
+
+        let caps = GeminiProvider::infer_capabilities("gemini-2.0-flash");
+        assert!(!caps.contains(&ModelCapability::Thinking));
+    }
+
+    #[test]
+    fn test_build_request_body() {
+        let provider = GeminiProvider::new("test-key", "gemini-2.5-flash")
+            .unwrap()
+            .with_safety_settings(vec![SafetySetting::new(
+                HarmCategory::DangerousContent,
+                HarmBlockThreshold::BlockOnlyHigh,
+            )]);
+        let messages = vec![
+            Message::system("Prefer short answers."),
+            Message::user_with_parts(vec![
+                ContentPart::text("What is in this image?"),
+                ContentPart::image_url("data:image/png;base64,AAAA"),
+            ]),
+            Message::with_tool_calls(vec![
+                ToolCall::new("call_1", "read_file", r#"{"path":"a.txt"}"#),
+                ToolCall::new("call_2", "read_file", r#"{"path":"b.txt"}"#),
+            ]),
+            Message::tool("call_1", "alpha"),
+            Message::tool("call_2", "beta"),
+        ];
+        let tools = vec![ToolDefinition::new(
+            "read_file",
+            "Read a file",
+            json!({
+                "$schema": "http://json-schema.org/draft-07/schema#",
+                "type": "object",
+                "properties": {"path": {"type": "string"}},
+                "additionalProperties": false,
+            }),
+        )];
+        let body = provider.build_request_body(Some("Be helpful."), &messages, Some(&tools));
+
+        assert_eq!(body["systemInstruction"]["parts"][0]["text"], "Be helpful.\n\nPrefer short answers.");
+        let contents = body["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[0]["role"], "user");
+        assert_eq!(contents[0]["parts"][1]["inlineData"], json!({"mimeType": "image/png", "data": "AAAA"}));
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["functionCall"], json!({"name": "read_file", "args": {"path": "a.txt"}}));
+        assert_eq!(contents[2]["role"], "user");
+        assert_eq!(
+            contents[2]["parts"],
+            json!([
+                {"functionResponse": {"name": "read_file", "response": {"content": "alpha"}}},
+                {"functionResponse": {"name": "read_file", "response": {"content": "beta"}}},
+            ])
+        );
+        assert_eq!(
+            body["tools"][0]["functionDeclarations"][0]["parameters"],
+            json!({"type": "object", "properties": {"path": {"type": "string"}}})
+        );
+        assert_eq!(
+            body["safetySettings"],
+            json!([{"category": "HARM_CATEGORY_DANGEROUS_CONTENT", "threshold": "BLOCK_ONLY_HIGH"}])
+        );
+        assert!(body.get("generationConfig").is_none());
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let mut calls = 0;
+        let data = r#"{"candidates":[{"content":{"role":"model","parts":[
+            {"text":"Considering","thought":true},
+            {"text":"Reading it."},
+            {"functionCall":{"name":"read_file","args":{"path":"a.txt"}}}
+        ]}}]}"#;
+        let chunks: Vec<StreamChunk> = parse_event(data, &mut calls).into_iter().map(Result::unwrap).collect();
+        assert_eq!(
+            chunks,
+            [
+                StreamChunk::Thinking("Considering".to_string()),
+                StreamChunk::Text("Reading it.".to_string()),
+                StreamChunk::ToolCall(ToolCall::new("call_read_file_1", "read_file", r#"{"path":"a.txt"}"#)),
+            ]
+        );
+
+        let last = r#"{"candidates":[{"content":{"parts":[{"text":"Done."}]},"finishReason":"STOP"}],
+            "usageMetadata":{"promptTokenCount":40,"candidatesTokenCount":8,"thoughtsTokenCount":100}}"#;
+        let chunks: Vec<StreamChunk> = parse_event(last, &mut calls).into_iter().map(Result::unwrap).collect();
+        assert_eq!(chunks[1], StreamChunk::Usage(TokenUsage::new(40, 108)));
+
+        let blocked = parse_event(r#"{"candidates":[{"finishReason":"SAFETY"}]}"#, &mut calls);
+        assert!(matches!(&blocked[..], [Err(ChatError::Other(message))] if message.contains("SAFETY")));
+        let blocked = parse_event(r#"{"promptFeedback":{"blockReason":"OTHER"}}"#, &mut calls);
+        assert!(blocked[0].is_err());
+
+        assert_eq!("dangerous_content".parse::<HarmCategory>().unwrap(), HarmCategory::DangerousContent);
+        assert_eq!("HARM_CATEGORY_HATE_SPEECH".parse::<HarmCategory>().unwrap(), HarmCategory::HateSpeech);
+        assert_eq!("block_none".parse::<HarmBlockThreshold>().unwrap(), HarmBlockThreshold::BlockNone);
+        assert!("sometimes".parse::<HarmBlockThreshold>().is_err());
+    }
+
+    #[test]
+    fn test_lines_split_inside_a_character() {
+        let event = "data: {\"text\":\"héllo\"}\n".as_bytes();
+        let split = event.iter().position(|&b| b == 0xc3).unwrap() + 1;
+        let mut buffer = event[..split].to_vec();
+        assert_eq!(crate::chat_provider::take_line(&mut buffer), None);
+        buffer.extend_from_slice(&event[split..]);
+        assert_eq!(crate::chat_provider::take_line(&mut buffer).as_deref(), Some("data: {\"text\":\"héllo\"}\n"));
+        assert!(buffer.is_empty());
+    }
+}
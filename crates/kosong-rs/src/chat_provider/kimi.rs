@@ -3,14 +3,18 @@
 //! This module provides a [`ChatProvider`] implementation for Moonshot AI's Kimi API.
 
 use crate::chat_provider::{
-    ChatError, ChatOptions, ChatProvider, GenerateStream, StreamChunk, ModelCapability, ThinkingEffort,
+    ChatError, ChatOptions, ChatProvider, CompletionResponse, FinishReason, GenerateStream, StreamChunk,
+    ModelCapability, ThinkingEffort, TokenUsage,
 };
-use crate::message::{Message, ToolCall, ToolCallPart};
+use crate::chat_provider::token::{send_authorized, StaticToken, TokenSource};
+use crate::chat_provider::tool_calls::ToolCallAccumulator;
+use crate::message::{ContentPart, Message, MessageContent, ToolCall, ToolCallPart};
 use async_trait::async_trait;
 use futures::{stream, StreamExt};
 use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
-
+use std::collections::VecDeque;
+use std::sync::Arc;
 
 /// Default base URL for the Kimi API.
 const KIMI_API_BASE: HeaderValue = HeaderValue::from_static("https://api.moonshot.cn/v1");
@@ -19,9 +23,11 @@ const KIMI_API_BASE: HeaderValue = HeaderValue::from_static("https://api.moonsho
 #[derive(Debug, Clone)]
 pub struct KimiProvider {
     client: reqwest::Client,
-    api_key: String,
+    token: Arc<dyn TokenSource>,
     model: String,
     base_url: String,
+    /// Headers sent with every request, over the provider's own
+    custom_headers: HeaderMap,
     options: ChatOptions,
     thinking_effort: ThinkingEffort,
     capabilities: Vec<ModelCapability>,
@@ -46,11 +52,19 @@ struct KimiRequest {
     stop: Option<Vec<String>>,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
+    stream_options: Option<StreamOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     response_format: Option<ResponseFormat>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<super::ToolDefinition>>,
 }
 
+/// Options of a streamed response.
+#[derive(Debug, Serialize)]
+struct StreamOptions {
+    include_usage: bool,
+}
+
 /// Response format for structured outputs.
 #[derive(Debug, Serialize)]
 struct ResponseFormat {
@@ -62,26 +76,30 @@ struct ResponseFormat {
 struct KimiMessage {
     role: String,
     #[serde(skip_serializing_if = "Option::is_none")]
-    content: Option<String>,
+    content: Option<MessageContent>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_calls: Option<Vec<ToolCall>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tool_call_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     name: Option<String>,
+    /// The model's thinking, in responses
+    #[serde(default, skip_serializing)]
+    reasoning_content: Option<String>,
 }
 
 /// Response from the Kimi API (non-streaming).
 #[derive(Debug, Deserialize)]
 struct KimiResponse {
     choices: Vec<KimiChoice>,
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 /// A choice in the Kimi API response.
 #[derive(Debug, Deserialize)]
 struct KimiChoice {
     message: KimiMessage,
-    #[allow(dead_code)]
     finish_reason: Option<String>,
 }
 
@@ -89,14 +107,19 @@ struct KimiChoice {
 #[derive(Debug, Deserialize)]
 struct KimiStreamChunk {
     choices: Vec<KimiStreamChoice>,
+    /// Token usage, on the last chunk of OpenAI-style streams
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 /// A choice within a streaming chunk.
 #[derive(Debug, Deserialize)]
 struct KimiStreamChoice {
     delta: KimiDelta,
-    #[allow(dead_code)]
     finish_reason: Option<String>,
+    /// Token usage; Moonshot sends it on the choice of the last chunk
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 /// The delta content in a streaming chunk.
@@ -105,13 +128,11 @@ struct KimiDelta {
     #[serde(default)]
     content: Option<String>,
     #[serde(default)]
-    #[allow(dead_code)]
     reasoning_content: Option<String>,
     #[serde(default)]
     #[allow(dead_code)]
     role: Option<String>,
     #[serde(default)]
-    #[allow(dead_code)]
     tool_calls: Option<Vec<ToolCallPart>>,
 }
 
@@ -168,9 +189,10 @@ impl KimiProvider {
 
         Ok(Self {
             client,
-            api_key: api_key.into(),
+            token: Arc::new(StaticToken::new(api_key)),
             model: model_str,
             base_url: KIMI_API_BASE.to_str().unwrap().to_string(),
+            custom_headers: HeaderMap::new(),
             options,
             thinking_effort: ThinkingEffort::default(),
             capabilities,
@@ -190,8 +212,34 @@ impl KimiProvider {
         Ok(provider)
     }
 
+    /// Uses `source` for the bearer token of each request instead of the
+    /// API key given at construction.
+    pub fn with_token_source(mut self, source: Arc<dyn TokenSource>) -> Self {
+        self.token = source;
+        self
+    }
+
+    /// Sends `headers` with every request, replacing the provider's own
+    /// headers of the same names.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.custom_headers.extend(headers);
+        self
+    }
+
+    /// Sends requests through the HTTP(S) proxy at `url`, e.g.
+    /// `http://proxy.internal:8080`, instead of the one the `HTTPS_PROXY`
+    /// environment variable names, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` is not a valid proxy URL.
+    pub fn with_proxy(mut self, url: &str) -> Result<Self, ChatError> {
+        self.client = super::proxied_client(url)?;
+        Ok(self)
+    }
+
     /// Infers model capabilities based on the model name.
-    fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
+    pub fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
         let mut caps = vec![
             ModelCapability::Streaming,
             ModelCapability::ToolCalling,
@@ -218,22 +266,38 @@ impl KimiProvider {
     }
 
     /// Converts a generic message to the Kimi API format.
+    ///
+    /// Messages with images or other media keep their parts, minus thinking;
+    /// everything else is flattened to text.
     fn convert_message(msg: &Message) -> KimiMessage {
+        let content = msg.content.as_ref().map(|content| match content.as_parts() {
+            Some(parts) if parts.iter().any(|p| p.as_text().is_none() && p.as_think().is_none()) => {
+                MessageContent::Parts(
+                    parts
+                        .iter()
+                        .filter(|p| !matches!(p, ContentPart::Think { .. }))
+                        .cloned()
+                        .collect(),
+                )
+            }
+            _ => MessageContent::Text(content.to_text()),
+        });
         KimiMessage {
             role: msg.role.as_str().to_string(),
-            content: msg.text(),
+            content,
             tool_calls: msg.tool_calls.clone(),
             tool_call_id: msg.tool_call_id.clone(),
             name: msg.name.clone(),
+            reasoning_content: None,
         }
     }
 
     /// Builds the request headers.
-    fn build_headers(&self) -> Result<HeaderMap, ChatError> {
+    fn build_headers(&self, token: &str) -> Result<HeaderMap, ChatError> {
         let mut headers = HeaderMap::new();
         headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
 
-        let auth_value = format!("Bearer {}", self.api_key);
+        let auth_value = format!("Bearer {}", token);
         let auth_header = HeaderValue::from_str(&auth_value)
             .map_err(|e| ChatError::Config(format!("Invalid API key: {}", e)))?;
         headers.insert(AUTHORIZATION, auth_header);
@@ -274,6 +338,7 @@ impl KimiProvider {
             headers.insert("X-Msh-Device-Id", device_id_header);
         }
 
+        headers.extend(self.custom_headers.clone());
         Ok(headers)
     }
 
@@ -286,7 +351,7 @@ impl KimiProvider {
     ) -> KimiRequest {
         let mut kimi_messages: Vec<KimiMessage> = messages
             .iter()
-            .map(|msg| Self::convert_message(msg))
+            .map(Self::convert_message)
             .collect();
 
         // Prepend system message if provided
@@ -295,10 +360,11 @@ impl KimiProvider {
                 0,
                 KimiMessage {
                     role: "system".to_string(),
-                    content: Some(system.to_string()),
+                    content: Some(MessageContent::Text(system.to_string())),
                     tool_calls: None,
                     tool_call_id: None,
                     name: None,
+                    reasoning_content: None,
                 },
             );
         }
@@ -313,37 +379,30 @@ impl KimiProvider {
             presence_penalty: self.options.presence_penalty,
             stop: self.options.stop.clone(),
             stream: self.options.stream,
+            // Streams only report usage when asked to
+            stream_options: self.options.stream.then_some(StreamOptions { include_usage: true }),
+            // The API has JSON mode but takes no schema; the caller checks
+            // replies against it
             response_format: self.options.response_format.as_ref().map(|f| ResponseFormat {
-                r#type: f.type_str().to_string(),
+                r#type: match f {
+                    super::ResponseFormat::JsonSchema { .. } => "json_object".to_string(),
+                    _ => f.type_str().to_string(),
+                },
             }),
             tools: tools.map(|t| t.to_vec()),
         }
     }
-}
-
-#[async_trait]
-impl ChatProvider for KimiProvider {
-    async fn generate_with_tools(
-        &self,
-        system_prompt: Option<&str>,
-        messages: &[Message],
-        tools: Option<&[super::ToolDefinition]>,
-    ) -> Result<GenerateStream, ChatError> {
-        let headers = self.build_headers()?;
-        let body = self.build_request_body(system_prompt, messages, tools);
 
+    /// Sends a chat completion request, failing on an error status.
+    async fn send(&self, body: &KimiRequest) -> Result<reqwest::Response, ChatError> {
         let url = format!("{}/chat/completions", self.base_url);
 
         tracing::debug!("Sending request to {}", url);
 
-        let response = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await
-            .map_err(ChatError::Request)?;
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(body))
+        })
+        .await?;
         
         tracing::debug!("Response status: {}", response.status());
 
@@ -358,147 +417,100 @@ impl ChatProvider for KimiProvider {
                 message: error_text,
             });
         }
+        Ok(response)
+    }
 
-        // For non-streaming, we'd parse the full response
+    /// Converts a non-streaming response, using its first choice.
+    fn completion(response: KimiResponse) -> CompletionResponse {
+        let Some(choice) = response.choices.into_iter().next() else {
+            return CompletionResponse { usage: response.usage, ..CompletionResponse::new(String::new(), Vec::new()) };
+        };
+        let text = choice.message.content.map(|c| c.to_text()).unwrap_or_default();
+        let thinking = choice.message.reasoning_content.unwrap_or_default();
+        CompletionResponse {
+            finish_reason: choice.finish_reason.as_deref().map(FinishReason::parse),
+            usage: response.usage,
+            ..CompletionResponse::new(text, choice.message.tool_calls.unwrap_or_default()).with_thinking(thinking)
+        }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for KimiProvider {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        let body = self.build_request_body(system_prompt, messages, tools);
+        let response = self.send(&body).await?;
+
+        // For non-streaming, we parse the full response
         // For streaming, we process the SSE stream
         if !self.options.stream {
             let kimi_response: KimiResponse = response.json().await.map_err(ChatError::Request)?;
-            
-            // Check for tool calls first
-            if let Some(choice) = kimi_response.choices.into_iter().next() {
-                if let Some(tool_calls) = choice.message.tool_calls {
-                    // Return tool calls as stream chunks
-                    let chunks: Vec<_> = tool_calls.into_iter()
-                        .map(|tc| Ok(StreamChunk::ToolCall(tc)))
-                        .collect();
-                    let stream = stream::iter(chunks);
-                    return Ok(Box::pin(stream));
-                }
-                
-                // Return text content
-                let text = choice.message.content.unwrap_or_default();
-                let stream = stream::once(async move { Ok(StreamChunk::Text(text)) });
-                return Ok(Box::pin(stream));
-            }
-            
-            // Empty response
-            let stream = stream::once(async move { Ok(StreamChunk::Text(String::new())) });
-            return Ok(Box::pin(stream));
+            let completion = Self::completion(kimi_response);
+
+            // Tool calls take the place of the text
+            let chunks: Vec<_> = match completion.message.tool_calls {
+                Some(tool_calls) => tool_calls.into_iter().map(|tc| Ok(StreamChunk::ToolCall(tc))).collect(),
+                None => vec![Ok(StreamChunk::Text(completion.message.text().unwrap_or_default()))],
+            };
+            return Ok(Box::pin(stream::iter(chunks)));
         }
 
         // Handle streaming response with proper SSE parsing
         tracing::trace!("Processing streaming response...");
-        
-        // Use unfold to maintain state (buffer and pending tool calls) across chunks
+
+        // Use unfold to keep the buffer, the chunks parsed but not yet
+        // returned and the tool calls being assembled across reads
         let stream = futures::stream::unfold(
-            (response.bytes_stream(), String::new(), std::collections::HashMap::<String, ToolCallPart>::new(), None::<ToolCall>),
-            |(mut byte_stream, mut buffer, mut pending_tool_calls, complete_tool_call)| async move {
-                // First, return any complete tool call we have queued
-                if let Some(tool_call) = complete_tool_call {
-                    return Some((Ok(StreamChunk::ToolCall(tool_call)), (byte_stream, buffer, pending_tool_calls, None)));
-                }
-                
+            (response.bytes_stream(), String::new(), VecDeque::new(), StreamState::default(), false),
+            |(mut byte_stream, mut buffer, mut pending, mut state, mut finished)| async move {
                 loop {
+                    if let Some(chunk) = pending.pop_front() {
+                        return Some((chunk, (byte_stream, buffer, pending, state, finished)));
+                    }
+                    if finished {
+                        return None;
+                    }
+
                     // Try to process any complete lines in the buffer first
                     if let Some(newline_pos) = buffer.find('\n') {
                         let line = buffer.drain(..=newline_pos).collect::<String>();
                         let line = line.trim_end();
-                        
                         tracing::trace!("Processing line: {}", line);
-                        
-                        if line.starts_with("data:") {
-                            let data = line[5..].trim_start();
+
+                        if let Some(data) = line.strip_prefix("data:") {
+                            let data = data.trim_start();
                             if data == "[DONE]" {
                                 tracing::debug!("Received [DONE]");
-                                // Try to return one complete tool call, or end stream
-                                for (id, part) in pending_tool_calls.iter() {
-                                    if let Some(tool_call) = part.to_tool_call() {
-                                        let id = id.clone();
-                                        pending_tool_calls.remove(&id);
-                                        return Some((Ok(StreamChunk::ToolCall(tool_call)), (byte_stream, buffer, pending_tool_calls, None)));
-                                    }
-                                }
-                                return None; // End of stream
-                            }
-                            
-                            tracing::trace!("Parsing JSON chunk");
-                            match serde_json::from_str::<KimiStreamChunk>(data) {
-                                Ok(chunk) => {
-                                    if let Some(choice) = chunk.choices.into_iter().next() {
-                                        // Check for tool_calls first
-                                        if let Some(tool_call_parts) = choice.delta.tool_calls {
-                                            for part in tool_call_parts {
-                                                let id = part.id.clone();
-                                                
-                                                // Check if this is a new tool call or an update
-                                                if let Some(existing) = pending_tool_calls.get_mut(&id) {
-                                                    // Merge the new part into the existing one
-                                                    existing.merge(&part);
-                                                    
-                                                    // Check if it's now complete
-                                                    if let Some(tool_call) = existing.to_tool_call() {
-                                                        pending_tool_calls.remove(&id);
-                                                        // Queue any other complete tool calls for next iteration
-                                                        let mut next_complete = None;
-                                                        for (other_id, other_part) in pending_tool_calls.iter() {
-                                                            if let Some(tc) = other_part.to_tool_call() {
-                                                                next_complete = Some(tc);
-                                                                let oid = other_id.clone();
-                                                                pending_tool_calls.remove(&oid);
-                                                                break;
-                                                            }
-                                                        }
-                                                        return Some((Ok(StreamChunk::ToolCall(tool_call)), (byte_stream, buffer, pending_tool_calls, next_complete)));
-                                                    }
-                                                } else {
-                                                    // New tool call part
-                                                    pending_tool_calls.insert(id, part);
-                                                }
-                                            }
-                                            // Continue to process more data
-                                            continue;
-                                        }
-                                        // Check for content
-                                        if let Some(content) = choice.delta.content {
-                                            if !content.is_empty() {
-                                                return Some((Ok(StreamChunk::Text(content)), (byte_stream, buffer, pending_tool_calls, None)));
-                                            }
-                                        }
-                                        // Skip reasoning_content for now
-                                    }
-                                }
-                                Err(e) => {
-                                    tracing::warn!("Failed to parse chunk: {}", e);
-                                    // Continue to next line on parse error
-                                }
+                                pending.extend(state.finish());
+                                finished = true;
+                            } else {
+                                pending.extend(state.parse_data(data));
                             }
                         }
-                        // Continue to process more lines
                         continue;
                     }
-                    
+
                     // No complete line in buffer, fetch more data
                     match byte_stream.next().await {
                         Some(Ok(bytes)) => {
                             let text = String::from_utf8_lossy(&bytes);
                             tracing::trace!("Received bytes: {}", text);
                             buffer.push_str(&text);
-                            // Continue the loop to try processing again
                         }
                         Some(Err(e)) => {
                             tracing::error!("Stream error: {}", e);
-                            return Some((Err(ChatError::Request(e)), (byte_stream, buffer, pending_tool_calls, None)));
+                            finished = true;
+                            pending.push_back(Err(ChatError::Request(e)));
                         }
                         None => {
-                            // End of byte stream - return any remaining complete tool calls
-                            for (id, part) in pending_tool_calls.iter() {
-                                if let Some(tool_call) = part.to_tool_call() {
-                                    let id = id.clone();
-                                    pending_tool_calls.remove(&id);
-                                    return Some((Ok(StreamChunk::ToolCall(tool_call)), (byte_stream, buffer, pending_tool_calls, None)));
-                                }
-                            }
-                            return None;
+                            // End of byte stream without [DONE]
+                            finished = true;
+                            pending.extend(state.finish());
                         }
                     }
                 }
@@ -508,6 +520,20 @@ impl ChatProvider for KimiProvider {
         Ok(Box::pin(stream))
     }
 
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        let mut body = self.build_request_body(system_prompt, messages, tools);
+        body.stream = false;
+        body.stream_options = None;
+        let response = self.send(&body).await?;
+        let kimi_response: KimiResponse = response.json().await.map_err(ChatError::Request)?;
+        Ok(Self::completion(kimi_response))
+    }
+
     fn model_name(&self) -> &str {
         &self.model
     }
@@ -518,23 +544,87 @@ impl ChatProvider for KimiProvider {
         Box::new(new_provider)
     }
 
+    fn with_response_format(&self, format: super::ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let mut new_provider = self.clone();
+        new_provider.options.response_format = Some(format);
+        Some(Box::new(new_provider))
+    }
+
     fn capabilities(&self) -> &[ModelCapability] {
         &self.capabilities
     }
 }
 
+/// What a streamed response has sent so far that is returned at its end.
+#[derive(Debug, Default)]
+struct StreamState {
+    /// Tool calls being assembled from their parts
+    calls: ToolCallAccumulator,
+    /// The usage, once a chunk has carried it
+    usage: Option<TokenUsage>,
+}
+
+impl StreamState {
+    /// Parses the data of one SSE event into chunks.
+    ///
+    /// Tool call parts are held until their choice finishes, as the
+    /// arguments are incomplete before.
+    fn parse_data(&mut self, data: &str) -> Vec<Result<StreamChunk, ChatError>> {
+        let chunk = match serde_json::from_str::<KimiStreamChunk>(data) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                tracing::warn!("Failed to parse chunk: {}", e);
+                return Vec::new();
+            }
+        };
+        if chunk.usage.is_some() {
+            self.usage = chunk.usage;
+        }
+        let mut chunks = Vec::new();
+        let Some(choice) = chunk.choices.into_iter().next() else {
+            return chunks;
+        };
+        if choice.usage.is_some() {
+            self.usage = choice.usage;
+        }
+        if let Some(thinking) = choice.delta.reasoning_content.filter(|thinking| !thinking.is_empty()) {
+            chunks.push(Ok(StreamChunk::Thinking(thinking)));
+        }
+        if let Some(content) = choice.delta.content.filter(|content| !content.is_empty()) {
+            chunks.push(Ok(StreamChunk::Text(content)));
+        }
+        for part in choice.delta.tool_calls.into_iter().flatten() {
+            self.calls.push(part);
+        }
+        if choice.finish_reason.is_some() {
+            chunks.extend(self.calls.finish().into_iter().map(|call| Ok(StreamChunk::ToolCall(call))));
+        }
+        chunks
+    }
+
+    /// The chunks left at the end of the stream: tool calls whose choice
+    /// never finished, then the usage.
+    fn finish(&mut self) -> Vec<Result<StreamChunk, ChatError>> {
+        let mut chunks: Vec<_> = self.calls.finish().into_iter().map(|call| Ok(StreamChunk::ToolCall(call))).collect();
+        if let Some(usage) = self.usage.take() {
+            chunks.push(Ok(StreamChunk::Usage(usage)));
+        }
+        chunks
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_kimi_provider_new() {
+    #[tokio::test]
+    async fn test_kimi_provider_new() {
         let provider = KimiProvider::new("test-key", "kimi-k2-0711-preview", None::<&str>);
         assert!(provider.is_ok());
 
         let provider = provider.unwrap();
         assert_eq!(provider.model, "kimi-k2-0711-preview");
-        assert_eq!(provider.api_key, "test-key");
+        assert_eq!(provider.token.token().await.unwrap(), "test-key");
     }
 
     #[test]
@@ -550,6 +640,25 @@ mod tests {
         assert_eq!(provider.base_url, "https://custom.api.com/v1");
     }
 
+    #[test]
+    fn test_custom_headers_and_proxy() {
+        let mut custom = HeaderMap::new();
+        custom.insert("X-Gateway-Team", HeaderValue::from_static("platform"));
+        custom.insert("X-Msh-Platform", HeaderValue::from_static("gateway"));
+        let provider = KimiProvider::new("test-key", "kimi-k2", None)
+            .unwrap()
+            .with_headers(custom)
+            .with_proxy("http://proxy.internal:8080")
+            .unwrap();
+        let headers = provider.build_headers("test-key").unwrap();
+        assert_eq!(headers["x-gateway-team"], "platform");
+        assert_eq!(headers["x-msh-platform"], "gateway");
+        assert_eq!(headers[AUTHORIZATION], "Bearer test-key");
+
+        let err = KimiProvider::new("test-key", "kimi-k2", None).unwrap().with_proxy("not a url").unwrap_err();
+        assert!(err.to_string().contains("Invalid proxy URL 'not a url'"), "{}", err);
+    }
+
     #[test]
     fn test_infer_capabilities_k2() {
         let caps = KimiProvider::infer_capabilities("kimi-k2-0711-preview");
@@ -574,7 +683,24 @@ mod tests {
         let msg = Message::user("Hello");
         let kimi_msg = KimiProvider::convert_message(&msg);
         assert_eq!(kimi_msg.role, "user");
-        assert_eq!(kimi_msg.content, Some("Hello".to_string()));
+        assert_eq!(kimi_msg.content, Some(MessageContent::Text("Hello".to_string())));
+
+        let msg = Message::user_with_parts(vec![
+            ContentPart::text("What is this?"),
+            ContentPart::think("Hmm"),
+            ContentPart::image_url("data:image/png;base64,AAAA"),
+        ]);
+        let json = serde_json::to_value(KimiProvider::convert_message(&msg)).unwrap();
+        assert_eq!(
+            json["content"],
+            serde_json::json!([
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+            ])
+        );
+
+        let msg = Message::user_with_parts(vec![ContentPart::text("a"), ContentPart::text("b")]);
+        assert_eq!(KimiProvider::convert_message(&msg).content, Some(MessageContent::Text("ab".to_string())));
     }
 
     #[test]
@@ -587,6 +713,67 @@ mod tests {
         assert_eq!(body.messages.len(), 2); // system + user
         assert_eq!(body.messages[0].role, "system");
         assert_eq!(body.messages[1].role, "user");
+        assert!(body.stream_options.is_some_and(|options| options.include_usage));
+    }
+
+    #[test]
+    fn test_stream_tool_calls() {
+        let mut state = StreamState::default();
+        let events = [
+            r#"{"choices":[{"index":0,"delta":{"role":"assistant","reasoning_content":"The user wants a.txt"}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"content":"Reading."}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"id":"read_file:0","type":"function","function":{"name":"read_file","arguments":""}}]}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"path\":"}}]}}]}"#,
+            r#"{"choices":[{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"a.txt\"}"}}]}}]}"#,
+        ];
+        let mut chunks: Vec<StreamChunk> = events
+            .iter()
+            .flat_map(|data| state.parse_data(data))
+            .map(Result::unwrap)
+            .collect();
+        // Nothing but the text until the choice finishes
+        assert_eq!(
+            chunks,
+            [
+                StreamChunk::Thinking("The user wants a.txt".to_string()),
+                StreamChunk::Text("Reading.".to_string()),
+            ]
+        );
+
+        let last = r#"{"choices":[{"index":0,"delta":{},"finish_reason":"tool_calls","usage":{"prompt_tokens":50,"completion_tokens":20,"total_tokens":70}}]}"#;
+        chunks.extend(state.parse_data(last).into_iter().map(Result::unwrap));
+        chunks.extend(state.finish().into_iter().map(Result::unwrap));
+        assert_eq!(
+            chunks[2..],
+            [
+                StreamChunk::ToolCall(ToolCall::new("read_file:0", "read_file", r#"{"path":"a.txt"}"#)),
+                StreamChunk::Usage(TokenUsage::new(50, 20)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completion() {
+        let response: KimiResponse = serde_json::from_value(serde_json::json!({
+            "choices": [{
+                "message": {
+                    "role": "assistant",
+                    "content": "",
+                    "reasoning_content": "Read it first.",
+                    "tool_calls": [{"id": "call_1", "type": "function", "function": {"name": "read_file", "arguments": "{}"}}],
+                },
+                "finish_reason": "tool_calls",
+            }],
+            "usage": {"prompt_tokens": 120, "completion_tokens": 15, "total_tokens": 135},
+        }))
+        .unwrap();
+        let completion = KimiProvider::completion(response);
+
+        assert_eq!(completion.text(), "");
+        assert_eq!(completion.thinking(), Some("Read it first."));
+        assert_eq!(completion.tool_calls(), [ToolCall::new("call_1", "read_file", "{}")]);
+        assert_eq!(completion.finish_reason, Some(FinishReason::ToolCalls));
+        assert_eq!(completion.usage, Some(TokenUsage::new(120, 15)));
     }
 
     #[test]
@@ -3,9 +3,10 @@
 //! This module defines the core [`ChatProvider`] trait and related types
 //! for implementing LLM provider clients.
 
-use crate::message::{Message, ToolCall, ToolCallPart};
+use crate::message::{ContentPart, Message, MessageContent, Role, ToolCall, ToolCallPart};
 use async_trait::async_trait;
-use futures::Stream;
+use futures::future::BoxFuture;
+use futures::{Stream, StreamExt};
 use std::pin::Pin;
 use thiserror::Error;
 
@@ -75,25 +76,181 @@ pub enum ChatError {
     #[error("Invalid configuration: {0}")]
     Config(String),
 
+    /// The generation was cancelled through its [`CancellationToken`].
+    #[error("Generation cancelled")]
+    Cancelled,
+
     /// A generic error with a message.
     #[error("{0}")]
     Other(String),
 }
 
+impl ChatError {
+    /// Whether the request may succeed if sent again, possibly to another
+    /// provider: network failures, timeouts, rate limits, server errors and
+    /// streams that were cut off.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ChatError::Request(e) => match e.status() {
+                Some(status) => status.is_server_error() || status.as_u16() == 429,
+                None => !e.is_builder(),
+            },
+            ChatError::Api { status, .. } => matches!(status, 408 | 429) || *status >= 500,
+            ChatError::StreamEnded => true,
+            _ => false,
+        }
+    }
+}
+
 /// A chunk in the generated stream.
 #[derive(Debug, Clone, PartialEq)]
 pub enum StreamChunk {
     /// Text content
     Text(String),
+    /// The model's reasoning before its answer, from models that show it
+    Thinking(String),
     /// Complete tool call
     ToolCall(ToolCall),
     /// Partial tool call (for streaming)
     ToolCallPart(ToolCallPart),
+    /// Token counts of the request, sent near the end of the stream by
+    /// providers whose API reports them
+    Usage(TokenUsage),
 }
 
 /// A stream of generated chunks (text or tool calls).
 pub type GenerateStream = Pin<Box<dyn Stream<Item = Result<StreamChunk, ChatError>> + Send>>;
 
+/// Token counts of a single generation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct TokenUsage {
+    /// Tokens in the prompt, including the conversation history.
+    #[serde(default)]
+    pub prompt_tokens: u32,
+    /// Tokens the model generated.
+    #[serde(default)]
+    pub completion_tokens: u32,
+}
+
+impl TokenUsage {
+    /// Creates a new usage record.
+    pub fn new(prompt_tokens: u32, completion_tokens: u32) -> Self {
+        Self { prompt_tokens, completion_tokens }
+    }
+
+    /// Returns the prompt and completion tokens together.
+    pub fn total(&self) -> u32 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+/// Why the model stopped generating.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinishReason {
+    /// The model finished its answer or hit a stop sequence.
+    Stop,
+    /// The response reached the maximum number of tokens.
+    Length,
+    /// The model stopped to have its tool calls run.
+    ToolCalls,
+    /// The response was withheld by a content filter.
+    ContentFilter,
+    /// A reason this crate does not know, as the API named it.
+    Other(String),
+}
+
+impl FinishReason {
+    /// Parses a finish reason as the APIs name it, ignoring case (e.g.
+    /// `stop`, `length`, `tool_calls` or Gemini's `MAX_TOKENS`).
+    pub fn parse(reason: &str) -> Self {
+        match reason.to_ascii_lowercase().as_str() {
+            "stop" | "end_turn" | "stop_sequence" => FinishReason::Stop,
+            "length" | "max_tokens" => FinishReason::Length,
+            "tool_calls" | "function_call" | "tool_use" => FinishReason::ToolCalls,
+            "content_filter" | "safety" => FinishReason::ContentFilter,
+            _ => FinishReason::Other(reason.to_string()),
+        }
+    }
+}
+
+/// A complete response of the model, from [`ChatProvider::generate_complete`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompletionResponse {
+    /// The assistant message, with its text and tool calls.
+    pub message: Message,
+    /// Why generation stopped, if the provider reported it.
+    pub finish_reason: Option<FinishReason>,
+    /// Token counts, if the provider reported them.
+    pub usage: Option<TokenUsage>,
+}
+
+impl CompletionResponse {
+    /// Creates a response from the assistant's text and tool calls.
+    pub fn new(text: String, tool_calls: Vec<ToolCall>) -> Self {
+        Self {
+            message: Message {
+                role: Role::Assistant,
+                content: (!text.is_empty()).then_some(MessageContent::Text(text)),
+                tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+                tool_call_id: None,
+                name: None,
+            },
+            finish_reason: None,
+            usage: None,
+        }
+    }
+
+    /// Adds the model's thinking to the message, as a
+    /// [`ContentPart::Think`] before the text.
+    pub fn with_thinking(mut self, thinking: String) -> Self {
+        if thinking.is_empty() {
+            return self;
+        }
+        let mut parts = vec![ContentPart::think(thinking)];
+        if let Some(text) = self.message.text().filter(|text| !text.is_empty()) {
+            parts.push(ContentPart::text(text));
+        }
+        self.message.content = Some(MessageContent::Parts(parts));
+        self
+    }
+
+    /// Collects a generated stream into a response.
+    ///
+    /// Streams carry no finish reason, so it is left unset; the usage is
+    /// set if the stream reported it.
+    pub async fn collect(mut stream: GenerateStream) -> Result<Self, ChatError> {
+        let mut text = String::new();
+        let mut thinking = String::new();
+        let mut tool_calls = Vec::new();
+        let mut usage = None;
+        while let Some(chunk) = stream.next().await {
+            match chunk? {
+                StreamChunk::Text(part) => text.push_str(&part),
+                StreamChunk::Thinking(part) => thinking.push_str(&part),
+                StreamChunk::ToolCall(call) => tool_calls.push(call),
+                StreamChunk::ToolCallPart(_) => {}
+                StreamChunk::Usage(reported) => usage = Some(reported),
+            }
+        }
+        Ok(Self { usage, ..Self::new(text, tool_calls).with_thinking(thinking) })
+    }
+
+    /// Returns the text of the response, empty if it only has tool calls.
+    pub fn text(&self) -> String {
+        self.message.text().unwrap_or_default()
+    }
+
+    /// Returns the model's thinking, if it showed any.
+    pub fn thinking(&self) -> Option<&str> {
+        self.message.content.as_ref()?.as_parts()?.iter().find_map(ContentPart::as_think)
+    }
+
+    /// Returns the tool calls of the response.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.message.tool_calls().unwrap_or_default()
+    }
+}
+
 /// Capabilities that a model may support.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ModelCapability {
@@ -223,6 +380,43 @@ pub trait ChatProvider: Send + Sync {
         tools: Option<&[ToolDefinition]>,
     ) -> Result<GenerateStream, ChatError>;
 
+    /// Starts a generation like
+    /// [`generate_with_tools`](ChatProvider::generate_with_tools), returning
+    /// a token that cancels it from elsewhere.
+    ///
+    /// Cancelling before the response arrives makes the request fail with
+    /// [`ChatError::Cancelled`]; cancelling while it streams ends the stream
+    /// early. Either way the HTTP connection is closed instead of being read
+    /// to the end of the response.
+    fn generate_cancellable<'a>(
+        &'a self,
+        system_prompt: Option<&'a str>,
+        messages: &'a [Message],
+        tools: Option<&'a [ToolDefinition]>,
+    ) -> (BoxFuture<'a, Result<GenerateStream, ChatError>>, CancellationToken) {
+        CancellationToken::wrap(self.generate_with_tools(system_prompt, messages, tools))
+    }
+
+    /// Generates a complete response, returning it once the model is done.
+    ///
+    /// The default implementation collects the stream of
+    /// [`generate_with_tools`](ChatProvider::generate_with_tools), which
+    /// leaves the finish reason unset; providers whose API reports it make a
+    /// non-streaming request instead.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`ChatError`] if the request fails or the response cannot be parsed.
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        let stream = self.generate_with_tools(system_prompt, messages, tools).await?;
+        CompletionResponse::collect(stream).await
+    }
+
     /// Returns the model name used by this provider.
     fn model_name(&self) -> &str;
 
@@ -232,6 +426,14 @@ pub trait ChatProvider: Send + Sync {
     /// of thinking effort (low, medium, high).
     fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider>;
 
+    /// Returns a new provider instance whose replies follow `format`, or
+    /// `None` if the provider cannot constrain its output.
+    ///
+    /// Used by [`generate_json`](structured); the default returns `None`.
+    fn with_response_format(&self, _format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        None
+    }
+
     /// Returns the capabilities supported by this model.
     fn capabilities(&self) -> &[ModelCapability];
 
@@ -256,6 +458,45 @@ pub trait ChatProvider: Send + Sync {
     }
 }
 
+/// Boxed providers are providers too, so wrappers like
+/// [`CachingProvider`] can take the providers built at runtime.
+#[async_trait]
+impl<P: ChatProvider + ?Sized> ChatProvider for Box<P> {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        (**self).generate_with_tools(system_prompt, messages, tools).await
+    }
+
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        (**self).generate_complete(system_prompt, messages, tools).await
+    }
+
+    fn model_name(&self) -> &str {
+        (**self).model_name()
+    }
+
+    fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        (**self).with_thinking(effort)
+    }
+
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        (**self).with_response_format(format)
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        (**self).capabilities()
+    }
+}
+
 /// Configuration options for chat completion requests.
 #[derive(Debug, Clone)]
 pub struct ChatOptions {
@@ -348,6 +589,17 @@ pub enum ResponseFormat {
 }
 
 impl ResponseFormat {
+    /// Returns the format as an OpenAI-style `response_format` object.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            ResponseFormat::JsonSchema { name, schema, strict } => serde_json::json!({
+                "type": self.type_str(),
+                "json_schema": { "name": name, "schema": schema, "strict": strict },
+            }),
+            _ => serde_json::json!({ "type": self.type_str() }),
+        }
+    }
+
     /// Returns the type string for the response format.
     pub fn type_str(&self) -> &'static str {
         match self {
@@ -358,6 +610,26 @@ impl ResponseFormat {
     }
 }
 
+/// An HTTP client sending its requests through the proxy at `url`
+pub(crate) fn proxied_client(url: &str) -> Result<reqwest::Client, ChatError> {
+    let proxy = reqwest::Proxy::all(url)
+        .map_err(|e| ChatError::Config(format!("Invalid proxy URL '{}': {}", url, e)))?;
+    reqwest::Client::builder()
+        .proxy(proxy)
+        .build()
+        .map_err(|e| ChatError::Config(format!("Failed to create HTTP client: {}", e)))
+}
+
+/// Removes the first complete line from `buffer` and decodes it
+///
+/// Streams arrive in chunks that may split a multibyte character, so bytes
+/// are buffered and only decoded a whole line at a time.
+pub(crate) fn take_line(buffer: &mut Vec<u8>) -> Option<String> {
+    let newline_pos = buffer.iter().position(|&b| b == b'\n')?;
+    let line: Vec<u8> = buffer.drain(..=newline_pos).collect();
+    Some(String::from_utf8_lossy(&line).into_owned())
+}
+
 /// Get a unique device ID for this installation.
 /// 
 /// This generates a persistent device ID that is stored in the user's data directory.
@@ -397,9 +669,23 @@ pub fn get_device_id() -> String {
     new_id
 }
 
+pub mod caching;
+pub mod cancel;
+pub mod fallback;
+pub mod gemini;
 pub mod kimi;
+pub mod ollama;
 pub mod openai;
+pub mod structured;
+pub mod token;
+mod tool_calls;
 
 // Re-export provider implementations
+pub use caching::{CachingProvider, ResponseCache};
+pub use cancel::CancellationToken;
+pub use fallback::{Failover, FailoverHandler, FallbackProvider};
+pub use gemini::GeminiProvider;
 pub use kimi::KimiProvider;
+pub use ollama::OllamaProvider;
 pub use openai::OpenAiProvider;
+pub use token::{StaticToken, TokenSource};
@@ -0,0 +1,696 @@
+//! Ollama chat provider implementation.
+//!
+//! This module provides a [`ChatProvider`] implementation for a local
+//! [Ollama](https://ollama.com) server, using its native `/api/chat`
+//! endpoint rather than the OpenAI-compatible one. The native API takes the
+//! context size (`num_ctx`) and `keep_alive` per request, so a model can be
+//! given the context it was configured with instead of Ollama's small
+//! default, and kept loaded between turns.
+//!
+//! Besides chatting, the provider lists the server's models
+//! ([`OllamaProvider::list_models`]) and reads a model's context length and
+//! capabilities ([`OllamaProvider::show_model`]).
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use kosong_rs::{ChatProvider, Message, OllamaProvider, StreamChunk};
+//! use futures::StreamExt;
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let provider = OllamaProvider::new("qwen2.5-coder:7b")?.with_num_ctx(32768);
+//!
+//! for model in provider.list_models().await? {
+//!     println!("{}", model.name);
+//! }
+//!
+//! let messages = vec![Message::user("Hello!")];
+//! let mut stream = provider.generate(None, &messages).await?;
+//! while let Some(chunk) = stream.next().await {
+//!     if let StreamChunk::Text(text) = chunk? {
+//!         print!("{}", text);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use super::token::{send_authorized, StaticToken, TokenSource};
+use super::{
+    ChatError, ChatOptions, ChatProvider, GenerateStream, ModelCapability, ResponseFormat, StreamChunk, ThinkingEffort,
+    TokenUsage,
+};
+use crate::message::{ContentPart, Message, Role, ToolCall};
+use async_trait::async_trait;
+use futures::StreamExt;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+
+/// The default address of a local Ollama server.
+pub const OLLAMA_API_BASE: &str = "http://localhost:11434";
+
+/// Ollama chat provider.
+#[derive(Debug, Clone)]
+pub struct OllamaProvider {
+    client: reqwest::Client,
+    token: Arc<dyn TokenSource>,
+    model: String,
+    base_url: String,
+    options: ChatOptions,
+    thinking_effort: Option<ThinkingEffort>,
+    capabilities: Vec<ModelCapability>,
+    num_ctx: Option<u32>,
+    keep_alive: Option<String>,
+}
+
+/// A model installed on an Ollama server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OllamaModel {
+    /// The model's name with its tag, e.g. `llama3.2:latest`.
+    pub name: String,
+    /// Size of the model's files in bytes.
+    pub size: u64,
+    /// Model family, e.g. `llama`.
+    pub family: Option<String>,
+    /// Parameter count as Ollama reports it, e.g. `3.2B`.
+    pub parameter_size: Option<String>,
+    /// Quantization, e.g. `Q4_K_M`.
+    pub quantization_level: Option<String>,
+}
+
+/// Details of a model from `/api/show`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OllamaModelInfo {
+    /// The longest context the model was trained for, in tokens.
+    pub context_length: Option<u64>,
+    /// The `num_ctx` the model's Modelfile sets, if any.
+    pub num_ctx: Option<u64>,
+    /// What the model supports, e.g. `completion`, `tools`, `vision` and
+    /// `thinking`; empty on servers too old to report it.
+    pub capabilities: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<TagsModel>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagsModel {
+    name: String,
+    #[serde(default)]
+    size: u64,
+    #[serde(default)]
+    details: TagsDetails,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagsDetails {
+    family: Option<String>,
+    parameter_size: Option<String>,
+    quantization_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ShowResponse {
+    #[serde(default)]
+    parameters: String,
+    #[serde(default)]
+    model_info: HashMap<String, Value>,
+    #[serde(default)]
+    capabilities: Vec<String>,
+}
+
+/// One line of a `/api/chat` response.
+#[derive(Debug, Deserialize)]
+struct ChatResponse {
+    message: Option<ChatMessage>,
+    error: Option<String>,
+    #[serde(default)]
+    done: bool,
+    /// Tokens in the prompt, on the last line
+    prompt_eval_count: Option<u32>,
+    /// Tokens generated, on the last line
+    eval_count: Option<u32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatMessage {
+    #[serde(default)]
+    content: String,
+    /// The model's thinking, when `think` is set
+    #[serde(default)]
+    thinking: String,
+    #[serde(default)]
+    tool_calls: Vec<ChatToolCall>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatToolCall {
+    function: ChatFunction,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatFunction {
+    name: String,
+    #[serde(default)]
+    arguments: Value,
+}
+
+impl OllamaProvider {
+    /// Creates a provider for `model` on the local server.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new<S: Into<String>>(model: S) -> Result<Self, ChatError> {
+        Self::with_options(model, ChatOptions::default())
+    }
+
+    /// Creates a provider for `model` on the local server with custom options.
+    pub fn with_options<S: Into<String>>(model: S, options: ChatOptions) -> Result<Self, ChatError> {
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| ChatError::Config(format!("Failed to create HTTP client: {}", e)))?;
+
+        let model_str = model.into();
+        let capabilities = Self::infer_capabilities(&model_str);
+
+        Ok(Self {
+            client,
+            token: Arc::new(StaticToken::new("")),
+            model: model_str,
+            base_url: OLLAMA_API_BASE.to_string(),
+            options,
+            thinking_effort: None,
+            capabilities,
+            num_ctx: None,
+            keep_alive: None,
+        })
+    }
+
+    /// Creates a provider for `model` on the server at `base_url`.
+    ///
+    /// A trailing `/v1`, the address of Ollama's OpenAI-compatible API, is
+    /// dropped, so either address of the server can be given.
+    pub fn with_base_url<S: Into<String>>(model: S, base_url: S) -> Result<Self, ChatError> {
+        let mut provider = Self::new(model)?;
+        let base_url = base_url.into();
+        let base_url = base_url.trim_end_matches('/');
+        provider.base_url = base_url.strip_suffix("/v1").unwrap_or(base_url).to_string();
+        Ok(provider)
+    }
+
+    /// Uses `source` for a bearer token sent with each request, for servers
+    /// behind an authenticating proxy; an empty token sends none.
+    pub fn with_token_source(mut self, source: Arc<dyn TokenSource>) -> Self {
+        self.token = source;
+        self
+    }
+
+    /// Sets the context size the model is loaded with, in tokens.
+    ///
+    /// Without it Ollama uses the Modelfile's `num_ctx` or its own small
+    /// default, and silently drops the start of longer conversations.
+    pub fn with_num_ctx(mut self, num_ctx: u32) -> Self {
+        self.num_ctx = Some(num_ctx);
+        self
+    }
+
+    /// Sets how long the model stays loaded after a request, as an Ollama
+    /// duration such as `10m`, or `-1` to keep it loaded.
+    pub fn with_keep_alive(mut self, keep_alive: impl Into<String>) -> Self {
+        self.keep_alive = Some(keep_alive.into());
+        self
+    }
+
+    /// Returns the server's base URL.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Sets the chat options.
+    pub fn set_options(&mut self, options: ChatOptions) {
+        self.options = options;
+    }
+
+    /// Infers model capabilities based on the model name.
+    ///
+    /// Tool calling is assumed, as the agent depends on it; Ollama answers
+    /// with an error for models without it. [`OllamaProvider::show_model`]
+    /// reports what a model actually supports.
+    pub fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
+        let model = model.to_lowercase();
+        let mut caps = vec![
+            ModelCapability::Streaming,
+            ModelCapability::ToolCalling,
+            ModelCapability::JsonMode,
+        ];
+
+        if ["llava", "vision", "gemma3", "qwen2.5vl", "minicpm-v", "moondream"]
+            .iter()
+            .any(|name| model.contains(name))
+        {
+            caps.push(ModelCapability::Vision);
+        }
+
+        if ["deepseek-r1", "qwq", "qwen3", "gpt-oss", "magistral"]
+            .iter()
+            .any(|name| model.contains(name))
+        {
+            caps.push(ModelCapability::Thinking);
+        }
+
+        caps
+    }
+
+    /// Lists the models installed on the server.
+    pub async fn list_models(&self) -> Result<Vec<OllamaModel>, ChatError> {
+        let url = format!("{}/api/tags", self.base_url);
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.get(&url).headers(self.build_headers(token)?))
+        })
+        .await?;
+        let tags: TagsResponse = check_status(response).await?.json().await?;
+        Ok(tags
+            .models
+            .into_iter()
+            .map(|model| OllamaModel {
+                name: model.name,
+                size: model.size,
+                family: model.details.family,
+                parameter_size: model.details.parameter_size,
+                quantization_level: model.details.quantization_level,
+            })
+            .collect())
+    }
+
+    /// Reads the context length and capabilities of the model `name`.
+    pub async fn show_model(&self, name: &str) -> Result<OllamaModelInfo, ChatError> {
+        let url = format!("{}/api/show", self.base_url);
+        let body = json!({ "model": name });
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(&body))
+        })
+        .await?;
+        let show: ShowResponse = check_status(response).await?.json().await?;
+        Ok(model_info(show))
+    }
+
+    /// The context window of this provider's model: the configured
+    /// `num_ctx`, or else the model's own context length.
+    pub async fn context_window(&self) -> Result<Option<u64>, ChatError> {
+        if let Some(num_ctx) = self.num_ctx {
+            return Ok(Some(num_ctx.into()));
+        }
+        let info = self.show_model(&self.model).await?;
+        Ok(info.context_length)
+    }
+
+    fn build_headers(&self, token: &str) -> Result<HeaderMap, ChatError> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        if !token.is_empty() {
+            headers.insert(
+                AUTHORIZATION,
+                HeaderValue::from_str(&format!("Bearer {}", token))
+                    .map_err(|e| ChatError::Config(format!("Invalid API key: {}", e)))?,
+            );
+        }
+        Ok(headers)
+    }
+
+    fn build_request_body(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Value {
+        let mut body = json!({
+            "model": self.model,
+            "messages": convert_messages(system_prompt, messages),
+            "stream": self.options.stream,
+        });
+
+        if let Some(tools) = tools.filter(|tools| !tools.is_empty()) {
+            body["tools"] = serde_json::to_value(tools).unwrap_or_default();
+        }
+
+        let mut options = serde_json::Map::new();
+        if let Some(num_ctx) = self.num_ctx {
+            options.insert("num_ctx".to_string(), num_ctx.into());
+        }
+        if let Some(max_tokens) = self.options.max_tokens {
+            options.insert("num_predict".to_string(), max_tokens.into());
+        }
+        if let Some(temperature) = self.options.temperature {
+            options.insert("temperature".to_string(), temperature.into());
+        }
+        if let Some(top_p) = self.options.top_p {
+            options.insert("top_p".to_string(), top_p.into());
+        }
+        if let Some(penalty) = self.options.frequency_penalty {
+            options.insert("frequency_penalty".to_string(), penalty.into());
+        }
+        if let Some(penalty) = self.options.presence_penalty {
+            options.insert("presence_penalty".to_string(), penalty.into());
+        }
+        if let Some(stop) = &self.options.stop {
+            options.insert("stop".to_string(), stop.clone().into());
+        }
+        if !options.is_empty() {
+            body["options"] = Value::Object(options);
+        }
+
+        match &self.options.response_format {
+            Some(ResponseFormat::JsonObject) => body["format"] = "json".into(),
+            Some(ResponseFormat::JsonSchema { schema, .. }) => body["format"] = schema.clone(),
+            Some(ResponseFormat::Text) | None => {}
+        }
+        if let Some(keep_alive) = &self.keep_alive {
+            // Ollama takes durations as strings and plain numbers as seconds
+            body["keep_alive"] = match keep_alive.parse::<i64>() {
+                Ok(seconds) => seconds.into(),
+                Err(_) => keep_alive.clone().into(),
+            };
+        }
+        if self.thinking_effort.is_some() && self.has_capability(ModelCapability::Thinking) {
+            body["think"] = true.into();
+        }
+
+        body
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OllamaProvider {
+    async fn generate_with_tools(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Result<GenerateStream, ChatError> {
+        let url = format!("{}/api/chat", self.base_url);
+        let body = self.build_request_body(system_prompt, messages, tools);
+
+        tracing::debug!("Sending request to {}", url);
+
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(&body))
+        })
+        .await?;
+        let response = check_status(response).await?;
+
+        // Responses are newline-delimited JSON, one object per line; a
+        // non-streaming response is a single line
+        let stream = futures::stream::unfold(
+            (response.bytes_stream(), Vec::new(), VecDeque::new(), 0usize, false),
+            |(mut byte_stream, mut buffer, mut pending, mut calls, mut finished)| async move {
+                loop {
+                    if let Some(chunk) = pending.pop_front() {
+                        return Some((chunk, (byte_stream, buffer, pending, calls, finished)));
+                    }
+                    if let Some(line) = super::take_line(&mut buffer) {
+                        pending.extend(parse_line(&line, &mut calls));
+                        continue;
+                    }
+                    if finished {
+                        return None;
+                    }
+                    match byte_stream.next().await {
+                        Some(Ok(bytes)) => buffer.extend_from_slice(&bytes),
+                        Some(Err(e)) => {
+                            return Some((Err(ChatError::Request(e)), (byte_stream, buffer, pending, calls, finished)));
+                        }
+                        None => {
+                            finished = true;
+                            let rest = String::from_utf8_lossy(&std::mem::take(&mut buffer)).into_owned();
+                            pending.extend(parse_line(&rest, &mut calls));
+                        }
+                    }
+                }
+            },
+        );
+
+        Ok(Box::pin(stream))
+    }
+
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    fn with_thinking(&self, effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+        let mut new_provider = self.clone();
+        new_provider.thinking_effort = Some(effort);
+        Box::new(new_provider)
+    }
+
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let mut new_provider = self.clone();
+        new_provider.options.response_format = Some(format);
+        Some(Box::new(new_provider))
+    }
+
+    fn capabilities(&self) -> &[ModelCapability] {
+        &self.capabilities
+    }
+}
+
+/// Turns an unsuccessful response into an error.
+async fn check_status(response: reqwest::Response) -> Result<reqwest::Response, ChatError> {
+    if response.status().is_success() {
+        return Ok(response);
+    }
+    let status = response.status().as_u16();
+    let text = response.text().await.unwrap_or_default();
+    // Ollama wraps its errors as {"error": "..."}
+    let message = serde_json::from_str::<Value>(&text)
+        .ok()
+        .and_then(|value| value["error"].as_str().map(str::to_string))
+        .unwrap_or(text);
+    Err(ChatError::Api { status, message })
+}
+
+/// The context length and capabilities out of a `/api/show` response.
+fn model_info(show: ShowResponse) -> OllamaModelInfo {
+    // The key is prefixed with the architecture, e.g. `llama.context_length`
+    let context_length = show
+        .model_info
+        .iter()
+        .find(|(key, _)| key.ends_with(".context_length"))
+        .and_then(|(_, value)| value.as_u64());
+    let num_ctx = show.parameters.lines().find_map(|line| {
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (Some("num_ctx"), Some(value)) => value.parse().ok(),
+            _ => None,
+        }
+    });
+    OllamaModelInfo {
+        context_length,
+        num_ctx,
+        capabilities: show.capabilities,
+    }
+}
+
+/// Converts messages to `/api/chat` messages.
+fn convert_messages(system_prompt: Option<&str>, messages: &[Message]) -> Vec<Value> {
+    let mut out = Vec::new();
+    if let Some(prompt) = system_prompt {
+        out.push(json!({ "role": "system", "content": prompt }));
+    }
+    // Tool results name their call by ID; Ollama wants the function's name
+    let mut call_names: HashMap<&str, &str> = HashMap::new();
+
+    for msg in messages {
+        let mut text = String::new();
+        let mut images = Vec::new();
+        if let Some(content) = &msg.content {
+            match content.as_parts() {
+                Some(parts) => {
+                    for part in parts {
+                        match part {
+                            ContentPart::Text { text: part } => text.push_str(part),
+                            ContentPart::ImageUrl { image_url } => match base64_data(&image_url.url) {
+                                Some(data) => images.push(data.to_string()),
+                                None => tracing::warn!("Ollama only takes inline images; skipping {}", image_url.url),
+                            },
+                            ContentPart::Think { .. } | ContentPart::AudioUrl { .. } | ContentPart::VideoUrl { .. } => {}
+                        }
+                    }
+                }
+                None => text = content.to_text(),
+            }
+        }
+
+        let mut message = json!({ "role": msg.role.as_str(), "content": text });
+        if !images.is_empty() {
+            message["images"] = images.into();
+        }
+        if let Some(calls) = msg.tool_calls.as_ref().filter(|calls| !calls.is_empty()) {
+            let calls: Vec<Value> = calls
+                .iter()
+                .map(|call| {
+                    call_names.insert(&call.id, &call.function.name);
+                    let arguments = serde_json::from_str::<Value>(&call.function.arguments)
+                        .ok()
+                        .filter(Value::is_object)
+                        .unwrap_or_else(|| json!({}));
+                    json!({ "function": { "name": call.function.name, "arguments": arguments } })
+                })
+                .collect();
+            message["tool_calls"] = calls.into();
+        }
+        if msg.role == Role::Tool {
+            let name = msg
+                .tool_call_id
+                .as_deref()
+                .and_then(|id| call_names.get(id).copied())
+                .or(msg.name.as_deref());
+            if let Some(name) = name {
+                message["tool_name"] = name.into();
+            }
+        }
+        out.push(message);
+    }
+    out
+}
+
+/// The base64 data of a `data:` URL.
+fn base64_data(url: &str) -> Option<&str> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    header.ends_with(";base64").then_some(data)
+}
+
+/// Parses one line of a `/api/chat` response into chunks.
+///
+/// `calls` counts the tool calls seen so far in the response, to give each
+/// an ID that is unique within it; Ollama does not assign IDs.
+fn parse_line(line: &str, calls: &mut usize) -> Vec<Result<StreamChunk, ChatError>> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Vec::new();
+    }
+    let response: ChatResponse = match serde_json::from_str(line) {
+        Ok(response) => response,
+        Err(e) => return vec![Err(ChatError::Parse(format!("Failed to parse chunk: {} - {}", e, line)))],
+    };
+    if let Some(error) = response.error {
+        return vec![Err(ChatError::Other(error))];
+    }
+
+    let mut chunks = Vec::new();
+    if let Some(message) = response.message {
+        if !message.thinking.is_empty() {
+            chunks.push(Ok(StreamChunk::Thinking(message.thinking)));
+        }
+        if !message.content.is_empty() {
+            chunks.push(Ok(StreamChunk::Text(message.content)));
+        }
+        for call in message.tool_calls {
+            *calls += 1;
+            let arguments = match call.function.arguments {
+                Value::Null => "{}".to_string(),
+                arguments => arguments.to_string(),
+            };
+            let id = format!("call_{}_{}", call.function.name, calls);
+            chunks.push(Ok(StreamChunk::ToolCall(ToolCall::new(id, call.function.name, arguments))));
+        }
+    }
+    if response.done && (response.prompt_eval_count.is_some() || response.eval_count.is_some()) {
+        let usage = TokenUsage::new(response.prompt_eval_count.unwrap_or(0), response.eval_count.unwrap_or(0));
+        chunks.push(Ok(StreamChunk::Usage(usage)));
+    }
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_base_url() {
+        let provider = OllamaProvider::with_base_url("llama3.2", "http://gpu-box:11434/v1/").unwrap();
+        assert_eq!(provider.base_url(), "http://gpu-box:11434");
+        assert!(OllamaProvider::infer_capabilities("qwen3:8b").contains(&ModelCapability::Thinking));
+        assert!(OllamaProvider::infer_capabilities("llava:13b").contains(&ModelCapability::Vision));
+    }
+
+    #[test]
+    fn test_build_request_body() {
+        let provider = OllamaProvider::new("qwen2.5-coder:7b").unwrap().with_num_ctx(32768).with_keep_alive("30m");
+        let messages = vec![
+            Message::user_with_parts(vec![
+                ContentPart::text("Describe this"),
+                ContentPart::image_url("data:image/png;base64,AAAA"),
+            ]),
+            Message::with_tool_calls(vec![ToolCall::new("call_1", "read_file", r#"{"path":"a.txt"}"#)]),
+            Message::tool("call_1", "alpha"),
+        ];
+        let body = provider.build_request_body(Some("Be brief."), &messages, None);
+
+        assert_eq!(body["options"], json!({"num_ctx": 32768}));
+        assert_eq!(body["keep_alive"], "30m");
+        assert_eq!(body["stream"], true);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[0], json!({"role": "system", "content": "Be brief."}));
+        assert_eq!(messages[1], json!({"role": "user", "content": "Describe this", "images": ["AAAA"]}));
+        assert_eq!(
+            messages[2]["tool_calls"],
+            json!([{"function": {"name": "read_file", "arguments": {"path": "a.txt"}}}])
+        );
+        assert_eq!(messages[3], json!({"role": "tool", "content": "alpha", "tool_name": "read_file"}));
+    }
+
+    #[test]
+    fn test_parse_responses() {
+        let mut calls = 0;
+        let text = parse_line(r#"{"message":{"role":"assistant","content":"Hi"},"done":false}"#, &mut calls);
+        assert_eq!(text[0].as_ref().unwrap(), &StreamChunk::Text("Hi".to_string()));
+        let thinking = parse_line(r#"{"message":{"role":"assistant","content":"","thinking":"Hmm"},"done":false}"#, &mut calls);
+        assert_eq!(thinking[0].as_ref().unwrap(), &StreamChunk::Thinking("Hmm".to_string()));
+
+        let line = r#"{"message":{"role":"assistant","content":"","tool_calls":[{"function":{"name":"read_file","arguments":{"path":"a.txt"}}}]},"done":false}"#;
+        let chunks = parse_line(line, &mut calls);
+        assert_eq!(
+            chunks[0].as_ref().unwrap(),
+            &StreamChunk::ToolCall(ToolCall::new("call_read_file_1", "read_file", r#"{"path":"a.txt"}"#))
+        );
+        assert!(parse_line(r#"{"done":true}"#, &mut calls).is_empty());
+        assert_eq!(
+            parse_line(r#"{"done":true,"prompt_eval_count":30,"eval_count":12}"#, &mut calls)[0].as_ref().unwrap(),
+            &StreamChunk::Usage(TokenUsage::new(30, 12))
+        );
+        assert!(parse_line(r#"{"error":"model 'x' not found"}"#, &mut calls)[0].is_err());
+
+        let show: ShowResponse = serde_json::from_value(json!({
+            "parameters": "stop \"<|im_end|>\"\nnum_ctx 8192",
+            "model_info": {"general.architecture": "qwen2", "qwen2.context_length": 32768},
+            "capabilities": ["completion", "tools"],
+        }))
+        .unwrap();
+        assert_eq!(
+            model_info(show),
+            OllamaModelInfo {
+                context_length: Some(32768),
+                num_ctx: Some(8192),
+                capabilities: vec!["completion".to_string(), "tools".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_line_split_inside_a_character() {
+        let line = "{\"message\":{\"role\":\"assistant\",\"content\":\"日本\"},\"done\":false}\n".as_bytes();
+        let split = line.iter().position(|&b| b >= 0x80).unwrap() + 1;
+        let mut buffer = line[..split].to_vec();
+        assert_eq!(crate::chat_provider::take_line(&mut buffer), None);
+        buffer.extend_from_slice(&line[split..]);
+        let line = crate::chat_provider::take_line(&mut buffer).unwrap();
+        assert_eq!(parse_line(&line, &mut 0)[0].as_ref().unwrap(), &StreamChunk::Text("日本".to_string()));
+    }
+}
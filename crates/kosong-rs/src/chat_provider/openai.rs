@@ -19,21 +19,25 @@
 //! while let Some(chunk) = stream.next().await {
 //!     match chunk? {
 //!         StreamChunk::Text(text) => print!("{}", text),
+//!         StreamChunk::Thinking(_) => {}, // Reasoning shown before the answer
 //!         StreamChunk::ToolCall(tool_call) => println!("Tool call: {:?}", tool_call),
 //!         StreamChunk::ToolCallPart(_) => {}, // Parts are accumulated by the provider
+//!         StreamChunk::Usage(usage) => println!("\n[{} tokens]", usage.total()),
 //!     }
 //! }
 //! # Ok(())
 //! # }
 //! ```
 
-use super::{ChatError, ChatProvider, ChatOptions, GenerateStream, StreamChunk, ModelCapability, ThinkingEffort};
-use crate::message::{Message, ToolCall};
+use super::{ChatError, ChatProvider, ChatOptions, CompletionResponse, FinishReason, GenerateStream, StreamChunk, ModelCapability, ResponseFormat, ThinkingEffort, TokenUsage};
+use super::token::{send_authorized, StaticToken, TokenSource};
+use crate::message::{ContentPart, Message, MessageContent, ToolCall};
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
-use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE};
 use serde::{Deserialize, Serialize};
 use crate::tooling::Tool;
+use std::sync::Arc;
 
 /// The base URL for the OpenAI API.
 pub const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
@@ -42,9 +46,13 @@ pub const OPENAI_API_BASE: &str = "https://api.openai.com/v1";
 #[derive(Debug, Clone)]
 pub struct OpenAiProvider {
     client: reqwest::Client,
-    api_key: String,
+    token: Arc<dyn TokenSource>,
     model: String,
     base_url: String,
+    /// Headers sent with every request, over the provider's own
+    custom_headers: HeaderMap,
+    /// The Azure OpenAI API version, for Azure deployments
+    azure_api_version: Option<String>,
     options: ChatOptions,
     thinking_effort: ThinkingEffort,
     capabilities: Vec<ModelCapability>,
@@ -108,9 +116,11 @@ impl OpenAiProvider {
 
         Ok(Self {
             client,
-            api_key: api_key.into(),
+            token: Arc::new(StaticToken::new(api_key)),
             model: model_str,
             base_url: OPENAI_API_BASE.to_string(),
+            custom_headers: HeaderMap::new(),
+            azure_api_version: None,
             options,
             thinking_effort: ThinkingEffort::default(),
             capabilities,
@@ -148,7 +158,7 @@ impl OpenAiProvider {
     }
 
     /// Infers model capabilities based on the model name.
-    fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
+    pub fn infer_capabilities(model: &str) -> Vec<ModelCapability> {
         let mut caps = vec![
             ModelCapability::Streaming,
             ModelCapability::ToolCalling,
@@ -179,16 +189,62 @@ impl OpenAiProvider {
         caps
     }
 
+    /// Talks to the Azure OpenAI deployment `deployment` of the resource
+    /// at `endpoint`, e.g. `https://my-resource.openai.azure.com`.
+    ///
+    /// Requests go to the deployment's URL with `api_version` as the
+    /// `api-version` query parameter, and the key is sent in the `api-key`
+    /// header, as Azure expects.
+    pub fn with_azure_deployment(mut self, endpoint: &str, deployment: &str, api_version: &str) -> Self {
+        self.base_url = format!("{}/openai/deployments/{}", endpoint.trim_end_matches('/'), deployment);
+        self.azure_api_version = Some(api_version.to_string());
+        self
+    }
+
     /// Returns the API base URL.
     pub fn base_url(&self) -> &str {
         &self.base_url
     }
 
+    /// The URL of the chat completions endpoint.
+    fn completions_url(&self) -> String {
+        match &self.azure_api_version {
+            Some(version) => format!("{}/chat/completions?api-version={}", self.base_url, version),
+            None => format!("{}/chat/completions", self.base_url),
+        }
+    }
+
     /// Sets the chat options.
     pub fn set_options(&mut self, options: ChatOptions) {
         self.options = options;
     }
 
+    /// Uses `source` for the bearer token of each request instead of the
+    /// API key given at construction.
+    pub fn with_token_source(mut self, source: Arc<dyn TokenSource>) -> Self {
+        self.token = source;
+        self
+    }
+
+    /// Sends `headers` with every request, replacing the provider's own
+    /// headers of the same names.
+    pub fn with_headers(mut self, headers: HeaderMap) -> Self {
+        self.custom_headers.extend(headers);
+        self
+    }
+
+    /// Sends requests through the HTTP(S) proxy at `url`, e.g.
+    /// `http://proxy.internal:8080`, instead of the one the `HTTPS_PROXY`
+    /// environment variable names, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` is not a valid proxy URL.
+    pub fn with_proxy(mut self, url: &str) -> Result<Self, ChatError> {
+        self.client = super::proxied_client(url)?;
+        Ok(self)
+    }
+
     /// Adds tools to the provider for function calling.
     ///
     /// # Example
@@ -257,20 +313,45 @@ impl OpenAiProvider {
         self.tools = Some(tool_defs);
     }
 
-    fn build_headers(&self) -> Result<HeaderMap, ChatError> {
+    fn build_headers(&self, token: &str) -> Result<HeaderMap, ChatError> {
         let mut headers = HeaderMap::new();
+        let (name, value) = match self.azure_api_version {
+            Some(_) => (HeaderName::from_static("api-key"), token.to_string()),
+            None => (AUTHORIZATION, format!("Bearer {}", token)),
+        };
         headers.insert(
-            AUTHORIZATION,
-            HeaderValue::from_str(&format!("Bearer {}", self.api_key))
+            name,
+            HeaderValue::from_str(&value)
                 .map_err(|e| ChatError::Config(format!("Invalid API key: {}", e)))?,
         );
         headers.insert(
             CONTENT_TYPE,
             HeaderValue::from_static("application/json"),
         );
+        headers.extend(self.custom_headers.clone());
         Ok(headers)
     }
 
+    /// Converts a message to the Chat Completions format.
+    ///
+    /// Messages with images keep their text and image parts; everything else
+    /// is flattened to text. Thinking, audio and video parts are left out, as
+    /// the API does not accept them.
+    fn convert_message(msg: &Message) -> serde_json::Value {
+        let mut msg = msg.clone();
+        msg.content = msg.content.map(|content| match content.as_parts() {
+            Some(parts) if parts.iter().any(|p| matches!(p, ContentPart::ImageUrl { .. })) => MessageContent::Parts(
+                parts
+                    .iter()
+                    .filter(|p| matches!(p, ContentPart::Text { .. } | ContentPart::ImageUrl { .. }))
+                    .cloned()
+                    .collect(),
+            ),
+            _ => MessageContent::Text(content.to_text()),
+        });
+        serde_json::to_value(msg).unwrap()
+    }
+
     fn build_request_body(
         &self,
         system_prompt: Option<&str>,
@@ -289,7 +370,7 @@ impl OpenAiProvider {
 
         // Add conversation messages
         for msg in messages {
-            msgs.push(serde_json::to_value(msg).unwrap());
+            msgs.push(Self::convert_message(msg));
         }
 
         let mut body = serde_json::json!({
@@ -298,6 +379,11 @@ impl OpenAiProvider {
             "stream": self.options.stream,
         });
 
+        // Streams only report usage when asked to
+        if self.options.stream {
+            body["stream_options"] = serde_json::json!({ "include_usage": true });
+        }
+
         // Add optional parameters
         if let Some(max_tokens) = self.options.max_tokens {
             body["max_tokens"] = max_tokens.into();
@@ -324,9 +410,7 @@ impl OpenAiProvider {
         }
 
         if let Some(format) = &self.options.response_format {
-            body["response_format"] = serde_json::json!({
-                "type": format.type_str()
-            });
+            body["response_format"] = format.to_json();
         }
 
         // Add tools if provided or configured
@@ -348,17 +432,13 @@ impl ChatProvider for OpenAiProvider {
         messages: &[Message],
         tools: Option<&[super::ToolDefinition]>,
     ) -> Result<GenerateStream, ChatError> {
-        let url = format!("{}/chat/completions", self.base_url);
-        let headers = self.build_headers()?;
+        let url = self.completions_url();
         let body = self.build_request_body(system_prompt, messages, tools);
 
-        let response = self
-            .client
-            .post(&url)
-            .headers(headers)
-            .json(&body)
-            .send()
-            .await?;
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(&body))
+        })
+        .await?;
 
         if !response.status().is_success() {
             let status = response.status().as_u16();
@@ -375,6 +455,37 @@ impl ChatProvider for OpenAiProvider {
         Ok(Box::pin(stream))
     }
 
+    async fn generate_complete(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        tools: Option<&[super::ToolDefinition]>,
+    ) -> Result<CompletionResponse, ChatError> {
+        let url = self.completions_url();
+        let mut body = self.build_request_body(system_prompt, messages, tools);
+        body["stream"] = false.into();
+        if let Some(body) = body.as_object_mut() {
+            body.remove("stream_options");
+        }
+
+        let response = send_authorized(self.token.as_ref(), |token| {
+            Ok(self.client.post(&url).headers(self.build_headers(token)?).json(&body))
+        })
+        .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let error_text = response.text().await.unwrap_or_default();
+            return Err(ChatError::Api {
+                status,
+                message: error_text,
+            });
+        }
+
+        let completion: ChatCompletionResponse = response.json().await?;
+        Ok(completion.into_completion())
+    }
+
     fn model_name(&self) -> &str {
         &self.model
     }
@@ -385,6 +496,12 @@ impl ChatProvider for OpenAiProvider {
         Box::new(new_provider)
     }
 
+    fn with_response_format(&self, format: ResponseFormat) -> Option<Box<dyn ChatProvider>> {
+        let mut new_provider = self.clone();
+        new_provider.options.response_format = Some(format);
+        Some(Box::new(new_provider))
+    }
+
     fn capabilities(&self) -> &[ModelCapability] {
         &self.capabilities
     }
@@ -433,8 +550,7 @@ fn parse_sse_chunks(text: &str) -> Vec<Result<StreamChunk, ChatError>> {
 
             // Parse the JSON chunk
             match parse_chunk_json(data) {
-                Ok(Some(chunk)) => results.push(Ok(chunk)),
-                Ok(None) => {} // No content in this chunk
+                Ok(chunks) => results.extend(chunks.into_iter().map(Ok)),
                 Err(e) => results.push(Err(e)),
             }
         }
@@ -444,27 +560,34 @@ fn parse_sse_chunks(text: &str) -> Vec<Result<StreamChunk, ChatError>> {
 }
 
 /// Parses a single SSE data chunk.
-fn parse_chunk_json(data: &str) -> Result<Option<super::StreamChunk>, ChatError> {
+fn parse_chunk_json(data: &str) -> Result<Vec<super::StreamChunk>, ChatError> {
     let chunk: OpenAiStreamChunk = serde_json::from_str(data)
         .map_err(|e| ChatError::Parse(format!("Failed to parse chunk: {} - {}", e, data)))?;
+    let mut chunks = Vec::new();
 
     // Extract content from delta
     if let Some(choice) = chunk.choices.first() {
         if let Some(delta) = &choice.delta {
             // Check for tool calls first
-            if let Some(tool_calls) = &delta.tool_calls {
-                if let Some(tool_call) = tool_calls.first() {
-                    return Ok(Some(super::StreamChunk::ToolCall(tool_call.clone())));
-                }
+            // Reasoning models behind OpenAI-compatible servers send their
+            // thinking as `reasoning_content`
+            if let Some(thinking) = delta.reasoning_content.as_ref().filter(|thinking| !thinking.is_empty()) {
+                chunks.push(super::StreamChunk::Thinking(thinking.clone()));
             }
-            // Check for content
-            if let Some(content) = &delta.content {
-                return Ok(Some(super::StreamChunk::Text(content.clone())));
+            if let Some(tool_call) = delta.tool_calls.as_ref().and_then(|calls| calls.first()) {
+                chunks.push(super::StreamChunk::ToolCall(tool_call.clone()));
+            } else if let Some(content) = &delta.content {
+                chunks.push(super::StreamChunk::Text(content.clone()));
             }
         }
     }
 
-    Ok(None)
+    // The last chunk carries the usage, usually without choices
+    if let Some(usage) = chunk.usage {
+        chunks.push(super::StreamChunk::Usage(usage));
+    }
+
+    Ok(chunks)
 }
 
 /// A chunk from the streaming response (internal parsing struct).
@@ -478,6 +601,9 @@ struct OpenAiStreamChunk {
     object: String,
     /// The choices in this chunk.
     choices: Vec<OpenAiStreamChoice>,
+    /// Token usage, on the last chunk when `include_usage` is set.
+    #[serde(default)]
+    usage: Option<TokenUsage>,
 }
 
 /// A choice within a stream chunk.
@@ -502,6 +628,9 @@ struct OpenAiStreamDelta {
     role: Option<String>,
     /// The content text.
     content: Option<String>,
+    /// The model's thinking, from servers that send it.
+    #[serde(default)]
+    reasoning_content: Option<String>,
     /// Tool calls if present.
     #[serde(rename = "tool_calls")]
     #[allow(dead_code)]
@@ -510,7 +639,6 @@ struct OpenAiStreamDelta {
 
 /// Non-streaming response from the OpenAI API.
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 struct ChatCompletionResponse {
     /// The response ID.
     #[allow(dead_code)]
@@ -527,13 +655,11 @@ struct ChatCompletionResponse {
     /// The completion choices.
     choices: Vec<CompletionChoice>,
     /// Token usage information.
-    #[allow(dead_code)]
-    usage: Option<Usage>,
+    usage: Option<TokenUsage>,
 }
 
 /// A completion choice in the non-streaming response.
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 struct CompletionChoice {
     /// The index of this choice.
     #[allow(dead_code)]
@@ -542,43 +668,47 @@ struct CompletionChoice {
     message: ResponseMessage,
     /// The finish reason.
     #[serde(rename = "finish_reason")]
-    #[allow(dead_code)]
     finish_reason: Option<String>,
 }
 
 /// A message in the API response.
 #[derive(Debug, Deserialize)]
-#[allow(dead_code)]
 struct ResponseMessage {
     /// The role of the message.
     #[allow(dead_code)]
     role: String,
     /// The content of the message.
     content: Option<String>,
+    /// The model's thinking, from servers that send it.
+    #[serde(default)]
+    reasoning_content: Option<String>,
     /// Tool calls if present.
     #[serde(rename = "tool_calls")]
-    #[allow(dead_code)]
     tool_calls: Option<Vec<ToolCall>>,
 }
 
-/// Token usage information.
-#[derive(Debug, Deserialize)]
-#[allow(dead_code)]
-struct Usage {
-    /// Tokens in the prompt.
-    #[allow(dead_code)]
-    prompt_tokens: u32,
-    /// Tokens in the completion.
-    #[allow(dead_code)]
-    completion_tokens: u32,
-    /// Total tokens used.
-    #[allow(dead_code)]
-    total_tokens: u32,
+impl ChatCompletionResponse {
+    /// Converts the response, using its first choice.
+    fn into_completion(self) -> CompletionResponse {
+        let Some(choice) = self.choices.into_iter().next() else {
+            return CompletionResponse { usage: self.usage, ..CompletionResponse::new(String::new(), Vec::new()) };
+        };
+        CompletionResponse {
+            finish_reason: choice.finish_reason.as_deref().map(FinishReason::parse),
+            usage: self.usage,
+            ..CompletionResponse::new(
+                choice.message.content.unwrap_or_default(),
+                choice.message.tool_calls.unwrap_or_default(),
+            )
+            .with_thinking(choice.message.reasoning_content.unwrap_or_default())
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::message::Role;
 
     #[test]
     fn test_infer_capabilities_gpt4o() {
@@ -612,12 +742,29 @@ mod tests {
 
 data: {"id":"chat-123","object":"chat.completion.chunk","choices":[{"index":0,"delta":{"content":" world"},"finish_reason":null}]}
 
+data: {"id":"chat-123","object":"chat.completion.chunk","choices":[],"usage":{"prompt_tokens":12,"completion_tokens":2,"total_tokens":14}}
+
 data: [DONE]"#;
 
         let results = parse_sse_chunks(sse_data);
-        assert_eq!(results.len(), 2);
+        assert_eq!(results.len(), 3);
         assert_eq!(results[0].as_ref().unwrap(), &super::StreamChunk::Text("Hello".to_string()));
         assert_eq!(results[1].as_ref().unwrap(), &super::StreamChunk::Text(" world".to_string()));
+        assert_eq!(results[2].as_ref().unwrap(), &super::StreamChunk::Usage(TokenUsage::new(12, 2)));
+    }
+
+    #[test]
+    fn test_into_completion() {
+        let response: ChatCompletionResponse = serde_json::from_str(
+            r#"{"id":"chat-123","object":"chat.completion","created":1700000000,"model":"gpt-4o","choices":[{"index":0,"message":{"role":"assistant","content":"Hello"},"finish_reason":"length"}],"usage":{"prompt_tokens":9,"completion_tokens":1,"total_tokens":10}}"#,
+        )
+        .unwrap();
+        let completion = response.into_completion();
+
+        assert_eq!(completion.text(), "Hello");
+        assert!(completion.tool_calls().is_empty());
+        assert_eq!(completion.finish_reason, Some(FinishReason::Length));
+        assert_eq!(completion.usage.map(|usage| usage.total()), Some(10));
     }
 
     #[test]
@@ -629,7 +776,44 @@ data: [DONE]"#;
         
         assert_eq!(body["model"], "gpt-4o");
         assert_eq!(body["stream"], true);
+        assert_eq!(body["stream_options"]["include_usage"], true);
         assert!(body["messages"].as_array().unwrap().len() >= 2);
+
+        let schema = serde_json::json!({"type": "object"});
+        let format = ResponseFormat::JsonSchema { name: "answer".to_string(), schema: schema.clone(), strict: false };
+        let options = ChatOptions::default().with_response_format(format);
+        let provider = OpenAiProvider::with_options("test-key", "gpt-4o", options).unwrap();
+        let body = provider.build_request_body(None, &messages, None);
+        assert_eq!(
+            body["response_format"],
+            serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": "answer", "schema": schema, "strict": false},
+            })
+        );
+    }
+
+    #[test]
+    fn test_build_request_body_with_images() {
+        let provider = OpenAiProvider::new("test-key", "gpt-4o").unwrap();
+        let with_image = Message::user_with_parts(vec![
+            ContentPart::text("What is this?"),
+            ContentPart::image_base64("image/png", "AAAA"),
+            ContentPart::image_url("https://example.com/cat.jpg"),
+        ]);
+        let with_thinking =
+            Message::with_parts(Role::Assistant, vec![ContentPart::think("Hmm"), ContentPart::text("A cat.")]);
+
+        let body = provider.build_request_body(None, &[with_image, with_thinking], None);
+        assert_eq!(
+            body["messages"][0]["content"],
+            serde_json::json!([
+                {"type": "text", "text": "What is this?"},
+                {"type": "image_url", "image_url": {"url": "data:image/png;base64,AAAA"}},
+                {"type": "image_url", "image_url": {"url": "https://example.com/cat.jpg"}},
+            ])
+        );
+        assert_eq!(body["messages"][1]["content"], "A cat.");
     }
 
     #[test]
@@ -643,6 +827,40 @@ data: [DONE]"#;
         assert_eq!(provider.base_url(), "http://localhost:11434/v1");
     }
 
+    #[test]
+    fn test_custom_headers_and_proxy() {
+        let mut custom = HeaderMap::new();
+        custom.insert("api-key", HeaderValue::from_static("azure-key"));
+        let provider = OpenAiProvider::new("test-key", "gpt-4o")
+            .unwrap()
+            .with_headers(custom)
+            .with_proxy("http://proxy.internal:8080")
+            .unwrap();
+        let headers = provider.build_headers("test-key").unwrap();
+        assert_eq!(headers["api-key"], "azure-key");
+        assert_eq!(headers[CONTENT_TYPE], "application/json");
+        assert!(OpenAiProvider::new("test-key", "gpt-4o").unwrap().with_proxy("not a url").is_err());
+    }
+
+    #[test]
+    fn test_azure_deployment() {
+        let provider = OpenAiProvider::new("azure-key", "gpt-4o")
+            .unwrap()
+            .with_azure_deployment("https://my-resource.openai.azure.com/", "gpt4o-prod", "2024-10-21");
+        assert_eq!(provider.base_url(), "https://my-resource.openai.azure.com/openai/deployments/gpt4o-prod");
+        assert_eq!(
+            provider.completions_url(),
+            "https://my-resource.openai.azure.com/openai/deployments/gpt4o-prod/chat/completions?api-version=2024-10-21"
+        );
+        let headers = provider.build_headers("azure-key").unwrap();
+        assert_eq!(headers["api-key"], "azure-key");
+        assert!(!headers.contains_key(AUTHORIZATION));
+
+        let provider = OpenAiProvider::new("test-key", "gpt-4o").unwrap();
+        assert_eq!(provider.completions_url(), "https://api.openai.com/v1/chat/completions");
+        assert_eq!(provider.build_headers("test-key").unwrap()[AUTHORIZATION], "Bearer test-key");
+    }
+
     #[test]
     fn test_thinking_effort() {
         let provider = OpenAiProvider::new("test-key", "gpt-4o").unwrap();
@@ -0,0 +1,325 @@
+//! Structured output: JSON replies checked against a schema.
+//!
+//! `generate_structured`, available on any `dyn ChatProvider`, asks the
+//! model for JSON matching the schema of a type deriving
+//! [`ToolSchema`](macro@crate::tooling::ToolSchema) and deserializes the
+//! reply into it; `generate_json` does the same with a schema given by hand.
+//! The model's output is constrained where the provider
+//! supports it, and checks the reply itself either way: models in plain
+//! JSON mode, and some servers that accept a schema, do not enforce it. A
+//! reply that fails the check is sent back with the error so the model can
+//! correct it.
+//!
+//! Only the common keywords of JSON Schema are checked: `type`, `enum`,
+//! `const`, `properties`, `required`, `additionalProperties`, `items` and
+//! `anyOf`/`oneOf`. Others, like `$ref` or `pattern`, are accepted as is;
+//! deserializing into the target type catches most of what they would.
+
+use super::{ChatError, ChatProvider, ResponseFormat};
+use crate::message::{Message, Role};
+use crate::tooling::ToolSchema;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Replies asked for before giving up on a valid one.
+pub const MAX_ATTEMPTS: usize = 3;
+
+impl dyn ChatProvider + '_ {
+    /// Generates a reply as JSON and deserializes it into `T`, checking it
+    /// against the schema `T` derives.
+    ///
+    /// This is `generate_json` with `T::schema()`, so the schema always
+    /// matches the type.
+    ///
+    /// # Errors
+    ///
+    /// As for `generate_json`.
+    pub async fn generate_structured<T: DeserializeOwned + ToolSchema>(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+    ) -> Result<T, ChatError> {
+        self.generate_json(system_prompt, messages, &T::schema()).await
+    }
+
+    /// Generates a reply as JSON matching `schema` and deserializes it.
+    ///
+    /// The provider's output is constrained to the schema when it supports
+    /// that; the schema is also added to the system prompt for those that
+    /// do not. Replies that are not valid JSON, do not match the schema or
+    /// do not deserialize into `T` are retried, up to [`MAX_ATTEMPTS`]
+    /// replies in all.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ChatError::Parse`] with the last problem if no reply was
+    /// valid, or the error of a failed request.
+    pub async fn generate_json<T: DeserializeOwned>(
+        &self,
+        system_prompt: Option<&str>,
+        messages: &[Message],
+        schema: &Value,
+    ) -> Result<T, ChatError> {
+        let name = schema.get("title").and_then(Value::as_str).unwrap_or("response");
+        let format = ResponseFormat::JsonSchema { name: name.to_string(), schema: schema.clone(), strict: false };
+        let constrained = self.with_response_format(format);
+        let provider = constrained.as_deref().unwrap_or(self);
+
+        let instructions = format!(
+            "Reply with only a JSON value matching this JSON Schema, without any other text:\n{}",
+            serde_json::to_string_pretty(schema)?
+        );
+        let system_prompt = match system_prompt {
+            Some(prompt) => format!("{}\n\n{}", prompt, instructions),
+            None => instructions,
+        };
+
+        let mut messages = messages.to_vec();
+        let mut problem = String::new();
+        for attempt in 1..=MAX_ATTEMPTS {
+            let reply = provider.generate_complete(Some(&system_prompt), &messages, None).await?.text();
+            match parse_reply(&reply, schema) {
+                Ok(value) => return Ok(value),
+                Err(error) => problem = error,
+            }
+            tracing::debug!("Structured reply {} was invalid: {}", attempt, problem);
+            messages.push(Message::new(Role::Assistant, reply));
+            messages.push(Message::user(format!(
+                "That reply is invalid: {}. Reply again with only the corrected JSON.",
+                problem
+            )));
+        }
+        Err(ChatError::Parse(format!("No valid JSON after {} attempts: {}", MAX_ATTEMPTS, problem)))
+    }
+}
+
+/// Parses a reply, checks it against `schema` and deserializes it.
+fn parse_reply<T: DeserializeOwned>(reply: &str, schema: &Value) -> Result<T, String> {
+    let value: Value = serde_json::from_str(strip_fence(reply)).map_err(|e| format!("not valid JSON ({})", e))?;
+    validate(&value, schema)?;
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+/// The reply without a surrounding Markdown code fence.
+fn strip_fence(reply: &str) -> &str {
+    let reply = reply.trim();
+    let Some(body) = reply.strip_prefix("```").and_then(|rest| rest.strip_suffix("```")) else {
+        return reply;
+    };
+    // Skip the language tag on the opening line
+    body.split_once('\n').map_or(body, |(_, code)| code).trim()
+}
+
+/// Checks `value` against `schema`, describing the first mismatch.
+pub fn validate(value: &Value, schema: &Value) -> Result<(), String> {
+    check(value, schema, "$")
+}
+
+fn check(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    let Some(schema) = schema.as_object() else {
+        // `true`, `{}` and unknown forms accept anything; `false` nothing
+        return match schema {
+            Value::Bool(false) => Err(format!("{} is not allowed", path)),
+            _ => Ok(()),
+        };
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !types.is_empty() && !types.iter().any(|name| has_type(value, name)) {
+            return Err(format!("{} should be of type {}", path, types.join(" or ")));
+        }
+    }
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!("{} should be one of {}", path, Value::Array(allowed.clone())));
+        }
+    }
+    if let Some(constant) = schema.get("const") {
+        if value != constant {
+            return Err(format!("{} should be {}", path, constant));
+        }
+    }
+    for keyword in ["anyOf", "oneOf"] {
+        if let Some(options) = schema.get(keyword).and_then(Value::as_array) {
+            if !options.iter().any(|option| check(value, option, path).is_ok()) {
+                return Err(format!("{} matches none of the allowed schemas", path));
+            }
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for name in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+                if let Some(name) = name.as_str() {
+                    if !object.contains_key(name) {
+                        return Err(format!("{} is missing the required property \"{}\"", path, name));
+                    }
+                }
+            }
+            for (name, item) in object {
+                let item_path = format!("{}.{}", path, name);
+                match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => check(item, property, &item_path)?,
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(format!("{} has the unexpected property \"{}\"", path, name));
+                        }
+                        Some(additional) => check(item, additional, &item_path)?,
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (index, item) in items.iter().enumerate() {
+                    check(item, item_schema, &format!("{}[{}]", path, index))?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether `value` is of the JSON Schema type `name`.
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_provider::{GenerateStream, ModelCapability, StreamChunk, ThinkingEffort, ToolDefinition};
+    use async_trait::async_trait;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::Mutex;
+
+    /// Replies with the next scripted text, recording what it was sent.
+    struct Scripted {
+        replies: Mutex<Vec<&'static str>>,
+        requests: Mutex<Vec<Vec<Message>>>,
+    }
+
+    #[async_trait]
+    impl ChatProvider for Scripted {
+        async fn generate_with_tools(
+            &self,
+            _system_prompt: Option<&str>,
+            messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+        ) -> Result<GenerateStream, ChatError> {
+            self.requests.lock().unwrap().push(messages.to_vec());
+            let reply = self.replies.lock().unwrap().remove(0);
+            Ok(Box::pin(futures::stream::iter([Ok(StreamChunk::Text(reply.to_string()))])))
+        }
+
+        fn model_name(&self) -> &str {
+            "scripted"
+        }
+
+        fn with_thinking(&self, _effort: ThinkingEffort) -> Box<dyn ChatProvider> {
+            unimplemented!()
+        }
+
+        fn capabilities(&self) -> &[ModelCapability] {
+            &[]
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Person {
+        name: String,
+        age: u32,
+    }
+
+    fn person_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {"name": {"type": "string"}, "age": {"type": "integer"}},
+            "required": ["name", "age"],
+            "additionalProperties": false,
+        })
+    }
+
+    #[test]
+    fn test_validate() {
+        let schema = person_schema();
+        assert!(validate(&json!({"name": "Ada", "age": 36}), &schema).is_ok());
+        assert_eq!(
+            validate(&json!({"name": "Ada"}), &schema).unwrap_err(),
+            "$ is missing the required property \"age\""
+        );
+        assert_eq!(
+            validate(&json!({"name": "Ada", "age": "36"}), &schema).unwrap_err(),
+            "$.age should be of type integer"
+        );
+        assert!(validate(&json!({"name": "Ada", "age": 36, "x": 1}), &schema).is_err());
+
+        let list = json!({"type": "array", "items": {"enum": ["a", "b"]}});
+        assert!(validate(&json!(["a", "b"]), &list).is_ok());
+        assert_eq!(validate(&json!(["a", "c"]), &list).unwrap_err(), "$[1] should be one of [\"a\",\"b\"]");
+        assert!(validate(&json!(null), &json!({"type": ["string", "null"]})).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_generate_json_retries_invalid_replies() {
+        let provider = Scripted {
+            replies: Mutex::new(vec![
+                "Sure! Here it is",
+                "{\"name\": \"Ada\"}",
+                "```json\n{\"name\": \"Ada\", \"age\": 36}\n```",
+            ]),
+            requests: Mutex::new(Vec::new()),
+        };
+        let messages = [Message::user("Ada Lovelace, 36")];
+
+        let dyn_provider: &dyn ChatProvider = &provider;
+        let person: Person = dyn_provider.generate_json(None, &messages, &person_schema()).await.unwrap();
+        assert_eq!(person, Person { name: "Ada".to_string(), age: 36 });
+
+        let requests = provider.requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        let correction = requests[2].last().unwrap().text().unwrap();
+        assert!(correction.contains("missing the required property \"age\""));
+    }
+
+    #[tokio::test]
+    async fn test_generate_structured_uses_the_type_schema() {
+        #[derive(Debug, Deserialize, ToolSchema, PartialEq)]
+        struct Release {
+            /// The version number
+            version: String,
+            /// Whether it breaks compatibility
+            breaking: bool,
+        }
+
+        let provider = Scripted {
+            replies: Mutex::new(vec!["{\"version\": \"1.2.0\"}", "{\"version\": \"1.2.0\", \"breaking\": false}"]),
+            requests: Mutex::new(Vec::new()),
+        };
+        let dyn_provider: &dyn ChatProvider = &provider;
+        let release: Release = dyn_provider.generate_structured(None, &[Message::user("1.2.0, no breaking changes")]).await.unwrap();
+        assert_eq!(release, Release { version: "1.2.0".to_string(), breaking: false });
+
+        let requests = provider.requests.lock().unwrap();
+        let correction = requests[1].last().unwrap().text().unwrap();
+        assert!(correction.contains("missing the required property \"breaking\""), "{}", correction);
+    }
+}
@@ -0,0 +1,159 @@
+//! Access tokens for provider requests.
+//!
+//! Providers ask their [`TokenSource`] for a token on every request rather
+//! than holding a fixed API key, so short-lived OAuth tokens can be renewed
+//! while a provider is in use. A request the server rejects with 401 is
+//! retried once with a refreshed token.
+
+use crate::chat_provider::ChatError;
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::fmt;
+
+/// Supplies the bearer token sent with each request.
+#[async_trait]
+pub trait TokenSource: Send + Sync + fmt::Debug {
+    /// Returns the token to send with the next request.
+    async fn token(&self) -> Result<String, ChatError>;
+
+    /// Returns a new token after the server rejected `rejected`, or `None`
+    /// if the source cannot refresh.
+    async fn refresh(&self, rejected: &str) -> Result<Option<String>, ChatError> {
+        let _ = rejected;
+        Ok(None)
+    }
+}
+
+/// A fixed API key.
+#[derive(Clone)]
+pub struct StaticToken(String);
+
+impl StaticToken {
+    /// Creates a source that always returns `token`.
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+}
+
+impl fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StaticToken(..)")
+    }
+}
+
+#[async_trait]
+impl TokenSource for StaticToken {
+    async fn token(&self) -> Result<String, ChatError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// Sends the request built by `build` with a token from `source`.
+///
+/// On a 401 response the source is asked to refresh, and the request is
+/// rebuilt and sent once more with the new token.
+pub(crate) async fn send_authorized<F>(source: &dyn TokenSource, build: F) -> Result<Response, ChatError>
+where
+    F: Fn(&str) -> Result<RequestBuilder, ChatError>,
+{
+    let token = source.token().await?;
+    let response = build(&token)?.send().await?;
+    if response.status() != StatusCode::UNAUTHORIZED {
+        return Ok(response);
+    }
+
+    match source.refresh(&token).await? {
+        Some(refreshed) => {
+            tracing::debug!("Request unauthorized, retrying with a refreshed token");
+            Ok(build(&refreshed)?.send().await?)
+        }
+        None => Ok(response),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers 401 unless the request carries `Bearer fresh`, and returns
+    /// the authorization header of each request.
+    fn serve(requests: usize) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut seen = Vec::new();
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let (mut auth, mut length) = (String::new(), 0);
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    match name.to_ascii_lowercase().as_str() {
+                        "authorization" => auth = value.to_string(),
+                        "content-length" => length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+                reader.read_exact(&mut vec![0; length]).unwrap();
+                let status = if auth == "Bearer fresh" { "200 OK" } else { "401 Unauthorized" };
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status).unwrap();
+                seen.push(auth);
+            }
+            seen
+        });
+        (url, handle)
+    }
+
+    #[derive(Debug, Default)]
+    struct Expiring {
+        refreshes: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl TokenSource for Expiring {
+        async fn token(&self) -> Result<String, ChatError> {
+            Ok("stale".to_string())
+        }
+
+        async fn refresh(&self, rejected: &str) -> Result<Option<String>, ChatError> {
+            assert_eq!(rejected, "stale");
+            self.refreshes.fetch_add(1, Ordering::SeqCst);
+            Ok(Some("fresh".to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_authorized_retries_after_refresh() {
+        let (url, server) = serve(2);
+        let client = reqwest::Client::new();
+        let source = Expiring::default();
+
+        let response = send_authorized(&source, |token| Ok(client.post(&url).bearer_auth(token).body("{}")))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(source.refreshes.load(Ordering::SeqCst), 1);
+        assert_eq!(server.join().unwrap(), ["Bearer stale", "Bearer fresh"]);
+    }
+
+    #[tokio::test]
+    async fn test_send_authorized_static_token_is_not_retried() {
+        let (url, server) = serve(1);
+        let client = reqwest::Client::new();
+
+        let response = send_authorized(&StaticToken::new("stale"), |token| Ok(client.get(&url).bearer_auth(token)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(server.join().unwrap(), ["Bearer stale"]);
+    }
+}
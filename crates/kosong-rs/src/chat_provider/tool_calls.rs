@@ -0,0 +1,86 @@
+//! Assembling tool calls from streamed parts.
+//!
+//! OpenAI-style streams send each tool call in pieces: the first delta has
+//! the call's ID and function name, later ones only its index and the next
+//! fragment of the arguments. A call is only complete once the choice
+//! finishes, as the arguments are not valid JSON until then.
+
+use crate::message::{ToolCall, ToolCallPart};
+use std::collections::BTreeMap;
+
+/// Accumulates the tool call parts of one streamed response.
+#[derive(Debug, Default)]
+pub(crate) struct ToolCallAccumulator {
+    parts: BTreeMap<usize, ToolCallPart>,
+}
+
+impl ToolCallAccumulator {
+    /// Adds a part, merging it into the call with the same index.
+    ///
+    /// Parts without an index are matched by ID, and parts with neither
+    /// continue the last call.
+    pub(crate) fn push(&mut self, part: ToolCallPart) {
+        let index = part.index.unwrap_or_else(|| {
+            let by_id = self.parts.iter().find(|(_, existing)| !part.id.is_empty() && existing.id == part.id);
+            match (by_id, self.parts.keys().next_back()) {
+                (Some((index, _)), _) => *index,
+                (None, Some(last)) if part.id.is_empty() => *last,
+                (None, Some(last)) => last + 1,
+                (None, None) => 0,
+            }
+        });
+        match self.parts.get_mut(&index) {
+            Some(existing) => existing.merge(&part),
+            None => {
+                self.parts.insert(index, part);
+            }
+        }
+    }
+
+    /// Takes the accumulated calls in index order.
+    ///
+    /// Calls without a function name are dropped. Calls without an ID get
+    /// one, and calls without arguments get an empty object.
+    pub(crate) fn finish(&mut self) -> Vec<ToolCall> {
+        std::mem::take(&mut self.parts)
+            .into_iter()
+            .filter_map(|(index, part)| {
+                let name = part.function.name.filter(|name| !name.is_empty())?;
+                let id = match part.id.is_empty() {
+                    true => format!("call_{}_{}", name, index),
+                    false => part.id,
+                };
+                let arguments = part.function.arguments.filter(|args| !args.trim().is_empty());
+                Some(ToolCall::new(id, name, arguments.unwrap_or_else(|| "{}".to_string())))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accumulate_parts() {
+        let mut calls = ToolCallAccumulator::default();
+        calls.push(ToolCallPart::new("call_a", "function").with_name("read_file").with_arguments("").with_index(0));
+        calls.push(ToolCallPart::new("", "").with_arguments(r#"{"path":"#).with_index(0));
+        calls.push(ToolCallPart::new("call_b", "function").with_name("glob").with_index(1));
+        calls.push(ToolCallPart::new("", "").with_arguments(r#""a.txt"}"#).with_index(0));
+        // Without an index, a part continues the last call
+        calls.push(ToolCallPart::new("", "").with_arguments(r#"{"pattern":"*"}"#));
+
+        assert_eq!(
+            calls.finish(),
+            [
+                ToolCall::new("call_a", "read_file", r#"{"path":"a.txt"}"#),
+                ToolCall::new("call_b", "glob", r#"{"pattern":"*"}"#),
+            ]
+        );
+        assert!(calls.finish().is_empty());
+
+        calls.push(ToolCallPart::new("", "").with_name("ls"));
+        assert_eq!(calls.finish(), [ToolCall::new("call_ls_0", "ls", "{}")]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Text embedding providers.
+//!
+//! An [`EmbeddingProvider`] turns texts into vectors whose cosine similarity
+//! reflects how related the texts are. [`OpenAiEmbeddings`] implements it for
+//! OpenAI-compatible `/embeddings` endpoints.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use kosong_rs::{EmbeddingProvider, OpenAiEmbeddings};
+//!
+//! # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+//! let provider = OpenAiEmbeddings::new("your-api-key", "text-embedding-3-small")?;
+//! let vectors = provider.embed(&["fn main() {}".to_string()]).await?;
+//! assert_eq!(vectors.len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::chat_provider::openai::OPENAI_API_BASE;
+use crate::chat_provider::ChatError;
+use async_trait::async_trait;
+use serde::Deserialize;
+
+/// A provider of text embeddings.
+#[async_trait]
+pub trait EmbeddingProvider: Send + Sync {
+    /// Returns the name of the embedding model.
+    fn model_name(&self) -> &str;
+
+    /// Embeds `inputs`, returning one vector per input in the same order.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, ChatError>;
+}
+
+/// Embedding provider for OpenAI-compatible APIs.
+#[derive(Debug, Clone)]
+pub struct OpenAiEmbeddings {
+    client: reqwest::Client,
+    api_key: String,
+    model: String,
+    base_url: String,
+}
+
+impl OpenAiEmbeddings {
+    /// Creates a provider for the OpenAI API.
+    pub fn new<S: Into<String>>(api_key: S, model: S) -> Result<Self, ChatError> {
+        Self::with_base_url(api_key.into(), model.into(), OPENAI_API_BASE.to_string())
+    }
+
+    /// Creates a provider for an OpenAI-compatible endpoint.
+    pub fn with_base_url<S: Into<String>>(api_key: S, model: S, base_url: S) -> Result<Self, ChatError> {
+        let client = reqwest::Client::builder()
+            .build()
+            .map_err(|e| ChatError::Config(format!("Failed to create HTTP client: {}", e)))?;
+        Ok(Self {
+            client,
+            api_key: api_key.into(),
+            model: model.into(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmbeddingProvider for OpenAiEmbeddings {
+    fn model_name(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, ChatError> {
+        if inputs.is_empty() {
+            return Ok(Vec::new());
+        }
+        let response = self
+            .client
+            .post(format!("{}/embeddings", self.base_url))
+            .bearer_auth(&self.api_key)
+            .json(&serde_json::json!({ "model": self.model, "input": inputs }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            let message = response.text().await.unwrap_or_default();
+            return Err(ChatError::Api { status, message });
+        }
+
+        let body = response.text().await?;
+        parse_embeddings(&body, inputs.len())
+    }
+}
+
+/// Response from an `/embeddings` endpoint.
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+/// One embedding in the response.
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Parses an `/embeddings` response, ordering vectors by input index.
+fn parse_embeddings(body: &str, expected: usize) -> Result<Vec<Vec<f32>>, ChatError> {
+    let mut response: EmbeddingResponse = serde_json::from_str(body)?;
+    if response.data.len() != expected {
+        return Err(ChatError::Parse(format!(
+            "Expected {} embeddings, got {}",
+            expected,
+            response.data.len()
+        )));
+    }
+    response.data.sort_by_key(|d| d.index);
+    Ok(response.data.into_iter().map(|d| d.embedding).collect())
+}
+
+/// Cosine similarity of two vectors, or 0 if either is zero or they differ in length.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot / norms
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_embeddings() {
+        let body = r#"{"object":"list","data":[
+            {"object":"embedding","index":1,"embedding":[0.0,1.0]},
+            {"object":"embedding","index":0,"embedding":[1.0,0.0]}
+        ],"model":"text-embedding-3-small"}"#;
+        assert_eq!(parse_embeddings(body, 2).unwrap(), vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        assert!(matches!(parse_embeddings(body, 3), Err(ChatError::Parse(_))));
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+}
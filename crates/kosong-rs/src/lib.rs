@@ -3,14 +3,15 @@
 //! An LLM abstraction layer for AI agent applications.
 //!
 //! This crate provides a unified interface for interacting with various LLM providers
-//! including Moonshot AI's Kimi API and OpenAI-compatible endpoints.
+//! including Moonshot AI's Kimi API, Google Gemini, Ollama and OpenAI-compatible endpoints.
 //!
 //! ## Features
 //!
 //! - **Unified ChatProvider trait** - Abstract interface for LLM providers
 //! - **Streaming responses** - Real-time token streaming support
 //! - **Tool calling** - Function calling capabilities for agents
-//! - **Multiple providers** - Kimi and OpenAI-compatible implementations
+//! - **Multiple providers** - Kimi, Gemini, Ollama and OpenAI-compatible implementations
+//! - **Embeddings** - EmbeddingProvider trait for semantic search
 //!
 //! ## Example
 //!
@@ -29,24 +30,34 @@
 //! while let Some(chunk) = stream.next().await {
 //!     match chunk? {
 //!         StreamChunk::Text(text) => print!("{}", text),
+//!         StreamChunk::Thinking(_) => {}, // Reasoning shown before the answer
 //!         StreamChunk::ToolCall(tool_call) => println!("Tool call: {:?}", tool_call),
 //!         StreamChunk::ToolCallPart(_) => {}, // Parts are accumulated by the provider
+//!         StreamChunk::Usage(usage) => println!("\n[{} tokens]", usage.total()),
 //!     }
 //! }
 //! # Ok(())
 //! # }
 //! ```
 
+// Lets the derive macros' `::kosong_rs` paths resolve within this crate
+extern crate self as kosong_rs;
+
 pub mod chat_provider;
+pub mod embedding;
 pub mod message;
 pub mod tooling;
 
 // Re-export main types for convenience
-pub use chat_provider::{ChatProvider, ChatError, GenerateStream, StreamChunk, ModelCapability, ThinkingEffort};
+pub use chat_provider::{CachingProvider, CancellationToken, ChatProvider, ChatError, CompletionResponse, Failover, FailoverHandler, FallbackProvider, FinishReason, GenerateStream, StreamChunk, ModelCapability, ResponseCache, ThinkingEffort, TokenUsage};
+pub use chat_provider::gemini::GeminiProvider;
 pub use chat_provider::kimi::KimiProvider;
+pub use chat_provider::ollama::OllamaProvider;
 pub use chat_provider::openai::OpenAiProvider;
+pub use chat_provider::token::{StaticToken, TokenSource};
+pub use embedding::{EmbeddingProvider, OpenAiEmbeddings};
 pub use message::{ContentPart, Message, Role, ToolCall, ToolCallPart, ToolResult};
-pub use tooling::{Tool, Toolset, ToolError as ToolingError};
+pub use tooling::{Tool, ToolSchema, Toolset, ToolError as ToolingError};
 
 // Re-export async_trait for users implementing custom providers
 pub use async_trait::async_trait;
@@ -71,6 +71,12 @@ impl ContentPart {
         }
     }
 
+    /// Creates an image content part from base64-encoded data, sent inline
+    /// as a `data:` URL.
+    pub fn image_base64(mime_type: &str, data: &str) -> Self {
+        Self::image_url(format!("data:{};base64,{}", mime_type, data))
+    }
+
     /// Creates a new audio URL content part.
     pub fn audio_url<S: Into<String>>(url: S) -> Self {
         ContentPart::AudioUrl {
@@ -383,12 +389,15 @@ pub struct FunctionCallPart {
 /// A partial tool call for streaming.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ToolCallPart {
-    /// The unique identifier for this tool call.
+    /// The unique identifier for this tool call; streams usually send it
+    /// with the first part only.
+    #[serde(default)]
     pub id: String,
     /// The type of the tool call (typically "function").
-    #[serde(rename = "type")]
+    #[serde(rename = "type", default)]
     pub call_type: String,
     /// The partial function call.
+    #[serde(default)]
     pub function: FunctionCallPart,
     /// Index for ordering in streaming responses.
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -426,6 +435,12 @@ impl ToolCallPart {
 
     /// Merges another part into this one, accumulating arguments.
     pub fn merge(&mut self, other: &ToolCallPart) {
+        if self.id.is_empty() {
+            self.id = other.id.clone();
+        }
+        if self.call_type.is_empty() {
+            self.call_type = other.call_type.clone();
+        }
         if let Some(ref name) = other.function.name {
             self.function.name = Some(name.clone());
         }
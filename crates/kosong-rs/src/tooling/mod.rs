@@ -74,6 +74,11 @@ use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod schema;
+
+pub use kosong_derive::ToolSchema;
+pub use schema::ToolSchema;
+
 /// Errors that can occur during tool operations.
 #[derive(Error, Debug, Clone)]
 pub enum ToolError {
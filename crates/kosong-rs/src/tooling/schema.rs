@@ -0,0 +1,254 @@
+//! Typed tool parameters.
+//!
+//! Instead of writing a tool's JSON schema by hand and picking values out
+//! of a `serde_json::Value`, a tool can declare its parameters as a struct
+//! deriving both `Deserialize` and [`ToolSchema`](macro@crate::tooling::ToolSchema):
+//!
+//! ```rust
+//! use kosong_rs::tooling::ToolSchema;
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize, ToolSchema)]
+//! struct Params {
+//!     /// The path to the file to read
+//!     path: String,
+//!     /// The number of lines to read
+//!     limit: Option<u32>,
+//! }
+//!
+//! let schema = Params::schema();
+//! assert_eq!(schema["properties"]["limit"]["type"], "integer");
+//! assert_eq!(schema["required"], serde_json::json!(["path"]));
+//!
+//! let params = Params::from_params(serde_json::json!({"path": "src/lib.rs"})).unwrap();
+//! assert_eq!(params.path, "src/lib.rs");
+//! assert!(params.limit.is_none());
+//! ```
+//!
+//! `parameters_schema` then returns `Params::schema()`, and `execute`
+//! starts with `Params::from_params(params)?`.
+
+use super::ToolError;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::PathBuf;
+
+/// A type with a JSON schema, usable as tool parameters or part of them.
+///
+/// Derive it for structs and enums with
+/// [`#[derive(ToolSchema)]`](macro@crate::tooling::ToolSchema); it is
+/// implemented here for strings, numbers, booleans, paths, `Option`,
+/// sequences, string-keyed maps and `serde_json::Value`.
+pub trait ToolSchema {
+    /// Whether a property of this type may be left out, as for `Option`.
+    const OPTIONAL: bool = false;
+
+    /// Returns the JSON schema of this type.
+    fn schema() -> Value;
+
+    /// Deserializes tool call parameters into this type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ToolError::InvalidParameters`] if `params` do not match.
+    fn from_params(params: Value) -> Result<Self, ToolError>
+    where
+        Self: DeserializeOwned + Sized,
+    {
+        serde_json::from_value(params).map_err(|e| ToolError::InvalidParameters(e.to_string()))
+    }
+}
+
+macro_rules! impl_tool_schema {
+    ($kind:literal: $($ty:ty),*) => {
+        $(
+            impl ToolSchema for $ty {
+                fn schema() -> Value {
+                    json!({"type": $kind})
+                }
+            }
+        )*
+    };
+}
+
+impl_tool_schema!("string": String, char, PathBuf);
+impl_tool_schema!("boolean": bool);
+impl_tool_schema!("integer": i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+impl_tool_schema!("number": f32, f64);
+
+impl ToolSchema for Value {
+    fn schema() -> Value {
+        json!({})
+    }
+}
+
+impl<T: ToolSchema> ToolSchema for Option<T> {
+    const OPTIONAL: bool = true;
+
+    fn schema() -> Value {
+        T::schema()
+    }
+}
+
+impl<T: ToolSchema> ToolSchema for Box<T> {
+    const OPTIONAL: bool = T::OPTIONAL;
+
+    fn schema() -> Value {
+        T::schema()
+    }
+}
+
+macro_rules! impl_array_schema {
+    ($($ty:ident),*) => {
+        $(
+            impl<T: ToolSchema> ToolSchema for $ty<T> {
+                fn schema() -> Value {
+                    json!({"type": "array", "items": T::schema()})
+                }
+            }
+        )*
+    };
+}
+
+impl_array_schema!(Vec, BTreeSet, HashSet);
+
+impl<T: ToolSchema> ToolSchema for HashMap<String, T> {
+    fn schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::schema()})
+    }
+}
+
+impl<T: ToolSchema> ToolSchema for BTreeMap<String, T> {
+    fn schema() -> Value {
+        json!({"type": "object", "additionalProperties": T::schema()})
+    }
+}
+
+/// Used by the derive macro; not part of the API.
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json;
+    use serde_json::{json, Value};
+
+    /// A struct field, as a property of an object schema.
+    pub struct Property {
+        pub name: &'static str,
+        pub schema: Value,
+        pub description: Option<&'static str>,
+        pub required: bool,
+    }
+
+    /// The schema of an object with `properties`.
+    pub fn object(properties: Vec<Property>) -> Value {
+        let mut schemas = serde_json::Map::new();
+        let mut required = Vec::new();
+        for Property { name, mut schema, description, required: is_required } in properties {
+            if let (Some(description), Some(schema)) = (description, schema.as_object_mut()) {
+                schema.insert("description".to_string(), json!(description));
+            }
+            schemas.insert(name.to_string(), schema);
+            if is_required {
+                required.push(name);
+            }
+        }
+        let mut object = json!({"type": "object", "properties": schemas});
+        if !required.is_empty() {
+            object["required"] = json!(required);
+        }
+        object
+    }
+
+    /// The schema of a string that is one of `names`.
+    pub fn string_enum(names: &[&str]) -> Value {
+        json!({"type": "string", "enum": names})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tooling::ToolSchema;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, ToolSchema, PartialEq)]
+    #[serde(rename_all = "kebab-case")]
+    enum Mode {
+        /// Show matching lines
+        Content,
+        FilesWithMatches,
+        #[serde(rename = "count")]
+        CountMatches,
+    }
+
+    #[derive(Debug, Deserialize, ToolSchema)]
+    struct Item {
+        /// What to do.
+        ///
+        /// Keep it short.
+        description: String,
+        #[serde(default)]
+        done: bool,
+    }
+
+    #[derive(Debug, Deserialize, ToolSchema)]
+    #[allow(dead_code)]
+    struct Params {
+        /// The pattern to search for
+        pattern: String,
+        #[serde(rename = "limit")]
+        head_limit: Option<usize>,
+        mode: Mode,
+        items: Vec<Item>,
+        env: HashMap<String, String>,
+        #[serde(skip)]
+        internal: u8,
+    }
+
+    #[test]
+    fn test_derived_schema() {
+        assert_eq!(
+            Params::schema(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {"type": "string", "description": "The pattern to search for"},
+                    "limit": {"type": "integer"},
+                    "mode": {"type": "string", "enum": ["content", "files-with-matches", "count"]},
+                    "items": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "description": {"type": "string", "description": "What to do. Keep it short"},
+                                "done": {"type": "boolean"},
+                            },
+                            "required": ["description"],
+                        },
+                    },
+                    "env": {"type": "object", "additionalProperties": {"type": "string"}},
+                },
+                "required": ["pattern", "mode", "items", "env"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_params() {
+        let params = Params::from_params(json!({
+            "pattern": "fn main",
+            "limit": 5,
+            "mode": "count",
+            "items": [{"description": "Look"}],
+            "env": {},
+        }))
+        .unwrap();
+        assert_eq!(params.head_limit, Some(5));
+        assert_eq!(params.mode, Mode::CountMatches);
+        assert_eq!(params.items[0].description, "Look");
+        assert!(!params.items[0].done);
+
+        let err = Params::from_params(json!({"pattern": 1})).unwrap_err();
+        assert!(matches!(err, ToolError::InvalidParameters(_)));
+    }
+}